@@ -0,0 +1,246 @@
+use crate::catalog::TableInfo;
+use crate::execution::QueryResult;
+use crate::types::{DataType, Value};
+use crate::vacuum::VacuumStats;
+use crate::{Connection, Database, DatabaseError, DatabaseOptions, Rows, Statement, sql};
+use std::io::Read;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+/// runs `f` on tokio's blocking pool, where waiting on page I/O and locks doesn't
+/// hold up the tasks of the runtime
+async fn blocking<T: Send + 'static>(f: impl FnOnce() -> T + Send + 'static) -> T {
+    match tokio::task::spawn_blocking(f).await {
+        Ok(result) => result,
+        // blocking tasks aren't aborted, so it panicked
+        Err(error) => std::panic::resume_unwind(error.into_panic()),
+    }
+}
+
+/// A `Database` whose methods that read or write pages are `async`, for
+/// embedding in services running on tokio.
+///
+/// The work is done on tokio's blocking thread pool, so it has to be used
+/// from within a tokio runtime. Dropping the last handle to a database (this,
+/// a clone or one of its connections) takes a final checkpoint, which blocks;
+/// `close` does that on the pool instead.
+///
+/// # Examples
+///
+/// ```
+/// use gondor_rdbms::AsyncDatabase;
+/// use gondor_rdbms::types::Value;
+///
+/// let runtime = tokio::runtime::Builder::new_current_thread().build().unwrap();
+/// runtime.block_on(async {
+///     let dir = tempfile::tempdir().unwrap();
+///     let database = AsyncDatabase::open(dir.path().join("shire.db")).await.unwrap();
+///     let connection = database.connect();
+///
+///     connection.execute("CREATE TABLE hobbits (name VARCHAR(32), age INT)").await.unwrap();
+///     connection.execute("INSERT INTO hobbits VALUES ('Bilbo', 111), ('Frodo', 33)").await.unwrap();
+///
+///     let rows = connection.query("SELECT name FROM hobbits WHERE age > 100").await.unwrap();
+///     assert_eq!(rows.iter().next().unwrap().get(0), Some(&Value::Varchar("Bilbo".to_string())));
+///     database.close().await;
+/// });
+/// ```
+#[derive(Clone)]
+pub struct AsyncDatabase {
+    database: Database,
+}
+
+impl AsyncDatabase {
+    /// Opens the database at `path` like `Database::open`.
+    pub async fn open(path: impl Into<PathBuf>) -> Result<Self, DatabaseError> {
+        Self::open_with_options(path, DatabaseOptions::default()).await
+    }
+
+    /// Opens the database at `path` like `Database::open_with_options`.
+    pub async fn open_with_options(path: impl Into<PathBuf>, options: DatabaseOptions) -> Result<Self, DatabaseError> {
+        let path = path.into();
+        let database = blocking(move || Database::open_with_options(path, options)).await?;
+        Ok(Self { database })
+    }
+
+    /// The database this wraps, for anything that's fine to block on.
+    pub fn database(&self) -> &Database {
+        &self.database
+    }
+
+    /// Size in bytes of the pages the database was created with.
+    pub fn page_size(&self) -> usize {
+        self.database.page_size()
+    }
+
+    /// Takes a checkpoint now, like `Database::checkpoint`.
+    pub async fn checkpoint(&self) -> Result<(), DatabaseError> {
+        let database = self.database.clone();
+        blocking(move || database.checkpoint()).await
+    }
+
+    /// Vacuums every table now, like `Database::vacuum`.
+    pub async fn vacuum(&self) -> Result<VacuumStats, DatabaseError> {
+        let database = self.database.clone();
+        blocking(move || database.vacuum()).await
+    }
+
+    /// Opens a new connection.
+    pub fn connect(&self) -> AsyncConnection {
+        AsyncConnection { connection: Arc::new(self.database.connect()) }
+    }
+
+    /// Lets go of this handle on the blocking pool, where the final checkpoint
+    /// runs if it was the last one.
+    pub async fn close(self) {
+        let database = self.database;
+        blocking(move || drop(database)).await
+    }
+}
+
+/// A `Connection` whose methods are `async`, made by `AsyncDatabase::connect`.
+///
+/// Each statement runs on tokio's blocking pool, in a transaction of its own
+/// unless the connection is in a transaction block.
+#[derive(Clone)]
+pub struct AsyncConnection {
+    connection: Arc<Connection>,
+}
+
+impl AsyncConnection {
+    /// Runs a single statement like `Connection::execute`.
+    pub async fn execute(&self, sql: &str) -> Result<usize, DatabaseError> {
+        let (connection, sql) = (self.connection.clone(), sql.to_string());
+        blocking(move || connection.execute(&sql)).await
+    }
+
+    /// Runs every `;` separated statement in `sql` like `Connection::execute_batch`.
+    pub async fn execute_batch(&self, sql: &str) -> Result<(), DatabaseError> {
+        let (connection, sql) = (self.connection.clone(), sql.to_string());
+        blocking(move || connection.execute_batch(&sql)).await
+    }
+
+    /// Runs a single statement and returns its rows like `Connection::query`.
+    pub async fn query(&self, sql: &str) -> Result<Rows, DatabaseError> {
+        let (connection, sql) = (self.connection.clone(), sql.to_string());
+        blocking(move || connection.query(&sql)).await
+    }
+
+    /// Every table in the database, in creation order.
+    pub async fn tables(&self) -> Result<Vec<TableInfo>, DatabaseError> {
+        let connection = self.connection.clone();
+        blocking(move || connection.tables()).await
+    }
+
+    /// Runs an already parsed statement like `Connection::run`.
+    pub async fn run(&self, statement: sql::Statement) -> Result<QueryResult, DatabaseError> {
+        let connection = self.connection.clone();
+        blocking(move || connection.run(&statement)).await
+    }
+
+    /// Loads the CSV read from `reader` into `table` like `Connection::copy_in`.
+    pub async fn copy_in(&self, table: &str, reader: impl Read + Send + 'static, options: sql::CopyOptions) -> Result<usize, DatabaseError> {
+        let (connection, table) = (self.connection.clone(), table.to_string());
+        blocking(move || connection.copy_in(&table, reader, &options)).await
+    }
+
+    /// Prepares a statement like `Connection::prepare`.
+    pub async fn prepare(&self, sql: &str) -> Result<AsyncStatement, DatabaseError> {
+        let (connection, sql) = (self.connection.clone(), sql.to_string());
+        let statement = blocking(move || connection.prepare(&sql)).await?;
+        Ok(AsyncStatement { statement })
+    }
+}
+
+/// A `Statement` whose methods that run it are `async`, made by `AsyncConnection::prepare`.
+pub struct AsyncStatement {
+    statement: Statement,
+}
+
+impl AsyncStatement {
+    /// How many values the statement has to be run with.
+    pub fn parameter_count(&self) -> usize {
+        self.statement.parameter_count()
+    }
+
+    /// The type of each parameter, `None` for one that takes a value of any type.
+    pub fn parameter_types(&self) -> &[Option<DataType>] {
+        self.statement.parameter_types()
+    }
+
+    /// Runs the statement like `Statement::execute`.
+    pub async fn execute(&mut self, parameters: &[Value]) -> Result<usize, DatabaseError> {
+        self.with_statement(parameters, |statement, parameters| statement.execute(parameters)).await
+    }
+
+    /// Runs the statement and returns its rows like `Statement::query`.
+    pub async fn query(&mut self, parameters: &[Value]) -> Result<Rows, DatabaseError> {
+        self.with_statement(parameters, |statement, parameters| statement.query(parameters)).await
+    }
+
+    /// Runs the statement like `Statement::run`.
+    pub async fn run(&mut self, parameters: &[Value]) -> Result<QueryResult, DatabaseError> {
+        self.with_statement(parameters, |statement, parameters| statement.run(parameters)).await
+    }
+
+    /// calls `f` with a clone of the statement on the blocking pool, keeping the
+    /// clone afterwards in case it planned the statement again
+    async fn with_statement<T: Send + 'static>(&mut self, parameters: &[Value], f: impl FnOnce(&mut Statement, &[Value]) -> T + Send + 'static) -> T {
+        let (mut statement, parameters) = (self.statement.clone(), parameters.to_vec());
+        let (statement, result) = blocking(move || {
+            let result = f(&mut statement, &parameters);
+            (statement, result)
+        })
+        .await;
+        self.statement = statement;
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn block_on<T>(future: impl Future<Output = T>) -> T {
+        tokio::runtime::Builder::new_current_thread().build().unwrap().block_on(future)
+    }
+
+    #[test]
+    fn test_async_connections() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("test.db");
+        block_on(async {
+            let database = AsyncDatabase::open(&path).await.unwrap();
+            let connection = database.connect();
+            connection.execute_batch("CREATE TABLE t (id INT, name VARCHAR); INSERT INTO t VALUES (1, 'one')").await.unwrap();
+
+            // statements on several connections at once, which take turns on the pool
+            let inserts: Vec<_> = (2..10)
+                .map(|id| {
+                    let connection = database.connect();
+                    tokio::spawn(async move { connection.execute(&format!("INSERT INTO t VALUES ({id}, 'many')")).await })
+                })
+                .collect();
+            for insert in inserts {
+                assert_eq!(insert.await.unwrap().unwrap(), 1);
+            }
+            assert_eq!(connection.query("SELECT * FROM t").await.unwrap().len(), 9);
+            let csv = std::io::Cursor::new("10,copied\n11,copied\n");
+            assert_eq!(connection.copy_in("t", csv, sql::CopyOptions::default()).await.unwrap(), 2);
+            assert_eq!(connection.tables().await.unwrap()[0].name, "t");
+            assert!(matches!(connection.execute("SELECT * FROM missing").await, Err(DatabaseError::ExecutionError(_))));
+
+            let mut select = connection.prepare("SELECT name FROM t WHERE id = $1").await.unwrap();
+            assert_eq!(select.parameter_count(), 1);
+            let rows = select.query(&[Value::Int(1)]).await.unwrap();
+            assert_eq!(rows.iter().next().unwrap().values(), [Value::Varchar("one".into())]);
+            drop((connection, select));
+            database.close().await;
+        });
+
+        // the database was shut down cleanly, and opens again
+        let rows = block_on(async { AsyncDatabase::open(&path).await.unwrap().connect().query("SELECT * FROM t WHERE name = 'many'").await.unwrap() });
+        assert_eq!(rows.len(), 8);
+    }
+}
@@ -0,0 +1,898 @@
+use crate::index::{BPlusTree, IndexError, KeyOrder, encode_key_with};
+use crate::planner::{ColumnStatistics, TableStatistics};
+use crate::sql::{Expr, parse_expr};
+use crate::storage::{BufferPool, HeapError, HeapFile, PageType, RecordId};
+use crate::transaction::Transaction;
+use crate::types::{Column, DataType, Decimal, Interval, Value};
+use crate::wal::LogManager;
+use std::sync::{Arc, Mutex};
+
+/// root page of the catalog's heap file, the first page of every database
+pub const CATALOG_ROOT_PAGE_ID: u32 = 0;
+
+#[derive(Debug)]
+pub enum CatalogError {
+    TableExists(String),
+    TableNotFound(String),
+    DuplicateColumn(String),
+    /// an index of this name exists already, on this table or another one
+    IndexExists(String),
+    /// the table has a primary key already
+    MultiplePrimaryKeys(String),
+    /// the table has a constraint by this name already
+    ConstraintExists(String),
+    /// the catalog tuple at this record id isn't a valid table definition or statistics entry
+    CorruptEntry(RecordId),
+    /// the statistics of this table don't cover each of its columns exactly once
+    CorruptStatistics(String),
+    HeapError(HeapError),
+    IndexError(IndexError),
+}
+
+impl std::fmt::Display for CatalogError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CatalogError::TableExists(name) => write!(f, "Table {name} already exists"),
+            CatalogError::TableNotFound(name) => write!(f, "Table {name} does not exist"),
+            CatalogError::DuplicateColumn(name) => write!(f, "Column {name} is defined more than once"),
+            CatalogError::IndexExists(name) => write!(f, "Index {name} already exists"),
+            CatalogError::MultiplePrimaryKeys(table) => write!(f, "Table {table} can only have one primary key"),
+            CatalogError::ConstraintExists(name) => write!(f, "Constraint {name} already exists"),
+            CatalogError::CorruptEntry(rid) => write!(f, "Catalog entry at {rid:?} is corrupt"),
+            CatalogError::CorruptStatistics(table) => write!(f, "Statistics of table {table} are corrupt"),
+            CatalogError::HeapError(error) => write!(f, "Heap error: {error}"),
+            CatalogError::IndexError(error) => write!(f, "Index error: {error}"),
+        }
+    }
+}
+
+impl std::error::Error for CatalogError {}
+
+impl From<HeapError> for CatalogError {
+    fn from(error: HeapError) -> Self {
+        CatalogError::HeapError(error)
+    }
+}
+
+impl From<IndexError> for CatalogError {
+    fn from(error: IndexError) -> Self {
+        CatalogError::IndexError(error)
+    }
+}
+
+/// Definition of a table as recorded in the catalog.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TableInfo {
+    pub name: String,
+    pub columns: Vec<Column>,
+    /// root page of the heap file holding the table's rows
+    pub root_page_id: u32,
+    /// indexes on the table, in creation order
+    pub indexes: Vec<IndexInfo>,
+    /// root page of the heap file holding the table's statistics, once it has been analyzed
+    pub statistics_page_id: Option<u32>,
+    pub constraints: Constraints,
+}
+
+impl TableInfo {
+    pub fn primary_key(&self) -> Option<&IndexInfo> {
+        self.indexes.iter().find(|index| index.kind == IndexKind::PrimaryKey)
+    }
+}
+
+/// What a table's rows are allowed to hold besides values of its column
+/// types, other than the keys its unique indexes keep apart, and what a row
+/// inserted without a value for a column gets instead.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Constraints {
+    /// positions of the columns declared `NOT NULL`
+    pub not_null: Vec<usize>,
+    /// the `DEFAULT` of each column that has one, by position; the others default to NULL
+    pub defaults: Vec<(usize, Expr)>,
+    /// `CHECK` constraints, in the order they were declared
+    pub checks: Vec<CheckConstraint>,
+}
+
+impl Constraints {
+    pub fn is_empty(&self) -> bool {
+        self.not_null.is_empty() && self.defaults.is_empty() && self.checks.is_empty()
+    }
+
+    /// the default of the column at `position`, `None` if it's NULL
+    pub fn default_of(&self, position: usize) -> Option<&Expr> {
+        self.defaults.iter().find(|(column, _)| *column == position).map(|(_, expr)| expr)
+    }
+}
+
+/// A `CHECK` constraint, which a row passes unless `expr` is false for it:
+/// NULL lets it through, as it does in PostgreSQL.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CheckConstraint {
+    pub name: String,
+    pub expr: Expr,
+}
+
+/// What an index allows, and which constraint (if any) it enforces.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IndexKind {
+    /// any number of rows per key
+    NonUnique,
+    /// at most one row per key, from a `UNIQUE` constraint
+    Unique,
+    /// the table's primary key: unique, and NULL isn't allowed in its columns
+    PrimaryKey,
+}
+
+impl IndexKind {
+    pub fn is_unique(self) -> bool {
+        self != IndexKind::NonUnique
+    }
+}
+
+/// Definition of an index as recorded in the catalog, with its table's.
+#[derive(Debug, Clone, PartialEq)]
+pub struct IndexInfo {
+    pub name: String,
+    /// the indexed columns, in key order
+    pub columns: Vec<IndexColumn>,
+    pub kind: IndexKind,
+    /// root page of the `BPlusTree` holding the index
+    pub root_page_id: u32,
+}
+
+impl IndexInfo {
+    /// Opens the index's tree, with unique keys for unique indexes.
+    pub fn open(&self, buffer_pool: Arc<Mutex<BufferPool>>) -> BPlusTree {
+        let tree = BPlusTree::open(buffer_pool, self.root_page_id);
+        if self.kind.is_unique() { tree.with_unique_keys() } else { tree }
+    }
+
+    /// how each key column sorts
+    pub fn orders(&self) -> Vec<KeyOrder> {
+        self.columns.iter().map(|column| column.order).collect()
+    }
+
+    /// The index key of a table row, `row` holding all of the table's columns.
+    pub fn key(&self, row: &[Value]) -> Vec<u8> {
+        let values: Vec<Value> = self.columns.iter().map(|column| row[column.position].clone()).collect();
+        encode_key_with(&values, &self.orders())
+    }
+}
+
+/// One column of an index key.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IndexColumn {
+    /// position of the column in the table
+    pub position: usize,
+    pub order: KeyOrder,
+}
+
+impl IndexColumn {
+    /// an ascending key column, NULLs last
+    pub fn new(position: usize) -> Self {
+        Self { position, order: KeyOrder::ASC }
+    }
+}
+
+/// The system catalog: the definitions of every table in the database, and
+/// of the indexes on them.
+///
+/// Table definitions are tuples in a heap file whose root is always page 0, so
+/// they are read through the buffer pool like any other data and survive
+/// restarts. Opening the catalog on an empty database bootstraps it by
+/// creating that heap file. A table's indexes are part of its tuple.
+///
+/// The catalog keeps nothing in memory besides the heap file, so a table
+/// created by a transaction that rolls back disappears along with its tuple.
+///
+/// The statistics `ANALYZE` gathers on a table are kept in a heap file of
+/// catalog pages of their own, created the first time the table is analyzed
+/// and recorded in the table's tuple.
+///
+/// # Examples
+///
+/// ```
+/// use gondor_rdbms::catalog::Catalog;
+/// use gondor_rdbms::storage::{BufferPool, DiskManager};
+/// use gondor_rdbms::transaction::Transaction;
+/// use gondor_rdbms::types::{Column, DataType};
+/// use std::sync::{Arc, Mutex};
+///
+/// let dir = tempfile::tempdir().unwrap();
+/// let disk_manager = DiskManager::open(dir.path().join("example.db")).unwrap();
+/// let buffer_pool = Arc::new(Mutex::new(BufferPool::new(disk_manager)));
+/// let mut catalog = Catalog::open(buffer_pool).unwrap();
+///
+/// let mut txn = Transaction::new(1);
+/// let columns = vec![Column::new("id", DataType::Int), Column::new("name", DataType::Varchar(Some(32)))];
+/// catalog.create_table(&mut txn, "users", columns.clone()).unwrap();
+/// assert_eq!(catalog.table("users").unwrap().unwrap().columns, columns);
+/// ```
+pub struct Catalog {
+    buffer_pool: Arc<Mutex<BufferPool>>,
+    /// one tuple per table
+    heap: HeapFile,
+    /// changes to statistics are logged here, if set
+    log_manager: Option<Arc<Mutex<LogManager>>>,
+}
+
+impl Catalog {
+    /// Opens the catalog of the database behind `buffer_pool`, bootstrapping it
+    /// if the database doesn't have any pages yet.
+    pub fn open(buffer_pool: Arc<Mutex<BufferPool>>) -> Result<Self, CatalogError> {
+        let is_new = buffer_pool.lock().unwrap().num_pages() == 0;
+        let heap = if is_new {
+            let heap = HeapFile::create_of_type(buffer_pool.clone(), PageType::Catalog)?;
+            debug_assert_eq!(heap.root_page_id(), CATALOG_ROOT_PAGE_ID);
+            heap
+        } else {
+            HeapFile::open(buffer_pool.clone(), CATALOG_ROOT_PAGE_ID)?
+        };
+        Ok(Self { buffer_pool, heap, log_manager: None })
+    }
+
+    /// Logs every subsequent change to the catalog to `log_manager`.
+    pub fn set_log_manager(&mut self, log_manager: Arc<Mutex<LogManager>>) {
+        self.heap.set_log_manager(log_manager.clone());
+        self.log_manager = Some(log_manager);
+    }
+
+    /// Every table in the database, in creation order.
+    pub fn tables(&self) -> Result<Vec<TableInfo>, CatalogError> {
+        Ok(self.entries()?.into_iter().map(|(_, table)| table).collect())
+    }
+
+    pub fn table(&self, name: &str) -> Result<Option<TableInfo>, CatalogError> {
+        Ok(self.tables()?.into_iter().find(|table| table.name == name))
+    }
+
+    /// Records a new table and creates the (empty) heap file for its rows.
+    pub fn create_table(&mut self, txn: &mut Transaction, name: &str, columns: Vec<Column>) -> Result<TableInfo, CatalogError> {
+        if self.table(name)?.is_some() {
+            return Err(CatalogError::TableExists(name.to_string()));
+        }
+        for (i, column) in columns.iter().enumerate() {
+            if columns[..i].iter().any(|other| other.name == column.name) {
+                return Err(CatalogError::DuplicateColumn(column.name.clone()));
+            }
+        }
+
+        let heap = HeapFile::create(self.buffer_pool.clone())?;
+        let table = TableInfo {
+            name: name.to_string(),
+            columns,
+            root_page_id: heap.root_page_id(),
+            indexes: Vec::new(),
+            statistics_page_id: None,
+            constraints: Constraints::default(),
+        };
+        self.heap.insert(txn, &encode_table(&table))?;
+        Ok(table)
+    }
+
+    /// Records a new index on the columns at positions `columns` of table
+    /// `table_name` and creates its (empty) tree. Filling it with the rows
+    /// already in the table is up to the caller.
+    pub fn create_index(&mut self, txn: &mut Transaction, table_name: &str, name: &str, columns: Vec<IndexColumn>, kind: IndexKind) -> Result<IndexInfo, CatalogError> {
+        let mut entries = self.entries()?;
+        let name_taken = entries.iter().any(|(_, table)| table.indexes.iter().any(|index| index.name == name));
+        let (rid, table) = entries
+            .iter_mut()
+            .find(|(_, table)| table.name == table_name)
+            .ok_or_else(|| CatalogError::TableNotFound(table_name.to_string()))?;
+        // checked first, a second primary key would usually clash on its name as well
+        if kind == IndexKind::PrimaryKey && table.primary_key().is_some() {
+            return Err(CatalogError::MultiplePrimaryKeys(table_name.to_string()));
+        }
+        if name_taken {
+            return Err(CatalogError::IndexExists(name.to_string()));
+        }
+
+        let tree = BPlusTree::create(self.buffer_pool.clone())?;
+        let index = IndexInfo {
+            name: name.to_string(),
+            columns,
+            kind,
+            root_page_id: tree.root_page_id(),
+        };
+        table.indexes.push(index.clone());
+        self.heap.update(txn, *rid, &encode_table(table))?;
+        Ok(index)
+    }
+
+    /// Records `constraints` as those of table `name`, replacing any it had.
+    /// Checking the rows already in the table against them is up to the caller;
+    /// two `CHECK` constraints by the same name are a `ConstraintExists`.
+    pub fn set_constraints(&mut self, txn: &mut Transaction, name: &str, constraints: Constraints) -> Result<TableInfo, CatalogError> {
+        let mut entries = self.entries()?;
+        let (rid, table) = entries
+            .iter_mut()
+            .find(|(_, table)| table.name == name)
+            .ok_or_else(|| CatalogError::TableNotFound(name.to_string()))?;
+        for (i, check) in constraints.checks.iter().enumerate() {
+            if constraints.checks[..i].iter().any(|other| other.name == check.name) {
+                return Err(CatalogError::ConstraintExists(check.name.clone()));
+            }
+        }
+        table.constraints = constraints;
+        self.heap.update(txn, *rid, &encode_table(table))?;
+        Ok(table.clone())
+    }
+
+    /// The statistics last recorded for table `name`, `None` if it hasn't
+    /// been analyzed (or doesn't exist).
+    pub fn statistics(&self, name: &str) -> Result<Option<TableStatistics>, CatalogError> {
+        let Some(table) = self.table(name)? else {
+            return Ok(None);
+        };
+        let Some(page_id) = table.statistics_page_id else {
+            return Ok(None);
+        };
+        let heap = HeapFile::open(self.buffer_pool.clone(), page_id)?;
+        let mut statistics = None;
+        let mut columns = vec![None; table.columns.len()];
+        for tuple in heap.scan() {
+            let (rid, tuple) = tuple?;
+            match decode_statistics(&tuple, &table.columns).ok_or(CatalogError::CorruptEntry(rid))? {
+                StatisticsEntry::Table { row_count, page_count } => statistics = Some((row_count, page_count)),
+                StatisticsEntry::Column(position, column) => columns[position] = Some(column),
+            }
+        }
+        // a table is always recorded with all of its columns
+        let Some((row_count, page_count)) = statistics else {
+            return Ok(None);
+        };
+        let columns = columns.into_iter().collect::<Option<Vec<_>>>().ok_or(CatalogError::CorruptStatistics(name.to_string()))?;
+        Ok(Some(TableStatistics { row_count, page_count, columns }))
+    }
+
+    /// Records `statistics` for table `name`, replacing any it had.
+    pub fn set_statistics(&mut self, txn: &mut Transaction, name: &str, statistics: &TableStatistics) -> Result<(), CatalogError> {
+        let mut entries = self.entries()?;
+        let (rid, table) = entries
+            .iter_mut()
+            .find(|(_, table)| table.name == name)
+            .ok_or_else(|| CatalogError::TableNotFound(name.to_string()))?;
+        if statistics.columns.len() != table.columns.len() {
+            return Err(CatalogError::CorruptStatistics(name.to_string()));
+        }
+
+        let mut heap = match table.statistics_page_id {
+            Some(page_id) => HeapFile::open(self.buffer_pool.clone(), page_id)?,
+            None => {
+                let heap = HeapFile::create_of_type(self.buffer_pool.clone(), PageType::Catalog)?;
+                table.statistics_page_id = Some(heap.root_page_id());
+                self.heap.update(txn, *rid, &encode_table(table))?;
+                heap
+            }
+        };
+        if let Some(log_manager) = &self.log_manager {
+            heap.set_log_manager(log_manager.clone());
+        }
+        let old: Vec<RecordId> = heap.scan().map(|tuple| Ok(tuple?.0)).collect::<Result<_, CatalogError>>()?;
+        for rid in old {
+            heap.delete(txn, rid)?;
+        }
+        heap.insert(txn, &encode_statistics(&StatisticsEntry::Table { row_count: statistics.row_count, page_count: statistics.page_count }))?;
+        for (position, column) in statistics.columns.iter().enumerate() {
+            heap.insert(txn, &encode_statistics(&StatisticsEntry::Column(position, column.clone())))?;
+        }
+        Ok(())
+    }
+
+    /// every table with the record id of its catalog tuple
+    fn entries(&self) -> Result<Vec<(RecordId, TableInfo)>, CatalogError> {
+        self.heap
+            .scan()
+            .map(|tuple| {
+                let (rid, tuple) = tuple?;
+                Ok((rid, decode_table(&tuple).ok_or(CatalogError::CorruptEntry(rid))?))
+            })
+            .collect()
+    }
+}
+
+// catalog tuple layout: name, root page id (u32), column count (u16), then each column's
+// name and type. names are a u16 length followed by UTF-8, types a tag byte plus the
+// VARCHAR length (u32) when there is one. then the index count (u16) and each index's
+// name, kind tag, root page id (u32), column count (u16) and columns, each a position (u16)
+// and a flags byte for its order. then the root page id (u32) of the table's statistics,
+// and last the table's constraints: the count (u16) and positions (u16 each) of its NOT
+// NULL columns, the count (u16) of its defaults and for each a column position (u16) and
+// the expression as SQL text, written like a name, and the count (u16) of its checks and
+// each one's name and SQL text. tables recorded before indexes existed end after their
+// columns, tables never analyzed and without constraints after their indexes, and tables
+// without constraints after their statistics. a table with constraints that was never
+// analyzed has 0, the catalog's own root page, for the root of its statistics
+
+const TAG_INT: u8 = 0;
+const TAG_BIGINT: u8 = 1;
+const TAG_BOOL: u8 = 2;
+const TAG_VARCHAR: u8 = 3;
+const TAG_BOUNDED_VARCHAR: u8 = 4;
+const TAG_NUMERIC: u8 = 5;
+const TAG_BOUNDED_NUMERIC: u8 = 6;
+const TAG_DATE: u8 = 7;
+const TAG_TIME: u8 = 8;
+const TAG_TIMESTAMP: u8 = 9;
+const TAG_INTERVAL: u8 = 10;
+
+const TAG_NON_UNIQUE: u8 = 0;
+const TAG_UNIQUE: u8 = 1;
+const TAG_PRIMARY_KEY: u8 = 2;
+
+const FLAG_DESCENDING: u8 = 1;
+const FLAG_NULLS_FIRST: u8 = 2;
+
+fn encode_table(table: &TableInfo) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    encode_name(&mut bytes, &table.name);
+    bytes.extend_from_slice(&table.root_page_id.to_le_bytes());
+    bytes.extend_from_slice(&(table.columns.len() as u16).to_le_bytes());
+    for column in &table.columns {
+        encode_name(&mut bytes, &column.name);
+        match column.data_type {
+            DataType::Int => bytes.push(TAG_INT),
+            DataType::BigInt => bytes.push(TAG_BIGINT),
+            DataType::Bool => bytes.push(TAG_BOOL),
+            DataType::Varchar(None) => bytes.push(TAG_VARCHAR),
+            DataType::Varchar(Some(length)) => {
+                bytes.push(TAG_BOUNDED_VARCHAR);
+                bytes.extend_from_slice(&length.to_le_bytes());
+            }
+            DataType::Numeric(None) => bytes.push(TAG_NUMERIC),
+            DataType::Numeric(Some((precision, scale))) => bytes.extend_from_slice(&[TAG_BOUNDED_NUMERIC, precision, scale]),
+            DataType::Date => bytes.push(TAG_DATE),
+            DataType::Time => bytes.push(TAG_TIME),
+            DataType::Timestamp => bytes.push(TAG_TIMESTAMP),
+            DataType::Interval => bytes.push(TAG_INTERVAL),
+        }
+    }
+
+    bytes.extend_from_slice(&(table.indexes.len() as u16).to_le_bytes());
+    for index in &table.indexes {
+        encode_name(&mut bytes, &index.name);
+        bytes.push(match index.kind {
+            IndexKind::NonUnique => TAG_NON_UNIQUE,
+            IndexKind::Unique => TAG_UNIQUE,
+            IndexKind::PrimaryKey => TAG_PRIMARY_KEY,
+        });
+        bytes.extend_from_slice(&index.root_page_id.to_le_bytes());
+        bytes.extend_from_slice(&(index.columns.len() as u16).to_le_bytes());
+        for column in &index.columns {
+            bytes.extend_from_slice(&(column.position as u16).to_le_bytes());
+            let mut flags = 0;
+            if column.order.descending {
+                flags |= FLAG_DESCENDING;
+            }
+            if column.order.nulls_first {
+                flags |= FLAG_NULLS_FIRST;
+            }
+            bytes.push(flags);
+        }
+    }
+    if table.statistics_page_id.is_some() || !table.constraints.is_empty() {
+        bytes.extend_from_slice(&table.statistics_page_id.unwrap_or(CATALOG_ROOT_PAGE_ID).to_le_bytes());
+    }
+
+    let constraints = &table.constraints;
+    if !constraints.is_empty() {
+        bytes.extend_from_slice(&(constraints.not_null.len() as u16).to_le_bytes());
+        for &position in &constraints.not_null {
+            bytes.extend_from_slice(&(position as u16).to_le_bytes());
+        }
+        bytes.extend_from_slice(&(constraints.defaults.len() as u16).to_le_bytes());
+        for (position, expr) in &constraints.defaults {
+            bytes.extend_from_slice(&(*position as u16).to_le_bytes());
+            encode_name(&mut bytes, &expr.to_string());
+        }
+        bytes.extend_from_slice(&(constraints.checks.len() as u16).to_le_bytes());
+        for check in &constraints.checks {
+            encode_name(&mut bytes, &check.name);
+            encode_name(&mut bytes, &check.expr.to_string());
+        }
+    }
+    bytes
+}
+
+fn encode_name(bytes: &mut Vec<u8>, name: &str) {
+    bytes.extend_from_slice(&(name.len() as u16).to_le_bytes());
+    bytes.extend_from_slice(name.as_bytes());
+}
+
+/// `None` if `bytes` isn't exactly one encoded table
+fn decode_table(mut bytes: &[u8]) -> Option<TableInfo> {
+    let bytes = &mut bytes;
+    let name = decode_name(bytes)?;
+    let root_page_id = u32::from_le_bytes(take(bytes, 4)?.try_into().unwrap());
+    let column_count = u16::from_le_bytes(take(bytes, 2)?.try_into().unwrap());
+
+    let mut columns = Vec::with_capacity(column_count as usize);
+    for _ in 0..column_count {
+        let name = decode_name(bytes)?;
+        let data_type = match take(bytes, 1)?[0] {
+            TAG_INT => DataType::Int,
+            TAG_BIGINT => DataType::BigInt,
+            TAG_BOOL => DataType::Bool,
+            TAG_VARCHAR => DataType::Varchar(None),
+            TAG_BOUNDED_VARCHAR => DataType::Varchar(Some(u32::from_le_bytes(take(bytes, 4)?.try_into().unwrap()))),
+            TAG_NUMERIC => DataType::Numeric(None),
+            TAG_BOUNDED_NUMERIC => {
+                let modifiers = take(bytes, 2)?;
+                DataType::Numeric(Some((modifiers[0], modifiers[1])))
+            }
+            TAG_DATE => DataType::Date,
+            TAG_TIME => DataType::Time,
+            TAG_TIMESTAMP => DataType::Timestamp,
+            TAG_INTERVAL => DataType::Interval,
+            _ => return None,
+        };
+        columns.push(Column { name, data_type });
+    }
+
+    let mut indexes = Vec::new();
+    if !bytes.is_empty() {
+        let index_count = u16::from_le_bytes(take(bytes, 2)?.try_into().unwrap());
+        for _ in 0..index_count {
+            let name = decode_name(bytes)?;
+            let kind = match take(bytes, 1)?[0] {
+                TAG_NON_UNIQUE => IndexKind::NonUnique,
+                TAG_UNIQUE => IndexKind::Unique,
+                TAG_PRIMARY_KEY => IndexKind::PrimaryKey,
+                _ => return None,
+            };
+            let root_page_id = u32::from_le_bytes(take(bytes, 4)?.try_into().unwrap());
+            let column_count = u16::from_le_bytes(take(bytes, 2)?.try_into().unwrap());
+            let mut index_columns = Vec::with_capacity(column_count as usize);
+            for _ in 0..column_count {
+                let position = u16::from_le_bytes(take(bytes, 2)?.try_into().unwrap()) as usize;
+                let flags = take(bytes, 1)?[0];
+                if position >= columns.len() || flags & !(FLAG_DESCENDING | FLAG_NULLS_FIRST) != 0 {
+                    return None;
+                }
+                let order = KeyOrder { descending: flags & FLAG_DESCENDING != 0, nulls_first: flags & FLAG_NULLS_FIRST != 0 };
+                index_columns.push(IndexColumn { position, order });
+            }
+            indexes.push(IndexInfo { name, columns: index_columns, kind, root_page_id });
+        }
+    }
+    let statistics_page_id = match bytes.is_empty() {
+        true => None,
+        false => Some(u32::from_le_bytes(take(bytes, 4)?.try_into().unwrap())).filter(|&page_id| page_id != CATALOG_ROOT_PAGE_ID),
+    };
+
+    let mut constraints = Constraints::default();
+    if !bytes.is_empty() {
+        let position = |bytes: &mut &[u8]| {
+            let position = u16::from_le_bytes(take(bytes, 2)?.try_into().unwrap()) as usize;
+            (position < columns.len()).then_some(position)
+        };
+        let expr = |bytes: &mut &[u8]| parse_expr(&decode_name(bytes)?).ok();
+        for _ in 0..u16::from_le_bytes(take(bytes, 2)?.try_into().unwrap()) {
+            constraints.not_null.push(position(bytes)?);
+        }
+        for _ in 0..u16::from_le_bytes(take(bytes, 2)?.try_into().unwrap()) {
+            constraints.defaults.push((position(bytes)?, expr(bytes)?));
+        }
+        for _ in 0..u16::from_le_bytes(take(bytes, 2)?.try_into().unwrap()) {
+            constraints.checks.push(CheckConstraint { name: decode_name(bytes)?, expr: expr(bytes)? });
+        }
+    }
+
+    bytes.is_empty().then_some(TableInfo { name, columns, root_page_id, indexes, statistics_page_id, constraints })
+}
+
+// statistics tuple layout: a tag byte, then for the table its row and page counts (u64
+// each), for a column its position (u16), NULL fraction and distinct count (f64 each) and
+// the bound count (u16) of its histogram followed by the bounds. bounds are never NULL and
+// are stored as values of the column's type, VARCHARs like names
+
+const TAG_TABLE_STATISTICS: u8 = 0;
+const TAG_COLUMN_STATISTICS: u8 = 1;
+
+/// one tuple of a table's statistics heap file
+#[derive(Debug, Clone, PartialEq)]
+enum StatisticsEntry {
+    Table { row_count: u64, page_count: u64 },
+    /// statistics of the column at this position
+    Column(usize, ColumnStatistics),
+}
+
+fn encode_statistics(entry: &StatisticsEntry) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    match entry {
+        StatisticsEntry::Table { row_count, page_count } => {
+            bytes.push(TAG_TABLE_STATISTICS);
+            bytes.extend_from_slice(&row_count.to_le_bytes());
+            bytes.extend_from_slice(&page_count.to_le_bytes());
+        }
+        StatisticsEntry::Column(position, column) => {
+            bytes.push(TAG_COLUMN_STATISTICS);
+            bytes.extend_from_slice(&(*position as u16).to_le_bytes());
+            bytes.extend_from_slice(&column.null_fraction.to_le_bytes());
+            bytes.extend_from_slice(&column.distinct_count.to_le_bytes());
+            bytes.extend_from_slice(&(column.histogram.len() as u16).to_le_bytes());
+            for bound in &column.histogram {
+                match bound {
+                    Value::Int(value) => bytes.extend_from_slice(&value.to_le_bytes()),
+                    Value::BigInt(value) => bytes.extend_from_slice(&value.to_le_bytes()),
+                    Value::Bool(value) => bytes.push(*value as u8),
+                    Value::Varchar(value) => encode_name(&mut bytes, value),
+                    Value::Numeric(value) => bytes.extend_from_slice(&value.to_bytes()),
+                    Value::Date(days) => bytes.extend_from_slice(&days.to_le_bytes()),
+                    Value::Time(micros) | Value::Timestamp(micros) => bytes.extend_from_slice(&micros.to_le_bytes()),
+                    Value::Interval(interval) => bytes.extend_from_slice(&interval.to_bytes()),
+                    Value::Null => unreachable!("histogram bounds are never NULL"),
+                }
+            }
+        }
+    }
+    bytes
+}
+
+/// `None` if `bytes` isn't exactly one encoded statistics entry of a table with `columns`
+fn decode_statistics(mut bytes: &[u8], columns: &[Column]) -> Option<StatisticsEntry> {
+    let bytes = &mut bytes;
+    let entry = match take(bytes, 1)?[0] {
+        TAG_TABLE_STATISTICS => {
+            let row_count = u64::from_le_bytes(take(bytes, 8)?.try_into().unwrap());
+            let page_count = u64::from_le_bytes(take(bytes, 8)?.try_into().unwrap());
+            StatisticsEntry::Table { row_count, page_count }
+        }
+        TAG_COLUMN_STATISTICS => {
+            let position = u16::from_le_bytes(take(bytes, 2)?.try_into().unwrap()) as usize;
+            let data_type = columns.get(position)?.data_type;
+            let null_fraction = f64::from_le_bytes(take(bytes, 8)?.try_into().unwrap());
+            let distinct_count = f64::from_le_bytes(take(bytes, 8)?.try_into().unwrap());
+            let bound_count = u16::from_le_bytes(take(bytes, 2)?.try_into().unwrap());
+            let mut histogram = Vec::with_capacity(bound_count as usize);
+            for _ in 0..bound_count {
+                histogram.push(match data_type {
+                    DataType::Int => Value::Int(i32::from_le_bytes(take(bytes, 4)?.try_into().unwrap())),
+                    DataType::BigInt => Value::BigInt(i64::from_le_bytes(take(bytes, 8)?.try_into().unwrap())),
+                    DataType::Bool => Value::Bool(take(bytes, 1)?[0] != 0),
+                    DataType::Varchar(_) => Value::Varchar(decode_name(bytes)?),
+                    DataType::Numeric(_) => Value::Numeric(Decimal::from_bytes(take(bytes, 17)?.try_into().unwrap())?),
+                    DataType::Date => Value::Date(i32::from_le_bytes(take(bytes, 4)?.try_into().unwrap())),
+                    DataType::Time => Value::Time(i64::from_le_bytes(take(bytes, 8)?.try_into().unwrap())),
+                    DataType::Timestamp => Value::Timestamp(i64::from_le_bytes(take(bytes, 8)?.try_into().unwrap())),
+                    DataType::Interval => Value::Interval(Interval::from_bytes(take(bytes, 16)?.try_into().unwrap())),
+                });
+            }
+            StatisticsEntry::Column(position, ColumnStatistics { null_fraction, distinct_count, histogram })
+        }
+        _ => return None,
+    };
+    bytes.is_empty().then_some(entry)
+}
+
+fn decode_name(bytes: &mut &[u8]) -> Option<String> {
+    let length = u16::from_le_bytes(take(bytes, 2)?.try_into().unwrap());
+    String::from_utf8(take(bytes, length as usize)?.to_vec()).ok()
+}
+
+/// splits the first `length` bytes off the front of `bytes`
+fn take<'a>(bytes: &mut &'a [u8], length: usize) -> Option<&'a [u8]> {
+    let (front, rest) = bytes.split_at_checked(length)?;
+    *bytes = rest;
+    Some(front)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::DiskManager;
+    use tempfile::TempDir;
+
+    fn open(dir: &TempDir) -> (Catalog, Arc<Mutex<BufferPool>>) {
+        let disk_manager = DiskManager::open(dir.path().join("test.db")).unwrap();
+        let buffer_pool = Arc::new(Mutex::new(BufferPool::new(disk_manager)));
+        (Catalog::open(buffer_pool.clone()).unwrap(), buffer_pool)
+    }
+
+    fn users_columns() -> Vec<Column> {
+        vec![
+            Column::new("id", DataType::Int),
+            Column::new("name", DataType::Varchar(Some(64))),
+            Column::new("bio", DataType::Varchar(None)),
+            Column::new("visits", DataType::BigInt),
+            Column::new("active", DataType::Bool),
+        ]
+    }
+
+    #[test]
+    fn test_bootstrap_creates_empty_catalog_at_page_zero() {
+        let dir = TempDir::new().unwrap();
+        let (catalog, buffer_pool) = open(&dir);
+        assert_eq!(catalog.heap.root_page_id(), CATALOG_ROOT_PAGE_ID);
+        assert_eq!(buffer_pool.lock().unwrap().num_pages(), 1);
+        assert_eq!(buffer_pool.lock().unwrap().get_page(CATALOG_ROOT_PAGE_ID).unwrap().page_type(), PageType::Catalog);
+        assert!(catalog.tables().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_tables_survive_restart() {
+        let dir = TempDir::new().unwrap();
+        let created = {
+            let (mut catalog, buffer_pool) = open(&dir);
+            let mut txn = Transaction::new(1);
+            let users = catalog.create_table(&mut txn, "users", users_columns()).unwrap();
+            let orders = catalog.create_table(&mut txn, "orders", vec![Column::new("id", DataType::BigInt)]).unwrap();
+            buffer_pool.lock().unwrap().flush_all().unwrap();
+            vec![users, orders]
+        };
+
+        let (catalog, _buffer_pool) = open(&dir);
+        assert_eq!(catalog.tables().unwrap(), created);
+        assert_eq!(catalog.table("orders").unwrap(), Some(created[1].clone()));
+        assert_eq!(catalog.table("missing").unwrap(), None);
+    }
+
+    #[test]
+    fn test_table_heap_is_usable() {
+        let dir = TempDir::new().unwrap();
+        let (mut catalog, buffer_pool) = open(&dir);
+        let mut txn = Transaction::new(1);
+        let table = catalog.create_table(&mut txn, "users", users_columns()).unwrap();
+
+        let mut heap = HeapFile::open(buffer_pool.clone(), table.root_page_id).unwrap();
+        let rid = heap.insert(&mut txn, b"row").unwrap();
+        let reopened = HeapFile::open(buffer_pool, table.root_page_id).unwrap();
+        assert_eq!(reopened.get(rid).unwrap(), b"row");
+    }
+
+    #[test]
+    fn test_duplicate_names_rejected() {
+        let dir = TempDir::new().unwrap();
+        let (mut catalog, _buffer_pool) = open(&dir);
+        let mut txn = Transaction::new(1);
+        catalog.create_table(&mut txn, "users", users_columns()).unwrap();
+
+        let result = catalog.create_table(&mut txn, "users", users_columns());
+        assert!(matches!(result, Err(CatalogError::TableExists(name)) if name == "users"));
+        let columns = vec![Column::new("id", DataType::Int), Column::new("id", DataType::Bool)];
+        let result = catalog.create_table(&mut txn, "other", columns);
+        assert!(matches!(result, Err(CatalogError::DuplicateColumn(name)) if name == "id"));
+        assert_eq!(catalog.tables().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_table_encoding_round_trips() {
+        let mut table = TableInfo { name: "users".into(), columns: users_columns(), root_page_id: 7, indexes: Vec::new(), statistics_page_id: None, constraints: Constraints::default() };
+        let bytes = encode_table(&table);
+        assert_eq!(decode_table(&bytes), Some(table.clone()));
+        assert_eq!(decode_table(&bytes[..bytes.len() - 1]), None);
+        assert_eq!(decode_table(&[bytes.as_slice(), &[0]].concat()), None);
+        // tables recorded before there were indexes have no index count at all
+        assert_eq!(decode_table(&bytes[..bytes.len() - 2]), Some(table.clone()));
+
+        table.indexes = vec![
+            IndexInfo { name: "users_pkey".into(), columns: vec![IndexColumn::new(0)], kind: IndexKind::PrimaryKey, root_page_id: 8 },
+            IndexInfo {
+                name: "users_name_visits_idx".into(),
+                columns: vec![IndexColumn::new(1), IndexColumn { position: 3, order: KeyOrder::DESC }, IndexColumn { position: 2, order: KeyOrder { nulls_first: true, ..KeyOrder::ASC } }],
+                kind: IndexKind::NonUnique,
+                root_page_id: 9,
+            },
+        ];
+        let bytes = encode_table(&table);
+        assert_eq!(decode_table(&bytes), Some(table.clone()));
+        assert_eq!(decode_table(&bytes[..bytes.len() - 1]), None);
+
+        // tables that have been analyzed end with the root of their statistics
+        table.statistics_page_id = Some(10);
+        let bytes = encode_table(&table);
+        assert_eq!(decode_table(&bytes), Some(table.clone()));
+        assert_eq!(decode_table(&bytes[..bytes.len() - 1]), None);
+        table.statistics_page_id = None;
+        assert_eq!(decode_table(&bytes[..bytes.len() - 4]), Some(table.clone()));
+
+        // constraints come last, after the statistics' root whether or not there are statistics
+        let expr = |sql: &str| parse_expr(sql).unwrap();
+        table.constraints = Constraints {
+            not_null: vec![0, 1],
+            defaults: vec![(3, expr("0")), (1, expr("'it''s'"))],
+            checks: vec![CheckConstraint { name: "users_visits_check".into(), expr: expr("visits >= 0 AND \"Odd Name\" IS NULL") }],
+        };
+        for statistics_page_id in [None, Some(10)] {
+            table.statistics_page_id = statistics_page_id;
+            let bytes = encode_table(&table);
+            assert_eq!(decode_table(&bytes), Some(table.clone()));
+            assert_eq!(decode_table(&bytes[..bytes.len() - 1]), None);
+        }
+        // a NOT NULL column the table doesn't have, right after the NOT NULL count
+        let mut bytes = encode_table(&table);
+        let start = encode_table(&TableInfo { constraints: Constraints::default(), ..table.clone() }).len();
+        bytes[start + 2..start + 4].copy_from_slice(&5u16.to_le_bytes());
+        assert_eq!(decode_table(&bytes), None);
+    }
+
+    #[test]
+    fn test_statistics_encoding_round_trips() {
+        let columns = users_columns();
+        let entries = [
+            StatisticsEntry::Table { row_count: 1 << 40, page_count: 12 },
+            StatisticsEntry::Column(0, ColumnStatistics { null_fraction: 0.0, distinct_count: 3.0, histogram: vec![Value::Int(-4), Value::Int(9)] }),
+            StatisticsEntry::Column(
+                1,
+                ColumnStatistics { null_fraction: 0.25, distinct_count: 1e9, histogram: vec![Value::Varchar("a".into()), Value::Varchar("zz".into())] },
+            ),
+            StatisticsEntry::Column(3, ColumnStatistics { null_fraction: 0.5, distinct_count: 2.0, histogram: vec![Value::BigInt(1 << 33); 3] }),
+            StatisticsEntry::Column(4, ColumnStatistics { null_fraction: 1.0, distinct_count: 0.0, histogram: Vec::new() }),
+            StatisticsEntry::Column(4, ColumnStatistics { null_fraction: 0.0, distinct_count: 2.0, histogram: vec![Value::Bool(false), Value::Bool(true)] }),
+        ];
+        for entry in entries {
+            let bytes = encode_statistics(&entry);
+            assert_eq!(decode_statistics(&bytes, &columns), Some(entry));
+            assert_eq!(decode_statistics(&bytes[..bytes.len() - 1], &columns), None);
+            assert_eq!(decode_statistics(&[bytes.as_slice(), &[0]].concat(), &columns), None);
+        }
+        // a column the table doesn't have
+        let bytes = encode_statistics(&StatisticsEntry::Column(5, ColumnStatistics::new(1.0)));
+        assert_eq!(decode_statistics(&bytes, &columns), None);
+    }
+
+    #[test]
+    fn test_statistics_survive_restart() {
+        let dir = TempDir::new().unwrap();
+        let column = |distinct_count: f64| ColumnStatistics { null_fraction: 0.1, distinct_count, histogram: vec![Value::Int(1), Value::Int(5)] };
+        let statistics = TableStatistics { row_count: 500, page_count: 4, columns: vec![column(5.0)] };
+        {
+            let (mut catalog, buffer_pool) = open(&dir);
+            let mut txn = Transaction::new(1);
+            catalog.create_table(&mut txn, "users", users_columns()).unwrap();
+            catalog.create_table(&mut txn, "numbers", vec![Column::new("n", DataType::Int)]).unwrap();
+            assert_eq!(catalog.statistics("numbers").unwrap(), None);
+            assert_eq!(catalog.statistics("missing").unwrap(), None);
+
+            // analyzing again replaces what was there
+            let first = TableStatistics { row_count: 10, page_count: 1, columns: vec![column(2.0)] };
+            catalog.set_statistics(&mut txn, "numbers", &first).unwrap();
+            catalog.set_statistics(&mut txn, "numbers", &statistics).unwrap();
+
+            let result = catalog.set_statistics(&mut txn, "users", &statistics);
+            assert!(matches!(result, Err(CatalogError::CorruptStatistics(name)) if name == "users"));
+            let result = catalog.set_statistics(&mut txn, "missing", &statistics);
+            assert!(matches!(result, Err(CatalogError::TableNotFound(name)) if name == "missing"));
+            buffer_pool.lock().unwrap().flush_all().unwrap();
+        }
+
+        let (catalog, buffer_pool) = open(&dir);
+        assert_eq!(catalog.statistics("numbers").unwrap(), Some(statistics));
+        assert_eq!(catalog.statistics("users").unwrap(), None);
+        let page_id = catalog.table("numbers").unwrap().unwrap().statistics_page_id.unwrap();
+        assert_eq!(buffer_pool.lock().unwrap().get_page(page_id).unwrap().page_type(), PageType::Catalog);
+    }
+
+    #[test]
+    fn test_create_index() {
+        let dir = TempDir::new().unwrap();
+        let created = {
+            let (mut catalog, buffer_pool) = open(&dir);
+            let mut txn = Transaction::new(1);
+            catalog.create_table(&mut txn, "users", users_columns()).unwrap();
+            catalog.create_table(&mut txn, "orders", vec![Column::new("id", DataType::BigInt)]).unwrap();
+
+            let primary_key = catalog.create_index(&mut txn, "users", "users_pkey", vec![IndexColumn::new(0)], IndexKind::PrimaryKey).unwrap();
+            catalog.create_index(&mut txn, "users", "users_name_key", vec![IndexColumn::new(1)], IndexKind::Unique).unwrap();
+            assert!(primary_key.open(buffer_pool.clone()).is_unique());
+
+            let result = catalog.create_index(&mut txn, "orders", "users_pkey", vec![IndexColumn::new(0)], IndexKind::Unique);
+            assert!(matches!(result, Err(CatalogError::IndexExists(name)) if name == "users_pkey"));
+            let result = catalog.create_index(&mut txn, "users", "users_id_pkey", vec![IndexColumn::new(0)], IndexKind::PrimaryKey);
+            assert!(matches!(result, Err(CatalogError::MultiplePrimaryKeys(name)) if name == "users"));
+            let result = catalog.create_index(&mut txn, "missing", "missing_pkey", vec![IndexColumn::new(0)], IndexKind::PrimaryKey);
+            assert!(matches!(result, Err(CatalogError::TableNotFound(name)) if name == "missing"));
+
+            buffer_pool.lock().unwrap().flush_all().unwrap();
+            catalog.table("users").unwrap().unwrap()
+        };
+
+        let (catalog, _buffer_pool) = open(&dir);
+        let users = catalog.table("users").unwrap().unwrap();
+        assert_eq!(users, created);
+        assert_eq!(users.indexes.iter().map(|index| index.name.as_str()).collect::<Vec<_>>(), ["users_pkey", "users_name_key"]);
+        assert_eq!(users.primary_key().unwrap().columns, vec![IndexColumn::new(0)]);
+        assert!(catalog.table("orders").unwrap().unwrap().indexes.is_empty());
+    }
+}
@@ -0,0 +1,278 @@
+use crate::storage::{BufferPool, BufferPoolError};
+use crate::wal::{INVALID_LSN, LogManager, LogRecordBody, Lsn, WalError};
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+#[derive(Debug)]
+pub enum CheckpointError {
+    WalError(WalError),
+    BufferPoolError(BufferPoolError),
+}
+
+impl std::fmt::Display for CheckpointError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CheckpointError::WalError(error) => write!(f, "WAL error: {error}"),
+            CheckpointError::BufferPoolError(error) => write!(f, "Buffer pool error: {error}"),
+        }
+    }
+}
+
+impl std::error::Error for CheckpointError {}
+
+impl From<WalError> for CheckpointError {
+    fn from(error: WalError) -> Self {
+        CheckpointError::WalError(error)
+    }
+}
+
+impl From<BufferPoolError> for CheckpointError {
+    fn from(error: BufferPoolError) -> Self {
+        CheckpointError::BufferPoolError(error)
+    }
+}
+
+/// Takes fuzzy checkpoints, which keep recovery time and log size bounded.
+///
+/// A checkpoint logs the active transaction table and the buffer pool's dirty
+/// page table without waiting for any page to be written, so transactions keep
+/// running while it's taken. Recovery starts its analysis from the last
+/// checkpoint instead of the beginning of the log. Once the checkpoint is
+/// logged the dirty pages are written back, after which the log before the
+/// checkpoint is only needed to roll back transactions that were still running,
+/// and everything older than that is truncated away.
+///
+/// # Examples
+///
+/// ```
+/// use gondor_rdbms::checkpoint::CheckpointManager;
+/// use gondor_rdbms::storage::{BufferPool, DiskManager, HeapFile};
+/// use gondor_rdbms::transaction::TransactionManager;
+/// use gondor_rdbms::wal::LogManager;
+/// use std::sync::{Arc, Mutex};
+///
+/// let dir = tempfile::tempdir().unwrap();
+/// let disk_manager = DiskManager::open(dir.path().join("example.db")).unwrap();
+/// let log_manager = Arc::new(Mutex::new(LogManager::open(dir.path().join("example.wal")).unwrap()));
+/// let mut buffer_pool = BufferPool::new(disk_manager);
+/// buffer_pool.set_log_manager(log_manager.clone());
+/// let buffer_pool = Arc::new(Mutex::new(buffer_pool));
+///
+/// let mut heap = HeapFile::create(buffer_pool.clone()).unwrap();
+/// heap.set_log_manager(log_manager.clone());
+/// let mut transaction_manager = TransactionManager::new(buffer_pool.clone(), log_manager.clone());
+/// let mut txn = transaction_manager.begin();
+/// heap.insert(&mut txn, b"Hello, world!").unwrap();
+/// transaction_manager.commit(txn).unwrap();
+///
+/// // nothing is running, so only the checkpoint record itself is left in the log
+/// let checkpoint_manager = CheckpointManager::new(buffer_pool.clone(), log_manager.clone());
+/// let lsn = checkpoint_manager.checkpoint().unwrap();
+/// let records = log_manager.lock().unwrap().read_records().unwrap();
+/// assert_eq!(records.len(), 1);
+/// assert_eq!(records[0].lsn, lsn);
+/// ```
+#[derive(Clone)]
+pub struct CheckpointManager {
+    buffer_pool: Arc<Mutex<BufferPool>>,
+    log_manager: Arc<Mutex<LogManager>>,
+}
+
+impl CheckpointManager {
+    pub fn new(buffer_pool: Arc<Mutex<BufferPool>>, log_manager: Arc<Mutex<LogManager>>) -> Self {
+        Self { buffer_pool, log_manager }
+    }
+
+    /// Takes a checkpoint, writes back the dirty pages and truncates the log.
+    /// Returns the LSN of the checkpoint record.
+    pub fn checkpoint(&self) -> Result<Lsn, CheckpointError> {
+        let (checkpoint_lsn, truncate_before) = {
+            // holding the pool stops pages changing (and their changes being logged) in between the two tables
+            let mut buffer_pool = self.buffer_pool.lock().unwrap();
+            // pages written out before now aren't in the dirty page table, so they have to be durable
+            buffer_pool.sync()?;
+            let dirty_pages = buffer_pool.dirty_page_table();
+
+            let mut log_manager = self.log_manager.lock().unwrap();
+            let active_txns = log_manager.active_txns();
+            let oldest_txn_lsn = active_txns.iter().map(|txn| txn.first_lsn).min();
+            let body = LogRecordBody::Checkpoint { max_txn_id: log_manager.max_txn_id(), active_txns, dirty_pages };
+            let lsn = log_manager.append(0, INVALID_LSN, body);
+            log_manager.flush_to_lsn(lsn)?;
+            (lsn, oldest_txn_lsn.map_or(lsn, |oldest_txn_lsn| oldest_txn_lsn.min(lsn)))
+        };
+
+        // after this every change logged before the checkpoint is on disk, so redo never needs to go back further
+        self.buffer_pool.lock().unwrap().flush_all()?;
+        // only undo still needs older records, for the transactions that were running
+        self.log_manager.lock().unwrap().truncate(truncate_before)?;
+        Ok(checkpoint_lsn)
+    }
+
+    /// Takes a checkpoint every `interval` on a background thread, until the
+    /// returned worker is dropped.
+    pub fn start(&self, interval: Duration) -> CheckpointWorker {
+        let checkpoint_manager = self.clone();
+        let stop = Arc::new((Mutex::new(false), Condvar::new()));
+        let thread_stop = stop.clone();
+        let thread = std::thread::spawn(move || {
+            let (stopped, condvar) = &*thread_stop;
+            let mut stopped = stopped.lock().unwrap();
+            loop {
+                stopped = condvar.wait_timeout_while(stopped, interval, |stopped| !*stopped).unwrap().0;
+                if *stopped {
+                    break;
+                }
+                // a failed checkpoint only means recovery has more log to read, try again next time round
+                let _ = checkpoint_manager.checkpoint();
+            }
+        });
+
+        CheckpointWorker { stop, thread: Some(thread) }
+    }
+}
+
+/// Background thread taking periodic checkpoints, see `CheckpointManager::start`.
+/// Dropping it stops the thread, waiting for a checkpoint in progress to finish.
+pub struct CheckpointWorker {
+    /// set to stop the thread, with the condvar it sleeps on between checkpoints
+    stop: Arc<(Mutex<bool>, Condvar)>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl Drop for CheckpointWorker {
+    fn drop(&mut self) {
+        let (stopped, condvar) = &*self.stop;
+        *stopped.lock().unwrap() = true;
+        condvar.notify_all();
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::recovery;
+    use crate::storage::{DiskManager, HeapError, HeapFile, PageError};
+    use crate::transaction::TransactionManager;
+    use tempfile::TempDir;
+
+    /// buffer pool and log over the database in `dir`, as if the process had just started
+    fn open(dir: &TempDir) -> (Arc<Mutex<BufferPool>>, Arc<Mutex<LogManager>>) {
+        let disk_manager = DiskManager::open(dir.path().join("test.db")).unwrap();
+        let log_manager = Arc::new(Mutex::new(LogManager::open(dir.path().join("test.wal")).unwrap()));
+        let mut buffer_pool = BufferPool::new(disk_manager);
+        buffer_pool.set_log_manager(log_manager.clone());
+        (Arc::new(Mutex::new(buffer_pool)), log_manager)
+    }
+
+    fn logged_heap(buffer_pool: &Arc<Mutex<BufferPool>>, log_manager: &Arc<Mutex<LogManager>>, root_page_id: Option<u32>) -> HeapFile {
+        let mut heap = match root_page_id {
+            Some(root_page_id) => HeapFile::open(buffer_pool.clone(), root_page_id).unwrap(),
+            None => HeapFile::create(buffer_pool.clone()).unwrap(),
+        };
+        heap.set_log_manager(log_manager.clone());
+        heap
+    }
+
+    #[test]
+    fn test_checkpoint_keeps_log_of_running_transactions() {
+        let dir = TempDir::new().unwrap();
+        let (buffer_pool, log_manager) = open(&dir);
+        let mut heap = logged_heap(&buffer_pool, &log_manager, None);
+        let mut transaction_manager = TransactionManager::new(buffer_pool.clone(), log_manager.clone());
+        let checkpoint_manager = CheckpointManager::new(buffer_pool.clone(), log_manager.clone());
+
+        let mut finished = transaction_manager.begin();
+        heap.insert(&mut finished, b"finished").unwrap();
+        transaction_manager.commit(finished).unwrap();
+        let mut running = transaction_manager.begin();
+        let running_begin = running.prev_lsn();
+        heap.insert(&mut running, b"running").unwrap();
+
+        let checkpoint_lsn = checkpoint_manager.checkpoint().unwrap();
+        assert_eq!(buffer_pool.lock().unwrap().dirty_page_count(), 0);
+        let records = log_manager.lock().unwrap().read_records().unwrap();
+        assert_eq!(records[0].lsn, running_begin);
+        let LogRecordBody::Checkpoint { active_txns, .. } = &records.last().unwrap().body else {
+            panic!("expected the checkpoint record last");
+        };
+        assert_eq!(active_txns.len(), 1);
+        assert_eq!((active_txns[0].txn_id, active_txns[0].first_lsn), (running.id(), running_begin));
+
+        // once it finishes the next checkpoint can drop its records too
+        transaction_manager.commit(running).unwrap();
+        let next_lsn = checkpoint_manager.checkpoint().unwrap();
+        assert!(next_lsn > checkpoint_lsn);
+        let records = log_manager.lock().unwrap().read_records().unwrap();
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0].lsn, next_lsn);
+    }
+
+    #[test]
+    fn test_recovery_after_checkpoint() {
+        let dir = TempDir::new().unwrap();
+        let (before, after, lost, root_page_id, loser) = {
+            let (buffer_pool, log_manager) = open(&dir);
+            let mut heap = logged_heap(&buffer_pool, &log_manager, None);
+            let mut transaction_manager = TransactionManager::new(buffer_pool.clone(), log_manager.clone());
+
+            let mut txn = transaction_manager.begin();
+            let before = heap.insert(&mut txn, b"before the checkpoint").unwrap();
+            transaction_manager.commit(txn).unwrap();
+            // running across the checkpoint, and still running at the crash
+            let mut loser = transaction_manager.begin();
+            let lost = heap.insert(&mut loser, b"never committed").unwrap();
+
+            CheckpointManager::new(buffer_pool.clone(), log_manager.clone()).checkpoint().unwrap();
+
+            let mut txn = transaction_manager.begin();
+            let after = heap.insert(&mut txn, b"after the checkpoint").unwrap();
+            transaction_manager.commit(txn).unwrap();
+            // crash: nothing written since the checkpoint reaches the database file
+            (before, after, lost, heap.root_page_id(), loser.id())
+        };
+
+        let (buffer_pool, log_manager) = open(&dir);
+        let report = recovery::recover(&buffer_pool, &log_manager).unwrap();
+        assert_eq!(report.rolled_back, vec![loser]);
+
+        let heap = logged_heap(&buffer_pool, &log_manager, Some(root_page_id));
+        assert_eq!(heap.get(before).unwrap(), b"before the checkpoint");
+        assert_eq!(heap.get(after).unwrap(), b"after the checkpoint");
+        assert!(matches!(heap.get(lost), Err(HeapError::PageError(PageError::TupleNotFound))));
+
+        // ids carry on after the highest one in the log
+        let mut transaction_manager = TransactionManager::new(buffer_pool.clone(), log_manager.clone());
+        assert_eq!(transaction_manager.begin().id(), loser + 2);
+    }
+
+    #[test]
+    fn test_worker_checkpoints_periodically() {
+        let dir = TempDir::new().unwrap();
+        let (buffer_pool, log_manager) = open(&dir);
+        let mut heap = logged_heap(&buffer_pool, &log_manager, None);
+        let mut transaction_manager = TransactionManager::new(buffer_pool.clone(), log_manager.clone());
+        let mut txn = transaction_manager.begin();
+        heap.insert(&mut txn, b"Hello, world!").unwrap();
+        transaction_manager.commit(txn).unwrap();
+
+        let worker = CheckpointManager::new(buffer_pool.clone(), log_manager.clone()).start(Duration::from_millis(10));
+        let is_checkpointed = || {
+            let records = log_manager.lock().unwrap().read_records().unwrap();
+            records.len() == 1 && matches!(records[0].body, LogRecordBody::Checkpoint { .. })
+        };
+        for _ in 0..500 {
+            if is_checkpointed() {
+                break;
+            }
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        drop(worker);
+        assert!(is_checkpointed());
+        assert_eq!(buffer_pool.lock().unwrap().dirty_page_count(), 0);
+    }
+}
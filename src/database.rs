@@ -0,0 +1,1516 @@
+use crate::catalog::{Catalog, CatalogError, TableInfo};
+use crate::checkpoint::{CheckpointError, CheckpointManager, CheckpointWorker};
+use crate::execution::{AccessLog, ExecutionError, Executor, PreparedStatement, QueryResult};
+use crate::recovery::{self, RecoveryError};
+use crate::sql::{self, ParseError};
+use crate::storage::{BufferPool, DiskManager, DiskManagerError, FreeSpaceMap, SyncMode};
+use crate::transaction::{IsolationLevel, Transaction, TransactionManager};
+use crate::types::{DataType, Value};
+use crate::vacuum::{self, VacuumStats, VacuumWorker};
+use crate::wal::{GroupCommit, LogManager, Lsn, WalError};
+use std::collections::{HashMap, HashSet};
+use std::ffi::OsString;
+use std::io::{BufReader, Read};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Condvar, Mutex, MutexGuard, Weak};
+use std::time::Duration;
+
+/// how often the background checkpoint runs
+const CHECKPOINT_INTERVAL: Duration = Duration::from_secs(30);
+
+/// how many prepared statements the plan cache keeps
+const PLAN_CACHE_CAPACITY: usize = 128;
+
+#[derive(Debug)]
+pub enum DatabaseError {
+    ParseError(ParseError),
+    ExecutionError(ExecutionError),
+    CatalogError(CatalogError),
+    DiskManagerError(DiskManagerError),
+    WalError(WalError),
+    RecoveryError(RecoveryError),
+    CheckpointError(CheckpointError),
+    /// a statement in a transaction block after one failed, which only `ROLLBACK`
+    /// (or `ROLLBACK TO` a savepoint from before the failure) gets past
+    TransactionAborted,
+    /// `BEGIN` in a transaction block
+    TransactionInProgress,
+    /// a savepoint statement, named here, outside of a transaction block
+    NoTransaction(&'static str),
+    /// `ROLLBACK TO` or `RELEASE` a savepoint the transaction doesn't have
+    UnknownSavepoint(String),
+    /// `SET TRANSACTION ISOLATION LEVEL` after a statement ran in the block
+    IsolationLevelAfterQuery,
+    /// a statement in a `SERIALIZABLE` block after another transaction changed
+    /// a table it read, which fails the block: retrying it from the start may succeed
+    SerializationFailure,
+    /// a statement that would wait for a transaction block that's waiting for
+    /// this connection's own block, which fails it the same way
+    Deadlock,
+    /// a statement, named here, that can't run in a transaction block
+    InTransactionBlock(&'static str),
+}
+
+impl std::fmt::Display for DatabaseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DatabaseError::ParseError(error) => write!(f, "Parse error: {error}"),
+            DatabaseError::ExecutionError(error) => write!(f, "{error}"),
+            DatabaseError::CatalogError(error) => write!(f, "Catalog error: {error}"),
+            DatabaseError::DiskManagerError(error) => write!(f, "Disk manager error: {error}"),
+            DatabaseError::WalError(error) => write!(f, "WAL error: {error}"),
+            DatabaseError::RecoveryError(error) => write!(f, "Recovery error: {error}"),
+            DatabaseError::CheckpointError(error) => write!(f, "Checkpoint error: {error}"),
+            DatabaseError::TransactionAborted => write!(f, "Current transaction is aborted, commands ignored until end of transaction block"),
+            DatabaseError::TransactionInProgress => write!(f, "There is already a transaction in progress"),
+            DatabaseError::NoTransaction(statement) => write!(f, "{statement} can only be used in transaction blocks"),
+            DatabaseError::UnknownSavepoint(name) => write!(f, "Savepoint {name} does not exist"),
+            DatabaseError::IsolationLevelAfterQuery => write!(f, "SET TRANSACTION ISOLATION LEVEL must be called before any query"),
+            DatabaseError::SerializationFailure => write!(f, "Could not serialize access due to read/write dependencies among transactions"),
+            DatabaseError::Deadlock => write!(f, "Deadlock detected"),
+            DatabaseError::InTransactionBlock(statement) => write!(f, "{statement} cannot run inside a transaction block"),
+        }
+    }
+}
+
+impl std::error::Error for DatabaseError {}
+
+impl From<ParseError> for DatabaseError {
+    fn from(error: ParseError) -> Self {
+        DatabaseError::ParseError(error)
+    }
+}
+
+impl From<ExecutionError> for DatabaseError {
+    fn from(error: ExecutionError) -> Self {
+        DatabaseError::ExecutionError(error)
+    }
+}
+
+impl From<CatalogError> for DatabaseError {
+    fn from(error: CatalogError) -> Self {
+        DatabaseError::CatalogError(error)
+    }
+}
+
+impl From<DiskManagerError> for DatabaseError {
+    fn from(error: DiskManagerError) -> Self {
+        DatabaseError::DiskManagerError(error)
+    }
+}
+
+impl From<WalError> for DatabaseError {
+    fn from(error: WalError) -> Self {
+        DatabaseError::WalError(error)
+    }
+}
+
+impl From<RecoveryError> for DatabaseError {
+    fn from(error: RecoveryError) -> Self {
+        DatabaseError::RecoveryError(error)
+    }
+}
+
+impl From<CheckpointError> for DatabaseError {
+    fn from(error: CheckpointError) -> Self {
+        DatabaseError::CheckpointError(error)
+    }
+}
+
+/// Settings for `Database::open_with_options`.
+///
+/// # Examples
+///
+/// ```
+/// use gondor_rdbms::{Database, DatabaseOptions};
+/// use std::time::Duration;
+///
+/// let dir = tempfile::tempdir().unwrap();
+/// let options = DatabaseOptions {
+///     page_size: Some(8192),
+///     group_commit_window: Duration::from_millis(2),
+///     ..DatabaseOptions::default()
+/// };
+/// let database = Database::open_with_options(dir.path().join("shire.db"), options).unwrap();
+/// assert_eq!(database.page_size(), 8192);
+/// ```
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct DatabaseOptions {
+    /// size of the pages to create the database with, one of
+    /// `storage::SUPPORTED_PAGE_SIZES`. `None` uses whatever an existing
+    /// database was created with, or `storage::DEFAULT_PAGE_SIZE` for a new one
+    pub page_size: Option<usize>,
+    /// how long a commit waits for other commits to share its log flush, see
+    /// `wal::GroupCommit`. Zero by default; a few milliseconds trades latency
+    /// for throughput when many connections commit at once
+    pub group_commit_window: Duration,
+    /// how the database file and log are forced to disk. `SyncMode::Off` gives
+    /// up durability, e.g. for a bulk load that can be redone after a crash
+    pub sync_mode: SyncMode,
+    /// how often a background thread vacuums every table, as `VACUUM` does.
+    /// `None` by default, which leaves it to `VACUUM` and `Database::vacuum`
+    pub vacuum_interval: Option<Duration>,
+}
+
+/// everything the connections to one database share
+struct Shared {
+    buffer_pool: Arc<Mutex<BufferPool>>,
+    log_manager: Arc<Mutex<LogManager>>,
+    /// held for the whole of each statement, so statements run one at a time
+    state: Mutex<State>,
+    /// signalled when a transaction block ends, for connections waiting on one that held the database
+    block_ended: Condvar,
+    /// id handed to the next connection
+    next_connection_id: AtomicU64,
+    /// commits wait for their commit record to be flushed here, after letting go of `state`
+    group_commit: Arc<GroupCommit>,
+    checkpoint_manager: CheckpointManager,
+    /// takes a checkpoint every `CHECKPOINT_INTERVAL`, stopped before the last one on shutdown
+    checkpoint_worker: Option<CheckpointWorker>,
+    /// the room vacuum found in tables, which inserts fill before growing them
+    free_space_map: Arc<Mutex<FreeSpaceMap>>,
+    /// vacuums every `DatabaseOptions::vacuum_interval`, if set
+    vacuum_worker: Option<VacuumWorker>,
+}
+
+struct State {
+    catalog: Catalog,
+    transaction_manager: TransactionManager,
+    plans: PlanCache,
+    /// the transaction blocks connections have open, by connection id
+    blocks: HashMap<u64, TransactionBlock>,
+    /// the connections waiting for transaction blocks to end, with the table
+    /// each one's statement writes, to tell when waiting would deadlock
+    waiting: HashMap<u64, Option<String>>,
+}
+
+impl State {
+    /// the connections other than `connection` whose transaction blocks a
+    /// statement on it has to wait for, if it writes the table `writes`
+    fn blockers(&self, connection: u64, writes: Option<&str>) -> HashSet<u64> {
+        let holds = |block: &TransactionBlock| {
+            block.holding || (block.txn.isolation_level() == IsolationLevel::RepeatableRead && writes.is_some_and(|table| block.reads.contains(table)))
+        };
+        self.blocks.iter().filter(|&(&id, block)| id != connection && holds(block)).map(|(&id, _)| id).collect()
+    }
+
+    /// whether one of `blockers` is waiting, however indirectly, for `connection`
+    fn waits_on(&self, connection: u64, blockers: &HashSet<u64>) -> bool {
+        let mut seen = HashSet::new();
+        let mut pending: Vec<u64> = blockers.iter().copied().collect();
+        while let Some(id) = pending.pop() {
+            if id == connection {
+                return true;
+            }
+            if seen.insert(id)
+                && let Some(writes) = self.waiting.get(&id)
+            {
+                pending.extend(self.blockers(id, writes.as_deref()));
+            }
+        }
+        false
+    }
+}
+
+/// A transaction begun by `BEGIN`, that the statements of its connection run in
+/// until `COMMIT` or `ROLLBACK`.
+///
+/// Pages aren't versioned, so a transaction sees whatever the others left
+/// behind them, and keeps the others from seeing what it changed only by
+/// holding the database: statements on other connections wait until it
+/// ends. A block holds it once it has written something, and lets other
+/// connections run between its statements until then. One at `REPEATABLE
+/// READ` also notes the tables it reads, and statements elsewhere that would
+/// write one of them wait for it to end, so none of what it read changes
+/// under it. Reading them, or anything else, doesn't wait. Two blocks that
+/// would each end up waiting for the other are a deadlock, which fails the
+/// one that would have waited last.
+///
+/// So does one at `SERIALIZABLE`, which also notes what it reads: the ranges
+/// of keys it looked up through an index, and the whole table for anything
+/// else (see `AccessLog`). A transaction that writes a row in one of them
+/// before the block holds the database has to come after it in any serial
+/// order, while the block's next statement would see the change and have to
+/// come after that, so the block fails instead. Writes to rows it didn't read,
+/// even in the same table, don't. Once it holds the database nothing can
+/// change under it, and all of it happens as if at the statement it started
+/// holding with (or at its last one, if it only reads).
+struct TransactionBlock {
+    txn: Transaction,
+    /// by name, oldest first, with the transaction's last LSN when each was set
+    savepoints: Vec<(String, Lsn)>,
+    /// whether a statement failed since the block began (or was last rolled back to a savepoint)
+    failed: bool,
+    /// whether a statement has run in it, which fixes its isolation level
+    started: bool,
+    /// whether statements on other connections wait for it to end
+    holding: bool,
+    /// the tables a `REPEATABLE READ` block has read
+    reads: HashSet<String>,
+    /// what a `SERIALIZABLE` block has read
+    read_log: AccessLog,
+    /// whether another transaction wrote something in `read_log` since
+    read_write_conflict: bool,
+}
+
+impl TransactionBlock {
+    fn new(txn: Transaction) -> Self {
+        Self { txn, savepoints: Vec::new(), failed: false, started: false, holding: false, reads: HashSet::new(), read_log: AccessLog::default(), read_write_conflict: false }
+    }
+}
+
+/// The tables a statement reads and writes the rows of, as far as
+/// `REPEATABLE READ` blocks keep track.
+#[derive(Default)]
+struct Access<'a> {
+    reads: Vec<&'a str>,
+    writes: Option<&'a str>,
+}
+
+impl<'a> Access<'a> {
+    fn of(statement: &'a sql::Statement) -> Self {
+        match statement {
+            sql::Statement::Select(select) | sql::Statement::Explain(sql::Explain { analyze: true, query: select }) => {
+                Self { reads: select.from.iter().map(|table| table.name.as_str()).collect(), writes: None }
+            }
+            sql::Statement::Insert(insert) => Self { reads: Vec::new(), writes: Some(&insert.table) },
+            sql::Statement::Update(sql::Update { table, .. }) | sql::Statement::Delete(sql::Delete { table, .. }) => Self { reads: vec![table], writes: Some(table) },
+            sql::Statement::Copy(copy) if copy.direction == sql::CopyDirection::From => Self { reads: Vec::new(), writes: Some(&copy.table) },
+            sql::Statement::Copy(copy) => Self { reads: vec![&copy.table], writes: None },
+            sql::Statement::CreateIndex(index) => Self { reads: vec![&index.table], writes: None },
+            _ => Self::default(),
+        }
+    }
+}
+
+impl Shared {
+    /// `state`, once no transaction block on another connection than
+    /// `connection` holds the database, or has read the table `writes` at
+    /// `REPEATABLE READ`. Fails with `Deadlock`, and fails the connection's
+    /// block, if one of those is waiting for it in turn.
+    fn lock_state(&self, connection: u64, writes: Option<&str>) -> Result<MutexGuard<'_, State>, DatabaseError> {
+        let mut state = self.state.lock().unwrap();
+        loop {
+            let blockers = state.blockers(connection, writes);
+            if blockers.is_empty() {
+                state.waiting.remove(&connection);
+                return Ok(state);
+            }
+            if state.waits_on(connection, &blockers) {
+                state.waiting.remove(&connection);
+                if let Some(block) = state.blocks.get_mut(&connection) {
+                    block.failed = true;
+                }
+                return Err(DatabaseError::Deadlock);
+            }
+            state.waiting.insert(connection, writes.map(str::to_string));
+            state = self.block_ended.wait(state).unwrap();
+        }
+    }
+
+    /// Runs `run`, which reads and writes the tables of `access`, for
+    /// `connection` in its transaction block, or else in a transaction of its
+    /// own, which commits if it succeeds and rolls back if it fails. In a
+    /// block, a failure rolls back just what `run` did and leaves the block failed.
+    fn run<T>(&self, connection: u64, access: Access, run: impl FnOnce(&mut Executor, &mut Transaction, &mut PlanCache) -> Result<T, DatabaseError>) -> Result<T, DatabaseError> {
+        let (result, commit_lsn) = {
+            let mut state = self.lock_state(connection, access.writes)?;
+            let State { catalog, transaction_manager, plans, blocks, .. } = &mut *state;
+            let mut executor = Executor::new(self.buffer_pool.clone(), catalog);
+            executor.set_log_manager(self.log_manager.clone());
+            executor.set_free_space_map(self.free_space_map.clone());
+            let access_log = Arc::new(Mutex::new(AccessLog::default()));
+            executor.set_access_log(access_log.clone());
+
+            if let Some(block) = blocks.get_mut(&connection) {
+                if block.failed {
+                    return Err(DatabaseError::TransactionAborted);
+                }
+                if block.read_write_conflict {
+                    block.failed = true;
+                    return Err(DatabaseError::SerializationFailure);
+                }
+                block.started = true;
+                if block.txn.isolation_level() == IsolationLevel::RepeatableRead {
+                    block.reads.extend(access.reads.iter().map(|table| table.to_string()));
+                }
+                let start = block.txn.prev_lsn();
+                let result = run(&mut executor, &mut block.txn, plans);
+                let accessed = access_log.lock().unwrap();
+                if block.txn.isolation_level() == IsolationLevel::Serializable {
+                    block.read_log.add_reads(&accessed);
+                }
+                if result.is_err() {
+                    transaction_manager.rollback_to(&mut block.txn, start)?;
+                    block.failed = true;
+                }
+                // anything it logged is a change no one else may see (or make
+                // on top of) until it commits
+                let wrote = block.txn.prev_lsn() != start;
+                block.holding |= wrote;
+                if wrote && result.is_ok() {
+                    read_write_conflicts(blocks, connection, &accessed);
+                }
+                return result;
+            }
+
+            let mut txn = transaction_manager.begin();
+            let start = txn.prev_lsn();
+            match run(&mut executor, &mut txn, plans) {
+                Ok(result) => {
+                    if txn.prev_lsn() != start {
+                        read_write_conflicts(blocks, connection, &access_log.lock().unwrap());
+                    }
+                    (result, transaction_manager.append_commit(txn))
+                }
+                Err(error) => {
+                    transaction_manager.abort(txn)?;
+                    return Err(error);
+                }
+            }
+        };
+
+        // the next statement can start while this one waits for its commit to be flushed,
+        // possibly together with others. it may see this one's changes before they're
+        // durable, but its own commit record comes later in the log, so it can't outlive them
+        self.group_commit.wait_for(commit_lsn)?;
+        Ok(result)
+    }
+
+    /// Vacuums every table, see `Database::vacuum`.
+    fn vacuum(&self) -> Result<VacuumStats, DatabaseError> {
+        // no connection has id 0, so this waits for any block holding the database
+        let mut state = self.lock_state(0, None)?;
+        let mut executor = Executor::new(self.buffer_pool.clone(), &mut state.catalog);
+        executor.set_free_space_map(self.free_space_map.clone());
+        Ok(executor.vacuum(None)?)
+    }
+
+    /// fails if `statement` can't run where `connection` is, as `VACUUM` can't in a transaction block
+    fn check_block(&self, connection: u64, statement: &sql::Statement) -> Result<(), DatabaseError> {
+        if matches!(statement, sql::Statement::Vacuum(_)) && self.state.lock().unwrap().blocks.contains_key(&connection) {
+            return Err(DatabaseError::InTransactionBlock("VACUUM"));
+        }
+        Ok(())
+    }
+
+    /// Runs the transaction statement `statement` for `connection`.
+    fn control(&self, connection: u64, statement: &sql::TransactionStatement) -> Result<(), DatabaseError> {
+        // none of these touch the rows of a table, so they don't wait for other
+        // blocks, which might be waiting for this one to end
+        let mut state = self.state.lock().unwrap();
+        let State { transaction_manager, plans, blocks, .. } = &mut *state;
+        let Some(open) = blocks.get_mut(&connection) else {
+            return match statement {
+                sql::TransactionStatement::Begin(isolation_level) => {
+                    blocks.insert(connection, TransactionBlock::new(transaction_manager.begin_with(isolation_level.unwrap_or_default())));
+                    Ok(())
+                }
+                // like PostgreSQL, ending a block that isn't there does nothing
+                sql::TransactionStatement::Commit | sql::TransactionStatement::Rollback => Ok(()),
+                sql::TransactionStatement::SetIsolationLevel(_) => Err(DatabaseError::NoTransaction("SET TRANSACTION")),
+                sql::TransactionStatement::Savepoint(_) => Err(DatabaseError::NoTransaction("SAVEPOINT")),
+                sql::TransactionStatement::RollbackTo(_) => Err(DatabaseError::NoTransaction("ROLLBACK TO SAVEPOINT")),
+                sql::TransactionStatement::Release(_) => Err(DatabaseError::NoTransaction("RELEASE SAVEPOINT")),
+            };
+        };
+
+        let savepoint = |name: &str| open.savepoints.iter().rposition(|(savepoint, _)| savepoint == name).ok_or_else(|| DatabaseError::UnknownSavepoint(name.to_string()));
+        match statement {
+            sql::TransactionStatement::Begin(_) => Err(DatabaseError::TransactionInProgress),
+            sql::TransactionStatement::SetIsolationLevel(isolation_level) => {
+                if open.failed {
+                    return Err(DatabaseError::TransactionAborted);
+                }
+                if open.started {
+                    return Err(DatabaseError::IsolationLevelAfterQuery);
+                }
+                open.txn.set_isolation_level(*isolation_level);
+                Ok(())
+            }
+            sql::TransactionStatement::Commit => {
+                let open = blocks.remove(&connection).unwrap();
+                self.block_ended.notify_all();
+                if open.failed {
+                    // there's nothing left to commit but the statements before the failure, which mustn't be on their own
+                    transaction_manager.abort(open.txn)?;
+                    plans.invalidate();
+                    return Err(DatabaseError::TransactionAborted);
+                }
+                let commit_lsn = transaction_manager.append_commit(open.txn);
+                drop(state);
+                self.group_commit.wait_for(commit_lsn)?;
+                Ok(())
+            }
+            sql::TransactionStatement::Rollback => {
+                let open = blocks.remove(&connection).unwrap();
+                self.block_ended.notify_all();
+                transaction_manager.abort(open.txn)?;
+                // whatever tables and indexes it made are gone again
+                plans.invalidate();
+                Ok(())
+            }
+            sql::TransactionStatement::Savepoint(name) => {
+                if open.failed {
+                    return Err(DatabaseError::TransactionAborted);
+                }
+                open.savepoints.push((name.clone(), open.txn.prev_lsn()));
+                Ok(())
+            }
+            sql::TransactionStatement::RollbackTo(name) => {
+                // the savepoint stays, so it can be rolled back to again
+                let position = savepoint(name)?;
+                let lsn = open.savepoints[position].1;
+                open.savepoints.truncate(position + 1);
+                transaction_manager.rollback_to(&mut open.txn, lsn)?;
+                open.failed = false;
+                plans.invalidate();
+                Ok(())
+            }
+            sql::TransactionStatement::Release(name) => {
+                if open.failed {
+                    return Err(DatabaseError::TransactionAborted);
+                }
+                // along with every savepoint set after it
+                let position = savepoint(name)?;
+                open.savepoints.truncate(position);
+                Ok(())
+            }
+        }
+    }
+}
+
+/// marks the `SERIALIZABLE` blocks other than `writer`'s that read a row
+/// `writes` wrote as failing, see `TransactionBlock`
+fn read_write_conflicts(blocks: &mut HashMap<u64, TransactionBlock>, writer: u64, writes: &AccessLog) {
+    for (_, block) in blocks.iter_mut().filter(|(id, _)| **id != writer) {
+        block.read_write_conflict |= block.txn.isolation_level() == IsolationLevel::Serializable && block.read_log.conflicts_with(writes);
+    }
+}
+
+/// Statements prepared on any connection, by their normalized SQL (see
+/// `sql::normalize`), so preparing one again reuses its plan. Past
+/// `PLAN_CACHE_CAPACITY` the least recently used is dropped.
+#[derive(Default)]
+struct PlanCache {
+    entries: HashMap<String, CachedPlan>,
+    /// counts the lookups, to tell which entry was used least recently
+    clock: u64,
+    /// goes up whenever a table, index or statistics change, which makes the
+    /// plans made before stale
+    version: u64,
+}
+
+struct CachedPlan {
+    prepared: Arc<PreparedStatement>,
+    last_used: u64,
+}
+
+impl PlanCache {
+    /// the statement cached under `key`, or else `statement` prepared and cached under it
+    fn prepare(&mut self, executor: &Executor, key: &str, statement: impl FnOnce() -> Result<sql::Statement, ParseError>) -> Result<Arc<PreparedStatement>, DatabaseError> {
+        self.clock += 1;
+        if let Some(cached) = self.entries.get_mut(key) {
+            cached.last_used = self.clock;
+            return Ok(cached.prepared.clone());
+        }
+        let prepared = Arc::new(executor.prepare(statement()?)?);
+        if self.entries.len() >= PLAN_CACHE_CAPACITY
+            && let Some(oldest) = self.entries.iter().min_by_key(|(_, cached)| cached.last_used).map(|(key, _)| key.clone())
+        {
+            self.entries.remove(&oldest);
+        }
+        self.entries.insert(key.to_string(), CachedPlan { prepared: prepared.clone(), last_used: self.clock });
+        Ok(prepared)
+    }
+
+    /// notes that `statement` ran, dropping every plan if it changed what they were made from
+    fn executed(&mut self, statement: &sql::Statement) {
+        if matches!(statement, sql::Statement::CreateTable(_) | sql::Statement::CreateIndex(_) | sql::Statement::Analyze(_)) {
+            self.invalidate();
+        }
+    }
+
+    /// drops every plan, so statements are planned again before they next run
+    fn invalidate(&mut self) {
+        self.entries.clear();
+        self.version += 1;
+    }
+}
+
+impl Drop for Shared {
+    fn drop(&mut self) {
+        // clean shutdown: a final checkpoint writes everything back and leaves next to no
+        // log to recover. errors are ignored, whatever didn't make it to disk is recovered
+        // from the log next time
+        self.vacuum_worker.take();
+        self.checkpoint_worker.take();
+        let _ = self.checkpoint_manager.checkpoint();
+    }
+}
+
+/// A database stored in a single file, with its write-ahead log next to it.
+///
+/// Opening a database recovers it from the log if it wasn't shut down cleanly,
+/// and creates it (file, log and catalog) if it doesn't exist yet. Pages are
+/// protected against torn writes by a double-write buffer, see
+/// `DiskManager::enable_double_write`. SQL is run
+/// through `Connection`s, which all share the database's buffer pool. Clones
+/// are handles to the same open database.
+///
+/// # Examples
+///
+/// ```
+/// use gondor_rdbms::Database;
+/// use gondor_rdbms::types::Value;
+///
+/// let dir = tempfile::tempdir().unwrap();
+/// let database = Database::open(dir.path().join("shire.db")).unwrap();
+/// let connection = database.connect();
+///
+/// connection.execute("CREATE TABLE hobbits (name VARCHAR(32), age INT)").unwrap();
+/// connection.execute("INSERT INTO hobbits VALUES ('Bilbo', 111), ('Frodo', 33)").unwrap();
+///
+/// let rows = connection.query("SELECT name FROM hobbits WHERE age > 100").unwrap();
+/// assert_eq!(rows.columns(), ["name"]);
+/// assert_eq!(rows.iter().next().unwrap().get(0), Some(&Value::Varchar("Bilbo".to_string())));
+/// ```
+#[derive(Clone)]
+pub struct Database {
+    shared: Arc<Shared>,
+}
+
+impl Database {
+    /// Opens the database at `path`, creating it if needed. The log is kept in a
+    /// file with `-wal` appended to the name.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, DatabaseError> {
+        Self::open_with_options(path, DatabaseOptions::default())
+    }
+
+    /// Like `open`, with settings other than the defaults. The page size can't
+    /// be changed once the database exists; opening it with a different one fails.
+    pub fn open_with_options(path: impl AsRef<Path>, options: DatabaseOptions) -> Result<Self, DatabaseError> {
+        let path = path.as_ref();
+        let mut disk_manager = match options.page_size {
+            Some(page_size) => DiskManager::open_with_page_size(path, page_size)?,
+            None => DiskManager::open(path)?,
+        };
+        disk_manager.set_sync_mode(options.sync_mode)?;
+        // torn pages are put back before recovery needs to read them
+        disk_manager.enable_double_write()?;
+        let mut log_manager = LogManager::open(wal_path(path))?;
+        log_manager.set_sync_mode(options.sync_mode)?;
+        let log_manager = Arc::new(Mutex::new(log_manager));
+        let mut buffer_pool = BufferPool::new(disk_manager);
+        buffer_pool.set_log_manager(log_manager.clone());
+        let buffer_pool = Arc::new(Mutex::new(buffer_pool));
+
+        recovery::recover(&buffer_pool, &log_manager)?;
+        let mut catalog = Catalog::open(buffer_pool.clone())?;
+        catalog.set_log_manager(log_manager.clone());
+        let group_commit = Arc::new(GroupCommit::new(log_manager.clone(), options.group_commit_window));
+        let mut transaction_manager = TransactionManager::new(buffer_pool.clone(), log_manager.clone());
+        transaction_manager.set_group_commit(group_commit.clone());
+        let checkpoint_manager = CheckpointManager::new(buffer_pool.clone(), log_manager.clone());
+        let checkpoint_worker = checkpoint_manager.start(CHECKPOINT_INTERVAL);
+
+        Ok(Self {
+            shared: Arc::new_cyclic(|shared: &Weak<Shared>| Shared {
+                buffer_pool,
+                log_manager,
+                state: Mutex::new(State { catalog, transaction_manager, plans: PlanCache::default(), blocks: HashMap::new(), waiting: HashMap::new() }),
+                block_ended: Condvar::new(),
+                next_connection_id: AtomicU64::new(1),
+                group_commit,
+                checkpoint_manager,
+                checkpoint_worker: Some(checkpoint_worker),
+                free_space_map: Arc::new(Mutex::new(FreeSpaceMap::new())),
+                vacuum_worker: options.vacuum_interval.map(|interval| {
+                    let shared = shared.clone();
+                    vacuum::start(interval, move || {
+                        // a database that's shutting down has no more need of it
+                        if let Some(shared) = shared.upgrade() {
+                            let _ = shared.vacuum();
+                        }
+                    })
+                }),
+            }),
+        })
+    }
+
+    /// Size in bytes of the pages the database was created with.
+    pub fn page_size(&self) -> usize {
+        self.shared.buffer_pool.lock().unwrap().page_size()
+    }
+
+    /// Takes a checkpoint now rather than waiting for the background one, writing
+    /// back every dirty page and truncating the log.
+    pub fn checkpoint(&self) -> Result<(), DatabaseError> {
+        self.shared.checkpoint_manager.checkpoint()?;
+        Ok(())
+    }
+
+    /// Vacuums every table now, like `VACUUM`: compacts the pages deleted rows
+    /// left space in and records the room for inserts to fill. See `vacuum::vacuum_heap`.
+    pub fn vacuum(&self) -> Result<VacuumStats, DatabaseError> {
+        self.shared.vacuum()
+    }
+
+    /// Opens a new connection. Connections can be moved to other threads, and
+    /// keep the database open for as long as they're around.
+    pub fn connect(&self) -> Connection {
+        let id = self.shared.next_connection_id.fetch_add(1, Ordering::Relaxed);
+        Connection { shared: self.shared.clone(), id }
+    }
+}
+
+/// the log lives next to the database file, `shire.db` logs to `shire.db-wal`
+fn wal_path(path: &Path) -> PathBuf {
+    let mut wal_path = OsString::from(path.as_os_str());
+    wal_path.push("-wal");
+    PathBuf::from(wal_path)
+}
+
+/// A connection to a `Database` that runs SQL.
+///
+/// Each statement runs in a transaction of its own, which commits when the
+/// statement succeeds and rolls back when it fails, unless `BEGIN` has opened a
+/// transaction block. Then every statement runs in the block's transaction
+/// until `COMMIT` or `ROLLBACK`, and `SAVEPOINT name` marks a point that
+/// `ROLLBACK TO name` undoes the statements after, without ending the block.
+/// A statement that fails in a block is undone, and fails the block: nothing
+/// else runs until it's rolled back, all of it or to a savepoint from before
+/// the failure. Dropping a connection rolls back its block.
+///
+/// `BEGIN ISOLATION LEVEL level` or `SET TRANSACTION ISOLATION LEVEL level`
+/// picks how the block is kept apart from the others, `READ COMMITTED` if
+/// neither does. At `READ COMMITTED`, each statement sees what was committed
+/// before it, and statements on other connections wait for the block to end
+/// once it has changed something. At `REPEATABLE READ` those that would
+/// write a table the block has read wait for it too, so what it read stays
+/// the same throughout, while the rest run. `SERIALIZABLE` lets them all run
+/// until the block has changed something, but if one of them writes a row
+/// the block read (or one a range of index keys it read would now find), the
+/// block's next statement fails with
+/// `DatabaseError::SerializationFailure`, which it can be retried after.
+/// Blocks waiting for each other fail with `DatabaseError::Deadlock`.
+/// Either way, a thread mustn't use two connections at once.
+///
+/// # Examples
+///
+/// ```
+/// use gondor_rdbms::Database;
+/// use gondor_rdbms::types::Value;
+///
+/// let dir = tempfile::tempdir().unwrap();
+/// let database = Database::open(dir.path().join("shire.db")).unwrap();
+/// let connection = database.connect();
+/// connection.execute("CREATE TABLE hobbits (name VARCHAR(32), age INT)").unwrap();
+///
+/// let mut insert = connection.prepare("INSERT INTO hobbits VALUES (?, ?)").unwrap();
+/// insert.execute(&[Value::Varchar("Bilbo".to_string()), Value::Int(111)]).unwrap();
+/// insert.execute(&[Value::Varchar("Frodo".to_string()), Value::Int(33)]).unwrap();
+///
+/// let mut older = connection.prepare("SELECT name FROM hobbits WHERE age > $1").unwrap();
+/// assert_eq!(older.query(&[Value::Int(100)]).unwrap().len(), 1);
+/// assert_eq!(older.query(&[Value::Int(10)]).unwrap().len(), 2);
+/// ```
+pub struct Connection {
+    shared: Arc<Shared>,
+    /// tells this connection's transaction block apart from those of others
+    id: u64,
+}
+
+/// Whether a connection is in a transaction block, see `Connection::transaction_status`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TransactionStatus {
+    /// not in a block, each statement is a transaction of its own
+    Idle,
+    /// in a block
+    InTransaction,
+    /// in a block that a statement failed in
+    Failed,
+}
+
+impl Connection {
+    /// Runs a single statement, returning the number of rows it inserted, updated
+    /// or deleted (or, for a `SELECT`, returned).
+    pub fn execute(&self, sql: &str) -> Result<usize, DatabaseError> {
+        match self.run(&sql::parse_statement(sql)?)? {
+            QueryResult::Rows { rows, .. } => Ok(rows.len()),
+            QueryResult::Affected(count) => Ok(count),
+        }
+    }
+
+    /// Runs every `;` separated statement in `sql`, stopping at the first that fails.
+    pub fn execute_batch(&self, sql: &str) -> Result<(), DatabaseError> {
+        for statement in sql::parse(sql)? {
+            self.run(&statement)?;
+        }
+        Ok(())
+    }
+
+    /// Runs a single statement and returns the rows it produced, which are
+    /// none for anything but a `SELECT`.
+    pub fn query(&self, sql: &str) -> Result<Rows, DatabaseError> {
+        Ok(rows(self.run(&sql::parse_statement(sql)?)?))
+    }
+
+    /// Every table in the database, in creation order.
+    pub fn tables(&self) -> Result<Vec<TableInfo>, DatabaseError> {
+        Ok(self.shared.lock_state(self.id, None)?.catalog.tables()?)
+    }
+
+    /// Whether the connection is in a transaction block, and if so whether it failed.
+    pub fn transaction_status(&self) -> TransactionStatus {
+        let state = self.shared.state.lock().unwrap();
+        match state.blocks.get(&self.id) {
+            Some(block) if block.failed => TransactionStatus::Failed,
+            Some(_) => TransactionStatus::InTransaction,
+            None => TransactionStatus::Idle,
+        }
+    }
+
+    /// The isolation level of the connection's transaction block, `None` outside of one.
+    pub fn isolation_level(&self) -> Option<IsolationLevel> {
+        self.shared.state.lock().unwrap().blocks.get(&self.id).map(|block| block.txn.isolation_level())
+    }
+
+    /// Runs an already parsed statement, in a transaction of its own unless
+    /// the connection is in a transaction block.
+    pub fn run(&self, statement: &sql::Statement) -> Result<QueryResult, DatabaseError> {
+        if let sql::Statement::Transaction(statement) = statement {
+            self.shared.control(self.id, statement)?;
+            return Ok(QueryResult::Affected(0));
+        }
+        self.shared.check_block(self.id, statement)?;
+        self.shared.run(self.id, Access::of(statement), |executor, txn, plans| {
+            let result = executor.execute(txn, statement)?;
+            plans.executed(statement);
+            Ok(result)
+        })
+    }
+
+    /// Loads the CSV read from `reader` into `table` in a transaction of its
+    /// own, like `COPY table FROM` but from anything that reads, returning how
+    /// many rows there were. The reader is read a bit at a time as the rows
+    /// go in, so the CSV needn't fit in memory.
+    pub fn copy_in(&self, table: &str, reader: impl Read, options: &sql::CopyOptions) -> Result<usize, DatabaseError> {
+        let reader = BufReader::new(reader);
+        let access = Access { reads: Vec::new(), writes: Some(table) };
+        self.shared.run(self.id, access, |executor, txn, _| Ok(executor.copy_in(txn, table, &[], reader, options)?))
+    }
+
+    /// Parses and plans a single statement, with `?` or `$1`, `$2`... for the
+    /// values it's run with, to run it any number of times. Statements are
+    /// cached, so preparing one that was prepared before (on any connection)
+    /// doesn't plan it again.
+    pub fn prepare(&self, sql: &str) -> Result<Statement, DatabaseError> {
+        let key = sql::normalize(sql)?;
+        let mut state = self.shared.lock_state(self.id, None)?;
+        let State { catalog, plans, .. } = &mut *state;
+        let prepared = plans.prepare(&Executor::new(self.shared.buffer_pool.clone(), catalog), &key, || sql::parse_statement(sql))?;
+        Ok(Statement { shared: self.shared.clone(), connection: self.id, key, prepared, version: plans.version })
+    }
+}
+
+impl Drop for Connection {
+    fn drop(&mut self) {
+        // an open block would keep every other connection waiting forever
+        if self.transaction_status() != TransactionStatus::Idle {
+            let _ = self.shared.control(self.id, &sql::TransactionStatement::Rollback);
+        }
+    }
+}
+
+/// A statement prepared by `Connection::prepare`, run with values for its
+/// parameters, `$1` (or the first `?`) first.
+///
+/// Each run is a transaction of its own, like a statement run by the
+/// connection, unless the connection is in a transaction block. If tables, indexes or statistics have changed since the
+/// statement was planned, it's planned again before it runs. Clones share the
+/// plan.
+#[derive(Clone)]
+pub struct Statement {
+    shared: Arc<Shared>,
+    /// id of the connection that prepared it, whose transaction block it runs in
+    connection: u64,
+    /// the statement's normalized SQL, which it's cached under
+    key: String,
+    prepared: Arc<PreparedStatement>,
+    /// of the plan cache when the statement was prepared
+    version: u64,
+}
+
+impl Statement {
+    /// How many values the statement has to be run with.
+    pub fn parameter_count(&self) -> usize {
+        self.prepared.parameter_types().len()
+    }
+
+    /// The type of each parameter, `None` for one that takes a value of any type.
+    pub fn parameter_types(&self) -> &[Option<DataType>] {
+        self.prepared.parameter_types()
+    }
+
+    /// Names of the columns of the rows the statement returns, none unless it's
+    /// a `SELECT` or `EXPLAIN`.
+    pub fn column_names(&self) -> Vec<String> {
+        self.prepared.column_names()
+    }
+
+    /// Types of the columns of the rows the statement returns, `None` for one
+    /// whose values can be of any type.
+    pub fn column_types(&self) -> Vec<Option<DataType>> {
+        self.prepared.column_types()
+    }
+
+    /// Runs the statement, returning the number of rows it inserted, updated or
+    /// deleted (or, for a `SELECT`, returned).
+    pub fn execute(&mut self, parameters: &[Value]) -> Result<usize, DatabaseError> {
+        match self.run(parameters)? {
+            QueryResult::Rows { rows, .. } => Ok(rows.len()),
+            QueryResult::Affected(count) => Ok(count),
+        }
+    }
+
+    /// Runs the statement and returns the rows it produced, which are none for
+    /// anything but a `SELECT`.
+    pub fn query(&mut self, parameters: &[Value]) -> Result<Rows, DatabaseError> {
+        Ok(rows(self.run(parameters)?))
+    }
+
+    /// The statement as it was parsed.
+    pub fn parsed(&self) -> &sql::Statement {
+        self.prepared.statement()
+    }
+
+    /// Runs the statement, returning what it produced.
+    pub fn run(&mut self, parameters: &[Value]) -> Result<QueryResult, DatabaseError> {
+        if let sql::Statement::Transaction(statement) = self.prepared.statement() {
+            self.shared.control(self.connection, statement)?;
+            return Ok(QueryResult::Affected(0));
+        }
+        self.shared.check_block(self.connection, self.prepared.statement())?;
+        let prepared = self.prepared.clone();
+        self.shared.run(self.connection, Access::of(prepared.statement()), |executor, txn, plans| {
+            if plans.version != self.version {
+                self.prepared = plans.prepare(executor, &self.key, || Ok(self.prepared.statement().clone()))?;
+                self.version = plans.version;
+            }
+            let result = executor.execute_prepared(txn, &self.prepared, parameters)?;
+            plans.executed(self.prepared.statement());
+            Ok(result)
+        })
+    }
+}
+
+/// the rows of `result`, none if it's a count
+fn rows(result: QueryResult) -> Rows {
+    match result {
+        QueryResult::Rows { columns, rows } => Rows {
+            columns,
+            rows: rows.into_iter().map(|values| Row { values }).collect(),
+        },
+        QueryResult::Affected(_) => Rows { columns: Vec::new(), rows: Vec::new() },
+    }
+}
+
+/// Rows returned by `Connection::query` or `Statement::query`, all read before the query returns.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Rows {
+    columns: Vec<String>,
+    rows: Vec<Row>,
+}
+
+impl Rows {
+    /// Names of the columns, in the order values appear in each row.
+    pub fn columns(&self) -> &[String] {
+        &self.columns
+    }
+
+    pub fn len(&self) -> usize {
+        self.rows.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rows.is_empty()
+    }
+
+    pub fn iter(&self) -> std::slice::Iter<'_, Row> {
+        self.rows.iter()
+    }
+}
+
+impl IntoIterator for Rows {
+    type Item = Row;
+    type IntoIter = std::vec::IntoIter<Row>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.rows.into_iter()
+    }
+}
+
+impl<'a> IntoIterator for &'a Rows {
+    type Item = &'a Row;
+    type IntoIter = std::slice::Iter<'a, Row>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.rows.iter()
+    }
+}
+
+/// One row of a query result.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Row {
+    values: Vec<Value>,
+}
+
+impl Row {
+    /// Value of the column at `index`, `None` if there's no such column.
+    pub fn get(&self, index: usize) -> Option<&Value> {
+        self.values.get(index)
+    }
+
+    pub fn values(&self) -> &[Value] {
+        &self.values
+    }
+
+    pub fn into_values(self) -> Vec<Value> {
+        self.values
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn values(rows: &Rows) -> Vec<Vec<Value>> {
+        rows.iter().map(|row| row.values().to_vec()).collect()
+    }
+
+    #[test]
+    fn test_data_survives_reopen() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("test.db");
+        {
+            let database = Database::open(&path).unwrap();
+            let connection = database.connect();
+            connection.execute_batch("CREATE TABLE t (id INT, name VARCHAR); INSERT INTO t VALUES (1, 'one'), (2, 'two');").unwrap();
+            assert_eq!(connection.execute("DELETE FROM t WHERE id = 1").unwrap(), 1);
+        }
+
+        let database = Database::open(&path).unwrap();
+        let rows = database.connect().query("SELECT * FROM t").unwrap();
+        assert_eq!(rows.columns(), ["id", "name"]);
+        assert_eq!(database.connect().tables().unwrap()[0].name, "t");
+        assert_eq!(values(&rows), vec![vec![Value::Int(2), Value::Varchar("two".into())]]);
+    }
+
+    #[test]
+    fn test_checkpoint_truncates_log() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("test.db");
+        {
+            let database = Database::open(&path).unwrap();
+            let connection = database.connect();
+            connection.execute("CREATE TABLE t (id INT)").unwrap();
+            for id in 0..50 {
+                connection.execute(&format!("INSERT INTO t VALUES ({id})")).unwrap();
+            }
+            let log_length = || std::fs::metadata(wal_path(&path)).unwrap().len();
+            let before = log_length();
+            database.checkpoint().unwrap();
+            assert!(log_length() < before);
+            connection.execute("DELETE FROM t WHERE id >= 10").unwrap();
+        }
+
+        let database = Database::open(&path).unwrap();
+        assert_eq!(database.connect().execute("SELECT * FROM t").unwrap(), 10);
+    }
+
+    #[test]
+    fn test_page_size_is_chosen_at_creation() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("test.db");
+        {
+            let options = DatabaseOptions { page_size: Some(16384), ..DatabaseOptions::default() };
+            let database = Database::open_with_options(&path, options).unwrap();
+            let connection = database.connect();
+            // rows too large for a default sized page
+            connection.execute("CREATE TABLE t (id INT, note VARCHAR(10000))").unwrap();
+            connection.execute(&format!("INSERT INTO t VALUES (1, '{}')", "x".repeat(9000))).unwrap();
+        }
+
+        let database = Database::open(&path).unwrap();
+        assert_eq!(database.page_size(), 16384);
+        assert_eq!(database.connect().execute("SELECT * FROM t").unwrap(), 1);
+        drop(database);
+
+        assert!(matches!(
+            Database::open_with_options(&path, DatabaseOptions { page_size: Some(8192), ..DatabaseOptions::default() }),
+            Err(DatabaseError::DiskManagerError(DiskManagerError::PageSizeMismatch { expected: 8192, found: 16384 }))
+        ));
+    }
+
+    #[test]
+    fn test_sync_mode_off_still_persists_on_clean_shutdown() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("test.db");
+        {
+            let options = DatabaseOptions { sync_mode: SyncMode::Off, ..DatabaseOptions::default() };
+            let database = Database::open_with_options(&path, options).unwrap();
+            let connection = database.connect();
+            connection.execute("CREATE TABLE t (id INT)").unwrap();
+            connection.execute("INSERT INTO t VALUES (1), (2)").unwrap();
+        }
+
+        // nothing was forced, but the writes themselves reached the files
+        let database = Database::open(&path).unwrap();
+        assert_eq!(database.connect().execute("SELECT * FROM t").unwrap(), 2);
+    }
+
+    #[test]
+    fn test_concurrent_commits_are_grouped() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("test.db");
+        {
+            let options = DatabaseOptions { group_commit_window: Duration::from_millis(20), ..DatabaseOptions::default() };
+            let database = Database::open_with_options(&path, options).unwrap();
+            database.connect().execute("CREATE TABLE t (id INT)").unwrap();
+            let flushes_before = database.shared.group_commit.flush_count();
+
+            let writers: Vec<_> = (0..8)
+                .map(|writer| {
+                    let connection = database.connect();
+                    std::thread::spawn(move || {
+                        for id in 0..5 {
+                            connection.execute(&format!("INSERT INTO t VALUES ({})", writer * 5 + id)).unwrap();
+                        }
+                    })
+                })
+                .collect();
+            for writer in writers {
+                writer.join().unwrap();
+            }
+            let flushes = database.shared.group_commit.flush_count() - flushes_before;
+            assert!(flushes < 40, "{flushes} flushes for 40 commits");
+        }
+
+        // every commit that returned is durable
+        let database = Database::open(&path).unwrap();
+        assert_eq!(database.connect().execute("SELECT * FROM t").unwrap(), 40);
+    }
+
+    #[test]
+    fn test_failed_statement_rolls_back() {
+        let dir = TempDir::new().unwrap();
+        let database = Database::open(dir.path().join("test.db")).unwrap();
+        let connection = database.connect();
+        connection.execute("CREATE TABLE t (id INT)").unwrap();
+
+        // the second row fails after the first was inserted
+        let result = connection.execute("INSERT INTO t VALUES (1), ('two')");
+        assert!(matches!(result, Err(DatabaseError::ExecutionError(_))));
+        assert!(connection.query("SELECT * FROM t").unwrap().is_empty());
+
+        assert!(matches!(connection.execute("SELEC 1"), Err(DatabaseError::ParseError(_))));
+        assert!(matches!(connection.execute("CREATE TABLE t (id INT)"), Err(DatabaseError::ExecutionError(ExecutionError::CatalogError(_)))));
+    }
+
+    #[test]
+    fn test_unique_violation_rolls_back_the_index_too() {
+        let dir = TempDir::new().unwrap();
+        let database = Database::open(dir.path().join("test.db")).unwrap();
+        let connection = database.connect();
+        connection.execute("CREATE TABLE t (id INT PRIMARY KEY)").unwrap();
+        connection.execute("INSERT INTO t VALUES (1)").unwrap();
+
+        // 2 goes into the heap and the index before 1 is found to clash
+        let result = connection.execute("INSERT INTO t VALUES (2), (1)");
+        assert!(matches!(result, Err(DatabaseError::ExecutionError(ExecutionError::UniqueViolation { .. }))));
+        connection.execute("INSERT INTO t VALUES (2)").unwrap();
+        assert_eq!(values(&connection.query("SELECT * FROM t").unwrap()), vec![vec![Value::Int(1)], vec![Value::Int(2)]]);
+    }
+
+    #[test]
+    fn test_copy() {
+        let dir = TempDir::new().unwrap();
+        let database = Database::open(dir.path().join("test.db")).unwrap();
+        let connection = database.connect();
+        connection.execute("CREATE TABLE t (id INT PRIMARY KEY, name VARCHAR(32), active BOOL)").unwrap();
+
+        let csv: String = (0..3000).map(|id| format!("{id};\"row {id}; of many\";{}\n", if id % 2 == 0 { "t" } else { "" })).collect();
+        let options = sql::CopyOptions { delimiter: ';', ..sql::CopyOptions::default() };
+        assert_eq!(connection.copy_in("t", csv.as_bytes(), &options).unwrap(), 3000);
+        let rows = values(&connection.query("SELECT name, active FROM t WHERE id = 1 OR id = 2998").unwrap());
+        assert_eq!(rows, [vec![Value::Varchar("row 1; of many".into()), Value::Null], vec![Value::Varchar("row 2998; of many".into()), Value::Bool(true)]]);
+
+        // the table and its file round trip through COPY TO and COPY FROM
+        let path = dir.path().join("t.csv");
+        let count = connection.execute(&format!("COPY t (name, id) TO '{}' WITH (HEADER, NULL 'none')", path.display())).unwrap();
+        assert_eq!(count, 3000);
+        let file = std::fs::read_to_string(&path).unwrap();
+        assert!(file.starts_with("name,id\nrow 0; of many,0\n"));
+        connection.execute("CREATE TABLE copied (id INT, name VARCHAR(32))").unwrap();
+        connection.execute(&format!("COPY copied (name, id) FROM '{}' (HEADER TRUE, NULL 'none')", path.display())).unwrap();
+        assert_eq!(connection.query("SELECT * FROM copied WHERE name = 'row 1234; of many' AND id = 1234").unwrap().len(), 1);
+
+        // a bad line or a duplicate rolls the whole load back
+        let result = connection.copy_in("t", "5000,five thousand,\n5001,,maybe\n".as_bytes(), &sql::CopyOptions::default());
+        assert!(matches!(result, Err(DatabaseError::ExecutionError(ExecutionError::InvalidCsv { line: 2, .. }))));
+        let result = connection.copy_in("t", "5000,five thousand,\n1,one,f\n".as_bytes(), &sql::CopyOptions::default());
+        assert!(matches!(result, Err(DatabaseError::ExecutionError(ExecutionError::UniqueViolation { .. }))));
+        assert_eq!(connection.execute("SELECT * FROM t").unwrap(), 3000);
+        assert!(matches!(connection.execute("COPY missing FROM 'missing.csv'"), Err(DatabaseError::ExecutionError(ExecutionError::IoError(_)))));
+    }
+
+    #[test]
+    fn test_transaction_blocks() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("test.db");
+        {
+            let database = Database::open(&path).unwrap();
+            let connection = database.connect();
+            connection.execute("CREATE TABLE t (id INT PRIMARY KEY)").unwrap();
+            connection.execute_batch("BEGIN; INSERT INTO t VALUES (1); SAVEPOINT a; INSERT INTO t VALUES (2); SAVEPOINT b; INSERT INTO t VALUES (3)").unwrap();
+            assert_eq!(connection.transaction_status(), TransactionStatus::InTransaction);
+            assert!(matches!(connection.execute("BEGIN"), Err(DatabaseError::TransactionInProgress)));
+
+            // a failure undoes just its statement, and nothing more runs until a rollback
+            assert!(matches!(connection.execute("INSERT INTO t VALUES (4), (1)"), Err(DatabaseError::ExecutionError(ExecutionError::UniqueViolation { .. }))));
+            assert_eq!(connection.transaction_status(), TransactionStatus::Failed);
+            assert!(matches!(connection.execute("SELECT * FROM t"), Err(DatabaseError::TransactionAborted)));
+            connection.execute("ROLLBACK TO b").unwrap();
+            assert_eq!(connection.execute("SELECT * FROM t").unwrap(), 2);
+
+            // rolling back to a savepoint forgets the ones after it, but keeps it
+            connection.execute("ROLLBACK TO SAVEPOINT a").unwrap();
+            assert!(matches!(connection.execute("RELEASE b"), Err(DatabaseError::UnknownSavepoint(name)) if name == "b"));
+            connection.execute("INSERT INTO t VALUES (5)").unwrap();
+            connection.execute("ROLLBACK TO a").unwrap();
+            connection.execute("INSERT INTO t VALUES (6)").unwrap();
+            connection.execute("RELEASE SAVEPOINT a").unwrap();
+            assert!(matches!(connection.execute("ROLLBACK TO a"), Err(DatabaseError::UnknownSavepoint(_))));
+
+            // another connection waits for the block to end before it runs
+            let other = database.connect();
+            let waiting = std::thread::spawn(move || other.execute("SELECT * FROM t").unwrap());
+            std::thread::sleep(Duration::from_millis(50));
+            assert!(!waiting.is_finished());
+            connection.execute("COMMIT").unwrap();
+            assert_eq!(waiting.join().unwrap(), 2);
+            assert_eq!(connection.transaction_status(), TransactionStatus::Idle);
+            assert!(matches!(connection.execute("SAVEPOINT a"), Err(DatabaseError::NoTransaction(_))));
+
+            // a block that failed can't commit, and one left open rolls back with its connection
+            connection.execute_batch("BEGIN; INSERT INTO t VALUES (7)").unwrap();
+            assert!(connection.execute("INSERT INTO t VALUES (1)").is_err());
+            assert!(matches!(connection.execute("COMMIT"), Err(DatabaseError::TransactionAborted)));
+            connection.execute_batch("BEGIN; CREATE TABLE u (id INT); INSERT INTO t VALUES (8)").unwrap();
+            drop(connection);
+            assert_eq!(database.connect().tables().unwrap().len(), 1);
+        }
+
+        let database = Database::open(&path).unwrap();
+        assert_eq!(values(&database.connect().query("SELECT * FROM t").unwrap()), vec![vec![Value::Int(1)], vec![Value::Int(6)]]);
+    }
+
+    #[test]
+    fn test_isolation_levels() {
+        let dir = TempDir::new().unwrap();
+        let database = Database::open(dir.path().join("test.db")).unwrap();
+        let (connection, other) = (database.connect(), database.connect());
+        connection.execute_batch("CREATE TABLE t (id INT); INSERT INTO t VALUES (1)").unwrap();
+        let count = |connection: &Connection| connection.execute("SELECT * FROM t").unwrap();
+
+        // READ COMMITTED sees each commit, until it writes and the others have to wait
+        connection.execute("BEGIN").unwrap();
+        assert_eq!(connection.isolation_level(), Some(IsolationLevel::ReadCommitted));
+        assert_eq!(count(&connection), 1);
+        other.execute("INSERT INTO t VALUES (2)").unwrap();
+        assert_eq!(count(&connection), 2);
+        connection.execute("INSERT INTO t VALUES (3)").unwrap();
+        let waiting = std::thread::spawn(move || (count(&other), other));
+        std::thread::sleep(Duration::from_millis(50));
+        assert!(!waiting.is_finished());
+        connection.execute("COMMIT").unwrap();
+        let (seen, other) = waiting.join().unwrap();
+        assert_eq!(seen, 3);
+
+        // REPEATABLE READ sees the same from its first statement on
+        connection.execute("BEGIN ISOLATION LEVEL REPEATABLE READ").unwrap();
+        other.execute("INSERT INTO t VALUES (4)").unwrap();
+        assert_eq!(count(&connection), 4);
+        let waiting = std::thread::spawn(move || other.execute("INSERT INTO t VALUES (5)").unwrap());
+        std::thread::sleep(Duration::from_millis(50));
+        assert!(!waiting.is_finished());
+        assert_eq!(count(&connection), 4);
+        connection.execute("COMMIT").unwrap();
+        waiting.join().unwrap();
+        assert_eq!(count(&connection), 5);
+
+        // the level can change until the first statement
+        connection.execute_batch("BEGIN; SET TRANSACTION ISOLATION LEVEL SERIALIZABLE").unwrap();
+        assert_eq!(connection.isolation_level(), Some(IsolationLevel::Serializable));
+        assert_eq!(count(&connection), 5);
+        assert!(matches!(connection.execute("SET TRANSACTION ISOLATION LEVEL READ COMMITTED"), Err(DatabaseError::IsolationLevelAfterQuery)));
+        connection.execute("ROLLBACK").unwrap();
+        assert_eq!(connection.isolation_level(), None);
+        assert!(matches!(connection.execute("SET TRANSACTION ISOLATION LEVEL READ COMMITTED"), Err(DatabaseError::NoTransaction(_))));
+    }
+
+    #[test]
+    fn test_repeatable_read_holds_only_what_it_read() {
+        let dir = TempDir::new().unwrap();
+        let database = Database::open(dir.path().join("test.db")).unwrap();
+        let (connection, other) = (database.connect(), database.connect());
+        connection.execute_batch("CREATE TABLE t (id INT); CREATE TABLE u (id INT); INSERT INTO t VALUES (1); INSERT INTO u VALUES (1)").unwrap();
+
+        // other connections go on reading it, and reading and writing the rest
+        connection.execute("BEGIN ISOLATION LEVEL REPEATABLE READ").unwrap();
+        assert_eq!(connection.execute("SELECT * FROM t").unwrap(), 1);
+        assert_eq!(other.execute("SELECT * FROM u").unwrap(), 1);
+        assert_eq!(other.execute("SELECT * FROM t").unwrap(), 1);
+        other.execute("INSERT INTO u VALUES (2)").unwrap();
+        other.execute_batch("BEGIN ISOLATION LEVEL REPEATABLE READ; SELECT * FROM t").unwrap();
+
+        // two blocks writing what both read would wait for each other, so the second fails
+        let writing = std::thread::spawn(move || (connection.execute("UPDATE t SET id = 2").unwrap(), connection));
+        std::thread::sleep(Duration::from_millis(50));
+        assert!(!writing.is_finished());
+        assert!(matches!(other.execute("DELETE FROM t"), Err(DatabaseError::Deadlock)));
+        assert_eq!(other.transaction_status(), TransactionStatus::Failed);
+        other.execute("ROLLBACK").unwrap();
+        let (updated, connection) = writing.join().unwrap();
+        assert_eq!(updated, 1);
+        connection.execute("COMMIT").unwrap();
+        assert_eq!(other.query("SELECT id FROM t").unwrap().iter().map(|row| row.values().to_vec()).collect::<Vec<_>>(), vec![vec![Value::Int(2)]]);
+    }
+
+    #[test]
+    fn test_serializable_blocks_fail_rather_than_skew() {
+        let dir = TempDir::new().unwrap();
+        let database = Database::open(dir.path().join("test.db")).unwrap();
+        let (alice, bob) = (database.connect(), database.connect());
+        alice.execute_batch("CREATE TABLE doctors (name VARCHAR(16), on_call BOOL); CREATE TABLE shifts (day INT)").unwrap();
+        alice.execute("INSERT INTO doctors VALUES ('alice', TRUE), ('bob', TRUE)").unwrap();
+        let on_call = |connection: &Connection| connection.execute("SELECT * FROM doctors WHERE on_call = TRUE").unwrap();
+
+        // both see someone else on call, and each takes themselves off
+        alice.execute("BEGIN ISOLATION LEVEL SERIALIZABLE").unwrap();
+        bob.execute("BEGIN ISOLATION LEVEL SERIALIZABLE").unwrap();
+        assert_eq!(on_call(&alice), 2);
+        assert_eq!(on_call(&bob), 2);
+        alice.execute("UPDATE doctors SET on_call = FALSE WHERE name = 'alice'").unwrap();
+        alice.execute("COMMIT").unwrap();
+        assert!(matches!(bob.execute("UPDATE doctors SET on_call = FALSE WHERE name = 'bob'"), Err(DatabaseError::SerializationFailure)));
+        assert_eq!(bob.transaction_status(), TransactionStatus::Failed);
+        bob.execute("ROLLBACK").unwrap();
+        // and a retry sees why not
+        bob.execute("BEGIN ISOLATION LEVEL SERIALIZABLE").unwrap();
+        assert_eq!(on_call(&bob), 1);
+        bob.execute("COMMIT").unwrap();
+
+        // a write to a table it didn't read doesn't get in its way, and one after
+        // its last statement leaves it before the writer
+        bob.execute("BEGIN ISOLATION LEVEL SERIALIZABLE").unwrap();
+        assert_eq!(on_call(&bob), 1);
+        alice.execute("INSERT INTO shifts VALUES (1)").unwrap();
+        assert_eq!(bob.execute("SELECT * FROM shifts").unwrap(), 1);
+        alice.execute("UPDATE doctors SET on_call = TRUE").unwrap();
+        bob.execute("COMMIT").unwrap();
+    }
+
+    #[test]
+    fn test_serializable_blocks_on_disjoint_keys_both_commit() {
+        let dir = TempDir::new().unwrap();
+        let database = Database::open(dir.path().join("test.db")).unwrap();
+        let (alice, bob) = (database.connect(), database.connect());
+        alice.execute("CREATE TABLE accounts (id INT PRIMARY KEY, balance INT)").unwrap();
+        // enough of them that looking one up goes through the index, rather than reading them all
+        let values: Vec<String> = (1..=1000).map(|id| format!("({id}, 100)")).collect();
+        alice.execute(&format!("INSERT INTO accounts VALUES {}", values.join(", "))).unwrap();
+        alice.execute("ANALYZE accounts").unwrap();
+
+        // each reads and writes only their own account
+        alice.execute("BEGIN ISOLATION LEVEL SERIALIZABLE").unwrap();
+        bob.execute("BEGIN ISOLATION LEVEL SERIALIZABLE").unwrap();
+        assert_eq!(alice.execute("SELECT balance FROM accounts WHERE id = 1").unwrap(), 1);
+        assert_eq!(bob.execute("SELECT balance FROM accounts WHERE id = 2").unwrap(), 1);
+        alice.execute("UPDATE accounts SET balance = 50 WHERE id = 1").unwrap();
+        alice.execute("COMMIT").unwrap();
+        bob.execute("UPDATE accounts SET balance = 50 WHERE id = 2").unwrap();
+        bob.execute("COMMIT").unwrap();
+        assert_eq!(alice.execute("SELECT * FROM accounts WHERE balance = 50").unwrap(), 2);
+
+        // but one that wrote a key the other read, or one it would now find, still fails it
+        for write in ["UPDATE accounts SET balance = 0 WHERE id = 999", "INSERT INTO accounts VALUES (1001, 0)"] {
+            alice.execute("BEGIN ISOLATION LEVEL SERIALIZABLE").unwrap();
+            bob.execute("BEGIN ISOLATION LEVEL SERIALIZABLE").unwrap();
+            assert!(alice.execute("SELECT balance FROM accounts WHERE id = 1").is_ok());
+            assert!(bob.execute("SELECT balance FROM accounts WHERE id >= 999").is_ok());
+            alice.execute(write).unwrap();
+            alice.execute("COMMIT").unwrap();
+            assert!(matches!(bob.execute("SELECT * FROM accounts"), Err(DatabaseError::SerializationFailure)));
+            bob.execute("ROLLBACK").unwrap();
+        }
+    }
+
+    #[test]
+    fn test_vacuum() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("test.db");
+        let pages = |database: &Database| {
+            let table = database.connect().tables().unwrap().remove(0);
+            crate::storage::HeapFile::open(database.shared.buffer_pool.clone(), table.root_page_id).unwrap().page_ids().len()
+        };
+        let insert = |connection: &Connection, ids: std::ops::Range<i32>| {
+            let rows: Vec<String> = ids.map(|id| format!("({id}, '{}')", "x".repeat(80))).collect();
+            connection.execute(&format!("INSERT INTO t VALUES {}", rows.join(", "))).unwrap();
+        };
+        {
+            let database = Database::open(&path).unwrap();
+            let connection = database.connect();
+            connection.execute("CREATE TABLE t (id INT PRIMARY KEY, note VARCHAR(100))").unwrap();
+            insert(&connection, 0..1000);
+            let before = pages(&database);
+
+            // the room deletes leave is only filled again once vacuum has found it
+            connection.execute("DELETE FROM t WHERE id % 2 = 0").unwrap();
+            insert(&connection, 1000..1100);
+            assert!(pages(&database) > before);
+            let grown = pages(&database);
+            connection.execute("VACUUM t").unwrap();
+            insert(&connection, 1100..1500);
+            assert_eq!(pages(&database), grown);
+            assert_eq!(connection.execute("SELECT * FROM t").unwrap(), 1000);
+            assert_eq!(connection.execute("SELECT * FROM t WHERE id = 1234").unwrap(), 1);
+
+            assert!(matches!(connection.execute("VACUUM missing"), Err(DatabaseError::ExecutionError(ExecutionError::TableNotFound(_)))));
+            connection.execute("BEGIN").unwrap();
+            assert!(matches!(connection.execute("VACUUM"), Err(DatabaseError::InTransactionBlock(_))));
+            connection.execute("ROLLBACK").unwrap();
+        }
+
+        // compacted pages are written back like any other, and the background worker vacuums too
+        let options = DatabaseOptions { vacuum_interval: Some(Duration::from_millis(10)), ..DatabaseOptions::default() };
+        let database = Database::open_with_options(&path, options).unwrap();
+        let connection = database.connect();
+        assert_eq!(connection.execute("SELECT * FROM t WHERE id < 1000").unwrap(), 500);
+        let before = pages(&database);
+        connection.execute("DELETE FROM t WHERE id >= 1000").unwrap();
+        while database.shared.free_space_map.lock().unwrap().free_bytes(connection.tables().unwrap()[0].root_page_id) < 500 * 90 {
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        insert(&connection, 1000..1500);
+        assert_eq!(pages(&database), before);
+        assert!(database.vacuum().unwrap().pages >= before);
+    }
+
+    #[test]
+    fn test_create_index_on_a_large_table() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("test.db");
+        {
+            let database = Database::open(&path).unwrap();
+            let connection = database.connect();
+            connection.execute("CREATE TABLE t (id INT, name VARCHAR)").unwrap();
+            for batch in 0..20 {
+                let rows: Vec<String> = (0..100).map(|i| format!("({}, 'row {i}')", batch * 100 + i)).collect();
+                connection.execute(&format!("INSERT INTO t VALUES {}", rows.join(", "))).unwrap();
+            }
+
+            // a failed build leaves nothing behind, not even the name
+            let result = connection.execute("CREATE UNIQUE INDEX t_idx ON t (name)");
+            assert!(matches!(result, Err(DatabaseError::ExecutionError(ExecutionError::UniqueViolation { .. }))));
+            assert!(connection.tables().unwrap()[0].indexes.is_empty());
+            connection.execute("CREATE UNIQUE INDEX t_idx ON t (id DESC)").unwrap();
+        }
+
+        let database = Database::open(&path).unwrap();
+        let connection = database.connect();
+        assert_eq!(connection.tables().unwrap()[0].indexes[0].name, "t_idx");
+        let result = connection.execute("INSERT INTO t VALUES (1999, 'again')");
+        assert!(matches!(result, Err(DatabaseError::ExecutionError(ExecutionError::UniqueViolation { .. }))));
+        connection.execute("INSERT INTO t VALUES (2000, 'new')").unwrap();
+    }
+
+    #[test]
+    fn test_connections_share_data_across_threads() {
+        let dir = TempDir::new().unwrap();
+        let database = Database::open(dir.path().join("test.db")).unwrap();
+        database.connect().execute("CREATE TABLE counts (thread INT, n INT)").unwrap();
+
+        let handles: Vec<_> = (0..4)
+            .map(|thread| {
+                let connection = database.connect();
+                std::thread::spawn(move || {
+                    for n in 0..10 {
+                        connection.execute(&format!("INSERT INTO counts VALUES ({thread}, {n})")).unwrap();
+                    }
+                })
+            })
+            .collect();
+        for handle in handles {
+            handle.join().unwrap();
+        }
+
+        let rows = database.connect().query("SELECT n FROM counts WHERE thread = 2").unwrap();
+        assert_eq!(rows.len(), 10);
+        assert_eq!(database.connect().execute("SELECT * FROM counts").unwrap(), 40);
+    }
+
+    #[test]
+    fn test_prepared_statements() {
+        let dir = TempDir::new().unwrap();
+        let database = Database::open(dir.path().join("test.db")).unwrap();
+        let connection = database.connect();
+        connection.execute("CREATE TABLE t (id INT, name VARCHAR(8))").unwrap();
+
+        let mut insert = connection.prepare("INSERT INTO t VALUES (?, ?)").unwrap();
+        assert_eq!(insert.parameter_count(), 2);
+        for id in 0..20 {
+            assert_eq!(insert.execute(&[Value::Int(id), Value::Varchar(format!("n{id}"))]).unwrap(), 1);
+        }
+        let mut select = connection.prepare("SELECT name FROM t WHERE id = $1").unwrap();
+        assert_eq!(values(&select.query(&[Value::BigInt(7)]).unwrap()), vec![vec![Value::Varchar("n7".into())]]);
+        assert_eq!(values(&select.query(&[Value::Int(8)]).unwrap()), vec![vec![Value::Varchar("n8".into())]]);
+
+        assert!(matches!(select.query(&[]), Err(DatabaseError::ExecutionError(ExecutionError::ParameterCountMismatch { .. }))));
+        assert!(matches!(select.query(&[Value::Bool(true)]), Err(DatabaseError::ExecutionError(ExecutionError::TypeError(_)))));
+        // a failed run rolls back like a failed statement
+        assert!(insert.execute(&[Value::Int(20), Value::Varchar("far too long".into())]).is_err());
+        assert_eq!(connection.query("SELECT * FROM t").unwrap().len(), 20);
+    }
+
+    #[test]
+    fn test_plan_cache() {
+        let dir = TempDir::new().unwrap();
+        let database = Database::open(dir.path().join("test.db")).unwrap();
+        let connection = database.connect();
+        connection.execute("CREATE TABLE t (id INT, name VARCHAR)").unwrap();
+        let rows: Vec<String> = (0..2000).map(|id| format!("({id}, 'n{id}')")).collect();
+        connection.execute(&format!("INSERT INTO t VALUES {}", rows.join(", "))).unwrap();
+        connection.execute("ANALYZE t").unwrap();
+
+        // the same statement written differently, even on another connection, shares the plan
+        let mut select = connection.prepare("SELECT name FROM t WHERE id = $1").unwrap();
+        let again = database.connect().prepare("select name\n  from T where ID = $1").unwrap();
+        assert!(Arc::ptr_eq(&select.prepared, &again.prepared));
+        let scans_index = |statement: &Statement| {
+            let Some(plan) = statement.prepared.plan() else { unreachable!() };
+            format!("{:?}", plan.node).contains("IndexScan")
+        };
+        assert!(!scans_index(&select));
+
+        // a new index makes the plans stale, so they're made again the next time they run
+        connection.execute("CREATE INDEX t_id_idx ON t (id)").unwrap();
+        assert_eq!(values(&select.query(&[Value::Int(1234)]).unwrap()), vec![vec![Value::Varchar("n1234".into())]]);
+        assert!(scans_index(&select));
+        assert!(!Arc::ptr_eq(&select.prepared, &again.prepared));
+        assert!(Arc::ptr_eq(&select.prepared, &connection.prepare("SELECT name FROM t WHERE id = $1").unwrap().prepared));
+    }
+}
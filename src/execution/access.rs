@@ -0,0 +1,160 @@
+use super::{ExecutionError, index_key};
+use super::operators::index_range;
+use crate::catalog::TableInfo;
+use crate::planner::{PhysicalPlan, PlanNode};
+use crate::storage::RecordId;
+use crate::types::Value;
+use std::collections::HashMap;
+use std::ops::{Bound, RangeBounds};
+
+/// most keys (or ranges of them) noted for one table before it's noted as
+/// read or written in full instead
+const MAX_TRACKED_KEYS: usize = 1000;
+
+/// What the statements an executor runs read and wrote, noted with
+/// `Executor::set_access_log`: the rows written by their key in each index of
+/// their table, and what was read as the ranges of keys an index scan went
+/// through, or the whole table for anything else.
+///
+/// A transaction that read something another one then wrote has to come
+/// before it in any serial order, which is what `conflicts_with` tells.
+#[derive(Debug, Default, Clone)]
+pub struct AccessLog {
+    reads: HashMap<String, Part<KeyRange>>,
+    /// the key in every index of each row written, by table
+    writes: HashMap<String, Part<(String, Vec<u8>)>>,
+}
+
+/// what was read or written of one table
+#[derive(Debug, Clone)]
+enum Part<T> {
+    Whole,
+    Keys(Vec<T>),
+}
+
+impl<T> Part<T> {
+    fn add(&mut self, items: impl IntoIterator<Item = T>) {
+        if let Part::Keys(keys) = self {
+            keys.extend(items);
+            if keys.len() > MAX_TRACKED_KEYS {
+                *self = Part::Whole;
+            }
+        }
+    }
+
+    fn merge(&mut self, other: Part<T>) {
+        match other {
+            Part::Whole => *self = Part::Whole,
+            Part::Keys(keys) => self.add(keys),
+        }
+    }
+}
+
+/// keys of the index `index` from `start` to `end`
+#[derive(Debug, Clone)]
+struct KeyRange {
+    index: String,
+    start: Bound<Vec<u8>>,
+    end: Bound<Vec<u8>>,
+}
+
+impl AccessLog {
+    /// Notes what running `plan` reads.
+    pub(super) fn read_plan(&mut self, plan: &PhysicalPlan) -> Result<(), ExecutionError> {
+        match &plan.node {
+            PlanNode::IndexScan { table, index, prefix, lower, upper, .. } => {
+                // a NULL key reads nothing
+                if let Some((start, end)) = index_range(table, index, prefix, lower, upper)? {
+                    self.read_range(&table.name, KeyRange { index: index.name.clone(), start, end });
+                }
+            }
+            // the keys an index nested loop join looks up aren't known until it runs
+            PlanNode::SeqScan { table, .. } | PlanNode::IndexNestedLoopJoin { table, .. } => self.read_table(&table.name),
+            _ => {}
+        }
+        for child in plan.children() {
+            self.read_plan(child)?;
+        }
+        Ok(())
+    }
+
+    /// Notes that all of `table` was read.
+    pub(super) fn read_table(&mut self, table: &str) {
+        self.reads.insert(table.to_string(), Part::Whole);
+    }
+
+    fn read_range(&mut self, table: &str, range: KeyRange) {
+        self.reads.entry(table.to_string()).or_insert_with(|| Part::Keys(Vec::new())).add([range]);
+    }
+
+    /// Notes that `row`, stored at `rid`, was written to `table` (or deleted from it).
+    pub(super) fn wrote(&mut self, table: &TableInfo, row: &[Value], rid: RecordId) -> Result<(), ExecutionError> {
+        let keys = table.indexes.iter().map(|index| Ok((index.name.clone(), index_key(table, index, row, rid)?))).collect::<Result<Vec<_>, ExecutionError>>()?;
+        // a table without indexes is still written
+        self.writes.entry(table.name.clone()).or_insert_with(|| Part::Keys(Vec::new())).add(keys);
+        Ok(())
+    }
+
+    /// Adds what `other` read to what this one did.
+    pub fn add_reads(&mut self, other: &AccessLog) {
+        for (table, part) in &other.reads {
+            match self.reads.get_mut(table) {
+                Some(reads) => reads.merge(part.clone()),
+                None => {
+                    self.reads.insert(table.clone(), part.clone());
+                }
+            }
+        }
+    }
+
+    /// whether `writes` wrote a row this read, or one where a range of keys it read would now find it
+    pub fn conflicts_with(&self, writes: &AccessLog) -> bool {
+        self.reads.iter().any(|(table, read)| match (read, writes.writes.get(table)) {
+            (_, None) => false,
+            (Part::Whole, Some(_)) | (_, Some(Part::Whole)) => true,
+            (Part::Keys(ranges), Some(Part::Keys(keys))) => {
+                ranges.iter().any(|range| keys.iter().any(|(index, key)| *index == range.index && (range.start.as_ref(), range.end.as_ref()).contains(key)))
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write(table: &str, index: &str, key: &[u8]) -> AccessLog {
+        let mut log = AccessLog::default();
+        log.writes.insert(table.to_string(), Part::Keys(vec![(index.to_string(), key.to_vec())]));
+        log
+    }
+
+    #[test]
+    fn test_conflicts_by_key() {
+        let mut reads = AccessLog::default();
+        reads.read_range("t", KeyRange { index: "t_pkey".to_string(), start: Bound::Included(vec![1]), end: Bound::Excluded(vec![2]) });
+        assert!(reads.conflicts_with(&write("t", "t_pkey", &[1])));
+        assert!(reads.conflicts_with(&write("t", "t_pkey", &[1, 5])));
+        assert!(!reads.conflicts_with(&write("t", "t_pkey", &[2])));
+        assert!(!reads.conflicts_with(&write("t", "other", &[1])));
+        assert!(!reads.conflicts_with(&write("u", "t_pkey", &[1])));
+
+        // a whole table conflicts with any write to it
+        let mut scanned = AccessLog::default();
+        scanned.read_table("t");
+        assert!(scanned.conflicts_with(&write("t", "t_pkey", &[2])));
+        reads.add_reads(&scanned);
+        assert!(reads.conflicts_with(&write("t", "t_pkey", &[2])));
+    }
+
+    #[test]
+    fn test_too_many_keys_become_the_whole_table() {
+        let mut reads = AccessLog::default();
+        for key in 0..=MAX_TRACKED_KEYS as u16 {
+            let key = key.to_be_bytes().to_vec();
+            reads.read_range("t", KeyRange { index: "i".to_string(), start: Bound::Included(key.clone()), end: Bound::Included(key) });
+        }
+        assert!(matches!(reads.reads["t"], Part::Whole));
+        assert!(reads.conflicts_with(&write("t", "i", &[0xff, 0xff])));
+    }
+}
@@ -0,0 +1,204 @@
+use super::ExecutionError;
+use crate::catalog::TableInfo;
+use crate::sql::CopyOptions;
+use crate::types::Value;
+use std::io::{BufRead, Write};
+
+/// the quote character of CSV, which fields holding a delimiter, quote or line break are wrapped in
+const QUOTE: char = '"';
+
+/// A field of a CSV line, with whether any of it was quoted: a quoted field
+/// is never NULL, even when it spells the NULL text.
+#[derive(Debug, PartialEq)]
+struct Field {
+    text: String,
+    quoted: bool,
+}
+
+/// Reads the lines of a CSV file as rows of a table.
+///
+/// Fields are separated by the delimiter of the options and may be wrapped in
+/// double quotes, inside which a quote is written twice and delimiters and line
+/// breaks are part of the field. An unquoted field that is the NULL text of the
+/// options is NULL, anything else is read as a value of its column's type.
+pub(super) struct CsvReader<'a, R> {
+    reader: R,
+    options: &'a CopyOptions,
+    /// number of the last line read, from 1
+    line: u64,
+}
+
+impl<'a, R: BufRead> CsvReader<'a, R> {
+    pub(super) fn new(reader: R, options: &'a CopyOptions) -> Self {
+        Self { reader, options, line: 0 }
+    }
+
+    /// Skips the header line, if the options say there is one.
+    pub(super) fn skip_header(&mut self) -> Result<(), ExecutionError> {
+        if self.options.header {
+            self.record()?;
+        }
+        Ok(())
+    }
+
+    /// The next row for `table`, with the fields of a line as the values of
+    /// the columns at `targets` and NULL in the rest, `None` at the end of the file.
+    pub(super) fn row(&mut self, table: &TableInfo, targets: &[usize]) -> Result<Option<Vec<Value>>, ExecutionError> {
+        let line = self.line + 1;
+        let Some(fields) = self.record()? else {
+            return Ok(None);
+        };
+        if fields.len() != targets.len() {
+            return Err(ExecutionError::InvalidCsv { line, message: format!("expected {} fields, found {}", targets.len(), fields.len()) });
+        }
+        let mut values = vec![Value::Null; table.columns.len()];
+        for (field, &target) in fields.into_iter().zip(targets) {
+            if !field.quoted && field.text == self.options.null {
+                continue;
+            }
+            let column = &table.columns[target];
+            values[target] = Value::parse(&field.text, column.data_type).ok_or_else(|| ExecutionError::InvalidCsv {
+                line,
+                message: format!("{:?} is not a valid {} for column {}", field.text, column.data_type, column.name),
+            })?;
+        }
+        Ok(Some(values))
+    }
+
+    /// the fields of the next line, which runs on past line breaks inside quotes
+    fn record(&mut self) -> Result<Option<Vec<Field>>, ExecutionError> {
+        let mut line = String::new();
+        if self.reader.read_line(&mut line)? == 0 {
+            return Ok(None);
+        }
+        self.line += 1;
+        let start = self.line;
+
+        let mut fields = Vec::new();
+        let mut field = Field { text: String::new(), quoted: false };
+        let mut in_quotes = false;
+        let mut position = 0;
+        loop {
+            let Some(c) = line[position..].chars().next() else {
+                if !in_quotes {
+                    break;
+                }
+                // the quoted field goes on on the next line
+                if self.reader.read_line(&mut line)? == 0 {
+                    return Err(ExecutionError::InvalidCsv { line: start, message: "unterminated quoted field".to_string() });
+                }
+                self.line += 1;
+                continue;
+            };
+            position += c.len_utf8();
+            if in_quotes {
+                if c != QUOTE {
+                    field.text.push(c);
+                } else if line[position..].starts_with(QUOTE) {
+                    field.text.push(QUOTE);
+                    position += 1;
+                } else {
+                    in_quotes = false;
+                }
+            } else if c == QUOTE {
+                (in_quotes, field.quoted) = (true, true);
+            } else if c == self.options.delimiter {
+                fields.push(std::mem::replace(&mut field, Field { text: String::new(), quoted: false }));
+            } else if c == '\n' || c == '\r' {
+                break;
+            } else {
+                field.text.push(c);
+            }
+        }
+        fields.push(field);
+        Ok(Some(fields))
+    }
+}
+
+/// Writes `fields` as a line of CSV, NULL (`None`) as the NULL text of the
+/// options and the rest quoted if they'd be read back as something else otherwise.
+pub(super) fn write_record<'a>(writer: &mut impl Write, fields: impl IntoIterator<Item = Option<&'a str>>, options: &CopyOptions) -> std::io::Result<()> {
+    let mut line = String::new();
+    for (i, field) in fields.into_iter().enumerate() {
+        if i > 0 {
+            line.push(options.delimiter);
+        }
+        match field {
+            None => line.push_str(&options.null),
+            Some(text) if text == options.null || text.contains([options.delimiter, QUOTE, '\r', '\n']) => {
+                line.push(QUOTE);
+                line.push_str(&text.replace(QUOTE, "\"\""));
+                line.push(QUOTE);
+            }
+            Some(text) => line.push_str(text),
+        }
+    }
+    line.push('\n');
+    writer.write_all(line.as_bytes())
+}
+
+/// `value` as the text of a CSV field, the way PostgreSQL writes it
+pub(super) fn value_text(value: &Value) -> Option<String> {
+    match value {
+        Value::Null => None,
+        Value::Bool(value) => Some((if *value { "t" } else { "f" }).to_string()),
+        value => Some(value.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn records(csv: &str, options: &CopyOptions) -> Vec<Vec<Field>> {
+        let mut reader = CsvReader::new(csv.as_bytes(), options);
+        std::iter::from_fn(|| reader.record().unwrap()).collect()
+    }
+
+    fn field(text: &str, quoted: bool) -> Field {
+        Field { text: text.to_string(), quoted }
+    }
+
+    #[test]
+    fn test_read_records() {
+        let options = CopyOptions::default();
+        let csv = "1,frodo,\n2,\"baggins, frodo\",\"\"\r\n3,\"say \"\"friend\"\"\nand enter\",x";
+        assert_eq!(
+            records(csv, &options),
+            [
+                vec![field("1", false), field("frodo", false), field("", false)],
+                vec![field("2", false), field("baggins, frodo", true), field("", true)],
+                vec![field("3", false), field("say \"friend\"\nand enter", true), field("x", false)],
+            ]
+        );
+
+        let options = CopyOptions { delimiter: '\t', ..CopyOptions::default() };
+        assert_eq!(records("a,b\tc\n\n", &options), [vec![field("a,b", false), field("c", false)], vec![field("", false)]]);
+
+        let mut reader = CsvReader::new("1\n\"2\n3\n".as_bytes(), &options);
+        reader.record().unwrap();
+        assert!(matches!(reader.record(), Err(ExecutionError::InvalidCsv { line: 2, .. })));
+    }
+
+    #[test]
+    fn test_write_records() {
+        let options = CopyOptions::default();
+        let mut csv = Vec::new();
+        write_record(&mut csv, [Some("1"), None, Some("")], &options).unwrap();
+        write_record(&mut csv, [Some("a,b"), Some("say \"friend\""), Some("two\nlines")], &options).unwrap();
+        assert_eq!(String::from_utf8(csv.clone()).unwrap(), "1,,\"\"\n\"a,b\",\"say \"\"friend\"\"\",\"two\nlines\"\n");
+        // and reads back the same
+        assert_eq!(
+            records(std::str::from_utf8(&csv).unwrap(), &options),
+            [
+                vec![field("1", false), field("", false), field("", true)],
+                vec![field("a,b", true), field("say \"friend\"", true), field("two\nlines", true)],
+            ]
+        );
+
+        let options = CopyOptions { delimiter: '|', header: false, null: "\\N".into() };
+        let mut csv = Vec::new();
+        write_record(&mut csv, [None, Some(""), Some("\\N"), Some("a,b")], &options).unwrap();
+        assert_eq!(csv, b"\\N||\"\\N\"|a,b\n");
+    }
+}
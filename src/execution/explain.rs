@@ -0,0 +1,202 @@
+use super::operators::Profile;
+use crate::planner::{PhysicalPlan, PlanNode};
+use crate::sql::{BinaryOp, Expr};
+use std::ops::Bound;
+
+/// The lines `EXPLAIN` shows for `plan`, laid out like PostgreSQL's: each
+/// operator with the planner's estimates, the conditions it checks below it,
+/// and the operators it reads from indented under those.
+///
+/// With `profiles`, one per plan node in the order `operators::build_profiled`
+/// lists them, each operator also shows what running it actually took.
+pub(super) fn explain(plan: &PhysicalPlan, profiles: Option<&[Profile]>) -> Vec<String> {
+    let mut lines = Vec::new();
+    let mut position = 0;
+    explain_node(plan, profiles, 0, &mut position, &mut lines);
+    lines
+}
+
+/// adds the lines of `plan` to `lines`, indented by `depth`; `position` is the
+/// index of `plan`'s profile
+fn explain_node(plan: &PhysicalPlan, profiles: Option<&[Profile]>, depth: usize, position: &mut usize, lines: &mut Vec<String>) {
+    let mut line = if depth == 0 { String::new() } else { format!("{}->  ", " ".repeat(6 * depth - 4)) };
+    line += &format!("{}  (cost={:.2} rows={:.0})", label(&plan.node), plan.cost, plan.rows);
+    if let Some(profile) = profiles.map(|profiles| profiles[*position]) {
+        line += &format!(" (actual time={:.3} ms rows={})", profile.time.as_secs_f64() * 1000.0, profile.rows);
+    }
+    lines.push(line);
+    *position += 1;
+
+    let indent = " ".repeat(if depth == 0 { 2 } else { 6 * depth + 2 });
+    if let Some(keys) = sort_keys(&plan.node) {
+        lines.push(format!("{indent}Sort Key: {keys}"));
+    }
+    if let PlanNode::HashAggregate { group_by, .. } = &plan.node
+        && !group_by.is_empty()
+    {
+        let keys: Vec<String> = group_by.iter().map(Expr::to_string).collect();
+        lines.push(format!("{indent}Group Key: {}", keys.join(", ")));
+    }
+    for (name, condition) in conditions(&plan.node) {
+        lines.push(format!("{indent}{name}: {condition}"));
+    }
+    for child in plan.children() {
+        explain_node(child, profiles, depth + 1, position, lines);
+    }
+}
+
+fn label(node: &PlanNode) -> String {
+    // the alias only when the query gave the table one
+    let on = |table: &str, alias: &str| if table == alias { table.to_string() } else { format!("{table} {alias}") };
+    match node {
+        PlanNode::OneRow => "Result".to_string(),
+        PlanNode::SeqScan { table, alias, .. } => format!("Seq Scan on {}", on(&table.name, alias)),
+        PlanNode::IndexScan { table, alias, index, .. } => format!("Index Scan using {} on {}", index.name, on(&table.name, alias)),
+        PlanNode::Filter { .. } => "Filter".to_string(),
+        PlanNode::NestedLoopJoin { .. } => "Nested Loop".to_string(),
+        PlanNode::IndexNestedLoopJoin { table, alias, index, .. } => format!("Index Nested Loop using {} on {}", index.name, on(&table.name, alias)),
+        PlanNode::HashJoin { .. } => "Hash Join".to_string(),
+        // named the way PostgreSQL names them, which only says it hashes when there are groups
+        PlanNode::HashAggregate { group_by, .. } if group_by.is_empty() => "Aggregate".to_string(),
+        PlanNode::HashAggregate { .. } => "HashAggregate".to_string(),
+        PlanNode::Sort { .. } => "Sort".to_string(),
+        PlanNode::TopN { count, .. } => format!("Top-N Sort ({count} rows)"),
+        PlanNode::Limit { .. } => "Limit".to_string(),
+        PlanNode::Project { .. } => "Project".to_string(),
+    }
+}
+
+/// the conditions `node` checks, each with what kind of condition it is
+fn conditions(node: &PlanNode) -> Vec<(&'static str, Expr)> {
+    match node {
+        PlanNode::SeqScan { filter, .. } => filter.iter().map(|filter| ("Filter", filter.clone())).collect(),
+        PlanNode::IndexScan { table, index, prefix, lower, upper, filter, .. } => {
+            // the key columns the prefix and bounds are on, as conditions on those columns
+            let column = |i: usize| Expr::column(&table.columns[index.columns[i].position].name);
+            let mut keys: Vec<Expr> = prefix.iter().enumerate().map(|(i, value)| Expr::binary(column(i), BinaryOp::Eq, value.clone())).collect();
+            for (bound, inclusive, exclusive) in [(lower, BinaryOp::GtEq, BinaryOp::Gt), (upper, BinaryOp::LtEq, BinaryOp::Lt)] {
+                match bound {
+                    Bound::Included(value) => keys.push(Expr::binary(column(prefix.len()), inclusive, value.clone())),
+                    Bound::Excluded(value) => keys.push(Expr::binary(column(prefix.len()), exclusive, value.clone())),
+                    Bound::Unbounded => {}
+                }
+            }
+            let mut conditions = Vec::new();
+            if let Some(keys) = keys.into_iter().reduce(|left, right| Expr::binary(left, BinaryOp::And, right)) {
+                conditions.push(("Index Cond", keys));
+            }
+            conditions.extend(filter.iter().map(|filter| ("Filter", filter.clone())));
+            conditions
+        }
+        PlanNode::Filter { predicate, .. } => vec![("Filter", predicate.clone())],
+        PlanNode::NestedLoopJoin { condition, .. } => condition.iter().map(|condition| ("Join Filter", condition.clone())).collect(),
+        PlanNode::IndexNestedLoopJoin { table, alias, index, outer_keys, filter, condition, .. } => {
+            // the key columns looked up, as qualified columns equal to the outer values
+            let keys = outer_keys.iter().zip(&index.columns).map(|(outer_key, column)| {
+                let inner = Expr::Column { table: Some(alias.clone()), name: table.columns[column.position].name.clone() };
+                Expr::binary(inner, BinaryOp::Eq, outer_key.clone())
+            });
+            let mut conditions = vec![("Index Cond", keys.reduce(|left, right| Expr::binary(left, BinaryOp::And, right)).unwrap())];
+            conditions.extend(filter.iter().map(|filter| ("Filter", filter.clone())));
+            conditions.extend(condition.iter().map(|condition| ("Join Filter", condition.clone())));
+            conditions
+        }
+        PlanNode::HashJoin { outer_keys, inner_keys, condition, .. } => {
+            let keys = outer_keys.iter().zip(inner_keys).map(|(outer, inner)| Expr::binary(outer.clone(), BinaryOp::Eq, inner.clone()));
+            let mut conditions = vec![("Hash Cond", keys.reduce(|left, right| Expr::binary(left, BinaryOp::And, right)).unwrap())];
+            conditions.extend(condition.iter().map(|condition| ("Join Filter", condition.clone())));
+            conditions
+        }
+        PlanNode::OneRow | PlanNode::HashAggregate { .. } | PlanNode::Sort { .. } | PlanNode::TopN { .. } | PlanNode::Limit { .. } | PlanNode::Project { .. } => {
+            Vec::new()
+        }
+    }
+}
+
+/// the keys a `Sort` or `TopN` node orders by, written the way `ORDER BY` takes them
+fn sort_keys(node: &PlanNode) -> Option<String> {
+    let (PlanNode::Sort { keys, .. } | PlanNode::TopN { keys, .. }) = node else {
+        return None;
+    };
+    let keys: Vec<String> = keys
+        .iter()
+        .map(|key| {
+            let mut key_text = key.expr.to_string();
+            if key.order.descending {
+                key_text += " DESC";
+            }
+            // NULLs go last going up and first going down unless told otherwise
+            if key.order.nulls_first != key.order.descending {
+                key_text += if key.order.nulls_first { " NULLS FIRST" } else { " NULLS LAST" };
+            }
+            key_text
+        })
+        .collect();
+    Some(keys.join(", "))
+}
+
+/// `value` written the way a query would
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::catalog::{IndexColumn, IndexInfo, IndexKind, TableInfo};
+    use crate::index::KeyOrder;
+    use crate::planner::SortKey;
+    use crate::sql::Literal;
+    use crate::types::{Column, DataType};
+    use std::time::Duration;
+
+    #[test]
+    fn test_index_scans() {
+        let table = TableInfo {
+            name: "scores".into(),
+            columns: vec![Column::new("player", DataType::Varchar(None)), Column::new("game", DataType::Int), Column::new("score", DataType::BigInt)],
+            root_page_id: 1,
+            indexes: Vec::new(),
+            statistics_page_id: None,
+            constraints: Default::default(),
+        };
+        let index = IndexInfo { name: "scores_game_score_idx".into(), columns: vec![IndexColumn::new(1), IndexColumn::new(2)], kind: IndexKind::NonUnique, root_page_id: 2 };
+        let plan = PhysicalPlan {
+            node: PlanNode::IndexScan {
+                table,
+                alias: "scores".into(),
+                columns: vec![0, 1, 2],
+                index,
+                prefix: vec![Expr::Literal(Literal::Integer(7))],
+                lower: Bound::Excluded(Expr::Literal(Literal::Integer(10))),
+                upper: Bound::Included(Expr::Parameter(1)),
+                filter: Some(Expr::binary(Expr::column("player"), BinaryOp::Eq, Expr::Literal(Literal::String("sam".into())))),
+            },
+            rows: 2.0,
+            cost: 12.345,
+        };
+        assert_eq!(
+            explain(&plan, None),
+            [
+                "Index Scan using scores_game_score_idx on scores  (cost=12.35 rows=2)",
+                "  Index Cond: (((game = 7) AND (score > 10)) AND (score <= $1))",
+                "  Filter: (player = 'sam')",
+            ]
+        );
+
+        let profile = Profile { rows: 1, time: Duration::from_micros(1500) };
+        assert_eq!(explain(&plan, Some(&[profile]))[0], "Index Scan using scores_game_score_idx on scores  (cost=12.35 rows=2) (actual time=1.500 ms rows=1)");
+    }
+
+    #[test]
+    fn test_sort_keys() {
+        let key = |name: &str, descending, nulls_first| SortKey { expr: Expr::column(name), order: KeyOrder { descending, nulls_first } };
+        let input = PhysicalPlan { node: PlanNode::OneRow, rows: 1.0, cost: 0.0 };
+        let keys = vec![key("a", false, false), key("b", true, true), key("c", false, true), key("d", true, false)];
+        let plan = PhysicalPlan { node: PlanNode::Sort { input: Box::new(input), keys }, rows: 1.0, cost: 0.5 };
+        assert_eq!(
+            explain(&plan, None),
+            [
+                "Sort  (cost=0.50 rows=1)",
+                "  Sort Key: a, b DESC, c NULLS FIRST, d DESC NULLS LAST",
+                "  ->  Result  (cost=0.00 rows=1)",
+            ]
+        );
+    }
+}
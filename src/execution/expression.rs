@@ -0,0 +1,476 @@
+use super::{ExecutionError, functions};
+use crate::catalog::TableInfo;
+use crate::sql::{BinaryOp, Expr, Literal, UnaryOp};
+use crate::types::temporal::MICROS_PER_DAY;
+use crate::types::{Decimal, Interval, Value};
+use std::cmp::Ordering;
+
+/// The columns an expression can refer to, in the order they appear in the rows
+/// it's evaluated against: those of the tables being read, or none at all for
+/// statements without a `FROM`.
+///
+/// Each column is known by its name and the name of its table (or the table's
+/// alias), which a reference can use to tell apart columns of the same name.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Scope {
+    columns: Vec<ScopeColumn>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ScopeColumn {
+    /// the table the column comes from, `None` for computed columns
+    pub table: Option<String>,
+    pub name: String,
+}
+
+impl Scope {
+    pub fn new(columns: Vec<ScopeColumn>) -> Self {
+        Self { columns }
+    }
+
+    pub fn empty() -> Self {
+        Self::default()
+    }
+
+    pub fn table(table: &TableInfo) -> Self {
+        Self::aliased(table, &table.name)
+    }
+
+    /// Columns of `table`, referred to as `alias`.
+    pub fn aliased(table: &TableInfo, alias: &str) -> Self {
+        let columns = table.columns.iter().map(|column| ScopeColumn { table: Some(alias.to_string()), name: column.name.clone() }).collect();
+        Self { columns }
+    }
+
+    /// The columns of `table` at `positions`, referred to as `alias`.
+    pub fn aliased_columns(table: &TableInfo, alias: &str, positions: &[usize]) -> Self {
+        let columns = positions.iter().map(|&position| ScopeColumn { table: Some(alias.to_string()), name: table.columns[position].name.clone() }).collect();
+        Self { columns }
+    }
+
+    /// Unqualified columns with the given names.
+    pub fn named(names: impl IntoIterator<Item = String>) -> Self {
+        Self { columns: names.into_iter().map(|name| ScopeColumn { table: None, name }).collect() }
+    }
+
+    /// columns of the rows made by appending a row of `other` to a row of this scope
+    pub fn join(&self, other: &Scope) -> Self {
+        Self { columns: self.columns.iter().chain(&other.columns).cloned().collect() }
+    }
+
+    pub fn columns(&self) -> &[ScopeColumn] {
+        &self.columns
+    }
+
+    /// position of the column in the rows of this scope
+    pub fn resolve(&self, qualifier: Option<&str>, name: &str) -> Result<usize, ExecutionError> {
+        let full_name = || match qualifier {
+            Some(qualifier) => format!("{qualifier}.{name}"),
+            None => name.to_string(),
+        };
+
+        let mut matches = self
+            .columns
+            .iter()
+            .enumerate()
+            .filter(|(_, column)| column.name == name && qualifier.is_none_or(|qualifier| column.table.as_deref() == Some(qualifier)));
+        let (position, _) = matches.next().ok_or_else(|| ExecutionError::ColumnNotFound(full_name()))?;
+        if matches.next().is_some() {
+            return Err(ExecutionError::AmbiguousColumn(full_name()));
+        }
+        Ok(position)
+    }
+}
+
+/// Evaluates `expr` against `row`, whose values line up with the columns of `scope`.
+pub fn evaluate(expr: &Expr, scope: &Scope, row: &[Value]) -> Result<Value, ExecutionError> {
+    match expr {
+        Expr::Literal(literal) => Ok(literal_value(literal)),
+        Expr::Column { table, name } => Ok(row[scope.resolve(table.as_deref(), name)?].clone()),
+        // a prepared statement replaces them with the values it runs with
+        Expr::Parameter(number) => Err(ExecutionError::MissingParameter(*number)),
+        Expr::Unary { op, expr } => unary(*op, evaluate(expr, scope, row)?),
+        Expr::Binary { left, op, right } => binary(*op, evaluate(left, scope, row)?, evaluate(right, scope, row)?),
+        Expr::IsNull { expr, negated } => Ok(Value::Bool(evaluate(expr, scope, row)?.is_null() != *negated)),
+        // the planner turns them into columns of the rows an aggregate produces
+        Expr::Aggregate { .. } => Err(ExecutionError::AggregateNotAllowed("expressions of a single row")),
+        Expr::Function { function, args } => {
+            let args = args.iter().map(|arg| evaluate(arg, scope, row)).collect::<Result<Vec<_>, _>>()?;
+            functions::call(*function, &args)
+        }
+    }
+}
+
+/// Whether `expr` holds for `row`, as a `WHERE` clause sees it: `NULL` counts as false.
+pub fn is_true(expr: &Expr, scope: &Scope, row: &[Value]) -> Result<bool, ExecutionError> {
+    match evaluate(expr, scope, row)? {
+        Value::Bool(value) => Ok(value),
+        Value::Null => Ok(false),
+        other => Err(ExecutionError::TypeError(format!("WHERE clause must be a boolean, not {other}"))),
+    }
+}
+
+/// integer literals are `INT` when they fit and `BIGINT` otherwise, decimal ones `NUMERIC`
+fn literal_value(literal: &Literal) -> Value {
+    match literal {
+        Literal::Integer(value) => match i32::try_from(*value) {
+            Ok(value) => Value::Int(value),
+            Err(_) => Value::BigInt(*value),
+        },
+        Literal::Decimal(value) => Value::Numeric(*value),
+        Literal::String(value) => Value::Varchar(value.clone()),
+        Literal::Boolean(value) => Value::Bool(*value),
+        Literal::Null => Value::Null,
+        Literal::Date(days) => Value::Date(*days),
+        Literal::Time(micros) => Value::Time(*micros),
+        Literal::Timestamp(micros) => Value::Timestamp(*micros),
+        Literal::Interval(interval) => Value::Interval(*interval),
+    }
+}
+
+/// the literal `value` is the value of
+pub(super) fn value_literal(value: &Value) -> Literal {
+    match value {
+        Value::Null => Literal::Null,
+        Value::Int(value) => Literal::Integer(*value as i64),
+        Value::BigInt(value) => Literal::Integer(*value),
+        Value::Varchar(value) => Literal::String(value.clone()),
+        Value::Bool(value) => Literal::Boolean(*value),
+        Value::Numeric(value) => Literal::Decimal(*value),
+        Value::Date(days) => Literal::Date(*days),
+        Value::Time(micros) => Literal::Time(*micros),
+        Value::Timestamp(micros) => Literal::Timestamp(*micros),
+        Value::Interval(interval) => Literal::Interval(*interval),
+    }
+}
+
+fn unary(op: UnaryOp, value: Value) -> Result<Value, ExecutionError> {
+    match (op, value) {
+        (_, Value::Null) => Ok(Value::Null),
+        (UnaryOp::Not, Value::Bool(value)) => Ok(Value::Bool(!value)),
+        (UnaryOp::Minus, Value::Int(value)) => value.checked_neg().map(Value::Int).ok_or(ExecutionError::NumericOverflow),
+        (UnaryOp::Minus, Value::BigInt(value)) => value.checked_neg().map(Value::BigInt).ok_or(ExecutionError::NumericOverflow),
+        (UnaryOp::Minus, Value::Numeric(value)) => Ok(Value::Numeric(-value)),
+        (UnaryOp::Minus, Value::Interval(value)) => value.checked_neg().map(Value::Interval).ok_or(ExecutionError::DatetimeOverflow),
+        (UnaryOp::Not, value) => Err(ExecutionError::TypeError(format!("cannot apply NOT to {value}"))),
+        (UnaryOp::Minus, value) => Err(ExecutionError::TypeError(format!("cannot negate {value}"))),
+    }
+}
+
+fn binary(op: BinaryOp, left: Value, right: Value) -> Result<Value, ExecutionError> {
+    if let BinaryOp::And | BinaryOp::Or = op {
+        return logical(op, &left, &right);
+    }
+    // any other operator is unknown with a NULL operand
+    if left.is_null() || right.is_null() {
+        return Ok(Value::Null);
+    }
+
+    match op {
+        BinaryOp::And | BinaryOp::Or => unreachable!("handled above"),
+        BinaryOp::Eq | BinaryOp::NotEq | BinaryOp::Lt | BinaryOp::LtEq | BinaryOp::Gt | BinaryOp::GtEq => {
+            let ordering = compare(&left, &right).ok_or_else(|| type_error(op, &left, &right))?;
+            let result = match op {
+                BinaryOp::Eq => ordering == Ordering::Equal,
+                BinaryOp::NotEq => ordering != Ordering::Equal,
+                BinaryOp::Lt => ordering == Ordering::Less,
+                BinaryOp::LtEq => ordering != Ordering::Greater,
+                BinaryOp::Gt => ordering == Ordering::Greater,
+                _ => ordering != Ordering::Less,
+            };
+            Ok(Value::Bool(result))
+        }
+        BinaryOp::Plus | BinaryOp::Minus | BinaryOp::Multiply | BinaryOp::Divide | BinaryOp::Modulo => {
+            if let Some(result) = datetime_arithmetic(op, &left, &right) {
+                return result.ok_or(ExecutionError::DatetimeOverflow);
+            }
+            match (&left, &right) {
+                // INT stays INT as long as the result fits, like the operands
+                (Value::Int(a), Value::Int(b)) => {
+                    let result = integer_arithmetic(op, *a as i64, *b as i64)?;
+                    i32::try_from(result).map(Value::Int).map_err(|_| ExecutionError::NumericOverflow)
+                }
+                // exact with a NUMERIC on either side, the integer taken as one
+                (Value::Numeric(_), _) | (_, Value::Numeric(_)) => match (left.to_decimal(), right.to_decimal()) {
+                    (Some(a), Some(b)) => Ok(Value::Numeric(decimal_arithmetic(op, a, b)?)),
+                    _ => Err(type_error(op, &left, &right)),
+                },
+                _ => match (as_i64(&left), as_i64(&right)) {
+                    (Some(a), Some(b)) => Ok(Value::BigInt(integer_arithmetic(op, a, b)?)),
+                    _ => Err(type_error(op, &left, &right)),
+                },
+            }
+        }
+    }
+}
+
+/// `AND` and `OR` in three-valued logic, `NULL` being unknown: false and
+/// anything is false, and true or anything true, even if the other is unknown
+fn logical(op: BinaryOp, left: &Value, right: &Value) -> Result<Value, ExecutionError> {
+    // the operand that decides the result on its own, false for AND and true for OR
+    let deciding = op == BinaryOp::Or;
+    match (left, right) {
+        (Value::Bool(a), Value::Bool(b)) => Ok(Value::Bool(if deciding { *a || *b } else { *a && *b })),
+        (Value::Bool(value), Value::Null) | (Value::Null, Value::Bool(value)) if *value == deciding => Ok(Value::Bool(deciding)),
+        (Value::Bool(_) | Value::Null, Value::Bool(_) | Value::Null) => Ok(Value::Null),
+        _ => Err(type_error(op, left, right)),
+    }
+}
+
+fn integer_arithmetic(op: BinaryOp, a: i64, b: i64) -> Result<i64, ExecutionError> {
+    if b == 0 && matches!(op, BinaryOp::Divide | BinaryOp::Modulo) {
+        return Err(ExecutionError::DivisionByZero);
+    }
+    let result = match op {
+        BinaryOp::Plus => a.checked_add(b),
+        BinaryOp::Minus => a.checked_sub(b),
+        BinaryOp::Multiply => a.checked_mul(b),
+        BinaryOp::Divide => a.checked_div(b),
+        _ => a.checked_rem(b),
+    };
+    result.ok_or(ExecutionError::NumericOverflow)
+}
+
+fn decimal_arithmetic(op: BinaryOp, a: Decimal, b: Decimal) -> Result<Decimal, ExecutionError> {
+    if b.is_zero() && matches!(op, BinaryOp::Divide | BinaryOp::Modulo) {
+        return Err(ExecutionError::DivisionByZero);
+    }
+    let result = match op {
+        BinaryOp::Plus => a.checked_add(b),
+        BinaryOp::Minus => a.checked_sub(b),
+        BinaryOp::Multiply => a.checked_mul(b),
+        BinaryOp::Divide => a.checked_div(b),
+        _ => a.checked_rem(b),
+    };
+    result.ok_or(ExecutionError::NumericOverflow)
+}
+
+/// `left op right` with a date, time, timestamp or interval on either side, or
+/// `None` if there isn't one or `op` can't be applied to them. The result is
+/// `None` if it's out of range.
+///
+/// Like PostgreSQL, a date and a number of days make a date, a date or timestamp
+/// and an interval a timestamp, and the difference of two timestamps (or times)
+/// is an interval, but that of two dates a number of days. Times wrap around
+/// midnight, as only the time of day of an interval moves them.
+fn datetime_arithmetic(op: BinaryOp, left: &Value, right: &Value) -> Option<Option<Value>> {
+    let result = match (left, op, right) {
+        // the other way round is the same
+        (Value::Int(_) | Value::BigInt(_), BinaryOp::Plus, Value::Date(_))
+        | (Value::Interval(_), BinaryOp::Plus, Value::Date(_) | Value::Time(_) | Value::Timestamp(_))
+        | (Value::Time(_), BinaryOp::Plus, Value::Date(_))
+        | (Value::Int(_) | Value::BigInt(_), BinaryOp::Multiply, Value::Interval(_)) => return datetime_arithmetic(op, right, left),
+        (Value::Date(days), BinaryOp::Plus | BinaryOp::Minus, Value::Int(_) | Value::BigInt(_)) => {
+            let offset = as_i64(right)?;
+            let offset = if op == BinaryOp::Minus { offset.checked_neg() } else { Some(offset) };
+            offset.and_then(|offset| (*days as i64).checked_add(offset)).and_then(|days| i32::try_from(days).ok()).map(Value::Date)
+        }
+        (Value::Date(a), BinaryOp::Minus, Value::Date(b)) => i32::try_from(*a as i64 - *b as i64).ok().map(Value::Int),
+        (Value::Date(_) | Value::Timestamp(_), BinaryOp::Plus | BinaryOp::Minus, Value::Interval(interval)) => {
+            let interval = if op == BinaryOp::Minus { interval.checked_neg() } else { Some(*interval) };
+            timestamp(left).zip(interval).and_then(|(micros, interval)| interval.add_to(micros)).map(Value::Timestamp)
+        }
+        (Value::Date(_), BinaryOp::Plus, Value::Time(micros)) => timestamp(left).and_then(|midnight| midnight.checked_add(*micros)).map(Value::Timestamp),
+        (Value::Date(_) | Value::Timestamp(_), BinaryOp::Minus, Value::Date(_) | Value::Timestamp(_)) => {
+            // whole days and the rest, never months, as PostgreSQL has it
+            let micros = timestamp(left).zip(timestamp(right)).and_then(|(left, right)| left.checked_sub(right));
+            micros.and_then(|micros| {
+                let days = i32::try_from(micros / MICROS_PER_DAY).ok()?;
+                Some(Value::Interval(Interval { months: 0, days, micros: micros % MICROS_PER_DAY }))
+            })
+        }
+        (Value::Time(micros), BinaryOp::Plus | BinaryOp::Minus, Value::Interval(interval)) => {
+            let offset = interval.micros.rem_euclid(MICROS_PER_DAY);
+            let offset = if op == BinaryOp::Minus { -offset } else { offset };
+            Some(Value::Time((micros + offset).rem_euclid(MICROS_PER_DAY)))
+        }
+        (Value::Time(a), BinaryOp::Minus, Value::Time(b)) => Some(Value::Interval(Interval { micros: a - b, ..Interval::default() })),
+        (Value::Interval(a), BinaryOp::Plus, Value::Interval(b)) => a.checked_add(*b).map(Value::Interval),
+        (Value::Interval(a), BinaryOp::Minus, Value::Interval(b)) => a.checked_sub(*b).map(Value::Interval),
+        (Value::Interval(interval), BinaryOp::Multiply, Value::Int(_) | Value::BigInt(_)) => interval.checked_mul(as_i64(right)?).map(Value::Interval),
+        _ => return None,
+    };
+    Some(result)
+}
+
+/// a date (at midnight) or timestamp as microseconds since 1970-01-01, `None`
+/// for anything else or a date too far off for a timestamp
+fn timestamp(value: &Value) -> Option<i64> {
+    match value {
+        Value::Date(days) => (*days as i64).checked_mul(MICROS_PER_DAY),
+        Value::Timestamp(micros) => Some(*micros),
+        _ => None,
+    }
+}
+
+fn as_i64(value: &Value) -> Option<i64> {
+    match value {
+        Value::Int(value) => Some(*value as i64),
+        Value::BigInt(value) => Some(*value),
+        _ => None,
+    }
+}
+
+/// order of two non-NULL values, `None` if they can't be compared
+pub fn compare(left: &Value, right: &Value) -> Option<Ordering> {
+    match (left, right) {
+        (Value::Varchar(left), Value::Varchar(right)) => Some(left.cmp(right)),
+        (Value::Bool(left), Value::Bool(right)) => Some(left.cmp(right)),
+        // a string is read as a value of the type it's compared with, so `day < '2024-01-01'` works
+        (Value::Varchar(text), other) if is_datetime(other) => compare(&Value::parse(text, other.data_type()?)?, other),
+        (other, Value::Varchar(text)) if is_datetime(other) => compare(other, &Value::parse(text, other.data_type()?)?),
+        // a date is its midnight, as far as timestamps are concerned
+        (Value::Date(_) | Value::Timestamp(_), Value::Date(_) | Value::Timestamp(_)) => Some(midnight_or_moment(left)?.cmp(&midnight_or_moment(right)?)),
+        (Value::Time(left), Value::Time(right)) => Some(left.cmp(right)),
+        (Value::Interval(left), Value::Interval(right)) => Some(left.cmp(right)),
+        (left, right) if is_datetime(left) || is_datetime(right) => None,
+        (Value::Numeric(_), _) | (_, Value::Numeric(_)) => Some(left.to_decimal()?.cmp(&right.to_decimal()?)),
+        _ => Some(as_i64(left)?.cmp(&as_i64(right)?)),
+    }
+}
+
+fn is_datetime(value: &Value) -> bool {
+    matches!(value, Value::Date(_) | Value::Time(_) | Value::Timestamp(_) | Value::Interval(_))
+}
+
+/// microseconds since 1970-01-01 of a date or timestamp, which can't overflow an i128
+fn midnight_or_moment(value: &Value) -> Option<i128> {
+    match value {
+        Value::Date(days) => Some(*days as i128 * MICROS_PER_DAY as i128),
+        Value::Timestamp(micros) => Some(*micros as i128),
+        _ => None,
+    }
+}
+
+fn type_error(op: BinaryOp, left: &Value, right: &Value) -> ExecutionError {
+    ExecutionError::TypeError(format!("cannot apply {op} to {left} and {right}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sql::{SelectItem, Statement, parse_statement};
+    use crate::types::{Column, DataType};
+
+    /// evaluates the single item of `SELECT <sql>`
+    fn eval(sql: &str, scope: &Scope, row: &[Value]) -> Result<Value, ExecutionError> {
+        let Statement::Select(select) = parse_statement(&format!("SELECT {sql}")).unwrap() else {
+            panic!("expected a select");
+        };
+        let SelectItem::Expr { expr, .. } = &select.projection[0] else {
+            panic!("expected an expression");
+        };
+        evaluate(expr, scope, row)
+    }
+
+    #[test]
+    fn test_arithmetic_and_comparison() {
+        let scope = &Scope::empty();
+        assert_eq!(eval("1 + 2 * 3", scope, &[]).unwrap(), Value::Int(7));
+        assert_eq!(eval("7 / 2 - 7 % 2", scope, &[]).unwrap(), Value::Int(2));
+        // widened to BIGINT once an operand is one, or the result doesn't fit
+        assert_eq!(eval("3000000000 + 1", scope, &[]).unwrap(), Value::BigInt(3000000001));
+        assert!(matches!(eval("2147483647 + 1", scope, &[]), Err(ExecutionError::NumericOverflow)));
+        assert!(matches!(eval("1 / 0", scope, &[]), Err(ExecutionError::DivisionByZero)));
+        assert_eq!(eval("'a' < 'b' AND NOT 2 >= 3000000000", scope, &[]).unwrap(), Value::Bool(true));
+        assert!(matches!(eval("1 + 'a'", scope, &[]), Err(ExecutionError::TypeError(_))));
+    }
+
+    #[test]
+    fn test_numeric_arithmetic() {
+        let scope = &Scope::empty();
+        let numeric = |text: &str| Value::Numeric(Decimal::parse(text).unwrap());
+        assert_eq!(eval("0.1 + 0.2 = 0.3", scope, &[]).unwrap(), Value::Bool(true));
+        // integers are taken as decimals, and results keep the places of the operands
+        assert_eq!(eval("1.50 * 2 - 1", scope, &[]).unwrap(), numeric("2.00"));
+        assert_eq!(eval("-(7 / 2.0)", scope, &[]).unwrap(), numeric("-3.5000000000000000"));
+        assert_eq!(eval("3000000000 < 3000000000.5 AND 2 = 2.000", scope, &[]).unwrap(), Value::Bool(true));
+        assert!(matches!(eval("1.5 % 0", scope, &[]), Err(ExecutionError::DivisionByZero)));
+        assert!(matches!(eval("99999999999999999999999999999999999999 + 1", scope, &[]), Err(ExecutionError::NumericOverflow)));
+        assert!(matches!(eval("1.5 + 'a'", scope, &[]), Err(ExecutionError::TypeError(_))));
+    }
+
+    #[test]
+    fn test_datetime_arithmetic_and_comparison() {
+        let scope = &Scope::empty();
+        let text = |sql: &str| eval(sql, scope, &[]).unwrap().to_string();
+        assert_eq!(text("DATE '2024-02-28' + 2"), "2024-03-01");
+        assert_eq!(text("DATE '2024-03-01' - DATE '2023-03-01'"), "366");
+        assert_eq!(text("DATE '2024-01-31' + INTERVAL '1 mon'"), "2024-02-29 00:00:00");
+        assert_eq!(text("INTERVAL '90 minutes' + TIMESTAMP '2024-12-31 23:00'"), "2025-01-01 00:30:00");
+        assert_eq!(text("TIMESTAMP '2024-03-01 06:00' - TIMESTAMP '2024-02-28 12:00'"), "1 day 18:00:00");
+        assert_eq!(text("DATE '2024-03-01' + TIME '06:30'"), "2024-03-01 06:30:00");
+        // times wrap around midnight
+        assert_eq!(text("TIME '23:00' + INTERVAL '2 hours'"), "01:00:00");
+        assert_eq!(text("-(INTERVAL '1 day' * 3 - INTERVAL '1 hour')"), "-3 days 01:00:00");
+
+        // strings are read as dates and times when compared with them, and dates are their midnights
+        assert_eq!(eval("DATE '2024-01-01' < '2024-01-02' AND '12:00' > TIME '09:30'", scope, &[]).unwrap(), Value::Bool(true));
+        assert_eq!(eval("DATE '2024-01-01' = TIMESTAMP '2024-01-01 00:00'", scope, &[]).unwrap(), Value::Bool(true));
+        assert_eq!(eval("INTERVAL '1 mon' = INTERVAL '30 days'", scope, &[]).unwrap(), Value::Bool(true));
+        assert!(matches!(eval("DATE '2024-01-01' < 'soon'", scope, &[]), Err(ExecutionError::TypeError(_))));
+        assert!(matches!(eval("DATE '2024-01-01' + TIME '01:00' < 1", scope, &[]), Err(ExecutionError::TypeError(_))));
+        assert!(matches!(eval("DATE '2024-01-01' * 2", scope, &[]), Err(ExecutionError::TypeError(_))));
+        assert!(matches!(eval("DATE '2024-01-01' + 3000000000", scope, &[]), Err(ExecutionError::DatetimeOverflow)));
+    }
+
+    #[test]
+    fn test_nulls_propagate() {
+        let scope = &Scope::empty();
+        assert_eq!(eval("NULL + 1", scope, &[]).unwrap(), Value::Null);
+        assert_eq!(eval("NULL = NULL", scope, &[]).unwrap(), Value::Null);
+        assert_eq!(eval("-NULL IS NULL", scope, &[]).unwrap(), Value::Bool(true));
+        assert_eq!(eval("1 IS NOT NULL", scope, &[]).unwrap(), Value::Bool(true));
+        assert_eq!(eval("NOT NULL", scope, &[]).unwrap(), Value::Null);
+        assert_eq!(eval("(NULL > 1) IS NULL", scope, &[]).unwrap(), Value::Bool(true));
+        assert_eq!(eval("DATE '2024-01-01' + NULL", scope, &[]).unwrap(), Value::Null);
+    }
+
+    #[test]
+    fn test_three_valued_logic() {
+        let scope = &Scope::empty();
+        let truth = |sql: &str| eval(sql, scope, &[]).unwrap();
+        // the truth tables, NULL being unknown
+        for (left, right, and, or) in [
+            ("TRUE", "NULL", Value::Null, Value::Bool(true)),
+            ("FALSE", "NULL", Value::Bool(false), Value::Null),
+            ("NULL", "TRUE", Value::Null, Value::Bool(true)),
+            ("NULL", "FALSE", Value::Bool(false), Value::Null),
+            ("NULL", "NULL", Value::Null, Value::Null),
+        ] {
+            assert_eq!(truth(&format!("{left} AND {right}")), and, "{left} AND {right}");
+            assert_eq!(truth(&format!("{left} OR {right}")), or, "{left} OR {right}");
+        }
+        assert_eq!(truth("NOT (NULL = 1 AND 1 = 2)"), Value::Bool(true));
+        assert_eq!(truth("1 = 1 OR NULL < 2"), Value::Bool(true));
+        // unknown isn't a type of its own, so the other side still has to be a boolean
+        assert!(matches!(eval("NULL AND 1", scope, &[]), Err(ExecutionError::TypeError(_))));
+        assert!(matches!(eval("FALSE AND 'x'", scope, &[]), Err(ExecutionError::TypeError(_))));
+    }
+
+    #[test]
+    fn test_column_references() {
+        let table = TableInfo {
+            name: "users".into(),
+            columns: vec![Column::new("id", DataType::Int), Column::new("name", DataType::Varchar(None))],
+            root_page_id: 1,
+            indexes: Vec::new(),
+            statistics_page_id: None,
+            constraints: Default::default(),
+        };
+        let row = [Value::Int(4), Value::Varchar("Pippin".into())];
+        let scope = &Scope::table(&table);
+        assert_eq!(eval("users.id * 2", scope, &row).unwrap(), Value::Int(8));
+        assert_eq!(eval("name = 'Pippin'", scope, &row).unwrap(), Value::Bool(true));
+        assert!(matches!(eval("age", scope, &row), Err(ExecutionError::ColumnNotFound(name)) if name == "age"));
+        assert!(matches!(eval("orders.id", scope, &row), Err(ExecutionError::ColumnNotFound(name)) if name == "orders.id"));
+        assert!(matches!(eval("id", &Scope::empty(), &[]), Err(ExecutionError::ColumnNotFound(name)) if name == "id"));
+
+        // a self join sees two of each column, told apart by alias
+        let joined = Scope::aliased(&table, "a").join(&Scope::aliased(&table, "b"));
+        let rows = [Value::Int(4), Value::Varchar("Pippin".into()), Value::Int(5), Value::Varchar("Merry".into())];
+        assert_eq!(eval("b.id - a.id", &joined, &rows).unwrap(), Value::Int(1));
+        assert!(matches!(eval("name", &joined, &rows), Err(ExecutionError::AmbiguousColumn(name)) if name == "name"));
+        assert!(matches!(eval("users.id", &joined, &rows), Err(ExecutionError::ColumnNotFound(_))));
+    }
+}
@@ -0,0 +1,194 @@
+use super::ExecutionError;
+use crate::sql::Function;
+use crate::types::temporal::{self, MICROS_PER_DAY, MICROS_PER_HOUR, MICROS_PER_MINUTE, MICROS_PER_SECOND};
+use crate::types::{Decimal, Value};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Calls `function` with `args`, as many as it takes.
+pub(super) fn call(function: Function, args: &[Value]) -> Result<Value, ExecutionError> {
+    match (function, args) {
+        // whenever it's called, not once per statement or transaction like PostgreSQL's
+        (Function::Now, []) => {
+            let micros = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |since| since.as_micros() as i64);
+            Ok(Value::Timestamp(micros))
+        }
+        (Function::DateTrunc | Function::DatePart, [_, Value::Null] | [Value::Null, _]) => Ok(Value::Null),
+        (Function::DateTrunc, [Value::Varchar(unit), value]) => {
+            let micros = timestamp(value).ok_or_else(|| argument_error(function, value))?;
+            date_trunc(&unit.to_ascii_lowercase(), micros).map(Value::Timestamp)
+        }
+        (Function::DatePart, [Value::Varchar(field), value]) => date_part(&field.to_ascii_lowercase(), value).map(Value::Numeric),
+        (_, [unit, _]) => Err(ExecutionError::TypeError(format!("{} takes the name of a unit, not {unit}", function.name()))),
+        _ => Err(ExecutionError::TypeError(format!("wrong number of arguments for {}", function.name()))),
+    }
+}
+
+fn argument_error(function: Function, value: &Value) -> ExecutionError {
+    ExecutionError::TypeError(format!("{} cannot take {value}", function.name()))
+}
+
+fn unit_error(unit: &str, value: &Value) -> ExecutionError {
+    let type_name = value.data_type().map_or("NULL".to_string(), |data_type| data_type.to_string());
+    ExecutionError::TypeError(format!("unit \"{unit}\" is not supported for {type_name}"))
+}
+
+/// a date or timestamp as microseconds since 1970-01-01
+fn timestamp(value: &Value) -> Option<i64> {
+    match value {
+        Value::Date(days) => (*days as i64).checked_mul(MICROS_PER_DAY),
+        Value::Timestamp(micros) => Some(*micros),
+        _ => None,
+    }
+}
+
+/// `micros` with everything smaller than `unit` zeroed, weeks starting on Monday
+fn date_trunc(unit: &str, micros: i64) -> Result<i64, ExecutionError> {
+    let step = match unit {
+        "microseconds" => 1,
+        "milliseconds" => 1000,
+        "second" => MICROS_PER_SECOND,
+        "minute" => MICROS_PER_MINUTE,
+        "hour" => MICROS_PER_HOUR,
+        "day" => MICROS_PER_DAY,
+        _ => {
+            let days = micros.div_euclid(MICROS_PER_DAY) as i32;
+            let (year, month, _) = temporal::civil_from_days(days);
+            let first_of = |year: i32, month: u32| temporal::days_from_civil(year, month, 1);
+            let days = match unit {
+                "week" => Some(days - iso_weekday(days) as i32 + 1),
+                "month" => first_of(year, month),
+                "quarter" => first_of(year, (month - 1) / 3 * 3 + 1),
+                "year" => first_of(year, 1),
+                "decade" => first_of(year - year.rem_euclid(10), 1),
+                // centuries and millennia start in their year 1, 2001 rather than 2000
+                "century" => first_of((year - 1).div_euclid(100) * 100 + 1, 1),
+                "millennium" => first_of((year - 1).div_euclid(1000) * 1000 + 1, 1),
+                _ => return Err(unit_error(unit, &Value::Timestamp(micros))),
+            };
+            return days.and_then(|days| (days as i64).checked_mul(MICROS_PER_DAY)).ok_or(ExecutionError::DatetimeOverflow);
+        }
+    };
+    Ok(micros - micros.rem_euclid(step))
+}
+
+/// the field of `value` a date, time, timestamp or interval called `field`, like
+/// PostgreSQL's `date_part`: seconds come with their fraction
+fn date_part(field: &str, value: &Value) -> Result<Decimal, ExecutionError> {
+    // time fields, of microseconds into the day or of an interval's microseconds
+    let time_part = |micros: i64| -> Option<Decimal> {
+        let part = match field {
+            "microseconds" => Decimal::from(micros % (60 * MICROS_PER_SECOND)),
+            "milliseconds" => Decimal::new((micros % (60 * MICROS_PER_SECOND)) as i128, 3)?,
+            "second" => Decimal::new((micros % (60 * MICROS_PER_SECOND)) as i128, 6)?,
+            "minute" => Decimal::from(micros / MICROS_PER_MINUTE % 60),
+            "hour" => Decimal::from(micros / MICROS_PER_HOUR),
+            _ => return None,
+        };
+        Some(part)
+    };
+    let part = match value {
+        Value::Time(micros) if field == "epoch" => Decimal::new(*micros as i128, 6),
+        Value::Time(micros) => time_part(*micros),
+        Value::Interval(interval) => match field {
+            // a month as 30 days, like when intervals are compared
+            "epoch" => Decimal::new(interval.span(), 6),
+            "day" => Some(Decimal::from(interval.days)),
+            "month" => Some(Decimal::from(interval.months % 12)),
+            "year" => Some(Decimal::from(interval.months / 12)),
+            // hours can go past a day in an interval
+            _ => time_part(interval.micros),
+        },
+        Value::Date(_) | Value::Timestamp(_) => {
+            let micros = timestamp(value).ok_or(ExecutionError::DatetimeOverflow)?;
+            let (days, time) = (micros.div_euclid(MICROS_PER_DAY), micros.rem_euclid(MICROS_PER_DAY));
+            let days = days as i32;
+            let (year, month, day) = temporal::civil_from_days(days);
+            let whole = |part: i64| Some(Decimal::from(part));
+            match field {
+                "epoch" => Decimal::new(micros as i128, 6),
+                "day" => whole(day as i64),
+                "month" => whole(month as i64),
+                "quarter" => whole((month as i64 - 1) / 3 + 1),
+                "year" => whole(year as i64),
+                "decade" => whole(year.div_euclid(10) as i64),
+                "century" => whole((year - 1).div_euclid(100) as i64 + 1),
+                "millennium" => whole((year - 1).div_euclid(1000) as i64 + 1),
+                "dow" => whole(iso_weekday(days) as i64 % 7),
+                "isodow" => whole(iso_weekday(days) as i64),
+                "doy" => temporal::days_from_civil(year, 1, 1).and_then(|first| whole((days - first) as i64 + 1)),
+                "week" => {
+                    // the week of the ISO year its Thursday is in, the first having January 4th
+                    let thursday = days - iso_weekday(days) as i32 + 4;
+                    let (iso_year, _, _) = temporal::civil_from_days(thursday);
+                    temporal::days_from_civil(iso_year, 1, 1).and_then(|first| whole((thursday - first) as i64 / 7 + 1))
+                }
+                _ => time_part(time),
+            }
+        }
+        _ => return Err(argument_error(Function::DatePart, value)),
+    };
+    part.ok_or_else(|| unit_error(field, value))
+}
+
+/// 1 for Monday up to 7 for Sunday, 1970-01-01 having been a Thursday
+fn iso_weekday(days: i32) -> u32 {
+    (days as i64 + 3).rem_euclid(7) as u32 + 1
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::DataType;
+
+    fn parse(text: &str, data_type: DataType) -> Value {
+        Value::parse(text, data_type).unwrap()
+    }
+
+    fn part(field: &str, value: &Value) -> String {
+        call(Function::DatePart, &[Value::Varchar(field.into()), value.clone()]).unwrap().to_string()
+    }
+
+    #[test]
+    fn test_date_trunc() {
+        let moment = parse("2024-08-15 13:45:30.25", DataType::Timestamp);
+        let trunc = |unit: &str| call(Function::DateTrunc, &[Value::Varchar(unit.into()), moment.clone()]).unwrap().to_string();
+        assert_eq!(trunc("second"), "2024-08-15 13:45:30");
+        assert_eq!(trunc("HOUR"), "2024-08-15 13:00:00");
+        assert_eq!(trunc("day"), "2024-08-15 00:00:00");
+        // a Thursday, so the Monday before
+        assert_eq!(trunc("week"), "2024-08-12 00:00:00");
+        assert_eq!(trunc("quarter"), "2024-07-01 00:00:00");
+        assert_eq!(trunc("century"), "2001-01-01 00:00:00");
+        let date = parse("1969-12-31", DataType::Date);
+        assert_eq!(call(Function::DateTrunc, &[Value::Varchar("month".into()), date]).unwrap().to_string(), "1969-12-01 00:00:00");
+        assert!(matches!(call(Function::DateTrunc, &[Value::Varchar("fortnight".into()), moment]), Err(ExecutionError::TypeError(_))));
+        assert_eq!(call(Function::DateTrunc, &[Value::Varchar("day".into()), Value::Null]).unwrap(), Value::Null);
+    }
+
+    #[test]
+    fn test_date_part() {
+        let moment = parse("2024-12-30 13:45:30.25", DataType::Timestamp);
+        assert_eq!(part("year", &moment), "2024");
+        assert_eq!(part("second", &moment), "30.250000");
+        assert_eq!(part("milliseconds", &moment), "30250.000");
+        assert_eq!(part("dow", &moment), "1");
+        assert_eq!(part("doy", &moment), "365");
+        // already in the first week of 2025
+        assert_eq!(part("week", &moment), "1");
+        assert_eq!(part("epoch", &parse("1970-01-02", DataType::Date)), "86400.000000");
+        assert_eq!(part("minute", &parse("07:08:09", DataType::Time)), "8");
+        let interval = parse("1 year 2 mons 3 days 27:00:00", DataType::Interval);
+        assert_eq!(part("month", &interval), "2");
+        assert_eq!(part("hour", &interval), "27");
+        assert!(matches!(call(Function::DatePart, &[Value::Varchar("year".into()), parse("07:08:09", DataType::Time)]), Err(ExecutionError::TypeError(_))));
+        assert!(matches!(call(Function::DatePart, &[Value::Varchar("year".into()), Value::Int(1)]), Err(ExecutionError::TypeError(_))));
+    }
+
+    #[test]
+    fn test_now_is_recent() {
+        let Value::Timestamp(now) = call(Function::Now, &[]).unwrap() else {
+            panic!("now() should be a timestamp");
+        };
+        assert!(now > temporal::parse_timestamp("2024-01-01").unwrap());
+    }
+}
@@ -0,0 +1,1526 @@
+mod expression;
+pub use expression::{Scope, ScopeColumn, compare, evaluate, is_true};
+
+mod functions;
+
+mod access;
+pub use access::AccessLog;
+
+mod copy;
+use copy::{CsvReader, value_text, write_record};
+
+mod explain;
+use explain::explain;
+
+mod operators;
+use operators::TableScan;
+
+mod prepared;
+pub use prepared::PreparedStatement;
+use prepared::{bind_plan, bind_statement, parameter_types};
+
+mod table;
+use table::{TableWriter, index_key};
+
+use crate::catalog::{Catalog, CatalogError, CheckConstraint, Constraints, IndexColumn, IndexKind, TableInfo};
+use crate::index::{EntrySorter, IndexError, KeyOrder};
+use crate::planner::{PhysicalPlan, Planner, TableStatistics, column_references, expr_type};
+use crate::sql::{Analyze, ColumnConstraint, Copy, CopyDirection, CopyOptions, CreateIndex, CreateTable, Delete, Explain, Expr, Insert, Select, Statement, TableConstraint, Update, Vacuum};
+use crate::storage::{BufferPool, FreeSpaceMap, HeapError, HeapFile, RecordId};
+use crate::transaction::Transaction;
+use crate::types::temporal::MICROS_PER_DAY;
+use crate::types::{Column, DataType, Tuple, TupleError, Value};
+use crate::vacuum::{self, VacuumStats};
+use crate::wal::LogManager;
+use std::fs::File;
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+/// bytes of rows an operator keeps in memory by default before it spills them to disk
+pub const DEFAULT_WORK_MEMORY: usize = 4 * 1024 * 1024;
+
+/// most sorted runs a sort merges at once; more than that are merged in
+/// several passes, so a sort never has more than this many files open
+pub const MERGE_FAN_IN: usize = 32;
+
+/// the one column of the rows `EXPLAIN` returns
+const QUERY_PLAN_COLUMN: &str = "QUERY PLAN";
+
+#[derive(Debug)]
+pub enum ExecutionError {
+    TableNotFound(String),
+    ColumnNotFound(String),
+    /// an unqualified column name matches columns of more than one table
+    AmbiguousColumn(String),
+    /// a table (or alias) appears more than once in a `FROM` clause
+    DuplicateTable(String),
+    /// a query reads more than `planner::MAX_JOIN_TABLES` tables
+    TooManyTables,
+    /// a column is named more than once in an `INSERT` column list
+    DuplicateColumn(String),
+    /// an `INSERT` row has a different number of values than there are target columns
+    ValueCountMismatch { expected: usize, found: usize },
+    /// `SELECT *` without a table to take the columns from
+    WildcardWithoutTable,
+    /// an aggregate function where only a single row is seen, like a `WHERE` clause
+    AggregateNotAllowed(&'static str),
+    /// a column a grouped query uses outside of an aggregate function, that it isn't grouped by
+    UngroupedColumn(String),
+    /// `ORDER BY` a position that isn't that of a selected expression
+    InvalidOrderByPosition(i64),
+    /// a statement was run without a value for its parameter `$n`
+    MissingParameter(usize),
+    /// a prepared statement was run with a different number of values than it has parameters
+    ParameterCountMismatch { expected: usize, found: usize },
+    TypeError(String),
+    DivisionByZero,
+    NumericOverflow,
+    /// text that should be a date, time, timestamp or interval (of `data_type`) but isn't
+    InvalidDatetime { data_type: DataType, text: String },
+    /// a date, time or timestamp out of the range they can have, or an interval too long
+    DatetimeOverflow,
+    /// a row would have the same key as another in the unique index (or primary key) `constraint`
+    UniqueViolation { constraint: String },
+    /// a row would have a NULL in `column`, which doesn't allow it
+    NullViolation { column: String },
+    /// a row would make the `CHECK` constraint `constraint` false
+    CheckViolation { constraint: String },
+    /// a `DEFAULT` or `CHECK` in a `CREATE TABLE` that can't be one, and why
+    InvalidConstraint(String),
+    CatalogError(CatalogError),
+    HeapError(HeapError),
+    IndexError(IndexError),
+    TupleError(TupleError),
+    /// `BEGIN`, `COMMIT`, `ROLLBACK` or a savepoint statement, which only a
+    /// `Connection` can run, as it starts and ends the transactions
+    TransactionControl,
+    /// line `line` of a CSV file `COPY` reads can't be made into a row
+    InvalidCsv { line: u64, message: String },
+    /// reading or writing rows spilled to disk, or the file of a `COPY`, failed
+    IoError(std::io::Error),
+}
+
+impl std::fmt::Display for ExecutionError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ExecutionError::TableNotFound(name) => write!(f, "Table {name} does not exist"),
+            ExecutionError::ColumnNotFound(name) => write!(f, "Column {name} does not exist"),
+            ExecutionError::AmbiguousColumn(name) => write!(f, "Column reference {name} is ambiguous"),
+            ExecutionError::DuplicateTable(name) => write!(f, "Table name {name} is specified more than once"),
+            ExecutionError::TooManyTables => write!(f, "A query can read at most {} tables", crate::planner::MAX_JOIN_TABLES),
+            ExecutionError::DuplicateColumn(name) => write!(f, "Column {name} is specified more than once"),
+            ExecutionError::ValueCountMismatch { expected, found } => write!(f, "Expected {expected} values, found {found}"),
+            ExecutionError::WildcardWithoutTable => write!(f, "SELECT * requires a FROM clause"),
+            ExecutionError::AggregateNotAllowed(clause) => write!(f, "Aggregate functions are not allowed in {clause}"),
+            ExecutionError::UngroupedColumn(name) => {
+                write!(f, "Column {name} must appear in the GROUP BY clause or be used in an aggregate function")
+            }
+            ExecutionError::InvalidOrderByPosition(position) => write!(f, "ORDER BY position {position} is not in select list"),
+            ExecutionError::MissingParameter(number) => write!(f, "There is no parameter ${number}"),
+            ExecutionError::ParameterCountMismatch { expected, found } => write!(f, "Expected {expected} parameters, found {found}"),
+            ExecutionError::TypeError(message) => write!(f, "Type error: {message}"),
+            ExecutionError::DivisionByZero => write!(f, "Division by zero"),
+            ExecutionError::NumericOverflow => write!(f, "Numeric value out of range"),
+            ExecutionError::InvalidDatetime { data_type, text } => write!(f, "Invalid {data_type} value: '{text}'"),
+            ExecutionError::DatetimeOverflow => write!(f, "Date or time value out of range"),
+            ExecutionError::UniqueViolation { constraint } => write!(f, "Duplicate key violates unique constraint {constraint}"),
+            ExecutionError::NullViolation { column } => write!(f, "Column {column} does not allow NULL"),
+            ExecutionError::CheckViolation { constraint } => write!(f, "Row violates check constraint {constraint}"),
+            ExecutionError::InvalidConstraint(message) => write!(f, "Invalid constraint: {message}"),
+            ExecutionError::CatalogError(error) => write!(f, "Catalog error: {error}"),
+            ExecutionError::HeapError(error) => write!(f, "Heap error: {error}"),
+            ExecutionError::IndexError(error) => write!(f, "Index error: {error}"),
+            ExecutionError::TupleError(error) => write!(f, "Tuple error: {error}"),
+            ExecutionError::TransactionControl => write!(f, "Transaction statements can only be run by a connection"),
+            ExecutionError::InvalidCsv { line, message } => write!(f, "Invalid CSV on line {line}: {message}"),
+            ExecutionError::IoError(error) => write!(f, "I/O error: {error}"),
+        }
+    }
+}
+
+impl std::error::Error for ExecutionError {}
+
+impl From<CatalogError> for ExecutionError {
+    fn from(error: CatalogError) -> Self {
+        ExecutionError::CatalogError(error)
+    }
+}
+
+impl From<HeapError> for ExecutionError {
+    fn from(error: HeapError) -> Self {
+        ExecutionError::HeapError(error)
+    }
+}
+
+impl From<IndexError> for ExecutionError {
+    fn from(error: IndexError) -> Self {
+        ExecutionError::IndexError(error)
+    }
+}
+
+impl From<TupleError> for ExecutionError {
+    fn from(error: TupleError) -> Self {
+        ExecutionError::TupleError(error)
+    }
+}
+
+impl From<std::io::Error> for ExecutionError {
+    fn from(error: std::io::Error) -> Self {
+        ExecutionError::IoError(error)
+    }
+}
+
+/// What running a statement produced.
+#[derive(Debug, Clone, PartialEq)]
+pub enum QueryResult {
+    /// rows returned by a `SELECT`, with the names of their columns
+    Rows { columns: Vec<String>, rows: Vec<Vec<Value>> },
+    /// number of rows inserted, updated or deleted (0 for DDL)
+    Affected(usize),
+}
+
+/// Runs parsed statements against the tables in a catalog.
+///
+/// Every statement runs on behalf of a `Transaction` that the caller begins and
+/// commits (or aborts, if the statement fails). Queries are planned by the
+/// `Planner`, which decides how each table is read and in what order tables are
+/// joined; `UPDATE` and `DELETE` find their rows the same way. The planner
+/// goes by the statistics `ANALYZE` records in the catalog, and `EXPLAIN`
+/// shows the plan it picks. Rows are decoded
+/// with the schema recorded in the catalog. Operators keep up to a set amount
+/// of rows in memory each, and a hash join, sort or aggregate whose rows don't
+/// fit spills them to temporary files.
+///
+/// Every change to a table is made to its indexes too. Unique indexes, which
+/// back `UNIQUE` and `PRIMARY KEY` constraints, turn a row with a duplicate key
+/// into an `ExecutionError::UniqueViolation`; the caller rolls the statement
+/// back, indexes included. `NOT NULL` and `CHECK` constraints are checked on
+/// every row written the same way, and columns a row is inserted without get
+/// their `DEFAULT`. `CREATE INDEX` sorts the entries for the rows a
+/// table already has and bulk loads them into the new index, and `COPY ... FROM`
+/// does the same for the indexes of a table it loads into while empty.
+///
+/// # Examples
+///
+/// ```
+/// use gondor_rdbms::catalog::Catalog;
+/// use gondor_rdbms::execution::{Executor, QueryResult};
+/// use gondor_rdbms::sql::parse_statement;
+/// use gondor_rdbms::storage::{BufferPool, DiskManager};
+/// use gondor_rdbms::transaction::Transaction;
+/// use gondor_rdbms::types::Value;
+/// use std::sync::{Arc, Mutex};
+///
+/// let dir = tempfile::tempdir().unwrap();
+/// let disk_manager = DiskManager::open(dir.path().join("example.db")).unwrap();
+/// let buffer_pool = Arc::new(Mutex::new(BufferPool::new(disk_manager)));
+/// let mut catalog = Catalog::open(buffer_pool.clone()).unwrap();
+/// let mut executor = Executor::new(buffer_pool, &mut catalog);
+/// let mut txn = Transaction::new(1);
+///
+/// for sql in ["CREATE TABLE numbers (n INT)", "INSERT INTO numbers VALUES (1), (2), (3)"] {
+///     executor.execute(&mut txn, &parse_statement(sql).unwrap()).unwrap();
+/// }
+/// let result = executor.execute(&mut txn, &parse_statement("SELECT n * 10 FROM numbers WHERE n > 1").unwrap()).unwrap();
+/// let QueryResult::Rows { rows, .. } = result else { unreachable!() };
+/// assert_eq!(rows, vec![vec![Value::Int(20)], vec![Value::Int(30)]]);
+/// ```
+pub struct Executor<'a> {
+    buffer_pool: Arc<Mutex<BufferPool>>,
+    /// changes to tables are logged here, if set
+    log_manager: Option<Arc<Mutex<LogManager>>>,
+    /// where inserts look for room in tables and vacuum records it, if set
+    free_space_map: Option<Arc<Mutex<FreeSpaceMap>>>,
+    /// what statements read and write is noted here, if set
+    access_log: Option<Arc<Mutex<AccessLog>>>,
+    catalog: &'a mut Catalog,
+    /// bytes of rows each operator of a query can keep in memory
+    work_memory: usize,
+}
+
+impl<'a> Executor<'a> {
+    pub fn new(buffer_pool: Arc<Mutex<BufferPool>>, catalog: &'a mut Catalog) -> Self {
+        Self {
+            buffer_pool,
+            log_manager: None,
+            free_space_map: None,
+            access_log: None,
+            catalog,
+            work_memory: DEFAULT_WORK_MEMORY,
+        }
+    }
+
+    /// Lets each operator of a query keep up to `bytes` of rows in memory, beyond
+    /// which hash joins, sorts and aggregates spill them to disk. `DEFAULT_WORK_MEMORY` otherwise.
+    pub fn set_work_memory(&mut self, bytes: usize) {
+        self.work_memory = bytes;
+    }
+
+    /// Logs every change the executor makes to a table to `log_manager`.
+    pub fn set_log_manager(&mut self, log_manager: Arc<Mutex<LogManager>>) {
+        self.log_manager = Some(log_manager);
+    }
+
+    /// Has inserts reuse the room `VACUUM` records in `free_space_map`.
+    pub fn set_free_space_map(&mut self, free_space_map: Arc<Mutex<FreeSpaceMap>>) {
+        self.free_space_map = Some(free_space_map);
+    }
+
+    /// Notes the rows each statement reads and writes in `access_log`.
+    pub fn set_access_log(&mut self, access_log: Arc<Mutex<AccessLog>>) {
+        self.access_log = Some(access_log);
+    }
+
+    pub fn execute(&mut self, txn: &mut Transaction, statement: &Statement) -> Result<QueryResult, ExecutionError> {
+        match statement {
+            Statement::CreateTable(create) => self.create_table(txn, create),
+            Statement::CreateIndex(create) => self.create_index(txn, create),
+            Statement::Insert(insert) => self.insert(txn, insert),
+            Statement::Select(select) => self.select(select),
+            Statement::Update(update) => self.update(txn, update),
+            Statement::Delete(delete) => self.delete(txn, delete),
+            Statement::Analyze(analyze) => self.analyze(txn, analyze),
+            Statement::Vacuum(Vacuum { table }) => {
+                self.vacuum(table.as_deref())?;
+                Ok(QueryResult::Affected(0))
+            }
+            Statement::Explain(explain) => self.explain(explain),
+            Statement::Copy(copy) => self.copy(txn, copy),
+            Statement::Transaction(_) => Err(ExecutionError::TransactionControl),
+        }
+    }
+
+    /// Reads the rows of the CSV in `reader` into `table`, returning how many
+    /// there were. The fields of each line are the values of `columns`, or of
+    /// every column in order if none are listed, and the rest get their defaults.
+    pub fn copy_in(&mut self, txn: &mut Transaction, table: &str, columns: &[String], reader: impl BufRead, options: &CopyOptions) -> Result<usize, ExecutionError> {
+        let mut writer = self.open_table(table)?;
+        let table = writer.info.clone();
+        let targets = targets(&table, columns)?;
+        let mut reader = CsvReader::new(reader, options);
+        reader.skip_header()?;
+        let rows = std::iter::from_fn(|| reader.row(&table, &targets).transpose());
+        writer.load(txn, rows.map(|row| fill_defaults(&table, row?, &targets)))
+    }
+
+    /// Writes the rows of `table` to `writer` as CSV, returning how many there
+    /// were, with the values of `columns` or of every column in order if none are listed.
+    pub fn copy_out(&self, table: &str, columns: &[String], mut writer: impl Write, options: &CopyOptions) -> Result<usize, ExecutionError> {
+        let table = self.table(table)?;
+        let targets = targets(&table, columns)?;
+        if options.header {
+            write_record(&mut writer, targets.iter().map(|&target| Some(table.columns[target].name.as_str())), options)?;
+        }
+        let plan = self.planner().plan_scan(&table, None)?;
+        self.note_reads(&plan)?;
+        let mut scan = TableScan::open(&plan, &self.buffer_pool)?;
+        let mut count = 0;
+        while let Some((_, row)) = scan.next_entry()? {
+            let texts: Vec<Option<String>> = targets.iter().map(|&target| value_text(&row[target])).collect();
+            write_record(&mut writer, texts.iter().map(Option::as_deref), options)?;
+            count += 1;
+        }
+        writer.flush()?;
+        Ok(count)
+    }
+
+    /// Gets `statement` ready to be run by `execute_prepared`, with the types
+    /// of its parameters worked out and, if it's a query, its plan made.
+    pub fn prepare(&self, statement: Statement) -> Result<PreparedStatement, ExecutionError> {
+        let parameter_types = parameter_types(self.catalog, &statement)?;
+        let plan = match &statement {
+            Statement::Select(select) => Some(self.planner().plan_select(select)?),
+            _ => None,
+        };
+        Ok(PreparedStatement { statement, parameter_types, plan })
+    }
+
+    /// Runs `prepared` with `parameters` as the values of its parameters, `$1`
+    /// first. A query runs the plan it was prepared with, even if a better one
+    /// could be made now.
+    pub fn execute_prepared(&mut self, txn: &mut Transaction, prepared: &PreparedStatement, parameters: &[Value]) -> Result<QueryResult, ExecutionError> {
+        let values = prepared.bind(parameters)?;
+        match &prepared.plan {
+            Some(plan) => {
+                let mut plan = plan.clone();
+                bind_plan(&mut plan, &values);
+                self.query(&plan)
+            }
+            None => self.execute(txn, &bind_statement(&prepared.statement, &values)),
+        }
+    }
+
+    fn create_table(&mut self, txn: &mut Transaction, create: &CreateTable) -> Result<QueryResult, ExecutionError> {
+        let columns = create.columns.iter().map(|column| Column::new(&column.name, column.data_type)).collect();
+        let table = self.catalog.create_table(txn, &create.name, columns)?;
+
+        // column constraints are table constraints on just that column, but for
+        // NOT NULL and DEFAULT, which only a column can have
+        let mut constraints = Vec::new();
+        let mut rules = Constraints::default();
+        for (position, column) in create.columns.iter().enumerate() {
+            for constraint in &column.constraints {
+                constraints.push(match constraint {
+                    ColumnConstraint::PrimaryKey => TableConstraint::PrimaryKey(vec![column.name.clone()]),
+                    ColumnConstraint::Unique => TableConstraint::Unique(vec![column.name.clone()]),
+                    ColumnConstraint::NotNull => {
+                        if !rules.not_null.contains(&position) {
+                            rules.not_null.push(position);
+                        }
+                        continue;
+                    }
+                    ColumnConstraint::Default(_) if rules.default_of(position).is_some() => {
+                        return Err(ExecutionError::InvalidConstraint(format!("column {} has more than one default", column.name)));
+                    }
+                    ColumnConstraint::Default(expr) => {
+                        check_default(&table, position, expr)?;
+                        rules.defaults.push((position, expr.clone()));
+                        continue;
+                    }
+                    // named after the column unless it's given a name
+                    ColumnConstraint::Check { name, expr } => {
+                        TableConstraint::Check { name: Some(name.clone().unwrap_or_else(|| format!("{}_{}_check", table.name, column.name))), expr: expr.clone() }
+                    }
+                });
+            }
+        }
+        constraints.extend(create.constraints.iter().cloned());
+
+        let scope = Scope::table(&table);
+        let mut indexes = Vec::new();
+        for constraint in constraints {
+            match constraint {
+                TableConstraint::PrimaryKey(names) => indexes.push((names, IndexKind::PrimaryKey)),
+                TableConstraint::Unique(names) => indexes.push((names, IndexKind::Unique)),
+                TableConstraint::Check { name, expr } => {
+                    let name = name.unwrap_or_else(|| check_name(&table, &rules.checks, &expr));
+                    check_check(&table, &name, &expr)?;
+                    rules.checks.push(CheckConstraint { name, expr });
+                }
+            }
+        }
+        if !rules.is_empty() {
+            self.catalog.set_constraints(txn, &table.name, rules)?;
+        }
+
+        for (names, kind) in indexes {
+            let columns = names.iter().map(|name| Ok(IndexColumn::new(scope.resolve(None, name)?))).collect::<Result<Vec<_>, ExecutionError>>()?;
+            // named the way PostgreSQL names them
+            let name = match kind {
+                IndexKind::PrimaryKey => format!("{}_pkey", table.name),
+                _ => format!("{}_{}_key", table.name, names.join("_")),
+            };
+            self.catalog.create_index(txn, &table.name, &name, columns, kind)?;
+        }
+        Ok(QueryResult::Affected(0))
+    }
+
+    fn create_index(&mut self, txn: &mut Transaction, create: &CreateIndex) -> Result<QueryResult, ExecutionError> {
+        let table = self.catalog.table(&create.table)?.ok_or_else(|| ExecutionError::TableNotFound(create.table.clone()))?;
+        let scope = Scope::table(&table);
+        let columns = create
+            .columns
+            .iter()
+            .map(|column| {
+                let position = scope.resolve(None, &column.name)?;
+                // like PostgreSQL, NULLs sort as the largest value unless told otherwise
+                let nulls_first = column.nulls_first.unwrap_or(column.descending);
+                Ok(IndexColumn { position, order: KeyOrder { descending: column.descending, nulls_first } })
+            })
+            .collect::<Result<Vec<_>, ExecutionError>>()?;
+        let kind = if create.unique { IndexKind::Unique } else { IndexKind::NonUnique };
+        let index = self.catalog.create_index(txn, &table.name, &create.name, columns, kind)?;
+
+        if let Some(access_log) = &self.access_log {
+            access_log.lock().unwrap().read_table(&table.name);
+        }
+        let heap = HeapFile::open(self.buffer_pool.clone(), table.root_page_id)?;
+        let mut sorter = EntrySorter::new();
+        for tuple in heap.scan() {
+            let (rid, bytes) = tuple?;
+            let row = Tuple::decode(&bytes, &table.columns)?.into_values();
+            sorter.push(index_key(&table, &index, &row, rid)?, rid)?;
+        }
+        let mut tree = index.open(self.buffer_pool.clone());
+        if let Some(log_manager) = &self.log_manager {
+            tree.set_log_manager(log_manager.clone());
+        }
+        match tree.bulk_load(txn, sorter) {
+            Err(IndexError::DuplicateKey) => Err(ExecutionError::UniqueViolation { constraint: index.name }),
+            result => Ok(result.map(|_| QueryResult::Affected(0))?),
+        }
+    }
+
+    fn insert(&mut self, txn: &mut Transaction, insert: &Insert) -> Result<QueryResult, ExecutionError> {
+        let mut writer = self.open_table(&insert.table)?;
+        let table = writer.info.clone();
+        let targets = targets(&table, &insert.columns)?;
+
+        for row in &insert.rows {
+            if row.len() != targets.len() {
+                return Err(ExecutionError::ValueCountMismatch { expected: targets.len(), found: row.len() });
+            }
+            let mut values = vec![Value::Null; table.columns.len()];
+            for (expr, &target) in row.iter().zip(&targets) {
+                let value = evaluate(expr, &Scope::empty(), &[])?;
+                values[target] = coerce(value, table.columns[target].data_type)?;
+            }
+            writer.insert(txn, fill_defaults(&table, values, &targets)?)?;
+        }
+        Ok(QueryResult::Affected(insert.rows.len()))
+    }
+
+    fn select(&mut self, select: &Select) -> Result<QueryResult, ExecutionError> {
+        let plan = self.planner().plan_select(select)?;
+        self.query(&plan)
+    }
+
+    /// the rows `plan` gives
+    fn query(&self, plan: &PhysicalPlan) -> Result<QueryResult, ExecutionError> {
+        self.note_reads(plan)?;
+        let mut root = operators::build(plan, &self.buffer_pool, self.work_memory)?;
+        let mut rows = Vec::new();
+        while let Some(row) = root.next()? {
+            rows.push(row);
+        }
+        Ok(QueryResult::Rows { columns: plan.column_names(), rows })
+    }
+
+    fn update(&mut self, txn: &mut Transaction, update: &Update) -> Result<QueryResult, ExecutionError> {
+        let mut writer = self.open_table(&update.table)?;
+        let table = writer.info.clone();
+        let scope = Scope::table(&table);
+        let assignments = update
+            .assignments
+            .iter()
+            .map(|(name, expr)| Ok((scope.resolve(None, name)?, expr)))
+            .collect::<Result<Vec<(usize, &Expr)>, ExecutionError>>()?;
+
+        // find every row before changing any, so rows moved by the update aren't visited twice
+        let rows = self.matching_rows(&table, update.where_clause.as_ref())?;
+        for (rid, row) in &rows {
+            let mut values = row.clone();
+            for &(target, expr) in &assignments {
+                // assignments see the row as it was before the update
+                values[target] = coerce(evaluate(expr, &scope, row)?, table.columns[target].data_type)?;
+            }
+            writer.update(txn, *rid, row, values)?;
+        }
+        Ok(QueryResult::Affected(rows.len()))
+    }
+
+    fn delete(&mut self, txn: &mut Transaction, delete: &Delete) -> Result<QueryResult, ExecutionError> {
+        let mut writer = self.open_table(&delete.table)?;
+        let rows = self.matching_rows(&writer.info.clone(), delete.where_clause.as_ref())?;
+        for (rid, row) in &rows {
+            writer.delete(txn, *rid, row)?;
+        }
+        Ok(QueryResult::Affected(rows.len()))
+    }
+
+    fn analyze(&mut self, txn: &mut Transaction, analyze: &Analyze) -> Result<QueryResult, ExecutionError> {
+        let tables = match &analyze.table {
+            Some(name) => vec![self.catalog.table(name)?.ok_or_else(|| ExecutionError::TableNotFound(name.clone()))?],
+            None => self.catalog.tables()?,
+        };
+        for table in &tables {
+            let statistics = TableStatistics::gather(self.buffer_pool.clone(), table)?;
+            self.catalog.set_statistics(txn, &table.name, &statistics)?;
+        }
+        Ok(QueryResult::Affected(0))
+    }
+
+    fn explain(&mut self, statement: &Explain) -> Result<QueryResult, ExecutionError> {
+        let start = Instant::now();
+        let plan = self.planner().plan_select(&statement.query)?;
+        let planning_time = start.elapsed();
+
+        let lines = if statement.analyze {
+            self.note_reads(&plan)?;
+            let start = Instant::now();
+            let (mut root, profiles) = operators::build_profiled(&plan, &self.buffer_pool, self.work_memory)?;
+            while root.next()?.is_some() {}
+            let execution_time = start.elapsed();
+
+            let mut lines = explain(&plan, Some(&profiles.get()));
+            lines.push(format!("Planning Time: {:.3} ms", planning_time.as_secs_f64() * 1000.0));
+            lines.push(format!("Execution Time: {:.3} ms", execution_time.as_secs_f64() * 1000.0));
+            lines
+        } else {
+            explain(&plan, None)
+        };
+        let rows = lines.into_iter().map(|line| vec![Value::Varchar(line)]).collect();
+        Ok(QueryResult::Rows { columns: vec![QUERY_PLAN_COLUMN.to_string()], rows })
+    }
+
+    fn copy(&mut self, txn: &mut Transaction, copy: &Copy) -> Result<QueryResult, ExecutionError> {
+        let count = match copy.direction {
+            CopyDirection::From => {
+                let file = File::open(&copy.path)?;
+                self.copy_in(txn, &copy.table, &copy.columns, BufReader::new(file), &copy.options)?
+            }
+            CopyDirection::To => {
+                // don't leave a file behind for a table that isn't there
+                self.table(&copy.table)?;
+                let file = File::create(&copy.path)?;
+                self.copy_out(&copy.table, &copy.columns, BufWriter::new(file), &copy.options)?
+            }
+        };
+        Ok(QueryResult::Affected(count))
+    }
+
+    /// Reclaims the space deleted rows left in `table`, or in every table, see
+    /// `vacuum::vacuum_heap`. The room it finds is recorded in the executor's
+    /// free space map, and lost without one.
+    pub fn vacuum(&self, table: Option<&str>) -> Result<VacuumStats, ExecutionError> {
+        let tables = match table {
+            Some(name) => vec![self.table(name)?],
+            None => self.catalog.tables()?,
+        };
+        let free_space_map = self.free_space_map.clone().unwrap_or_default();
+        let mut stats = VacuumStats::default();
+        for table in &tables {
+            let heap = HeapFile::open(self.buffer_pool.clone(), table.root_page_id)?;
+            stats += vacuum::vacuum_heap(&self.buffer_pool, &heap, &free_space_map)?;
+        }
+        Ok(stats)
+    }
+
+    fn table(&self, name: &str) -> Result<TableInfo, ExecutionError> {
+        self.catalog.table(name)?.ok_or_else(|| ExecutionError::TableNotFound(name.to_string()))
+    }
+
+    /// table `name`, with its heap file and indexes ready to log changes
+    fn open_table(&self, name: &str) -> Result<TableWriter, ExecutionError> {
+        let mut writer = TableWriter::open(&self.buffer_pool, self.log_manager.as_ref(), self.table(name)?)?;
+        if let Some(free_space_map) = &self.free_space_map {
+            writer.heap.set_free_space_map(free_space_map.clone());
+        }
+        writer.access_log = self.access_log.clone();
+        Ok(writer)
+    }
+
+    /// notes what running `plan` reads in the access log, if there is one
+    fn note_reads(&self, plan: &PhysicalPlan) -> Result<(), ExecutionError> {
+        match &self.access_log {
+            Some(access_log) => access_log.lock().unwrap().read_plan(plan),
+            None => Ok(()),
+        }
+    }
+
+    fn planner(&self) -> Planner<'_> {
+        Planner::new(self.catalog, self.buffer_pool.clone(), self.catalog).with_work_memory(self.work_memory)
+    }
+
+    /// decoded rows of `table` that satisfy `predicate` (all of them if there is none), and where they are
+    fn matching_rows(&self, table: &TableInfo, predicate: Option<&Expr>) -> Result<Vec<(RecordId, Vec<Value>)>, ExecutionError> {
+        let plan = self.planner().plan_scan(table, predicate)?;
+        self.note_reads(&plan)?;
+        let mut scan = TableScan::open(&plan, &self.buffer_pool)?;
+        let mut rows = Vec::new();
+        while let Some(entry) = scan.next_entry()? {
+            rows.push(entry);
+        }
+        Ok(rows)
+    }
+}
+
+/// positions in `table` of the values `columns` name, all columns in order if none are listed
+fn targets(table: &TableInfo, columns: &[String]) -> Result<Vec<usize>, ExecutionError> {
+    if columns.is_empty() {
+        return Ok((0..table.columns.len()).collect());
+    }
+    let scope = Scope::table(table);
+    let mut targets = Vec::new();
+    for name in columns {
+        let target = scope.resolve(None, name)?;
+        if targets.contains(&target) {
+            return Err(ExecutionError::DuplicateColumn(name.clone()));
+        }
+        targets.push(target);
+    }
+    Ok(targets)
+}
+
+/// `row` with the default of every column of `table` but those at `targets`,
+/// evaluated anew for each row so a default like `now()` changes
+fn fill_defaults(table: &TableInfo, mut row: Vec<Value>, targets: &[usize]) -> Result<Vec<Value>, ExecutionError> {
+    for (position, expr) in &table.constraints.defaults {
+        if !targets.contains(position) {
+            row[*position] = coerce(evaluate(expr, &Scope::empty(), &[])?, table.columns[*position].data_type)?;
+        }
+    }
+    Ok(row)
+}
+
+/// that `expr` can be the default of the column of `table` at `position`: it
+/// doesn't refer to any column, and its value fits the column
+fn check_default(table: &TableInfo, position: usize, expr: &Expr) -> Result<(), ExecutionError> {
+    let column = &table.columns[position];
+    if !column_references(expr).is_empty() {
+        return Err(ExecutionError::InvalidConstraint(format!("the default of column {} cannot refer to columns", column.name)));
+    }
+    let value = coerce(evaluate(expr, &Scope::empty(), &[])?, column.data_type)?;
+    if !value.fits(column.data_type) {
+        return Err(ExecutionError::InvalidConstraint(format!("the default of column {} of type {} cannot be {value}", column.name, column.data_type)));
+    }
+    Ok(())
+}
+
+/// that `expr` can be the `CHECK` constraint `name` of `table`: a boolean
+/// expression of the columns of a single row
+fn check_check(table: &TableInfo, name: &str, expr: &Expr) -> Result<(), ExecutionError> {
+    // evaluated for a row of NULLs, which finds the columns it refers to and any aggregates
+    let scope = Scope::table(table);
+    evaluate(expr, &scope, &vec![Value::Null; table.columns.len()])?;
+    let types: Vec<Option<DataType>> = table.columns.iter().map(|column| Some(column.data_type)).collect();
+    if !matches!(expr_type(expr, &scope, &types), Some(DataType::Bool) | None) {
+        return Err(ExecutionError::InvalidConstraint(format!("check constraint {name} must be a boolean expression")));
+    }
+    Ok(())
+}
+
+/// the name PostgreSQL would give an unnamed `CHECK` constraint of `table`:
+/// after the first column it refers to, and numbered if `checks` has it already
+fn check_name(table: &TableInfo, checks: &[CheckConstraint], expr: &Expr) -> String {
+    let base = match column_references(expr).first() {
+        Some(Expr::Column { name, .. }) => format!("{}_{name}_check", table.name),
+        _ => format!("{}_check", table.name),
+    };
+    let mut name = base.clone();
+    for number in 1.. {
+        if !checks.iter().any(|check| check.name == name) {
+            break;
+        }
+        name = format!("{base}{number}");
+    }
+    name
+}
+
+/// converts between the number types so values of any fit a column of another,
+/// rounding to the scale of the column like PostgreSQL does when assigning a
+/// number, reads dates, times and intervals written as strings, and turns
+/// dates into timestamps and back. Anything else is left for the tuple
+/// encoding to check
+fn coerce(value: Value, data_type: DataType) -> Result<Value, ExecutionError> {
+    match (value, data_type) {
+        (Value::Int(value), DataType::BigInt) => Ok(Value::BigInt(value as i64)),
+        (Value::BigInt(value), DataType::Int) => i32::try_from(value).map(Value::Int).map_err(|_| ExecutionError::NumericOverflow),
+        (value @ (Value::Int(_) | Value::BigInt(_) | Value::Numeric(_)), DataType::Numeric(modifiers)) => {
+            let value = value.to_decimal().unwrap();
+            match modifiers {
+                Some((precision, scale)) => match value.rescale(scale) {
+                    Some(value) if value.precision() <= precision => Ok(Value::Numeric(value)),
+                    _ => Err(ExecutionError::NumericOverflow),
+                },
+                None => Ok(Value::Numeric(value)),
+            }
+        }
+        (Value::Numeric(value), DataType::Int | DataType::BigInt) => {
+            let value = value.rescale(0).and_then(|value| value.to_i64()).ok_or(ExecutionError::NumericOverflow)?;
+            coerce(Value::BigInt(value), data_type)
+        }
+        (Value::Varchar(text), DataType::Date | DataType::Time | DataType::Timestamp | DataType::Interval) => {
+            Value::parse(&text, data_type).ok_or(ExecutionError::InvalidDatetime { data_type, text })
+        }
+        (Value::Date(days), DataType::Timestamp) => (days as i64).checked_mul(MICROS_PER_DAY).map(Value::Timestamp).ok_or(ExecutionError::DatetimeOverflow),
+        (Value::Timestamp(micros), DataType::Date) => i32::try_from(micros.div_euclid(MICROS_PER_DAY)).map(Value::Date).map_err(|_| ExecutionError::DatetimeOverflow),
+        (value, _) => Ok(value),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::index::encode_key;
+    use crate::planner::PlanNode;
+    use crate::sql::parse_statement;
+    use crate::storage::DiskManager;
+    use tempfile::NamedTempFile;
+
+    struct Fixture {
+        buffer_pool: Arc<Mutex<BufferPool>>,
+        catalog: Catalog,
+        _file: NamedTempFile,
+    }
+
+    impl Fixture {
+        fn new() -> Self {
+            let file = NamedTempFile::new().unwrap();
+            let buffer_pool = Arc::new(Mutex::new(BufferPool::new(DiskManager::open(file.path()).unwrap())));
+            let catalog = Catalog::open(buffer_pool.clone()).unwrap();
+            Self { buffer_pool, catalog, _file: file }
+        }
+
+        fn run(&mut self, sql: &str) -> Result<QueryResult, ExecutionError> {
+            let mut executor = Executor::new(self.buffer_pool.clone(), &mut self.catalog);
+            executor.execute(&mut Transaction::new(1), &parse_statement(sql).unwrap())
+        }
+
+        fn rows(&mut self, sql: &str) -> Vec<Vec<Value>> {
+            match self.run(sql).unwrap() {
+                QueryResult::Rows { rows, .. } => rows,
+                other => panic!("expected rows, got {other:?}"),
+            }
+        }
+    }
+
+    fn users() -> Fixture {
+        let mut fixture = Fixture::new();
+        fixture.run("CREATE TABLE users (id INT, name VARCHAR(16), visits BIGINT, active BOOL)").unwrap();
+        fixture
+            .run("INSERT INTO users VALUES (1, 'frodo', 10, TRUE), (2, 'sam', 20, TRUE), (3, 'gollum', 500, FALSE)")
+            .unwrap();
+        fixture
+    }
+
+    fn int(value: i32) -> Value {
+        Value::Int(value)
+    }
+
+    fn text(value: &str) -> Value {
+        Value::Varchar(value.to_string())
+    }
+
+    #[test]
+    fn test_insert_and_select() {
+        let mut fixture = users();
+        let result = fixture.run("SELECT id, name AS who, visits + 1 FROM users WHERE active").unwrap();
+        assert_eq!(
+            result,
+            QueryResult::Rows {
+                columns: vec!["id".into(), "who".into(), "(visits + 1)".into()],
+                rows: vec![
+                    vec![int(1), text("frodo"), Value::BigInt(11)],
+                    vec![int(2), text("sam"), Value::BigInt(21)],
+                ],
+            }
+        );
+        assert_eq!(fixture.rows("SELECT * FROM users WHERE id = 3")[0].len(), 4);
+        assert_eq!(fixture.rows("SELECT 1 + 1"), vec![vec![int(2)]]);
+        assert!(fixture.rows("SELECT 1 WHERE FALSE").is_empty());
+    }
+
+    #[test]
+    fn test_insert_column_list() {
+        let mut fixture = users();
+        let result = fixture.run("INSERT INTO users (name, id) VALUES ('merry', 4)").unwrap();
+        assert_eq!(result, QueryResult::Affected(1));
+        assert_eq!(
+            fixture.rows("SELECT * FROM users WHERE name = 'merry'"),
+            vec![vec![int(4), text("merry"), Value::Null, Value::Null]]
+        );
+
+        assert!(matches!(
+            fixture.run("INSERT INTO users (id, id) VALUES (1, 2)"),
+            Err(ExecutionError::DuplicateColumn(name)) if name == "id"
+        ));
+        assert!(matches!(
+            fixture.run("INSERT INTO users (id) VALUES (1, 2)"),
+            Err(ExecutionError::ValueCountMismatch { expected: 1, found: 2 })
+        ));
+        assert!(matches!(
+            fixture.run("INSERT INTO users (id) VALUES ('one')"),
+            Err(ExecutionError::TupleError(TupleError::TypeMismatch { .. }))
+        ));
+        assert!(matches!(
+            fixture.run("INSERT INTO users (id) VALUES (3000000000)"),
+            Err(ExecutionError::NumericOverflow)
+        ));
+    }
+
+    #[test]
+    fn test_update() {
+        let mut fixture = users();
+        let result = fixture.run("UPDATE users SET visits = visits * 2, name = 'samwise' WHERE id = 2").unwrap();
+        assert_eq!(result, QueryResult::Affected(1));
+        assert_eq!(
+            fixture.rows("SELECT name, visits FROM users WHERE id = 2"),
+            vec![vec![text("samwise"), Value::BigInt(40)]]
+        );
+
+        // every row is updated exactly once, even when growing rows move to other pages
+        let result = fixture.run("UPDATE users SET name = 'a longer name!!'").unwrap();
+        assert_eq!(result, QueryResult::Affected(3));
+        assert!(matches!(fixture.run("UPDATE users SET age = 1"), Err(ExecutionError::ColumnNotFound(_))));
+    }
+
+    #[test]
+    fn test_delete() {
+        let mut fixture = users();
+        assert_eq!(fixture.run("DELETE FROM users WHERE visits > 15").unwrap(), QueryResult::Affected(2));
+        assert_eq!(fixture.rows("SELECT id FROM users"), vec![vec![int(1)]]);
+        assert_eq!(fixture.run("DELETE FROM users").unwrap(), QueryResult::Affected(1));
+        assert!(fixture.rows("SELECT * FROM users").is_empty());
+    }
+
+    #[test]
+    fn test_unique_and_primary_key_constraints() {
+        let mut fixture = Fixture::new();
+        fixture.run("CREATE TABLE users (id INT PRIMARY KEY, email VARCHAR(32) UNIQUE, name VARCHAR(16))").unwrap();
+        fixture.run("INSERT INTO users VALUES (1, 'frodo@shire', 'frodo'), (2, NULL, 'sam'), (3, NULL, 'merry')").unwrap();
+
+        // an update is only checked against the other rows, and may move its row
+        fixture.run("UPDATE users SET email = 'sam@shire', name = 'samwise gamgee' WHERE id = 2").unwrap();
+        fixture.run("UPDATE users SET id = 1, email = 'frodo@shire' WHERE id = 1").unwrap();
+
+        // a deleted key can be used again
+        fixture.run("DELETE FROM users WHERE id = 3").unwrap();
+        fixture.run("INSERT INTO users VALUES (3, 'merry@buckland', 'merry')").unwrap();
+        assert_eq!(fixture.rows("SELECT id FROM users WHERE email = 'merry@buckland'"), vec![vec![int(3)]]);
+
+        let table = fixture.catalog.table("users").unwrap().unwrap();
+        let tree = table.indexes[1].open(fixture.buffer_pool.clone());
+        assert_eq!(tree.get(&encode_key(&[text("sam@shire")])).unwrap().len(), 1);
+        assert_eq!(tree.scan().unwrap().count(), 3);
+
+        // nothing rolls back what a failed statement already wrote here, so these come last
+        assert!(matches!(
+            fixture.run("UPDATE users SET id = 1 WHERE name = 'merry'"),
+            Err(ExecutionError::UniqueViolation { constraint }) if constraint == "users_pkey"
+        ));
+        assert!(matches!(
+            fixture.run("INSERT INTO users VALUES (2, 'pippin@shire', 'pippin')"),
+            Err(ExecutionError::UniqueViolation { constraint }) if constraint == "users_pkey"
+        ));
+        assert!(matches!(
+            fixture.run("INSERT INTO users VALUES (4, 'frodo@shire', 'pippin')"),
+            Err(ExecutionError::UniqueViolation { constraint }) if constraint == "users_email_key"
+        ));
+        assert!(matches!(
+            fixture.run("INSERT INTO users (email) VALUES ('pippin@shire')"),
+            Err(ExecutionError::NullViolation { column }) if column == "id"
+        ));
+    }
+
+    #[test]
+    fn test_table_constraints() {
+        let mut fixture = Fixture::new();
+        fixture.run("CREATE TABLE moves (game INT, turn INT, piece VARCHAR, PRIMARY KEY (game, turn))").unwrap();
+        fixture.run("INSERT INTO moves VALUES (1, 1, 'pawn'), (1, 2, 'knight'), (2, 1, 'pawn')").unwrap();
+        assert!(matches!(
+            fixture.run("INSERT INTO moves VALUES (2, 1, 'rook')"),
+            Err(ExecutionError::UniqueViolation { constraint }) if constraint == "moves_pkey"
+        ));
+
+        assert!(matches!(
+            fixture.run("CREATE TABLE other (id INT PRIMARY KEY, PRIMARY KEY (id))"),
+            Err(ExecutionError::CatalogError(CatalogError::MultiplePrimaryKeys(_)))
+        ));
+        assert!(matches!(fixture.run("CREATE TABLE bad (id INT, UNIQUE (nope))"), Err(ExecutionError::ColumnNotFound(_))));
+    }
+
+    #[test]
+    fn test_check_not_null_and_default() {
+        let mut fixture = Fixture::new();
+        fixture
+            .run(
+                "CREATE TABLE hobbits (id INT PRIMARY KEY, name VARCHAR(16) NOT NULL, age INT DEFAULT 33 CHECK (age >= 0), \
+                 joined TIMESTAMP DEFAULT now(), CONSTRAINT sensible CHECK (age < 150 OR name = 'gandalf'), CHECK (id > 0))",
+            )
+            .unwrap();
+        let table = fixture.catalog.table("hobbits").unwrap().unwrap();
+        let names: Vec<&str> = table.constraints.checks.iter().map(|check| check.name.as_str()).collect();
+        assert_eq!(names, ["hobbits_age_check", "sensible", "hobbits_id_check"]);
+
+        // columns left out get their defaults, but an explicit NULL stays NULL
+        fixture.run("INSERT INTO hobbits (id, name) VALUES (1, 'frodo')").unwrap();
+        fixture.run("INSERT INTO hobbits VALUES (2, 'sam', NULL, NULL)").unwrap();
+        let rows = fixture.rows("SELECT id, age, joined IS NULL FROM hobbits");
+        assert_eq!(rows, vec![vec![int(1), int(33), Value::Bool(false)], vec![int(2), Value::Null, Value::Bool(true)]]);
+
+        let mut executor = Executor::new(fixture.buffer_pool.clone(), &mut fixture.catalog);
+        let csv = "3,merry\n4,pippin\n";
+        let columns = ["id".to_string(), "name".to_string()];
+        executor.copy_in(&mut Transaction::new(1), "hobbits", &columns, csv.as_bytes(), &CopyOptions::default()).unwrap();
+        assert_eq!(fixture.rows("SELECT age FROM hobbits WHERE id = 4"), vec![vec![int(33)]]);
+        fixture.run("UPDATE hobbits SET age = 140 WHERE id = 3").unwrap();
+
+        // the constraints are still there once the catalog is read again
+        fixture.catalog = Catalog::open(fixture.buffer_pool.clone()).unwrap();
+        assert!(matches!(
+            fixture.run("INSERT INTO hobbits (id) VALUES (5)"),
+            Err(ExecutionError::NullViolation { column }) if column == "name"
+        ));
+        assert!(matches!(
+            fixture.run("INSERT INTO hobbits (id, name, age) VALUES (5, 'bilbo', -1)"),
+            Err(ExecutionError::CheckViolation { constraint }) if constraint == "hobbits_age_check"
+        ));
+        assert!(matches!(
+            fixture.run("UPDATE hobbits SET age = age + 20 WHERE id = 3"),
+            Err(ExecutionError::CheckViolation { constraint }) if constraint == "sensible"
+        ));
+        assert!(matches!(
+            fixture.run("UPDATE hobbits SET name = NULL WHERE id = 1"),
+            Err(ExecutionError::NullViolation { column }) if column == "name"
+        ));
+        let csv = "0,bilbo\n";
+        let mut executor = Executor::new(fixture.buffer_pool.clone(), &mut fixture.catalog);
+        assert!(matches!(
+            executor.copy_in(&mut Transaction::new(1), "hobbits", &columns, csv.as_bytes(), &CopyOptions::default()),
+            Err(ExecutionError::CheckViolation { constraint }) if constraint == "hobbits_id_check"
+        ));
+    }
+
+    #[test]
+    fn test_invalid_constraints() {
+        let mut fixture = Fixture::new();
+        assert!(matches!(fixture.run("CREATE TABLE a (id INT DEFAULT 'one')"), Err(ExecutionError::InvalidConstraint(_))));
+        assert!(matches!(fixture.run("CREATE TABLE b (id INT, other INT DEFAULT id)"), Err(ExecutionError::InvalidConstraint(_))));
+        assert!(matches!(fixture.run("CREATE TABLE g (id INT DEFAULT 1 DEFAULT 2)"), Err(ExecutionError::InvalidConstraint(_))));
+        assert!(matches!(fixture.run("CREATE TABLE c (id INT CHECK (id + 1))"), Err(ExecutionError::InvalidConstraint(_))));
+        assert!(matches!(fixture.run("CREATE TABLE d (id INT CHECK (nope > 0))"), Err(ExecutionError::ColumnNotFound(_))));
+        assert!(matches!(
+            fixture.run("CREATE TABLE e (id INT, CONSTRAINT positive CHECK (id > 0), CONSTRAINT positive CHECK (id < 10))"),
+            Err(ExecutionError::CatalogError(CatalogError::ConstraintExists(name))) if name == "positive"
+        ));
+
+        // unnamed checks on the same column are numbered
+        fixture.run("CREATE TABLE f (id INT CHECK (id > 0), CHECK (id < 10), CHECK (TRUE))").unwrap();
+        let table = fixture.catalog.table("f").unwrap().unwrap();
+        let names: Vec<&str> = table.constraints.checks.iter().map(|check| check.name.as_str()).collect();
+        assert_eq!(names, ["f_id_check", "f_id_check1", "f_check"]);
+    }
+
+    /// rows of `table` in the order of its index `name`
+    fn rows_by_index(fixture: &Fixture, table: &str, name: &str) -> Vec<Vec<Value>> {
+        let table = fixture.catalog.table(table).unwrap().unwrap();
+        let heap = HeapFile::open(fixture.buffer_pool.clone(), table.root_page_id).unwrap();
+        let index = table.indexes.iter().find(|index| index.name == name).unwrap();
+        let tree = index.open(fixture.buffer_pool.clone());
+        tree.scan().unwrap().map(|entry| Tuple::decode(&heap.get(entry.unwrap().1).unwrap(), &table.columns).unwrap().into_values()).collect()
+    }
+
+    #[test]
+    fn test_create_index_on_existing_rows() {
+        let mut fixture = Fixture::new();
+        fixture.run("CREATE TABLE scores (game INT, player VARCHAR, score INT)").unwrap();
+        fixture
+            .run("INSERT INTO scores VALUES (2, 'sam', 40), (1, 'frodo', 70), (1, 'sam', NULL), (2, 'merry', 90), (1, 'pippin', 10)")
+            .unwrap();
+
+        fixture.run("CREATE INDEX scores_game_score_idx ON scores (game, score DESC)").unwrap();
+        let score = |row: &Vec<Value>| (row[0].clone(), row[2].clone());
+        let expected = [(int(1), Value::Null), (int(1), int(70)), (int(1), int(10)), (int(2), int(90)), (int(2), int(40))];
+        assert_eq!(rows_by_index(&fixture, "scores", "scores_game_score_idx").iter().map(score).collect::<Vec<_>>(), expected);
+
+        // NULLS LAST overrides the default, and rows inserted later are indexed too
+        fixture.run("CREATE INDEX scores_score_idx ON scores (score DESC NULLS LAST)").unwrap();
+        fixture.run("INSERT INTO scores VALUES (3, 'gandalf', 50)").unwrap();
+        let scores: Vec<Value> = rows_by_index(&fixture, "scores", "scores_score_idx").into_iter().map(|row| row[2].clone()).collect();
+        assert_eq!(scores, [int(90), int(70), int(50), int(40), int(10), Value::Null]);
+        assert_eq!(rows_by_index(&fixture, "scores", "scores_game_score_idx").len(), 6);
+    }
+
+    #[test]
+    fn test_create_unique_index() {
+        let mut fixture = Fixture::new();
+        fixture.run("CREATE TABLE players (name VARCHAR, email VARCHAR)").unwrap();
+        fixture.run("INSERT INTO players VALUES ('frodo', NULL), ('sam', NULL), ('sam', 'sam@shire')").unwrap();
+
+        // NULLs don't clash, but the two sams do
+        fixture.run("CREATE UNIQUE INDEX players_email_idx ON players (email)").unwrap();
+        assert!(matches!(
+            fixture.run("CREATE UNIQUE INDEX players_name_idx ON players (name)"),
+            Err(ExecutionError::UniqueViolation { constraint }) if constraint == "players_name_idx"
+        ));
+        assert!(matches!(
+            fixture.run("INSERT INTO players VALUES ('rosie', 'sam@shire')"),
+            Err(ExecutionError::UniqueViolation { constraint }) if constraint == "players_email_idx"
+        ));
+
+        assert!(matches!(fixture.run("CREATE INDEX i ON missing (name)"), Err(ExecutionError::TableNotFound(_))));
+        assert!(matches!(fixture.run("CREATE INDEX i ON players (age)"), Err(ExecutionError::ColumnNotFound(_))));
+        assert!(matches!(
+            fixture.run("CREATE INDEX players_email_idx ON players (name)"),
+            Err(ExecutionError::CatalogError(CatalogError::IndexExists(_)))
+        ));
+    }
+
+    #[test]
+    fn test_joins() {
+        let mut fixture = users();
+        fixture.run("CREATE TABLE friends (a INT, b INT)").unwrap();
+        fixture.run("INSERT INTO friends VALUES (1, 2), (2, 1), (2, 3), (4, 1)").unwrap();
+
+        let result = fixture.run("SELECT u.name, v.name AS friend FROM users u JOIN friends ON u.id = a JOIN users v ON v.id = b WHERE v.active").unwrap();
+        let QueryResult::Rows { columns, mut rows } = result else {
+            panic!("expected rows");
+        };
+        assert_eq!(columns, ["name", "friend"]);
+        rows.sort_by_key(|row| row[0].to_string());
+        assert_eq!(rows, vec![vec![text("frodo"), text("sam")], vec![text("sam"), text("frodo")]]);
+
+        // the columns of every table, in the order of the FROM clause
+        let result = fixture.run("SELECT * FROM friends, users WHERE users.id = friends.a AND friends.b = 3").unwrap();
+        assert_eq!(
+            result,
+            QueryResult::Rows {
+                columns: ["a", "b", "id", "name", "visits", "active"].map(String::from).to_vec(),
+                rows: vec![vec![int(2), int(3), int(2), text("sam"), Value::BigInt(20), Value::Bool(true)]],
+            }
+        );
+        assert_eq!(fixture.rows("SELECT * FROM users CROSS JOIN friends").len(), 12);
+        assert!(fixture.rows("SELECT * FROM users, friends WHERE FALSE").is_empty());
+        assert!(matches!(fixture.run("SELECT id FROM users, users u WHERE a = 1"), Err(ExecutionError::ColumnNotFound(_))));
+        assert!(matches!(fixture.run("SELECT id FROM users a, users b"), Err(ExecutionError::AmbiguousColumn(_))));
+    }
+
+    #[test]
+    fn test_hash_joins() {
+        let mut fixture = Fixture::new();
+        fixture.run("CREATE TABLE accounts (id BIGINT, owner VARCHAR)").unwrap();
+        fixture.run("CREATE TABLE payments (account INT, amount INT)").unwrap();
+        for chunk in (0..4000).collect::<Vec<i32>>().chunks(500) {
+            let accounts: Vec<String> = chunk.iter().filter(|i| *i % 2 == 0).map(|i| format!("({i}, 'owner of account {i}')")).collect();
+            fixture.run(&format!("INSERT INTO accounts VALUES {}", accounts.join(", "))).unwrap();
+            let payments: Vec<String> = chunk.iter().map(|i| format!("({}, {})", i % 1000, i)).collect();
+            fixture.run(&format!("INSERT INTO payments VALUES {}", payments.join(", "))).unwrap();
+        }
+        fixture.run("INSERT INTO payments VALUES (NULL, 1)").unwrap();
+        fixture.run("ANALYZE").unwrap();
+
+        // the rows of `sql` run with `work_memory` bytes
+        let mut run = |sql: &str, work_memory: usize| {
+            let mut executor = Executor::new(fixture.buffer_pool.clone(), &mut fixture.catalog);
+            executor.set_work_memory(work_memory);
+            let QueryResult::Rows { rows, .. } = executor.execute(&mut Transaction::new(1), &parse_statement(sql).unwrap()).unwrap() else {
+                panic!("expected rows");
+            };
+            rows
+        };
+        let sql = "SELECT p.amount, a.owner FROM payments p JOIN accounts a ON a.id = p.account AND p.amount > a.id";
+        let plan: Vec<String> = run(&format!("EXPLAIN {sql}"), DEFAULT_WORK_MEMORY).into_iter().map(|row| row[0].to_string()).collect();
+        assert!(plan[1].contains("Hash Join"), "{plan:?}");
+        assert_eq!(plan[2].trim(), "Hash Cond: (p.account = a.id)");
+        assert_eq!(plan[3].trim(), "Join Filter: (p.amount > a.id)");
+
+        // each even account below 1000 has four payments, all but the first larger than its id
+        let mut rows = run(sql, DEFAULT_WORK_MEMORY);
+        assert_eq!(rows.len(), 500 * 3);
+        assert!(rows.contains(&vec![int(3998), text("owner of account 998")]));
+        // the same rows with so little memory the accounts are written out in partitions
+        let mut spilled = run(sql, 4096);
+        rows.sort_by_key(|row| format!("{row:?}"));
+        spilled.sort_by_key(|row| format!("{row:?}"));
+        assert_eq!(spilled, rows);
+    }
+
+    #[test]
+    fn test_index_nested_loop_joins() {
+        let mut fixture = Fixture::new();
+        fixture.run("CREATE TABLE accounts (id BIGINT PRIMARY KEY, owner VARCHAR(24), branch INT)").unwrap();
+        fixture.run("CREATE TABLE payments (id INT, account INT, amount INT)").unwrap();
+        fixture.run("CREATE INDEX payments_account_idx ON payments (account)").unwrap();
+        for chunk in (0..3000).collect::<Vec<i32>>().chunks(500) {
+            let accounts: Vec<String> = chunk.iter().map(|i| format!("({i}, 'owner of account {i}', {})", i % 10)).collect();
+            fixture.run(&format!("INSERT INTO accounts VALUES {}", accounts.join(", "))).unwrap();
+            let payments: Vec<String> = chunk.iter().map(|i| format!("({i}, {}, {})", i % 1000, i / 3)).collect();
+            fixture.run(&format!("INSERT INTO payments VALUES {}", payments.join(", "))).unwrap();
+        }
+        fixture.run("INSERT INTO payments VALUES (3000, NULL, 1)").unwrap();
+        fixture.run("ANALYZE").unwrap();
+
+        // the payments of one owner's account found through the index, with INT accounts
+        // looked up among BIGINT ids the other way around
+        let sql = "SELECT a.owner, p.id FROM accounts a, payments p WHERE a.id = p.account AND a.owner = 'owner of account 7' AND p.amount > a.branch";
+        let plan: Vec<String> = fixture.rows(&format!("EXPLAIN {sql}")).into_iter().map(|row| row[0].to_string()).collect();
+        assert!(plan[1].contains("Index Nested Loop using payments_account_idx on payments p"), "{plan:?}");
+        assert_eq!(plan[2].trim(), "Index Cond: (p.account = a.id)");
+        assert_eq!(plan[3].trim(), "Join Filter: (p.amount > a.branch)");
+        let mut rows = fixture.rows(sql);
+        rows.sort_by_key(|row| format!("{row:?}"));
+        assert_eq!(rows, [vec![text("owner of account 7"), int(1007)], vec![text("owner of account 7"), int(2007)]]);
+
+        let sql = "SELECT p.id, a.owner FROM payments p, accounts a WHERE p.account = a.id AND p.id > 2996 AND a.branch > 5";
+        let plan: Vec<String> = fixture.rows(&format!("EXPLAIN {sql}")).into_iter().map(|row| row[0].to_string()).collect();
+        assert!(plan[1].contains("Index Nested Loop using accounts_pkey on accounts a"), "{plan:?}");
+        assert_eq!(plan[3].trim(), "Filter: (a.branch > 5)");
+        let mut rows = fixture.rows(sql);
+        rows.sort_by_key(|row| format!("{row:?}"));
+        // the NULL account matches nothing
+        let expected: Vec<Vec<Value>> = (2997..3000).filter(|i| i % 1000 % 10 > 5).map(|i| vec![int(i), text(&format!("owner of account {}", i % 1000))]).collect();
+        assert_eq!(rows, expected);
+    }
+
+    #[test]
+    fn test_three_valued_logic() {
+        let mut fixture = users();
+        fixture.run("INSERT INTO users VALUES (4, 'merry', NULL, TRUE), (5, 'pippin', 10, NULL)").unwrap();
+        let names = |rows: Vec<Vec<Value>>| rows.into_iter().map(|row| row[0].to_string()).collect::<Vec<_>>();
+
+        // an unknown side doesn't matter once the other decides the result
+        assert_eq!(names(fixture.rows("SELECT name FROM users WHERE active OR visits > 100 ORDER BY id")), ["frodo", "sam", "gollum", "merry"]);
+        // and NOT of unknown is unknown, so neither merry nor pippin is found either way
+        assert_eq!(names(fixture.rows("SELECT name FROM users WHERE NOT (active AND visits < 100) ORDER BY id")), ["gollum"]);
+        assert_eq!(names(fixture.rows("SELECT name FROM users WHERE active AND visits < 100 ORDER BY id")), ["frodo", "sam"]);
+        assert_eq!(names(fixture.rows("SELECT name FROM users WHERE visits IS NULL OR active IS NULL ORDER BY id")), ["merry", "pippin"]);
+        let rows = fixture.rows("SELECT active AND visits > 15 FROM users ORDER BY id");
+        assert_eq!(rows, [[Value::Bool(false)], [Value::Bool(true)], [Value::Bool(false)], [Value::Null], [Value::Bool(false)]]);
+    }
+
+    #[test]
+    fn test_order_by_and_limit() {
+        let mut fixture = users();
+        fixture.run("INSERT INTO users VALUES (4, 'merry', NULL, TRUE), (5, 'pippin', 10, NULL)").unwrap();
+        let names = |rows: Vec<Vec<Value>>| rows.into_iter().map(|row| row[0].to_string()).collect::<Vec<_>>();
+
+        // NULLs go last going up and first going down, and ties keep the order they were read in
+        assert_eq!(names(fixture.rows("SELECT name FROM users ORDER BY visits")), ["frodo", "pippin", "sam", "gollum", "merry"]);
+        assert_eq!(names(fixture.rows("SELECT name FROM users ORDER BY visits DESC, id DESC")), ["merry", "gollum", "sam", "pippin", "frodo"]);
+        assert_eq!(names(fixture.rows("SELECT name FROM users ORDER BY visits DESC NULLS LAST LIMIT 2")), ["gollum", "sam"]);
+        // by an expression that isn't selected, a selected one's name or its position
+        assert_eq!(names(fixture.rows("SELECT name FROM users ORDER BY id % 3, id DESC")), ["gollum", "merry", "frodo", "pippin", "sam"]);
+        assert_eq!(names(fixture.rows("SELECT name, 10 - id AS countdown FROM users ORDER BY countdown LIMIT 2")), ["pippin", "merry"]);
+        assert_eq!(names(fixture.rows("SELECT name, id FROM users ORDER BY 2 DESC OFFSET 3")), ["sam", "frodo"]);
+        assert_eq!(names(fixture.rows("SELECT name FROM users ORDER BY name LIMIT 2 OFFSET 1")), ["gollum", "merry"]);
+        assert!(fixture.rows("SELECT name FROM users ORDER BY name LIMIT 0").is_empty());
+        assert!(fixture.rows("SELECT name FROM users OFFSET 10").is_empty());
+        assert_eq!(fixture.rows("SELECT name FROM users LIMIT 3").len(), 3);
+
+        // groups sorted by their keys or aggregates
+        let rows = fixture.rows("SELECT active, count(*) FROM users GROUP BY active ORDER BY count(*) DESC, active LIMIT 2");
+        assert_eq!(rows, [vec![Value::Bool(true), Value::BigInt(3)], vec![Value::Bool(false), Value::BigInt(1)]]);
+        assert!(matches!(fixture.run("SELECT active FROM users GROUP BY active ORDER BY name"), Err(ExecutionError::UngroupedColumn(_))));
+        assert!(matches!(fixture.run("SELECT name FROM users ORDER BY 2"), Err(ExecutionError::InvalidOrderByPosition(2))));
+        assert!(matches!(fixture.run("SELECT name FROM users ORDER BY missing"), Err(ExecutionError::ColumnNotFound(_))));
+    }
+
+    #[test]
+    fn test_top_n() {
+        let mut fixture = Fixture::new();
+        fixture.run("CREATE TABLE events (id INT, score INT)").unwrap();
+        for chunk in (0..5000).collect::<Vec<i32>>().chunks(500) {
+            let events: Vec<String> = chunk.iter().map(|i| format!("({i}, {})", i * 7919 % 1000)).collect();
+            fixture.run(&format!("INSERT INTO events VALUES {}", events.join(", "))).unwrap();
+        }
+
+        // a few rows off the top are kept in a heap rather than sorting them all
+        let sql = "SELECT id, score FROM events ORDER BY score DESC, id LIMIT 3 OFFSET 2";
+        let plan: Vec<String> = fixture.rows(&format!("EXPLAIN {sql}")).into_iter().map(|row| row[0].to_string()).collect();
+        assert!(plan[1].contains("Limit") && plan[2].contains("Top-N Sort (5 rows)"), "{plan:?}");
+        assert_eq!(plan[3].trim(), "Sort Key: score DESC, id");
+        let mut expected: Vec<(i32, i32)> = (0..5000).map(|i| (i, i * 7919 % 1000)).collect();
+        expected.sort_by_key(|&(id, score)| (-score, id));
+        let expected: Vec<Vec<Value>> = expected[2..5].iter().map(|&(id, score)| vec![int(id), int(score)]).collect();
+        assert_eq!(fixture.rows(sql), expected);
+
+        // but all of them are sorted when most of them are asked for
+        let plan: Vec<String> = fixture.rows("EXPLAIN SELECT id FROM events ORDER BY score LIMIT 4000").into_iter().map(|row| row[0].to_string()).collect();
+        assert!(plan[2].contains("->  Sort"), "{plan:?}");
+    }
+
+    #[test]
+    fn test_aggregates() {
+        let mut fixture = users();
+        fixture.run("INSERT INTO users VALUES (4, 'merry', NULL, TRUE), (5, 'pippin', 10, NULL)").unwrap();
+        let big = Value::BigInt;
+
+        let QueryResult::Rows { columns, rows } = fixture.run("SELECT count(*), count(visits), sum(visits), avg(visits) AS mean, min(name), max(id) FROM users").unwrap() else {
+            panic!("expected rows");
+        };
+        assert_eq!(columns, ["count", "count", "sum", "mean", "min", "max"]);
+        assert_eq!(rows, [vec![big(5), big(4), big(540), big(135), text("frodo"), int(5)]]);
+
+        // a group for each value of the keys, NULL among them
+        let mut rows = fixture.rows("SELECT active, count(*), sum(visits) + 1 FROM users GROUP BY active");
+        rows.sort_by_key(|row| format!("{row:?}"));
+        assert_eq!(rows, [vec![Value::Bool(false), big(1), big(501)], vec![Value::Bool(true), big(3), big(31)], vec![Value::Null, big(1), big(11)]]);
+        let rows = fixture.rows("SELECT visits / 10 AS tens FROM users GROUP BY visits / 10 HAVING count(*) > 1 AND min(id) < 3");
+        assert_eq!(rows, [vec![big(1)]]);
+        let plan: Vec<String> = fixture.rows("EXPLAIN SELECT visits / 10 FROM users GROUP BY visits / 10 HAVING count(*) > 1").into_iter().map(|row| row[0].to_string()).collect();
+        assert!(plan[1].contains("Filter") && plan[3].contains("HashAggregate"), "{plan:?}");
+        assert_eq!(plan[4].trim(), "Group Key: (visits / 10)");
+
+        // without GROUP BY there's a row even for no rows at all, with it there's none
+        assert_eq!(fixture.rows("SELECT count(*), max(visits) FROM users WHERE id > 10"), [vec![big(0), Value::Null]]);
+        assert!(fixture.rows("SELECT count(*) FROM users WHERE id > 10 GROUP BY active").is_empty());
+        assert_eq!(fixture.rows("SELECT count(*) FROM users HAVING count(*) > 10"), Vec::<Vec<Value>>::new());
+        // checked before grouping, but with the same groups
+        assert_eq!(fixture.rows("SELECT active, count(*) FROM users GROUP BY active HAVING active AND count(*) > 1"), [vec![Value::Bool(true), big(3)]]);
+
+        assert!(matches!(fixture.run("SELECT name, count(*) FROM users"), Err(ExecutionError::UngroupedColumn(name)) if name == "name"));
+        assert!(matches!(fixture.run("SELECT name FROM users WHERE count(*) > 1"), Err(ExecutionError::AggregateNotAllowed("WHERE"))));
+        assert!(matches!(fixture.run("SELECT sum(name) FROM users"), Err(ExecutionError::TypeError(_))));
+    }
+
+    #[test]
+    fn test_aggregates_beyond_work_memory() {
+        let mut fixture = Fixture::new();
+        fixture.run("CREATE TABLE readings (sensor INT, value BIGINT)").unwrap();
+        for chunk in (0..6000).collect::<Vec<i32>>().chunks(500) {
+            let readings: Vec<String> = chunk.iter().map(|i| format!("({}, {i})", i % 2000)).collect();
+            fixture.run(&format!("INSERT INTO readings VALUES {}", readings.join(", "))).unwrap();
+        }
+
+        let mut run = |work_memory: usize| {
+            let mut executor = Executor::new(fixture.buffer_pool.clone(), &mut fixture.catalog);
+            executor.set_work_memory(work_memory);
+            let sql = "SELECT sensor, count(*), max(value) FROM readings GROUP BY sensor";
+            let QueryResult::Rows { mut rows, .. } = executor.execute(&mut Transaction::new(1), &parse_statement(sql).unwrap()).unwrap() else {
+                panic!("expected rows");
+            };
+            rows.sort_by_key(|row| format!("{row:?}"));
+            rows
+        };
+        let rows = run(DEFAULT_WORK_MEMORY);
+        assert_eq!(rows.len(), 2000);
+        assert!(rows.contains(&vec![int(1999), Value::BigInt(3), Value::BigInt(5999)]));
+        // the same groups when most of them are written out to disk first
+        assert_eq!(run(2048), rows);
+    }
+
+    #[test]
+    fn test_index_scans() {
+        let mut fixture = Fixture::new();
+        fixture.run("CREATE TABLE items (id INT PRIMARY KEY, kind VARCHAR(8), price INT, note VARCHAR)").unwrap();
+        fixture.run("CREATE INDEX items_kind_price_idx ON items (kind, price DESC)").unwrap();
+        for chunk in (0..3000).collect::<Vec<i32>>().chunks(500) {
+            let rows: Vec<String> = chunk.iter().map(|i| format!("({i}, 'kind{}', {}, 'item number {i} of three thousand')", i % 300, i % 7)).collect();
+            fixture.run(&format!("INSERT INTO items VALUES {}", rows.join(", "))).unwrap();
+        }
+
+        // checks the planner picks `index` for `sql`, and that it finds the same rows as a full scan
+        let mut check = |sql: &str, index: &str, expected: usize| {
+            let Statement::Select(select) = parse_statement(sql).unwrap() else { unreachable!() };
+            let plan = Planner::new(&fixture.catalog, fixture.buffer_pool.clone(), &fixture.catalog).plan_select(&select).unwrap();
+            let PlanNode::Project { input, .. } = &plan.node else { unreachable!() };
+            assert!(matches!(&input.node, PlanNode::IndexScan { index: used, .. } if used.name == index), "{sql}: {input:?}");
+
+            let mut rows = fixture.rows(sql);
+            // no index helps with an OR
+            let mut scanned = fixture.rows(&format!("{} OR FALSE", sql.replace("WHERE ", "WHERE (")).replace(" OR FALSE", ") OR FALSE"));
+            rows.sort_by_key(|row| row[0].to_string());
+            scanned.sort_by_key(|row| row[0].to_string());
+            assert_eq!(rows.len(), expected, "{sql}");
+            assert_eq!(rows, scanned, "{sql}");
+        };
+        check("SELECT * FROM items WHERE id = 1234", "items_pkey", 1);
+        check("SELECT * FROM items WHERE 2000 < id AND id <= 2010", "items_pkey", 10);
+        check("SELECT * FROM items WHERE kind = 'kind7' AND price >= 3", "items_kind_price_idx", 6);
+        check("SELECT id FROM items WHERE kind = 'kind7' AND price < 3 AND id > 1000", "items_kind_price_idx", 3);
+
+        // updates and deletes find their rows through the index too
+        assert_eq!(fixture.run("UPDATE items SET price = price + 100 WHERE kind = 'kind7'").unwrap(), QueryResult::Affected(10));
+        assert_eq!(fixture.rows("SELECT price FROM items WHERE id = 307"), vec![vec![int(106)]]);
+        assert_eq!(fixture.run("DELETE FROM items WHERE id >= 2990").unwrap(), QueryResult::Affected(10));
+        assert_eq!(fixture.rows("SELECT * FROM items").len(), 2990);
+        assert!(fixture.rows("SELECT * FROM items WHERE id = 2995").is_empty());
+    }
+
+    #[test]
+    fn test_numeric() {
+        let mut fixture = Fixture::new();
+        fixture.run("CREATE TABLE ledger (id INT PRIMARY KEY, amount NUMERIC(12, 2), rate NUMERIC)").unwrap();
+        fixture.run("CREATE INDEX ledger_amount_idx ON ledger (amount)").unwrap();
+        let rows: Vec<String> = (0..1000).map(|i| format!("({i}, {}.{:02}, 0.1)", i - 500, i % 100)).collect();
+        fixture.run(&format!("INSERT INTO ledger VALUES {}", rows.join(", "))).unwrap();
+        let strings = |rows: Vec<Vec<Value>>| -> Vec<Vec<String>> { rows.iter().map(|row| row.iter().map(Value::to_string).collect()).collect() };
+
+        // a thousand tenths add up to exactly a hundred
+        assert_eq!(strings(fixture.rows("SELECT sum(rate), min(amount), max(amount) FROM ledger")), [["100.0", "-500.00", "499.99"]]);
+        // stored numbers are rounded to the column's scale, and mustn't have more digits than it takes
+        fixture.run("INSERT INTO ledger VALUES (1000, 2.345, 1), (1001, 7, 7)").unwrap();
+        assert!(matches!(fixture.run("INSERT INTO ledger VALUES (1002, 12345678901.5, 1)"), Err(ExecutionError::NumericOverflow)));
+        assert_eq!(strings(fixture.rows("SELECT amount, rate FROM ledger WHERE id >= 1000")), [["2.35", "1"], ["7.00", "7"]]);
+        assert_eq!(strings(fixture.rows("SELECT amount * 3 + 1, amount / 3, -amount % 1, avg(amount) FROM ledger WHERE id = 1000 GROUP BY amount")), [["8.05", "0.7833333333333333", "-0.35", "2.3500000000000000"]]);
+        assert!(matches!(fixture.run("SELECT amount / 0 FROM ledger"), Err(ExecutionError::DivisionByZero)));
+
+        // the index finds numbers by value, whatever their scale, and doesn't round what it looks for
+        fixture.run("ANALYZE").unwrap();
+        let ids = |fixture: &mut Fixture, condition: &str| {
+            let sql = format!("SELECT id FROM ledger WHERE {condition}");
+            let plan = fixture.rows(&format!("EXPLAIN {sql}"));
+            assert!(plan.iter().any(|row| row[0].to_string().contains("ledger_amount_idx")), "{plan:?}");
+            fixture.rows(&sql)
+        };
+        assert_eq!(ids(&mut fixture, "amount = 2.350"), [[int(1000)]]);
+        assert_eq!(ids(&mut fixture, "amount = 7"), [[int(1001)]]);
+        assert!(ids(&mut fixture, "amount = 2.345").is_empty());
+        assert_eq!(ids(&mut fixture, "amount >= 2.345 AND amount < 3"), [[int(1000)]]);
+        assert_eq!(fixture.rows("SELECT id FROM ledger ORDER BY amount DESC LIMIT 2"), [[int(999)], [int(998)]]);
+
+        // numbers of all types compare and join by value
+        assert_eq!(fixture.rows("SELECT count(*) FROM ledger WHERE amount = id - 500"), [[Value::BigInt(10)]]);
+        fixture.run("CREATE TABLE whole (n INT)").unwrap();
+        fixture.run("INSERT INTO whole VALUES (7), (-400), (3)").unwrap();
+        let mut joined = fixture.rows("SELECT l.id FROM ledger l JOIN whole w ON l.amount = w.n");
+        joined.sort_by_key(|row| row[0].to_string());
+        assert_eq!(joined, [[int(100)], [int(1001)]]);
+    }
+
+    #[test]
+    fn test_temporal() {
+        let mut fixture = Fixture::new();
+        fixture.run("CREATE TABLE visits (id INT PRIMARY KEY, day DATE, at TIMESTAMP, stay INTERVAL)").unwrap();
+        fixture.run("CREATE INDEX visits_at_idx ON visits (at)").unwrap();
+        // a visit every 7 hours from the start of 2024, staying a minute longer each time
+        let rows: Vec<String> = (0..1000)
+            .map(|i| format!("({i}, DATE '2024-01-01' + {}, TIMESTAMP '2024-01-01 00:00' + INTERVAL '7 hours' * {i}, '{i} minutes')", i * 7 / 24))
+            .collect();
+        fixture.run(&format!("INSERT INTO visits VALUES {}", rows.join(", "))).unwrap();
+        let strings = |rows: Vec<Vec<Value>>| -> Vec<Vec<String>> { rows.iter().map(|row| row.iter().map(Value::to_string).collect()).collect() };
+
+        assert_eq!(strings(fixture.rows("SELECT day, at, stay FROM visits WHERE id = 5")), [["2024-01-02", "2024-01-02 11:00:00", "00:05:00"]]);
+        assert_eq!(strings(fixture.rows("SELECT min(at), max(day), max(stay) FROM visits")), [["2024-01-01 00:00:00", "2024-10-18", "16:39:00"]]);
+        assert!(matches!(fixture.run("INSERT INTO visits VALUES (1000, '2024-13-01', NULL, NULL)"), Err(ExecutionError::InvalidDatetime { data_type: DataType::Date, .. })));
+
+        // the index finds timestamps given as strings or dates
+        fixture.run("ANALYZE").unwrap();
+        let ids = |fixture: &mut Fixture, condition: &str| {
+            let sql = format!("SELECT id FROM visits WHERE {condition}");
+            let plan = fixture.rows(&format!("EXPLAIN {sql}"));
+            assert!(plan.iter().any(|row| row[0].to_string().contains("visits_at_idx")), "{plan:?}");
+            fixture.rows(&sql)
+        };
+        assert_eq!(ids(&mut fixture, "at = '2024-01-02 11:00'"), [[int(5)]]);
+        assert_eq!(ids(&mut fixture, "at >= DATE '2024-01-02' AND at < '2024-01-02T12:00:00Z'"), [[int(4)], [int(5)]]);
+
+        // grouped by month, and with the functions and arithmetic of dates and times
+        assert_eq!(
+            strings(fixture.rows("SELECT date_trunc('month', at), count(*) FROM visits WHERE at < '2024-03-01' GROUP BY date_trunc('month', at) ORDER BY 1")),
+            [["2024-01-01 00:00:00", "107"], ["2024-02-01 00:00:00", "99"]]
+        );
+        assert_eq!(
+            strings(fixture.rows("SELECT extract(dow FROM day), date_part('hour', at), at + stay, day - DATE '2023-12-25' FROM visits WHERE id = 999")),
+            [["5", "9", "2024-10-19 01:39:00", "298"]]
+        );
+        assert_eq!(fixture.rows("SELECT id FROM visits WHERE at - day > INTERVAL '20 hours' ORDER BY stay DESC LIMIT 2"), [[int(994)], [int(987)]]);
+        assert!(matches!(fixture.run("SELECT date_trunc('fortnight', at) FROM visits"), Err(ExecutionError::TypeError(_))));
+        assert_eq!(fixture.rows("SELECT count(*) FROM visits WHERE at < now()"), [[Value::BigInt(1000)]]);
+    }
+
+    #[test]
+    fn test_analyze() {
+        let mut fixture = users();
+        fixture.run("CREATE TABLE readings (id INT, sensor VARCHAR, value INT)").unwrap();
+        let rows: Vec<String> = (0..2000)
+            .map(|i| {
+                let sensor = if i % 4 == 0 { "NULL".to_string() } else { format!("'sensor{}'", i % 10) };
+                format!("({i}, {sensor}, {})", i % 1000)
+            })
+            .collect();
+        fixture.run(&format!("INSERT INTO readings VALUES {}", rows.join(", "))).unwrap();
+        assert_eq!(fixture.catalog.statistics("readings").unwrap(), None);
+
+        assert_eq!(fixture.run("ANALYZE readings").unwrap(), QueryResult::Affected(0));
+        assert_eq!(fixture.catalog.statistics("users").unwrap(), None);
+        let statistics = fixture.catalog.statistics("readings").unwrap().unwrap();
+        assert_eq!(statistics.row_count, 2000);
+        let columns: Vec<(f64, f64)> = statistics.columns.iter().map(|column| (column.null_fraction, column.distinct_count)).collect();
+        assert_eq!(columns, [(0.0, 2000.0), (0.25, 10.0), (0.0, 1000.0)]);
+        assert_eq!(statistics.columns[2].histogram.first(), Some(&int(0)));
+        assert_eq!(statistics.columns[2].histogram.last(), Some(&int(999)));
+
+        // which the planner goes by from now on
+        let plan = |fixture: &Fixture, sql: &str| {
+            let Statement::Select(select) = parse_statement(sql).unwrap() else { unreachable!() };
+            Planner::new(&fixture.catalog, fixture.buffer_pool.clone(), &fixture.catalog).plan_select(&select).unwrap()
+        };
+        let rows = plan(&fixture, "SELECT * FROM readings WHERE value < 100").rows;
+        assert!((rows - 200.0).abs() < 10.0, "{rows}");
+        let rows = plan(&fixture, "SELECT * FROM readings WHERE value >= 100 AND value < 300").rows;
+        assert!((rows - 400.0).abs() < 10.0, "{rows}");
+        let rows = plan(&fixture, "SELECT * FROM readings WHERE sensor IS NULL").rows;
+        assert_eq!(rows, 500.0);
+
+        // every table without one named
+        fixture.run("ANALYZE").unwrap();
+        assert_eq!(fixture.catalog.statistics("users").unwrap().unwrap().row_count, 3);
+        assert!(matches!(fixture.run("ANALYZE missing"), Err(ExecutionError::TableNotFound(name)) if name == "missing"));
+    }
+
+    #[test]
+    fn test_explain() {
+        let mut fixture = users();
+        fixture.run("CREATE TABLE orders (id INT PRIMARY KEY, user_id INT, total INT)").unwrap();
+        fixture.run("INSERT INTO orders VALUES (1, 1, 30), (2, 1, 5), (3, 2, 12), (4, 3, 99)").unwrap();
+        fixture.run("ANALYZE").unwrap();
+        let lines = |fixture: &mut Fixture, sql: &str| -> Vec<String> {
+            fixture.rows(sql).into_iter().map(|row| row[0].to_string()).collect()
+        };
+        let sql = "SELECT u.name, o.total FROM users u JOIN orders o ON o.user_id = u.id WHERE o.total > 10";
+        let expected = [
+            "Project  (cost=2.17 rows=3)",
+            "  ->  Hash Join  (cost=2.16 rows=3)",
+            "        Hash Cond: (o.user_id = u.id)",
+            "        ->  Seq Scan on orders o  (cost=1.05 rows=3)",
+            "              Filter: (o.total > 10)",
+            "        ->  Seq Scan on users u  (cost=1.03 rows=3)",
+        ];
+        assert_eq!(lines(&mut fixture, &format!("EXPLAIN {sql}")), expected);
+
+        // the same plan, with what each operator actually produced
+        let output = lines(&mut fixture, &format!("EXPLAIN ANALYZE {sql}"));
+        assert_eq!(output.len(), expected.len() + 2);
+        for (line, expected) in output.iter().zip(expected) {
+            let Some((estimated, actual)) = line.split_once(" (actual time=") else {
+                assert_eq!(line, expected);
+                continue;
+            };
+            assert_eq!(estimated, expected);
+            assert!(actual.ends_with(" ms rows=3)"), "{line}");
+        }
+        assert!(output[6].starts_with("Planning Time: ") && output[7].starts_with("Execution Time: "));
+
+        // only EXPLAIN ANALYZE runs the query
+        assert_eq!(lines(&mut fixture, "EXPLAIN SELECT 1 / 0"), ["Project  (cost=0.00 rows=1)", "  ->  Result  (cost=0.00 rows=1)"]);
+        assert!(matches!(fixture.run("EXPLAIN ANALYZE SELECT 1 / 0"), Err(ExecutionError::DivisionByZero)));
+    }
+
+    #[test]
+    fn test_unknown_tables_and_columns() {
+        let mut fixture = users();
+        assert!(matches!(fixture.run("SELECT * FROM orders"), Err(ExecutionError::TableNotFound(name)) if name == "orders"));
+        assert!(matches!(fixture.run("SELECT age FROM users"), Err(ExecutionError::ColumnNotFound(_))));
+        assert!(matches!(fixture.run("SELECT *"), Err(ExecutionError::WildcardWithoutTable)));
+        assert!(matches!(fixture.run("SELECT * FROM users WHERE id"), Err(ExecutionError::TypeError(_))));
+    }
+
+    #[test]
+    fn test_prepared_statements() {
+        let mut fixture = users();
+        fixture.run("CREATE INDEX users_id_idx ON users (id)").unwrap();
+        fixture.run("CREATE TABLE numbers (id INT PRIMARY KEY, name VARCHAR)").unwrap();
+        let rows: Vec<String> = (0..3000).map(|id| format!("({id}, 'number {id}')")).collect();
+        fixture.run(&format!("INSERT INTO numbers VALUES {}", rows.join(", "))).unwrap();
+        let mut executor = Executor::new(fixture.buffer_pool.clone(), &mut fixture.catalog);
+
+        // a parameter is as good as a literal for picking an index
+        let prepared = executor.prepare(parse_statement("SELECT name FROM numbers WHERE id = $1").unwrap()).unwrap();
+        let PlanNode::Project { input, .. } = &prepared.plan.as_ref().unwrap().node else { unreachable!() };
+        assert!(matches!(&input.node, PlanNode::IndexScan { prefix, .. } if prefix == &[Expr::Parameter(1)]), "{input:?}");
+        assert_eq!(prepared.parameter_types(), [Some(DataType::Int)]);
+
+        let mut run = |sql: &str, parameters: &[Value]| {
+            let prepared = executor.prepare(parse_statement(sql).unwrap())?;
+            executor.execute_prepared(&mut Transaction::new(1), &prepared, parameters)
+        };
+        let rows = |result: Result<QueryResult, ExecutionError>| match result.unwrap() {
+            QueryResult::Rows { rows, .. } => rows,
+            other => panic!("expected rows, got {other:?}"),
+        };
+
+        // the index scan works out the parameter in its key when it runs
+        assert_eq!(rows(run("SELECT name FROM numbers WHERE id = $1", &[int(1234)])), vec![vec![text("number 1234")]]);
+        assert_eq!(rows(run("SELECT name FROM users WHERE id = $1", &[int(2)])), vec![vec![text("sam")]]);
+        assert_eq!(rows(run("SELECT name FROM users WHERE id = ?", &[Value::BigInt(3)])), vec![vec![text("gollum")]]);
+        assert!(rows(run("SELECT name FROM users WHERE id = $1", &[Value::Null])).is_empty());
+        assert_eq!(rows(run("SELECT id FROM users WHERE visits > $1 AND $2 ORDER BY id", &[int(15), Value::Bool(true)])), vec![vec![int(2)], vec![int(3)]]);
+        // aggregates and group keys with parameters keep their names above the aggregate
+        assert_eq!(
+            rows(run("SELECT active, sum(visits * $1) FROM users GROUP BY active HAVING sum(visits * $1) > $2", &[Value::BigInt(2), Value::BigInt(100)])),
+            vec![vec![Value::Bool(false), Value::BigInt(1000)]]
+        );
+
+        assert_eq!(run("INSERT INTO users VALUES (?, ?, ?, ?)", &[int(4), text("merry"), int(7), Value::Bool(true)]).unwrap(), QueryResult::Affected(1));
+        assert_eq!(run("UPDATE users SET visits = $2 WHERE name = $1", &[text("merry"), int(8)]).unwrap(), QueryResult::Affected(1));
+        assert_eq!(rows(run("SELECT visits FROM users WHERE id = $1", &[int(4)])), vec![vec![Value::BigInt(8)]]);
+
+        assert!(matches!(run("SELECT * FROM users WHERE id = $1", &[]), Err(ExecutionError::ParameterCountMismatch { expected: 1, found: 0 })));
+        assert!(matches!(run("SELECT * FROM users WHERE id = $1", &[text("one")]), Err(ExecutionError::TypeError(_))));
+        assert!(matches!(run("SELECT * FROM users WHERE id = $1 OR name = $1", &[int(1)]), Err(ExecutionError::TypeError(_))));
+        assert_eq!(rows(run("SELECT $1", &[text("any")])), vec![vec![text("any")]]);
+    }
+}
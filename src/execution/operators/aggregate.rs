@@ -0,0 +1,326 @@
+use super::Operator;
+use super::spill::{MAX_SPLITS, PARTITIONS, SpillFile, partition_of, row_size};
+use crate::execution::{ExecutionError, Scope, compare, evaluate};
+use crate::sql::{AggregateFunction, Expr};
+use crate::types::Value;
+use std::cmp::Ordering;
+use std::collections::HashMap;
+
+/// bytes charged for the running state of one aggregate of a group
+const ACCUMULATOR_SIZE: usize = 32;
+
+/// Groups the rows of `input` by their values of `group_by`, and hands out a
+/// row for each group: those values, then the value of each aggregate over
+/// the rows of the group.
+///
+/// All of the input is read on the first call, into a hash table of groups.
+/// Once the table takes up the memory budget, rows of groups already in it
+/// are still added to them, but rows of any other group are set aside in
+/// partitions on disk, split by a hash of their group. When the table's
+/// groups have been handed out, each partition is read back the same way,
+/// and split again if its groups don't fit either.
+///
+/// Without `group_by` there's a single group, even of no rows at all.
+pub(super) struct HashAggregate {
+    /// taken once its rows have been read
+    input: Option<Box<dyn Operator>>,
+    group_by: Vec<Expr>,
+    /// each aggregate's function, and what it's of (`None` for `COUNT(*)`)
+    aggregates: Vec<(AggregateFunction, Option<Expr>)>,
+    /// the columns of the input rows
+    scope: Scope,
+    /// bytes of groups the table can hold
+    work_memory: usize,
+    groups: HashMap<Vec<Value>, Vec<Accumulator>>,
+    /// bytes the groups in the table take up
+    memory: usize,
+    /// the groups being handed out
+    output: Option<std::collections::hash_map::IntoIter<Vec<Value>, Vec<Accumulator>>>,
+    /// rows set aside, each written as its group's values followed by the values
+    /// the aggregates are of, with the round of splitting they're from
+    partitions: Vec<(SpillFile, usize)>,
+    /// partitions written to disk so far
+    spilled: usize,
+}
+
+impl HashAggregate {
+    pub(super) fn new(input: Box<dyn Operator>, group_by: Vec<Expr>, aggregates: &[Expr], scope: Scope, work_memory: usize) -> Self {
+        let aggregates = aggregates
+            .iter()
+            .map(|aggregate| match aggregate {
+                Expr::Aggregate { function, arg } => (*function, arg.as_deref().cloned()),
+                other => unreachable!("{other} is not an aggregate"),
+            })
+            .collect();
+        Self {
+            input: Some(input),
+            group_by,
+            aggregates,
+            scope,
+            work_memory,
+            groups: HashMap::new(),
+            memory: 0,
+            output: None,
+            partitions: Vec::new(),
+            spilled: 0,
+        }
+    }
+
+    /// reads every input row into the table or the first partitions
+    fn aggregate_input(&mut self, mut input: Box<dyn Operator>) -> Result<(), ExecutionError> {
+        let mut partitions = None;
+        while let Some(row) = input.next()? {
+            let key = self.group_by.iter().map(|expr| evaluate(expr, &self.scope, &row)).collect::<Result<Vec<_>, _>>()?;
+            // COUNT(*) counts every row, as if of a value that's never NULL
+            let values = self
+                .aggregates
+                .iter()
+                .map(|(_, arg)| arg.as_ref().map_or(Ok(Value::Bool(true)), |arg| evaluate(arg, &self.scope, &row)))
+                .collect::<Result<Vec<_>, _>>()?;
+            self.add(key, values, &mut partitions, 0)?;
+        }
+        if self.group_by.is_empty() && self.groups.is_empty() {
+            self.groups.insert(Vec::new(), self.aggregates.iter().map(|(function, _)| Accumulator::new(*function)).collect());
+        }
+        self.set_aside(partitions, 0);
+        Ok(())
+    }
+
+    /// reads the rows of a partition from round `splits` into the table or further partitions
+    fn aggregate_partition(&mut self, mut rows: SpillFile, splits: usize) -> Result<(), ExecutionError> {
+        let mut partitions = None;
+        while let Some(mut key) = rows.read()? {
+            let values = key.split_off(self.group_by.len());
+            self.add(key, values, &mut partitions, splits + 1)?;
+        }
+        self.set_aside(partitions, splits + 1);
+        Ok(())
+    }
+
+    /// Adds a row with the group `key` and the aggregated `values` to its group,
+    /// in the table if there's room, or else to one of `partitions` from round
+    /// `splits`, which are created the first time.
+    fn add(&mut self, key: Vec<Value>, values: Vec<Value>, partitions: &mut Option<Vec<SpillFile>>, splits: usize) -> Result<(), ExecutionError> {
+        if let Some(accumulators) = self.groups.get_mut(&key) {
+            for (accumulator, value) in accumulators.iter_mut().zip(values) {
+                accumulator.add(value)?;
+            }
+            return Ok(());
+        }
+        let size = row_size(&key) + ACCUMULATOR_SIZE * self.aggregates.len();
+        if self.memory + size > self.work_memory && !self.groups.is_empty() && splits < MAX_SPLITS {
+            let partitions = match partitions {
+                Some(partitions) => partitions,
+                None => partitions.insert((0..PARTITIONS).map(|_| SpillFile::create()).collect::<Result<_, _>>()?),
+            };
+            let partition = partition_of(&key, splits);
+            let mut row = key;
+            row.extend(values);
+            return partitions[partition].write(&row);
+        }
+        self.memory += size;
+        let mut accumulators: Vec<Accumulator> = self.aggregates.iter().map(|(function, _)| Accumulator::new(*function)).collect();
+        for (accumulator, value) in accumulators.iter_mut().zip(values) {
+            accumulator.add(value)?;
+        }
+        self.groups.insert(key, accumulators);
+        Ok(())
+    }
+
+    /// keeps the partitions rows were written to in round `splits` for later
+    fn set_aside(&mut self, partitions: Option<Vec<SpillFile>>, splits: usize) {
+        for partition in partitions.into_iter().flatten() {
+            self.spilled += 1;
+            if partition.len() > 0 {
+                self.partitions.push((partition, splits));
+            }
+        }
+    }
+}
+
+impl Operator for HashAggregate {
+    fn next(&mut self) -> Result<Option<Vec<Value>>, ExecutionError> {
+        loop {
+            if let Some((mut row, accumulators)) = self.output.as_mut().and_then(Iterator::next) {
+                for accumulator in accumulators {
+                    row.push(accumulator.finish()?);
+                }
+                return Ok(Some(row));
+            }
+            if let Some(input) = self.input.take() {
+                self.aggregate_input(input)?;
+            } else if let Some((rows, splits)) = self.partitions.pop() {
+                self.aggregate_partition(rows, splits)?;
+            } else {
+                return Ok(None);
+            }
+            self.output = Some(std::mem::take(&mut self.groups).into_iter());
+            self.memory = 0;
+        }
+    }
+}
+
+/// The running state of an aggregate function over the rows of a group so far.
+///
+/// Like PostgreSQL's, every function but `COUNT` skips NULLs and is NULL over
+/// no other values. `SUM` adds integers up as a `BIGINT`, and `AVG` divides
+/// that by the count the way `/` does, rounding toward zero. Once a `NUMERIC`
+/// is added both are exact `NUMERIC`s, the average rounded the way dividing
+/// decimals is.
+enum Accumulator {
+    Count(i64),
+    Sum(Option<Value>),
+    Avg { sum: Value, count: i64 },
+    Min(Option<Value>),
+    Max(Option<Value>),
+}
+
+impl Accumulator {
+    fn new(function: AggregateFunction) -> Self {
+        match function {
+            AggregateFunction::Count => Accumulator::Count(0),
+            AggregateFunction::Sum => Accumulator::Sum(None),
+            AggregateFunction::Avg => Accumulator::Avg { sum: Value::BigInt(0), count: 0 },
+            AggregateFunction::Min => Accumulator::Min(None),
+            AggregateFunction::Max => Accumulator::Max(None),
+        }
+    }
+
+    fn add(&mut self, value: Value) -> Result<(), ExecutionError> {
+        if value.is_null() {
+            return Ok(());
+        }
+        match self {
+            Accumulator::Count(count) => *count += 1,
+            Accumulator::Sum(sum) => *sum = Some(add(sum.as_ref().unwrap_or(&Value::BigInt(0)), &value, "sum")?),
+            Accumulator::Avg { sum, count } => {
+                *sum = add(sum, &value, "avg")?;
+                *count += 1;
+            }
+            Accumulator::Min(min) => {
+                if min.as_ref().map_or(Ok(true), |min| ordering(&value, min).map(|ordering| ordering == Ordering::Less))? {
+                    *min = Some(value);
+                }
+            }
+            Accumulator::Max(max) => {
+                if max.as_ref().map_or(Ok(true), |max| ordering(&value, max).map(|ordering| ordering == Ordering::Greater))? {
+                    *max = Some(value);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn finish(self) -> Result<Value, ExecutionError> {
+        let value = match self {
+            Accumulator::Count(count) => Value::BigInt(count),
+            Accumulator::Sum(sum) => sum.unwrap_or(Value::Null),
+            Accumulator::Avg { count: 0, .. } => Value::Null,
+            Accumulator::Avg { sum: Value::Numeric(sum), count } => Value::Numeric(sum.checked_div(count.into()).ok_or(ExecutionError::NumericOverflow)?),
+            Accumulator::Avg { sum: Value::BigInt(sum), count } => Value::BigInt(sum / count),
+            Accumulator::Avg { .. } => unreachable!("sums are BIGINTs or NUMERICs"),
+            Accumulator::Min(value) | Accumulator::Max(value) => value.unwrap_or(Value::Null),
+        };
+        Ok(value)
+    }
+}
+
+/// the running total `total` of `function` with `value` added, a `BIGINT` while
+/// only integers have been and a `NUMERIC` once one has
+fn add(total: &Value, value: &Value, function: &str) -> Result<Value, ExecutionError> {
+    let sum = match (total, value) {
+        (Value::BigInt(total), Value::Int(value)) => total.checked_add(*value as i64).map(Value::BigInt),
+        (Value::BigInt(total), Value::BigInt(value)) => total.checked_add(*value).map(Value::BigInt),
+        _ => match (total.to_decimal(), value.to_decimal()) {
+            (Some(total), Some(value)) => total.checked_add(value).map(Value::Numeric),
+            _ => return Err(ExecutionError::TypeError(format!("cannot {function} {value}"))),
+        },
+    };
+    sum.ok_or(ExecutionError::NumericOverflow)
+}
+
+fn ordering(left: &Value, right: &Value) -> Result<Ordering, ExecutionError> {
+    compare(left, right).ok_or_else(|| ExecutionError::TypeError(format!("cannot compare {left} and {right}")))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::execution::operators::tests::Rows;
+
+    /// aggregates rows of (`kind`, `n`) with `work_memory` bytes, the rows sorted, and the partitions it wrote
+    fn aggregate(rows: Vec<Vec<Value>>, group_by: Vec<Expr>, aggregates: &[&str], work_memory: usize) -> (Vec<Vec<Value>>, usize) {
+        let aggregates: Vec<Expr> = aggregates
+            .iter()
+            .map(|name| {
+                let (function, arg) = name.split_once(' ').unwrap();
+                let arg = (arg != "*").then(|| Box::new(Expr::column(arg)));
+                Expr::Aggregate { function: AggregateFunction::from_name(function).unwrap(), arg }
+            })
+            .collect();
+        let scope = Scope::named(["kind".to_string(), "n".to_string()]);
+        let mut aggregate = HashAggregate::new(Box::new(Rows::new(rows)), group_by, &aggregates, scope, work_memory);
+        let mut groups = Vec::new();
+        while let Some(row) = aggregate.next().unwrap() {
+            groups.push(row);
+        }
+        groups.sort_by_key(|row| format!("{row:?}"));
+        (groups, aggregate.spilled)
+    }
+
+    #[test]
+    fn test_functions() {
+        let rows = vec![
+            vec![Value::Varchar("a".into()), Value::Int(3)],
+            vec![Value::Varchar("b".into()), Value::Null],
+            vec![Value::Varchar("a".into()), Value::Int(-8)],
+            vec![Value::Null, Value::Int(5)],
+            vec![Value::Varchar("a".into()), Value::Null],
+            vec![Value::Null, Value::Int(6)],
+        ];
+        let functions = ["count *", "count n", "sum n", "avg n", "min n", "max n"];
+        let (groups, _) = aggregate(rows.clone(), vec![Expr::column("kind")], &functions, usize::MAX);
+        let groups: Vec<String> = groups.iter().map(|row| row.iter().map(Value::to_string).collect::<Vec<_>>().join(" ")).collect();
+        // NULL is a group of its own, and averages round toward zero
+        assert_eq!(groups, ["NULL 2 2 11 5 5 6", "a 3 2 -5 -2 -8 3", "b 1 0 NULL NULL NULL NULL"]);
+
+        // everything is one group, and no rows still make one
+        let (groups, _) = aggregate(rows, Vec::new(), &["count *", "max kind"], usize::MAX);
+        assert_eq!(groups, [vec![Value::BigInt(6), Value::Varchar("b".into())]]);
+        let (groups, _) = aggregate(Vec::new(), Vec::new(), &["count *", "sum n"], usize::MAX);
+        assert_eq!(groups, [vec![Value::BigInt(0), Value::Null]]);
+        let (groups, _) = aggregate(Vec::new(), vec![Expr::column("kind")], &["count *"], usize::MAX);
+        assert!(groups.is_empty());
+    }
+
+    #[test]
+    fn test_groups_beyond_memory_are_spilled() {
+        let rows: Vec<Vec<Value>> = (0..20_000).map(|i| vec![Value::Varchar(format!("kind {}", i % 3000)), Value::BigInt(i)]).collect();
+        let group_by = vec![Expr::column("kind")];
+        let functions = ["count *", "sum n", "min n"];
+        let (in_memory, spilled) = aggregate(rows.clone(), group_by.clone(), &functions, usize::MAX);
+        assert_eq!((in_memory.len(), spilled), (3000, 0));
+        assert_eq!(in_memory[0], [Value::Varchar("kind 0".into()), Value::BigInt(7), Value::BigInt((0..7).map(|i| i * 3000).sum()), Value::BigInt(0)]);
+
+        // room for a few hundred groups at a time, then for a handful
+        let (partitioned, spilled) = aggregate(rows.clone(), group_by.clone(), &functions, 48 * 1024);
+        assert_eq!(spilled, PARTITIONS);
+        assert_eq!(partitioned, in_memory);
+        let (partitioned, spilled) = aggregate(rows, group_by, &functions, 1024);
+        assert!(spilled > PARTITIONS * 2, "{spilled}");
+        assert_eq!(partitioned, in_memory);
+    }
+
+    #[test]
+    fn test_type_errors() {
+        let rows = vec![vec![Value::Varchar("a".into()), Value::Int(1)]];
+        let aggregates = [Expr::Aggregate { function: AggregateFunction::Sum, arg: Some(Box::new(Expr::column("kind"))) }];
+        let scope = Scope::named(["kind".to_string(), "n".to_string()]);
+        let mut aggregate = HashAggregate::new(Box::new(Rows::new(rows)), Vec::new(), &aggregates, scope, usize::MAX);
+        assert!(matches!(aggregate.next(), Err(ExecutionError::TypeError(_))));
+
+        let mut sum = Accumulator::new(AggregateFunction::Sum);
+        sum.add(Value::BigInt(i64::MAX)).unwrap();
+        assert!(matches!(sum.add(Value::Int(1)), Err(ExecutionError::NumericOverflow)));
+    }
+}
@@ -0,0 +1,304 @@
+use super::Operator;
+use super::spill::{MAX_SPLITS, PARTITIONS, SpillFile, partition_of, row_size};
+use crate::execution::{ExecutionError, Scope, evaluate, is_true};
+use crate::sql::Expr;
+use crate::types::Value;
+use std::collections::HashMap;
+
+/// Joins each row of `outer` with the rows of `inner` whose keys are equal to its own.
+///
+/// The rows of `inner` are read into a hash table by key on the first call,
+/// and the rows of `outer` then probe it one at a time. If the table grows
+/// beyond the memory budget, the inner rows are split by a hash of their
+/// keys into partitions written to disk, and the outer rows are split the
+/// same way. Each partition of inner rows is then loaded and probed by the
+/// outer rows of the same partition in turn, split again first if it's still
+/// too large.
+///
+/// Keys with a NULL never match, and rows for which the keys match also have
+/// to satisfy the condition, if there is one.
+pub(super) struct HashJoin {
+    outer: Box<dyn Operator>,
+    /// taken once its rows have been read
+    inner: Option<Box<dyn Operator>>,
+    outer_keys: Vec<Expr>,
+    inner_keys: Vec<Expr>,
+    condition: Option<Expr>,
+    outer_scope: Scope,
+    inner_scope: Scope,
+    /// the columns of the joined rows
+    scope: Scope,
+    /// bytes of rows the hash table can hold
+    work_memory: usize,
+    /// the inner rows being joined, by key
+    table: HashMap<Vec<Value>, Vec<Vec<Value>>>,
+    probe: Probe,
+    /// partitions still to be joined
+    partitions: Vec<Partition>,
+    /// the row probing the table with its key, and the position of the next match to try with it
+    current: Option<(Vec<Value>, Vec<Value>)>,
+    position: usize,
+    /// partitions written to disk so far
+    spilled: usize,
+}
+
+/// where the rows probing the hash table come from
+enum Probe {
+    /// the inner rows haven't been read yet
+    Start,
+    /// straight from the outer rows, all of the inner ones fit in memory
+    Outer,
+    /// the outer rows are to be split up like these partitions of the inner ones
+    Split(Vec<Partition>),
+    /// the outer rows of the partition in the table
+    Spilled(SpillFile),
+    /// the partition in the table has been joined
+    PartitionDone,
+}
+
+/// inner and outer rows whose keys hash alike, after `splits` rounds of splitting
+struct Partition {
+    build: SpillFile,
+    probe: SpillFile,
+    splits: usize,
+}
+
+impl HashJoin {
+    pub(super) fn new(
+        outer: Box<dyn Operator>,
+        inner: Box<dyn Operator>,
+        (outer_keys, outer_scope): (Vec<Expr>, Scope),
+        (inner_keys, inner_scope): (Vec<Expr>, Scope),
+        condition: Option<Expr>,
+        work_memory: usize,
+    ) -> Self {
+        let scope = outer_scope.join(&inner_scope);
+        Self {
+            outer,
+            inner: Some(inner),
+            outer_keys,
+            inner_keys,
+            condition,
+            outer_scope,
+            inner_scope,
+            scope,
+            work_memory,
+            table: HashMap::new(),
+            probe: Probe::Start,
+            partitions: Vec::new(),
+            current: None,
+            position: 0,
+            spilled: 0,
+        }
+    }
+
+    /// reads the inner rows into the table, or into partitions once they don't fit
+    fn build(&mut self) -> Result<(), ExecutionError> {
+        let mut inner = self.inner.take().unwrap();
+        let mut memory = 0;
+        let mut partitions: Option<Vec<Partition>> = None;
+        while let Some(row) = inner.next()? {
+            let Some(key) = key(&self.inner_keys, &self.inner_scope, &row)? else {
+                continue;
+            };
+            if let Some(partitions) = &mut partitions {
+                partitions[partition_of(&key, 0)].build.write(&row)?;
+                continue;
+            }
+            memory += row_size(&row) + row_size(&key);
+            self.table.entry(key).or_default().push(row);
+            if memory > self.work_memory {
+                partitions = Some(self.spill_table(0)?);
+            }
+        }
+        self.probe = match partitions {
+            Some(partitions) => Probe::Split(partitions),
+            None => Probe::Outer,
+        };
+        Ok(())
+    }
+
+    /// moves the rows in the table out to new partitions, split by how their keys hash in round `splits`
+    fn spill_table(&mut self, splits: usize) -> Result<Vec<Partition>, ExecutionError> {
+        let mut partitions = (0..PARTITIONS)
+            .map(|_| Ok(Partition { build: SpillFile::create()?, probe: SpillFile::create()?, splits }))
+            .collect::<Result<Vec<_>, ExecutionError>>()?;
+        for (key, rows) in self.table.drain() {
+            let partition = &mut partitions[partition_of(&key, splits)];
+            for row in rows {
+                partition.build.write(&row)?;
+            }
+        }
+        self.spilled += PARTITIONS;
+        Ok(partitions)
+    }
+
+    /// loads the inner rows of `partition` into the table and gets ready to probe it
+    /// with its outer rows, or splits it further if it doesn't fit
+    fn load(&mut self, partition: Partition) -> Result<(), ExecutionError> {
+        let Partition { mut build, mut probe, splits } = partition;
+        if build.len() == 0 || probe.len() == 0 {
+            return Ok(());
+        }
+        let mut memory = 0;
+        let mut split: Option<Vec<Partition>> = None;
+        while let Some(row) = build.read()? {
+            let key = key(&self.inner_keys, &self.inner_scope, &row)?.expect("rows with NULL keys aren't spilled");
+            if let Some(split) = &mut split {
+                split[partition_of(&key, splits + 1)].build.write(&row)?;
+                continue;
+            }
+            memory += row_size(&row) + row_size(&key);
+            self.table.entry(key).or_default().push(row);
+            if memory > self.work_memory && splits + 1 < MAX_SPLITS {
+                split = Some(self.spill_table(splits + 1)?);
+            }
+        }
+        match split {
+            Some(mut split) => {
+                while let Some(row) = probe.read()? {
+                    let key = key(&self.outer_keys, &self.outer_scope, &row)?.expect("rows with NULL keys aren't spilled");
+                    split[partition_of(&key, splits + 1)].probe.write(&row)?;
+                }
+                self.partitions.extend(split);
+            }
+            None => self.probe = Probe::Spilled(probe),
+        }
+        Ok(())
+    }
+
+    /// the next row to probe the table with
+    fn next_probe(&mut self) -> Result<Option<Vec<Value>>, ExecutionError> {
+        loop {
+            match &mut self.probe {
+                Probe::Start => {
+                    self.build()?;
+                    // nothing to join the outer rows with, no need to read them
+                    if self.table.is_empty() && matches!(self.probe, Probe::Outer) {
+                        return Ok(None);
+                    }
+                }
+                Probe::Outer => return self.outer.next(),
+                Probe::Split(partitions) => {
+                    while let Some(row) = self.outer.next()? {
+                        if let Some(key) = key(&self.outer_keys, &self.outer_scope, &row)? {
+                            partitions[partition_of(&key, 0)].probe.write(&row)?;
+                        }
+                    }
+                    let Probe::Split(partitions) = std::mem::replace(&mut self.probe, Probe::PartitionDone) else { unreachable!() };
+                    self.partitions = partitions;
+                }
+                Probe::Spilled(rows) => match rows.read()? {
+                    Some(row) => return Ok(Some(row)),
+                    None => self.probe = Probe::PartitionDone,
+                },
+                Probe::PartitionDone => {
+                    self.table.clear();
+                    let Some(partition) = self.partitions.pop() else {
+                        return Ok(None);
+                    };
+                    self.load(partition)?;
+                }
+            }
+        }
+    }
+}
+
+impl Operator for HashJoin {
+    fn next(&mut self) -> Result<Option<Vec<Value>>, ExecutionError> {
+        loop {
+            if let Some((outer_row, key)) = &self.current {
+                let matches = self.table.get(key).map_or(&[][..], Vec::as_slice);
+                while let Some(inner_row) = matches.get(self.position) {
+                    self.position += 1;
+                    let mut row = outer_row.clone();
+                    row.extend_from_slice(inner_row);
+                    if self.condition.as_ref().map_or(Ok(true), |condition| is_true(condition, &self.scope, &row))? {
+                        return Ok(Some(row));
+                    }
+                }
+                self.current = None;
+            }
+            let Some(row) = self.next_probe()? else {
+                return Ok(None);
+            };
+            if let Some(key) = key(&self.outer_keys, &self.outer_scope, &row)? {
+                self.current = Some((row, key));
+                self.position = 0;
+            }
+        }
+    }
+}
+
+/// the values of `keys` for `row`, `None` if any is NULL; INTs are widened to BIGINTs, and
+/// whole NUMERICs that fit one taken as one, so equal numbers of any type hash alike
+fn key(keys: &[Expr], scope: &Scope, row: &[Value]) -> Result<Option<Vec<Value>>, ExecutionError> {
+    let mut values = Vec::with_capacity(keys.len());
+    for key in keys {
+        values.push(match evaluate(key, scope, row)? {
+            Value::Null => return Ok(None),
+            Value::Int(value) => Value::BigInt(value as i64),
+            Value::Numeric(value) => value.to_i64().map_or(Value::Numeric(value), Value::BigInt),
+            value => value,
+        });
+    }
+    Ok(Some(values))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::execution::operators::tests::Rows;
+    use crate::sql::BinaryOp;
+
+    /// joins `outer` (`id`, `x`) with `inner` (`id`, `y`) on their ids, where `x < y`
+    fn join(outer: Vec<Vec<Value>>, inner: Vec<Vec<Value>>, work_memory: usize) -> (Vec<Vec<Value>>, usize) {
+        let outer_scope = Scope::named(["id".to_string(), "x".to_string()]);
+        let inner_scope = Scope::named(["id".to_string(), "y".to_string()]);
+        let mut join = HashJoin::new(
+            Box::new(Rows::new(outer)),
+            Box::new(Rows::new(inner)),
+            (vec![Expr::column("id")], outer_scope),
+            (vec![Expr::column("id")], inner_scope),
+            Some(Expr::binary(Expr::column("x"), BinaryOp::Lt, Expr::column("y"))),
+            work_memory,
+        );
+        let mut rows = Vec::new();
+        while let Some(row) = join.next().unwrap() {
+            rows.push(row);
+        }
+        rows.sort_by_key(|row| format!("{row:?}"));
+        (rows, join.spilled)
+    }
+
+    #[test]
+    fn test_spills_and_matches_in_memory_join() {
+        // INT and BIGINT ids that are equal match, NULLs never do
+        let outer: Vec<Vec<Value>> = (0..2000).map(|i| vec![if i % 100 == 0 { Value::Null } else { Value::Int(i % 500) }, Value::Int(i)]).collect();
+        let inner: Vec<Vec<Value>> = (0..3000).map(|i| vec![if i % 7 == 0 { Value::Null } else { Value::BigInt(i % 600) }, Value::Int(i as i32)]).collect();
+
+        let (in_memory, spilled) = join(outer.clone(), inner.clone(), usize::MAX);
+        assert_eq!(spilled, 0);
+        // every pair of an outer i and an inner j with i % 500 = j % 600 and i < j, where neither is a multiple of 100 and 7 respectively
+        let expected = (0..2000).filter(|i| i % 100 != 0).map(|i: i64| (i + 1..3000).filter(|j| j % 7 != 0 && i % 500 == j % 600).count()).sum::<usize>();
+        assert_eq!(in_memory.len(), expected);
+
+        // a budget of a few hundred rows splits the inner rows once, a tiny one as far as it goes
+        let (partitioned, spilled) = join(outer.clone(), inner.clone(), 64 * 1024);
+        assert_eq!(spilled, PARTITIONS);
+        assert_eq!(partitioned, in_memory);
+        let (partitioned, spilled) = join(outer, inner, 1024);
+        assert!(spilled > PARTITIONS, "{spilled}");
+        assert_eq!(partitioned, in_memory);
+    }
+
+    #[test]
+    fn test_skewed_keys_are_joined_in_memory_eventually() {
+        let outer = vec![vec![Value::Int(1), Value::Int(0)]; 3];
+        let inner = vec![vec![Value::Int(1), Value::Int(5)]; 200];
+        let (rows, spilled) = join(outer, inner, 100);
+        assert_eq!(rows.len(), 600);
+        // every round of splitting puts all of the rows in the same partition
+        assert_eq!(spilled, PARTITIONS * MAX_SPLITS);
+    }
+}
@@ -0,0 +1,182 @@
+use super::Operator;
+use super::scan::RowReader;
+use crate::execution::{ExecutionError, Scope, evaluate, is_true};
+use crate::index::{BPlusTree, KeyOrder, RangeScan, key_range};
+use crate::planner::{PhysicalPlan, PlanNode};
+use crate::sql::Expr;
+use crate::storage::{BufferPool, HeapFile};
+use crate::types::temporal::MICROS_PER_DAY;
+use crate::types::{DataType, Value};
+use std::ops::Bound;
+use std::sync::{Arc, Mutex};
+
+/// Joins each row of `outer` with every row of `inner` the condition holds for.
+///
+/// The inner rows are read into memory on the first call and tried against
+/// every outer row after that.
+pub(super) struct NestedLoopJoin {
+    outer: Box<dyn Operator>,
+    /// taken once its rows are in `inner_rows`
+    inner: Option<Box<dyn Operator>>,
+    inner_rows: Vec<Vec<Value>>,
+    condition: Option<Expr>,
+    /// the columns of the joined rows
+    scope: Scope,
+    /// the outer row being joined, and the position of the next inner row to try with it
+    current: Option<Vec<Value>>,
+    position: usize,
+}
+
+impl NestedLoopJoin {
+    pub(super) fn new(outer: Box<dyn Operator>, inner: Box<dyn Operator>, condition: Option<Expr>, scope: Scope) -> Self {
+        Self { outer, inner: Some(inner), inner_rows: Vec::new(), condition, scope, current: None, position: 0 }
+    }
+}
+
+impl Operator for NestedLoopJoin {
+    fn next(&mut self) -> Result<Option<Vec<Value>>, ExecutionError> {
+        if let Some(mut inner) = self.inner.take() {
+            while let Some(row) = inner.next()? {
+                self.inner_rows.push(row);
+            }
+        }
+        // nothing to join the outer rows with, no need to read them
+        if self.inner_rows.is_empty() {
+            return Ok(None);
+        }
+
+        loop {
+            let Some(outer_row) = &self.current else {
+                self.current = self.outer.next()?;
+                self.position = 0;
+                if self.current.is_none() {
+                    return Ok(None);
+                }
+                continue;
+            };
+            while let Some(inner_row) = self.inner_rows.get(self.position) {
+                self.position += 1;
+                let mut row = outer_row.clone();
+                row.extend_from_slice(inner_row);
+                if self.condition.as_ref().map_or(Ok(true), |condition| is_true(condition, &self.scope, &row))? {
+                    return Ok(Some(row));
+                }
+            }
+            self.current = None;
+        }
+    }
+}
+
+/// Joins each row of `outer` with the rows of a table an index finds under its
+/// values of the outer keys.
+///
+/// Nothing is read from the table up front: every outer row looks up its keys
+/// in the index, and each entry found is read from the heap file, checked
+/// against the filter and then the join condition. An outer row with a key
+/// that's NULL, or that no key column could hold, has nothing to join with.
+pub(super) struct IndexNestedLoopJoin {
+    outer: Box<dyn Operator>,
+    heap: HeapFile,
+    index: BPlusTree,
+    orders: Vec<KeyOrder>,
+    /// the type of each key column looked up
+    key_types: Vec<DataType>,
+    outer_keys: Vec<Expr>,
+    outer_scope: Scope,
+    /// decodes and filters the rows of the table found
+    rows: RowReader,
+    condition: Option<Expr>,
+    /// the columns of the joined rows
+    scope: Scope,
+    /// the outer row being joined, and the entries of the index under its key
+    current: Option<(Vec<Value>, RangeScan)>,
+}
+
+impl IndexNestedLoopJoin {
+    /// Starts the join `plan`, a `PlanNode::IndexNestedLoopJoin`, of the rows of `outer`.
+    pub(super) fn open(plan: &PhysicalPlan, outer: Box<dyn Operator>, buffer_pool: &Arc<Mutex<BufferPool>>) -> Result<Self, ExecutionError> {
+        let PlanNode::IndexNestedLoopJoin { outer: outer_plan, table, alias, columns, index, outer_keys, filter, condition } = &plan.node else {
+            unreachable!("{:?} isn't an index nested loop join", plan.node);
+        };
+        Ok(Self {
+            outer,
+            heap: HeapFile::open(buffer_pool.clone(), table.root_page_id)?,
+            index: index.open(buffer_pool.clone()),
+            orders: index.orders(),
+            key_types: index.columns.iter().map(|column| table.columns[column.position].data_type).collect(),
+            outer_keys: outer_keys.clone(),
+            outer_scope: outer_plan.scope(),
+            rows: RowReader::new(table, alias, columns, filter.as_ref()),
+            condition: condition.clone(),
+            scope: plan.scope(),
+            current: None,
+        })
+    }
+
+    /// the values to look up in the index for `row`, `None` if no entry can have them
+    fn key(&self, row: &[Value]) -> Result<Option<Vec<Value>>, ExecutionError> {
+        let mut values = Vec::with_capacity(self.outer_keys.len());
+        for (key, data_type) in self.outer_keys.iter().zip(&self.key_types) {
+            let value = match (evaluate(key, &self.outer_scope, row)?, *data_type) {
+                (Value::Null, _) => return Ok(None),
+                (Value::Int(value), DataType::BigInt) => Value::BigInt(value as i64),
+                (Value::BigInt(value), DataType::Int) => match i32::try_from(value) {
+                    Ok(value) => Value::Int(value),
+                    Err(_) => return Ok(None),
+                },
+                (value @ (Value::Int(_) | Value::BigInt(_) | Value::Numeric(_)), DataType::Numeric(_)) => Value::Numeric(value.to_decimal().unwrap()),
+                // only a whole number can be equal to an integer
+                (Value::Numeric(value), DataType::Int) => match value.to_i64().and_then(|value| i32::try_from(value).ok()) {
+                    Some(value) => Value::Int(value),
+                    None => return Ok(None),
+                },
+                (Value::Numeric(value), DataType::BigInt) => match value.to_i64() {
+                    Some(value) => Value::BigInt(value),
+                    None => return Ok(None),
+                },
+                // a date is equal to the timestamp of its midnight
+                (Value::Date(days), DataType::Timestamp) => match (days as i64).checked_mul(MICROS_PER_DAY) {
+                    Some(micros) => Value::Timestamp(micros),
+                    None => return Ok(None),
+                },
+                (Value::Timestamp(micros), DataType::Date) if micros % MICROS_PER_DAY == 0 => match i32::try_from(micros / MICROS_PER_DAY) {
+                    Ok(days) => Value::Date(days),
+                    Err(_) => return Ok(None),
+                },
+                (value, data_type) if value.fits(data_type) => value,
+                _ => return Ok(None),
+            };
+            values.push(value);
+        }
+        Ok(Some(values))
+    }
+}
+
+impl Operator for IndexNestedLoopJoin {
+    fn next(&mut self) -> Result<Option<Vec<Value>>, ExecutionError> {
+        loop {
+            if let Some((outer_row, entries)) = &mut self.current {
+                for entry in entries.by_ref() {
+                    let (_, rid) = entry?;
+                    let Some(inner_row) = self.rows.read(&self.heap.get(rid)?)? else {
+                        continue;
+                    };
+                    let mut row = outer_row.clone();
+                    row.extend(inner_row);
+                    if self.condition.as_ref().map_or(Ok(true), |condition| is_true(condition, &self.scope, &row))? {
+                        return Ok(Some(row));
+                    }
+                }
+                self.current = None;
+            }
+            let Some(row) = self.outer.next()? else {
+                return Ok(None);
+            };
+            if let Some(key) = self.key(&row)? {
+                let (start, end) = key_range(&key, Bound::Unbounded, Bound::Unbounded, &self.orders);
+                let entries = self.index.range(start.as_ref().map(Vec::as_slice), end.as_ref().map(Vec::as_slice))?;
+                self.current = Some((row, entries));
+            }
+        }
+    }
+}
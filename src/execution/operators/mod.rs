@@ -0,0 +1,232 @@
+mod aggregate;
+use aggregate::HashAggregate;
+
+mod hash_join;
+use hash_join::HashJoin;
+
+mod join;
+use join::{IndexNestedLoopJoin, NestedLoopJoin};
+
+mod scan;
+pub(super) use scan::{TableScan, index_range};
+
+mod sort;
+use sort::{Sort, TopN};
+
+mod spill;
+
+use super::{ExecutionError, Scope, evaluate, is_true};
+use crate::planner::{PhysicalPlan, PlanNode};
+use crate::sql::Expr;
+use crate::storage::BufferPool;
+use crate::types::Value;
+use std::cell::Cell;
+use std::rc::Rc;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// A running step of a plan, which hands out its rows one at a time and pulls
+/// rows from the operators below it as it needs them.
+pub(super) trait Operator {
+    /// the next row, `None` once there are no more
+    fn next(&mut self) -> Result<Option<Vec<Value>>, ExecutionError>;
+}
+
+/// Sets up the operators that run `plan`, each keeping up to `work_memory` bytes of rows in memory.
+pub(super) fn build(plan: &PhysicalPlan, buffer_pool: &Arc<Mutex<BufferPool>>, work_memory: usize) -> Result<Box<dyn Operator>, ExecutionError> {
+    Builder { buffer_pool, work_memory, profiles: None }.build(plan)
+}
+
+/// Like `build`, but also counts the rows each operator produces and the time
+/// spent producing them, into profiles listed in the order plan nodes are
+/// visited going depth first, each before the plans below it.
+pub(super) fn build_profiled(
+    plan: &PhysicalPlan,
+    buffer_pool: &Arc<Mutex<BufferPool>>,
+    work_memory: usize,
+) -> Result<(Box<dyn Operator>, Profiles), ExecutionError> {
+    let mut builder = Builder { buffer_pool, work_memory, profiles: Some(Vec::new()) };
+    let root = builder.build(plan)?;
+    Ok((root, Profiles(builder.profiles.unwrap())))
+}
+
+/// The profiles of the operators `build_profiled` set up, kept up to date as they run.
+pub(super) struct Profiles(Vec<Rc<Cell<Profile>>>);
+
+impl Profiles {
+    /// what each operator has taken so far
+    pub(super) fn get(&self) -> Vec<Profile> {
+        self.0.iter().map(|profile| profile.get()).collect()
+    }
+}
+
+/// What running one operator took.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub(super) struct Profile {
+    pub(super) rows: u64,
+    /// time spent in the operator and the ones below it
+    pub(super) time: Duration,
+}
+
+struct Builder<'a> {
+    buffer_pool: &'a Arc<Mutex<BufferPool>>,
+    work_memory: usize,
+    /// one per operator built so far, if they're profiled
+    profiles: Option<Vec<Rc<Cell<Profile>>>>,
+}
+
+impl Builder<'_> {
+    fn build(&mut self, plan: &PhysicalPlan) -> Result<Box<dyn Operator>, ExecutionError> {
+        let profile = self.profiles.as_mut().map(|profiles| {
+            profiles.push(Rc::default());
+            profiles.last().unwrap().clone()
+        });
+        let operator: Box<dyn Operator> = match &plan.node {
+            PlanNode::OneRow => Box::new(OneRow { done: false }),
+            PlanNode::SeqScan { .. } | PlanNode::IndexScan { .. } => Box::new(TableScan::open(plan, self.buffer_pool)?),
+            PlanNode::Filter { input, predicate } => Box::new(Filter {
+                input: self.build(input)?,
+                predicate: predicate.clone(),
+                scope: input.scope(),
+            }),
+            PlanNode::NestedLoopJoin { outer, inner, condition } => {
+                Box::new(NestedLoopJoin::new(self.build(outer)?, self.build(inner)?, condition.clone(), plan.scope()))
+            }
+            PlanNode::IndexNestedLoopJoin { outer, .. } => Box::new(IndexNestedLoopJoin::open(plan, self.build(outer)?, self.buffer_pool)?),
+            PlanNode::HashJoin { outer: outer_plan, inner: inner_plan, outer_keys, inner_keys, condition } => Box::new(HashJoin::new(
+                self.build(outer_plan)?,
+                self.build(inner_plan)?,
+                (outer_keys.clone(), outer_plan.scope()),
+                (inner_keys.clone(), inner_plan.scope()),
+                condition.clone(),
+                self.work_memory,
+            )),
+            PlanNode::HashAggregate { input, group_by, aggregates } => {
+                Box::new(HashAggregate::new(self.build(input)?, group_by.clone(), aggregates, input.scope(), self.work_memory))
+            }
+            PlanNode::Sort { input, keys } => Box::new(Sort::new(self.build(input)?, keys.clone(), input.scope(), self.work_memory)),
+            PlanNode::TopN { input, keys, count } => Box::new(TopN::new(self.build(input)?, keys.clone(), input.scope(), *count)),
+            PlanNode::Limit { input, limit, offset } => Box::new(Limit { input: self.build(input)?, limit: *limit, offset: *offset }),
+            PlanNode::Project { input, exprs, .. } => Box::new(Project {
+                input: self.build(input)?,
+                exprs: exprs.clone(),
+                scope: input.scope(),
+            }),
+        };
+        Ok(match profile {
+            Some(profile) => Box::new(Profiled { operator, profile }),
+            None => operator,
+        })
+    }
+}
+
+/// counts what goes through `operator`
+struct Profiled {
+    operator: Box<dyn Operator>,
+    profile: Rc<Cell<Profile>>,
+}
+
+impl Operator for Profiled {
+    fn next(&mut self) -> Result<Option<Vec<Value>>, ExecutionError> {
+        let start = Instant::now();
+        let row = self.operator.next()?;
+        let mut profile = self.profile.get();
+        profile.time += start.elapsed();
+        profile.rows += row.is_some() as u64;
+        self.profile.set(profile);
+        Ok(row)
+    }
+}
+
+struct OneRow {
+    done: bool,
+}
+
+impl Operator for OneRow {
+    fn next(&mut self) -> Result<Option<Vec<Value>>, ExecutionError> {
+        if std::mem::replace(&mut self.done, true) { Ok(None) } else { Ok(Some(Vec::new())) }
+    }
+}
+
+/// the rows of `input` for which `predicate` holds
+struct Filter {
+    input: Box<dyn Operator>,
+    predicate: Expr,
+    scope: Scope,
+}
+
+impl Operator for Filter {
+    fn next(&mut self) -> Result<Option<Vec<Value>>, ExecutionError> {
+        while let Some(row) = self.input.next()? {
+            if is_true(&self.predicate, &self.scope, &row)? {
+                return Ok(Some(row));
+            }
+        }
+        Ok(None)
+    }
+}
+
+/// the rows of `input` after the first `offset`, no more than `limit` of them
+struct Limit {
+    input: Box<dyn Operator>,
+    limit: Option<u64>,
+    offset: u64,
+}
+
+impl Operator for Limit {
+    fn next(&mut self) -> Result<Option<Vec<Value>>, ExecutionError> {
+        // the rest of the input isn't read once there's no more to hand out
+        if self.limit == Some(0) {
+            return Ok(None);
+        }
+        while self.offset > 0 {
+            if self.input.next()?.is_none() {
+                return Ok(None);
+            }
+            self.offset -= 1;
+        }
+        let row = self.input.next()?;
+        if let Some(limit) = &mut self.limit
+            && row.is_some()
+        {
+            *limit -= 1;
+        }
+        Ok(row)
+    }
+}
+
+struct Project {
+    input: Box<dyn Operator>,
+    exprs: Vec<Expr>,
+    scope: Scope,
+}
+
+impl Operator for Project {
+    fn next(&mut self) -> Result<Option<Vec<Value>>, ExecutionError> {
+        let Some(row) = self.input.next()? else {
+            return Ok(None);
+        };
+        let values = self.exprs.iter().map(|expr| evaluate(expr, &self.scope, &row)).collect::<Result<_, _>>()?;
+        Ok(Some(values))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// hands out the rows it was given
+    pub(super) struct Rows(std::vec::IntoIter<Vec<Value>>);
+
+    impl Rows {
+        pub(super) fn new(rows: Vec<Vec<Value>>) -> Self {
+            Self(rows.into_iter())
+        }
+    }
+
+    impl Operator for Rows {
+        fn next(&mut self) -> Result<Option<Vec<Value>>, ExecutionError> {
+            Ok(self.0.next())
+        }
+    }
+}
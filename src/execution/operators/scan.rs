@@ -0,0 +1,168 @@
+use super::Operator;
+use crate::catalog::{IndexInfo, TableInfo};
+use crate::execution::{ExecutionError, Scope, coerce, evaluate, is_true};
+use crate::index::{RangeScan, key_range};
+use crate::planner::{PhysicalPlan, PlanNode, column_references};
+use crate::sql::Expr;
+use crate::storage::{BufferPool, HeapFile, HeapScan, RecordId};
+use crate::types::{Column, DataType, Tuple, Value};
+use std::ops::Bound;
+use std::sync::{Arc, Mutex};
+
+/// Reads the rows of a table that pass a filter, straight from its heap file or
+/// in the order of one of its indexes, along with where each row is stored.
+pub(in crate::execution) struct TableScan {
+    source: Source,
+    rows: RowReader,
+}
+
+enum Source {
+    /// what an index scan for a NULL key reads
+    Nothing,
+    Heap(HeapScan),
+    /// the entries of the index, and the heap file they point into
+    Index { entries: RangeScan, heap: HeapFile },
+}
+
+impl TableScan {
+    /// Starts the scan `plan`, a `PlanNode::SeqScan` or `PlanNode::IndexScan`.
+    pub(in crate::execution) fn open(plan: &PhysicalPlan, buffer_pool: &Arc<Mutex<BufferPool>>) -> Result<Self, ExecutionError> {
+        let (source, rows) = match &plan.node {
+            PlanNode::SeqScan { table, alias, columns, filter } => {
+                let heap = HeapFile::open(buffer_pool.clone(), table.root_page_id)?;
+                (Source::Heap(heap.scan()), RowReader::new(table, alias, columns, filter.as_ref()))
+            }
+            PlanNode::IndexScan { table, alias, columns, index, prefix, lower, upper, filter } => {
+                let rows = RowReader::new(table, alias, columns, filter.as_ref());
+                let Some((start, end)) = index_range(table, index, prefix, lower, upper)? else {
+                    return Ok(Self { source: Source::Nothing, rows });
+                };
+                let heap = HeapFile::open(buffer_pool.clone(), table.root_page_id)?;
+                let entries = index.open(buffer_pool.clone()).range(bytes(&start), bytes(&end))?;
+                (Source::Index { entries, heap }, rows)
+            }
+            node => unreachable!("{node:?} isn't a scan"),
+        };
+        Ok(Self { source, rows })
+    }
+
+    /// the next row that passes the filter, and where it's stored
+    pub(in crate::execution) fn next_entry(&mut self) -> Result<Option<(RecordId, Vec<Value>)>, ExecutionError> {
+        loop {
+            let (rid, bytes) = match &mut self.source {
+                Source::Nothing => return Ok(None),
+                Source::Heap(tuples) => match tuples.next() {
+                    Some(tuple) => tuple?,
+                    None => return Ok(None),
+                },
+                Source::Index { entries, heap } => match entries.next() {
+                    Some(entry) => {
+                        let (_, rid) = entry?;
+                        (rid, heap.get(rid)?)
+                    }
+                    None => return Ok(None),
+                },
+            };
+            if let Some(row) = self.rows.read(&bytes)? {
+                return Ok(Some((rid, row)));
+            }
+        }
+    }
+}
+
+impl Operator for TableScan {
+    fn next(&mut self) -> Result<Option<Vec<Value>>, ExecutionError> {
+        Ok(self.next_entry()?.map(|(_, row)| row))
+    }
+}
+
+/// Decodes the rows a scan of a table reads: the columns it hands on, and
+/// whichever others its filter needs, which are dropped once it's checked.
+/// The bytes of any other column are skipped without being decoded.
+pub(super) struct RowReader {
+    schema: Vec<Column>,
+    /// positions of the columns decoded, in increasing order
+    read: Vec<usize>,
+    /// the decoded columns, under the query's alias for the table
+    scope: Scope,
+    filter: Option<Expr>,
+    /// where in the decoded columns each column handed on is, `None` if they're all handed on as they are
+    output: Option<Vec<usize>>,
+}
+
+impl RowReader {
+    /// reads the columns at positions `columns` of `table`, called `alias`, of the rows `filter` holds for
+    pub(super) fn new(table: &TableInfo, alias: &str, columns: &[usize], filter: Option<&Expr>) -> Self {
+        let all = Scope::aliased(table, alias);
+        let mut read = columns.to_vec();
+        for column in filter.map(column_references).unwrap_or_default() {
+            let Expr::Column { table, name } = column else { unreachable!() };
+            // a column that isn't there is left for the filter to report
+            read.extend(all.resolve(table.as_deref(), name));
+        }
+        read.sort_unstable();
+        read.dedup();
+        let output = (read != columns).then(|| columns.iter().map(|column| read.binary_search(column).unwrap()).collect());
+        let scope = Scope::aliased_columns(table, alias, &read);
+        Self { schema: table.columns.clone(), read, scope, filter: filter.cloned(), output }
+    }
+
+    /// the columns handed on of the row stored as `bytes`, if the filter holds for it
+    pub(super) fn read(&self, bytes: &[u8]) -> Result<Option<Vec<Value>>, ExecutionError> {
+        let row = Tuple::decode_columns(bytes, &self.schema, &self.read)?.into_values();
+        if !self.filter.as_ref().map_or(Ok(true), |filter| is_true(filter, &self.scope, &row))? {
+            return Ok(None);
+        }
+        Ok(Some(match &self.output {
+            Some(output) => output.iter().map(|&i| row[i].clone()).collect(),
+            None => row,
+        }))
+    }
+}
+
+/// `expr`, a literal or a bound parameter, as a value of an index column of
+/// type `data_type`. `None` for NULL, which no key is equal to or between.
+fn key_value(expr: &Expr, data_type: DataType) -> Result<Option<Value>, ExecutionError> {
+    let value = evaluate(expr, &Scope::empty(), &[])?;
+    let value = match value.to_decimal() {
+        // keys of numbers compare by value whatever their scale, so the number
+        // mustn't be rounded to the column's
+        Some(decimal) if matches!(data_type, DataType::Numeric(_)) => Value::Numeric(decimal),
+        _ => coerce(value, data_type)?,
+    };
+    match value {
+        Value::Null => Ok(None),
+        // the length of a VARCHAR doesn't matter to how it compares
+        value @ Value::Varchar(_) if matches!(data_type, DataType::Varchar(_)) => Ok(Some(value)),
+        value @ Value::Numeric(_) if matches!(data_type, DataType::Numeric(_)) => Ok(Some(value)),
+        value if value.fits(data_type) => Ok(Some(value)),
+        value => Err(ExecutionError::TypeError(format!("{value} cannot be compared with a key of type {data_type}"))),
+    }
+}
+
+/// the first and last keys of a range of them
+pub(in crate::execution) type KeyBounds = (Bound<Vec<u8>>, Bound<Vec<u8>>);
+
+/// The keys of `index` a `PlanNode::IndexScan` with `prefix`, `lower` and
+/// `upper` reads, none if one of them is NULL.
+pub(in crate::execution) fn index_range(table: &TableInfo, index: &IndexInfo, prefix: &[Expr], lower: &Bound<Expr>, upper: &Bound<Expr>) -> Result<Option<KeyBounds>, ExecutionError> {
+    // the key column each value is compared with, the bounds being on the one after the prefix
+    let data_type = |i: usize| table.columns[index.columns[i].position].data_type;
+    let next = prefix.len();
+    let bound = |bound: &Bound<Expr>| -> Result<Option<Bound<Value>>, ExecutionError> {
+        Ok(match bound {
+            Bound::Included(value) => key_value(value, data_type(next))?.map(Bound::Included),
+            Bound::Excluded(value) => key_value(value, data_type(next))?.map(Bound::Excluded),
+            Bound::Unbounded => Some(Bound::Unbounded),
+        })
+    };
+    let prefix: Option<Vec<Value>> = prefix.iter().enumerate().map(|(i, value)| key_value(value, data_type(i))).collect::<Result<_, _>>()?;
+    let (Some(prefix), Some(lower), Some(upper)) = (prefix, bound(lower)?, bound(upper)?) else {
+        return Ok(None);
+    };
+    Ok(Some(key_range(&prefix, lower.as_ref(), upper.as_ref(), &index.orders())))
+}
+
+fn bytes(bound: &Bound<Vec<u8>>) -> Bound<&[u8]> {
+    bound.as_ref().map(Vec::as_slice)
+}
@@ -0,0 +1,366 @@
+use super::Operator;
+use super::spill::{SpillFile, row_size};
+use crate::execution::{ExecutionError, MERGE_FAN_IN, Scope, compare, evaluate};
+use crate::index::KeyOrder;
+use crate::planner::SortKey;
+use crate::types::Value;
+use std::cmp::Ordering;
+
+/// Hands out the rows of `input` ordered by `keys`.
+///
+/// All of the input is read on the first call. Rows are buffered with their
+/// keys until they take up more than the memory budget, then sorted and
+/// written out to a file as a run. If everything fit in memory the buffer is
+/// just sorted; otherwise the runs are merged, a group at a time while there
+/// are too many to merge at once, and the rows come out of the last merge.
+///
+/// The sort is stable: rows with equal keys keep the order `input` gave them
+/// in, which earlier runs holding earlier rows and ties going to the earlier
+/// run preserve.
+pub(super) struct Sort {
+    /// taken once its rows have been read
+    input: Option<Box<dyn Operator>>,
+    keys: Vec<SortKey>,
+    /// the order of each key
+    orders: Vec<KeyOrder>,
+    /// the columns of the input rows
+    scope: Scope,
+    /// bytes of rows sorted in memory at a time
+    work_memory: usize,
+    output: Output,
+    /// runs written to disk so far, merged ones included
+    runs: usize,
+}
+
+/// a row, with the values of the sort keys for it
+struct Keyed {
+    key: Vec<Value>,
+    row: Vec<Value>,
+}
+
+enum Output {
+    /// the input hasn't been read yet
+    Unsorted,
+    Memory(std::vec::IntoIter<Keyed>),
+    Merge(Merge),
+}
+
+impl Sort {
+    pub(super) fn new(input: Box<dyn Operator>, keys: Vec<SortKey>, scope: Scope, work_memory: usize) -> Self {
+        let orders = keys.iter().map(|key| key.order).collect();
+        Self { input: Some(input), keys, orders, scope, work_memory, output: Output::Unsorted, runs: 0 }
+    }
+
+    /// reads every input row, sorting them in memory or into runs
+    fn sort(&mut self) -> Result<Output, ExecutionError> {
+        let mut input = self.input.take().unwrap();
+        let mut buffer = Vec::new();
+        let mut memory = 0;
+        let mut runs = Vec::new();
+        while let Some(row) = input.next()? {
+            let key = self.keys.iter().map(|key| evaluate(&key.expr, &self.scope, &row)).collect::<Result<Vec<_>, _>>()?;
+            memory += row_size(&row) + row_size(&key);
+            buffer.push(Keyed { key, row });
+            if memory > self.work_memory {
+                runs.push(self.write_run(&mut buffer)?);
+                memory = 0;
+            }
+        }
+        if runs.is_empty() {
+            buffer.sort_by(|left, right| compare_keys(&left.key, &right.key, &self.orders));
+            return Ok(Output::Memory(buffer.into_iter()));
+        }
+        if !buffer.is_empty() {
+            runs.push(self.write_run(&mut buffer)?);
+        }
+
+        // each pass merges groups of neighbouring runs, so earlier rows stay in earlier runs
+        while runs.len() > MERGE_FAN_IN {
+            let mut merged = Vec::new();
+            while !runs.is_empty() {
+                let rest = runs.split_off(runs.len().min(MERGE_FAN_IN));
+                let mut merge = Merge::new(std::mem::replace(&mut runs, rest), self.orders.clone())?;
+                let mut run = SpillFile::create()?;
+                while let Some(keyed) = merge.next()? {
+                    write_keyed(&mut run, keyed)?;
+                }
+                merged.push(run);
+                self.runs += 1;
+            }
+            runs = merged;
+        }
+        Ok(Output::Merge(Merge::new(runs, self.orders.clone())?))
+    }
+
+    /// sorts the rows in `buffer` and moves them to a new run
+    fn write_run(&mut self, buffer: &mut Vec<Keyed>) -> Result<SpillFile, ExecutionError> {
+        buffer.sort_by(|left, right| compare_keys(&left.key, &right.key, &self.orders));
+        let mut run = SpillFile::create()?;
+        for keyed in buffer.drain(..) {
+            write_keyed(&mut run, keyed)?;
+        }
+        self.runs += 1;
+        Ok(run)
+    }
+}
+
+impl Operator for Sort {
+    fn next(&mut self) -> Result<Option<Vec<Value>>, ExecutionError> {
+        if let Output::Unsorted = self.output {
+            self.output = self.sort()?;
+        }
+        let keyed = match &mut self.output {
+            Output::Unsorted => unreachable!(),
+            Output::Memory(rows) => rows.next(),
+            Output::Merge(merge) => merge.next()?,
+        };
+        Ok(keyed.map(|keyed| keyed.row))
+    }
+}
+
+/// Hands out the first `count` rows `Sort` would for the same input and keys.
+///
+/// All of the input is read on the first call, into a heap that holds the
+/// first `count` rows seen so far with the last of them at the top. A row
+/// that comes before the top replaces it, and any other is dropped, so rows
+/// with equal keys still keep the order they came in. The rows kept are then
+/// sorted to be handed out.
+pub(super) struct TopN {
+    /// taken once its rows have been read
+    input: Option<Box<dyn Operator>>,
+    keys: Vec<SortKey>,
+    orders: Vec<KeyOrder>,
+    /// the columns of the input rows
+    scope: Scope,
+    count: usize,
+    output: std::vec::IntoIter<Numbered>,
+}
+
+impl TopN {
+    pub(super) fn new(input: Box<dyn Operator>, keys: Vec<SortKey>, scope: Scope, count: u64) -> Self {
+        let orders = keys.iter().map(|key| key.order).collect();
+        let count = usize::try_from(count).unwrap_or(usize::MAX);
+        Self { input: Some(input), keys, orders, scope, count, output: Vec::new().into_iter() }
+    }
+
+    /// reads every input row, keeping the first ones
+    fn select(&mut self, mut input: Box<dyn Operator>) -> Result<Vec<Numbered>, ExecutionError> {
+        let mut heap: Vec<Numbered> = Vec::new();
+        if self.count == 0 {
+            return Ok(heap);
+        }
+        let mut number = 0;
+        while let Some(row) = input.next()? {
+            let key = self.keys.iter().map(|key| evaluate(&key.expr, &self.scope, &row)).collect::<Result<Vec<_>, _>>()?;
+            number += 1;
+            // a later row only comes before the top if its key does
+            if heap.len() < self.count {
+                heap.push(Numbered { number, keyed: Keyed { key, row } });
+                self.sift_up(&mut heap);
+            } else if compare_keys(&key, &heap[0].keyed.key, &self.orders) == Ordering::Less {
+                heap[0] = Numbered { number, keyed: Keyed { key, row } };
+                self.sift_down(&mut heap);
+            }
+        }
+        Ok(heap)
+    }
+
+    /// order of two rows by their keys, then by which came first
+    fn compare(&self, left: &Numbered, right: &Numbered) -> Ordering {
+        compare_keys(&left.keyed.key, &right.keyed.key, &self.orders).then(left.number.cmp(&right.number))
+    }
+
+    /// moves the last row of `heap` up to where it belongs
+    fn sift_up(&self, heap: &mut [Numbered]) {
+        let mut i = heap.len() - 1;
+        while i > 0 && self.compare(&heap[i], &heap[(i - 1) / 2]) == Ordering::Greater {
+            heap.swap(i, (i - 1) / 2);
+            i = (i - 1) / 2;
+        }
+    }
+
+    /// moves the top row of `heap` down to where it belongs
+    fn sift_down(&self, heap: &mut [Numbered]) {
+        let mut i = 0;
+        loop {
+            let mut last = i;
+            for child in [2 * i + 1, 2 * i + 2] {
+                if child < heap.len() && self.compare(&heap[child], &heap[last]) == Ordering::Greater {
+                    last = child;
+                }
+            }
+            if last == i {
+                return;
+            }
+            heap.swap(i, last);
+            i = last;
+        }
+    }
+}
+
+/// a row kept by `TopN`, numbered in the order the rows came in
+struct Numbered {
+    number: u64,
+    keyed: Keyed,
+}
+
+impl Operator for TopN {
+    fn next(&mut self) -> Result<Option<Vec<Value>>, ExecutionError> {
+        if let Some(input) = self.input.take() {
+            let mut rows = self.select(input)?;
+            rows.sort_by(|left, right| self.compare(left, right));
+            self.output = rows.into_iter();
+        }
+        Ok(self.output.next().map(|numbered| numbered.keyed.row))
+    }
+}
+
+/// Rows of several sorted runs, merged into one order.
+struct Merge {
+    runs: Vec<SpillFile>,
+    /// the next row of each run, `None` once it's been read to the end
+    heads: Vec<Option<Keyed>>,
+    /// the order of each key, and so the number of key values at the start of each row written
+    orders: Vec<KeyOrder>,
+}
+
+impl Merge {
+    fn new(mut runs: Vec<SpillFile>, orders: Vec<KeyOrder>) -> Result<Self, ExecutionError> {
+        let heads = runs.iter_mut().map(|run| read_keyed(run, orders.len())).collect::<Result<_, _>>()?;
+        Ok(Self { runs, heads, orders })
+    }
+
+    /// the smallest row left in any run, from the earliest run among equal ones
+    fn next(&mut self) -> Result<Option<Keyed>, ExecutionError> {
+        let mut smallest: Option<usize> = None;
+        for (i, head) in self.heads.iter().enumerate() {
+            let Some(head) = head else {
+                continue;
+            };
+            if smallest.is_none_or(|s| compare_keys(&head.key, &self.heads[s].as_ref().unwrap().key, &self.orders) == Ordering::Less) {
+                smallest = Some(i);
+            }
+        }
+        let Some(i) = smallest else {
+            return Ok(None);
+        };
+        let next = read_keyed(&mut self.runs[i], self.orders.len())?;
+        Ok(std::mem::replace(&mut self.heads[i], next))
+    }
+}
+
+/// writes `keyed` to `run` as one row, its key values first
+fn write_keyed(run: &mut SpillFile, keyed: Keyed) -> Result<(), ExecutionError> {
+    let mut values = keyed.key;
+    values.extend(keyed.row);
+    run.write(&values)
+}
+
+fn read_keyed(run: &mut SpillFile, key_count: usize) -> Result<Option<Keyed>, ExecutionError> {
+    Ok(run.read()?.map(|mut key| {
+        let row = key.split_off(key_count);
+        Keyed { key, row }
+    }))
+}
+
+/// Order of two rows by the values of their sort keys, each going the way its
+/// `KeyOrder` says. Values that can't be compared are taken to be equal.
+pub(super) fn compare_keys(left: &[Value], right: &[Value], orders: &[KeyOrder]) -> Ordering {
+    for ((left, right), order) in left.iter().zip(right).zip(orders) {
+        let ordering = match (left.is_null(), right.is_null()) {
+            (true, true) => Ordering::Equal,
+            (true, false) if order.nulls_first => Ordering::Less,
+            (true, false) => Ordering::Greater,
+            (false, true) if order.nulls_first => Ordering::Greater,
+            (false, true) => Ordering::Less,
+            (false, false) => {
+                let ordering = compare(left, right).unwrap_or(Ordering::Equal);
+                if order.descending { ordering.reverse() } else { ordering }
+            }
+        };
+        if ordering != Ordering::Equal {
+            return ordering;
+        }
+    }
+    Ordering::Equal
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::execution::operators::tests::Rows;
+    use crate::sql::Expr;
+
+    /// sorts rows of (`n`, `label`) by `keys` with `work_memory` bytes, and the number of runs it wrote
+    fn sort(rows: Vec<Vec<Value>>, keys: Vec<SortKey>, work_memory: usize) -> (Vec<Vec<Value>>, usize) {
+        let scope = Scope::named(["n".to_string(), "label".to_string()]);
+        let mut sort = Sort::new(Box::new(Rows::new(rows)), keys, scope, work_memory);
+        let mut sorted = Vec::new();
+        while let Some(row) = sort.next().unwrap() {
+            sorted.push(row);
+        }
+        (sorted, sort.runs)
+    }
+
+    fn key(name: &str, order: KeyOrder) -> SortKey {
+        SortKey { expr: Expr::column(name), order }
+    }
+
+    #[test]
+    fn test_spilled_runs_merge_in_order() {
+        // n repeats, and the labels say which row each was
+        let mut state: u32 = 11;
+        let rows: Vec<Vec<Value>> = (0..20_000)
+            .map(|i| {
+                state = state.wrapping_mul(1103515245).wrapping_add(12345);
+                let n = if state.is_multiple_of(50) { Value::Null } else { Value::Int((state >> 8) as i32 % 1000) };
+                vec![n, Value::Varchar(format!("row {i:05}"))]
+            })
+            .collect();
+        let keys = vec![key("n", KeyOrder::DESC)];
+
+        // NULLs first, then largest first, and rows with the same n in the order they came
+        let mut expected = rows.clone();
+        expected.sort_by(|left, right| compare_keys(&left[..1], &right[..1], &[KeyOrder::DESC]));
+        let (in_memory, runs) = sort(rows.clone(), keys.clone(), usize::MAX);
+        assert_eq!(runs, 0);
+        assert_eq!(in_memory, expected);
+        assert!(in_memory[0][0].is_null() && in_memory.last().unwrap()[0] == Value::Int(0));
+
+        // 8KB holds about 60 rows, so hundreds of runs that take more than one pass to merge
+        let (spilled, runs) = sort(rows, keys, 8 * 1024);
+        assert!(runs > 300 + 300 / MERGE_FAN_IN, "{runs}");
+        assert_eq!(spilled, expected);
+    }
+
+    #[test]
+    fn test_top_n_matches_sort() {
+        let rows: Vec<Vec<Value>> = (0..5000).map(|i| vec![if i % 97 == 0 { Value::Null } else { Value::Int(i * 7919 % 300) }, Value::Int(i)]).collect();
+        let keys = vec![key("n", KeyOrder::ASC)];
+        let (sorted, _) = sort(rows.clone(), keys.clone(), usize::MAX);
+        for count in [0, 1, 17, 300, 5000, 6000] {
+            let mut top = TopN::new(Box::new(Rows::new(rows.clone())), keys.clone(), Scope::named(["n".to_string(), "label".to_string()]), count);
+            let mut first = Vec::new();
+            while let Some(row) = top.next().unwrap() {
+                first.push(row);
+            }
+            // the same rows in the same order, ties included
+            assert_eq!(first, sorted[..sorted.len().min(count as usize)], "{count}");
+        }
+    }
+
+    #[test]
+    fn test_several_keys() {
+        let rows = vec![
+            vec![Value::Int(2), Value::Varchar("b".into())],
+            vec![Value::Null, Value::Varchar("a".into())],
+            vec![Value::Int(1), Value::Null],
+            vec![Value::BigInt(2), Value::Varchar("a".into())],
+            vec![Value::Int(1), Value::Varchar("c".into())],
+        ];
+        let nulls_first = KeyOrder { descending: false, nulls_first: true };
+        let (sorted, _) = sort(rows, vec![key("n", KeyOrder::ASC), key("label", nulls_first)], 64);
+        let labels: Vec<String> = sorted.iter().map(|row| format!("{} {}", row[0], row[1])).collect();
+        assert_eq!(labels, ["1 NULL", "1 c", "2 a", "2 b", "NULL a"]);
+    }
+}
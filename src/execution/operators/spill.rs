@@ -0,0 +1,206 @@
+use crate::execution::ExecutionError;
+use crate::types::{Decimal, Interval, Value};
+use std::fs::{File, OpenOptions};
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::io::{BufReader, BufWriter, ErrorKind, Read, Seek, SeekFrom, Write};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// numbers the spill files of every operator in the process, so they never collide
+static NEXT_FILE: AtomicU64 = AtomicU64::new(0);
+
+/// bytes charged for a row held in memory on top of its values
+const ROW_OVERHEAD: usize = 24;
+/// bytes charged for each value, besides the text of a VARCHAR
+const VALUE_SIZE: usize = 24;
+
+/// roughly how much memory `row` takes up
+pub(super) fn row_size(row: &[Value]) -> usize {
+    let text: usize = row.iter().map(|value| if let Value::Varchar(text) = value { text.len() } else { 0 }).sum();
+    ROW_OVERHEAD + row.len() * VALUE_SIZE + text
+}
+
+/// partitions rows are split into each time they don't fit in memory
+pub(super) const PARTITIONS: usize = 16;
+/// most times rows are split into partitions; a partition that still doesn't fit after that
+/// likely has a single key, and is dealt with in memory anyway
+pub(super) const MAX_SPLITS: usize = 3;
+
+/// the partition `key` goes to in round `splits` of splitting, differently in every round
+pub(super) fn partition_of(key: &[Value], splits: usize) -> usize {
+    let mut hasher = DefaultHasher::new();
+    splits.hash(&mut hasher);
+    key.hash(&mut hasher);
+    (hasher.finish() % PARTITIONS as u64) as usize
+}
+
+/// A temporary file that rows are written out to when an operator runs out of
+/// memory, and read back from in the same order later. Removed when dropped.
+///
+/// Rows are written back to back: a u16 value count, then each value as a tag
+/// byte followed by its little-endian bytes, or a u32 length and UTF-8 for a
+/// VARCHAR.
+pub(super) struct SpillFile {
+    path: PathBuf,
+    writer: Option<BufWriter<File>>,
+    reader: Option<BufReader<File>>,
+    rows: usize,
+}
+
+const TAG_NULL: u8 = 0;
+const TAG_INT: u8 = 1;
+const TAG_BIGINT: u8 = 2;
+const TAG_VARCHAR: u8 = 3;
+const TAG_BOOL: u8 = 4;
+const TAG_NUMERIC: u8 = 5;
+const TAG_DATE: u8 = 6;
+const TAG_TIME: u8 = 7;
+const TAG_TIMESTAMP: u8 = 8;
+const TAG_INTERVAL: u8 = 9;
+
+impl SpillFile {
+    pub(super) fn create() -> Result<Self, ExecutionError> {
+        let number = NEXT_FILE.fetch_add(1, Ordering::Relaxed);
+        let path = std::env::temp_dir().join(format!("gondor-spill-{}-{number}.tmp", std::process::id()));
+        let file = OpenOptions::new().read(true).write(true).create_new(true).open(&path)?;
+        Ok(Self { path, writer: Some(BufWriter::new(file)), reader: None, rows: 0 })
+    }
+
+    /// number of rows written
+    pub(super) fn len(&self) -> usize {
+        self.rows
+    }
+
+    pub(super) fn write(&mut self, row: &[Value]) -> Result<(), ExecutionError> {
+        let writer = self.writer.as_mut().expect("rows are written before the file is read");
+        let mut bytes = Vec::with_capacity(row_size(row));
+        bytes.extend_from_slice(&(row.len() as u16).to_le_bytes());
+        for value in row {
+            match value {
+                Value::Null => bytes.push(TAG_NULL),
+                Value::Int(value) => {
+                    bytes.push(TAG_INT);
+                    bytes.extend_from_slice(&value.to_le_bytes());
+                }
+                Value::BigInt(value) => {
+                    bytes.push(TAG_BIGINT);
+                    bytes.extend_from_slice(&value.to_le_bytes());
+                }
+                Value::Varchar(text) => {
+                    bytes.push(TAG_VARCHAR);
+                    bytes.extend_from_slice(&(text.len() as u32).to_le_bytes());
+                    bytes.extend_from_slice(text.as_bytes());
+                }
+                Value::Bool(value) => bytes.extend_from_slice(&[TAG_BOOL, *value as u8]),
+                Value::Numeric(value) => {
+                    bytes.push(TAG_NUMERIC);
+                    bytes.extend_from_slice(&value.to_bytes());
+                }
+                Value::Date(days) => {
+                    bytes.push(TAG_DATE);
+                    bytes.extend_from_slice(&days.to_le_bytes());
+                }
+                Value::Time(micros) => {
+                    bytes.push(TAG_TIME);
+                    bytes.extend_from_slice(&micros.to_le_bytes());
+                }
+                Value::Timestamp(micros) => {
+                    bytes.push(TAG_TIMESTAMP);
+                    bytes.extend_from_slice(&micros.to_le_bytes());
+                }
+                Value::Interval(interval) => {
+                    bytes.push(TAG_INTERVAL);
+                    bytes.extend_from_slice(&interval.to_bytes());
+                }
+            }
+        }
+        writer.write_all(&bytes)?;
+        self.rows += 1;
+        Ok(())
+    }
+
+    /// the next row written, once all of them have been; `None` after the last one
+    pub(super) fn read(&mut self) -> Result<Option<Vec<Value>>, ExecutionError> {
+        if let Some(writer) = self.writer.take() {
+            let mut file = writer.into_inner().map_err(|error| error.into_error())?;
+            file.seek(SeekFrom::Start(0))?;
+            self.reader = Some(BufReader::new(file));
+        }
+        let reader = self.reader.as_mut().unwrap();
+        let mut count = [0u8; 2];
+        match reader.read_exact(&mut count) {
+            Ok(()) => {}
+            Err(error) if error.kind() == ErrorKind::UnexpectedEof => return Ok(None),
+            Err(error) => return Err(error.into()),
+        }
+        let mut row = Vec::with_capacity(u16::from_le_bytes(count) as usize);
+        for _ in 0..u16::from_le_bytes(count) {
+            let mut tag = [0u8; 1];
+            reader.read_exact(&mut tag)?;
+            row.push(match tag[0] {
+                TAG_NULL => Value::Null,
+                TAG_INT => Value::Int(i32::from_le_bytes(read_array(reader)?)),
+                TAG_BIGINT => Value::BigInt(i64::from_le_bytes(read_array(reader)?)),
+                TAG_VARCHAR => {
+                    let mut text = vec![0u8; u32::from_le_bytes(read_array(reader)?) as usize];
+                    reader.read_exact(&mut text)?;
+                    Value::Varchar(String::from_utf8(text).map_err(|error| std::io::Error::new(ErrorKind::InvalidData, error))?)
+                }
+                TAG_BOOL => Value::Bool(read_array::<1>(reader)?[0] != 0),
+                TAG_NUMERIC => Value::Numeric(
+                    Decimal::from_bytes(read_array(reader)?).ok_or_else(|| std::io::Error::new(ErrorKind::InvalidData, "invalid decimal"))?,
+                ),
+                TAG_DATE => Value::Date(i32::from_le_bytes(read_array(reader)?)),
+                TAG_TIME => Value::Time(i64::from_le_bytes(read_array(reader)?)),
+                TAG_TIMESTAMP => Value::Timestamp(i64::from_le_bytes(read_array(reader)?)),
+                TAG_INTERVAL => Value::Interval(Interval::from_bytes(read_array(reader)?)),
+                tag => return Err(std::io::Error::new(ErrorKind::InvalidData, format!("unknown value tag {tag}")).into()),
+            });
+        }
+        Ok(Some(row))
+    }
+}
+
+fn read_array<const N: usize>(reader: &mut impl Read) -> std::io::Result<[u8; N]> {
+    let mut bytes = [0u8; N];
+    reader.read_exact(&mut bytes)?;
+    Ok(bytes)
+}
+
+impl Drop for SpillFile {
+    fn drop(&mut self) {
+        // nothing to be done if it fails, the file is only left behind in the temp directory
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rows_round_trip() {
+        let rows = vec![
+            vec![Value::Int(-3), Value::Null, Value::Varchar("Éowyn".into())],
+            Vec::new(),
+            vec![Value::BigInt(i64::MIN), Value::Bool(true), Value::Bool(false), Value::Varchar(String::new())],
+            vec![Value::Date(-1), Value::Time(1), Value::Timestamp(i64::MAX), Value::Interval(Interval { months: 1, days: -2, micros: 3 })],
+        ];
+        let mut file = SpillFile::create().unwrap();
+        let path = file.path.clone();
+        for row in &rows {
+            file.write(row).unwrap();
+        }
+        assert_eq!(file.len(), 4);
+
+        let mut read = Vec::new();
+        while let Some(row) = file.read().unwrap() {
+            read.push(row);
+        }
+        assert_eq!(read, rows);
+        assert!(file.read().unwrap().is_none());
+
+        drop(file);
+        assert!(!path.exists());
+    }
+}
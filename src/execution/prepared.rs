@@ -0,0 +1,377 @@
+use super::expression::value_literal;
+use super::{ExecutionError, QUERY_PLAN_COLUMN, Scope, coerce};
+use crate::catalog::{Catalog, TableInfo};
+use crate::planner::{PhysicalPlan, PlanNode};
+use crate::sql::{BinaryOp, Expr, Function, Select, SelectItem, Statement, UnaryOp};
+use crate::types::{DataType, Value};
+use std::ops::Bound;
+
+/// A statement parsed, and planned if it's a query, once to be run any number
+/// of times with different values for its parameters. Made by
+/// `Executor::prepare` and run by `Executor::execute_prepared`.
+///
+/// The type of each parameter is worked out from where it's used: that of
+/// the column it's compared with, assigned to or inserted into, or `BOOLEAN`
+/// next to `AND`, `OR` or `NOT`. One used nowhere like that takes a value of
+/// any type.
+#[derive(Debug, Clone)]
+pub struct PreparedStatement {
+    pub(super) statement: Statement,
+    pub(super) parameter_types: Vec<Option<DataType>>,
+    pub(super) plan: Option<PhysicalPlan>,
+}
+
+impl PreparedStatement {
+    pub fn statement(&self) -> &Statement {
+        &self.statement
+    }
+
+    /// The plan of a `SELECT`, with the parameters still in it.
+    pub fn plan(&self) -> Option<&PhysicalPlan> {
+        self.plan.as_ref()
+    }
+
+    /// Names of the columns of the rows the statement returns, none unless
+    /// it's a `SELECT` or `EXPLAIN`.
+    pub fn column_names(&self) -> Vec<String> {
+        match (&self.plan, &self.statement) {
+            (Some(plan), _) => plan.column_names(),
+            (None, Statement::Explain(_)) => vec![QUERY_PLAN_COLUMN.to_string()],
+            (None, _) => Vec::new(),
+        }
+    }
+
+    /// Types of the columns of the rows the statement returns, `None` for one
+    /// whose values can be of any type.
+    pub fn column_types(&self) -> Vec<Option<DataType>> {
+        match (&self.plan, &self.statement) {
+            (Some(plan), _) => plan.column_types(),
+            (None, Statement::Explain(_)) => vec![Some(DataType::Varchar(None))],
+            (None, _) => Vec::new(),
+        }
+    }
+
+    /// The type of each parameter, `$1` first, `None` for one that takes any value.
+    pub fn parameter_types(&self) -> &[Option<DataType>] {
+        &self.parameter_types
+    }
+
+    /// `parameters` as values of the types of the parameters they're for. An
+    /// integer is converted to the other integer type if it fits in it.
+    pub(super) fn bind(&self, parameters: &[Value]) -> Result<Vec<Value>, ExecutionError> {
+        if parameters.len() != self.parameter_types.len() {
+            return Err(ExecutionError::ParameterCountMismatch { expected: self.parameter_types.len(), found: parameters.len() });
+        }
+        let mut values = Vec::with_capacity(parameters.len());
+        for (i, (value, data_type)) in parameters.iter().zip(&self.parameter_types).enumerate() {
+            let value = match data_type {
+                Some(data_type) => coerce(value.clone(), *data_type)?,
+                None => value.clone(),
+            };
+            if let (Some(data_type), Some(found)) = (data_type, value.data_type())
+                && !value.fits(*data_type)
+            {
+                return Err(ExecutionError::TypeError(format!("parameter ${} is of type {data_type}, not {found}", i + 1)));
+            }
+            values.push(value);
+        }
+        Ok(values)
+    }
+}
+
+/// the type of each parameter of `statement`, up to the highest numbered one
+pub(super) fn parameter_types(catalog: &Catalog, statement: &Statement) -> Result<Vec<Option<DataType>>, ExecutionError> {
+    let mut types = Vec::new();
+    match statement {
+        Statement::Select(select) => select_types(catalog, select, &mut types)?,
+        Statement::Explain(explain) => select_types(catalog, &explain.query, &mut types)?,
+        Statement::Insert(insert) => {
+            let table = table(catalog, &insert.table)?;
+            let scope = Scope::table(&table);
+            let columns = Columns::of(&[(&table, &table.name)]);
+            let targets: Vec<Option<usize>> = if insert.columns.is_empty() {
+                (0..table.columns.len()).map(Some).collect()
+            } else {
+                insert.columns.iter().map(|name| scope.resolve(None, name).ok()).collect()
+            };
+            for row in &insert.rows {
+                for (expr, target) in row.iter().zip(&targets) {
+                    if let (Expr::Parameter(number), Some(target)) = (expr, target) {
+                        deduce(&mut types, *number, table.columns[*target].data_type)?;
+                    }
+                    infer(expr, &columns, &mut types)?;
+                }
+            }
+        }
+        Statement::Update(update) => {
+            let table = table(catalog, &update.table)?;
+            let scope = Scope::table(&table);
+            let columns = Columns::of(&[(&table, &table.name)]);
+            for (name, expr) in &update.assignments {
+                if let (Expr::Parameter(number), Ok(target)) = (expr, scope.resolve(None, name)) {
+                    deduce(&mut types, *number, table.columns[target].data_type)?;
+                }
+                infer(expr, &columns, &mut types)?;
+            }
+            if let Some(expr) = &update.where_clause {
+                infer(expr, &columns, &mut types)?;
+            }
+        }
+        Statement::Delete(delete) => {
+            let table = table(catalog, &delete.table)?;
+            if let Some(expr) = &delete.where_clause {
+                infer(expr, &Columns::of(&[(&table, &table.name)]), &mut types)?;
+            }
+        }
+        Statement::CreateTable(_) | Statement::CreateIndex(_) | Statement::Analyze(_) | Statement::Vacuum(_) | Statement::Copy(_) | Statement::Transaction(_) => {}
+    }
+    Ok(types)
+}
+
+fn table(catalog: &Catalog, name: &str) -> Result<TableInfo, ExecutionError> {
+    catalog.table(name)?.ok_or_else(|| ExecutionError::TableNotFound(name.to_string()))
+}
+
+fn select_types(catalog: &Catalog, select: &Select, types: &mut Vec<Option<DataType>>) -> Result<(), ExecutionError> {
+    let tables = select.from.iter().map(|table_ref| table(catalog, &table_ref.name)).collect::<Result<Vec<_>, _>>()?;
+    let named: Vec<(&TableInfo, &str)> = tables.iter().zip(&select.from).map(|(table, table_ref)| (table, table_ref.reference())).collect();
+    let columns = Columns::of(&named);
+    let items = select.projection.iter().filter_map(|item| match item {
+        SelectItem::Expr { expr, .. } => Some(expr),
+        SelectItem::Wildcard => None,
+    });
+    let exprs = items
+        .chain(select.from.iter().filter_map(|table_ref| table_ref.on.as_ref()))
+        .chain(&select.where_clause)
+        .chain(&select.group_by)
+        .chain(&select.having)
+        .chain(select.order_by.iter().map(|item| &item.expr));
+    for expr in exprs {
+        infer(expr, &columns, types)?;
+    }
+    Ok(())
+}
+
+/// the columns the expressions of a statement can refer to, and their types
+struct Columns {
+    scope: Scope,
+    types: Vec<DataType>,
+}
+
+impl Columns {
+    /// every column of `tables`, each referred to by the name it's given
+    fn of(tables: &[(&TableInfo, &str)]) -> Self {
+        let mut columns = Self { scope: Scope::empty(), types: Vec::new() };
+        for (table, alias) in tables {
+            columns.scope = columns.scope.join(&Scope::aliased(table, alias));
+            columns.types.extend(table.columns.iter().map(|column| column.data_type));
+        }
+        columns
+    }
+
+    /// the type of the column `expr` refers to, if it's a reference to one
+    fn type_of(&self, expr: &Expr) -> Option<DataType> {
+        let Expr::Column { table, name } = expr else {
+            return None;
+        };
+        Some(self.types[self.scope.resolve(table.as_deref(), name).ok()?])
+    }
+}
+
+/// adds the types of the parameters in `expr` that can be told from what's next to them to `types`
+fn infer(expr: &Expr, columns: &Columns, types: &mut Vec<Option<DataType>>) -> Result<(), ExecutionError> {
+    match expr {
+        Expr::Literal(_) | Expr::Column { .. } => Ok(()),
+        Expr::Parameter(number) => {
+            if types.len() < *number {
+                types.resize(*number, None);
+            }
+            Ok(())
+        }
+        Expr::Unary { op, expr } => {
+            if let (UnaryOp::Not, Expr::Parameter(number)) = (op, &**expr) {
+                deduce(types, *number, DataType::Bool)?;
+            }
+            infer(expr, columns, types)
+        }
+        Expr::Binary { left, op, right } => {
+            for (side, other) in [(left, right), (right, left)] {
+                let expected = match op {
+                    BinaryOp::And | BinaryOp::Or => Some(DataType::Bool),
+                    _ => columns.type_of(other),
+                };
+                if let (Expr::Parameter(number), Some(expected)) = (&**side, expected) {
+                    deduce(types, *number, expected)?;
+                }
+            }
+            infer(left, columns, types)?;
+            infer(right, columns, types)
+        }
+        Expr::IsNull { expr, .. } => infer(expr, columns, types),
+        Expr::Aggregate { arg, .. } => arg.as_deref().map_or(Ok(()), |arg| infer(arg, columns, types)),
+        Expr::Function { function, args } => {
+            // the unit or field is a string, and what's truncated a timestamp
+            let expected: &[DataType] = match function {
+                Function::Now => &[],
+                Function::DateTrunc => &[DataType::Varchar(None), DataType::Timestamp],
+                Function::DatePart => &[DataType::Varchar(None)],
+            };
+            for (arg, expected) in args.iter().zip(expected) {
+                if let Expr::Parameter(number) = arg {
+                    deduce(types, *number, *expected)?;
+                }
+            }
+            args.iter().try_for_each(|arg| infer(arg, columns, types))
+        }
+    }
+}
+
+/// records that parameter `number` is of type `data_type`, which it has to be everywhere it's used
+fn deduce(types: &mut Vec<Option<DataType>>, number: usize, data_type: DataType) -> Result<(), ExecutionError> {
+    // a string of any length compares with a VARCHAR, and one too long to store fails when it's stored
+    let data_type = match data_type {
+        DataType::Varchar(_) => DataType::Varchar(None),
+        data_type => data_type,
+    };
+    if types.len() < number {
+        types.resize(number, None);
+    }
+    match types[number - 1] {
+        None => types[number - 1] = Some(data_type),
+        Some(deduced) if deduced == data_type => {}
+        Some(_) => return Err(ExecutionError::TypeError(format!("inconsistent types deduced for parameter ${number}"))),
+    }
+    Ok(())
+}
+
+/// `statement` with the values of its parameters, `$1` first, in place of them
+pub(super) fn bind_statement(statement: &Statement, values: &[Value]) -> Statement {
+    let mut statement = statement.clone();
+    let bind = |expr: &mut Expr| bind_expr(expr, values, &[]);
+    match &mut statement {
+        Statement::Select(select) => bind_select(select, values),
+        Statement::Explain(explain) => bind_select(&mut explain.query, values),
+        Statement::Insert(insert) => insert.rows.iter_mut().flatten().for_each(bind),
+        Statement::Update(update) => {
+            update.assignments.iter_mut().map(|(_, expr)| expr).for_each(bind);
+            update.where_clause.iter_mut().for_each(bind);
+        }
+        Statement::Delete(delete) => delete.where_clause.iter_mut().for_each(bind),
+        Statement::CreateTable(_) | Statement::CreateIndex(_) | Statement::Analyze(_) | Statement::Vacuum(_) | Statement::Copy(_) | Statement::Transaction(_) => {}
+    }
+    statement
+}
+
+fn bind_select(select: &mut Select, values: &[Value]) {
+    let bind = |expr: &mut Expr| bind_expr(expr, values, &[]);
+    for item in &mut select.projection {
+        if let SelectItem::Expr { expr, .. } = item {
+            bind(expr);
+        }
+    }
+    select.from.iter_mut().filter_map(|table_ref| table_ref.on.as_mut()).for_each(bind);
+    select.where_clause.iter_mut().for_each(bind);
+    select.group_by.iter_mut().for_each(bind);
+    select.having.iter_mut().for_each(bind);
+    select.order_by.iter_mut().map(|item| &mut item.expr).for_each(bind);
+}
+
+/// Replaces the parameters in `plan` with their values, `$1` first.
+///
+/// The columns of an aggregate's rows that aren't grouped columns are named
+/// after the expressions they're the values of, so when those had parameters
+/// the references to them above are renamed too. Returns the renamed columns
+/// of `plan`'s rows, as pairs of the old and new names.
+pub(super) fn bind_plan(plan: &mut PhysicalPlan, values: &[Value]) -> Vec<(String, String)> {
+    let renamed = match &mut plan.node {
+        PlanNode::Filter { input, .. } | PlanNode::Sort { input, .. } | PlanNode::TopN { input, .. } | PlanNode::Limit { input, .. } | PlanNode::Project { input, .. } => {
+            bind_plan(input, values)
+        }
+        PlanNode::NestedLoopJoin { outer, inner, .. } | PlanNode::HashJoin { outer, inner, .. } => {
+            let mut renamed = bind_plan(outer, values);
+            renamed.extend(bind_plan(inner, values));
+            renamed
+        }
+        PlanNode::IndexNestedLoopJoin { outer, .. } => bind_plan(outer, values),
+        PlanNode::HashAggregate { input, .. } => {
+            bind_plan(input, values);
+            Vec::new()
+        }
+        PlanNode::OneRow | PlanNode::SeqScan { .. } | PlanNode::IndexScan { .. } => Vec::new(),
+    };
+    let bind = |expr: &mut Expr| bind_expr(expr, values, &renamed);
+    match &mut plan.node {
+        PlanNode::OneRow => Vec::new(),
+        PlanNode::SeqScan { filter, .. } => {
+            filter.iter_mut().for_each(bind);
+            Vec::new()
+        }
+        PlanNode::IndexScan { prefix, lower, upper, filter, .. } => {
+            prefix.iter_mut().for_each(bind);
+            for bound in [lower, upper] {
+                if let Bound::Included(value) | Bound::Excluded(value) = bound {
+                    bind(value);
+                }
+            }
+            filter.iter_mut().for_each(bind);
+            Vec::new()
+        }
+        PlanNode::Filter { predicate, .. } => {
+            bind(predicate);
+            renamed
+        }
+        PlanNode::NestedLoopJoin { condition, .. } => {
+            condition.iter_mut().for_each(bind);
+            renamed
+        }
+        PlanNode::IndexNestedLoopJoin { outer_keys, filter, condition, .. } => {
+            outer_keys.iter_mut().chain(filter).chain(condition).for_each(bind);
+            renamed
+        }
+        PlanNode::HashJoin { outer_keys, inner_keys, condition, .. } => {
+            outer_keys.iter_mut().chain(inner_keys).chain(condition).for_each(bind);
+            renamed
+        }
+        PlanNode::HashAggregate { group_by, aggregates, .. } => {
+            let mut renamed = Vec::new();
+            for expr in group_by.iter_mut().chain(aggregates) {
+                let name = expr.to_string();
+                bind(expr);
+                if !matches!(expr, Expr::Column { .. }) && expr.to_string() != name {
+                    renamed.push((name, expr.to_string()));
+                }
+            }
+            renamed
+        }
+        PlanNode::Sort { keys, .. } | PlanNode::TopN { keys, .. } => {
+            keys.iter_mut().map(|key| &mut key.expr).for_each(bind);
+            renamed
+        }
+        PlanNode::Limit { .. } => renamed,
+        // the rows of a projection have columns named by the query instead
+        PlanNode::Project { exprs, .. } => {
+            exprs.iter_mut().for_each(bind);
+            Vec::new()
+        }
+    }
+}
+
+/// `expr` with the values of its parameters in place of them, and its
+/// references to the columns in `renamed` by their new names
+fn bind_expr(expr: &mut Expr, values: &[Value], renamed: &[(String, String)]) {
+    match expr {
+        Expr::Parameter(number) => *expr = Expr::Literal(value_literal(&values[*number - 1])),
+        Expr::Column { table: None, name } => {
+            if let Some((_, new_name)) = renamed.iter().find(|(old_name, _)| old_name == name) {
+                *name = new_name.clone();
+            }
+        }
+        Expr::Literal(_) | Expr::Column { .. } | Expr::Aggregate { arg: None, .. } => {}
+        Expr::Unary { expr, .. } | Expr::IsNull { expr, .. } | Expr::Aggregate { arg: Some(expr), .. } => bind_expr(expr, values, renamed),
+        Expr::Binary { left, right, .. } => {
+            bind_expr(left, values, renamed);
+            bind_expr(right, values, renamed);
+        }
+        Expr::Function { args, .. } => args.iter_mut().for_each(|arg| bind_expr(arg, values, renamed)),
+    }
+}
@@ -0,0 +1,316 @@
+use super::{AccessLog, ExecutionError};
+use super::expression::{Scope, evaluate};
+use crate::catalog::{IndexInfo, IndexKind, TableInfo};
+use crate::index::{BPlusTree, EntrySorter, IndexError};
+use crate::storage::{BufferPool, HeapFile, RecordId};
+use crate::transaction::Transaction;
+use crate::types::{Tuple, Value};
+use crate::wal::LogManager;
+use std::sync::{Arc, Mutex};
+
+/// A table opened for writing: its heap file and every index on it.
+///
+/// All of the executor's changes to rows go through here, so no index is ever
+/// left behind by an `INSERT`, `UPDATE` or `DELETE`. Each row change is made to
+/// the heap file first and then to each index, in the catalog's order; if an
+/// index rejects it the statement fails, and rolling it back undoes the heap
+/// change along with whatever indexes were already changed. Before that the
+/// row is checked against the table's `NOT NULL` and `CHECK` constraints.
+pub(super) struct TableWriter {
+    pub(super) info: TableInfo,
+    pub(super) heap: HeapFile,
+    /// trees of `info.indexes`, in the same order
+    indexes: Vec<BPlusTree>,
+    /// where the rows written are noted, if set
+    pub(super) access_log: Option<Arc<Mutex<AccessLog>>>,
+}
+
+impl TableWriter {
+    /// Opens the heap file and indexes of `info`, logging their changes to `log_manager` if there is one.
+    pub(super) fn open(buffer_pool: &Arc<Mutex<BufferPool>>, log_manager: Option<&Arc<Mutex<LogManager>>>, info: TableInfo) -> Result<Self, ExecutionError> {
+        let mut heap = HeapFile::open(buffer_pool.clone(), info.root_page_id)?;
+        let mut indexes: Vec<BPlusTree> = info.indexes.iter().map(|index| index.open(buffer_pool.clone())).collect();
+        if let Some(log_manager) = log_manager {
+            heap.set_log_manager(log_manager.clone());
+            for tree in &mut indexes {
+                tree.set_log_manager(log_manager.clone());
+            }
+        }
+        Ok(Self { info, heap, indexes, access_log: None })
+    }
+
+    /// Adds a row and its index entries, returning where the row went.
+    pub(super) fn insert(&mut self, txn: &mut Transaction, row: Vec<Value>) -> Result<RecordId, ExecutionError> {
+        self.check(&row)?;
+        let tuple = Tuple::new(row);
+        let rid = self.heap.insert(txn, &tuple.encode(&self.info.columns)?)?;
+        for (index, tree) in self.info.indexes.iter().zip(&mut self.indexes) {
+            insert_entry(txn, &self.info, index, tree, tuple.values(), rid)?;
+        }
+        self.wrote(tuple.values(), rid)?;
+        Ok(rid)
+    }
+
+    /// Adds every row of `rows` and their index entries, returning how many there were.
+    ///
+    /// The rows go into the heap file one after another as with `insert`, but
+    /// the entries for an index that has none yet are sorted and bulk loaded
+    /// into it once all the rows are in, rather than inserted one at a time.
+    /// A duplicate key among them is still a constraint violation.
+    pub(super) fn load(&mut self, txn: &mut Transaction, rows: impl IntoIterator<Item = Result<Vec<Value>, ExecutionError>>) -> Result<usize, ExecutionError> {
+        let mut sorters = Vec::new();
+        for tree in &self.indexes {
+            sorters.push(if tree.is_empty()? { Some(EntrySorter::new()) } else { None });
+        }
+
+        let mut count = 0;
+        for row in rows {
+            let row = row?;
+            self.check(&row)?;
+            let tuple = Tuple::new(row);
+            let rid = self.heap.insert(txn, &tuple.encode(&self.info.columns)?)?;
+            for ((index, tree), sorter) in self.info.indexes.iter().zip(&mut self.indexes).zip(&mut sorters) {
+                match sorter {
+                    Some(sorter) => sorter.push(index_key(&self.info, index, tuple.values(), rid)?, rid)?,
+                    None => insert_entry(txn, &self.info, index, tree, tuple.values(), rid)?,
+                }
+            }
+            self.wrote(tuple.values(), rid)?;
+            count += 1;
+        }
+
+        for ((index, tree), sorter) in self.info.indexes.iter().zip(&mut self.indexes).zip(sorters) {
+            let Some(sorter) = sorter else { continue };
+            match tree.bulk_load(txn, sorter) {
+                Err(IndexError::DuplicateKey) => return Err(ExecutionError::UniqueViolation { constraint: index.name.clone() }),
+                result => result?,
+            }
+        }
+        Ok(count)
+    }
+
+    /// Replaces the row `old` at `rid` with `new`, returning where it is now.
+    ///
+    /// An index entry is only replaced (deleted and inserted again) when the
+    /// row's key in that index changed, or the row had to move to another page.
+    pub(super) fn update(&mut self, txn: &mut Transaction, rid: RecordId, old: &[Value], new: Vec<Value>) -> Result<RecordId, ExecutionError> {
+        self.check(&new)?;
+        let tuple = Tuple::new(new);
+        let new_rid = self.heap.update(txn, rid, &tuple.encode(&self.info.columns)?)?;
+        for (index, tree) in self.info.indexes.iter().zip(&mut self.indexes) {
+            let old_key = index_key(&self.info, index, old, rid)?;
+            let new_key = index_key(&self.info, index, tuple.values(), new_rid)?;
+            if old_key != new_key || new_rid != rid {
+                tree.delete(txn, &old_key, rid)?;
+                insert_entry(txn, &self.info, index, tree, tuple.values(), new_rid)?;
+            }
+        }
+        self.wrote(old, rid)?;
+        self.wrote(tuple.values(), new_rid)?;
+        Ok(new_rid)
+    }
+
+    /// checks `row` against the table's `NOT NULL` and then its `CHECK`
+    /// constraints, which like in PostgreSQL only a false result violates
+    fn check(&self, row: &[Value]) -> Result<(), ExecutionError> {
+        let constraints = &self.info.constraints;
+        if let Some(&position) = constraints.not_null.iter().find(|&&position| row[position].is_null()) {
+            return Err(ExecutionError::NullViolation { column: self.info.columns[position].name.clone() });
+        }
+        if constraints.checks.is_empty() {
+            return Ok(());
+        }
+        let scope = Scope::table(&self.info);
+        for check in &constraints.checks {
+            match evaluate(&check.expr, &scope, row)? {
+                Value::Bool(true) | Value::Null => {}
+                Value::Bool(false) => return Err(ExecutionError::CheckViolation { constraint: check.name.clone() }),
+                value => return Err(ExecutionError::TypeError(format!("check constraint {} is {value}, not a boolean", check.name))),
+            }
+        }
+        Ok(())
+    }
+
+    /// Removes the row `row` at `rid` and its index entries.
+    pub(super) fn delete(&mut self, txn: &mut Transaction, rid: RecordId, row: &[Value]) -> Result<(), ExecutionError> {
+        self.heap.delete(txn, rid)?;
+        for (index, tree) in self.info.indexes.iter().zip(&mut self.indexes) {
+            tree.delete(txn, &index_key(&self.info, index, row, rid)?, rid)?;
+        }
+        self.wrote(row, rid)
+    }
+
+    /// notes the row `row` at `rid` as written in the access log, if there is one
+    fn wrote(&self, row: &[Value], rid: RecordId) -> Result<(), ExecutionError> {
+        match &self.access_log {
+            Some(access_log) => access_log.lock().unwrap().wrote(&self.info, row, rid),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Key of `row` (stored at `rid`) in `index`.
+///
+/// NULL is never equal to anything, so rows with a NULL in the key can't clash
+/// in a unique index. Their keys get the record id on the end to keep them apart
+/// in the tree.
+pub(super) fn index_key(table: &TableInfo, index: &IndexInfo, row: &[Value], rid: RecordId) -> Result<Vec<u8>, ExecutionError> {
+    let mut key = index.key(row);
+    if let Some(column) = index.columns.iter().find(|column| row[column.position].is_null()) {
+        match index.kind {
+            IndexKind::PrimaryKey => return Err(ExecutionError::NullViolation { column: table.columns[column.position].name.clone() }),
+            IndexKind::Unique => key.extend_from_slice(&rid.to_bytes()),
+            IndexKind::NonUnique => {}
+        }
+    }
+    Ok(key)
+}
+
+/// adds the entry for `row` to `index`, turning a duplicate key into a constraint violation
+fn insert_entry(txn: &mut Transaction, table: &TableInfo, index: &IndexInfo, tree: &mut BPlusTree, row: &[Value], rid: RecordId) -> Result<(), ExecutionError> {
+    let key = index_key(table, index, row, rid)?;
+    match tree.insert(txn, &key, rid) {
+        Err(IndexError::DuplicateKey) => Err(ExecutionError::UniqueViolation { constraint: index.name.clone() }),
+        result => Ok(result?),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::catalog::{Catalog, IndexColumn};
+    use crate::index::KeyOrder;
+    use crate::storage::DiskManager;
+    use crate::types::{Column, DataType};
+    use std::collections::BTreeMap;
+    use tempfile::NamedTempFile;
+
+    /// every index holds exactly the entries of the rows in `expected`, and the heap exactly those rows
+    fn assert_consistent(writer: &TableWriter, buffer_pool: &Arc<Mutex<BufferPool>>, expected: &BTreeMap<RecordId, Vec<Value>>) {
+        let heap: BTreeMap<RecordId, Vec<Value>> = writer
+            .heap
+            .scan()
+            .map(|tuple| {
+                let (rid, bytes) = tuple.unwrap();
+                (rid, Tuple::decode(&bytes, &writer.info.columns).unwrap().into_values())
+            })
+            .collect();
+        assert_eq!(&heap, expected);
+
+        for index in &writer.info.indexes {
+            let mut entries: Vec<(Vec<u8>, RecordId)> = index.open(buffer_pool.clone()).scan().unwrap().map(Result::unwrap).collect();
+            let mut keys: Vec<(Vec<u8>, RecordId)> = expected.iter().map(|(&rid, row)| (index_key(&writer.info, index, row, rid).unwrap(), rid)).collect();
+            entries.sort();
+            keys.sort();
+            assert_eq!(entries, keys, "index {} is stale", index.name);
+        }
+    }
+
+    #[test]
+    fn test_indexes_follow_every_row_change() {
+        let file = NamedTempFile::new().unwrap();
+        let buffer_pool = Arc::new(Mutex::new(BufferPool::new(DiskManager::open(file.path()).unwrap())));
+        let mut catalog = Catalog::open(buffer_pool.clone()).unwrap();
+        let mut txn = Transaction::new(1);
+        let columns = vec![Column::new("id", DataType::Int), Column::new("name", DataType::Varchar(None)), Column::new("score", DataType::Int)];
+        catalog.create_table(&mut txn, "players", columns).unwrap();
+        catalog.create_index(&mut txn, "players", "players_pkey", vec![IndexColumn::new(0)], IndexKind::PrimaryKey).unwrap();
+        catalog.create_index(&mut txn, "players", "players_name_key", vec![IndexColumn::new(1)], IndexKind::Unique).unwrap();
+        let by_score = vec![IndexColumn { position: 2, order: KeyOrder::DESC }, IndexColumn::new(1)];
+        catalog.create_index(&mut txn, "players", "players_score_idx", by_score, IndexKind::NonUnique).unwrap();
+        let table = catalog.table("players").unwrap().unwrap();
+        let mut writer = TableWriter::open(&buffer_pool, None, table).unwrap();
+
+        let mut expected = BTreeMap::new();
+        let mut state: u32 = 17;
+        let mut random = move |bound: u32| {
+            state = state.wrapping_mul(1103515245).wrapping_add(12345);
+            (state >> 16) % bound
+        };
+        for step in 0..600 {
+            let rids: Vec<RecordId> = expected.keys().copied().collect();
+            match random(4) {
+                0 | 1 => {
+                    let row = vec![Value::Int(step), Value::Varchar(format!("player {step}")), Value::Int(random(10) as i32)];
+                    let rid = writer.insert(&mut txn, row.clone()).unwrap();
+                    expected.insert(rid, row);
+                }
+                2 if !rids.is_empty() => {
+                    // new scores, names that are NULL or long enough to move the row, a new id now and then
+                    let rid = rids[random(rids.len() as u32) as usize];
+                    let old = expected.remove(&rid).unwrap();
+                    let mut new = old.clone();
+                    new[2] = if random(5) == 0 { Value::Null } else { Value::Int(random(10) as i32) };
+                    new[1] = match random(3) {
+                        0 => Value::Null,
+                        1 => Value::Varchar(format!("player {step} {}", "x".repeat(random(300) as usize))),
+                        _ => old[1].clone(),
+                    };
+                    if random(4) == 0 {
+                        new[0] = Value::Int(step + 1000);
+                    }
+                    let new_rid = writer.update(&mut txn, rid, &old, new.clone()).unwrap();
+                    expected.insert(new_rid, new);
+                }
+                _ if !rids.is_empty() => {
+                    let rid = rids[random(rids.len() as u32) as usize];
+                    let row = expected.remove(&rid).unwrap();
+                    writer.delete(&mut txn, rid, &row).unwrap();
+                }
+                _ => {}
+            }
+            if step % 50 == 0 {
+                assert_consistent(&writer, &buffer_pool, &expected);
+            }
+        }
+        assert_consistent(&writer, &buffer_pool, &expected);
+
+        // a change an index rejects fails as a constraint violation
+        let (&rid, row) = expected.iter().next().unwrap();
+        let mut duplicate = row.clone();
+        duplicate[1] = Value::Varchar("someone else".into());
+        assert!(matches!(writer.insert(&mut txn, duplicate), Err(ExecutionError::UniqueViolation { constraint }) if constraint == "players_pkey"));
+        let mut nameless = row.clone();
+        nameless[0] = Value::Null;
+        assert!(matches!(writer.update(&mut txn, rid, &row.clone(), nameless), Err(ExecutionError::NullViolation { column }) if column == "id"));
+    }
+
+    #[test]
+    fn test_load() {
+        let file = NamedTempFile::new().unwrap();
+        let buffer_pool = Arc::new(Mutex::new(BufferPool::new(DiskManager::open(file.path()).unwrap())));
+        let mut catalog = Catalog::open(buffer_pool.clone()).unwrap();
+        let mut txn = Transaction::new(1);
+        let columns = vec![Column::new("id", DataType::Int), Column::new("name", DataType::Varchar(None))];
+        catalog.create_table(&mut txn, "players", columns).unwrap();
+        catalog.create_index(&mut txn, "players", "players_pkey", vec![IndexColumn::new(0)], IndexKind::PrimaryKey).unwrap();
+        catalog.create_index(&mut txn, "players", "players_name_idx", vec![IndexColumn::new(1)], IndexKind::NonUnique).unwrap();
+        let table = catalog.table("players").unwrap().unwrap();
+        let mut writer = TableWriter::open(&buffer_pool, None, table).unwrap();
+
+        // into empty indexes, which are bulk loaded
+        let rows: Vec<Vec<Value>> = (0..2000).rev().map(|id| vec![Value::Int(id), Value::Varchar(format!("player {}", id % 7))]).collect();
+        assert_eq!(writer.load(&mut txn, rows.iter().cloned().map(Ok)).unwrap(), 2000);
+        // and into ones that aren't empty anymore, which take the entries one at a time
+        let more: Vec<Vec<Value>> = (2000..2100).map(|id| vec![Value::Int(id), Value::Null]).collect();
+        assert_eq!(writer.load(&mut txn, more.iter().cloned().map(Ok)).unwrap(), 100);
+        let expected: BTreeMap<RecordId, Vec<Value>> = writer
+            .heap
+            .scan()
+            .map(|tuple| {
+                let (rid, bytes) = tuple.unwrap();
+                (rid, Tuple::decode(&bytes, &writer.info.columns).unwrap().into_values())
+            })
+            .collect();
+        assert_eq!(expected.len(), 2100);
+        assert_consistent(&writer, &buffer_pool, &expected);
+
+        // duplicates are caught whichever way the entries go in
+        assert!(matches!(writer.load(&mut txn, [Ok(vec![Value::Int(5), Value::Null])]), Err(ExecutionError::UniqueViolation { constraint }) if constraint == "players_pkey"));
+        let info = catalog.create_table(&mut txn, "others", vec![Column::new("id", DataType::Int)]).unwrap();
+        catalog.create_index(&mut txn, "others", "others_pkey", vec![IndexColumn::new(0)], IndexKind::PrimaryKey).unwrap();
+        let mut writer = TableWriter::open(&buffer_pool, None, catalog.table(&info.name).unwrap().unwrap()).unwrap();
+        let duplicates = [Ok(vec![Value::Int(1)]), Ok(vec![Value::Int(2)]), Ok(vec![Value::Int(1)])];
+        assert!(matches!(writer.load(&mut txn, duplicates), Err(ExecutionError::UniqueViolation { constraint }) if constraint == "others_pkey"));
+    }
+}
@@ -0,0 +1,1169 @@
+use super::sort::EntrySorter;
+use crate::storage::{BufferPool, BufferPoolError, Page, PageError, PageType, RecordId, Reservation, max_tuple_size};
+use crate::transaction::Transaction;
+use crate::wal::{LogManager, LogRecordBody};
+use std::cmp::Ordering;
+use std::ops::Bound;
+use std::sync::{Arc, Mutex};
+
+/// longest key the tree accepts, small enough that a split always leaves both halves within a page
+pub const MAX_KEY_SIZE: usize = 512;
+
+/// the single tuple on a node page holds the serialized node
+pub(super) const NODE_SLOT: u16 = 0;
+
+/// marks "no next leaf" in the serialized form
+pub(super) const NO_PAGE: u32 = u32::MAX;
+
+/// frames a split reserves: the new sibling, the parent it adds a key to and the new page of a root split
+const SPLIT_FRAMES: usize = 3;
+
+/// how full `bulk_load` packs nodes, in percent, leaving room for inserts before the first splits
+const BULK_LOAD_FILL: usize = 90;
+
+const LEAF_KIND: u8 = 0;
+const INTERNAL_KIND: u8 = 1;
+
+#[derive(Debug)]
+pub enum IndexError {
+    /// the key is longer than `MAX_KEY_SIZE`
+    KeyTooLarge,
+    /// the exact key and record id pair is already in the index
+    DuplicateEntry,
+    /// the key is already in an index that only allows one entry per key
+    DuplicateKey,
+    /// the key and record id pair to delete isn't in the index
+    EntryNotFound,
+    /// a node page doesn't hold a valid node
+    CorruptNode(u32),
+    /// only an empty tree can be bulk loaded
+    TreeNotEmpty,
+    PageError(PageError),
+    BufferPoolError(BufferPoolError),
+    /// reading or writing a sort run failed
+    IoError(std::io::Error),
+}
+
+impl std::fmt::Display for IndexError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            IndexError::KeyTooLarge => write!(f, "Key is larger than {MAX_KEY_SIZE} bytes"),
+            IndexError::DuplicateEntry => write!(f, "Entry is already in the index"),
+            IndexError::DuplicateKey => write!(f, "Key is already in the unique index"),
+            IndexError::EntryNotFound => write!(f, "Entry not found in the index"),
+            IndexError::CorruptNode(page_id) => write!(f, "Page {page_id} does not hold a valid index node"),
+            IndexError::TreeNotEmpty => write!(f, "Only an empty tree can be bulk loaded"),
+            IndexError::PageError(error) => write!(f, "Page error: {error}"),
+            IndexError::BufferPoolError(error) => write!(f, "Buffer pool error: {error}"),
+            IndexError::IoError(error) => write!(f, "I/O error: {error}"),
+        }
+    }
+}
+
+impl std::error::Error for IndexError {}
+
+impl From<PageError> for IndexError {
+    fn from(error: PageError) -> Self {
+        IndexError::PageError(error)
+    }
+}
+
+impl From<BufferPoolError> for IndexError {
+    fn from(error: BufferPoolError) -> Self {
+        IndexError::BufferPoolError(error)
+    }
+}
+
+impl From<std::io::Error> for IndexError {
+    fn from(error: std::io::Error) -> Self {
+        IndexError::IoError(error)
+    }
+}
+
+/// a key together with the record it points at; entries are unique and ordered by key, then record id
+pub(super) type Entry = (Vec<u8>, RecordId);
+
+fn compare(entry: &Entry, key: &[u8], rid: RecordId) -> Ordering {
+    entry.0.as_slice().cmp(key).then(entry.1.cmp(&rid))
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Node {
+    Leaf {
+        entries: Vec<Entry>,
+        /// right sibling, for range scans
+        next: Option<u32>,
+    },
+    Internal {
+        /// `children[i]` holds entries below `keys[i]`, `children[i + 1]` those at or above it
+        keys: Vec<Entry>,
+        children: Vec<u32>,
+    },
+}
+
+impl Node {
+    fn empty_leaf() -> Self {
+        Node::Leaf { entries: Vec::new(), next: None }
+    }
+
+    /// size of the serialized node in bytes
+    fn size(&self) -> usize {
+        match self {
+            Node::Leaf { entries, .. } => 1 + 4 + 2 + entries.iter().map(|(key, _)| 2 + key.len() + RecordId::SIZE).sum::<usize>(),
+            Node::Internal { keys, .. } => 1 + 2 + 4 + keys.iter().map(|(key, _)| 2 + key.len() + RecordId::SIZE + 4).sum::<usize>(),
+        }
+    }
+
+    fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(self.size());
+        match self {
+            Node::Leaf { entries, next } => {
+                bytes.push(LEAF_KIND);
+                bytes.extend_from_slice(&next.unwrap_or(NO_PAGE).to_le_bytes());
+                bytes.extend_from_slice(&(entries.len() as u16).to_le_bytes());
+                for entry in entries {
+                    write_entry(&mut bytes, entry);
+                }
+            }
+            Node::Internal { keys, children } => {
+                bytes.push(INTERNAL_KIND);
+                bytes.extend_from_slice(&(keys.len() as u16).to_le_bytes());
+                bytes.extend_from_slice(&children[0].to_le_bytes());
+                for (key, child) in keys.iter().zip(&children[1..]) {
+                    write_entry(&mut bytes, key);
+                    bytes.extend_from_slice(&child.to_le_bytes());
+                }
+            }
+        }
+        bytes
+    }
+
+    fn from_bytes(bytes: &[u8]) -> Option<Self> {
+        let mut position = 0;
+        let kind = *bytes.first()?;
+        position += 1;
+
+        let node = match kind {
+            LEAF_KIND => {
+                let next = read_u32(bytes, &mut position)?;
+                let count = read_u16(bytes, &mut position)?;
+                let entries = (0..count).map(|_| read_entry(bytes, &mut position)).collect::<Option<Vec<_>>>()?;
+                Node::Leaf { entries, next: (next != NO_PAGE).then_some(next) }
+            }
+            INTERNAL_KIND => {
+                let count = read_u16(bytes, &mut position)?;
+                let mut children = vec![read_u32(bytes, &mut position)?];
+                let mut keys = Vec::with_capacity(count as usize);
+                for _ in 0..count {
+                    keys.push(read_entry(bytes, &mut position)?);
+                    children.push(read_u32(bytes, &mut position)?);
+                }
+                Node::Internal { keys, children }
+            }
+            _ => return None,
+        };
+
+        (position == bytes.len()).then_some(node)
+    }
+
+    /// Splits an overfull node into two halves of roughly equal byte size, returning
+    /// the left half, the separator for the parent and the right half.
+    ///
+    /// Leaf separators are copies of the right half's first entry; internal
+    /// separators move up and are no longer in either half. The left leaf's `next`
+    /// still has to be pointed at wherever the right half ends up.
+    fn split(self) -> (Node, Entry, Node) {
+        match self {
+            Node::Leaf { mut entries, next } => {
+                let mid = split_point(&entries, 1, entries.len() - 1);
+                let right_entries = entries.split_off(mid);
+                let separator = right_entries[0].clone();
+                (Node::Leaf { entries, next: None }, separator, Node::Leaf { entries: right_entries, next })
+            }
+            Node::Internal { mut keys, mut children } => {
+                let mid = split_point(&keys, 1, keys.len() - 1);
+                let right_keys = keys.split_off(mid + 1);
+                let separator = keys.pop().unwrap();
+                let right_children = children.split_off(mid + 1);
+                (Node::Internal { keys, children }, separator, Node::Internal { keys: right_keys, children: right_children })
+            }
+        }
+    }
+}
+
+/// index at which the cumulative key bytes first reach half the total, kept within `min..=max`
+fn split_point(entries: &[Entry], min: usize, max: usize) -> usize {
+    let total: usize = entries.iter().map(|(key, _)| key.len() + RecordId::SIZE).sum();
+    let mut accumulated = 0;
+    let mut mid = entries.len();
+    for (i, (key, _)) in entries.iter().enumerate() {
+        accumulated += key.len() + RecordId::SIZE;
+        if accumulated * 2 >= total {
+            mid = i;
+            break;
+        }
+    }
+    mid.clamp(min, max.max(min))
+}
+
+pub(super) fn write_entry(bytes: &mut Vec<u8>, (key, rid): &Entry) {
+    bytes.extend_from_slice(&(key.len() as u16).to_le_bytes());
+    bytes.extend_from_slice(key);
+    bytes.extend_from_slice(&rid.to_bytes());
+}
+
+pub(super) fn read_u16(bytes: &[u8], position: &mut usize) -> Option<u16> {
+    let value = u16::from_le_bytes(bytes.get(*position..*position + 2)?.try_into().ok()?);
+    *position += 2;
+    Some(value)
+}
+
+pub(super) fn read_u32(bytes: &[u8], position: &mut usize) -> Option<u32> {
+    let value = u32::from_le_bytes(bytes.get(*position..*position + 4)?.try_into().ok()?);
+    *position += 4;
+    Some(value)
+}
+
+pub(super) fn read_entry(bytes: &[u8], position: &mut usize) -> Option<Entry> {
+    let length = read_u16(bytes, position)? as usize;
+    let key = bytes.get(*position..*position + length)?.to_vec();
+    *position += length;
+    let rid = RecordId::from_bytes(bytes.get(*position..)?)?;
+    *position += RecordId::SIZE;
+    Some((key, rid))
+}
+
+fn read_node(buffer_pool: &mut BufferPool, page_id: u32) -> Result<Node, IndexError> {
+    let page = buffer_pool.get_page(page_id)?;
+    if !matches!(page.page_type(), PageType::BTreeLeaf | PageType::BTreeInternal) {
+        return Err(IndexError::CorruptNode(page_id));
+    }
+    let bytes = page.get_data(NODE_SLOT).map_err(|_| IndexError::CorruptNode(page_id))?;
+    Node::from_bytes(bytes).ok_or(IndexError::CorruptNode(page_id))
+}
+
+/// Stores `bytes` as the only tuple on node page `page_id`, replacing what was
+/// there, and logs the change for `txn` if there is a log manager.
+///
+/// A replacement is logged as a delete and an insert rather than an update, so
+/// redo and undo get to reuse the old version's space the same way.
+pub(super) fn write_node_tuple(page: &mut Page, page_id: u32, bytes: &[u8], log_manager: Option<&Arc<Mutex<LogManager>>>, txn: &mut Transaction) -> Result<(), IndexError> {
+    let rid = RecordId::new(page_id, NODE_SLOT);
+    let mut changes = Vec::new();
+    if let Ok(before) = page.get_data(NODE_SLOT) {
+        changes.push(LogRecordBody::Delete { rid, tuple: before.to_vec() });
+        page.delete_tuple(NODE_SLOT)?;
+    }
+    page.insert_tuple_at(NODE_SLOT, bytes)?;
+    changes.push(LogRecordBody::Insert { rid, tuple: bytes.to_vec() });
+
+    if let Some(log_manager) = log_manager {
+        let mut log_manager = log_manager.lock().unwrap();
+        for body in changes {
+            let lsn = txn.log(&mut log_manager, body);
+            page.set_lsn(lsn);
+        }
+    }
+    Ok(())
+}
+
+/// Allocates a page for an index node, logging the allocation for `txn` if there is a log manager.
+pub(super) fn new_node_page(buffer_pool: &mut BufferPool, page_type: PageType, log_manager: Option<&Arc<Mutex<LogManager>>>, txn: &mut Transaction) -> Result<u32, IndexError> {
+    let page_id = buffer_pool.new_page_of_type(page_type)?;
+    if let Some(log_manager) = log_manager {
+        let lsn = txn.log(&mut log_manager.lock().unwrap(), LogRecordBody::AllocatePage { page_id, page_type });
+        buffer_pool.get_page_mut(page_id)?.set_lsn(lsn);
+    }
+    Ok(page_id)
+}
+
+/// A B+Tree mapping byte-string keys to the `RecordId`s of the tuples they index.
+///
+/// Every node lives on its own page in the buffer pool. Keys compare
+/// lexicographically as bytes, so callers encode typed keys in an
+/// order-preserving form. The same key may point at many records: entries are
+/// unique per key and record id pair, and `get` returns every record for a key.
+///
+/// Leaves are linked left to right, so range scans walk the leaf level without
+/// going back up the tree. Nodes are split when they outgrow their page and
+/// merged with (or refilled from) a sibling when deletes leave them less than a
+/// quarter full. The root never moves, so `root_page_id` is all it takes to open
+/// the tree again later. Pages emptied by merges aren't reused yet. A split
+/// reserves the frames it needs in the buffer pool before changing any node,
+/// so a full pool fails the insert up front rather than halfway through.
+///
+/// A tree opened `with_unique_keys` allows only one record per key, which is
+/// how `UNIQUE` and `PRIMARY KEY` constraints are enforced. Once it has a log
+/// manager, every change to a node is logged for the transaction making it, so
+/// the tree rolls back and recovers along with the table it indexes.
+///
+/// # Examples
+///
+/// ```
+/// use gondor_rdbms::index::BPlusTree;
+/// use gondor_rdbms::storage::{BufferPool, DiskManager, RecordId};
+/// use gondor_rdbms::transaction::Transaction;
+/// use std::sync::{Arc, Mutex};
+///
+/// let dir = tempfile::tempdir().unwrap();
+/// let disk_manager = DiskManager::open(dir.path().join("example.db")).unwrap();
+/// let buffer_pool = Arc::new(Mutex::new(BufferPool::new(disk_manager)));
+/// let mut tree = BPlusTree::create(buffer_pool).unwrap();
+///
+/// let mut txn = Transaction::new(1);
+/// tree.insert(&mut txn, b"apple", RecordId::new(1, 0)).unwrap();
+/// tree.insert(&mut txn, b"banana", RecordId::new(1, 1)).unwrap();
+/// assert_eq!(tree.get(b"apple").unwrap(), vec![RecordId::new(1, 0)]);
+///
+/// let keys: Vec<Vec<u8>> = tree.scan().unwrap().map(|entry| entry.unwrap().0).collect();
+/// assert_eq!(keys, vec![b"apple".to_vec(), b"banana".to_vec()]);
+/// ```
+pub struct BPlusTree {
+    buffer_pool: Arc<Mutex<BufferPool>>,
+    /// changes are logged here, if set
+    log_manager: Option<Arc<Mutex<LogManager>>>,
+    root_page_id: u32,
+    /// largest serialized node, which is stored as the only tuple on its page
+    max_node_size: usize,
+    /// at most one entry per key
+    unique: bool,
+    /// frames claimed for the split in progress, if an insert is splitting nodes
+    reservation: Option<Reservation>,
+}
+
+impl BPlusTree {
+    /// Creates an empty tree, allocating its root page.
+    pub fn create(buffer_pool: Arc<Mutex<BufferPool>>) -> Result<Self, IndexError> {
+        let root_page_id = {
+            let mut buffer_pool = buffer_pool.lock().unwrap();
+            let root_page_id = buffer_pool.new_page_of_type(PageType::BTreeLeaf)?;
+            let page = buffer_pool.get_page_mut(root_page_id)?;
+            page.insert_tuple_at(NODE_SLOT, &Node::empty_leaf().to_bytes())?;
+            // creating the root isn't logged, write it out so a crash can't lose it
+            buffer_pool.write_page_to_disk(root_page_id)?;
+            root_page_id
+        };
+        Ok(Self::open(buffer_pool, root_page_id))
+    }
+
+    /// Opens a tree previously created with `create`.
+    pub fn open(buffer_pool: Arc<Mutex<BufferPool>>, root_page_id: u32) -> Self {
+        let max_node_size = max_tuple_size(buffer_pool.lock().unwrap().page_size());
+        Self {
+            buffer_pool,
+            log_manager: None,
+            root_page_id,
+            max_node_size,
+            unique: false,
+            reservation: None,
+        }
+    }
+
+    /// Makes the tree reject a second record for a key that's already in it
+    /// with `IndexError::DuplicateKey`. Uniqueness isn't stored in the tree, so
+    /// it has to be asked for again every time the tree is opened.
+    pub fn with_unique_keys(mut self) -> Self {
+        self.unique = true;
+        self
+    }
+
+    pub fn is_unique(&self) -> bool {
+        self.unique
+    }
+
+    /// Logs every subsequent change to `log_manager`.
+    pub fn set_log_manager(&mut self, log_manager: Arc<Mutex<LogManager>>) {
+        self.log_manager = Some(log_manager);
+    }
+
+    pub fn root_page_id(&self) -> u32 {
+        self.root_page_id
+    }
+
+    /// nodes (other than the root) smaller than this are merged with or refilled from a sibling
+    fn min_node_size(&self) -> usize {
+        self.max_node_size / 4
+    }
+
+    pub fn insert(&mut self, txn: &mut Transaction, key: &[u8], rid: RecordId) -> Result<(), IndexError> {
+        if key.len() > MAX_KEY_SIZE {
+            return Err(IndexError::KeyTooLarge);
+        }
+        if self.unique && !self.get(key)?.is_empty() {
+            return Err(IndexError::DuplicateKey);
+        }
+        let result = self.insert_entry(txn, (key.to_vec(), rid));
+        // whatever the split didn't use goes back to the pool
+        self.reservation = None;
+        result
+    }
+
+    fn insert_entry(&mut self, txn: &mut Transaction, entry: Entry) -> Result<(), IndexError> {
+        let Some((separator, right_page_id)) = self.insert_into(txn, self.root_page_id, entry)? else {
+            return Ok(());
+        };
+
+        // the root split: move its left half to a new page so the root keeps its page id
+        let left = self.read_node(self.root_page_id)?;
+        let left_page_id = self.new_node_page(txn)?;
+        self.write_node(txn, left_page_id, &left)?;
+        let root = Node::Internal {
+            keys: vec![separator],
+            children: vec![left_page_id, right_page_id],
+        };
+        self.write_node(txn, self.root_page_id, &root)
+    }
+
+    /// Removes the entry mapping `key` to `rid`.
+    pub fn delete(&mut self, txn: &mut Transaction, key: &[u8], rid: RecordId) -> Result<(), IndexError> {
+        self.delete_from(txn, self.root_page_id, key, rid)?;
+
+        // an internal root left with a single child is replaced by that child, shrinking the tree
+        loop {
+            match self.read_node(self.root_page_id)? {
+                Node::Internal { keys, children } if keys.is_empty() => {
+                    let child = self.read_node(children[0])?;
+                    self.write_node(txn, self.root_page_id, &child)?;
+                }
+                _ => return Ok(()),
+            }
+        }
+    }
+
+    /// Every record indexed under `key`, in record id order.
+    pub fn get(&self, key: &[u8]) -> Result<Vec<RecordId>, IndexError> {
+        self.range(Bound::Included(key), Bound::Included(key))?
+            .map(|entry| entry.map(|(_, rid)| rid))
+            .collect()
+    }
+
+    /// Entries with keys between `start` and `end`, in key order.
+    pub fn range(&self, start: Bound<&[u8]>, end: Bound<&[u8]>) -> Result<RangeScan, IndexError> {
+        // descend towards the smallest entry that could be in range
+        let lower = match start {
+            Bound::Included(key) | Bound::Excluded(key) => key,
+            Bound::Unbounded => &[],
+        };
+        let mut page_id = self.root_page_id;
+        loop {
+            match self.read_node(page_id)? {
+                Node::Internal { keys, children } => {
+                    page_id = children[child_index(&keys, lower, RecordId::new(0, 0))];
+                }
+                Node::Leaf { entries, next } => {
+                    return Ok(RangeScan {
+                        buffer_pool: Arc::clone(&self.buffer_pool),
+                        entries: entries.into_iter(),
+                        next_leaf: next,
+                        start: start.map(|key| key.to_vec()),
+                        end: end.map(|key| key.to_vec()),
+                        done: false,
+                    });
+                }
+            }
+        }
+    }
+
+    /// Every entry in the tree, in key order.
+    pub fn scan(&self) -> Result<RangeScan, IndexError> {
+        self.range(Bound::Unbounded, Bound::Unbounded)
+    }
+
+    /// Whether the tree is as `create` left it, with nothing ever inserted (or
+    /// everything inserted since deleted from its only node), so it can be bulk loaded.
+    pub fn is_empty(&self) -> Result<bool, IndexError> {
+        Ok(self.read_node(self.root_page_id)? == Node::empty_leaf())
+    }
+
+    /// Fills an empty tree with the entries of `sorter`, building it bottom-up.
+    ///
+    /// The sorted entries are packed into leaves left to right, and the first
+    /// entry of each node becomes its separator in a parent built the same way,
+    /// level by level until a single node is left, which becomes the root. Nodes
+    /// are filled to `BULK_LOAD_FILL` percent of a page and every page is written
+    /// once, so this is much faster than inserting the entries one at a time and
+    /// leaves the tree tightly packed. A unique tree still rejects duplicate keys.
+    pub fn bulk_load(&mut self, txn: &mut Transaction, sorter: EntrySorter) -> Result<(), IndexError> {
+        if !self.is_empty()? {
+            return Err(IndexError::TreeNotEmpty);
+        }
+
+        let mut levels: Vec<BulkLevel> = Vec::new();
+        let mut previous: Option<Entry> = None;
+        for entry in sorter.finish()? {
+            let entry = entry?;
+            if let Some(previous) = &previous {
+                if *previous == entry {
+                    return Err(IndexError::DuplicateEntry);
+                }
+                if self.unique && previous.0 == entry.0 {
+                    return Err(IndexError::DuplicateKey);
+                }
+            }
+            previous = Some(entry.clone());
+            self.bulk_add(txn, &mut levels, 0, entry, None)?;
+        }
+        if levels.is_empty() {
+            return Ok(());
+        }
+
+        // close the last node of every level, the one left on the top level is the root
+        for depth in 0.. {
+            if depth + 1 == levels.len() && levels[depth].previous.is_none() {
+                let root = match std::mem::replace(&mut levels[depth].node, Node::empty_leaf()) {
+                    // the level below merged its last two nodes into one, which is the root then
+                    Node::Internal { keys, children } if keys.is_empty() => self.read_node(children[0])?,
+                    root => root,
+                };
+                return self.write_node(txn, self.root_page_id, &root);
+            }
+            self.bulk_finish_level(txn, &mut levels, depth)?;
+        }
+        unreachable!()
+    }
+
+    /// Adds an entry to the last node of level `depth` (0 being the leaves), with
+    /// the child it leads to for internal levels. A node that's full is written
+    /// out first, and added to the level above.
+    fn bulk_add(&self, txn: &mut Transaction, levels: &mut Vec<BulkLevel>, depth: usize, entry: Entry, child: Option<u32>) -> Result<(), IndexError> {
+        if levels.len() == depth {
+            levels.push(BulkLevel::new(child.is_none()));
+        }
+        let level = &mut levels[depth];
+        if level.low.is_none() {
+            // the first entry of a node: a leaf keeps it, an internal node only needs the child
+            level.low = Some(entry.clone());
+            match &mut level.node {
+                Node::Leaf { entries, .. } => entries.push(entry),
+                Node::Internal { children, .. } => children.push(child.unwrap()),
+            }
+            return Ok(());
+        }
+
+        level.push(entry.clone(), child);
+        if level.node.size() * 100 <= self.max_node_size * BULK_LOAD_FILL {
+            return Ok(());
+        }
+        level.pop();
+
+        // the node is full: write it out and start the next one with the entry
+        let page_id = match level.page_id.take() {
+            Some(page_id) => page_id,
+            None => self.new_node_page(txn)?,
+        };
+        let next_page_id = self.new_node_page(txn)?;
+        let mut node = std::mem::replace(&mut level.node, Node::empty_leaf());
+        if let Node::Leaf { next, .. } = &mut node {
+            *next = Some(next_page_id);
+        }
+        self.write_node(txn, page_id, &node)?;
+        let low = level.low.take().unwrap();
+        *level = BulkLevel { page_id: Some(next_page_id), previous: Some((page_id, node)), ..BulkLevel::new(child.is_none()) };
+        self.bulk_add(txn, levels, depth, entry, child)?;
+        self.bulk_add(txn, levels, depth + 1, low, Some(page_id))
+    }
+
+    /// Writes out the last node of level `depth` and adds it to the level above.
+    /// If it's underfull it's merged with the node before it, or the two are
+    /// evened out if they don't fit on one page.
+    fn bulk_finish_level(&self, txn: &mut Transaction, levels: &mut Vec<BulkLevel>, depth: usize) -> Result<(), IndexError> {
+        let level = &mut levels[depth];
+        let node = std::mem::replace(&mut level.node, Node::empty_leaf());
+        let low = level.low.take().unwrap();
+        let page_id = match level.page_id.take() {
+            Some(page_id) => page_id,
+            None => self.new_node_page(txn)?,
+        };
+        let (previous_page_id, previous) = level.previous.take().expect("only the top level has a single node");
+        if node.size() >= self.min_node_size() {
+            self.write_node(txn, page_id, &node)?;
+            return self.bulk_add(txn, levels, depth + 1, low, Some(page_id));
+        }
+
+        let combined = match (previous, node) {
+            (Node::Leaf { entries: mut left, .. }, Node::Leaf { entries: right, .. }) => {
+                left.extend(right);
+                Node::Leaf { entries: left, next: None }
+            }
+            (Node::Internal { keys: mut left_keys, children: mut left_children }, Node::Internal { keys: right_keys, children: right_children }) => {
+                left_keys.push(low);
+                left_keys.extend(right_keys);
+                left_children.extend(right_children);
+                Node::Internal { keys: left_keys, children: left_children }
+            }
+            _ => unreachable!("a level holds nodes of one kind"),
+        };
+        if combined.size() <= self.max_node_size {
+            // the node's page is left unused, like pages emptied by merges
+            return self.write_node(txn, previous_page_id, &combined);
+        }
+        let (mut left, separator, right) = combined.split();
+        if let Node::Leaf { next, .. } = &mut left {
+            *next = Some(page_id);
+        }
+        self.write_node(txn, previous_page_id, &left)?;
+        self.write_node(txn, page_id, &right)?;
+        self.bulk_add(txn, levels, depth + 1, separator, Some(page_id))
+    }
+
+    /// inserts into the subtree at `page_id`, returning the separator and new right sibling if it split
+    fn insert_into(&mut self, txn: &mut Transaction, page_id: u32, entry: Entry) -> Result<Option<(Entry, u32)>, IndexError> {
+        let mut node = self.read_node(page_id)?;
+        match &mut node {
+            Node::Leaf { entries, .. } => match entries.binary_search_by(|probe| compare(probe, &entry.0, entry.1)) {
+                Ok(_) => return Err(IndexError::DuplicateEntry),
+                Err(position) => entries.insert(position, entry),
+            },
+            Node::Internal { keys, children } => {
+                let index = child_index(keys, &entry.0, entry.1);
+                match self.insert_into(txn, children[index], entry)? {
+                    Some((separator, right_page_id)) => {
+                        keys.insert(index, separator);
+                        children.insert(index + 1, right_page_id);
+                    }
+                    None => return Ok(None),
+                }
+            }
+        }
+
+        if node.size() <= self.max_node_size {
+            self.write_node(txn, page_id, &node)?;
+            return Ok(None);
+        }
+
+        // nothing has been written yet when the first node splits: claim frames
+        // for its new sibling, the parent it changes and the page a root split
+        // adds, so running out of them can't leave the tree half split
+        if self.reservation.is_none() {
+            let mut buffer_pool = self.buffer_pool.lock().unwrap();
+            let frame_size = BufferPool::frame_size(buffer_pool.page_size());
+            self.reservation = Some(buffer_pool.reserve(SPLIT_FRAMES * frame_size)?);
+        }
+        let (mut left, separator, right) = node.split();
+        let right_page_id = self.new_node_page(txn)?;
+        if let Node::Leaf { next, .. } = &mut left {
+            *next = Some(right_page_id);
+        }
+        self.write_node(txn, page_id, &left)?;
+        self.write_node(txn, right_page_id, &right)?;
+        Ok(Some((separator, right_page_id)))
+    }
+
+    /// deletes from the subtree at `page_id`, returning whether its root is now underfull
+    fn delete_from(&mut self, txn: &mut Transaction, page_id: u32, key: &[u8], rid: RecordId) -> Result<bool, IndexError> {
+        let mut node = self.read_node(page_id)?;
+        match &mut node {
+            Node::Leaf { entries, .. } => {
+                let position = entries
+                    .binary_search_by(|probe| compare(probe, key, rid))
+                    .map_err(|_| IndexError::EntryNotFound)?;
+                entries.remove(position);
+            }
+            Node::Internal { keys, children } => {
+                let index = child_index(keys, key, rid);
+                if !self.delete_from(txn, children[index], key, rid)? {
+                    return Ok(false);
+                }
+                self.rebalance(txn, keys, children, index)?;
+            }
+        }
+
+        self.write_node(txn, page_id, &node)?;
+        Ok(node.size() < self.min_node_size())
+    }
+
+    /// Fixes up the underfull child at `index` by merging it with a sibling, or by
+    /// evening out their entries if the two don't fit on one page.
+    fn rebalance(&mut self, txn: &mut Transaction, keys: &mut Vec<Entry>, children: &mut Vec<u32>, index: usize) -> Result<(), IndexError> {
+        if children.len() < 2 {
+            return Ok(());
+        }
+        // always work on a (left, right) pair of adjacent children
+        let left_index = if index > 0 { index - 1 } else { index };
+        let (left_page_id, right_page_id) = (children[left_index], children[left_index + 1]);
+
+        let combined = match (self.read_node(left_page_id)?, self.read_node(right_page_id)?) {
+            (Node::Leaf { entries: mut left, .. }, Node::Leaf { entries: right, next }) => {
+                left.extend(right);
+                Node::Leaf { entries: left, next }
+            }
+            (Node::Internal { keys: mut left_keys, children: mut left_children }, Node::Internal { keys: right_keys, children: right_children }) => {
+                // the separator comes back down between the two halves
+                left_keys.push(keys[left_index].clone());
+                left_keys.extend(right_keys);
+                left_children.extend(right_children);
+                Node::Internal { keys: left_keys, children: left_children }
+            }
+            _ => return Err(IndexError::CorruptNode(right_page_id)),
+        };
+
+        if combined.size() <= self.max_node_size {
+            self.write_node(txn, left_page_id, &combined)?;
+            keys.remove(left_index);
+            children.remove(left_index + 1);
+            return Ok(());
+        }
+
+        let (mut left, separator, right) = combined.split();
+        if let Node::Leaf { next, .. } = &mut left {
+            *next = Some(right_page_id);
+        }
+        self.write_node(txn, left_page_id, &left)?;
+        self.write_node(txn, right_page_id, &right)?;
+        keys[left_index] = separator;
+        Ok(())
+    }
+
+    /// runs `f` on the locked buffer pool, admitting pages against the split's reservation if there is one
+    fn with_buffer_pool<T>(&self, f: impl FnOnce(&mut BufferPool) -> T) -> T {
+        let mut buffer_pool = self.buffer_pool.lock().unwrap();
+        match &self.reservation {
+            Some(reservation) => buffer_pool.with_reservation(reservation, f),
+            None => f(&mut buffer_pool),
+        }
+    }
+
+    fn new_node_page(&self, txn: &mut Transaction) -> Result<u32, IndexError> {
+        // the type is set to match the node when it's written
+        self.with_buffer_pool(|buffer_pool| new_node_page(buffer_pool, PageType::BTreeLeaf, self.log_manager.as_ref(), txn))
+    }
+
+    fn read_node(&self, page_id: u32) -> Result<Node, IndexError> {
+        self.with_buffer_pool(|buffer_pool| read_node(buffer_pool, page_id))
+    }
+
+    fn write_node(&self, txn: &mut Transaction, page_id: u32, node: &Node) -> Result<(), IndexError> {
+        self.with_buffer_pool(|buffer_pool| {
+            let page = buffer_pool.get_page_mut(page_id)?;
+            // the type is only a hint, the node's own kind byte is what counts when reading it back
+            // (redoing a change after a crash puts back the node, but not the type)
+            page.set_page_type(match node {
+                Node::Leaf { .. } => PageType::BTreeLeaf,
+                Node::Internal { .. } => PageType::BTreeInternal,
+            });
+            write_node_tuple(page, page_id, &node.to_bytes(), self.log_manager.as_ref(), txn)
+        })
+    }
+}
+
+/// The node `BPlusTree::bulk_load` is filling on one level of the tree.
+struct BulkLevel {
+    node: Node,
+    /// page the node goes on, if it was needed before the node was full
+    page_id: Option<u32>,
+    /// lowest entry under the node, its separator in the parent
+    low: Option<Entry>,
+    /// the node written before it, kept so an underfull last node can be evened out
+    previous: Option<(u32, Node)>,
+}
+
+impl BulkLevel {
+    fn new(leaf: bool) -> Self {
+        let node = if leaf { Node::empty_leaf() } else { Node::Internal { keys: Vec::new(), children: Vec::new() } };
+        Self { node, page_id: None, low: None, previous: None }
+    }
+
+    /// appends an entry to the node, with the child to its right for internal nodes
+    fn push(&mut self, entry: Entry, child: Option<u32>) {
+        match &mut self.node {
+            Node::Leaf { entries, .. } => entries.push(entry),
+            Node::Internal { keys, children } => {
+                keys.push(entry);
+                children.push(child.unwrap());
+            }
+        }
+    }
+
+    /// takes back the last `push`
+    fn pop(&mut self) {
+        match &mut self.node {
+            Node::Leaf { entries, .. } => {
+                entries.pop();
+            }
+            Node::Internal { keys, children } => {
+                keys.pop();
+                children.pop();
+            }
+        }
+    }
+}
+
+/// child of an internal node whose subtree covers the entry (key, rid)
+fn child_index(keys: &[Entry], key: &[u8], rid: RecordId) -> usize {
+    keys.partition_point(|separator| compare(separator, key, rid) != Ordering::Greater)
+}
+
+/// Iterator over a key range of a `BPlusTree`, returned by `BPlusTree::range`.
+///
+/// Leaves are read one at a time as the scan reaches them, so a scan doesn't
+/// hold the buffer pool locked between items.
+pub struct RangeScan {
+    buffer_pool: Arc<Mutex<BufferPool>>,
+    /// remaining entries of the current leaf
+    entries: std::vec::IntoIter<Entry>,
+    next_leaf: Option<u32>,
+    start: Bound<Vec<u8>>,
+    end: Bound<Vec<u8>>,
+    done: bool,
+}
+
+impl Iterator for RangeScan {
+    type Item = Result<(Vec<u8>, RecordId), IndexError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while !self.done {
+            let Some((key, rid)) = self.entries.next() else {
+                let Some(page_id) = self.next_leaf else {
+                    self.done = true;
+                    break;
+                };
+                match read_node(&mut self.buffer_pool.lock().unwrap(), page_id) {
+                    Ok(Node::Leaf { entries, next }) => {
+                        self.entries = entries.into_iter();
+                        self.next_leaf = next;
+                    }
+                    Ok(Node::Internal { .. }) => {
+                        self.done = true;
+                        return Some(Err(IndexError::CorruptNode(page_id)));
+                    }
+                    Err(error) => {
+                        self.done = true;
+                        return Some(Err(error));
+                    }
+                }
+                continue;
+            };
+
+            let before_start = match &self.start {
+                Bound::Included(start) => key < *start,
+                Bound::Excluded(start) => key <= *start,
+                Bound::Unbounded => false,
+            };
+            if before_start {
+                continue;
+            }
+            let after_end = match &self.end {
+                Bound::Included(end) => key > *end,
+                Bound::Excluded(end) => key >= *end,
+                Bound::Unbounded => false,
+            };
+            if after_end {
+                self.done = true;
+                break;
+            }
+            return Some(Ok((key, rid)));
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::DiskManager;
+    use tempfile::NamedTempFile;
+
+    fn new_buffer_pool(frames: Option<usize>) -> (Arc<Mutex<BufferPool>>, NamedTempFile) {
+        let temp_file = NamedTempFile::new().unwrap();
+        let disk_manager = DiskManager::open(temp_file.path()).unwrap();
+        let buffer_pool = match frames {
+            Some(frames) => BufferPool::with_capacity(disk_manager, frames),
+            None => BufferPool::new(disk_manager),
+        };
+        (Arc::new(Mutex::new(buffer_pool)), temp_file)
+    }
+
+    /// zero padded so byte order matches numeric order
+    fn key(i: u32) -> Vec<u8> {
+        format!("key{i:08}").into_bytes()
+    }
+
+    /// reproducible shuffle of 0..n
+    fn shuffled(n: u32) -> Vec<u32> {
+        let mut values: Vec<u32> = (0..n).collect();
+        let mut state: u64 = 0x2545F4914F6CDD1D;
+        for i in (1..values.len()).rev() {
+            state = state.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+            values.swap(i, (state >> 33) as usize % (i + 1));
+        }
+        values
+    }
+
+    /// walks the whole tree checking ordering, separators, fill and that all leaves sit at the same depth
+    fn check_invariants(tree: &BPlusTree) -> usize {
+        fn check(tree: &BPlusTree, page_id: u32, is_root: bool, low: Option<&Entry>, high: Option<&Entry>, depth: usize, leaf_depth: &mut Option<usize>) -> usize {
+            let node = tree.read_node(page_id).unwrap();
+            assert!(node.size() <= tree.max_node_size);
+            if !is_root {
+                assert!(node.size() >= tree.min_node_size(), "node {page_id} is underfull");
+            }
+            let in_bounds = |entry: &Entry| low.is_none_or(|low| entry >= low) && high.is_none_or(|high| entry < high);
+            match node {
+                Node::Leaf { entries, .. } => {
+                    assert!(entries.windows(2).all(|pair| pair[0] < pair[1]));
+                    assert!(entries.iter().all(in_bounds));
+                    assert_eq!(*leaf_depth.get_or_insert(depth), depth, "leaves at different depths");
+                    entries.len()
+                }
+                Node::Internal { keys, children } => {
+                    assert_eq!(children.len(), keys.len() + 1);
+                    assert!(keys.windows(2).all(|pair| pair[0] < pair[1]));
+                    assert!(keys.iter().all(in_bounds));
+                    (0..children.len())
+                        .map(|i| {
+                            let child_low = if i == 0 { low } else { Some(&keys[i - 1]) };
+                            let child_high = if i == keys.len() { high } else { Some(&keys[i]) };
+                            check(tree, children[i], false, child_low, child_high, depth + 1, leaf_depth)
+                        })
+                        .sum()
+                }
+            }
+        }
+        check(tree, tree.root_page_id, true, None, None, 0, &mut None)
+    }
+
+    #[test]
+    fn test_node_round_trip() {
+        let leaf = Node::Leaf { entries: vec![(b"a".to_vec(), RecordId::new(1, 2)), (Vec::new(), RecordId::new(3, 4))], next: Some(9) };
+        assert_eq!(Node::from_bytes(&leaf.to_bytes()), Some(leaf.clone()));
+        assert_eq!(leaf.to_bytes().len(), leaf.size());
+
+        let internal = Node::Internal { keys: vec![(b"m".to_vec(), RecordId::new(0, 0))], children: vec![4, 5] };
+        assert_eq!(Node::from_bytes(&internal.to_bytes()), Some(internal.clone()));
+        assert_eq!(internal.to_bytes().len(), internal.size());
+
+        assert_eq!(Node::from_bytes(&[7]), None);
+    }
+
+    #[test]
+    fn test_insert_and_point_lookup_with_splits() {
+        let (buffer_pool, _file) = new_buffer_pool(None);
+        let mut tree = BPlusTree::create(buffer_pool.clone()).unwrap();
+        let mut txn = Transaction::new(1);
+
+        for i in shuffled(2000) {
+            tree.insert(&mut txn, &key(i), RecordId::new(i, 0)).unwrap();
+        }
+
+        assert!(buffer_pool.lock().unwrap().num_pages() > 10, "2000 entries should span many pages");
+        assert_eq!(check_invariants(&tree), 2000);
+        for i in 0..2000 {
+            assert_eq!(tree.get(&key(i)).unwrap(), vec![RecordId::new(i, 0)]);
+        }
+        assert!(tree.get(b"missing").unwrap().is_empty());
+        // the root split, so it's an internal node now
+        assert_eq!(buffer_pool.lock().unwrap().get_page(tree.root_page_id()).unwrap().page_type(), PageType::BTreeInternal);
+    }
+
+    #[test]
+    fn test_duplicate_keys_and_entries() {
+        let (buffer_pool, _file) = new_buffer_pool(None);
+        let mut tree = BPlusTree::create(buffer_pool).unwrap();
+        let mut txn = Transaction::new(1);
+
+        // many records under one key, spread over several leaves
+        for slot_id in (0..600u16).rev() {
+            tree.insert(&mut txn, b"same", RecordId::new(1, slot_id)).unwrap();
+        }
+        tree.insert(&mut txn, b"other", RecordId::new(2, 0)).unwrap();
+
+        let rids = tree.get(b"same").unwrap();
+        assert_eq!(rids, (0..600u16).map(|slot_id| RecordId::new(1, slot_id)).collect::<Vec<_>>());
+        assert!(matches!(tree.insert(&mut txn, b"same", RecordId::new(1, 7)), Err(IndexError::DuplicateEntry)));
+        check_invariants(&tree);
+    }
+
+    #[test]
+    fn test_range_scans() {
+        let (buffer_pool, _file) = new_buffer_pool(None);
+        let mut tree = BPlusTree::create(buffer_pool).unwrap();
+        let mut txn = Transaction::new(1);
+        for i in shuffled(1000) {
+            tree.insert(&mut txn, &key(i), RecordId::new(i, 0)).unwrap();
+        }
+
+        let collect = |start: Bound<&[u8]>, end: Bound<&[u8]>| -> Vec<u32> {
+            tree.range(start, end).unwrap().map(|entry| entry.unwrap().1.page_id).collect()
+        };
+        let (k100, k200) = (key(100), key(200));
+
+        assert_eq!(collect(Bound::Included(&k100), Bound::Excluded(&k200)), (100..200).collect::<Vec<_>>());
+        assert_eq!(collect(Bound::Excluded(&k100), Bound::Included(&k200)), (101..=200).collect::<Vec<_>>());
+        assert_eq!(collect(Bound::Unbounded, Bound::Excluded(&k100)), (0..100).collect::<Vec<_>>());
+        assert_eq!(collect(Bound::Excluded(&k200), Bound::Unbounded), (201..1000).collect::<Vec<_>>());
+        assert_eq!(collect(Bound::Included(&k200), Bound::Excluded(&k100)), Vec::<u32>::new());
+        assert_eq!(tree.scan().unwrap().count(), 1000);
+    }
+
+    #[test]
+    fn test_delete_merges_back_to_single_leaf() {
+        let (buffer_pool, _file) = new_buffer_pool(None);
+        let mut tree = BPlusTree::create(buffer_pool).unwrap();
+        let mut txn = Transaction::new(1);
+        for i in 0..2000 {
+            tree.insert(&mut txn, &key(i), RecordId::new(i, 0)).unwrap();
+        }
+
+        assert!(matches!(tree.delete(&mut txn, &key(5), RecordId::new(6, 0)), Err(IndexError::EntryNotFound)));
+
+        // delete in a scattered order, checking the tree stays balanced on the way down
+        for (n, i) in shuffled(2000).into_iter().enumerate() {
+            tree.delete(&mut txn, &key(i), RecordId::new(i, 0)).unwrap();
+            if n % 250 == 0 {
+                assert_eq!(check_invariants(&tree), 2000 - n - 1);
+            }
+        }
+
+        assert_eq!(tree.scan().unwrap().count(), 0);
+        assert_eq!(tree.read_node(tree.root_page_id()).unwrap(), Node::empty_leaf());
+
+        // the tree is still usable once it has shrunk
+        tree.insert(&mut txn, b"again", RecordId::new(0, 0)).unwrap();
+        assert_eq!(tree.get(b"again").unwrap(), vec![RecordId::new(0, 0)]);
+    }
+
+    #[test]
+    fn test_variable_length_keys() {
+        let (buffer_pool, _file) = new_buffer_pool(None);
+        let mut tree = BPlusTree::create(buffer_pool).unwrap();
+        let mut txn = Transaction::new(1);
+
+        let keys: Vec<Vec<u8>> = shuffled(400).into_iter().map(|i| vec![(i % 251) as u8; 1 + (i as usize * 37) % MAX_KEY_SIZE]).collect();
+        for (i, key) in keys.iter().enumerate() {
+            tree.insert(&mut txn, key, RecordId::new(i as u32, 0)).unwrap();
+        }
+        assert_eq!(check_invariants(&tree), 400);
+
+        for (i, key) in keys.iter().enumerate().filter(|(i, _)| i % 3 != 0) {
+            tree.delete(&mut txn, key, RecordId::new(i as u32, 0)).unwrap();
+        }
+        assert_eq!(check_invariants(&tree), 134);
+
+        assert!(matches!(tree.insert(&mut txn, &[0u8; MAX_KEY_SIZE + 1], RecordId::new(0, 0)), Err(IndexError::KeyTooLarge)));
+    }
+
+    #[test]
+    fn test_reopen_from_root_page_through_small_pool() {
+        let (buffer_pool, file) = new_buffer_pool(Some(4));
+        let root_page_id = {
+            let mut tree = BPlusTree::create(buffer_pool.clone()).unwrap();
+            let mut txn = Transaction::new(1);
+            for i in shuffled(1500) {
+                tree.insert(&mut txn, &key(i), RecordId::new(i, 0)).unwrap();
+            }
+            buffer_pool.lock().unwrap().flush_all().unwrap();
+            tree.root_page_id()
+        };
+
+        let disk_manager = DiskManager::open(file.path()).unwrap();
+        let tree = BPlusTree::open(Arc::new(Mutex::new(BufferPool::with_capacity(disk_manager, 4))), root_page_id);
+        assert_eq!(check_invariants(&tree), 1500);
+        assert_eq!(tree.get(&key(777)).unwrap(), vec![RecordId::new(777, 0)]);
+    }
+
+    #[test]
+    fn test_split_reserves_its_frames_up_front() {
+        let budget_pool = |frames: usize| {
+            let temp_file = NamedTempFile::new().unwrap();
+            let disk_manager = DiskManager::open(temp_file.path()).unwrap();
+            let budget = frames * BufferPool::frame_size(disk_manager.page_size());
+            (Arc::new(Mutex::new(BufferPool::with_memory_budget(disk_manager, budget))), temp_file)
+        };
+        let mut txn = Transaction::new(1);
+
+        // two frames can't hold a split, which fails before touching the full leaf
+        let (buffer_pool, _file) = budget_pool(2);
+        let mut tree = BPlusTree::create(buffer_pool.clone()).unwrap();
+        let mut inserted = 0;
+        let error = loop {
+            match tree.insert(&mut txn, &key(inserted), RecordId::new(inserted, 0)) {
+                Ok(()) => inserted += 1,
+                Err(error) => break error,
+            }
+        };
+        assert!(matches!(error, IndexError::BufferPoolError(BufferPoolError::OutOfMemory)), "{error:?}");
+        assert_eq!(check_invariants(&tree), inserted as usize);
+        assert_eq!(buffer_pool.lock().unwrap().memory_usage().reserved, 0);
+
+        // with room for them, splits go ahead and give back what they didn't use
+        let (buffer_pool, _file) = budget_pool(4);
+        let mut tree = BPlusTree::create(buffer_pool.clone()).unwrap();
+        for i in shuffled(1500) {
+            tree.insert(&mut txn, &key(i), RecordId::new(i, 0)).unwrap();
+        }
+        assert_eq!(check_invariants(&tree), 1500);
+        assert_eq!(buffer_pool.lock().unwrap().memory_usage().reserved, 0);
+    }
+
+    /// a tree bulk loaded with `count` shuffled keys through a sorter that spills every few hundred entries
+    fn bulk_loaded(buffer_pool: &Arc<Mutex<BufferPool>>, count: u32) -> BPlusTree {
+        let mut sorter = EntrySorter::with_memory_limit(16 * 1024);
+        for i in shuffled(count) {
+            sorter.push(key(i), RecordId::new(i, 0)).unwrap();
+        }
+        let mut tree = BPlusTree::create(buffer_pool.clone()).unwrap();
+        tree.bulk_load(&mut Transaction::new(1), sorter).unwrap();
+        tree
+    }
+
+    #[test]
+    fn test_bulk_load_builds_a_valid_tree() {
+        let (buffer_pool, _file) = new_buffer_pool(None);
+        // an empty tree, a single leaf, two leaves that have to be evened out, and several levels
+        for count in [0, 1, 20, 60, 20000] {
+            let mut tree = bulk_loaded(&buffer_pool, count);
+            assert_eq!(check_invariants(&tree), count as usize);
+            let keys: Vec<Vec<u8>> = tree.scan().unwrap().map(|entry| entry.unwrap().0).collect();
+            assert_eq!(keys, (0..count).map(key).collect::<Vec<_>>());
+
+            // and it keeps working like any other tree
+            let mut txn = Transaction::new(1);
+            for i in (0..count).step_by(3) {
+                tree.delete(&mut txn, &key(i), RecordId::new(i, 0)).unwrap();
+            }
+            for i in count..count + 500 {
+                tree.insert(&mut txn, &key(i), RecordId::new(i, 0)).unwrap();
+            }
+            assert_eq!(check_invariants(&tree), count as usize - count.div_ceil(3) as usize + 500);
+        }
+    }
+
+    #[test]
+    fn test_bulk_load_packs_pages_tighter_than_inserts() {
+        let (inserted_pool, _inserted_file) = new_buffer_pool(None);
+        let mut tree = BPlusTree::create(inserted_pool.clone()).unwrap();
+        let mut txn = Transaction::new(1);
+        for i in shuffled(5000) {
+            tree.insert(&mut txn, &key(i), RecordId::new(i, 0)).unwrap();
+        }
+        let (loaded_pool, _loaded_file) = new_buffer_pool(None);
+        bulk_loaded(&loaded_pool, 5000);
+
+        // random inserts leave nodes about 70% full
+        let pages = |pool: &Arc<Mutex<BufferPool>>| pool.lock().unwrap().num_pages();
+        assert!(pages(&loaded_pool) * 10 < pages(&inserted_pool) * 9, "{} vs {}", pages(&loaded_pool), pages(&inserted_pool));
+    }
+
+    #[test]
+    fn test_bulk_load_rejects_duplicates_and_full_trees() {
+        let (buffer_pool, _file) = new_buffer_pool(None);
+        let mut txn = Transaction::new(1);
+
+        let mut tree = BPlusTree::create(buffer_pool.clone()).unwrap().with_unique_keys();
+        let mut sorter = EntrySorter::new();
+        sorter.push(key(1), RecordId::new(1, 0)).unwrap();
+        sorter.push(key(1), RecordId::new(2, 0)).unwrap();
+        assert!(matches!(tree.bulk_load(&mut txn, sorter), Err(IndexError::DuplicateKey)));
+
+        let mut tree = BPlusTree::create(buffer_pool.clone()).unwrap();
+        let mut sorter = EntrySorter::new();
+        sorter.push(key(1), RecordId::new(1, 0)).unwrap();
+        sorter.push(key(1), RecordId::new(1, 0)).unwrap();
+        assert!(matches!(tree.bulk_load(&mut txn, sorter), Err(IndexError::DuplicateEntry)));
+
+        let mut tree = bulk_loaded(&buffer_pool, 10);
+        assert!(!tree.is_empty().unwrap());
+        assert!(matches!(tree.bulk_load(&mut txn, EntrySorter::new()), Err(IndexError::TreeNotEmpty)));
+        assert!(BPlusTree::create(buffer_pool).unwrap().is_empty().unwrap());
+    }
+}
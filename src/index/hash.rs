@@ -0,0 +1,533 @@
+use super::btree::{Entry, IndexError, MAX_KEY_SIZE, NO_PAGE, NODE_SLOT, new_node_page, read_entry, read_u16, read_u32, write_entry, write_node_tuple};
+use crate::storage::{BufferPool, PageType, RecordId, max_tuple_size};
+use crate::transaction::Transaction;
+use crate::wal::LogManager;
+use std::sync::{Arc, Mutex};
+
+/// 64-bit FNV-1a, finished with the MurmurHash3 mixer so that the low bits the
+/// directory uses depend on every bit of the key. The directory layout on disk
+/// depends on it, so it must never change.
+fn hash_key(key: &[u8]) -> u64 {
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    for &byte in key {
+        hash ^= byte as u64;
+        hash = hash.wrapping_mul(0x0100_0000_01b3);
+    }
+    hash ^= hash >> 33;
+    hash = hash.wrapping_mul(0xff51_afd7_ed55_8ccd);
+    hash ^= hash >> 33;
+    hash = hash.wrapping_mul(0xc4ce_b9fe_1a85_ec53);
+    hash ^ (hash >> 33)
+}
+
+/// low `depth` bits of a hash
+fn prefix(hash: u64, depth: u8) -> u64 {
+    hash & ((1u64 << depth) - 1)
+}
+
+fn entry_size(key: &[u8]) -> usize {
+    2 + key.len() + RecordId::SIZE
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Directory {
+    global_depth: u8,
+    /// first page of the bucket for every hash prefix, indexed by the low `global_depth` bits
+    buckets: Vec<u32>,
+}
+
+impl Directory {
+    fn size(&self) -> usize {
+        1 + 4 * self.buckets.len()
+    }
+
+    fn bucket_for(&self, hash: u64) -> u32 {
+        self.buckets[prefix(hash, self.global_depth) as usize]
+    }
+
+    fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(self.size());
+        bytes.push(self.global_depth);
+        for page_id in &self.buckets {
+            bytes.extend_from_slice(&page_id.to_le_bytes());
+        }
+        bytes
+    }
+
+    fn from_bytes(bytes: &[u8]) -> Option<Self> {
+        let global_depth = *bytes.first()?;
+        if global_depth >= 32 {
+            return None;
+        }
+        let mut position = 1;
+        let buckets = (0..1usize << global_depth).map(|_| read_u32(bytes, &mut position)).collect::<Option<Vec<_>>>()?;
+        (position == bytes.len()).then_some(Directory { global_depth, buckets })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Bucket {
+    /// number of low hash bits all keys in the bucket have in common
+    local_depth: u8,
+    /// overflow page holding more of the bucket's entries
+    next: Option<u32>,
+    entries: Vec<Entry>,
+}
+
+impl Bucket {
+    fn empty(local_depth: u8) -> Self {
+        Bucket { local_depth, next: None, entries: Vec::new() }
+    }
+
+    fn size(&self) -> usize {
+        1 + 4 + 2 + self.entries.iter().map(|(key, _)| entry_size(key)).sum::<usize>()
+    }
+
+    fn position(&self, key: &[u8], rid: RecordId) -> Option<usize> {
+        self.entries.iter().position(|(k, r)| k == key && *r == rid)
+    }
+
+    fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(self.size());
+        bytes.push(self.local_depth);
+        bytes.extend_from_slice(&self.next.unwrap_or(NO_PAGE).to_le_bytes());
+        bytes.extend_from_slice(&(self.entries.len() as u16).to_le_bytes());
+        for entry in &self.entries {
+            write_entry(&mut bytes, entry);
+        }
+        bytes
+    }
+
+    fn from_bytes(bytes: &[u8]) -> Option<Self> {
+        let local_depth = *bytes.first()?;
+        let mut position = 1;
+        let next = read_u32(bytes, &mut position)?;
+        let count = read_u16(bytes, &mut position)?;
+        let entries = (0..count).map(|_| read_entry(bytes, &mut position)).collect::<Option<Vec<_>>>()?;
+        (position == bytes.len()).then_some(Bucket { local_depth, next: (next != NO_PAGE).then_some(next), entries })
+    }
+}
+
+/// An extendible hash index mapping byte-string keys to `RecordId`s, for
+/// equality lookups that cost a directory read and a bucket read no matter how
+/// big the index gets.
+///
+/// The directory page maps the low `global_depth` bits of a key's hash to the
+/// bucket holding it. A full bucket is split in two by the next hash bit,
+/// doubling the directory first if the bucket already uses every bit the
+/// directory does. Keys that can't be told apart by splitting (the same key
+/// under many records, or a directory that has outgrown its page) go to
+/// overflow pages chained off the bucket instead.
+///
+/// Like `BPlusTree`, entries are unique per key and record id pair and `get`
+/// returns every record for a key, but there is no ordering to scan ranges by.
+/// Buckets are never merged and the directory never shrinks; overflow pages
+/// emptied by deletes are unlinked but not reused yet. Changes are logged like
+/// a `BPlusTree`'s once the index has a log manager.
+///
+/// # Examples
+///
+/// ```
+/// use gondor_rdbms::index::HashIndex;
+/// use gondor_rdbms::storage::{BufferPool, DiskManager, RecordId};
+/// use gondor_rdbms::transaction::Transaction;
+/// use std::sync::{Arc, Mutex};
+///
+/// let dir = tempfile::tempdir().unwrap();
+/// let disk_manager = DiskManager::open(dir.path().join("example.db")).unwrap();
+/// let buffer_pool = Arc::new(Mutex::new(BufferPool::new(disk_manager)));
+/// let mut index = HashIndex::create(buffer_pool).unwrap();
+///
+/// let mut txn = Transaction::new(1);
+/// index.insert(&mut txn, b"apple", RecordId::new(1, 0)).unwrap();
+/// index.insert(&mut txn, b"apple", RecordId::new(1, 3)).unwrap();
+/// assert_eq!(index.get(b"apple").unwrap(), vec![RecordId::new(1, 0), RecordId::new(1, 3)]);
+/// assert!(index.get(b"banana").unwrap().is_empty());
+/// ```
+pub struct HashIndex {
+    buffer_pool: Arc<Mutex<BufferPool>>,
+    /// changes are logged here, if set
+    log_manager: Option<Arc<Mutex<LogManager>>>,
+    directory_page_id: u32,
+    /// largest serialized directory or bucket, which is stored as the only tuple on its page
+    max_node_size: usize,
+}
+
+impl HashIndex {
+    /// Creates an empty index, allocating its directory page and a single bucket.
+    pub fn create(buffer_pool: Arc<Mutex<BufferPool>>) -> Result<Self, IndexError> {
+        let directory_page_id = {
+            let mut buffer_pool = buffer_pool.lock().unwrap();
+            let directory_page_id = buffer_pool.new_page_of_type(PageType::HashDirectory)?;
+            let bucket_page_id = buffer_pool.new_page_of_type(PageType::HashBucket)?;
+            let directory = Directory { global_depth: 0, buckets: vec![bucket_page_id] };
+            buffer_pool.get_page_mut(directory_page_id)?.insert_tuple_at(NODE_SLOT, &directory.to_bytes())?;
+            buffer_pool.get_page_mut(bucket_page_id)?.insert_tuple_at(NODE_SLOT, &Bucket::empty(0).to_bytes())?;
+            // creating the index isn't logged, write it out so a crash can't lose it
+            buffer_pool.write_page_to_disk(directory_page_id)?;
+            buffer_pool.write_page_to_disk(bucket_page_id)?;
+            directory_page_id
+        };
+        Ok(Self::open(buffer_pool, directory_page_id))
+    }
+
+    /// Opens an index previously created with `create`.
+    pub fn open(buffer_pool: Arc<Mutex<BufferPool>>, directory_page_id: u32) -> Self {
+        let max_node_size = max_tuple_size(buffer_pool.lock().unwrap().page_size());
+        Self {
+            buffer_pool,
+            log_manager: None,
+            directory_page_id,
+            max_node_size,
+        }
+    }
+
+    /// Logs every subsequent change to `log_manager`.
+    pub fn set_log_manager(&mut self, log_manager: Arc<Mutex<LogManager>>) {
+        self.log_manager = Some(log_manager);
+    }
+
+    pub fn directory_page_id(&self) -> u32 {
+        self.directory_page_id
+    }
+
+    pub fn insert(&mut self, txn: &mut Transaction, key: &[u8], rid: RecordId) -> Result<(), IndexError> {
+        if key.len() > MAX_KEY_SIZE {
+            return Err(IndexError::KeyTooLarge);
+        }
+        let hash = hash_key(key);
+
+        loop {
+            let mut directory = self.read_directory()?;
+            let mut chain = self.read_chain(directory.bucket_for(hash))?;
+            if chain.iter().any(|(_, bucket)| bucket.position(key, rid).is_some()) {
+                return Err(IndexError::DuplicateEntry);
+            }
+
+            if let Some((page_id, bucket)) = chain.iter_mut().find(|(_, bucket)| bucket.size() + entry_size(key) <= self.max_node_size) {
+                bucket.entries.push((key.to_vec(), rid));
+                return self.write_bucket(txn, *page_id, bucket);
+            }
+
+            // the bucket is full, but splitting only helps if the next hash bit separates its keys
+            let local_depth = chain[0].1.local_depth;
+            let all_same_hash = chain.iter().flat_map(|(_, bucket)| &bucket.entries).all(|(k, _)| hash_key(k) == hash);
+            let directory_full = local_depth == directory.global_depth && 2 * directory.size() - 1 > self.max_node_size;
+            if all_same_hash || directory_full {
+                let overflow_page_id = self.new_bucket_page(txn)?;
+                self.write_bucket(txn, overflow_page_id, &Bucket { local_depth, next: None, entries: vec![(key.to_vec(), rid)] })?;
+                let (last_page_id, last) = chain.last_mut().unwrap();
+                last.next = Some(overflow_page_id);
+                return self.write_bucket(txn, *last_page_id, last);
+            }
+
+            if local_depth == directory.global_depth {
+                directory.buckets.extend_from_within(..);
+                directory.global_depth += 1;
+            }
+            self.split(txn, &mut directory, chain)?;
+            self.write_directory(txn, &directory)?;
+            // the entry goes in on the next round, into whichever half it hashes to now
+        }
+    }
+
+    /// Removes the entry mapping `key` to `rid`.
+    pub fn delete(&mut self, txn: &mut Transaction, key: &[u8], rid: RecordId) -> Result<(), IndexError> {
+        let directory = self.read_directory()?;
+        let mut chain = self.read_chain(directory.bucket_for(hash_key(key)))?;
+        let (index, position) = chain
+            .iter()
+            .enumerate()
+            .find_map(|(index, (_, bucket))| bucket.position(key, rid).map(|position| (index, position)))
+            .ok_or(IndexError::EntryNotFound)?;
+
+        chain[index].1.entries.swap_remove(position);
+        if chain[index].1.entries.is_empty() && chain.len() > 1 {
+            // unlink the emptied page from the chain; the directory points at the
+            // first page, so if that's the empty one the second moves into it
+            let (unlinked, into) = if index == 0 { (1, 0) } else { (index, index - 1) };
+            let next = chain[unlinked].1.next;
+            if index == 0 {
+                chain[0].1.entries = std::mem::take(&mut chain[1].1.entries);
+            }
+            chain[into].1.next = next;
+            let (page_id, bucket) = &chain[into];
+            return self.write_bucket(txn, *page_id, bucket);
+        }
+        let (page_id, bucket) = &chain[index];
+        self.write_bucket(txn, *page_id, bucket)
+    }
+
+    /// Every record indexed under `key`, in record id order.
+    pub fn get(&self, key: &[u8]) -> Result<Vec<RecordId>, IndexError> {
+        let directory = self.read_directory()?;
+        let mut rids: Vec<RecordId> = self
+            .read_chain(directory.bucket_for(hash_key(key)))?
+            .into_iter()
+            .flat_map(|(_, bucket)| bucket.entries)
+            .filter(|(k, _)| k == key)
+            .map(|(_, rid)| rid)
+            .collect();
+        rids.sort();
+        Ok(rids)
+    }
+
+    /// Splits the bucket stored in `chain` by the first hash bit past its local
+    /// depth, pointing the directory slots with that bit set at the new half.
+    fn split(&self, txn: &mut Transaction, directory: &mut Directory, chain: Vec<(u32, Bucket)>) -> Result<(), IndexError> {
+        let page_id = chain[0].0;
+        let local_depth = chain[0].1.local_depth;
+        let bit = 1u64 << local_depth;
+
+        // the old overflow pages are reused before any new ones are allocated
+        let mut spare: Vec<u32> = chain[1..].iter().map(|(page_id, _)| *page_id).collect();
+        let (high, low): (Vec<Entry>, Vec<Entry>) = chain.into_iter().flat_map(|(_, bucket)| bucket.entries).partition(|(key, _)| hash_key(key) & bit != 0);
+
+        let high_page_id = self.take_page(txn, &mut spare)?;
+        self.write_chain(txn, page_id, local_depth + 1, low, &mut spare)?;
+        self.write_chain(txn, high_page_id, local_depth + 1, high, &mut spare)?;
+        for (slot, bucket) in directory.buckets.iter_mut().enumerate() {
+            if *bucket == page_id && slot as u64 & bit != 0 {
+                *bucket = high_page_id;
+            }
+        }
+        Ok(())
+    }
+
+    /// Writes `entries` as a bucket starting at `page_id`, continuing onto pages
+    /// from `spare` (or new ones) whenever a page fills up.
+    fn write_chain(&self, txn: &mut Transaction, mut page_id: u32, local_depth: u8, entries: Vec<Entry>, spare: &mut Vec<u32>) -> Result<(), IndexError> {
+        let mut bucket = Bucket::empty(local_depth);
+        for entry in entries {
+            if bucket.size() + entry_size(&entry.0) > self.max_node_size {
+                let next_page_id = self.take_page(txn, spare)?;
+                bucket.next = Some(next_page_id);
+                self.write_bucket(txn, page_id, &bucket)?;
+                page_id = next_page_id;
+                bucket = Bucket::empty(local_depth);
+            }
+            bucket.entries.push(entry);
+        }
+        self.write_bucket(txn, page_id, &bucket)
+    }
+
+    fn take_page(&self, txn: &mut Transaction, spare: &mut Vec<u32>) -> Result<u32, IndexError> {
+        match spare.pop() {
+            Some(page_id) => Ok(page_id),
+            None => self.new_bucket_page(txn),
+        }
+    }
+
+    fn new_bucket_page(&self, txn: &mut Transaction) -> Result<u32, IndexError> {
+        new_node_page(&mut self.buffer_pool.lock().unwrap(), PageType::HashBucket, self.log_manager.as_ref(), txn)
+    }
+
+    /// the bucket starting at `page_id` followed by its overflow pages
+    fn read_chain(&self, page_id: u32) -> Result<Vec<(u32, Bucket)>, IndexError> {
+        let mut chain = Vec::new();
+        let mut next = Some(page_id);
+        while let Some(page_id) = next {
+            let bucket = self.read_bucket(page_id)?;
+            next = bucket.next;
+            chain.push((page_id, bucket));
+        }
+        Ok(chain)
+    }
+
+    fn read_directory(&self) -> Result<Directory, IndexError> {
+        let bytes = self.read_node(self.directory_page_id, PageType::HashDirectory)?;
+        Directory::from_bytes(&bytes).ok_or(IndexError::CorruptNode(self.directory_page_id))
+    }
+
+    fn read_bucket(&self, page_id: u32) -> Result<Bucket, IndexError> {
+        let bytes = self.read_node(page_id, PageType::HashBucket)?;
+        Bucket::from_bytes(&bytes).ok_or(IndexError::CorruptNode(page_id))
+    }
+
+    fn write_directory(&self, txn: &mut Transaction, directory: &Directory) -> Result<(), IndexError> {
+        self.write_node(txn, self.directory_page_id, &directory.to_bytes())
+    }
+
+    fn write_bucket(&self, txn: &mut Transaction, page_id: u32, bucket: &Bucket) -> Result<(), IndexError> {
+        self.write_node(txn, page_id, &bucket.to_bytes())
+    }
+
+    fn read_node(&self, page_id: u32, page_type: PageType) -> Result<Vec<u8>, IndexError> {
+        let mut buffer_pool = self.buffer_pool.lock().unwrap();
+        let page = buffer_pool.get_page(page_id)?;
+        if page.page_type() != page_type {
+            return Err(IndexError::CorruptNode(page_id));
+        }
+        let bytes = page.get_data(NODE_SLOT).map_err(|_| IndexError::CorruptNode(page_id))?;
+        Ok(bytes.to_vec())
+    }
+
+    fn write_node(&self, txn: &mut Transaction, page_id: u32, bytes: &[u8]) -> Result<(), IndexError> {
+        let mut buffer_pool = self.buffer_pool.lock().unwrap();
+        let page = buffer_pool.get_page_mut(page_id)?;
+        write_node_tuple(page, page_id, bytes, self.log_manager.as_ref(), txn)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::DiskManager;
+    use tempfile::NamedTempFile;
+
+    fn new_buffer_pool(frames: Option<usize>) -> (Arc<Mutex<BufferPool>>, NamedTempFile) {
+        let temp_file = NamedTempFile::new().unwrap();
+        let disk_manager = DiskManager::open(temp_file.path()).unwrap();
+        let buffer_pool = match frames {
+            Some(frames) => BufferPool::with_capacity(disk_manager, frames),
+            None => BufferPool::new(disk_manager),
+        };
+        (Arc::new(Mutex::new(buffer_pool)), temp_file)
+    }
+
+    fn key(i: u32) -> Vec<u8> {
+        format!("key{i:08}").into_bytes()
+    }
+
+    /// checks every bucket holds only keys matching its directory slots, returning the number of entries
+    fn check_invariants(index: &HashIndex) -> usize {
+        let directory = index.read_directory().unwrap();
+        assert_eq!(directory.buckets.len(), 1 << directory.global_depth);
+
+        let mut seen = std::collections::HashSet::new();
+        let mut count = 0;
+        for (slot, &page_id) in directory.buckets.iter().enumerate() {
+            let chain = index.read_chain(page_id).unwrap();
+            let local_depth = chain[0].1.local_depth;
+            assert!(local_depth <= directory.global_depth);
+            // a bucket is shared by every slot agreeing on its local depth's bits
+            let sharing = directory.buckets.iter().filter(|&&other| other == page_id).count();
+            assert_eq!(sharing, 1 << (directory.global_depth - local_depth));
+
+            for (_, bucket) in &chain {
+                assert_eq!(bucket.local_depth, local_depth);
+                assert!(bucket.size() <= index.max_node_size);
+                for (key, _) in &bucket.entries {
+                    assert_eq!(prefix(hash_key(key), local_depth), prefix(slot as u64, local_depth));
+                }
+            }
+            if seen.insert(page_id) {
+                count += chain.iter().map(|(_, bucket)| bucket.entries.len()).sum::<usize>();
+            }
+        }
+        count
+    }
+
+    #[test]
+    fn test_node_round_trip() {
+        let directory = Directory { global_depth: 2, buckets: vec![3, 4, 3, 5] };
+        assert_eq!(Directory::from_bytes(&directory.to_bytes()), Some(directory.clone()));
+        assert_eq!(directory.to_bytes().len(), directory.size());
+        // the bucket count has to match the depth
+        assert_eq!(Directory::from_bytes(&directory.to_bytes()[..13]), None);
+
+        let bucket = Bucket { local_depth: 1, next: Some(8), entries: vec![(b"a".to_vec(), RecordId::new(1, 2))] };
+        assert_eq!(Bucket::from_bytes(&bucket.to_bytes()), Some(bucket.clone()));
+        assert_eq!(bucket.to_bytes().len(), bucket.size());
+    }
+
+    #[test]
+    fn test_insert_and_get_with_splits() {
+        let (buffer_pool, _file) = new_buffer_pool(None);
+        let mut index = HashIndex::create(buffer_pool).unwrap();
+        let mut txn = Transaction::new(1);
+
+        for i in 0..5000 {
+            index.insert(&mut txn, &key(i), RecordId::new(i, 0)).unwrap();
+        }
+
+        assert!(index.read_directory().unwrap().global_depth >= 4, "5000 entries should need many buckets");
+        assert_eq!(check_invariants(&index), 5000);
+        for i in 0..5000 {
+            assert_eq!(index.get(&key(i)).unwrap(), vec![RecordId::new(i, 0)]);
+        }
+        assert!(index.get(b"missing").unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_many_records_under_one_key_overflow() {
+        let (buffer_pool, _file) = new_buffer_pool(None);
+        let mut index = HashIndex::create(buffer_pool).unwrap();
+        let mut txn = Transaction::new(1);
+
+        for slot_id in (0..1000u16).rev() {
+            index.insert(&mut txn, b"same", RecordId::new(1, slot_id)).unwrap();
+        }
+        index.insert(&mut txn, b"other", RecordId::new(2, 0)).unwrap();
+
+        assert_eq!(index.get(b"same").unwrap(), (0..1000u16).map(|slot_id| RecordId::new(1, slot_id)).collect::<Vec<_>>());
+        assert_eq!(index.get(b"other").unwrap(), vec![RecordId::new(2, 0)]);
+        assert_eq!(check_invariants(&index), 1001);
+        let directory = index.read_directory().unwrap();
+        assert!(index.read_chain(directory.bucket_for(hash_key(b"same"))).unwrap().len() > 1);
+
+        // deleting empties the overflow pages, which drop out of the chain
+        for slot_id in 1..1000u16 {
+            index.delete(&mut txn, b"same", RecordId::new(1, slot_id)).unwrap();
+        }
+        assert_eq!(index.get(b"same").unwrap(), vec![RecordId::new(1, 0)]);
+        let directory = index.read_directory().unwrap();
+        assert_eq!(index.read_chain(directory.bucket_for(hash_key(b"same"))).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_duplicate_missing_and_oversized_entries() {
+        let (buffer_pool, _file) = new_buffer_pool(None);
+        let mut index = HashIndex::create(buffer_pool).unwrap();
+        let mut txn = Transaction::new(1);
+        index.insert(&mut txn, b"key", RecordId::new(1, 0)).unwrap();
+
+        assert!(matches!(index.insert(&mut txn, b"key", RecordId::new(1, 0)), Err(IndexError::DuplicateEntry)));
+        assert!(matches!(index.delete(&mut txn, b"key", RecordId::new(1, 1)), Err(IndexError::EntryNotFound)));
+        assert!(matches!(index.delete(&mut txn, b"nope", RecordId::new(1, 0)), Err(IndexError::EntryNotFound)));
+        assert!(matches!(index.insert(&mut txn, &[0u8; MAX_KEY_SIZE + 1], RecordId::new(0, 0)), Err(IndexError::KeyTooLarge)));
+        index.insert(&mut txn, &[0u8; MAX_KEY_SIZE], RecordId::new(0, 0)).unwrap();
+    }
+
+    #[test]
+    fn test_delete() {
+        let (buffer_pool, _file) = new_buffer_pool(None);
+        let mut index = HashIndex::create(buffer_pool).unwrap();
+        let mut txn = Transaction::new(1);
+        for i in 0..2000 {
+            index.insert(&mut txn, &key(i), RecordId::new(i, 0)).unwrap();
+        }
+
+        for i in (0..2000).step_by(2) {
+            index.delete(&mut txn, &key(i), RecordId::new(i, 0)).unwrap();
+        }
+        assert_eq!(check_invariants(&index), 1000);
+        for i in 0..2000 {
+            let expected = if i % 2 == 0 { vec![] } else { vec![RecordId::new(i, 0)] };
+            assert_eq!(index.get(&key(i)).unwrap(), expected);
+        }
+
+        // deleted keys can go back in
+        index.insert(&mut txn, &key(0), RecordId::new(9, 9)).unwrap();
+        assert_eq!(index.get(&key(0)).unwrap(), vec![RecordId::new(9, 9)]);
+    }
+
+    #[test]
+    fn test_reopen_from_directory_page_through_small_pool() {
+        let (buffer_pool, file) = new_buffer_pool(Some(4));
+        let directory_page_id = {
+            let mut index = HashIndex::create(buffer_pool.clone()).unwrap();
+            let mut txn = Transaction::new(1);
+            for i in 0..1500 {
+                index.insert(&mut txn, &key(i), RecordId::new(i, 0)).unwrap();
+            }
+            buffer_pool.lock().unwrap().flush_all().unwrap();
+            index.directory_page_id()
+        };
+
+        let disk_manager = DiskManager::open(file.path()).unwrap();
+        let index = HashIndex::open(Arc::new(Mutex::new(BufferPool::with_capacity(disk_manager, 4))), directory_page_id);
+        assert_eq!(check_invariants(&index), 1500);
+        assert_eq!(index.get(&key(777)).unwrap(), vec![RecordId::new(777, 0)]);
+    }
+}
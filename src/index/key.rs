@@ -0,0 +1,380 @@
+use crate::types::{Decimal, Value};
+
+use std::ops::Bound;
+
+// NULL takes one of the tags either side of VALUE_TAG, depending on where it sorts
+const NULLS_FIRST_TAG: u8 = 0;
+const VALUE_TAG: u8 = 1;
+const NULLS_LAST_TAG: u8 = 2;
+
+/// Which way one column of an index key sorts, and where its NULLs go.
+///
+/// The defaults are PostgreSQL's: NULL sorts as if it were larger than every
+/// value, so it comes last in ascending columns and first in descending ones.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct KeyOrder {
+    pub descending: bool,
+    pub nulls_first: bool,
+}
+
+impl KeyOrder {
+    /// ascending, NULLs last
+    pub const ASC: KeyOrder = KeyOrder { descending: false, nulls_first: false };
+    /// descending, NULLs first
+    pub const DESC: KeyOrder = KeyOrder { descending: true, nulls_first: true };
+}
+
+impl Default for KeyOrder {
+    fn default() -> Self {
+        KeyOrder::ASC
+    }
+}
+
+/// Encodes `values` as an ascending index key whose bytes compare the way the values do.
+///
+/// Same as [`encode_key_with`] with [`KeyOrder::ASC`] for every column.
+///
+/// # Examples
+///
+/// ```
+/// use gondor_rdbms::index::encode_key;
+/// use gondor_rdbms::types::Value;
+///
+/// assert!(encode_key(&[Value::Int(-5)]) < encode_key(&[Value::Int(3)]));
+/// assert!(encode_key(&[Value::Int(i32::MAX)]) < encode_key(&[Value::Null]));
+///
+/// let frodo = encode_key(&[Value::Varchar("baggins".into()), Value::Varchar("frodo".into())]);
+/// let bilbo = encode_key(&[Value::Varchar("baggins".into()), Value::Varchar("bilbo".into())]);
+/// assert!(bilbo < frodo);
+/// ```
+pub fn encode_key(values: &[Value]) -> Vec<u8> {
+    encode_key_with(values, &vec![KeyOrder::ASC; values.len()])
+}
+
+/// Encodes `values` as an index key whose bytes compare the way the values do,
+/// each column sorting as its entry in `orders` says.
+///
+/// Indexes compare keys as plain byte strings, so every value is written in
+/// an order-preserving form: integers big-endian with the sign bit flipped,
+/// strings with their zero bytes escaped and a terminator, so a string sorts
+/// before any longer string it is a prefix of, and decimals by sign, then
+/// magnitude, then digits, so numbers of any scale compare by value and equal
+/// ones have the same key. Descending columns have those
+/// bytes inverted. Each value is preceded by a tag that puts NULL before or
+/// after all of the column's values. Keys of several columns compare column by
+/// column, like a tuple, and no column's encoding is a prefix of another's, so
+/// the keys starting with some values are exactly the keys of rows that have
+/// them (see [`key_range`]).
+///
+/// # Panics
+///
+/// If `orders` doesn't have one entry per value.
+///
+/// # Examples
+///
+/// ```
+/// use gondor_rdbms::index::{encode_key_with, KeyOrder};
+/// use gondor_rdbms::types::Value;
+///
+/// // (game ASC, score DESC): a game's best score first
+/// let orders = [KeyOrder::ASC, KeyOrder::DESC];
+/// let key = |game, score| encode_key_with(&[Value::Int(game), score], &orders);
+/// assert!(key(1, Value::Int(90)) < key(1, Value::Int(40)));
+/// assert!(key(1, Value::Int(40)) < key(2, Value::Int(90)));
+/// assert!(key(2, Value::Null) < key(2, Value::Int(90)));
+/// ```
+pub fn encode_key_with(values: &[Value], orders: &[KeyOrder]) -> Vec<u8> {
+    assert_eq!(values.len(), orders.len(), "every column of a key needs an order");
+    let mut key = Vec::new();
+    for (value, &order) in values.iter().zip(orders) {
+        encode_value(&mut key, value, order);
+    }
+    key
+}
+
+/// Key bounds of the entries whose first columns equal `prefix` and whose next
+/// column lies between `lower` and `upper`, ready for `BPlusTree::range`.
+///
+/// This is how an index on `(a, b)` serves `a = 1 AND b > 5`: the prefix is
+/// `[1]` and the next column is bounded below by `Excluded(5)`. If either bound
+/// is given, rows with NULL in the next column are left out, as no comparison
+/// with NULL is true; with neither, every entry starting with the prefix is in
+/// range. `orders` has the order of each key column, at least one more than
+/// `prefix` has values unless both bounds are unbounded.
+///
+/// # Examples
+///
+/// ```
+/// use gondor_rdbms::index::{encode_key_with, key_range, KeyOrder};
+/// use gondor_rdbms::types::Value;
+/// use std::ops::{Bound, RangeBounds};
+///
+/// let orders = [KeyOrder::ASC, KeyOrder::DESC];
+/// let (start, end) = key_range(&[Value::Int(1)], Bound::Excluded(&Value::Int(5)), Bound::Unbounded, &orders);
+/// let in_range = |key: Vec<u8>| (start.as_ref(), end.as_ref()).contains(&key);
+///
+/// assert!(in_range(encode_key_with(&[Value::Int(1), Value::Int(6)], &orders)));
+/// assert!(!in_range(encode_key_with(&[Value::Int(1), Value::Int(5)], &orders)));
+/// assert!(!in_range(encode_key_with(&[Value::Int(1), Value::Null], &orders)));
+/// assert!(!in_range(encode_key_with(&[Value::Int(2), Value::Int(6)], &orders)));
+/// ```
+pub fn key_range(prefix: &[Value], lower: Bound<&Value>, upper: Bound<&Value>, orders: &[KeyOrder]) -> (Bound<Vec<u8>>, Bound<Vec<u8>>) {
+    let start = encode_key_with(prefix, &orders[..prefix.len()]);
+    if matches!((lower, upper), (Bound::Unbounded, Bound::Unbounded)) {
+        if prefix.is_empty() {
+            return (Bound::Unbounded, Bound::Unbounded);
+        }
+        let end = successor(&start);
+        return (Bound::Included(start), Bound::Excluded(end));
+    }
+
+    let order = orders[prefix.len()];
+    // a descending column stores its largest values first
+    let (low, high) = if order.descending { (upper, lower) } else { (lower, upper) };
+    // every key of a row with `value` in the column starts with `with(value)`, and
+    // the key of a row with any value there (not NULL) with `with_any`
+    let with = |value: &Value| {
+        let mut key = start.clone();
+        encode_value(&mut key, value, order);
+        key
+    };
+    let mut with_any = start.clone();
+    with_any.push(VALUE_TAG);
+
+    let start = match low {
+        Bound::Included(value) => with(value),
+        Bound::Excluded(value) => successor(&with(value)),
+        Bound::Unbounded => with_any.clone(),
+    };
+    let end = match high {
+        Bound::Included(value) => successor(&with(value)),
+        Bound::Excluded(value) => with(value),
+        Bound::Unbounded => successor(&with_any),
+    };
+    (Bound::Included(start), Bound::Excluded(end))
+}
+
+/// The smallest byte string greater than every string starting with `key`.
+///
+/// There is one for every encoded key, as the tags are all below 0xFF.
+fn successor(key: &[u8]) -> Vec<u8> {
+    let last = key.iter().rposition(|&byte| byte != 0xFF).expect("key has a byte below 0xFF");
+    let mut successor = key[..=last].to_vec();
+    successor[last] += 1;
+    successor
+}
+
+fn encode_value(key: &mut Vec<u8>, value: &Value, order: KeyOrder) {
+    if value.is_null() {
+        key.push(if order.nulls_first { NULLS_FIRST_TAG } else { NULLS_LAST_TAG });
+        return;
+    }
+    key.push(VALUE_TAG);
+    let start = key.len();
+    match value {
+        Value::Null => unreachable!(),
+        Value::Int(value) => key.extend_from_slice(&((*value as u32) ^ (1 << 31)).to_be_bytes()),
+        Value::BigInt(value) => key.extend_from_slice(&((*value as u64) ^ (1 << 63)).to_be_bytes()),
+        Value::Bool(value) => key.push(*value as u8),
+        Value::Varchar(text) => {
+            // 0x00 0xFF stands for a zero byte and 0x00 0x00 ends the string, which
+            // sorts below anything the string could have gone on with
+            for &byte in text.as_bytes() {
+                key.push(byte);
+                if byte == 0 {
+                    key.push(0xFF);
+                }
+            }
+            key.extend_from_slice(&[0, 0]);
+        }
+        Value::Numeric(value) => encode_decimal(key, value),
+        Value::Date(days) => key.extend_from_slice(&((*days as u32) ^ (1 << 31)).to_be_bytes()),
+        Value::Time(micros) | Value::Timestamp(micros) => key.extend_from_slice(&((*micros as u64) ^ (1 << 63)).to_be_bytes()),
+        // by span, like intervals compare, so `1 mon` and `30 days` are the same key
+        Value::Interval(interval) => key.extend_from_slice(&((interval.span() as u128) ^ (1 << 127)).to_be_bytes()),
+    }
+    if order.descending {
+        // inverting turns the terminator into 0xFF 0xFF, still above an escaped zero
+        // (now 0xFF 0x00), so the longer string comes first as it should
+        for byte in &mut key[start..] {
+            *byte = !*byte;
+        }
+    }
+}
+
+/// A sign byte, 0 for negative numbers, 1 for zero and 2 for positive ones, then
+/// for nonzero numbers where the first significant digit is relative to the
+/// point and those digits (without the zeros at the end) each plus one, ended
+/// by a zero byte. Those are inverted for negative numbers, so the larger ones
+/// in size come first.
+fn encode_decimal(key: &mut Vec<u8>, value: &Decimal) {
+    let value = value.normalize();
+    let sign = value.mantissa().signum();
+    key.push((sign + 1) as u8);
+    if sign == 0 {
+        return;
+    }
+    let start = key.len();
+    let digits = value.mantissa().unsigned_abs().to_string();
+    // -37 up to 38 for numbers of up to 38 digits
+    let exponent = digits.len() as i32 - value.scale() as i32;
+    key.push((exponent + 128) as u8);
+    key.extend(digits.bytes().map(|digit| digit - b'0' + 1));
+    key.push(0);
+    if sign < 0 {
+        for byte in &mut key[start..] {
+            *byte = !*byte;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::DataType;
+    use crate::index::BPlusTree;
+    use crate::storage::{BufferPool, DiskManager, RecordId};
+    use crate::transaction::Transaction;
+    use std::sync::{Arc, Mutex};
+    use tempfile::NamedTempFile;
+
+    fn assert_sorted_with(values: &[Vec<Value>], orders: &[KeyOrder]) {
+        for pair in values.windows(2) {
+            let (first, second) = (encode_key_with(&pair[0], orders), encode_key_with(&pair[1], orders));
+            assert!(first < second, "{:?} should sort before {:?}", pair[0], pair[1]);
+        }
+    }
+
+    fn assert_sorted(values: &[Vec<Value>]) {
+        assert_sorted_with(values, &vec![KeyOrder::ASC; values[0].len()]);
+    }
+
+    fn text(value: &str) -> Value {
+        Value::Varchar(value.into())
+    }
+
+    #[test]
+    fn test_integers_sort_numerically() {
+        assert_sorted(&[i32::MIN, -70000, -1, 0, 1, 255, 256, i32::MAX].map(|value| vec![Value::Int(value)]));
+        assert_sorted(&[i64::MIN, -1, 0, 1 << 40, i64::MAX].map(|value| vec![Value::BigInt(value)]));
+        assert_sorted(&[vec![Value::Bool(false)], vec![Value::Bool(true)], vec![Value::Null]]);
+    }
+
+    #[test]
+    fn test_temporal_values_sort_chronologically() {
+        assert_sorted(&["1969-12-31", "1970-01-01", "2024-02-29", "2024-03-01"].map(|date| vec![Value::parse(date, DataType::Date).unwrap()]));
+        assert_sorted(&["1900-01-01 00:00", "2024-02-29 23:59:59.999999", "2024-03-01 00:00"].map(|timestamp| vec![Value::parse(timestamp, DataType::Timestamp).unwrap()]));
+        assert_sorted(&["-1 year", "-1 day", "00:00:01", "1 day -01:00:00", "1 day", "1 mon", "1 mon 1 us"].map(|interval| vec![Value::parse(interval, DataType::Interval).unwrap()]));
+        let interval = |text| encode_key(&[Value::parse(text, DataType::Interval).unwrap()]);
+        assert_eq!(interval("1 mon"), interval("30 days"));
+    }
+
+    #[test]
+    fn test_decimals_sort_by_value() {
+        let decimals = ["-99999999999999999999999999999999999999", "-100.5", "-100", "-99.99", "-0.001", "0", "0.00000000000000000000000000000000000001", "0.1", "0.10001", "9.9", "10", "100", "100.0001", "1000"];
+        let values: Vec<Vec<Value>> = decimals.iter().map(|text| vec![Value::Numeric(Decimal::parse(text).unwrap())]).collect();
+        assert_sorted(&values);
+        assert_sorted_with(&values.iter().rev().cloned().collect::<Vec<_>>(), &[KeyOrder::DESC]);
+        // whatever their scale, equal numbers are one key
+        let key = |number| encode_key(&[Value::Numeric(Decimal::parse(number).unwrap()), text("after")]);
+        assert_eq!(key("12.50"), key("12.5"));
+        assert_eq!(key("0.000"), key("0"));
+    }
+
+    #[test]
+    fn test_strings_sort_bytewise_with_prefixes_first() {
+        let strings = ["", "\0", "\0\0", "\0a", "a", "a\0", "ab", "b"];
+        assert_sorted(&strings.map(|value| vec![text(value)]));
+    }
+
+    #[test]
+    fn test_descending_columns_and_null_placement() {
+        let desc = [KeyOrder::DESC];
+        assert_sorted_with(&[Value::Null, Value::Int(i32::MAX), Value::Int(0), Value::Int(i32::MIN)].map(|value| vec![value]), &desc);
+        let strings = ["b", "ab", "a\0", "a", "\0a", "\0\0", "\0", ""];
+        assert_sorted_with(&strings.map(|value| vec![text(value)]), &desc);
+
+        let asc_nulls_first = [KeyOrder { nulls_first: true, ..KeyOrder::ASC }];
+        assert_sorted_with(&[vec![Value::Null], vec![Value::Int(i32::MIN)], vec![Value::Int(0)]], &asc_nulls_first);
+        let desc_nulls_last = [KeyOrder { nulls_first: false, ..KeyOrder::DESC }];
+        assert_sorted_with(&[vec![Value::Int(0)], vec![Value::Int(i32::MIN)], vec![Value::Null]], &desc_nulls_last);
+    }
+
+    #[test]
+    fn test_composite_keys_compare_column_by_column() {
+        assert_sorted(&[
+            vec![text("a"), Value::Int(-1)],
+            vec![text("a"), Value::Int(7)],
+            vec![text("a"), Value::Null],
+            // a longer first column still sorts after, whatever follows it
+            vec![text("a\0"), Value::Int(i32::MIN)],
+            vec![text("ab"), Value::Null],
+            vec![Value::Null, Value::Int(0)],
+        ]);
+        // mixed directions, NULLs where each column puts them
+        assert_sorted_with(
+            &[
+                vec![text("a"), Value::Null],
+                vec![text("a"), Value::Int(7)],
+                vec![text("a"), Value::Int(-1)],
+                vec![text("ab"), Value::Int(7)],
+                vec![Value::Null, Value::Null],
+                vec![Value::Null, Value::Int(0)],
+            ],
+            &[KeyOrder::ASC, KeyOrder::DESC],
+        );
+    }
+
+    #[test]
+    fn test_key_range_serves_prefix_equality_and_a_range_on_the_next_column() {
+        let temp_file = NamedTempFile::new().unwrap();
+        let buffer_pool = Arc::new(Mutex::new(BufferPool::new(DiskManager::open(temp_file.path()).unwrap())));
+        let mut txn = Transaction::new(1);
+
+        for orders in [[KeyOrder::ASC, KeyOrder::ASC], [KeyOrder::ASC, KeyOrder::DESC], [KeyOrder::DESC, KeyOrder::DESC]] {
+            // rows (a, b, c) with an index on (a, b); the record id's slot numbers the row
+            let mut rows = Vec::new();
+            for a in [Value::Int(1), Value::Int(2), Value::Null] {
+                for b in [Value::Int(-3), Value::Int(0), Value::Int(5), Value::Int(6), Value::Null] {
+                    for c in [text("x"), text("y")] {
+                        rows.push(vec![a.clone(), b.clone(), c]);
+                    }
+                }
+            }
+            let mut tree = BPlusTree::create(buffer_pool.clone()).unwrap();
+            for (slot, row) in rows.iter().enumerate() {
+                tree.insert(&mut txn, &encode_key_with(&row[..2], &orders), RecordId::new(0, slot as u16)).unwrap();
+            }
+
+            let scan = |prefix: &[Value], lower: Bound<&Value>, upper: Bound<&Value>| {
+                let (start, end) = key_range(prefix, lower, upper, &orders);
+                let mut slots: Vec<usize> =
+                    tree.range(start.as_ref().map(Vec::as_slice), end.as_ref().map(Vec::as_slice)).unwrap().map(|entry| entry.unwrap().1.slot_id as usize).collect();
+                slots.sort();
+                slots
+            };
+            let expect = |predicate: &dyn Fn(&[Value]) -> bool| -> Vec<usize> { (0..rows.len()).filter(|&slot| predicate(&rows[slot])).collect() };
+            let int = |value: &Value| match value {
+                Value::Int(value) => Some(*value),
+                _ => None,
+            };
+
+            // a = 1 AND b > 5, a = 1 AND b >= 0, a = 2 AND b < 5, a = 2 AND b <= 5, a = 1 AND b BETWEEN 0 AND 5
+            let one = [Value::Int(1)];
+            let two = [Value::Int(2)];
+            assert_eq!(scan(&one, Bound::Excluded(&Value::Int(5)), Bound::Unbounded), expect(&|row| int(&row[0]) == Some(1) && int(&row[1]).is_some_and(|b| b > 5)));
+            assert_eq!(scan(&one, Bound::Included(&Value::Int(0)), Bound::Unbounded), expect(&|row| int(&row[0]) == Some(1) && int(&row[1]).is_some_and(|b| b >= 0)));
+            assert_eq!(scan(&two, Bound::Unbounded, Bound::Excluded(&Value::Int(5))), expect(&|row| int(&row[0]) == Some(2) && int(&row[1]).is_some_and(|b| b < 5)));
+            assert_eq!(scan(&two, Bound::Unbounded, Bound::Included(&Value::Int(5))), expect(&|row| int(&row[0]) == Some(2) && int(&row[1]).is_some_and(|b| b <= 5)));
+            assert_eq!(
+                scan(&one, Bound::Included(&Value::Int(0)), Bound::Included(&Value::Int(5))),
+                expect(&|row| int(&row[0]) == Some(1) && int(&row[1]).is_some_and(|b| (0..=5).contains(&b)))
+            );
+            // equality on the whole key, on a prefix, and a range on the first column
+            assert_eq!(scan(&[Value::Int(2), Value::Int(6)], Bound::Unbounded, Bound::Unbounded), expect(&|row| int(&row[0]) == Some(2) && int(&row[1]) == Some(6)));
+            assert_eq!(scan(&one, Bound::Unbounded, Bound::Unbounded), expect(&|row| int(&row[0]) == Some(1)));
+            assert_eq!(scan(&[], Bound::Excluded(&Value::Int(1)), Bound::Unbounded), expect(&|row| int(&row[0]).is_some_and(|a| a > 1)));
+            assert_eq!(scan(&[], Bound::Unbounded, Bound::Unbounded).len(), rows.len());
+        }
+    }
+}
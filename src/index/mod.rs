@@ -0,0 +1,11 @@
+mod btree;
+pub use btree::{BPlusTree, IndexError, MAX_KEY_SIZE, RangeScan};
+
+mod hash;
+pub use hash::HashIndex;
+
+mod key;
+pub use key::{KeyOrder, encode_key, encode_key_with, key_range};
+
+mod sort;
+pub use sort::{DEFAULT_SORT_MEMORY, EntrySorter};
@@ -0,0 +1,251 @@
+use super::btree::{Entry, IndexError, MAX_KEY_SIZE, write_entry};
+use crate::storage::RecordId;
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+use std::fs::{File, OpenOptions};
+use std::io::{BufReader, BufWriter, Read, Seek, SeekFrom, Write};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// memory an `EntrySorter` buffers entries in by default before spilling them
+pub const DEFAULT_SORT_MEMORY: usize = 16 * 1024 * 1024;
+
+/// bookkeeping bytes charged per buffered entry on top of its key (the `Vec` and the record id)
+const ENTRY_OVERHEAD: usize = 32;
+
+/// numbers the run files of every sorter in the process, so they never collide
+static NEXT_RUN: AtomicU64 = AtomicU64::new(0);
+
+/// Sorts index entries for `BPlusTree::bulk_load`, however many there are.
+///
+/// Entries are buffered in memory up to a limit. Each time the buffer fills up
+/// it is sorted and written out to a temporary file as a run, and the runs are
+/// merged when the entries are read back. Run files are deleted as soon as the
+/// sorter (or the tree it was handed to) is done with them.
+///
+/// # Examples
+///
+/// ```
+/// use gondor_rdbms::index::{BPlusTree, EntrySorter};
+/// use gondor_rdbms::storage::{BufferPool, DiskManager, RecordId};
+/// use gondor_rdbms::transaction::Transaction;
+/// use std::sync::{Arc, Mutex};
+///
+/// let dir = tempfile::tempdir().unwrap();
+/// let disk_manager = DiskManager::open(dir.path().join("example.db")).unwrap();
+/// let buffer_pool = Arc::new(Mutex::new(BufferPool::new(disk_manager)));
+///
+/// let mut sorter = EntrySorter::new();
+/// for (slot, name) in ["pippin", "frodo", "merry", "sam"].into_iter().enumerate() {
+///     sorter.push(name.as_bytes().to_vec(), RecordId::new(1, slot as u16)).unwrap();
+/// }
+/// let mut tree = BPlusTree::create(buffer_pool).unwrap();
+/// tree.bulk_load(&mut Transaction::new(1), sorter).unwrap();
+///
+/// let keys: Vec<Vec<u8>> = tree.scan().unwrap().map(|entry| entry.unwrap().0).collect();
+/// assert_eq!(keys, [b"frodo".to_vec(), b"merry".to_vec(), b"pippin".to_vec(), b"sam".to_vec()]);
+/// ```
+pub struct EntrySorter {
+    memory_limit: usize,
+    buffer: Vec<Entry>,
+    buffered_bytes: usize,
+    runs: Vec<Run>,
+}
+
+impl EntrySorter {
+    /// A sorter that buffers up to `DEFAULT_SORT_MEMORY` bytes of entries.
+    pub fn new() -> Self {
+        Self::with_memory_limit(DEFAULT_SORT_MEMORY)
+    }
+
+    /// A sorter that spills its entries to disk once they take up more than `memory_limit` bytes.
+    pub fn with_memory_limit(memory_limit: usize) -> Self {
+        Self { memory_limit, buffer: Vec::new(), buffered_bytes: 0, runs: Vec::new() }
+    }
+
+    pub fn push(&mut self, key: Vec<u8>, rid: RecordId) -> Result<(), IndexError> {
+        if key.len() > MAX_KEY_SIZE {
+            return Err(IndexError::KeyTooLarge);
+        }
+        self.buffered_bytes += key.len() + ENTRY_OVERHEAD;
+        self.buffer.push((key, rid));
+        if self.buffered_bytes > self.memory_limit {
+            self.spill()?;
+        }
+        Ok(())
+    }
+
+    /// number of runs written to disk so far
+    pub fn spilled_runs(&self) -> usize {
+        self.runs.len()
+    }
+
+    /// Every entry pushed, in order.
+    pub(super) fn finish(mut self) -> Result<SortedEntries, IndexError> {
+        self.buffer.sort_unstable();
+        let mut sources = Vec::with_capacity(self.runs.len() + 1);
+        sources.push(Source::Memory(std::mem::take(&mut self.buffer).into_iter()));
+        sources.extend(self.runs.drain(..).map(Source::Run));
+
+        // the smallest unread entry of every source
+        let mut heads = BinaryHeap::new();
+        for (index, source) in sources.iter_mut().enumerate() {
+            if let Some(entry) = source.next()? {
+                heads.push(Reverse((entry, index)));
+            }
+        }
+        Ok(SortedEntries { sources, heads })
+    }
+
+    /// writes the buffered entries out as a sorted run
+    fn spill(&mut self) -> Result<(), IndexError> {
+        self.buffer.sort_unstable();
+        let mut run = Run::create()?;
+        {
+            let mut writer = BufWriter::new(&run.file);
+            let mut bytes = Vec::new();
+            for entry in self.buffer.drain(..) {
+                bytes.clear();
+                write_entry(&mut bytes, &entry);
+                writer.write_all(&bytes)?;
+            }
+            writer.flush()?;
+        }
+        run.rewind()?;
+        self.runs.push(run);
+        self.buffered_bytes = 0;
+        Ok(())
+    }
+}
+
+impl Default for EntrySorter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// The entries of an `EntrySorter` in order, merged from memory and its runs.
+pub(super) struct SortedEntries {
+    sources: Vec<Source>,
+    heads: BinaryHeap<Reverse<(Entry, usize)>>,
+}
+
+impl Iterator for SortedEntries {
+    type Item = Result<Entry, IndexError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let Reverse((entry, index)) = self.heads.pop()?;
+        match self.sources[index].next() {
+            Ok(Some(next)) => self.heads.push(Reverse((next, index))),
+            Ok(None) => {}
+            Err(error) => {
+                self.heads.clear();
+                return Some(Err(error));
+            }
+        }
+        Some(Ok(entry))
+    }
+}
+
+enum Source {
+    Memory(std::vec::IntoIter<Entry>),
+    Run(Run),
+}
+
+impl Source {
+    fn next(&mut self) -> Result<Option<Entry>, IndexError> {
+        match self {
+            Source::Memory(entries) => Ok(entries.next()),
+            Source::Run(run) => run.next(),
+        }
+    }
+}
+
+/// A temporary file holding one sorted run, removed when dropped.
+///
+/// Entries are written back to back the way nodes store them: a u16 key
+/// length, the key and the record id.
+struct Run {
+    path: PathBuf,
+    file: File,
+    reader: Option<BufReader<File>>,
+}
+
+impl Run {
+    fn create() -> Result<Self, IndexError> {
+        let number = NEXT_RUN.fetch_add(1, Ordering::Relaxed);
+        let path = std::env::temp_dir().join(format!("gondor-sort-{}-{number}.run", std::process::id()));
+        let file = OpenOptions::new().read(true).write(true).create_new(true).open(&path)?;
+        Ok(Self { path, file, reader: None })
+    }
+
+    /// starts reading the run back from the beginning
+    fn rewind(&mut self) -> Result<(), IndexError> {
+        self.file.seek(SeekFrom::Start(0))?;
+        self.reader = Some(BufReader::new(self.file.try_clone()?));
+        Ok(())
+    }
+
+    fn next(&mut self) -> Result<Option<Entry>, IndexError> {
+        let reader = self.reader.as_mut().expect("runs are rewound before they're read");
+        let mut length = [0u8; 2];
+        match reader.read_exact(&mut length) {
+            Ok(()) => {}
+            Err(error) if error.kind() == std::io::ErrorKind::UnexpectedEof => return Ok(None),
+            Err(error) => return Err(error.into()),
+        }
+        let mut key = vec![0u8; u16::from_le_bytes(length) as usize];
+        reader.read_exact(&mut key)?;
+        let mut rid = [0u8; RecordId::SIZE];
+        reader.read_exact(&mut rid)?;
+        Ok(Some((key, RecordId::from_bytes(&rid).unwrap())))
+    }
+}
+
+impl Drop for Run {
+    fn drop(&mut self) {
+        // nothing to be done if it fails, the file is only left behind in the temp directory
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_spilled_runs_merge_in_order() {
+        let mut sorter = EntrySorter::with_memory_limit(4096);
+        let mut expected = Vec::new();
+        let mut state: u32 = 7;
+        for i in 0..5000u32 {
+            state = state.wrapping_mul(1103515245).wrapping_add(12345);
+            let key = format!("{:05}", state % 1000).into_bytes();
+            let rid = RecordId::new(i / 100, (i % 100) as u16);
+            expected.push((key.clone(), rid));
+            sorter.push(key, rid).unwrap();
+        }
+        assert!(sorter.spilled_runs() > 10);
+        let paths: Vec<PathBuf> = sorter.runs.iter().map(|run| run.path.clone()).collect();
+
+        expected.sort();
+        let sorted = sorter.finish().unwrap();
+        assert_eq!(sorted.collect::<Result<Vec<_>, _>>().unwrap(), expected);
+        // the run files are gone with the iterator
+        assert!(paths.iter().all(|path| !path.exists()));
+    }
+
+    #[test]
+    fn test_small_inputs_stay_in_memory() {
+        let mut sorter = EntrySorter::new();
+        assert_eq!(sorter.finish().unwrap().count(), 0);
+
+        sorter = EntrySorter::new();
+        sorter.push(b"b".to_vec(), RecordId::new(0, 1)).unwrap();
+        sorter.push(b"a".to_vec(), RecordId::new(0, 2)).unwrap();
+        assert!(matches!(sorter.push(vec![0; MAX_KEY_SIZE + 1], RecordId::new(0, 3)), Err(IndexError::KeyTooLarge)));
+        assert_eq!(sorter.spilled_runs(), 0);
+        let keys: Vec<Vec<u8>> = sorter.finish().unwrap().map(|entry| entry.unwrap().0).collect();
+        assert_eq!(keys, [b"a".to_vec(), b"b".to_vec()]);
+    }
+}
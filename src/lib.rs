@@ -1,4 +1,68 @@
 // ! Gondor RDBMS is a simple RDBMS implemented in Rust.
 // ! The storage module contains the implementation of the storage abstraction,
 // ! including things like pages.
-pub mod storage;
\ No newline at end of file
+pub mod storage;
+
+// ! The metrics module contains counters and gauges that subsystems register,
+// ! plus rendering in the Prometheus text format.
+pub mod metrics;
+
+// ! The wal module contains the write-ahead log: log records and the log manager
+// ! that makes them durable before the pages they describe.
+pub mod wal;
+
+// ! The transaction module contains transaction handles and the manager that
+// ! begins, commits and aborts them.
+pub mod transaction;
+
+// ! The recovery module replays and rolls back the write-ahead log after a crash.
+pub mod recovery;
+
+// ! The checkpoint module takes fuzzy checkpoints that bound recovery time and
+// ! let old log records be truncated.
+pub mod checkpoint;
+
+// ! The vacuum module reclaims the space deleted rows leave in heap pages and
+// ! records it in the free space map for inserts to reuse.
+pub mod vacuum;
+
+// ! The index module contains access methods that map keys to record ids,
+// ! a B+Tree for ordered scans and an extendible hash index for equality lookups,
+// ! and the order-preserving encoding of (multi-column) keys they store.
+pub mod index;
+
+// ! The types module contains the SQL data types and values, and the tuples
+// ! that encode rows of values for storage on pages.
+pub mod types;
+
+// ! The sql module contains the SQL front end: a lexer and a recursive-descent
+// ! parser that produce a typed AST.
+pub mod sql;
+
+// ! The catalog module keeps the definitions of every table in the database,
+// ! stored in pages so they survive restarts.
+pub mod catalog;
+
+// ! The planner module turns queries into logical plans, then picks the physical
+// ! operators to run them with (how each table is read, the join order) by their
+// ! estimated cost.
+pub mod planner;
+
+// ! The execution module runs parsed statements against the tables in the catalog.
+pub mod execution;
+
+// ! The database module is the embedding API: open a database file and run SQL
+// ! on connections to it.
+mod database;
+pub use database::{Connection, Database, DatabaseError, DatabaseOptions, Row, Rows, Statement, TransactionStatus};
+
+// ! The async_database module wraps the embedding API in async methods that run
+// ! on tokio's blocking pool, behind the `async` feature.
+#[cfg(feature = "async")]
+mod async_database;
+#[cfg(feature = "async")]
+pub use async_database::{AsyncConnection, AsyncDatabase, AsyncStatement};
+
+// ! The server module speaks the PostgreSQL wire protocol, so psql and Postgres
+// ! drivers can connect to a database over TCP.
+pub mod server;
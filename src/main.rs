@@ -1,15 +1,350 @@
-use gondor_rdbms::storage::Page;
+use gondor_rdbms::catalog::{IndexColumn, IndexKind, TableInfo};
+use gondor_rdbms::index::KeyOrder;
+use gondor_rdbms::execution::QueryResult;
+use gondor_rdbms::server::Server;
+use gondor_rdbms::sql::{self, ParseError, Token};
+use gondor_rdbms::types::Value;
+use gondor_rdbms::{Connection, Database};
+use rustyline::DefaultEditor;
+use rustyline::error::ReadlineError;
+use std::path::PathBuf;
+
+const HELP: &str = "\
+Enter SQL statements terminated by a semicolon. Statements can span lines.
+
+Meta-commands:
+  .tables           list the tables in the database
+  .schema [TABLE]   show the CREATE TABLE statement of every table, or just TABLE
+  .help             show this message
+  .quit             exit (so does Ctrl-D)";
+
+const USAGE: &str = "usage: gondor <database file> [--listen <address>]";
 
 fn main() {
-    let s1 = String::from("Hello and welcome to the Gondor RDBMS!");
-    let len = calculate_length(&s1);
-    println!("The length of '{s1}' is {len}.");
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let (path, listen) = match args.as_slice() {
+        [path] => (path, None),
+        [path, flag, address] if flag == "--listen" => (path, Some(address)),
+        _ => {
+            eprintln!("{USAGE}");
+            std::process::exit(2);
+        }
+    };
+    let database = match Database::open(path) {
+        Ok(database) => database,
+        Err(error) => {
+            eprintln!("cannot open {path}: {error}");
+            std::process::exit(1);
+        }
+    };
+
+    // serve clients instead of running the shell
+    if let Some(address) = listen {
+        serve(&database, address);
+        return;
+    }
+    let connection = database.connect();
+
+    let mut editor = match DefaultEditor::new() {
+        Ok(editor) => editor,
+        Err(error) => {
+            eprintln!("cannot start line editor: {error}");
+            std::process::exit(1);
+        }
+    };
+    let history = history_path();
+    if let Some(history) = &history {
+        // there's no history yet the first time round
+        let _ = editor.load_history(history);
+    }
+
+    println!("Gondor RDBMS, connected to {path}. Enter .help for help.");
+    let mut statement = String::new();
+    loop {
+        let prompt = if statement.is_empty() { "gondor> " } else { "   ...> " };
+        let line = match editor.readline(prompt) {
+            Ok(line) => line,
+            Err(ReadlineError::Interrupted) => {
+                // Ctrl-C drops the statement being typed
+                statement.clear();
+                continue;
+            }
+            Err(ReadlineError::Eof) => break,
+            Err(error) => {
+                eprintln!("error: {error}");
+                break;
+            }
+        };
+
+        if statement.is_empty() && line.trim_start().starts_with('.') {
+            let _ = editor.add_history_entry(line.as_str());
+            if !run_meta_command(&connection, line.trim()) {
+                break;
+            }
+            continue;
+        }
+
+        if !statement.is_empty() {
+            statement.push('\n');
+        }
+        statement.push_str(&line);
+        if is_complete(&statement) {
+            let _ = editor.add_history_entry(statement.as_str());
+            run_sql(&connection, &statement);
+            statement.clear();
+        } else if statement.trim().is_empty() {
+            statement.clear();
+        }
+    }
+
+    if let Some(history) = &history {
+        let _ = editor.save_history(history);
+    }
+}
+
+/// serves PostgreSQL clients on `address`, a port alone listening on every interface
+fn serve(database: &Database, address: &str) {
+    let address = match address.parse::<u16>() {
+        Ok(port) => format!("0.0.0.0:{port}"),
+        Err(_) => address.to_string(),
+    };
+    let server = match Server::bind(database, &address) {
+        Ok(server) => server,
+        Err(error) => {
+            eprintln!("cannot listen on {address}: {error}");
+            std::process::exit(1);
+        }
+    };
+    match server.local_addr() {
+        Ok(local) => println!("Gondor RDBMS, listening for PostgreSQL clients on {local}."),
+        Err(_) => println!("Gondor RDBMS, listening for PostgreSQL clients on {address}."),
+    }
+    server.run();
+}
+
+fn history_path() -> Option<PathBuf> {
+    std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".gondor_history"))
+}
+
+/// whether `sql` ends with a `;` outside of any string, so it's ready to run
+fn is_complete(sql: &str) -> bool {
+    match sql::tokenize(sql) {
+        Ok(tokens) => tokens.last() == Some(&Token::Semicolon),
+        // keep reading until the string is closed
+        Err(ParseError::UnterminatedString { .. }) => false,
+        // a statement that won't tokenize never will, run it to report the error
+        Err(_) => true,
+    }
+}
+
+fn run_sql(connection: &Connection, sql: &str) {
+    let statements = match sql::parse(sql) {
+        Ok(statements) => statements,
+        Err(error) => {
+            eprintln!("Parse error: {error}");
+            return;
+        }
+    };
+    for statement in statements {
+        match connection.run(&statement) {
+            Ok(QueryResult::Rows { columns, rows }) => print!("{}", render_table(&columns, &rows)),
+            Ok(_) if matches!(statement, sql::Statement::Transaction(_) | sql::Statement::Vacuum(_)) => println!("OK"),
+            Ok(QueryResult::Affected(count)) => println!("OK, {count} {}", if count == 1 { "row" } else { "rows" }),
+            Err(error) => {
+                // later statements may depend on this one, don't run them
+                eprintln!("Error: {error}");
+                return;
+            }
+        }
+    }
+}
+
+/// runs `.command`, returns false if the shell should exit
+fn run_meta_command(connection: &Connection, command: &str) -> bool {
+    let mut words = command.split_whitespace();
+    match (words.next(), words.next()) {
+        (Some(".quit" | ".exit"), _) => return false,
+        (Some(".help"), _) => println!("{HELP}"),
+        (Some(".tables"), _) => match connection.tables() {
+            Ok(tables) => {
+                for table in tables {
+                    println!("{}", table.name);
+                }
+            }
+            Err(error) => eprintln!("Error: {error}"),
+        },
+        (Some(".schema"), name) => match connection.tables() {
+            Ok(tables) => {
+                let mut tables = tables.iter().filter(|table| name.is_none_or(|name| table.name == name)).peekable();
+                if tables.peek().is_none() && let Some(name) = name {
+                    eprintln!("Error: no such table: {name}");
+                }
+                for table in tables {
+                    println!("{}", create_statement(table));
+                }
+            }
+            Err(error) => eprintln!("Error: {error}"),
+        },
+        _ => eprintln!("Unknown command {command}, enter .help for help"),
+    }
+    true
+}
+
+fn create_statement(table: &TableInfo) -> String {
+    let constraints = &table.constraints;
+    let mut elements: Vec<String> = table
+        .columns
+        .iter()
+        .enumerate()
+        .map(|(position, column)| {
+            let mut element = format!("{} {}", column.name, column.data_type);
+            if constraints.not_null.contains(&position) {
+                element.push_str(" NOT NULL");
+            }
+            if let Some(default) = constraints.default_of(position) {
+                element.push_str(&format!(" DEFAULT {default}"));
+            }
+            element
+        })
+        .collect();
+    let mut indexes = Vec::new();
+    for index in &table.indexes {
+        let name = |column: &IndexColumn| table.columns[column.position].name.as_str();
+        // constraints can't say how their columns sort, other orders need a CREATE INDEX
+        let constraint = match index.kind {
+            IndexKind::PrimaryKey => Some("PRIMARY KEY"),
+            IndexKind::Unique if index.columns.iter().all(|column| column.order == KeyOrder::ASC) => Some("UNIQUE"),
+            _ => None,
+        };
+        if let Some(constraint) = constraint {
+            let columns: Vec<&str> = index.columns.iter().map(name).collect();
+            elements.push(format!("{constraint} ({})", columns.join(", ")));
+            continue;
+        }
 
-    let page = Page::new(1);
+        let columns: Vec<String> = index
+            .columns
+            .iter()
+            .map(|column| {
+                let mut column_sql = name(column).to_string();
+                if column.order.descending {
+                    column_sql.push_str(" DESC");
+                }
+                // only spelled out when it isn't the default for the direction
+                if column.order.nulls_first != column.order.descending {
+                    column_sql.push_str(if column.order.nulls_first { " NULLS FIRST" } else { " NULLS LAST" });
+                }
+                column_sql
+            })
+            .collect();
+        let unique = if index.kind.is_unique() { "UNIQUE " } else { "" };
+        indexes.push(format!("\nCREATE {unique}INDEX {} ON {} ({});", index.name, table.name, columns.join(", ")));
+    }
+    // named, as they're kept, whether they were declared on a column or with one
+    elements.extend(constraints.checks.iter().map(|check| format!("CONSTRAINT {} CHECK ({})", check.name, check.expr)));
+    format!("CREATE TABLE {} ({});{}", table.name, elements.join(", "), indexes.concat())
 }
 
-fn calculate_length(s: &String) -> usize {
-    let length = s.len();
+/// lays out a query result as an aligned text table with a row count underneath
+fn render_table(columns: &[String], rows: &[Vec<Value>]) -> String {
+    let cells: Vec<Vec<String>> = rows.iter().map(|row| row.iter().map(Value::to_string).collect()).collect();
+    let widths: Vec<usize> = (0..columns.len())
+        .map(|i| cells.iter().map(|row| row[i].chars().count()).chain([columns[i].chars().count()]).max().unwrap())
+        .collect();
+
+    let format_line = |values: &[String]| {
+        let padded: Vec<String> = values.iter().zip(&widths).map(|(value, width)| format!(" {value:<width$} ")).collect();
+        padded.join("|").trim_end().to_string() + "\n"
+    };
+
+    let mut output = format_line(columns);
+    let rule: Vec<String> = widths.iter().map(|width| "-".repeat(width + 2)).collect();
+    output.push_str(&rule.join("+"));
+    output.push('\n');
+    for row in &cells {
+        output.push_str(&format_line(row));
+    }
+    output.push_str(&format!("({} {})\n", rows.len(), if rows.len() == 1 { "row" } else { "rows" }));
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use gondor_rdbms::catalog::{CheckConstraint, Constraints, IndexInfo};
+    use gondor_rdbms::types::{Column, DataType};
+
+    #[test]
+    fn test_is_complete() {
+        assert!(is_complete("SELECT 1;"));
+        assert!(is_complete("SELECT 1\n  FROM t;  "));
+        assert!(!is_complete("SELECT 1"));
+        assert!(!is_complete("SELECT 'a;"));
+        assert!(is_complete("SELECT 'a;';"));
+        assert!(is_complete("SELECT 1; -- trailing comment"));
+    }
+
+    #[test]
+    fn test_render_table() {
+        let columns = vec!["id".to_string(), "name".to_string()];
+        let rows = vec![
+            vec![Value::Int(1), Value::Varchar("Aragorn".into())],
+            vec![Value::Int(22), Value::Null],
+        ];
+        let expected = [" id | name", "----+---------", " 1  | Aragorn", " 22 | NULL", "(2 rows)", ""].join("\n");
+        assert_eq!(render_table(&columns, &rows), expected);
+    }
+
+    #[test]
+    fn test_create_statement() {
+        let mut table = TableInfo {
+            name: "rangers".into(),
+            columns: vec![Column::new("id", DataType::Int), Column::new("name", DataType::Varchar(Some(40)))],
+            root_page_id: 3,
+            indexes: Vec::new(),
+            statistics_page_id: None,
+            constraints: Constraints::default(),
+        };
+        assert_eq!(create_statement(&table), "CREATE TABLE rangers (id INT, name VARCHAR(40));");
+
+        table.indexes = vec![
+            IndexInfo { name: "rangers_pkey".into(), columns: vec![IndexColumn::new(0)], kind: IndexKind::PrimaryKey, root_page_id: 4 },
+            IndexInfo { name: "rangers_name_id_key".into(), columns: vec![IndexColumn::new(1), IndexColumn::new(0)], kind: IndexKind::Unique, root_page_id: 5 },
+        ];
+        assert_eq!(
+            create_statement(&table),
+            "CREATE TABLE rangers (id INT, name VARCHAR(40), PRIMARY KEY (id), UNIQUE (name, id));"
+        );
+
+        table.indexes = vec![
+            IndexInfo { name: "rangers_name_idx".into(), columns: vec![IndexColumn::new(1)], kind: IndexKind::NonUnique, root_page_id: 6 },
+            IndexInfo {
+                name: "rangers_id_name_idx".into(),
+                columns: vec![IndexColumn { position: 0, order: KeyOrder::DESC }, IndexColumn { position: 1, order: KeyOrder { nulls_first: true, ..KeyOrder::ASC } }],
+                kind: IndexKind::Unique,
+                root_page_id: 7,
+            },
+        ];
+        assert_eq!(
+            create_statement(&table),
+            [
+                "CREATE TABLE rangers (id INT, name VARCHAR(40));",
+                "CREATE INDEX rangers_name_idx ON rangers (name);",
+                "CREATE UNIQUE INDEX rangers_id_name_idx ON rangers (id DESC, name NULLS FIRST);",
+            ]
+            .join("\n")
+        );
 
-    length
+        table.indexes.clear();
+        let expr = |sql: &str| sql::parse_expr(sql).unwrap();
+        table.constraints = Constraints {
+            not_null: vec![1],
+            defaults: vec![(1, expr("'Strider'"))],
+            checks: vec![CheckConstraint { name: "rangers_id_check".into(), expr: expr("id > 0") }],
+        };
+        assert_eq!(
+            create_statement(&table),
+            "CREATE TABLE rangers (id INT, name VARCHAR(40) NOT NULL DEFAULT 'Strider', CONSTRAINT rangers_id_check CHECK ((id > 0)));"
+        );
+    }
 }
@@ -0,0 +1,276 @@
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::sync::atomic::{AtomicI64, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+
+/// A monotonically increasing counter, cheap to clone and share between threads.
+///
+/// Clones refer to the same underlying value, so a subsystem can keep its own
+/// handle while the same counter is registered with a `MetricsRegistry`.
+#[derive(Debug, Clone, Default)]
+pub struct Counter {
+    value: Arc<AtomicU64>,
+}
+
+impl Counter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn inc(&self) {
+        self.add(1);
+    }
+
+    pub fn add(&self, amount: u64) {
+        self.value.fetch_add(amount, Ordering::Relaxed);
+    }
+
+    pub fn get(&self) -> u64 {
+        self.value.load(Ordering::Relaxed)
+    }
+}
+
+/// A value that can go up and down, e.g. resident bytes or dirty page count.
+#[derive(Debug, Clone, Default)]
+pub struct Gauge {
+    value: Arc<AtomicI64>,
+}
+
+impl Gauge {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn set(&self, value: i64) {
+        self.value.store(value, Ordering::Relaxed);
+    }
+
+    pub fn add(&self, amount: i64) {
+        self.value.fetch_add(amount, Ordering::Relaxed);
+    }
+
+    pub fn sub(&self, amount: i64) {
+        self.value.fetch_sub(amount, Ordering::Relaxed);
+    }
+
+    pub fn get(&self) -> i64 {
+        self.value.load(Ordering::Relaxed)
+    }
+}
+
+#[derive(Debug, Clone)]
+enum Metric {
+    Counter(Counter),
+    Gauge(Gauge),
+}
+
+#[derive(Debug, Clone)]
+struct RegisteredMetric {
+    help: String,
+    metric: Metric,
+}
+
+/// Point-in-time value of a single registered metric.
+#[derive(Debug, Clone, PartialEq)]
+pub enum MetricValue {
+    Counter(u64),
+    Gauge(i64),
+}
+
+/// Named collection of counters and gauges.
+///
+/// Metric names follow the Prometheus conventions (`[a-zA-Z_:][a-zA-Z0-9_:]*`);
+/// registering a name twice returns the metric registered first so independent
+/// code paths can share a counter by name.
+///
+/// # Examples
+///
+/// ```
+/// use gondor_rdbms::metrics::{MetricsRegistry, render_prometheus};
+///
+/// let registry = MetricsRegistry::new();
+/// let queries = registry.counter("gondor_queries_total", "Queries executed");
+/// queries.inc();
+/// assert!(render_prometheus(&registry).contains("gondor_queries_total 1"));
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct MetricsRegistry {
+    metrics: Arc<Mutex<BTreeMap<String, RegisteredMetric>>>,
+}
+
+impl MetricsRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the counter registered under `name`, creating it if needed.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `name` is not a valid metric name or is already registered as a gauge.
+    pub fn counter(&self, name: &str, help: &str) -> Counter {
+        let counter = Counter::new();
+        self.register_counter(name, help, &counter)
+    }
+
+    /// Returns the gauge registered under `name`, creating it if needed.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `name` is not a valid metric name or is already registered as a counter.
+    pub fn gauge(&self, name: &str, help: &str) -> Gauge {
+        let gauge = Gauge::new();
+        self.register_gauge(name, help, &gauge)
+    }
+
+    /// Registers an existing counter under `name`, returning the handle that ends up
+    /// registered (the earlier one if the name was already taken).
+    pub fn register_counter(&self, name: &str, help: &str, counter: &Counter) -> Counter {
+        match self.register(name, help, Metric::Counter(counter.clone())) {
+            Metric::Counter(registered) => registered,
+            Metric::Gauge(_) => panic!("metric {name} is already registered as a gauge"),
+        }
+    }
+
+    /// Registers an existing gauge under `name`, returning the handle that ends up
+    /// registered (the earlier one if the name was already taken).
+    pub fn register_gauge(&self, name: &str, help: &str, gauge: &Gauge) -> Gauge {
+        match self.register(name, help, Metric::Gauge(gauge.clone())) {
+            Metric::Gauge(registered) => registered,
+            Metric::Counter(_) => panic!("metric {name} is already registered as a counter"),
+        }
+    }
+
+    /// Current value of every registered metric, keyed by name.
+    pub fn snapshot(&self) -> BTreeMap<String, MetricValue> {
+        let metrics = self.metrics.lock().unwrap();
+        metrics
+            .iter()
+            .map(|(name, registered)| {
+                let value = match &registered.metric {
+                    Metric::Counter(counter) => MetricValue::Counter(counter.get()),
+                    Metric::Gauge(gauge) => MetricValue::Gauge(gauge.get()),
+                };
+                (name.clone(), value)
+            })
+            .collect()
+    }
+
+    fn register(&self, name: &str, help: &str, metric: Metric) -> Metric {
+        assert!(is_valid_metric_name(name), "invalid metric name: {name}");
+
+        let mut metrics = self.metrics.lock().unwrap();
+        metrics
+            .entry(name.to_string())
+            .or_insert_with(|| RegisteredMetric {
+                help: help.to_string(),
+                metric,
+            })
+            .metric
+            .clone()
+    }
+}
+
+/// Renders every metric in `registry` in the Prometheus text exposition format.
+pub fn render_prometheus(registry: &MetricsRegistry) -> String {
+    let metrics = registry.metrics.lock().unwrap();
+    let mut output = String::new();
+
+    for (name, registered) in metrics.iter() {
+        // help text escapes backslashes and newlines per the exposition format
+        let help = registered.help.replace('\\', "\\\\").replace('\n', "\\n");
+        let _ = writeln!(output, "# HELP {name} {help}");
+        match &registered.metric {
+            Metric::Counter(counter) => {
+                let _ = writeln!(output, "# TYPE {name} counter");
+                let _ = writeln!(output, "{name} {}", counter.get());
+            }
+            Metric::Gauge(gauge) => {
+                let _ = writeln!(output, "# TYPE {name} gauge");
+                let _ = writeln!(output, "{name} {}", gauge.get());
+            }
+        }
+    }
+
+    output
+}
+
+fn is_valid_metric_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    match chars.next() {
+        Some(c) if c.is_ascii_alphabetic() || c == '_' || c == ':' => {}
+        _ => return false,
+    }
+    chars.all(|c| c.is_ascii_alphanumeric() || c == '_' || c == ':')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_counter_and_gauge_share_value_across_clones() {
+        let counter = Counter::new();
+        let clone = counter.clone();
+        clone.add(5);
+        counter.inc();
+        assert_eq!(counter.get(), 6);
+
+        let gauge = Gauge::new();
+        gauge.set(10);
+        gauge.clone().sub(3);
+        assert_eq!(gauge.get(), 7);
+    }
+
+    #[test]
+    fn test_registering_same_name_returns_existing_metric() {
+        let registry = MetricsRegistry::new();
+        let first = registry.counter("gondor_test_total", "first");
+        let second = registry.counter("gondor_test_total", "second");
+        first.inc();
+        second.inc();
+        assert_eq!(registry.snapshot()["gondor_test_total"], MetricValue::Counter(2));
+    }
+
+    #[test]
+    #[should_panic(expected = "already registered as a counter")]
+    fn test_kind_mismatch_panics() {
+        let registry = MetricsRegistry::new();
+        registry.counter("gondor_test_total", "a counter");
+        registry.gauge("gondor_test_total", "not a gauge");
+    }
+
+    #[test]
+    #[should_panic(expected = "invalid metric name")]
+    fn test_invalid_name_panics() {
+        MetricsRegistry::new().counter("9lives", "bad name");
+    }
+
+    #[test]
+    fn test_render_prometheus_line_syntax() {
+        let registry = MetricsRegistry::new();
+        registry.counter("gondor_reads_total", "Pages read\nfrom disk").add(3);
+        registry.gauge("gondor_resident_bytes", "Resident bytes").set(-2);
+
+        let output = render_prometheus(&registry);
+        assert_eq!(
+            output,
+            "# HELP gondor_reads_total Pages read\\nfrom disk\n\
+             # TYPE gondor_reads_total counter\n\
+             gondor_reads_total 3\n\
+             # HELP gondor_resident_bytes Resident bytes\n\
+             # TYPE gondor_resident_bytes gauge\n\
+             gondor_resident_bytes -2\n"
+        );
+
+        // every line is either a comment or `<name> <value>`
+        for line in output.lines() {
+            if line.starts_with("# HELP ") || line.starts_with("# TYPE ") {
+                continue;
+            }
+            let (name, value) = line.split_once(' ').expect("sample line should have a value");
+            assert!(is_valid_metric_name(name));
+            assert!(value.parse::<f64>().is_ok(), "value {value} should be numeric");
+        }
+    }
+}
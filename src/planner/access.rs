@@ -0,0 +1,222 @@
+use super::cost::{CPU_INDEX_TUPLE_COST, CPU_OPERATOR_COST, CPU_TUPLE_COST, RANDOM_PAGE_COST, Relation, SEQ_PAGE_COST, flipped};
+use super::join::{JoinGraph, conjunction};
+use super::physical::{PhysicalPlan, PlanNode};
+use crate::catalog::IndexInfo;
+use crate::execution::{Scope, evaluate};
+use crate::sql::{BinaryOp, Expr};
+use crate::types::{DataType, Value};
+use std::ops::Bound;
+
+/// entries per index node assumed when estimating how deep an index is
+const INDEX_FANOUT: f64 = 100.0;
+
+/// The cheapest way to read the rows of relation `r` of `graph` for which all
+/// of `conditions` hold: a sequential scan, or a scan of one of its indexes.
+pub(super) fn access_path(graph: &JoinGraph, r: usize, conditions: Vec<Expr>) -> PhysicalPlan {
+    let relation = &graph.relations[r];
+    let selectivity = graph.selectivity(&conditions);
+    let cost = SEQ_PAGE_COST * relation.pages + relation.rows * (CPU_TUPLE_COST + CPU_OPERATOR_COST * conditions.len() as f64);
+    let mut best = PhysicalPlan {
+        node: PlanNode::SeqScan {
+            table: relation.table.clone(),
+            alias: relation.alias.clone(),
+            columns: relation.columns.clone(),
+            filter: conjunction(conditions.clone()),
+        },
+        rows: (relation.rows * selectivity).max(1.0),
+        cost,
+    };
+    for index in &relation.table.indexes {
+        if let Some(plan) = index_scan(graph, r, index, &conditions)
+            && plan.cost < best.cost
+        {
+            best = plan;
+        }
+    }
+    best
+}
+
+/// Scan of `index` for the rows of relation `r` that `conditions` selects, if
+/// any of them limit which part of the index has to be read.
+///
+/// Equality conditions on the leading key columns make up a prefix of the
+/// keys to read, and range conditions on the column after that bound it.
+/// Whatever conditions the index can't check are left for a filter.
+fn index_scan(graph: &JoinGraph, r: usize, index: &IndexInfo, conditions: &[Expr]) -> Option<PhysicalPlan> {
+    let relation = &graph.relations[r];
+    let comparisons: Vec<Option<(usize, BinaryOp, Expr)>> = conditions.iter().map(|condition| comparison(relation, condition)).collect();
+    // the first comparison of the column at `position` with one of `ops`
+    let on_column = |position: usize, ops: &[BinaryOp]| {
+        comparisons.iter().enumerate().find_map(|(i, comparison)| match comparison {
+            Some((column, op, value)) if *column == position && ops.contains(op) => Some((i, *op, value.clone())),
+            _ => None,
+        })
+    };
+
+    let mut used = vec![false; conditions.len()];
+    let mut prefix = Vec::new();
+    let (mut lower, mut upper) = (Bound::Unbounded, Bound::Unbounded);
+    for column in &index.columns {
+        if let Some((i, _, value)) = on_column(column.position, &[BinaryOp::Eq]) {
+            prefix.push(value);
+            used[i] = true;
+            continue;
+        }
+        // the index is only read in one piece, so only the first bound on each side is used
+        if let Some((i, op, value)) = on_column(column.position, &[BinaryOp::Gt, BinaryOp::GtEq]) {
+            lower = if op == BinaryOp::Gt { Bound::Excluded(value) } else { Bound::Included(value) };
+            used[i] = true;
+        }
+        if let Some((i, op, value)) = on_column(column.position, &[BinaryOp::Lt, BinaryOp::LtEq]) {
+            upper = if op == BinaryOp::Lt { Bound::Excluded(value) } else { Bound::Included(value) };
+            used[i] = true;
+        }
+        break;
+    }
+    if !used.contains(&true) {
+        return None;
+    }
+
+    let picked = |index_checks: bool| -> Vec<Expr> {
+        conditions.iter().zip(&used).filter(|(_, used)| **used == index_checks).map(|(condition, _)| condition.clone()).collect()
+    };
+    let (index_conditions, residual) = (picked(true), picked(false));
+    let mut matched = relation.rows * graph.selectivity(&index_conditions);
+    if index.kind.is_unique() && prefix.len() == index.columns.len() {
+        matched = matched.min(1.0);
+    }
+    let rows = matched * graph.selectivity(&residual);
+
+    let cost = lookup_cost(relation, matched, residual.len());
+    let node = PlanNode::IndexScan {
+        table: relation.table.clone(),
+        alias: relation.alias.clone(),
+        columns: relation.columns.clone(),
+        index: index.clone(),
+        prefix,
+        lower,
+        upper,
+        filter: conjunction(residual),
+    };
+    Some(PhysicalPlan { node, rows: rows.max(1.0), cost })
+}
+
+/// The join of `outer`, the plan for the relations in `outer_set`, with
+/// relation `r` that looks up the rows of `r` for each outer row in one of its
+/// indexes, if some of `conditions` (the ones between them) require leading
+/// key columns of an index to equal values of the outer rows. The cheapest
+/// such join, estimated to produce `rows` rows.
+///
+/// The conditions on `r` alone filter the rows found, and the rest of the
+/// conditions between them are checked for each pair.
+pub(super) fn index_join(graph: &JoinGraph, outer: &PhysicalPlan, outer_set: u64, r: usize, conditions: &[Expr], rows: f64) -> Option<PhysicalPlan> {
+    let relation = &graph.relations[r];
+    let keys: Vec<Option<(Expr, usize)>> = conditions
+        .iter()
+        .map(|condition| {
+            let (outer_key, inner_key) = graph.hash_key(condition, outer_set, r)?;
+            Some((outer_key, graph.column(&inner_key)?.1))
+        })
+        .collect();
+    let local = graph.local_conditions(r);
+    let local_selectivity = graph.selectivity(&local);
+
+    let mut best: Option<PhysicalPlan> = None;
+    for index in &relation.table.indexes {
+        let mut used = vec![false; conditions.len()];
+        let mut outer_keys = Vec::new();
+        for column in &index.columns {
+            let Some(i) = keys.iter().position(|key| matches!(key, Some((_, position)) if *position == column.position)) else {
+                break;
+            };
+            outer_keys.push(keys[i].as_ref().unwrap().0.clone());
+            used[i] = true;
+        }
+        if outer_keys.is_empty() {
+            continue;
+        }
+
+        let picked = |index_checks: bool| -> Vec<Expr> {
+            conditions.iter().zip(&used).filter(|(_, used)| **used == index_checks).map(|(condition, _)| condition.clone()).collect()
+        };
+        let (index_conditions, residual) = (picked(true), picked(false));
+        // rows found by each lookup
+        let mut matched = relation.rows * graph.selectivity(&index_conditions);
+        if index.kind.is_unique() && outer_keys.len() == index.columns.len() {
+            matched = matched.min(1.0);
+        }
+        let cost = outer.cost
+            + outer.rows * lookup_cost(relation, matched, local.len())
+            + outer.rows * matched * local_selectivity * CPU_OPERATOR_COST * residual.len() as f64
+            + rows * CPU_TUPLE_COST;
+        if best.as_ref().is_none_or(|best| cost < best.cost) {
+            let node = PlanNode::IndexNestedLoopJoin {
+                outer: Box::new(outer.clone()),
+                table: relation.table.clone(),
+                alias: relation.alias.clone(),
+                columns: relation.columns.clone(),
+                index: index.clone(),
+                outer_keys,
+                filter: conjunction(local.clone()),
+                condition: conjunction(residual),
+            };
+            best = Some(PhysicalPlan { node, rows, cost });
+        }
+    }
+    best
+}
+
+/// Cost of finding `matched` rows of `relation` in one of its indexes and
+/// checking `conditions` conditions on each: every entry found costs a page
+/// read of its own, until every page of the table has been read.
+fn lookup_cost(relation: &Relation, matched: f64, conditions: usize) -> f64 {
+    let height = relation.rows.max(1.0).log(INDEX_FANOUT).ceil().max(1.0);
+    let pages = matched.ceil().min(relation.pages);
+    RANDOM_PAGE_COST * (height + pages) + matched * (CPU_INDEX_TUPLE_COST + CPU_TUPLE_COST + CPU_OPERATOR_COST * conditions as f64)
+}
+
+/// `condition` as (column position, operator, value) if it compares a column of
+/// `relation` with a literal that can be converted to the column's type, or
+/// with a parameter, with the column on the left
+fn comparison(relation: &Relation, condition: &Expr) -> Option<(usize, BinaryOp, Expr)> {
+    let Expr::Binary { left, op, right } = condition else {
+        return None;
+    };
+    let (column, op, value) = match (&**left, &**right) {
+        (column @ Expr::Column { .. }, value @ (Expr::Literal(_) | Expr::Parameter(_))) => (column, *op, value),
+        (value @ (Expr::Literal(_) | Expr::Parameter(_)), column @ Expr::Column { .. }) => (column, flipped(*op), value),
+        _ => return None,
+    };
+    if !matches!(op, BinaryOp::Eq | BinaryOp::Lt | BinaryOp::LtEq | BinaryOp::Gt | BinaryOp::GtEq) {
+        return None;
+    }
+    let Expr::Column { table, name } = column else { unreachable!() };
+    let position = relation.scope.resolve(table.as_deref(), name).ok()?;
+    // keys hold values of the column's type, comparisons with anything else are left to the filter.
+    // a parameter is bound to a value of the type of the column it's compared with
+    if let Expr::Literal(_) = value {
+        match (evaluate(value, &Scope::empty(), &[]).ok()?, relation.table.columns[position].data_type) {
+            (Value::Null, _) => return None,
+            (Value::Int(_), DataType::BigInt) => {}
+            (Value::BigInt(value), DataType::Int) => {
+                i32::try_from(value).ok()?;
+            }
+            (Value::Int(_) | Value::BigInt(_) | Value::Numeric(_), DataType::Numeric(_)) => {}
+            // a fraction would be rounded to the nearest integer
+            (Value::Numeric(value), DataType::Int) => {
+                i32::try_from(value.to_i64()?).ok()?;
+            }
+            (Value::Numeric(value), DataType::BigInt) => {
+                value.to_i64()?;
+            }
+            // dates and times written as strings, and dates as the timestamps of their midnights
+            (Value::Varchar(text), data_type @ (DataType::Date | DataType::Time | DataType::Timestamp | DataType::Interval)) => {
+                Value::parse(&text, data_type)?;
+            }
+            (Value::Date(_), DataType::Timestamp) => {}
+            (value, data_type) if value.fits(data_type) => {}
+            _ => return None,
+        }
+    }
+    Some((position, op, value.clone()))
+}
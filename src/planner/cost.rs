@@ -0,0 +1,349 @@
+use super::physical::{PhysicalPlan, PlanNode};
+use super::statistics::TableStatistics;
+use crate::catalog::TableInfo;
+use crate::execution::{MERGE_FAN_IN, Scope, compare, evaluate};
+use crate::sql::{BinaryOp, Expr, Literal, UnaryOp};
+use crate::types::temporal::MICROS_PER_DAY;
+use crate::types::{DataType, Value};
+use std::cmp::Ordering;
+
+// costs are in units of reading one page sequentially, and the relative prices
+// are PostgreSQL's defaults
+
+/// reading a page as part of a sequential scan
+pub const SEQ_PAGE_COST: f64 = 1.0;
+/// reading a page on its own, like an index scan does
+pub const RANDOM_PAGE_COST: f64 = 4.0;
+/// processing a row
+pub const CPU_TUPLE_COST: f64 = 0.01;
+/// processing an index entry
+pub const CPU_INDEX_TUPLE_COST: f64 = 0.005;
+/// evaluating an operator or condition once
+pub const CPU_OPERATOR_COST: f64 = 0.0025;
+
+/// selectivity of a condition nothing better is known about
+const DEFAULT_SELECTIVITY: f64 = 0.5;
+/// selectivity of `<`, `<=`, `>` and `>=`
+const DEFAULT_RANGE_SELECTIVITY: f64 = 1.0 / 3.0;
+/// selectivity of a lower and an upper bound on the same column together
+const DEFAULT_RANGE_PAIR_SELECTIVITY: f64 = 0.005;
+/// selectivity of `IS NULL` without statistics
+const DEFAULT_NULL_SELECTIVITY: f64 = 0.005;
+/// distinct values assumed in a column without statistics, unless the table has fewer rows
+pub(super) const DEFAULT_DISTINCT: f64 = 200.0;
+/// bytes a value of unlimited length is assumed to take
+const DEFAULT_VARCHAR_WIDTH: f64 = 32.0;
+/// bytes a row takes on a page besides its values: its slot and its header
+const ROW_OVERHEAD: f64 = 8.0;
+/// bytes a computed value is assumed to take
+const DEFAULT_VALUE_WIDTH: f64 = 8.0;
+
+/// A table a query reads, with what the planner knows about its size and contents.
+#[derive(Debug, Clone)]
+pub(super) struct Relation {
+    pub(super) table: TableInfo,
+    /// the name the query refers to the table by
+    pub(super) alias: String,
+    /// every column of the table, which positions in it refer to
+    pub(super) scope: Scope,
+    /// positions of the columns the query reads, all of them unless told otherwise
+    pub(super) columns: Vec<usize>,
+    pub(super) rows: f64,
+    pub(super) pages: f64,
+    pub(super) statistics: Option<TableStatistics>,
+}
+
+impl Relation {
+    /// `table` with `pages` heap pages now, sized by its statistics if it has any and
+    /// by how many rows of its width fit on those pages if not
+    pub(super) fn new(table: TableInfo, alias: String, pages: usize, page_size: usize, statistics: Option<TableStatistics>) -> Self {
+        let (rows, pages) = match &statistics {
+            Some(statistics) => (statistics.row_count as f64, statistics.page_count as f64),
+            None => {
+                let pages = pages as f64;
+                (pages * (page_size as f64 / row_width(&table)).floor(), pages)
+            }
+        };
+        let scope = Scope::aliased(&table, &alias);
+        let columns = (0..table.columns.len()).collect();
+        Self { table, alias, scope, columns, rows, pages, statistics }
+    }
+
+    /// bytes a row of the columns the query reads is assumed to take
+    pub(super) fn width(&self) -> f64 {
+        columns_width(&self.table, &self.columns)
+    }
+
+    /// estimated number of distinct non-NULL values in column `position`
+    pub(super) fn distinct(&self, position: usize) -> f64 {
+        if let Some(statistics) = &self.statistics {
+            return statistics.columns[position].distinct_count.max(1.0);
+        }
+        // a column that's unique on its own has a value per row
+        let unique = self.table.indexes.iter().any(|index| index.kind.is_unique() && index.columns.len() == 1 && index.columns[0].position == position);
+        if unique { self.rows.max(1.0) } else { self.rows.clamp(1.0, DEFAULT_DISTINCT) }
+    }
+
+    /// estimated fraction of the rows with a NULL in column `position`
+    pub(super) fn null_fraction(&self, position: usize) -> Option<f64> {
+        self.statistics.as_ref().map(|statistics| statistics.columns[position].null_fraction)
+    }
+
+    /// Estimated fraction of the non-NULL values in column `position` that are
+    /// less than `value`, or at most `value` if `inclusive`, if the column has a
+    /// histogram it can be compared with.
+    ///
+    /// Numbers are assumed to be spread evenly within a bucket, and anything
+    /// else to be in the middle of its bucket.
+    pub(super) fn fraction_below(&self, position: usize, value: &Value, inclusive: bool) -> Option<f64> {
+        let histogram = &self.statistics.as_ref()?.columns[position].histogram;
+        if histogram.len() < 2 || compare(&histogram[0], value).is_none() {
+            return None;
+        }
+        // a string compared with dates or times is one of them, see `compare`
+        let parsed;
+        let value = match (value, histogram[0].data_type()) {
+            (Value::Varchar(text), Some(data_type @ (DataType::Date | DataType::Time | DataType::Timestamp | DataType::Interval))) => {
+                parsed = Value::parse(text, data_type)?;
+                &parsed
+            }
+            _ => value,
+        };
+        let below = histogram.partition_point(|bound| match compare(bound, value) {
+            Some(Ordering::Less) => true,
+            Some(Ordering::Equal) => inclusive,
+            _ => false,
+        });
+        if below == 0 {
+            return Some(0.0);
+        }
+        if below == histogram.len() {
+            return Some(1.0);
+        }
+        let (low, high) = (&histogram[below - 1], &histogram[below]);
+        let within = match (numeric(low), numeric(high), numeric(value)) {
+            (Some(low), Some(high), Some(value)) if high > low => ((value - low) / (high - low)).clamp(0.0, 1.0),
+            _ => 0.5,
+        };
+        Some(((below - 1) as f64 + within) / (histogram.len() - 1) as f64)
+    }
+}
+
+/// bytes a row `plan` produces is assumed to take
+pub(super) fn plan_width(plan: &PhysicalPlan) -> f64 {
+    match &plan.node {
+        PlanNode::OneRow => ROW_OVERHEAD,
+        PlanNode::SeqScan { table, columns, .. } | PlanNode::IndexScan { table, columns, .. } => columns_width(table, columns),
+        PlanNode::Filter { input, .. } | PlanNode::Sort { input, .. } | PlanNode::TopN { input, .. } | PlanNode::Limit { input, .. } => plan_width(input),
+        PlanNode::NestedLoopJoin { outer, inner, .. } | PlanNode::HashJoin { outer, inner, .. } => plan_width(outer) + plan_width(inner),
+        PlanNode::IndexNestedLoopJoin { outer, table, columns, .. } => plan_width(outer) + columns_width(table, columns),
+        PlanNode::HashAggregate { group_by, aggregates, .. } => (group_by.len() + aggregates.len()) as f64 * DEFAULT_VALUE_WIDTH + ROW_OVERHEAD,
+        PlanNode::Project { exprs, .. } => exprs.len() as f64 * DEFAULT_VALUE_WIDTH + ROW_OVERHEAD,
+    }
+}
+
+/// Cost of sorting `rows` rows of `width` bytes each, like PostgreSQL takes it
+/// to be: two operators per comparison, for the `n log n` comparisons of a
+/// sort in memory. Rows beyond `work_memory` bytes are also written out in
+/// runs, then read and written again for every pass of merging them.
+pub(super) fn sort_cost(rows: f64, width: f64, work_memory: usize, page_size: usize) -> f64 {
+    let rows = rows.max(2.0);
+    let comparisons = 2.0 * CPU_OPERATOR_COST * rows * rows.log2();
+    let bytes = rows * width;
+    if bytes <= work_memory as f64 {
+        return comparisons;
+    }
+    let runs = (bytes / work_memory as f64).ceil();
+    let passes = runs.log(MERGE_FAN_IN as f64).ceil().max(1.0);
+    comparisons + 2.0 * SEQ_PAGE_COST * passes * bytes / page_size as f64
+}
+
+/// cost of keeping the first `count` of `rows` rows in a heap, which only ever
+/// compares a row with a logarithm of those
+pub(super) fn top_n_cost(rows: f64, count: u64) -> f64 {
+    2.0 * CPU_OPERATOR_COST * rows.max(1.0) * (2.0 * count.max(1) as f64).log2()
+}
+
+fn numeric(value: &Value) -> Option<f64> {
+    match value {
+        Value::Int(value) => Some(*value as f64),
+        Value::BigInt(value) => Some(*value as f64),
+        Value::Numeric(value) => Some(value.to_f64()),
+        // dates and times are spread out like numbers, for estimating a range of
+        // them; a date as its midnight, to be on the scale of timestamps
+        Value::Date(days) => Some(*days as f64 * MICROS_PER_DAY as f64),
+        Value::Time(micros) | Value::Timestamp(micros) => Some(*micros as f64),
+        Value::Interval(interval) => Some(interval.span() as f64),
+        _ => None,
+    }
+}
+
+/// bytes a row of `table` is assumed to take on a page
+fn row_width(table: &TableInfo) -> f64 {
+    columns_width(table, &(0..table.columns.len()).collect::<Vec<_>>())
+}
+
+/// bytes the values of `table`'s columns at `positions` are assumed to take in a row
+fn columns_width(table: &TableInfo, positions: &[usize]) -> f64 {
+    let values: f64 = positions
+        .iter()
+        .map(|&position| match table.columns[position].data_type {
+            DataType::Int => 4.0,
+            DataType::BigInt => 8.0,
+            DataType::Bool => 1.0,
+            DataType::Numeric(_) => 17.0,
+            DataType::Date => 4.0,
+            DataType::Time | DataType::Timestamp => 8.0,
+            DataType::Interval => 16.0,
+            DataType::Varchar(Some(length)) => (length as f64).min(DEFAULT_VARCHAR_WIDTH),
+            DataType::Varchar(None) => DEFAULT_VARCHAR_WIDTH,
+        })
+        .sum();
+    values + ROW_OVERHEAD
+}
+
+/// Estimates the fraction of rows for which `expr` holds.
+///
+/// `column` finds the relation a column reference belongs to, and the
+/// column's position in it.
+pub(super) fn selectivity(expr: &Expr, relations: &[Relation], column: &dyn Fn(&Expr) -> Option<(usize, usize)>) -> f64 {
+    let estimate = match expr {
+        Expr::Literal(Literal::Boolean(value)) => if *value { 1.0 } else { 0.0 },
+        Expr::Literal(Literal::Null) => 0.0,
+        Expr::Unary { op: UnaryOp::Not, expr } => 1.0 - selectivity(expr, relations, column),
+        Expr::Binary { left, op: BinaryOp::And, right } => selectivity(left, relations, column) * selectivity(right, relations, column),
+        Expr::Binary { left, op: BinaryOp::Or, right } => {
+            let (left, right) = (selectivity(left, relations, column), selectivity(right, relations, column));
+            left + right - left * right
+        }
+        Expr::Binary { left, op: BinaryOp::Eq, right } => equality(left, right, relations, column),
+        Expr::Binary { left, op: BinaryOp::NotEq, right } => {
+            let nulls = [left, right].iter().filter_map(|side| column(side)).map(|(relation, position)| relations[relation].null_fraction(position).unwrap_or(0.0)).fold(0.0, f64::max);
+            1.0 - equality(left, right, relations, column) - nulls
+        }
+        Expr::Binary { op: BinaryOp::Lt | BinaryOp::LtEq | BinaryOp::Gt | BinaryOp::GtEq, .. } => {
+            range_selectivity(expr, relations, column).unwrap_or(DEFAULT_RANGE_SELECTIVITY)
+        }
+        Expr::IsNull { expr, negated } => {
+            let nulls = column(expr).map_or(DEFAULT_NULL_SELECTIVITY, |(relation, position)| {
+                relations[relation].null_fraction(position).unwrap_or(DEFAULT_NULL_SELECTIVITY)
+            });
+            if *negated { 1.0 - nulls } else { nulls }
+        }
+        _ => DEFAULT_SELECTIVITY,
+    };
+    estimate.clamp(0.0, 1.0)
+}
+
+/// Estimates the fraction of rows for which all of `conditions` hold, taking
+/// them to be independent of each other.
+///
+/// Except that a lower and an upper bound on the same column select the range
+/// between them rather than what's left of the rows each would select on its
+/// own. Without a histogram to tell how wide that range is, it's assumed to
+/// be narrow.
+pub(super) fn conjunction_selectivity(conditions: &[Expr], relations: &[Relation], column: &dyn Fn(&Expr) -> Option<(usize, usize)>) -> f64 {
+    // the first lower and upper bound on each column, as positions in `conditions`
+    let mut bounds: Vec<(_, [Option<usize>; 2])> = Vec::new();
+    for (i, condition) in conditions.iter().enumerate() {
+        let Some((bounded, is_lower)) = range_bound(condition, column) else {
+            continue;
+        };
+        let position = bounds.iter().position(|(other, _)| *other == bounded).unwrap_or_else(|| {
+            bounds.push((bounded, [None, None]));
+            bounds.len() - 1
+        });
+        bounds[position].1[if is_lower { 0 } else { 1 }].get_or_insert(i);
+    }
+
+    let mut paired = vec![false; conditions.len()];
+    let mut estimate = 1.0;
+    for ((relation, position), [lower, upper]) in bounds {
+        let (Some(lower), Some(upper)) = (lower, upper) else {
+            continue;
+        };
+        paired[lower] = true;
+        paired[upper] = true;
+        let non_null = 1.0 - relations[relation].null_fraction(position).unwrap_or(0.0);
+        estimate *= match (range_selectivity(&conditions[lower], relations, column), range_selectivity(&conditions[upper], relations, column)) {
+            // the rows above the lower bound that aren't above the upper bound as well
+            (Some(lower), Some(upper)) => (lower + upper - non_null).max(0.0),
+            _ => DEFAULT_RANGE_PAIR_SELECTIVITY,
+        };
+    }
+    for (condition, _) in conditions.iter().zip(&paired).filter(|(_, paired)| !**paired) {
+        estimate *= selectivity(condition, relations, column);
+    }
+    estimate.clamp(0.0, 1.0)
+}
+
+/// the column `condition` compares with a literal, and whether it's a lower bound
+/// on the column (rather than an upper one)
+fn range_bound(condition: &Expr, column: &dyn Fn(&Expr) -> Option<(usize, usize)>) -> Option<((usize, usize), bool)> {
+    let Expr::Binary { left, op, right } = condition else {
+        return None;
+    };
+    let (bounded, op) = match (&**left, &**right) {
+        (left, Expr::Literal(_)) => (column(left)?, *op),
+        (Expr::Literal(_), right) => (column(right)?, flipped(*op)),
+        _ => return None,
+    };
+    match op {
+        BinaryOp::Gt | BinaryOp::GtEq => Some((bounded, true)),
+        BinaryOp::Lt | BinaryOp::LtEq => Some((bounded, false)),
+        _ => None,
+    }
+}
+
+/// selectivity of the comparison `condition` of a column with a literal, if
+/// the column has a histogram to estimate it from
+fn range_selectivity(condition: &Expr, relations: &[Relation], column: &dyn Fn(&Expr) -> Option<(usize, usize)>) -> Option<f64> {
+    let Expr::Binary { left, op, right } = condition else {
+        return None;
+    };
+    let ((relation, position), op, literal) = match (&**left, &**right) {
+        (left, literal @ Expr::Literal(_)) => (column(left)?, *op, literal),
+        (literal @ Expr::Literal(_), right) => (column(right)?, flipped(*op), literal),
+        _ => return None,
+    };
+    let value = evaluate(literal, &Scope::empty(), &[]).ok()?;
+    if value.is_null() {
+        return Some(0.0);
+    }
+    let relation = &relations[relation];
+    let non_null = 1.0 - relation.null_fraction(position)?;
+    let fraction = match op {
+        BinaryOp::Lt => relation.fraction_below(position, &value, false)?,
+        BinaryOp::LtEq => relation.fraction_below(position, &value, true)?,
+        BinaryOp::Gt => 1.0 - relation.fraction_below(position, &value, true)?,
+        BinaryOp::GtEq => 1.0 - relation.fraction_below(position, &value, false)?,
+        _ => return None,
+    };
+    Some(fraction * non_null)
+}
+
+/// the operator that gives the same result with its operands swapped
+pub(super) fn flipped(op: BinaryOp) -> BinaryOp {
+    match op {
+        BinaryOp::Lt => BinaryOp::Gt,
+        BinaryOp::LtEq => BinaryOp::GtEq,
+        BinaryOp::Gt => BinaryOp::Lt,
+        BinaryOp::GtEq => BinaryOp::LtEq,
+        op => op,
+    }
+}
+
+/// selectivity of `left = right`: the chance of a row having one particular value,
+/// or of two rows agreeing on their values when both sides are columns
+fn equality(left: &Expr, right: &Expr, relations: &[Relation], column: &dyn Fn(&Expr) -> Option<(usize, usize)>) -> f64 {
+    if matches!(left, Expr::Literal(Literal::Null)) || matches!(right, Expr::Literal(Literal::Null)) {
+        return 0.0;
+    }
+    let non_null = |(relation, position): (usize, usize)| 1.0 - relations[relation].null_fraction(position).unwrap_or(0.0);
+    let distinct = |(relation, position): (usize, usize)| relations[relation].distinct(position);
+    match (column(left), column(right)) {
+        (Some(left), Some(right)) => non_null(left) * non_null(right) / distinct(left).max(distinct(right)),
+        (Some(side), None) | (None, Some(side)) => non_null(side) / distinct(side),
+        (None, None) => 1.0 / DEFAULT_DISTINCT,
+    }
+}
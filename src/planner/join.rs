@@ -0,0 +1,254 @@
+use super::access::{access_path, index_join};
+use super::cost::{CPU_OPERATOR_COST, CPU_TUPLE_COST, DEFAULT_DISTINCT, Relation, SEQ_PAGE_COST, conjunction_selectivity};
+use super::logical::column_references;
+use super::physical::{PhysicalPlan, PlanNode};
+use crate::execution::Scope;
+use crate::sql::{BinaryOp, Expr};
+use crate::types::DataType;
+
+/// joins of up to this many tables are planned by comparing every join order,
+/// larger ones by adding one table at a time
+const EXHAUSTIVE_JOIN_LIMIT: usize = 10;
+
+/// A condition of a query and the relations it refers to, as a set of bits.
+struct Conjunct {
+    expr: Expr,
+    relations: u64,
+}
+
+/// The tables a query joins and the conditions between them, from which the
+/// planner picks a join order.
+///
+/// Every condition is checked as early as possible: one on a single relation
+/// by its scan, and one on several by the first join that brings them
+/// together. Conditions on no relation at all go with the first one.
+pub(super) struct JoinGraph {
+    pub(super) relations: Vec<Relation>,
+    conjuncts: Vec<Conjunct>,
+    /// the columns of every relation, in order
+    scope: Scope,
+    /// position of the first column of each relation in `scope`
+    offsets: Vec<usize>,
+    /// bytes of rows a hash join can keep in memory
+    work_memory: usize,
+    page_size: usize,
+}
+
+impl JoinGraph {
+    /// `relations` (at most 64 of them) and the conditions that must hold between them,
+    /// to be joined with `work_memory` bytes of memory per join
+    pub(super) fn new(relations: Vec<Relation>, conditions: Vec<Expr>, work_memory: usize, page_size: usize) -> Self {
+        let mut scope = Scope::empty();
+        let mut offsets = Vec::new();
+        for relation in &relations {
+            offsets.push(scope.columns().len());
+            scope = scope.join(&relation.scope);
+        }
+        let mut graph = Self { relations, conjuncts: Vec::new(), scope, offsets, work_memory, page_size };
+        graph.conjuncts = conditions
+            .into_iter()
+            .map(|expr| {
+                let relations = column_references(&expr).into_iter().filter_map(|column| graph.column(column)).fold(0, |set, (r, _)| set | 1 << r);
+                Conjunct { expr, relations }
+            })
+            .collect();
+        graph
+    }
+
+    /// the relation the column reference `expr` is to, and the column's position in it
+    pub(super) fn column(&self, expr: &Expr) -> Option<(usize, usize)> {
+        let Expr::Column { table, name } = expr else {
+            return None;
+        };
+        let position = self.scope.resolve(table.as_deref(), name).ok()?;
+        let r = self.offsets.partition_point(|&offset| offset <= position) - 1;
+        Some((r, position - self.offsets[r]))
+    }
+
+    /// estimated fraction of rows for which all of `conditions` hold
+    pub(super) fn selectivity(&self, conditions: &[Expr]) -> f64 {
+        conjunction_selectivity(conditions, &self.relations, &|column| self.column(column))
+    }
+
+    /// Estimated number of distinct values of `exprs` among `rows` joined rows:
+    /// the product of the number of values of each, but no more than the rows.
+    /// Anything but a column is taken to have as many values as a column
+    /// without statistics.
+    pub(super) fn group_count(&self, exprs: &[Expr], rows: f64) -> f64 {
+        let product: f64 = exprs
+            .iter()
+            .map(|expr| match self.column(expr) {
+                Some((r, position)) => self.relations[r].distinct(position),
+                None => DEFAULT_DISTINCT,
+            })
+            .product();
+        product.clamp(1.0, rows.max(1.0))
+    }
+
+    /// The cheapest plan found for joining all of the relations.
+    ///
+    /// Plans are left-deep: each join adds a single relation as the inner side
+    /// to the join of those before it.
+    pub(super) fn plan(&self) -> PhysicalPlan {
+        let access: Vec<PhysicalPlan> = (0..self.relations.len()).map(|r| access_path(self, r, self.local_conditions(r))).collect();
+        if access.len() == 1 {
+            return access.into_iter().next().unwrap();
+        }
+        if access.len() <= EXHAUSTIVE_JOIN_LIMIT { self.exhaustive(&access) } else { self.greedy(&access) }
+    }
+
+    /// best plan for every set of relations, built up from the best plans for the sets one smaller
+    fn exhaustive(&self, access: &[PhysicalPlan]) -> PhysicalPlan {
+        let all = (1usize << access.len()) - 1;
+        let mut best: Vec<Option<PhysicalPlan>> = vec![None; all + 1];
+        for (r, plan) in access.iter().enumerate() {
+            best[1 << r] = Some(plan.clone());
+        }
+        for set in 1..=all {
+            if set.count_ones() < 2 {
+                continue;
+            }
+            for (r, inner) in access.iter().enumerate().filter(|(r, _)| set & 1 << r != 0) {
+                let outer_set = set & !(1 << r);
+                let candidate = self.join(best[outer_set].as_ref().unwrap(), outer_set as u64, r, inner);
+                if best[set].as_ref().is_none_or(|plan| candidate.cost < plan.cost) {
+                    best[set] = Some(candidate);
+                }
+            }
+        }
+        best[all].take().unwrap()
+    }
+
+    /// starts from the smallest relation and keeps adding whichever is cheapest to join next
+    fn greedy(&self, access: &[PhysicalPlan]) -> PhysicalPlan {
+        let first = (0..access.len()).min_by(|&a, &b| access[a].rows.total_cmp(&access[b].rows)).unwrap();
+        let mut plan = access[first].clone();
+        let mut set = 1u64 << first;
+        for _ in 1..access.len() {
+            let (r, next) = (0..access.len())
+                .filter(|r| set & 1 << r == 0)
+                .map(|r| (r, self.join(&plan, set, r, &access[r])))
+                .min_by(|(_, a), (_, b)| a.cost.total_cmp(&b.cost))
+                .unwrap();
+            plan = next;
+            set |= 1 << r;
+        }
+        plan
+    }
+
+    /// `outer`, the plan for the relations in `outer_set`, joined with `inner`,
+    /// the plan for relation `r`, by whichever join is cheapest
+    fn join(&self, outer: &PhysicalPlan, outer_set: u64, r: usize, inner: &PhysicalPlan) -> PhysicalPlan {
+        let inner_set = 1 << r;
+        let conditions: Vec<Expr> = self
+            .conjuncts
+            .iter()
+            .filter(|conjunct| conjunct.relations & inner_set != 0 && conjunct.relations != inner_set && conjunct.relations & !(outer_set | inner_set) == 0)
+            .map(|conjunct| conjunct.expr.clone())
+            .collect();
+        let selectivity = self.selectivity(&conditions);
+
+        let pairs = outer.rows * inner.rows;
+        let rows = (pairs * selectivity).max(1.0);
+        // the inner rows are kept in memory, and every pair is tried
+        let cost = outer.cost
+            + inner.cost
+            + inner.rows * CPU_TUPLE_COST
+            + pairs * CPU_OPERATOR_COST * conditions.len().max(1) as f64
+            + rows * CPU_TUPLE_COST;
+
+        let node = PlanNode::NestedLoopJoin { outer: Box::new(outer.clone()), inner: Box::new(inner.clone()), condition: conjunction(conditions.clone()) };
+        let mut best = PhysicalPlan { node, rows, cost };
+
+        let (keys, residual): (Vec<Expr>, Vec<Expr>) = conditions.iter().cloned().partition(|condition| self.hash_key(condition, outer_set, r).is_some());
+        if !keys.is_empty() {
+            // the inner rows are hashed, each outer row looks up the ones with its key,
+            // and the rest of the conditions are checked for those
+            let matches = pairs * self.selectivity(&keys);
+            let key_cost = CPU_OPERATOR_COST * keys.len() as f64;
+            let mut hash_cost = outer.cost
+                + inner.cost
+                + inner.rows * (CPU_TUPLE_COST + key_cost)
+                + outer.rows * key_cost
+                + matches * CPU_OPERATOR_COST * residual.len() as f64
+                + rows * CPU_TUPLE_COST;
+            // inner rows that don't fit in memory are written out in partitions and read back,
+            // with the outer rows split up alike
+            let inner_bytes = inner.rows * self.relations[r].width();
+            if inner_bytes > self.work_memory as f64 {
+                let outer_width: f64 = (0..self.relations.len()).filter(|i| outer_set & 1 << i != 0).map(|i| self.relations[i].width()).sum();
+                hash_cost += 2.0 * SEQ_PAGE_COST * (inner_bytes + outer.rows * outer_width) / self.page_size as f64;
+            }
+            if hash_cost < best.cost {
+                let (outer_keys, inner_keys) = keys.iter().map(|key| self.hash_key(key, outer_set, r).unwrap()).unzip();
+                let node = PlanNode::HashJoin {
+                    outer: Box::new(outer.clone()),
+                    inner: Box::new(inner.clone()),
+                    outer_keys,
+                    inner_keys,
+                    condition: conjunction(residual),
+                };
+                best = PhysicalPlan { node, rows, cost: hash_cost };
+            }
+        }
+        // or the inner rows aren't read at all, and each outer row looks its own up in an index
+        if let Some(plan) = index_join(self, outer, outer_set, r, &conditions, rows)
+            && plan.cost < best.cost
+        {
+            best = plan;
+        }
+        best
+    }
+
+    /// The column of the outer side and the column of relation `r` that `condition`
+    /// requires to be equal, if it's such a condition and their values can be
+    /// hashed alike.
+    pub(super) fn hash_key(&self, condition: &Expr, outer_set: u64, r: usize) -> Option<(Expr, Expr)> {
+        let Expr::Binary { left, op: BinaryOp::Eq, right } = condition else {
+            return None;
+        };
+        let ((left_r, left_position), (right_r, right_position)) = (self.column(left)?, self.column(right)?);
+        let data_type = |r: usize, position: usize| self.relations[r].table.columns[position].data_type;
+        let numeric = |data_type| matches!(data_type, DataType::Int | DataType::BigInt | DataType::Numeric(_));
+        let (left_type, right_type) = (data_type(left_r, left_position), data_type(right_r, right_position));
+        let comparable = match (left_type, right_type) {
+            (DataType::Varchar(_), DataType::Varchar(_)) => true,
+            (left_type, right_type) => left_type == right_type || (numeric(left_type) && numeric(right_type)),
+        };
+        if !comparable {
+            return None;
+        }
+        if right_r == r && outer_set & 1 << left_r != 0 {
+            Some((left.as_ref().clone(), right.as_ref().clone()))
+        } else if left_r == r && outer_set & 1 << right_r != 0 {
+            Some((right.as_ref().clone(), left.as_ref().clone()))
+        } else {
+            None
+        }
+    }
+
+    /// the conditions the scan of relation `r` checks
+    pub(super) fn local_conditions(&self, r: usize) -> Vec<Expr> {
+        self.conjuncts
+            .iter()
+            .filter(|conjunct| conjunct.relations == 1 << r || (conjunct.relations == 0 && r == 0))
+            .map(|conjunct| conjunct.expr.clone())
+            .collect()
+    }
+}
+
+/// adds the operands of the `AND`s at the top of `expr` to `conjuncts`
+pub(super) fn split_conjuncts(expr: &Expr, conjuncts: &mut Vec<Expr>) {
+    match expr {
+        Expr::Binary { left, op: BinaryOp::And, right } => {
+            split_conjuncts(left, conjuncts);
+            split_conjuncts(right, conjuncts);
+        }
+        expr => conjuncts.push(expr.clone()),
+    }
+}
+
+/// `conditions` joined with `AND`, `None` if there are none
+pub(super) fn conjunction(conditions: Vec<Expr>) -> Option<Expr> {
+    conditions.into_iter().reduce(|left, right| Expr::binary(left, BinaryOp::And, right))
+}
@@ -0,0 +1,272 @@
+use super::physical::SortKey;
+use crate::catalog::{Catalog, TableInfo};
+use crate::execution::{ExecutionError, Scope, ScopeColumn};
+use crate::index::KeyOrder;
+use crate::sql::{Expr, Literal, OrderByItem, Select, SelectItem};
+
+/// A query as a tree of relational operators, in the shape it was written: the
+/// tables of the `FROM` clause joined left to right, filtered by the `WHERE`
+/// clause, grouped and filtered by the `HAVING` clause if it aggregates,
+/// sorted, cut down to the rows `LIMIT` and `OFFSET` ask for, then projected.
+///
+/// Building one binds the query to the catalog, so every table it names exists
+/// and every column reference is to exactly one column. Above an `Aggregate`,
+/// the aggregate functions and grouped expressions a query uses are replaced
+/// with references to the columns of the aggregate's rows.
+#[derive(Debug, Clone, PartialEq)]
+pub enum LogicalPlan {
+    /// a single row without columns, what a `SELECT` without a `FROM` reads
+    OneRow,
+    /// the columns at positions `columns` of the rows of `table` for which `filter` holds
+    Scan { table: TableInfo, alias: String, columns: Vec<usize>, filter: Option<Expr> },
+    Filter { input: Box<LogicalPlan>, predicate: Expr },
+    /// the rows of `left` each followed by a row of `right`, every pair for which the condition holds
+    Join { left: Box<LogicalPlan>, right: Box<LogicalPlan>, condition: Option<Expr> },
+    /// A row for each distinct value of `group_by` among the rows of `input`,
+    /// with those values followed by the value of each of `aggregates` over
+    /// the rows that have them. A single row over all of them without
+    /// `group_by`, even when there are none.
+    Aggregate { input: Box<LogicalPlan>, group_by: Vec<Expr>, aggregates: Vec<Expr> },
+    /// the rows of `input` ordered by `keys`, the first key first
+    Sort { input: Box<LogicalPlan>, keys: Vec<SortKey> },
+    /// the rows of `input` after the first `offset`, no more than `limit` of them
+    Limit { input: Box<LogicalPlan>, limit: Option<u64>, offset: u64 },
+    /// `exprs` evaluated for each row of `input`, as the columns `names`
+    Project { input: Box<LogicalPlan>, exprs: Vec<Expr>, names: Vec<String> },
+}
+
+impl LogicalPlan {
+    pub fn from_select(catalog: &Catalog, select: &Select) -> Result<Self, ExecutionError> {
+        let mut plan = LogicalPlan::OneRow;
+        let mut references: Vec<&str> = Vec::new();
+        for table_ref in &select.from {
+            let table = catalog.table(&table_ref.name)?.ok_or_else(|| ExecutionError::TableNotFound(table_ref.name.clone()))?;
+            if references.contains(&table_ref.reference()) {
+                return Err(ExecutionError::DuplicateTable(table_ref.reference().to_string()));
+            }
+            references.push(table_ref.reference());
+
+            let columns = (0..table.columns.len()).collect();
+            let scan = LogicalPlan::Scan { table, alias: table_ref.reference().to_string(), columns, filter: None };
+            plan = match plan {
+                LogicalPlan::OneRow => scan,
+                left => {
+                    let join = LogicalPlan::Join { left: Box::new(left), right: Box::new(scan), condition: table_ref.on.clone() };
+                    // the condition can only see the tables joined so far
+                    if let Some(condition) = &table_ref.on {
+                        check_columns(condition, &join.scope())?;
+                        check_no_aggregates(condition, "JOIN conditions")?;
+                    }
+                    join
+                }
+            };
+        }
+
+        if let Some(predicate) = &select.where_clause {
+            check_columns(predicate, &plan.scope())?;
+            check_no_aggregates(predicate, "WHERE")?;
+            plan = LogicalPlan::Filter { input: Box::new(plan), predicate: predicate.clone() };
+        }
+
+        let table_columns = plan.scope();
+        let mut items = Vec::new();
+        for item in &select.projection {
+            match item {
+                SelectItem::Wildcard if select.from.is_empty() => return Err(ExecutionError::WildcardWithoutTable),
+                SelectItem::Wildcard => {
+                    // every column of every table, qualified so they stay apart
+                    for column in table_columns.columns() {
+                        items.push((Expr::Column { table: column.table.clone(), name: column.name.clone() }, column.name.clone()));
+                    }
+                }
+                SelectItem::Expr { expr, alias } => {
+                    // the alias if there is one, else the column or function name or the expression itself
+                    let name = match (alias, expr) {
+                        (Some(alias), _) => alias.clone(),
+                        (None, Expr::Column { name, .. }) => name.clone(),
+                        (None, Expr::Aggregate { function, .. }) => function.name().to_string(),
+                        (None, Expr::Function { function, .. }) => function.name().to_string(),
+                        (None, expr) => expr.to_string(),
+                    };
+                    items.push((expr.clone(), name));
+                }
+            }
+        }
+
+        let order_by = select.order_by.iter().map(|item| order_by_key(item, &items)).collect::<Result<Vec<_>, _>>()?;
+        let mut aggregates = Vec::new();
+        for expr in items.iter().map(|(expr, _)| expr).chain(&select.having).chain(order_by.iter().map(|key| &key.expr)) {
+            collect_aggregates(expr, &mut aggregates);
+        }
+        let grouped = !select.group_by.is_empty() || !aggregates.is_empty() || select.having.is_some();
+        let (exprs, keys) = if grouped {
+            for expr in &select.group_by {
+                check_columns(expr, &table_columns)?;
+                check_no_aggregates(expr, "GROUP BY")?;
+            }
+            for aggregate in &aggregates {
+                let Expr::Aggregate { arg: Some(arg), .. } = aggregate else {
+                    continue;
+                };
+                check_columns(arg, &table_columns)?;
+                check_no_aggregates(arg, "the arguments of aggregate functions")?;
+            }
+            let group_by = select.group_by.clone();
+            plan = LogicalPlan::Aggregate { input: Box::new(plan), group_by: group_by.clone(), aggregates };
+            let groups = plan.scope();
+            // an expression of the groups, which can only use a column of the tables through them
+            let bind = |expr: &Expr| {
+                let expr = grouped_expr(expr, &group_by);
+                for column in column_references(&expr) {
+                    let Expr::Column { table, name } = column else { unreachable!() };
+                    if let Err(error) = groups.resolve(table.as_deref(), name) {
+                        // a column of the tables that isn't grouped, rather than one that doesn't exist
+                        return Err(match error {
+                            ExecutionError::ColumnNotFound(full_name) if table_columns.resolve(table.as_deref(), name).is_ok() => ExecutionError::UngroupedColumn(full_name),
+                            error => error,
+                        });
+                    }
+                }
+                Ok(expr)
+            };
+            if let Some(having) = &select.having {
+                plan = LogicalPlan::Filter { input: Box::new(plan), predicate: bind(having)? };
+            }
+            let exprs = items.iter().map(|(expr, _)| bind(expr)).collect::<Result<Vec<_>, _>>()?;
+            let keys = order_by.into_iter().map(|key| Ok(SortKey { expr: bind(&key.expr)?, order: key.order })).collect::<Result<Vec<_>, ExecutionError>>()?;
+            (exprs, keys)
+        } else {
+            for expr in items.iter().map(|(expr, _)| expr).chain(order_by.iter().map(|key| &key.expr)) {
+                check_columns(expr, &table_columns)?;
+            }
+            (items.iter().map(|(expr, _)| expr.clone()).collect(), order_by)
+        };
+
+        // sorted and limited before the projection, so the keys can be any expression of the rows
+        if !keys.is_empty() {
+            plan = LogicalPlan::Sort { input: Box::new(plan), keys };
+        }
+        if select.limit.is_some() || select.offset > 0 {
+            plan = LogicalPlan::Limit { input: Box::new(plan), limit: select.limit, offset: select.offset };
+        }
+        let names = items.into_iter().map(|(_, name)| name).collect();
+        Ok(LogicalPlan::Project { input: Box::new(plan), exprs, names })
+    }
+
+    /// the columns of the rows this plan produces
+    pub fn scope(&self) -> Scope {
+        match self {
+            LogicalPlan::OneRow => Scope::empty(),
+            LogicalPlan::Scan { table, alias, columns, .. } => Scope::aliased_columns(table, alias, columns),
+            LogicalPlan::Filter { input, .. } | LogicalPlan::Sort { input, .. } | LogicalPlan::Limit { input, .. } => input.scope(),
+            LogicalPlan::Join { left, right, .. } => left.scope().join(&right.scope()),
+            LogicalPlan::Aggregate { input, group_by, aggregates } => aggregate_scope(&input.scope(), group_by, aggregates),
+            LogicalPlan::Project { names, .. } => Scope::named(names.iter().cloned()),
+        }
+    }
+}
+
+/// The key `item` of an `ORDER BY` clause sorts by, given the expressions the
+/// query selects and their names.
+///
+/// Like in PostgreSQL, a number on its own is the position of a selected
+/// expression and a bare name is that of one if any has it, before either is
+/// taken to be a column of the tables.
+fn order_by_key(item: &OrderByItem, items: &[(Expr, String)]) -> Result<SortKey, ExecutionError> {
+    let expr = match &item.expr {
+        Expr::Literal(Literal::Integer(position)) => match usize::try_from(*position).ok().and_then(|position| items.get(position.wrapping_sub(1))) {
+            Some((expr, _)) => expr.clone(),
+            None => return Err(ExecutionError::InvalidOrderByPosition(*position)),
+        },
+        Expr::Column { table: None, name } => items.iter().find(|(_, item_name)| item_name == name).map_or_else(|| item.expr.clone(), |(expr, _)| expr.clone()),
+        expr => expr.clone(),
+    };
+    let order = KeyOrder { descending: item.descending, nulls_first: item.nulls_first.unwrap_or(item.descending) };
+    Ok(SortKey { expr, order })
+}
+
+/// The columns of the rows an aggregate of rows of `input` produces.
+///
+/// A column it groups by keeps its name and table, so references to it read
+/// the same after grouping as before. Every other key and aggregate is named
+/// after its expression, the way `grouped_expr` refers to them.
+pub(super) fn aggregate_scope(input: &Scope, group_by: &[Expr], aggregates: &[Expr]) -> Scope {
+    let columns = group_by
+        .iter()
+        .map(|expr| match expr {
+            Expr::Column { table, name } => {
+                let position = input.resolve(table.as_deref(), name).expect("group keys are bound to the input");
+                input.columns()[position].clone()
+            }
+            expr => ScopeColumn { table: None, name: expr.to_string() },
+        })
+        .chain(aggregates.iter().map(|aggregate| ScopeColumn { table: None, name: aggregate.to_string() }))
+        .collect();
+    Scope::new(columns)
+}
+
+/// `expr` with the group keys and aggregates in it replaced by references to
+/// the columns `aggregate_scope` gives them
+fn grouped_expr(expr: &Expr, group_by: &[Expr]) -> Expr {
+    if let Some(key) = group_by.iter().find(|key| *key == expr) {
+        return match key {
+            Expr::Column { .. } => key.clone(),
+            key => Expr::Column { table: None, name: key.to_string() },
+        };
+    }
+    match expr {
+        Expr::Literal(_) | Expr::Column { .. } | Expr::Parameter(_) => expr.clone(),
+        Expr::Aggregate { .. } => Expr::Column { table: None, name: expr.to_string() },
+        Expr::Unary { op, expr } => Expr::Unary { op: *op, expr: Box::new(grouped_expr(expr, group_by)) },
+        Expr::Binary { left, op, right } => Expr::binary(grouped_expr(left, group_by), *op, grouped_expr(right, group_by)),
+        Expr::IsNull { expr, negated } => Expr::IsNull { expr: Box::new(grouped_expr(expr, group_by)), negated: *negated },
+        Expr::Function { function, args } => Expr::Function { function: *function, args: args.iter().map(|arg| grouped_expr(arg, group_by)).collect() },
+    }
+}
+
+/// adds the aggregate functions `expr` calls to `aggregates`, unless they're there already
+fn collect_aggregates(expr: &Expr, aggregates: &mut Vec<Expr>) {
+    match expr {
+        Expr::Literal(_) | Expr::Column { .. } | Expr::Parameter(_) => {}
+        Expr::Aggregate { .. } if !aggregates.contains(expr) => aggregates.push(expr.clone()),
+        Expr::Aggregate { .. } => {}
+        Expr::Unary { expr, .. } | Expr::IsNull { expr, .. } => collect_aggregates(expr, aggregates),
+        Expr::Binary { left, right, .. } => {
+            collect_aggregates(left, aggregates);
+            collect_aggregates(right, aggregates);
+        }
+        Expr::Function { args, .. } => args.iter().for_each(|arg| collect_aggregates(arg, aggregates)),
+    }
+}
+
+/// fails if `expr`, part of `clause`, calls an aggregate function
+pub(super) fn check_no_aggregates(expr: &Expr, clause: &'static str) -> Result<(), ExecutionError> {
+    let mut aggregates = Vec::new();
+    collect_aggregates(expr, &mut aggregates);
+    if aggregates.is_empty() { Ok(()) } else { Err(ExecutionError::AggregateNotAllowed(clause)) }
+}
+
+/// checks that every column `expr` refers to is exactly one column of `scope`
+pub(super) fn check_columns(expr: &Expr, scope: &Scope) -> Result<(), ExecutionError> {
+    for column in column_references(expr) {
+        let Expr::Column { table, name } = column else { unreachable!() };
+        scope.resolve(table.as_deref(), name)?;
+    }
+    Ok(())
+}
+
+/// every `Expr::Column` in `expr`
+pub(crate) fn column_references(expr: &Expr) -> Vec<&Expr> {
+    match expr {
+        Expr::Literal(_) | Expr::Parameter(_) => Vec::new(),
+        Expr::Column { .. } => vec![expr],
+        Expr::Unary { expr, .. } | Expr::IsNull { expr, .. } => column_references(expr),
+        Expr::Aggregate { arg, .. } => arg.as_deref().map(column_references).unwrap_or_default(),
+        Expr::Binary { left, right, .. } => {
+            let mut columns = column_references(left);
+            columns.extend(column_references(right));
+            columns
+        }
+        Expr::Function { args, .. } => args.iter().flat_map(column_references).collect(),
+    }
+}
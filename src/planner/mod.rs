@@ -0,0 +1,631 @@
+mod access;
+
+mod cost;
+pub use cost::{CPU_INDEX_TUPLE_COST, CPU_OPERATOR_COST, CPU_TUPLE_COST, RANDOM_PAGE_COST, SEQ_PAGE_COST};
+use cost::{Relation, plan_width, selectivity, sort_cost, top_n_cost};
+
+mod join;
+use join::{JoinGraph, conjunction, split_conjuncts};
+
+mod logical;
+pub use logical::LogicalPlan;
+pub(crate) use logical::column_references;
+use logical::check_columns;
+
+mod physical;
+pub use physical::{PhysicalPlan, PlanNode, SortKey};
+pub(crate) use physical::expr_type;
+
+mod rewrite;
+use rewrite::rewrite;
+
+mod statistics;
+pub use statistics::{ColumnStatistics, HISTOGRAM_BUCKETS, NoStatistics, SAMPLE_ROWS, StatisticsStore, TableStatistics};
+
+use crate::catalog::{Catalog, TableInfo};
+use crate::execution::{DEFAULT_WORK_MEMORY, ExecutionError};
+use crate::sql::{Expr, Select};
+use crate::storage::{BufferPool, HeapFile};
+use std::sync::{Arc, Mutex};
+
+/// most tables a single query can read
+pub const MAX_JOIN_TABLES: usize = 64;
+
+/// Turns queries into plans for the executor to run, picking among the ways
+/// to run them by estimated cost.
+///
+/// A `SELECT` is bound to the catalog as a `LogicalPlan` first. Its `WHERE`,
+/// `ON` and `HAVING` conditions are then split at their `AND`s, and each one is
+/// pushed down to be checked as early as it can be, even before grouping if it
+/// only uses columns grouped by. Each table is read for just the columns the
+/// rest of the query uses, and the others are never decoded. A condition on a single table filters the scan of that
+/// table, where it may also let an index scan read just the rows it selects
+/// instead of the whole heap file. A condition on several tables is checked
+/// by the first join that has all of them. Every order of joining up to ten
+/// tables is considered, and more than that are joined one at a time,
+/// cheapest first. Each join either tries every pair of rows from its two
+/// sides, hashes the inner rows on the columns the conditions require to be
+/// equal to columns of the outer ones, or looks those columns up in an index
+/// of the inner table for each outer row, whichever is cheapest. A query that
+/// groups or aggregates its rows hashes them by their group keys, expecting a
+/// group for each combination of the keys' distinct values. Its rows are then
+/// sorted for `ORDER BY`, except that when `LIMIT` only wants the first few
+/// of them those are picked out with a heap instead.
+///
+/// Costs are estimated from the number of rows and pages of each table, and
+/// the fraction of rows each condition is expected to select. Those come from
+/// a `StatisticsStore`; a table it knows nothing about is assumed to be as
+/// full as its heap pages can hold, and conditions on it to be as selective
+/// as PostgreSQL assumes them to be by default.
+///
+/// # Examples
+///
+/// ```
+/// use gondor_rdbms::catalog::Catalog;
+/// use gondor_rdbms::execution::Executor;
+/// use gondor_rdbms::planner::{ColumnStatistics, PlanNode, Planner, TableStatistics};
+/// use gondor_rdbms::sql::{parse_statement, Statement};
+/// use gondor_rdbms::storage::{BufferPool, DiskManager};
+/// use gondor_rdbms::transaction::Transaction;
+/// use std::collections::HashMap;
+/// use std::sync::{Arc, Mutex};
+///
+/// let dir = tempfile::tempdir().unwrap();
+/// let disk_manager = DiskManager::open(dir.path().join("example.db")).unwrap();
+/// let buffer_pool = Arc::new(Mutex::new(BufferPool::new(disk_manager)));
+/// let mut catalog = Catalog::open(buffer_pool.clone()).unwrap();
+/// let create = parse_statement("CREATE TABLE users (id INT PRIMARY KEY, name VARCHAR)").unwrap();
+/// Executor::new(buffer_pool.clone(), &mut catalog).execute(&mut Transaction::new(1), &create).unwrap();
+///
+/// // a million users, which one id picks out of
+/// let column = ColumnStatistics::new(1e6);
+/// let users = TableStatistics { row_count: 1_000_000, page_count: 20_000, columns: vec![column.clone(), column] };
+/// let statistics = HashMap::from([("users".to_string(), users)]);
+///
+/// let planner = Planner::new(&catalog, buffer_pool, &statistics);
+/// let Statement::Select(select) = parse_statement("SELECT name FROM users WHERE id = 42").unwrap() else { unreachable!() };
+/// let plan = planner.plan_select(&select).unwrap();
+/// let PlanNode::Project { input, .. } = plan.node else { unreachable!() };
+/// assert!(matches!(input.node, PlanNode::IndexScan { .. }));
+/// assert_eq!(input.rows, 1.0);
+/// ```
+pub struct Planner<'a> {
+    catalog: &'a Catalog,
+    buffer_pool: Arc<Mutex<BufferPool>>,
+    statistics: &'a dyn StatisticsStore,
+    /// bytes of rows the executor keeps in memory before spilling them to disk
+    work_memory: usize,
+}
+
+impl<'a> Planner<'a> {
+    pub fn new(catalog: &'a Catalog, buffer_pool: Arc<Mutex<BufferPool>>, statistics: &'a dyn StatisticsStore) -> Self {
+        Self { catalog, buffer_pool, statistics, work_memory: DEFAULT_WORK_MEMORY }
+    }
+
+    /// Plans for an executor that keeps up to `bytes` of rows in memory per
+    /// operator, rather than `DEFAULT_WORK_MEMORY`, so hash joins expected to
+    /// spill to disk cost what doing so does.
+    pub fn with_work_memory(mut self, bytes: usize) -> Self {
+        self.work_memory = bytes;
+        self
+    }
+
+    pub fn plan_select(&self, select: &Select) -> Result<PhysicalPlan, ExecutionError> {
+        self.optimize(&rewrite(LogicalPlan::from_select(self.catalog, select)?))
+    }
+
+    /// Plans reading the rows of `table` that `predicate` holds for, all of
+    /// them without one, the way an `UPDATE` or `DELETE` finds its rows.
+    pub fn plan_scan(&self, table: &TableInfo, predicate: Option<&Expr>) -> Result<PhysicalPlan, ExecutionError> {
+        let relation = self.relation(table.clone(), table.name.clone())?;
+        let mut conditions = Vec::new();
+        if let Some(predicate) = predicate {
+            check_columns(predicate, &relation.scope)?;
+            split_conjuncts(predicate, &mut conditions);
+        }
+        Ok(self.join_graph(vec![relation], conditions).plan())
+    }
+
+    /// Picks the cheapest physical plan for a logical plan built by `LogicalPlan::from_select`.
+    pub fn optimize(&self, plan: &LogicalPlan) -> Result<PhysicalPlan, ExecutionError> {
+        match plan {
+            LogicalPlan::Project { input, exprs, names } => {
+                let input = self.optimize(input)?;
+                let cost = input.cost + input.rows * CPU_OPERATOR_COST * exprs.len() as f64;
+                let rows = input.rows;
+                let node = PlanNode::Project { input: Box::new(input), exprs: exprs.clone(), names: names.clone() };
+                Ok(PhysicalPlan { node, rows, cost })
+            }
+            // a HAVING clause, which can only be checked once the rows are grouped
+            LogicalPlan::Filter { input, predicate } if matches!(**input, LogicalPlan::Aggregate { .. }) => {
+                let input = self.optimize(input)?;
+                let rows = (input.rows * selectivity(predicate, &[], &|_| None)).max(1.0);
+                let cost = input.cost + input.rows * CPU_OPERATOR_COST;
+                Ok(PhysicalPlan { node: PlanNode::Filter { input: Box::new(input), predicate: predicate.clone() }, rows, cost })
+            }
+            LogicalPlan::Aggregate { input, group_by, aggregates } => {
+                let (input, graph) = self.plan_joins(input)?;
+                let rows = match &graph {
+                    Some(graph) if !group_by.is_empty() => graph.group_count(group_by, input.rows),
+                    _ => 1.0,
+                };
+                // every row is hashed by its key and added to each aggregate of its group
+                let cost = input.cost + input.rows * CPU_OPERATOR_COST * (group_by.len() + aggregates.len()) as f64 + rows * CPU_TUPLE_COST;
+                let node = PlanNode::HashAggregate { input: Box::new(input), group_by: group_by.clone(), aggregates: aggregates.clone() };
+                Ok(PhysicalPlan { node, rows, cost })
+            }
+            LogicalPlan::Sort { input, keys } => Ok(self.sort(self.optimize(input)?, keys)),
+            LogicalPlan::Limit { input, limit, offset } => {
+                let input = match (&**input, limit) {
+                    // just the first rows of a sort, which a heap can keep if there are few enough of them
+                    (LogicalPlan::Sort { input, keys }, Some(limit)) => {
+                        let input = self.optimize(input)?;
+                        let count = limit.saturating_add(*offset);
+                        let fits = count as f64 * plan_width(&input) <= self.work_memory as f64;
+                        let cost = input.cost + top_n_cost(input.rows, count);
+                        let sort = self.sort(input.clone(), keys);
+                        if fits && cost < sort.cost {
+                            let rows = input.rows.min(count as f64);
+                            PhysicalPlan { node: PlanNode::TopN { input: Box::new(input), keys: keys.clone(), count }, rows, cost }
+                        } else {
+                            sort
+                        }
+                    }
+                    (input, _) => self.optimize(input)?,
+                };
+                // the rows it doesn't hand out aren't taken off the cost of producing them
+                let rows = (input.rows - *offset as f64).min(limit.map_or(f64::INFINITY, |limit| limit as f64)).max(1.0);
+                let cost = input.cost;
+                Ok(PhysicalPlan { node: PlanNode::Limit { input: Box::new(input), limit: *limit, offset: *offset }, rows, cost })
+            }
+            plan => Ok(self.plan_joins(plan)?.0),
+        }
+    }
+
+    /// `input` sorted by `keys`
+    fn sort(&self, input: PhysicalPlan, keys: &[SortKey]) -> PhysicalPlan {
+        let page_size = self.buffer_pool.lock().unwrap().page_size();
+        let cost = input.cost + sort_cost(input.rows, plan_width(&input), self.work_memory, page_size);
+        let rows = input.rows;
+        PhysicalPlan { node: PlanNode::Sort { input: Box::new(input), keys: keys.to_vec() }, rows, cost }
+    }
+
+    /// The cheapest plan for a tree of scans, joins and filters, and the join
+    /// graph it was picked from if it reads any tables.
+    fn plan_joins(&self, plan: &LogicalPlan) -> Result<(PhysicalPlan, Option<JoinGraph>), ExecutionError> {
+        let mut tables = Vec::new();
+        let mut conditions = Vec::new();
+        flatten(plan, &mut tables, &mut conditions);
+        if tables.is_empty() {
+            let one_row = PhysicalPlan { node: PlanNode::OneRow, rows: 1.0, cost: 0.0 };
+            let Some(predicate) = conjunction(conditions) else {
+                return Ok((one_row, None));
+            };
+            let rows = selectivity(&predicate, &[], &|_| None);
+            return Ok((PhysicalPlan { node: PlanNode::Filter { input: Box::new(one_row), predicate }, rows, cost: CPU_OPERATOR_COST }, None));
+        }
+        if tables.len() > MAX_JOIN_TABLES {
+            return Err(ExecutionError::TooManyTables);
+        }
+        let mut relations = Vec::new();
+        for (table, alias, columns) in tables {
+            relations.push(Relation { columns, ..self.relation(table, alias)? });
+        }
+        let graph = self.join_graph(relations, conditions);
+        Ok((graph.plan(), Some(graph)))
+    }
+
+    fn join_graph(&self, relations: Vec<Relation>, conditions: Vec<Expr>) -> JoinGraph {
+        let page_size = self.buffer_pool.lock().unwrap().page_size();
+        JoinGraph::new(relations, conditions, self.work_memory, page_size)
+    }
+
+    /// `table`, read as `alias`, with its current size and its statistics
+    fn relation(&self, table: TableInfo, alias: String) -> Result<Relation, ExecutionError> {
+        let pages = HeapFile::open(self.buffer_pool.clone(), table.root_page_id)?.page_ids().len();
+        let page_size = self.buffer_pool.lock().unwrap().page_size();
+        let statistics = self.statistics.table_statistics(&table.name)?;
+        Ok(Relation::new(table, alias, pages, page_size, statistics))
+    }
+}
+
+/// collects the tables a tree of scans, joins and filters reads, with the columns
+/// it reads of each, and every condition in it
+fn flatten(plan: &LogicalPlan, tables: &mut Vec<(TableInfo, String, Vec<usize>)>, conditions: &mut Vec<Expr>) {
+    match plan {
+        LogicalPlan::OneRow => {}
+        LogicalPlan::Scan { table, alias, columns, filter } => {
+            tables.push((table.clone(), alias.clone(), columns.clone()));
+            if let Some(filter) = filter {
+                split_conjuncts(filter, conditions);
+            }
+        }
+        LogicalPlan::Filter { input, predicate } => {
+            flatten(input, tables, conditions);
+            split_conjuncts(predicate, conditions);
+        }
+        LogicalPlan::Join { left, right, condition } => {
+            flatten(left, tables, conditions);
+            flatten(right, tables, conditions);
+            if let Some(condition) = condition {
+                split_conjuncts(condition, conditions);
+            }
+        }
+        LogicalPlan::Aggregate { .. } | LogicalPlan::Sort { .. } | LogicalPlan::Limit { .. } | LogicalPlan::Project { .. } => {
+            unreachable!("only joins, scans and filters are below an aggregate, a sort or a limit")
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::execution::Executor;
+    use crate::sql::{Literal, Statement, parse_statement};
+    use crate::storage::DiskManager;
+    use crate::transaction::Transaction;
+    use crate::types::DataType;
+    use std::collections::HashMap;
+    use std::ops::Bound;
+    use tempfile::NamedTempFile;
+
+    struct Fixture {
+        buffer_pool: Arc<Mutex<BufferPool>>,
+        catalog: Catalog,
+        statistics: HashMap<String, TableStatistics>,
+        _file: NamedTempFile,
+    }
+
+    impl Fixture {
+        fn new(sql: &[&str]) -> Self {
+            let file = NamedTempFile::new().unwrap();
+            let buffer_pool = Arc::new(Mutex::new(BufferPool::new(DiskManager::open(file.path()).unwrap())));
+            let mut catalog = Catalog::open(buffer_pool.clone()).unwrap();
+            let mut executor = Executor::new(buffer_pool.clone(), &mut catalog);
+            for sql in sql {
+                executor.execute(&mut Transaction::new(1), &parse_statement(sql).unwrap()).unwrap();
+            }
+            Self { buffer_pool, catalog, statistics: HashMap::new(), _file: file }
+        }
+
+        /// claims `table` has `rows` rows, with `distinct` values in each column
+        fn analyzed(mut self, table: &str, rows: u64, distinct: &[f64]) -> Self {
+            let columns = distinct.iter().map(|&distinct_count| ColumnStatistics::new(distinct_count)).collect();
+            self.statistics.insert(table.to_string(), TableStatistics { row_count: rows, page_count: rows / 50, columns });
+            self
+        }
+
+        /// the plan below the projection of `sql`
+        fn plan(&self, sql: &str) -> PhysicalPlan {
+            let Statement::Select(select) = parse_statement(sql).unwrap() else {
+                panic!("expected a select");
+            };
+            let planner = Planner::new(&self.catalog, self.buffer_pool.clone(), &self.statistics);
+            let PlanNode::Project { input, .. } = planner.plan_select(&select).unwrap().node else {
+                panic!("expected a projection");
+            };
+            *input
+        }
+    }
+
+    /// the aliases of the relations a plan scans, in the order it joins them
+    fn join_order(plan: &PhysicalPlan) -> Vec<String> {
+        match &plan.node {
+            PlanNode::SeqScan { alias, .. } | PlanNode::IndexScan { alias, .. } => vec![alias.clone()],
+            PlanNode::NestedLoopJoin { outer, inner, .. } | PlanNode::HashJoin { outer, inner, .. } => {
+                let mut order = join_order(outer);
+                order.extend(join_order(inner));
+                order
+            }
+            PlanNode::IndexNestedLoopJoin { outer, alias, .. } => {
+                let mut order = join_order(outer);
+                order.push(alias.clone());
+                order
+            }
+            PlanNode::Filter { input, .. }
+            | PlanNode::Sort { input, .. }
+            | PlanNode::TopN { input, .. }
+            | PlanNode::Limit { input, .. }
+            | PlanNode::HashAggregate { input, .. } | PlanNode::Project { input, .. } => join_order(input),
+            PlanNode::OneRow => Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_index_scans_for_selective_conditions() {
+        let fixture = Fixture::new(&[
+            "CREATE TABLE scores (game INT, player VARCHAR, score BIGINT)",
+            "CREATE INDEX scores_game_score_idx ON scores (game, score DESC)",
+        ])
+        .analyzed("scores", 1_000_000, &[10_000.0, 50_000.0, 1000.0]);
+
+        // a prefix of the key and a range on the next column, the rest filters
+        let plan = fixture.plan("SELECT * FROM scores WHERE player = 'sam' AND 10 <= score AND game = 7 AND score < 3000000000");
+        let PlanNode::IndexScan { index, prefix, lower, upper, filter, .. } = plan.node else {
+            panic!("expected an index scan, got {plan:?}");
+        };
+        assert_eq!(index.name, "scores_game_score_idx");
+        let int = |value| Expr::Literal(Literal::Integer(value));
+        assert_eq!(prefix, [int(7)]);
+        assert_eq!(lower, Bound::Included(int(10)));
+        assert_eq!(upper, Bound::Excluded(int(3000000000)));
+        assert_eq!(filter.unwrap().to_string(), "(player = 'sam')");
+        // 1/10000 of the rows are in game 7, a third of those in range and 1/50000 of those sam's
+        assert_eq!(plan.rows, 1.0);
+
+        // a range over a third of a big table is cheaper to read in order
+        assert!(matches!(fixture.plan("SELECT * FROM scores WHERE game > 7").node, PlanNode::SeqScan { .. }));
+        // so are conditions the index can't use
+        assert!(matches!(fixture.plan("SELECT * FROM scores WHERE score = 10").node, PlanNode::SeqScan { .. }));
+        assert!(matches!(fixture.plan("SELECT * FROM scores WHERE game = 3000000000").node, PlanNode::SeqScan { .. }));
+        assert!(matches!(fixture.plan("SELECT * FROM scores WHERE game = 1 OR game = 2").node, PlanNode::SeqScan { .. }));
+    }
+
+    #[test]
+    fn test_small_tables_are_scanned() {
+        let fixture = Fixture::new(&["CREATE TABLE users (id INT PRIMARY KEY, name VARCHAR)", "INSERT INTO users VALUES (1, 'frodo'), (2, 'sam')"]);
+        let plan = fixture.plan("SELECT * FROM users WHERE id = 1");
+        assert!(matches!(&plan.node, PlanNode::SeqScan { filter: Some(_), .. }), "expected a seq scan, got {plan:?}");
+
+        // among a hundred thousand users, one is worth looking up in the primary key
+        let fixture = fixture.analyzed("users", 100_000, &[100_000.0, 100_000.0]);
+        let plan = fixture.plan("SELECT * FROM users WHERE id = 1");
+        assert!(matches!(&plan.node, PlanNode::IndexScan { filter: None, .. }), "expected an index scan, got {plan:?}");
+    }
+
+    #[test]
+    fn test_join_order() {
+        let fixture = Fixture::new(&[
+            "CREATE TABLE orders (id INT, customer INT, product INT)",
+            "CREATE TABLE customers (id INT, name VARCHAR)",
+            "CREATE TABLE products (id INT, name VARCHAR)",
+        ])
+        .analyzed("orders", 1_000_000, &[1_000_000.0, 10_000.0, 1000.0])
+        .analyzed("customers", 10_000, &[10_000.0, 10_000.0])
+        .analyzed("products", 1000, &[1000.0, 1000.0]);
+
+        // customers and products are never paired up on their own, and the one customer
+        // named frodo is kept in memory while the orders are read, not the other way around
+        let sql = "SELECT * FROM customers c, products p, orders o WHERE o.customer = c.id AND o.product = p.id AND c.name = 'frodo'";
+        let plan = fixture.plan(sql);
+        let order = join_order(&plan);
+        assert_eq!(order, ["o", "c", "p"]);
+        // with a hundred orders each
+        assert!((plan.rows - 100.0).abs() < 1.0, "{}", plan.rows);
+
+        // the join conditions are checked by the joins that have both sides, the filter by the scan;
+        // the products are hashed, while the one customer is simply tried with every order
+        let PlanNode::HashJoin { outer, outer_keys, inner_keys, condition: None, .. } = &plan.node else {
+            panic!("expected a hash join, got {plan:?}");
+        };
+        assert_eq!((outer_keys[0].to_string(), inner_keys[0].to_string()), ("o.product".to_string(), "p.id".to_string()));
+        let PlanNode::NestedLoopJoin { inner, condition: Some(condition), .. } = &outer.node else {
+            panic!("expected a nested loop join, got {outer:?}");
+        };
+        assert_eq!(condition.to_string(), "(o.customer = c.id)");
+        assert!(matches!(&inner.node, PlanNode::SeqScan { filter: Some(_), .. }));
+
+        // the same plan however the query is written
+        let sql = "SELECT * FROM orders o JOIN products p ON o.product = p.id JOIN customers c ON c.name = 'frodo' AND o.customer = c.id";
+        assert_eq!(join_order(&fixture.plan(sql)), order);
+    }
+
+    #[test]
+    fn test_hash_joins_on_equal_columns() {
+        let fixture = Fixture::new(&["CREATE TABLE a (id INT, name VARCHAR)", "CREATE TABLE b (id BIGINT, label VARCHAR(8))"])
+            .analyzed("a", 100_000, &[100_000.0, 1000.0])
+            .analyzed("b", 200_000, &[100_000.0, 1000.0]);
+
+        // INT and BIGINT hash alike, the other condition is checked for the rows with equal keys
+        let plan = fixture.plan("SELECT * FROM a, b WHERE b.id = a.id AND a.name < b.label");
+        let PlanNode::HashJoin { outer, inner, outer_keys, inner_keys, condition: Some(condition) } = &plan.node else {
+            panic!("expected a hash join, got {plan:?}");
+        };
+        assert_eq!(join_order(outer), ["b"]);
+        assert_eq!(join_order(inner), ["a"]);
+        assert_eq!((outer_keys[0].to_string(), inner_keys[0].to_string()), ("b.id".to_string(), "a.id".to_string()));
+        assert_eq!(condition.to_string(), "(a.name < b.label)");
+        assert!(matches!(fixture.plan("SELECT * FROM a, b WHERE a.name = b.label").node, PlanNode::HashJoin { .. }));
+
+        // without equal columns every pair has to be tried
+        assert!(matches!(fixture.plan("SELECT * FROM a, b WHERE a.id < b.id").node, PlanNode::NestedLoopJoin { .. }));
+        assert!(matches!(fixture.plan("SELECT * FROM a, b WHERE a.id = b.id + 1").node, PlanNode::NestedLoopJoin { .. }));
+
+        // rows that don't fit in memory are written out and read back
+        let sql = "SELECT * FROM a, b WHERE b.id = a.id";
+        let Statement::Select(select) = parse_statement(sql).unwrap() else { unreachable!() };
+        let cost = |work_memory: usize| {
+            let planner = Planner::new(&fixture.catalog, fixture.buffer_pool.clone(), &fixture.statistics).with_work_memory(work_memory);
+            planner.plan_select(&select).unwrap().cost
+        };
+        // both sides are read back once, a hundred thousand rows of a and two hundred thousand of b
+        let extra = cost(64 * 1024) - cost(1 << 30);
+        assert!(extra > 1000.0, "{extra}");
+    }
+
+    #[test]
+    fn test_index_nested_loop_joins() {
+        let fixture = Fixture::new(&[
+            "CREATE TABLE accounts (id BIGINT PRIMARY KEY, owner VARCHAR, branch INT)",
+            "CREATE TABLE payments (id INT, account INT, amount INT)",
+            "CREATE INDEX payments_account_amount_idx ON payments (account, amount)",
+        ])
+        .analyzed("accounts", 100_000, &[100_000.0, 90_000.0, 100.0])
+        .analyzed("payments", 1_000_000, &[1_000_000.0, 100_000.0, 10_000.0]);
+
+        // a single payment looks its account up by primary key rather than reading them all
+        let plan = fixture.plan("SELECT * FROM payments p, accounts a WHERE p.account = a.id AND p.id = 5 AND a.branch = 3");
+        let PlanNode::IndexNestedLoopJoin { outer, alias, index, outer_keys, filter: Some(filter), condition: None, .. } = &plan.node else {
+            panic!("expected an index nested loop join, got {plan:?}");
+        };
+        assert_eq!((alias.as_str(), index.name.as_str()), ("a", "accounts_pkey"));
+        assert_eq!(join_order(outer), ["p"]);
+        assert_eq!(outer_keys[0].to_string(), "p.account");
+        assert_eq!(filter.to_string(), "(a.branch = 3)");
+
+        // and the few accounts of a branch their payments, by the leading column of an index
+        let plan = fixture.plan("SELECT * FROM accounts a JOIN payments p ON p.account = a.id AND p.amount > a.branch WHERE a.owner = 'frodo'");
+        let PlanNode::IndexNestedLoopJoin { alias, outer_keys, filter: None, condition: Some(condition), .. } = &plan.node else {
+            panic!("expected an index nested loop join, got {plan:?}");
+        };
+        assert_eq!((alias.as_str(), outer_keys.len()), ("p", 1));
+        assert_eq!(condition.to_string(), "(p.amount > a.branch)");
+
+        // looking every account up once each costs more than reading them all
+        assert!(matches!(fixture.plan("SELECT * FROM payments p, accounts a WHERE p.account = a.id").node, PlanNode::HashJoin { .. }));
+    }
+
+    #[test]
+    fn test_many_tables_are_joined_greedily() {
+        let mut sql = Vec::new();
+        let mut from = Vec::new();
+        let mut conditions = Vec::new();
+        for i in 0..12 {
+            sql.push(format!("CREATE TABLE t{i} (id INT, next INT)"));
+            from.push(format!("t{i}"));
+            if i > 0 {
+                conditions.push(format!("t{}.next = t{i}.id", i - 1));
+            }
+        }
+        let mut fixture = Fixture::new(&sql.iter().map(String::as_str).collect::<Vec<_>>());
+        for i in 0..12 {
+            fixture = fixture.analyzed(&format!("t{i}"), 1000 * (i + 1), &[1000.0 * (i + 1) as f64, 1000.0]);
+        }
+
+        // a chain, joined from the smallest table along the chain without pairing up unrelated tables
+        let plan = fixture.plan(&format!("SELECT * FROM {} WHERE {}", from.join(", "), conditions.join(" AND ")));
+        let order = join_order(&plan);
+        assert_eq!(order.len(), 12);
+        assert_eq!(order[0], "t0");
+        for pair in order.windows(2) {
+            let index = |name: &str| name[1..].parse::<i32>().unwrap();
+            assert!(order.iter().take_while(|name| **name != pair[1]).any(|name| (index(name) - index(&pair[1])).abs() == 1));
+        }
+    }
+
+    #[test]
+    fn test_binding_errors() {
+        let fixture = Fixture::new(&["CREATE TABLE a (id INT, x INT)", "CREATE TABLE b (id INT, y INT)"]);
+        let planner = Planner::new(&fixture.catalog, fixture.buffer_pool.clone(), &NoStatistics);
+        let plan = |sql: &str| {
+            let Statement::Select(select) = parse_statement(sql).unwrap() else { unreachable!() };
+            planner.plan_select(&select)
+        };
+        assert!(matches!(plan("SELECT id FROM a, b"), Err(ExecutionError::AmbiguousColumn(name)) if name == "id"));
+        assert!(matches!(plan("SELECT * FROM a, a"), Err(ExecutionError::DuplicateTable(name)) if name == "a"));
+        assert!(matches!(plan("SELECT * FROM a JOIN b ON a.id = c.id JOIN b c ON TRUE"), Err(ExecutionError::ColumnNotFound(name)) if name == "c.id"));
+        assert!(matches!(plan("SELECT * FROM a x WHERE a.id = 1"), Err(ExecutionError::ColumnNotFound(_))));
+        assert!(plan("SELECT a.id, b.id, x, b2.y FROM a, b b2, b WHERE b2.y = a.x").is_ok());
+        assert!(matches!(plan("SELECT id, count(*) FROM a GROUP BY x"), Err(ExecutionError::UngroupedColumn(name)) if name == "id"));
+        assert!(matches!(plan("SELECT x FROM a HAVING count(*) > 1"), Err(ExecutionError::UngroupedColumn(_))));
+        assert!(matches!(plan("SELECT x FROM a WHERE sum(x) > 1"), Err(ExecutionError::AggregateNotAllowed("WHERE"))));
+        assert!(matches!(plan("SELECT sum(count(*)) FROM a"), Err(ExecutionError::AggregateNotAllowed(_))));
+        assert!(matches!(plan("SELECT sum(z) FROM a"), Err(ExecutionError::ColumnNotFound(name)) if name == "z"));
+        assert!(plan("SELECT a.x + 1, max(y) - min(a.id) FROM a JOIN b ON a.id = b.id GROUP BY a.x + 1 HAVING avg(y) > 0").is_ok());
+    }
+
+    #[test]
+    fn test_group_estimates() {
+        let fixture = Fixture::new(&["CREATE TABLE sales (store INT, product INT, amount BIGINT)"]).analyzed("sales", 100_000, &[20.0, 500.0, 90_000.0]);
+
+        // a group for every combination of the keys' values, but never more groups than rows
+        let rows = |sql: &str| {
+            let plan = fixture.plan(sql);
+            let PlanNode::HashAggregate { .. } = plan.node else {
+                panic!("expected an aggregate, got {:?}", plan.node);
+            };
+            plan.rows
+        };
+        assert_eq!(rows("SELECT store, sum(amount) FROM sales GROUP BY store"), 20.0);
+        assert_eq!(rows("SELECT store, product, count(*) FROM sales GROUP BY store, product"), 10_000.0);
+        assert_eq!(rows("SELECT amount, product FROM sales GROUP BY amount, product"), 100_000.0);
+        assert_eq!(rows("SELECT count(*) FROM sales WHERE store = 1"), 1.0);
+
+        // HAVING keeps some of the groups
+        let plan = fixture.plan("SELECT store FROM sales GROUP BY store HAVING sum(amount) > 1000");
+        assert!(matches!(plan.node, PlanNode::Filter { .. }));
+        assert!(plan.rows < 20.0 && plan.rows >= 1.0, "{}", plan.rows);
+    }
+
+    #[test]
+    fn test_push_down_and_prune() {
+        let fixture = Fixture::new(&[
+            "CREATE TABLE accounts (id INT, owner VARCHAR, branch INT)",
+            "CREATE TABLE payments (id INT, account INT, amount INT, note VARCHAR)",
+        ]);
+        let Statement::Select(select) = parse_statement("SELECT p.amount FROM payments p JOIN accounts a ON p.account = a.id AND a.branch = 3 WHERE a.owner = 'frodo' AND p.amount > a.branch").unwrap() else {
+            unreachable!()
+        };
+        let LogicalPlan::Project { input, .. } = rewrite(LogicalPlan::from_select(&fixture.catalog, &select).unwrap()) else {
+            panic!("expected a projection");
+        };
+
+        // the conditions on one table go to its scan, which hands on only the columns used above it
+        let LogicalPlan::Join { left, right, condition: Some(condition) } = *input else {
+            panic!("expected a join, got {input:?}");
+        };
+        assert_eq!(condition.to_string(), "((p.account = a.id) AND (p.amount > a.branch))");
+        let LogicalPlan::Scan { columns, filter: None, .. } = *left else {
+            panic!("expected an unfiltered scan, got {left:?}");
+        };
+        assert_eq!(columns, [1, 2]);
+        let LogicalPlan::Scan { columns, filter: Some(filter), .. } = *right else {
+            panic!("expected a filtered scan, got {right:?}");
+        };
+        assert_eq!(columns, [0, 2]);
+        assert_eq!(filter.to_string(), "((a.branch = 3) AND (a.owner = 'frodo'))");
+
+        // and so do the physical scans
+        let plan = fixture.plan("SELECT a.owner FROM payments p, accounts a WHERE p.account = a.id AND p.note = 'rent'");
+        let scans: Vec<&PhysicalPlan> = plan.children().into_iter().filter(|child| matches!(child.node, PlanNode::SeqScan { .. })).collect();
+        assert_eq!(scans.len(), 2, "{plan:?}");
+        for scan in scans {
+            let PlanNode::SeqScan { alias, columns, .. } = &scan.node else { unreachable!() };
+            assert_eq!(*columns, if alias == "p" { vec![1] } else { vec![0, 1] });
+        }
+        assert_eq!(plan.scope().columns().len(), 3);
+        // none at all for a count
+        let plan = fixture.plan("SELECT count(*) FROM payments");
+        assert!(matches!(&plan.children()[0].node, PlanNode::SeqScan { columns, .. } if columns.is_empty()), "{plan:?}");
+
+        // a HAVING condition on the grouped columns alone is checked before grouping
+        let plan = fixture.plan("SELECT account, sum(amount) FROM payments GROUP BY account HAVING account > 10 AND sum(amount) > 100");
+        let PlanNode::Filter { input, predicate } = &plan.node else {
+            panic!("expected a filter, got {plan:?}");
+        };
+        // the rest refers to the aggregate's column, which needs quoting to be read back as one
+        assert_eq!(predicate.to_string(), "(\"sum(amount)\" > 100)");
+        let PlanNode::HashAggregate { input, .. } = &input.node else {
+            panic!("expected an aggregate, got {input:?}");
+        };
+        let PlanNode::SeqScan { columns, filter: Some(filter), .. } = &input.node else {
+            panic!("expected a filtered scan, got {input:?}");
+        };
+        assert_eq!((columns.as_slice(), filter.to_string().as_str()), ([1, 2].as_slice(), "(account > 10)"));
+        // but not without GROUP BY, where there's a row even if none of them pass
+        let plan = fixture.plan("SELECT count(*) FROM payments HAVING 1 > 2");
+        assert!(matches!(plan.node, PlanNode::Filter { .. }), "{plan:?}");
+    }
+
+    #[test]
+    fn test_column_types() {
+        let fixture = Fixture::new(&["CREATE TABLE payments (id INT, account BIGINT, amount INT, note VARCHAR(20), settled BOOL)"]);
+        let Statement::Select(select) = parse_statement("SELECT account, note, amount + 1, amount + account, NOT settled, count(*), max(note), NULL FROM payments GROUP BY account, note, amount, settled").unwrap() else {
+            unreachable!()
+        };
+        let plan = Planner::new(&fixture.catalog, fixture.buffer_pool.clone(), &fixture.statistics).plan_select(&select).unwrap();
+        assert_eq!(
+            plan.column_types(),
+            [
+                Some(DataType::BigInt),
+                Some(DataType::Varchar(Some(20))),
+                Some(DataType::Int),
+                Some(DataType::BigInt),
+                Some(DataType::Bool),
+                Some(DataType::BigInt),
+                Some(DataType::Varchar(Some(20))),
+                None,
+            ]
+        );
+    }
+}
@@ -0,0 +1,232 @@
+use super::logical::aggregate_scope;
+use crate::catalog::{IndexInfo, TableInfo};
+use crate::execution::Scope;
+use crate::index::KeyOrder;
+use crate::sql::{AggregateFunction, BinaryOp, Expr, Function, Literal, UnaryOp};
+use crate::types::DataType;
+use std::ops::Bound;
+
+/// A plan the executor can run: a tree of physical operators, each with the
+/// planner's estimates of how many rows it produces and what producing all of
+/// them costs, in units of sequential page reads.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PhysicalPlan {
+    pub node: PlanNode,
+    pub rows: f64,
+    /// includes the cost of the operators below
+    pub cost: f64,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum PlanNode {
+    /// a single row without columns
+    OneRow,
+    /// The columns at positions `columns` of every row of the table's heap
+    /// file for which `filter` holds.
+    ///
+    /// `filter` may use any column of the table, not only those.
+    SeqScan { table: TableInfo, alias: String, columns: Vec<usize>, filter: Option<Expr> },
+    /// The columns at positions `columns` of the rows `index` has under keys
+    /// that start with the values in `prefix`, and have the next column
+    /// between `lower` and `upper`, for which `filter` holds.
+    ///
+    /// The values are literals or parameters, which can be converted to the
+    /// types of the columns they're compared with. They're worked out when
+    /// the scan starts, and a NULL among them leaves it without rows.
+    IndexScan {
+        table: TableInfo,
+        alias: String,
+        columns: Vec<usize>,
+        index: IndexInfo,
+        prefix: Vec<Expr>,
+        lower: Bound<Expr>,
+        upper: Bound<Expr>,
+        filter: Option<Expr>,
+    },
+    Filter { input: Box<PhysicalPlan>, predicate: Expr },
+    /// Each row of `outer` followed by each row of `inner` it satisfies the condition with.
+    ///
+    /// The rows of `inner` are read once and kept in memory.
+    NestedLoopJoin { outer: Box<PhysicalPlan>, inner: Box<PhysicalPlan>, condition: Option<Expr> },
+    /// Each row of `outer` followed by the columns at positions `columns` of
+    /// each row of `table` that `index` has under keys starting with the row's
+    /// values of `outer_keys`, for which `filter` holds and that it satisfies
+    /// the condition with.
+    ///
+    /// The index is looked up once for every outer row, and only the rows it
+    /// finds are read from the table.
+    IndexNestedLoopJoin {
+        outer: Box<PhysicalPlan>,
+        table: TableInfo,
+        alias: String,
+        columns: Vec<usize>,
+        index: IndexInfo,
+        outer_keys: Vec<Expr>,
+        filter: Option<Expr>,
+        condition: Option<Expr>,
+    },
+    /// Each row of `outer` followed by each row of `inner` whose values of
+    /// `inner_keys` equal its values of `outer_keys`, and that it satisfies the
+    /// condition with.
+    ///
+    /// The rows of `inner` are hashed by key, and split into partitions on
+    /// disk if they don't fit in the executor's work memory.
+    HashJoin {
+        outer: Box<PhysicalPlan>,
+        inner: Box<PhysicalPlan>,
+        outer_keys: Vec<Expr>,
+        inner_keys: Vec<Expr>,
+        condition: Option<Expr>,
+    },
+    /// A row for each group of rows of `input` with the same values of
+    /// `group_by`: those values, then the value of each of `aggregates` (all
+    /// `Expr::Aggregate`s) over the group.
+    ///
+    /// The groups are kept in a hash table while the input is read, and the
+    /// rows of groups that don't fit in the executor's work memory are set
+    /// aside on disk to be grouped later.
+    HashAggregate { input: Box<PhysicalPlan>, group_by: Vec<Expr>, aggregates: Vec<Expr> },
+    /// The rows of `input` ordered by `keys`, the first key first.
+    ///
+    /// Rows are sorted in memory up to the executor's work memory, and in runs
+    /// written to disk and merged beyond that. Rows with equal keys keep the
+    /// order they came in.
+    Sort { input: Box<PhysicalPlan>, keys: Vec<SortKey> },
+    /// The first `count` rows `Sort` would give for the same input and keys.
+    ///
+    /// Only those are kept while the input is read, in a heap with the last
+    /// of them at the top, so they have to fit in memory.
+    TopN { input: Box<PhysicalPlan>, keys: Vec<SortKey>, count: u64 },
+    /// the rows of `input` after the first `offset`, no more than `limit` of them
+    Limit { input: Box<PhysicalPlan>, limit: Option<u64>, offset: u64 },
+    /// `exprs` evaluated for each row of `input`, as the columns `names`
+    Project { input: Box<PhysicalPlan>, exprs: Vec<Expr>, names: Vec<String> },
+}
+
+/// An expression rows are sorted by, and which way.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SortKey {
+    pub expr: Expr,
+    pub order: KeyOrder,
+}
+
+impl PhysicalPlan {
+    /// the columns of the rows this plan produces
+    pub fn scope(&self) -> Scope {
+        match &self.node {
+            PlanNode::OneRow => Scope::empty(),
+            PlanNode::SeqScan { table, alias, columns, .. } | PlanNode::IndexScan { table, alias, columns, .. } => Scope::aliased_columns(table, alias, columns),
+            PlanNode::Filter { input, .. } | PlanNode::Sort { input, .. } | PlanNode::TopN { input, .. } | PlanNode::Limit { input, .. } => input.scope(),
+            PlanNode::HashAggregate { input, group_by, aggregates } => aggregate_scope(&input.scope(), group_by, aggregates),
+            PlanNode::NestedLoopJoin { outer, inner, .. } | PlanNode::HashJoin { outer, inner, .. } => outer.scope().join(&inner.scope()),
+            PlanNode::IndexNestedLoopJoin { outer, table, alias, columns, .. } => outer.scope().join(&Scope::aliased_columns(table, alias, columns)),
+            PlanNode::Project { names, .. } => Scope::named(names.iter().cloned()),
+        }
+    }
+
+    /// the plans this one reads its rows from, outer side first
+    pub fn children(&self) -> Vec<&PhysicalPlan> {
+        match &self.node {
+            PlanNode::OneRow | PlanNode::SeqScan { .. } | PlanNode::IndexScan { .. } => Vec::new(),
+            PlanNode::Filter { input, .. }
+            | PlanNode::IndexNestedLoopJoin { outer: input, .. }
+            | PlanNode::HashAggregate { input, .. }
+            | PlanNode::Sort { input, .. }
+            | PlanNode::TopN { input, .. }
+            | PlanNode::Limit { input, .. }
+            | PlanNode::Project { input, .. } => vec![input],
+            PlanNode::NestedLoopJoin { outer, inner, .. } | PlanNode::HashJoin { outer, inner, .. } => vec![outer, inner],
+        }
+    }
+
+    /// names of the columns of the rows this plan produces
+    pub fn column_names(&self) -> Vec<String> {
+        self.scope().columns().iter().map(|column| column.name.clone()).collect()
+    }
+
+    /// Types of the columns of the rows this plan produces, `None` for one
+    /// whose values can be of any type, like a `NULL` literal or a parameter.
+    pub fn column_types(&self) -> Vec<Option<DataType>> {
+        let table_types = |table: &TableInfo, columns: &[usize]| columns.iter().map(|&position| Some(table.columns[position].data_type)).collect::<Vec<_>>();
+        match &self.node {
+            PlanNode::OneRow => Vec::new(),
+            PlanNode::SeqScan { table, columns, .. } | PlanNode::IndexScan { table, columns, .. } => table_types(table, columns),
+            PlanNode::Filter { input, .. } | PlanNode::Sort { input, .. } | PlanNode::TopN { input, .. } | PlanNode::Limit { input, .. } => input.column_types(),
+            PlanNode::NestedLoopJoin { outer, inner, .. } | PlanNode::HashJoin { outer, inner, .. } => {
+                let mut types = outer.column_types();
+                types.extend(inner.column_types());
+                types
+            }
+            PlanNode::IndexNestedLoopJoin { outer, table, columns, .. } => {
+                let mut types = outer.column_types();
+                types.extend(table_types(table, columns));
+                types
+            }
+            PlanNode::HashAggregate { input, group_by, aggregates } => {
+                let (scope, types) = (input.scope(), input.column_types());
+                group_by.iter().chain(aggregates).map(|expr| expr_type(expr, &scope, &types)).collect()
+            }
+            PlanNode::Project { input, exprs, .. } => {
+                let (scope, types) = (input.scope(), input.column_types());
+                exprs.iter().map(|expr| expr_type(expr, &scope, &types)).collect()
+            }
+        }
+    }
+}
+
+/// the type of the values of `expr` for rows with the columns of `scope`, which are of `types`
+pub(crate) fn expr_type(expr: &Expr, scope: &Scope, types: &[Option<DataType>]) -> Option<DataType> {
+    match expr {
+        Expr::Literal(Literal::Integer(value)) => Some(if i32::try_from(*value).is_ok() { DataType::Int } else { DataType::BigInt }),
+        Expr::Literal(Literal::Decimal(_)) => Some(DataType::Numeric(None)),
+        Expr::Literal(Literal::String(_)) => Some(DataType::Varchar(None)),
+        Expr::Literal(Literal::Boolean(_)) => Some(DataType::Bool),
+        Expr::Literal(Literal::Date(_)) => Some(DataType::Date),
+        Expr::Literal(Literal::Time(_)) => Some(DataType::Time),
+        Expr::Literal(Literal::Timestamp(_)) => Some(DataType::Timestamp),
+        Expr::Literal(Literal::Interval(_)) => Some(DataType::Interval),
+        Expr::Literal(Literal::Null) | Expr::Parameter(_) => None,
+        Expr::Column { table, name } => types[scope.resolve(table.as_deref(), name).ok()?],
+        Expr::Unary { op: UnaryOp::Not, .. } | Expr::IsNull { .. } => Some(DataType::Bool),
+        Expr::Unary { op: UnaryOp::Minus, expr } => expr_type(expr, scope, types),
+        Expr::Binary { left, op: op @ (BinaryOp::Plus | BinaryOp::Minus | BinaryOp::Multiply | BinaryOp::Divide | BinaryOp::Modulo), right } => {
+            // INT with INT stays INT, anything else with an integer is BIGINT, and
+            // anything with a NUMERIC is NUMERIC
+            let (left, right) = (expr_type(left, scope, types), expr_type(right, scope, types));
+            if let Some(data_type) = datetime_arithmetic_type(left, *op, right) {
+                return Some(data_type);
+            }
+            match (left, right) {
+                (Some(DataType::Numeric(_)), _) | (_, Some(DataType::Numeric(_))) => Some(DataType::Numeric(None)),
+                (Some(DataType::Int) | None, Some(DataType::Int) | None) => Some(DataType::Int),
+                _ => Some(DataType::BigInt),
+            }
+        }
+        Expr::Binary { .. } => Some(DataType::Bool),
+        Expr::Aggregate { function: AggregateFunction::Sum | AggregateFunction::Avg, arg: Some(arg) }
+            if matches!(expr_type(arg, scope, types), Some(DataType::Numeric(_))) =>
+        {
+            Some(DataType::Numeric(None))
+        }
+        Expr::Aggregate { function: AggregateFunction::Count | AggregateFunction::Sum | AggregateFunction::Avg, .. } => Some(DataType::BigInt),
+        Expr::Aggregate { function: AggregateFunction::Min | AggregateFunction::Max, arg } => expr_type(arg.as_deref()?, scope, types),
+        Expr::Function { function: Function::Now | Function::DateTrunc, .. } => Some(DataType::Timestamp),
+        Expr::Function { function: Function::DatePart, .. } => Some(DataType::Numeric(None)),
+    }
+}
+
+/// the type of `left op right` when one side is a date, time, timestamp or
+/// interval, `None` when neither is, see `expression::binary`
+fn datetime_arithmetic_type(left: Option<DataType>, op: BinaryOp, right: Option<DataType>) -> Option<DataType> {
+    use DataType::{BigInt, Date, Int, Interval, Time, Timestamp};
+    let data_type = match (left?, op, right?) {
+        (Date, BinaryOp::Plus | BinaryOp::Minus, Int | BigInt) | (Int | BigInt, BinaryOp::Plus, Date) => Date,
+        (Date, BinaryOp::Minus, Date) => Int,
+        (Date | Timestamp, BinaryOp::Plus | BinaryOp::Minus, Interval) | (Interval, BinaryOp::Plus, Date | Timestamp) | (Date, BinaryOp::Plus, Time) | (Time, BinaryOp::Plus, Date) => Timestamp,
+        (Time, BinaryOp::Plus | BinaryOp::Minus, Interval) | (Interval, BinaryOp::Plus, Time) => Time,
+        (Date | Timestamp, BinaryOp::Minus, Date | Timestamp) | (Time, BinaryOp::Minus, Time) | (Interval, BinaryOp::Plus | BinaryOp::Minus, Interval) => Interval,
+        (Interval, BinaryOp::Multiply, Int | BigInt) | (Int | BigInt, BinaryOp::Multiply, Interval) => Interval,
+        _ => return None,
+    };
+    Some(data_type)
+}
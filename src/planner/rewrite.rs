@@ -0,0 +1,130 @@
+use super::join::{conjunction, split_conjuncts};
+use super::logical::{LogicalPlan, column_references};
+use crate::execution::Scope;
+use crate::sql::Expr;
+
+/// `plan`, as built by `LogicalPlan::from_select`, with its rows cut down as
+/// early as they can be: every condition is pushed down as far as the columns
+/// it uses allow, and every scan reads only the columns the operators above it
+/// use.
+pub(super) fn rewrite(plan: LogicalPlan) -> LogicalPlan {
+    let plan = push_down(plan, Vec::new());
+    let needed: Vec<Expr> = plan.scope().columns().iter().map(|column| Expr::Column { table: column.table.clone(), name: column.name.clone() }).collect();
+    prune(plan, &needed)
+}
+
+/// `plan` with `conditions`, which hold for its rows, checked as far down in it as they can be
+fn push_down(plan: LogicalPlan, conditions: Vec<Expr>) -> LogicalPlan {
+    match plan {
+        // a HAVING clause, whose conditions on grouped columns alone hold for
+        // either all of a group's rows or none, so they can be checked before grouping
+        LogicalPlan::Filter { input, predicate } if matches!(*input, LogicalPlan::Aggregate { .. }) => {
+            let conditions = and(Some(&predicate), conditions);
+            let LogicalPlan::Aggregate { input, group_by, aggregates } = *input else { unreachable!() };
+            // without keys there's a row even for no rows at all, which the condition might not hold for
+            let (below, above): (Vec<Expr>, Vec<Expr>) = conditions
+                .into_iter()
+                .partition(|condition| !group_by.is_empty() && column_references(condition).into_iter().all(|column| group_by.contains(column)));
+            let aggregate = LogicalPlan::Aggregate { input: Box::new(push_down(*input, below)), group_by, aggregates };
+            filtered(aggregate, above)
+        }
+        LogicalPlan::Filter { input, predicate } => push_down(*input, and(Some(&predicate), conditions)),
+        LogicalPlan::Join { left, right, condition } => {
+            // a condition goes to whichever side has all of its columns, and stays with the join if neither does
+            let (left_scope, right_scope) = (left.scope(), right.scope());
+            let (mut to_left, mut to_right, mut here) = (Vec::new(), Vec::new(), Vec::new());
+            for condition in and(condition.as_ref(), conditions) {
+                if resolves(&condition, &left_scope) {
+                    to_left.push(condition);
+                } else if resolves(&condition, &right_scope) {
+                    to_right.push(condition);
+                } else {
+                    here.push(condition);
+                }
+            }
+            LogicalPlan::Join { left: Box::new(push_down(*left, to_left)), right: Box::new(push_down(*right, to_right)), condition: conjunction(here) }
+        }
+        LogicalPlan::Scan { table, alias, columns, filter } => LogicalPlan::Scan { table, alias, columns, filter: conjunction(and(filter.as_ref(), conditions)) },
+        // sorting doesn't change which rows there are
+        LogicalPlan::Sort { input, keys } => LogicalPlan::Sort { input: Box::new(push_down(*input, conditions)), keys },
+        LogicalPlan::Aggregate { input, group_by, aggregates } => {
+            filtered(LogicalPlan::Aggregate { input: Box::new(push_down(*input, Vec::new())), group_by, aggregates }, conditions)
+        }
+        LogicalPlan::Limit { input, limit, offset } => filtered(LogicalPlan::Limit { input: Box::new(push_down(*input, Vec::new())), limit, offset }, conditions),
+        LogicalPlan::Project { input, exprs, names } => filtered(LogicalPlan::Project { input: Box::new(push_down(*input, Vec::new())), exprs, names }, conditions),
+        LogicalPlan::OneRow => filtered(LogicalPlan::OneRow, conditions),
+    }
+}
+
+/// the conditions `expr` splits into at its `AND`s, then `conditions`
+fn and(expr: Option<&Expr>, conditions: Vec<Expr>) -> Vec<Expr> {
+    let mut all = Vec::new();
+    if let Some(expr) = expr {
+        split_conjuncts(expr, &mut all);
+    }
+    all.extend(conditions);
+    all
+}
+
+/// `plan` with the rows `conditions` don't hold for filtered out
+fn filtered(plan: LogicalPlan, conditions: Vec<Expr>) -> LogicalPlan {
+    match conjunction(conditions) {
+        Some(predicate) => LogicalPlan::Filter { input: Box::new(plan), predicate },
+        None => plan,
+    }
+}
+
+/// whether every column `expr` refers to is one of `scope`
+fn resolves(expr: &Expr, scope: &Scope) -> bool {
+    column_references(expr).into_iter().all(|column| {
+        let Expr::Column { table, name } = column else { unreachable!() };
+        scope.resolve(table.as_deref(), name).is_ok()
+    })
+}
+
+/// `plan` with its scans cut down to the columns its operators use, and
+/// `needed`, the column references the operators above it make
+fn prune(plan: LogicalPlan, needed: &[Expr]) -> LogicalPlan {
+    // `needed` and the columns `exprs` refer to
+    let also = |exprs: &mut dyn Iterator<Item = &Expr>| -> Vec<Expr> { needed.iter().cloned().chain(exprs.flat_map(column_references).cloned()).collect() };
+    match plan {
+        LogicalPlan::OneRow => LogicalPlan::OneRow,
+        LogicalPlan::Scan { table, alias, columns, filter } => {
+            // the filter is checked as the rows are read, so the columns only it uses aren't handed on
+            let scope = Scope::aliased(&table, &alias);
+            let mut kept: Vec<usize> = needed
+                .iter()
+                .filter_map(|column| {
+                    let Expr::Column { table, name } = column else { unreachable!() };
+                    scope.resolve(table.as_deref(), name).ok()
+                })
+                .filter(|position| columns.contains(position))
+                .collect();
+            kept.sort_unstable();
+            kept.dedup();
+            LogicalPlan::Scan { table, alias, columns: kept, filter }
+        }
+        LogicalPlan::Filter { input, predicate } => {
+            let needed = also(&mut std::iter::once(&predicate));
+            LogicalPlan::Filter { input: Box::new(prune(*input, &needed)), predicate }
+        }
+        LogicalPlan::Join { left, right, condition } => {
+            let needed = also(&mut condition.iter());
+            LogicalPlan::Join { left: Box::new(prune(*left, &needed)), right: Box::new(prune(*right, &needed)), condition }
+        }
+        // the rows above are those of the groups, which only need the columns grouped by and aggregated
+        LogicalPlan::Aggregate { input, group_by, aggregates } => {
+            let needed: Vec<Expr> = group_by.iter().chain(&aggregates).flat_map(column_references).cloned().collect();
+            LogicalPlan::Aggregate { input: Box::new(prune(*input, &needed)), group_by, aggregates }
+        }
+        LogicalPlan::Sort { input, keys } => {
+            let needed = also(&mut keys.iter().map(|key| &key.expr));
+            LogicalPlan::Sort { input: Box::new(prune(*input, &needed)), keys }
+        }
+        LogicalPlan::Limit { input, limit, offset } => LogicalPlan::Limit { input: Box::new(prune(*input, needed)), limit, offset },
+        LogicalPlan::Project { input, exprs, names } => {
+            let needed: Vec<Expr> = exprs.iter().flat_map(column_references).cloned().collect();
+            LogicalPlan::Project { input: Box::new(prune(*input, &needed)), exprs, names }
+        }
+    }
+}
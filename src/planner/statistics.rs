@@ -0,0 +1,204 @@
+use crate::catalog::{Catalog, CatalogError, TableInfo};
+use crate::execution::{ExecutionError, compare};
+use crate::storage::{BufferPool, HeapFile};
+use crate::types::{Tuple, Value};
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+/// most rows `TableStatistics::gather` looks at, the same as PostgreSQL's default
+pub const SAMPLE_ROWS: usize = 30_000;
+/// most buckets in the histogram of a column
+pub const HISTOGRAM_BUCKETS: usize = 50;
+/// bytes of a VARCHAR kept as a histogram bound, longer values are cut short
+const MAX_BOUND_WIDTH: usize = 64;
+
+/// What the planner knows about the contents of a table, as of the last time
+/// they were counted.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TableStatistics {
+    pub row_count: u64,
+    /// pages of the heap file
+    pub page_count: u64,
+    /// one per column of the table, in order
+    pub columns: Vec<ColumnStatistics>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ColumnStatistics {
+    /// fraction of the rows that are NULL in the column
+    pub null_fraction: f64,
+    /// number of distinct non-NULL values
+    pub distinct_count: f64,
+    /// Bounds of an equi-depth histogram of the non-NULL values, smallest
+    /// first: about as many values fall between each bound and the next as
+    /// between any other two. Empty when nothing is known about the values.
+    pub histogram: Vec<Value>,
+}
+
+impl ColumnStatistics {
+    /// a column with `distinct_count` values and no NULLs, with no histogram
+    pub fn new(distinct_count: f64) -> Self {
+        Self { null_fraction: 0.0, distinct_count, histogram: Vec::new() }
+    }
+}
+
+impl TableStatistics {
+    /// Gathers statistics on the rows of `table`, as `ANALYZE` does.
+    ///
+    /// Every row is counted, but only a random sample of `SAMPLE_ROWS` of them
+    /// is decoded and looked at. NULL fractions and histograms are taken from
+    /// the sample as is. The number of distinct values is scaled up from the
+    /// sample with the Haas and Stokes estimator PostgreSQL uses, and a column
+    /// whose sampled values are all different is assumed to be unique.
+    pub fn gather(buffer_pool: Arc<Mutex<BufferPool>>, table: &TableInfo) -> Result<Self, ExecutionError> {
+        let heap = HeapFile::open(buffer_pool, table.root_page_id)?;
+        let page_count = heap.page_ids().len() as u64;
+
+        // reservoir sampling: the nth row replaces a random sampled one with a chance of SAMPLE_ROWS / n
+        let mut sample = Vec::new();
+        let mut row_count: u64 = 0;
+        let mut state: u64 = 0x2545F4914F6CDD1D;
+        for tuple in heap.scan() {
+            let (_, bytes) = tuple?;
+            row_count += 1;
+            if sample.len() < SAMPLE_ROWS {
+                sample.push(bytes);
+                continue;
+            }
+            state = state.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+            let slot = (state >> 11) % row_count;
+            if slot < SAMPLE_ROWS as u64 {
+                sample[slot as usize] = bytes;
+            }
+        }
+        let rows = sample.iter().map(|bytes| Ok(Tuple::decode(bytes, &table.columns)?.into_values())).collect::<Result<Vec<_>, ExecutionError>>()?;
+
+        let columns = (0..table.columns.len())
+            .map(|position| {
+                let values = rows.iter().map(|row| row[position].clone()).collect();
+                column_statistics(values, row_count)
+            })
+            .collect();
+        Ok(Self { row_count, page_count, columns })
+    }
+}
+
+/// statistics of a column with the values `sample` in a sample of a table of `row_count` rows
+fn column_statistics(sample: Vec<Value>, row_count: u64) -> ColumnStatistics {
+    let sampled = sample.len();
+    let mut values: Vec<Value> = sample.into_iter().filter(|value| !value.is_null()).collect();
+    if values.is_empty() {
+        let null_fraction = if sampled == 0 { 0.0 } else { 1.0 };
+        return ColumnStatistics { null_fraction, distinct_count: 0.0, histogram: Vec::new() };
+    }
+    let null_fraction = (sampled - values.len()) as f64 / sampled as f64;
+    values.sort_by(|a, b| compare(a, b).unwrap_or(Ordering::Equal));
+
+    // distinct values in the sample, and how many of them were seen only once
+    let mut distinct = 0;
+    let mut singles = 0;
+    for run in values.chunk_by(|a, b| compare(a, b) == Some(Ordering::Equal)) {
+        distinct += 1;
+        if run.len() == 1 {
+            singles += 1;
+        }
+    }
+    let (n, d, f1) = (values.len() as f64, distinct as f64, singles as f64);
+    let total = row_count as f64 * (1.0 - null_fraction);
+    let distinct_count = if sampled as u64 >= row_count {
+        d
+    } else if singles == values.len() {
+        total
+    } else {
+        (n * d / (n - f1 + f1 * n / total)).clamp(d, total)
+    };
+
+    let buckets = (values.len() - 1).clamp(1, HISTOGRAM_BUCKETS);
+    let histogram = (0..=buckets).map(|i| bound(&values[i * (values.len() - 1) / buckets])).collect();
+    ColumnStatistics { null_fraction, distinct_count, histogram }
+}
+
+/// `value` as a histogram bound, short enough to keep every bound of a column on one page
+fn bound(value: &Value) -> Value {
+    match value {
+        Value::Varchar(text) if text.len() > MAX_BOUND_WIDTH => {
+            let end = (0..=MAX_BOUND_WIDTH).rev().find(|&end| text.is_char_boundary(end)).unwrap();
+            Value::Varchar(text[..end].to_string())
+        }
+        value => value.clone(),
+    }
+}
+
+/// Where the planner looks up table statistics.
+///
+/// Tables without statistics are still planned, from the size of their heap
+/// file and default selectivities.
+pub trait StatisticsStore {
+    fn table_statistics(&self, table: &str) -> Result<Option<TableStatistics>, CatalogError>;
+}
+
+/// A store without statistics for any table.
+pub struct NoStatistics;
+
+impl StatisticsStore for NoStatistics {
+    fn table_statistics(&self, _table: &str) -> Result<Option<TableStatistics>, CatalogError> {
+        Ok(None)
+    }
+}
+
+impl StatisticsStore for HashMap<String, TableStatistics> {
+    fn table_statistics(&self, table: &str) -> Result<Option<TableStatistics>, CatalogError> {
+        Ok(self.get(table).cloned())
+    }
+}
+
+/// the statistics `ANALYZE` recorded in the catalog
+impl StatisticsStore for Catalog {
+    fn table_statistics(&self, table: &str) -> Result<Option<TableStatistics>, CatalogError> {
+        self.statistics(table)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_column_statistics() {
+        // every row sampled: exact counts, and bounds splitting the values evenly
+        let mut sample: Vec<Value> = (0..1000).map(|i| Value::Int(i % 100)).collect();
+        sample.extend(vec![Value::Null; 250]);
+        let statistics = column_statistics(sample, 1250);
+        assert_eq!(statistics.null_fraction, 0.2);
+        assert_eq!(statistics.distinct_count, 100.0);
+        assert_eq!(statistics.histogram.len(), HISTOGRAM_BUCKETS + 1);
+        assert_eq!(statistics.histogram[0], Value::Int(0));
+        assert_eq!(statistics.histogram[25], Value::Int(49));
+        assert_eq!(statistics.histogram[50], Value::Int(99));
+
+        // a sample of a larger table with every value different looks unique
+        let sample: Vec<Value> = (0..1000).map(Value::BigInt).collect();
+        assert_eq!(column_statistics(sample, 1_000_000).distinct_count, 1_000_000.0);
+
+        // values seen several times each don't get scaled up much
+        let sample: Vec<Value> = (0..1000).map(|i| Value::Varchar(format!("v{}", i % 10))).collect();
+        let statistics = column_statistics(sample, 1_000_000);
+        assert_eq!(statistics.distinct_count, 10.0);
+        assert_eq!(statistics.histogram.first(), Some(&Value::Varchar("v0".into())));
+        assert_eq!(statistics.histogram.last(), Some(&Value::Varchar("v9".into())));
+
+        let statistics = column_statistics(vec![Value::Bool(true)], 1);
+        assert_eq!(statistics.histogram, vec![Value::Bool(true), Value::Bool(true)]);
+        let statistics = column_statistics(vec![Value::Null, Value::Null], 2);
+        assert_eq!((statistics.null_fraction, statistics.distinct_count, statistics.histogram), (1.0, 0.0, Vec::new()));
+        assert_eq!(column_statistics(Vec::new(), 0).null_fraction, 0.0);
+    }
+
+    #[test]
+    fn test_long_bounds_are_cut_short() {
+        let long = "ä".repeat(100);
+        let Value::Varchar(bound) = bound(&Value::Varchar(long)) else { unreachable!() };
+        assert_eq!(bound, "ä".repeat(MAX_BOUND_WIDTH / 2));
+    }
+}
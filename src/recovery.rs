@@ -0,0 +1,508 @@
+use crate::storage::{BufferPool, BufferPoolError, Page, PageError};
+use crate::wal::{INVALID_LSN, LogManager, LogRecord, LogRecordBody, Lsn, TxnId, WalError};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+#[derive(Debug)]
+pub enum RecoveryError {
+    /// a record points back at an LSN that isn't in the log
+    MissingLogRecord(Lsn),
+    WalError(WalError),
+    BufferPoolError(BufferPoolError),
+    PageError(PageError),
+}
+
+impl std::fmt::Display for RecoveryError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RecoveryError::MissingLogRecord(lsn) => write!(f, "Log record {lsn} is missing"),
+            RecoveryError::WalError(error) => write!(f, "WAL error: {error}"),
+            RecoveryError::BufferPoolError(error) => write!(f, "Buffer pool error: {error}"),
+            RecoveryError::PageError(error) => write!(f, "Page error: {error}"),
+        }
+    }
+}
+
+impl std::error::Error for RecoveryError {}
+
+impl From<WalError> for RecoveryError {
+    fn from(error: WalError) -> Self {
+        RecoveryError::WalError(error)
+    }
+}
+
+impl From<BufferPoolError> for RecoveryError {
+    fn from(error: BufferPoolError) -> Self {
+        RecoveryError::BufferPoolError(error)
+    }
+}
+
+impl From<PageError> for RecoveryError {
+    fn from(error: PageError) -> Self {
+        RecoveryError::PageError(error)
+    }
+}
+
+/// Summary of what `recover` did.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RecoveryReport {
+    /// log records replayed onto pages that hadn't seen them yet
+    pub redone: usize,
+    /// transactions that were still running at the crash and have been rolled back
+    pub rolled_back: Vec<TxnId>,
+}
+
+/// state rebuilt from the log by the analysis pass
+struct Analysis {
+    /// transactions without a commit or abort record, with the LSN of their last record
+    active_txns: HashMap<TxnId, Lsn>,
+    /// pages touched by the log, with the first LSN that may not have reached disk
+    dirty_pages: HashMap<u32, Lsn>,
+}
+
+/// Brings the database back to a consistent state after an unclean shutdown.
+///
+/// Follows ARIES: an analysis pass over the log finds the transactions that
+/// never finished and the pages that may be stale, a redo pass replays history
+/// onto every page whose LSN shows it missed a change (committed or not), and
+/// an undo pass rolls back the unfinished transactions, logging a compensation
+/// record for each change it reverts. Because redo checks the page LSN and undo
+/// skips whatever compensation records already cover, running recovery again
+/// after a crash part way through is safe.
+///
+/// Analysis starts from the tables saved by the last checkpoint, if there is
+/// one, and redo from the oldest change the checkpoint's dirty page table says
+/// may not be on disk, so neither has to read the log from the beginning.
+///
+/// Finishes by flushing the log and every dirty page.
+pub fn recover(buffer_pool: &Arc<Mutex<BufferPool>>, log_manager: &Arc<Mutex<LogManager>>) -> Result<RecoveryReport, RecoveryError> {
+    let records = log_manager.lock().unwrap().read_records()?;
+    let analysis = analyze(&records);
+
+    let redone = redo(buffer_pool, &records, &analysis.dirty_pages)?;
+
+    let mut rolled_back: Vec<TxnId> = analysis.active_txns.keys().copied().collect();
+    rolled_back.sort();
+    undo(buffer_pool, log_manager, &records, analysis.active_txns, INVALID_LSN)?;
+
+    log_manager.lock().unwrap().flush()?;
+    buffer_pool.lock().unwrap().flush_all()?;
+    Ok(RecoveryReport { redone, rolled_back })
+}
+
+/// Undoes every change of transaction `txn_id`, whose last record is `last_lsn`,
+/// and logs its abort.
+pub fn rollback(buffer_pool: &Arc<Mutex<BufferPool>>, log_manager: &Arc<Mutex<LogManager>>, txn_id: TxnId, last_lsn: Lsn) -> Result<(), RecoveryError> {
+    let records = {
+        let mut log_manager = log_manager.lock().unwrap();
+        // the records to undo may still be sitting in the log buffer
+        log_manager.flush()?;
+        log_manager.read_records()?
+    };
+    undo(buffer_pool, log_manager, &records, HashMap::from([(txn_id, last_lsn)]), INVALID_LSN)?;
+    Ok(())
+}
+
+/// Undoes the changes transaction `txn_id` logged after `savepoint`, the LSN of
+/// its last record when the savepoint was set, and returns the LSN of its last
+/// record now. The transaction keeps running; the compensation records chain
+/// on after `last_lsn`, and the last one points back at `savepoint`, so a
+/// rollback of the whole transaction (or recovery, after a crash) goes straight
+/// on to what came before it.
+pub fn rollback_to(buffer_pool: &Arc<Mutex<BufferPool>>, log_manager: &Arc<Mutex<LogManager>>, txn_id: TxnId, last_lsn: Lsn, savepoint: Lsn) -> Result<Lsn, RecoveryError> {
+    let records = {
+        let mut log_manager = log_manager.lock().unwrap();
+        log_manager.flush()?;
+        log_manager.read_records()?
+    };
+    let last_lsns = undo(buffer_pool, log_manager, &records, HashMap::from([(txn_id, last_lsn)]), savepoint)?;
+    Ok(last_lsns[&txn_id])
+}
+
+fn analyze(records: &[LogRecord]) -> Analysis {
+    let mut active_txns = HashMap::new();
+    let mut dirty_pages = HashMap::new();
+
+    // the last checkpoint already summarizes everything before it
+    let checkpoint = records.iter().enumerate().rev().find_map(|(position, record)| match &record.body {
+        LogRecordBody::Checkpoint { active_txns, dirty_pages, .. } => Some((position, active_txns, dirty_pages)),
+        _ => None,
+    });
+    let mut start = 0;
+    if let Some((position, checkpoint_txns, checkpoint_pages)) = checkpoint {
+        active_txns.extend(checkpoint_txns.iter().map(|txn| (txn.txn_id, txn.last_lsn)));
+        dirty_pages.extend(checkpoint_pages.iter().copied());
+        start = position + 1;
+    }
+
+    for record in &records[start..] {
+        match record.body {
+            LogRecordBody::Commit | LogRecordBody::Abort => {
+                active_txns.remove(&record.txn_id);
+            }
+            _ => {
+                active_txns.insert(record.txn_id, record.lsn);
+            }
+        }
+        if let Some(page_id) = record.body.page_id() {
+            dirty_pages.entry(page_id).or_insert(record.lsn);
+        }
+    }
+
+    Analysis { active_txns, dirty_pages }
+}
+
+fn redo(buffer_pool: &Arc<Mutex<BufferPool>>, records: &[LogRecord], dirty_pages: &HashMap<u32, Lsn>) -> Result<usize, RecoveryError> {
+    let Some(&redo_start) = dirty_pages.values().min() else {
+        return Ok(0);
+    };
+
+    let mut buffer_pool = buffer_pool.lock().unwrap();
+    let mut redone = 0;
+    for record in records.iter().filter(|record| record.lsn >= redo_start) {
+        let Some(page_id) = record.body.page_id() else {
+            continue;
+        };
+        if dirty_pages.get(&page_id).is_none_or(|&rec_lsn| record.lsn < rec_lsn) {
+            continue;
+        }
+
+        // the allocation itself may be one of the things that never reached disk
+        buffer_pool.ensure_allocated(page_id)?;
+        if buffer_pool.get_page(page_id)?.lsn() >= record.lsn {
+            // the page was written out after this change, replaying it again would apply it twice
+            continue;
+        }
+
+        let page = buffer_pool.get_page_mut(page_id)?;
+        apply(page, &record.body)?;
+        page.set_lsn(record.lsn);
+        redone += 1;
+    }
+
+    Ok(redone)
+}
+
+/// Rolls back the transactions in `to_undo` (keyed by id, with the LSN of the next
+/// record to undo), newest change first across all of them, as far back as
+/// `until`. Those rolled back all the way (`until` is `INVALID_LSN`) are logged
+/// as aborted. Returns the LSN of each transaction's last record afterwards.
+fn undo(buffer_pool: &Arc<Mutex<BufferPool>>, log_manager: &Arc<Mutex<LogManager>>, records: &[LogRecord], mut to_undo: HashMap<TxnId, Lsn>, until: Lsn) -> Result<HashMap<TxnId, Lsn>, RecoveryError> {
+    let records_by_lsn: HashMap<Lsn, &LogRecord> = records.iter().map(|record| (record.lsn, record)).collect();
+    // compensation records continue each transaction's prev_lsn chain
+    let mut last_lsns = to_undo.clone();
+    let mut buffer_pool = buffer_pool.lock().unwrap();
+
+    while let Some((&txn_id, &lsn)) = to_undo.iter().max_by_key(|&(_, &lsn)| lsn) {
+        if lsn <= until {
+            // nothing left to undo, the transaction is rolled back as far as it goes
+            if until == INVALID_LSN {
+                let abort_lsn = log_manager.lock().unwrap().append(txn_id, last_lsns[&txn_id], LogRecordBody::Abort);
+                last_lsns.insert(txn_id, abort_lsn);
+            }
+            to_undo.remove(&txn_id);
+            continue;
+        }
+
+        let record = records_by_lsn.get(&lsn).ok_or(RecoveryError::MissingLogRecord(lsn))?;
+        let next_lsn = match &record.body {
+            // already undone before the crash, skip straight past what it covered
+            LogRecordBody::Compensation { undo_next_lsn, .. } => *undo_next_lsn,
+            body => {
+                if let Some(action) = compensating_action(body) {
+                    let page_id = action.page_id().unwrap();
+                    // don't hold the log lock here, evicting a page may need to flush the log
+                    let page = buffer_pool.get_page_mut(page_id)?;
+                    apply(page, &action)?;
+                    let compensation = LogRecordBody::Compensation { undo_next_lsn: record.prev_lsn, action: Box::new(action) };
+                    let clr_lsn = log_manager.lock().unwrap().append(txn_id, last_lsns[&txn_id], compensation);
+                    page.set_lsn(clr_lsn);
+                    last_lsns.insert(txn_id, clr_lsn);
+                }
+                record.prev_lsn
+            }
+        };
+        to_undo.insert(txn_id, next_lsn);
+    }
+
+    Ok(last_lsns)
+}
+
+/// the change that reverts `body`, `None` for records that don't change tuples
+fn compensating_action(body: &LogRecordBody) -> Option<LogRecordBody> {
+    match body {
+        LogRecordBody::Insert { rid, tuple } => Some(LogRecordBody::Delete { rid: *rid, tuple: tuple.clone() }),
+        LogRecordBody::Update { rid, before, after } => Some(LogRecordBody::Update { rid: *rid, before: after.clone(), after: before.clone() }),
+        LogRecordBody::Delete { rid, tuple } => Some(LogRecordBody::Insert { rid: *rid, tuple: tuple.clone() }),
+        _ => None,
+    }
+}
+
+/// applies the change described by `body` to `page`
+fn apply(page: &mut Page, body: &LogRecordBody) -> Result<(), PageError> {
+    match body {
+        LogRecordBody::Insert { rid, tuple } => page.insert_tuple_at(rid.slot_id, tuple),
+        LogRecordBody::Update { rid, after, .. } => page.update_tuple(rid.slot_id, after).map(|_| ()),
+        LogRecordBody::Delete { rid, .. } => page.delete_tuple(rid.slot_id),
+        LogRecordBody::Compensation { action, .. } => apply(page, action),
+        // the caller made sure the page exists, it only needs its type
+        LogRecordBody::AllocatePage { page_type, .. } => {
+            page.set_page_type(*page_type);
+            Ok(())
+        }
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::{DiskManager, HeapFile, PageType, RecordId};
+    use crate::transaction::TransactionManager;
+    use tempfile::TempDir;
+
+    /// buffer pool and log over the database in `dir`, as if the process had just started
+    fn open(dir: &TempDir) -> (Arc<Mutex<BufferPool>>, Arc<Mutex<LogManager>>) {
+        let disk_manager = DiskManager::open(dir.path().join("test.db")).unwrap();
+        let log_manager = Arc::new(Mutex::new(LogManager::open(dir.path().join("test.wal")).unwrap()));
+        let mut buffer_pool = BufferPool::new(disk_manager);
+        buffer_pool.set_log_manager(log_manager.clone());
+        (Arc::new(Mutex::new(buffer_pool)), log_manager)
+    }
+
+    /// heap file rooted at `root_page_id`, or a new one if there is none yet
+    fn logged_heap(buffer_pool: &Arc<Mutex<BufferPool>>, log_manager: &Arc<Mutex<LogManager>>, root_page_id: Option<u32>) -> HeapFile {
+        let mut heap = match root_page_id {
+            Some(root_page_id) => HeapFile::open(buffer_pool.clone(), root_page_id).unwrap(),
+            None => HeapFile::create(buffer_pool.clone()).unwrap(),
+        };
+        heap.set_log_manager(log_manager.clone());
+        heap
+    }
+
+    #[test]
+    fn test_committed_work_survives_crash_without_page_flush() {
+        let dir = TempDir::new().unwrap();
+        let (rid, root_page_id) = {
+            let (buffer_pool, log_manager) = open(&dir);
+            let mut heap = logged_heap(&buffer_pool, &log_manager, None);
+            let mut transaction_manager = TransactionManager::new(buffer_pool.clone(), log_manager.clone());
+
+            let mut txn = transaction_manager.begin();
+            let rid = heap.insert(&mut txn, b"committed").unwrap();
+            transaction_manager.commit(txn).unwrap();
+            // crash: the buffer pool goes away without writing anything back
+            (rid, heap.root_page_id())
+        };
+
+        let (buffer_pool, log_manager) = open(&dir);
+        let report = recover(&buffer_pool, &log_manager).unwrap();
+        assert!(report.redone > 0);
+        assert!(report.rolled_back.is_empty());
+
+        let heap = logged_heap(&buffer_pool, &log_manager, Some(root_page_id));
+        assert_eq!(heap.get(rid).unwrap(), b"committed");
+    }
+
+    #[test]
+    fn test_redone_allocation_restores_page_type() {
+        let dir = TempDir::new().unwrap();
+        let rid = {
+            let (buffer_pool, log_manager) = open(&dir);
+            let mut heap = HeapFile::create_of_type(buffer_pool.clone(), PageType::Catalog).unwrap();
+            heap.set_log_manager(log_manager.clone());
+            let mut transaction_manager = TransactionManager::new(buffer_pool.clone(), log_manager.clone());
+
+            let mut txn = transaction_manager.begin();
+            let rid = heap.insert(&mut txn, b"committed").unwrap();
+            transaction_manager.commit(txn).unwrap();
+            rid
+        };
+
+        // the data page never reached disk, so it reads back as a blank heap page until redo
+        let (buffer_pool, log_manager) = open(&dir);
+        recover(&buffer_pool, &log_manager).unwrap();
+        assert_eq!(buffer_pool.lock().unwrap().get_page(rid.page_id).unwrap().page_type(), PageType::Catalog);
+    }
+
+    #[test]
+    fn test_recovers_from_torn_file_extension() {
+        let dir = TempDir::new().unwrap();
+        let (rid, root_page_id) = {
+            let (buffer_pool, log_manager) = open(&dir);
+            let mut heap = logged_heap(&buffer_pool, &log_manager, None);
+            let mut transaction_manager = TransactionManager::new(buffer_pool.clone(), log_manager.clone());
+
+            let mut txn = transaction_manager.begin();
+            let rid = heap.insert(&mut txn, b"committed").unwrap();
+            transaction_manager.commit(txn).unwrap();
+            (rid, heap.root_page_id())
+        };
+        // the crash came part way through writing the heap's page at the end of the file
+        let path = dir.path().join("test.db");
+        let length = std::fs::metadata(&path).unwrap().len();
+        std::fs::OpenOptions::new().write(true).open(&path).unwrap().set_len(length - 100).unwrap();
+
+        let (buffer_pool, log_manager) = open(&dir);
+        recover(&buffer_pool, &log_manager).unwrap();
+        let heap = logged_heap(&buffer_pool, &log_manager, Some(root_page_id));
+        assert_eq!(heap.get(rid).unwrap(), b"committed");
+    }
+
+    #[test]
+    fn test_uncommitted_work_rolled_back_after_crash() {
+        let dir = TempDir::new().unwrap();
+        let (kept, lost, updated, deleted, root_page_id, loser) = {
+            let (buffer_pool, log_manager) = open(&dir);
+            let mut heap = logged_heap(&buffer_pool, &log_manager, None);
+            let mut transaction_manager = TransactionManager::new(buffer_pool.clone(), log_manager.clone());
+
+            let mut setup = transaction_manager.begin();
+            let kept = heap.insert(&mut setup, b"kept").unwrap();
+            let updated = heap.insert(&mut setup, b"original").unwrap();
+            let deleted = heap.insert(&mut setup, b"deleted then restored").unwrap();
+            transaction_manager.commit(setup).unwrap();
+
+            let mut txn = transaction_manager.begin();
+            let lost = heap.insert(&mut txn, b"never committed").unwrap();
+            heap.update(&mut txn, updated, b"overwritten").unwrap();
+            heap.delete(&mut txn, deleted).unwrap();
+            // the uncommitted changes reach the database file before the crash
+            buffer_pool.lock().unwrap().flush_all().unwrap();
+            (kept, lost, updated, deleted, heap.root_page_id(), txn.id())
+        };
+
+        let (buffer_pool, log_manager) = open(&dir);
+        let report = recover(&buffer_pool, &log_manager).unwrap();
+        assert_eq!(report.rolled_back, vec![loser]);
+
+        let heap = logged_heap(&buffer_pool, &log_manager, Some(root_page_id));
+        assert_eq!(heap.get(kept).unwrap(), b"kept");
+        assert_eq!(heap.get(updated).unwrap(), b"original");
+        assert_eq!(heap.get(deleted).unwrap(), b"deleted then restored");
+        assert!(matches!(heap.get(lost), Err(crate::storage::HeapError::PageError(PageError::TupleNotFound))));
+
+        // the rollback was logged, so the loser is finished as far as the log is concerned
+        let records = log_manager.lock().unwrap().read_records().unwrap();
+        let compensations = records
+            .iter()
+            .filter(|record| record.txn_id == loser && matches!(record.body, LogRecordBody::Compensation { .. }))
+            .count();
+        assert_eq!(compensations, 3);
+        assert_eq!(records.last().unwrap().body, LogRecordBody::Abort);
+        assert!(analyze(&records).active_txns.is_empty());
+    }
+
+    #[test]
+    fn test_recovery_is_idempotent() {
+        let dir = TempDir::new().unwrap();
+        let (rid, root_page_id) = {
+            let (buffer_pool, log_manager) = open(&dir);
+            let mut heap = logged_heap(&buffer_pool, &log_manager, None);
+            let mut transaction_manager = TransactionManager::new(buffer_pool.clone(), log_manager.clone());
+
+            let mut txn = transaction_manager.begin();
+            let rid = heap.insert(&mut txn, b"first").unwrap();
+            heap.update(&mut txn, rid, b"second").unwrap();
+            transaction_manager.commit(txn).unwrap();
+            // left running, so recovery has something to undo too
+            let mut loser = transaction_manager.begin();
+            heap.insert(&mut loser, b"loser").unwrap();
+            log_manager.lock().unwrap().flush().unwrap();
+            (rid, heap.root_page_id())
+        };
+
+        {
+            let (buffer_pool, log_manager) = open(&dir);
+            recover(&buffer_pool, &log_manager).unwrap();
+        }
+
+        // everything recovery did is already on the pages, and the loser is already aborted
+        let (buffer_pool, log_manager) = open(&dir);
+        let report = recover(&buffer_pool, &log_manager).unwrap();
+        assert_eq!(report, RecoveryReport::default());
+
+        let heap = logged_heap(&buffer_pool, &log_manager, Some(root_page_id));
+        assert_eq!(heap.get(rid).unwrap(), b"second");
+        assert_eq!(heap.get(RecordId::new(rid.page_id, rid.slot_id + 1)).ok(), None);
+    }
+
+    #[test]
+    fn test_abort_restores_previous_versions() {
+        let dir = TempDir::new().unwrap();
+        let (buffer_pool, log_manager) = open(&dir);
+        let mut heap = logged_heap(&buffer_pool, &log_manager, None);
+        let mut transaction_manager = TransactionManager::new(buffer_pool.clone(), log_manager.clone());
+
+        let mut setup = transaction_manager.begin();
+        let rid = heap.insert(&mut setup, b"original").unwrap();
+        transaction_manager.commit(setup).unwrap();
+
+        let mut txn = transaction_manager.begin();
+        heap.update(&mut txn, rid, b"changed").unwrap();
+        heap.delete(&mut txn, rid).unwrap();
+        // the first insert takes over the slot the delete freed, the second needs a new one
+        assert_eq!(heap.insert(&mut txn, b"reused").unwrap(), rid);
+        let inserted = heap.insert(&mut txn, b"inserted").unwrap();
+        transaction_manager.abort(txn).unwrap();
+
+        assert_eq!(heap.get(rid).unwrap(), b"original");
+        assert!(heap.get(inserted).is_err());
+    }
+
+    #[test]
+    fn test_crash_after_rollback_to_savepoint() {
+        let dir = TempDir::new().unwrap();
+        let (original, inserted, root_page_id) = {
+            let (buffer_pool, log_manager) = open(&dir);
+            let mut heap = logged_heap(&buffer_pool, &log_manager, None);
+            let mut transaction_manager = TransactionManager::new(buffer_pool.clone(), log_manager.clone());
+
+            let mut setup = transaction_manager.begin();
+            let original = heap.insert(&mut setup, b"original").unwrap();
+            transaction_manager.commit(setup).unwrap();
+
+            let mut txn = transaction_manager.begin();
+            heap.update(&mut txn, original, b"before the savepoint").unwrap();
+            let savepoint = txn.prev_lsn();
+            let inserted = heap.insert(&mut txn, b"after the savepoint").unwrap();
+            transaction_manager.rollback_to(&mut txn, savepoint).unwrap();
+            buffer_pool.lock().unwrap().flush_all().unwrap();
+            (original, inserted, heap.root_page_id())
+        };
+
+        // recovery picks up where the partial rollback left off, without undoing the insert twice
+        let (buffer_pool, log_manager) = open(&dir);
+        let report = recover(&buffer_pool, &log_manager).unwrap();
+        assert_eq!(report.rolled_back.len(), 1);
+        let heap = logged_heap(&buffer_pool, &log_manager, Some(root_page_id));
+        assert_eq!(heap.get(original).unwrap(), b"original");
+        assert!(heap.get(inserted).is_err());
+    }
+
+    #[test]
+    fn test_rollback_keeps_new_pages_in_heap() {
+        let dir = TempDir::new().unwrap();
+        let (rid, root_page_id) = {
+            let (buffer_pool, log_manager) = open(&dir);
+            let mut heap = logged_heap(&buffer_pool, &log_manager, None);
+            let mut transaction_manager = TransactionManager::new(buffer_pool.clone(), log_manager.clone());
+
+            // the first insert allocates the heap's first data page
+            let mut txn = transaction_manager.begin();
+            heap.insert(&mut txn, b"rolled back").unwrap();
+            transaction_manager.abort(txn).unwrap();
+
+            let mut txn = transaction_manager.begin();
+            let rid = heap.insert(&mut txn, b"committed").unwrap();
+            transaction_manager.commit(txn).unwrap();
+            (rid, heap.root_page_id())
+        };
+
+        let (buffer_pool, log_manager) = open(&dir);
+        recover(&buffer_pool, &log_manager).unwrap();
+        let heap = logged_heap(&buffer_pool, &log_manager, Some(root_page_id));
+        assert_eq!(heap.page_ids(), &[rid.page_id]);
+        assert_eq!(heap.get(rid).unwrap(), b"committed");
+    }
+}
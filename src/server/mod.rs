@@ -0,0 +1,704 @@
+mod protocol;
+use protocol::{Body, CANCEL_REQUEST, Format, GSSENC_REQUEST, Message, PROTOCOL_VERSION, SSL_REQUEST, decode_value, encode_value, oid_type, read_message, read_startup, row_description, type_oid};
+
+use crate::catalog::CatalogError;
+use crate::execution::{ExecutionError, QueryResult};
+use crate::sql::{self, ParseError, Token};
+use crate::types::{DataType, Value};
+use crate::{Connection, Database, DatabaseError, Statement, TransactionStatus};
+use std::collections::HashMap;
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+
+/// the port PostgreSQL listens on, which clients connect to unless told otherwise
+pub const DEFAULT_PORT: u16 = 5432;
+
+/// the version of PostgreSQL the server tells clients it is, which some use to
+/// decide what they can ask for
+const SERVER_VERSION: &str = "14.0";
+
+#[derive(Debug)]
+pub enum ServerError {
+    IoError(std::io::Error),
+    /// a message that doesn't follow the protocol, after which the session ends
+    InvalidMessage(String),
+    /// a startup message for a version of the protocol other than 3
+    UnsupportedProtocol(i32),
+    /// a parameter value that isn't one of the type it's sent as
+    InvalidValue(String),
+    /// a `Bind`, `Describe` or `Close` for a prepared statement that doesn't exist
+    UnknownStatement(String),
+    /// a `Describe`, `Execute` or `Close` for a portal that doesn't exist
+    UnknownPortal(String),
+    DatabaseError(DatabaseError),
+}
+
+impl std::fmt::Display for ServerError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ServerError::IoError(error) => write!(f, "I/O error: {error}"),
+            ServerError::InvalidMessage(message) => write!(f, "Invalid message: {message}"),
+            ServerError::UnsupportedProtocol(version) => write!(f, "Unsupported frontend protocol {}.{}", version >> 16, version & 0xffff),
+            ServerError::InvalidValue(message) => write!(f, "Invalid value: {message}"),
+            ServerError::UnknownStatement(name) => write!(f, "Prepared statement \"{name}\" does not exist"),
+            ServerError::UnknownPortal(name) => write!(f, "Portal \"{name}\" does not exist"),
+            ServerError::DatabaseError(error) => write!(f, "{error}"),
+        }
+    }
+}
+
+impl std::error::Error for ServerError {}
+
+impl From<std::io::Error> for ServerError {
+    fn from(error: std::io::Error) -> Self {
+        ServerError::IoError(error)
+    }
+}
+
+impl From<DatabaseError> for ServerError {
+    fn from(error: DatabaseError) -> Self {
+        ServerError::DatabaseError(error)
+    }
+}
+
+impl From<ParseError> for ServerError {
+    fn from(error: ParseError) -> Self {
+        ServerError::DatabaseError(error.into())
+    }
+}
+
+impl ServerError {
+    /// whether the session can't go on after the error
+    fn is_fatal(&self) -> bool {
+        matches!(self, ServerError::IoError(_) | ServerError::InvalidMessage(_) | ServerError::UnsupportedProtocol(_))
+    }
+
+    /// the SQLSTATE code clients are told the error has
+    fn code(&self) -> &'static str {
+        match self {
+            ServerError::IoError(_) => "58030",
+            ServerError::InvalidMessage(_) | ServerError::UnsupportedProtocol(_) => "08P01",
+            ServerError::InvalidValue(_) => "22P02",
+            ServerError::UnknownStatement(_) => "26000",
+            ServerError::UnknownPortal(_) => "34000",
+            ServerError::DatabaseError(DatabaseError::ParseError(ParseError::InvalidLiteral { .. })) => "22007",
+            ServerError::DatabaseError(DatabaseError::ParseError(_)) => "42601",
+            ServerError::DatabaseError(DatabaseError::TransactionAborted) => "25P02",
+            ServerError::DatabaseError(DatabaseError::TransactionInProgress | DatabaseError::IsolationLevelAfterQuery | DatabaseError::InTransactionBlock(_)) => "25001",
+            ServerError::DatabaseError(DatabaseError::NoTransaction(_)) => "25P01",
+            ServerError::DatabaseError(DatabaseError::UnknownSavepoint(_)) => "3B001",
+            ServerError::DatabaseError(DatabaseError::SerializationFailure) => "40001",
+            ServerError::DatabaseError(DatabaseError::Deadlock) => "40P01",
+            ServerError::DatabaseError(DatabaseError::ExecutionError(error)) => match error {
+                ExecutionError::TableNotFound(_) | ExecutionError::CatalogError(CatalogError::TableNotFound(_)) => "42P01",
+                ExecutionError::ColumnNotFound(_) => "42703",
+                ExecutionError::AmbiguousColumn(_) => "42702",
+                ExecutionError::DuplicateTable(_) => "42712",
+                ExecutionError::DuplicateColumn(_) | ExecutionError::CatalogError(CatalogError::DuplicateColumn(_)) => "42701",
+                ExecutionError::TooManyTables => "54000",
+                ExecutionError::ValueCountMismatch { .. } | ExecutionError::WildcardWithoutTable => "42601",
+                ExecutionError::AggregateNotAllowed(_) | ExecutionError::UngroupedColumn(_) => "42803",
+                ExecutionError::InvalidOrderByPosition(_) => "42P10",
+                ExecutionError::MissingParameter(_) => "42P02",
+                ExecutionError::ParameterCountMismatch { .. } => "08P01",
+                ExecutionError::TypeError(_) => "42804",
+                ExecutionError::DivisionByZero => "22012",
+                ExecutionError::NumericOverflow => "22003",
+                ExecutionError::InvalidDatetime { .. } => "22007",
+                ExecutionError::DatetimeOverflow => "22008",
+                ExecutionError::UniqueViolation { .. } => "23505",
+                ExecutionError::NullViolation { .. } => "23502",
+                ExecutionError::CheckViolation { .. } => "23514",
+                ExecutionError::InvalidCsv { .. } => "22P04",
+                ExecutionError::CatalogError(CatalogError::TableExists(_) | CatalogError::IndexExists(_)) => "42P07",
+                ExecutionError::CatalogError(CatalogError::MultiplePrimaryKeys(_)) => "42P16",
+                ExecutionError::CatalogError(CatalogError::ConstraintExists(_)) => "42710",
+                ExecutionError::InvalidConstraint(_) => "42P17",
+                _ => "XX000",
+            },
+            ServerError::DatabaseError(_) => "XX000",
+        }
+    }
+}
+
+/// A server speaking the PostgreSQL frontend/backend protocol (version 3), so
+/// `psql` and PostgreSQL drivers can run SQL on a database over TCP.
+///
+/// Each client gets a `Connection` of its own, served on a thread of its own.
+/// Both the simple query protocol (`Query`) and the extended one (`Parse`,
+/// `Bind`, `Describe`, `Execute`, `Sync`) are spoken; statements parsed by the
+/// latter are `Statement`s, which share the database's plan cache. There's no
+/// authentication or encryption: every client is let in, over plain TCP.
+///
+/// # Examples
+///
+/// ```no_run
+/// use gondor_rdbms::Database;
+/// use gondor_rdbms::server::Server;
+///
+/// let database = Database::open("shire.db").unwrap();
+/// let server = Server::bind(&database, "127.0.0.1:5432").unwrap();
+/// // psql -h 127.0.0.1 can connect now
+/// server.run();
+/// ```
+pub struct Server {
+    database: Database,
+    listener: TcpListener,
+}
+
+impl Server {
+    /// Listens on `address` for clients of `database`, who are served once `run` is called.
+    pub fn bind(database: &Database, address: impl ToSocketAddrs) -> std::io::Result<Self> {
+        Ok(Self { database: database.clone(), listener: TcpListener::bind(address)? })
+    }
+
+    /// The address the server listens on, with the port picked if it was bound to port 0.
+    pub fn local_addr(&self) -> std::io::Result<SocketAddr> {
+        self.listener.local_addr()
+    }
+
+    /// Serves clients until the process ends. A session that fails ends
+    /// without affecting the others.
+    pub fn run(self) {
+        for (process_id, stream) in (1..).zip(self.listener.incoming()) {
+            // a client that went away before it was accepted is just dropped
+            let Ok(stream) = stream else { continue };
+            let session = Session::new(self.database.connect(), process_id);
+            std::thread::spawn(move || session.serve(stream));
+        }
+    }
+}
+
+/// A statement prepared by a `Parse` message.
+#[derive(Clone)]
+struct Prepared {
+    /// `None` for a query with no statement in it
+    statement: Option<Statement>,
+    /// the types of the values the client sends for the parameters: those it
+    /// gave when it prepared the statement, the statement's own otherwise
+    parameter_types: Vec<Option<DataType>>,
+}
+
+/// A prepared statement with values for its parameters, made by a `Bind` message.
+struct Portal {
+    statement: Option<Statement>,
+    parameters: Vec<Value>,
+    /// the format each column of the rows is sent in
+    formats: Vec<Format>,
+    /// what the statement returned, once an `Execute` has run it
+    result: Option<PortalResult>,
+}
+
+enum PortalResult {
+    /// the rows of a query, those left to send
+    Rows(std::vec::IntoIter<Vec<Value>>),
+    /// the command tag of anything else
+    Done(String),
+}
+
+/// a client's session, from its startup message until it goes away
+struct Session {
+    connection: Connection,
+    process_id: i32,
+    /// by name, the unnamed one under ""
+    statements: HashMap<String, Prepared>,
+    /// by name, the unnamed one under ""
+    portals: HashMap<String, Portal>,
+    output: Vec<u8>,
+}
+
+impl Session {
+    fn new(connection: Connection, process_id: i32) -> Self {
+        Self { connection, process_id, statements: HashMap::new(), portals: HashMap::new(), output: Vec::new() }
+    }
+
+    /// serves the client on the other end of `stream` until it goes away
+    fn serve(mut self, stream: TcpStream) -> Result<(), ServerError> {
+        // replies are small and the client waits on each
+        stream.set_nodelay(true)?;
+        let mut input = BufReader::new(stream.try_clone()?);
+        let mut output = BufWriter::new(stream);
+        let result = self.run(&mut input, &mut output);
+        output.write_all(&self.output)?;
+        output.flush()?;
+        result
+    }
+
+    fn run(&mut self, input: &mut impl Read, output: &mut impl Write) -> Result<(), ServerError> {
+        if !self.start(input, output)? {
+            return Ok(());
+        }
+        // after an error in the extended protocol, messages are skipped up to the next Sync
+        let mut skipping = false;
+        loop {
+            let Some((tag, body)) = read_message(input)? else {
+                return Ok(());
+            };
+            if skipping && tag != b'S' && tag != b'X' {
+                continue;
+            }
+            let mut body = Body::new(&body);
+            let result = match tag {
+                b'Q' => self.query(&mut body),
+                b'P' => self.parse(&mut body),
+                b'B' => self.bind(&mut body),
+                b'D' => self.describe(&mut body),
+                b'E' => self.execute(&mut body),
+                b'C' => self.close(&mut body),
+                b'S' => {
+                    skipping = false;
+                    Ok(())
+                }
+                b'H' => {
+                    output.write_all(&std::mem::take(&mut self.output))?;
+                    output.flush()?;
+                    Ok(())
+                }
+                b'X' => return Ok(()),
+                tag => Err(ServerError::InvalidMessage(format!("unknown message type {:?}", tag as char))),
+            };
+            if let Err(error) = result {
+                self.error(&error);
+                if error.is_fatal() {
+                    return Err(error);
+                }
+                skipping = tag != b'Q';
+            }
+            if tag == b'Q' || tag == b'S' {
+                self.ready();
+                output.write_all(&std::mem::take(&mut self.output))?;
+                output.flush()?;
+            }
+        }
+    }
+
+    /// Handles the startup message, turning down requests for encryption,
+    /// and lets the client in. Returns false if the client went away instead.
+    fn start(&mut self, input: &mut impl Read, output: &mut impl Write) -> Result<bool, ServerError> {
+        loop {
+            let Some((version, _)) = read_startup(input)? else {
+                return Ok(false);
+            };
+            match version {
+                // the client can go on unencrypted, or give up
+                SSL_REQUEST | GSSENC_REQUEST => {
+                    output.write_all(b"N")?;
+                    output.flush()?;
+                }
+                // statements can't be cancelled, they run to the end
+                CANCEL_REQUEST => return Ok(false),
+                // the parameters (user, database...) make no difference
+                version if version >> 16 == PROTOCOL_VERSION >> 16 => {
+                    if version != PROTOCOL_VERSION {
+                        // a later minor version, which the client can fall back from
+                        self.send(Message::new(b'v').i32(PROTOCOL_VERSION).i32(0).finish());
+                    }
+                    break;
+                }
+                version => {
+                    let error = ServerError::UnsupportedProtocol(version);
+                    self.error(&error);
+                    return Err(error);
+                }
+            }
+        }
+
+        self.send(Message::new(b'R').i32(0).finish());
+        let parameters = [
+            ("server_version", SERVER_VERSION),
+            ("server_encoding", "UTF8"),
+            ("client_encoding", "UTF8"),
+            ("DateStyle", "ISO, MDY"),
+            ("integer_datetimes", "on"),
+            ("standard_conforming_strings", "on"),
+        ];
+        for (name, value) in parameters {
+            self.send(Message::new(b'S').string(name).string(value).finish());
+        }
+        self.send(Message::new(b'K').i32(self.process_id).i32(0).finish());
+        self.ready();
+        output.write_all(&std::mem::take(&mut self.output))?;
+        output.flush()?;
+        Ok(true)
+    }
+
+    /// sends a ReadyForQuery, with whether the connection is in a transaction block
+    fn ready(&mut self) {
+        let status = match self.connection.transaction_status() {
+            TransactionStatus::Idle => b'I',
+            TransactionStatus::InTransaction => b'T',
+            TransactionStatus::Failed => b'E',
+        };
+        self.send(Message::new(b'Z').u8(status).finish());
+    }
+
+    fn send(&mut self, message: Vec<u8>) {
+        self.output.extend_from_slice(&message);
+    }
+
+    /// sends an ErrorResponse for `error`
+    fn error(&mut self, error: &ServerError) {
+        let severity = if error.is_fatal() { "FATAL" } else { "ERROR" };
+        let message = Message::new(b'E').u8(b'S').string(severity).u8(b'V').string(severity).u8(b'C').string(error.code()).u8(b'M').string(&error.to_string()).u8(0);
+        self.send(message.finish());
+    }
+
+    /// a `Query`: runs each statement of the SQL in it, all of whose values are sent as text
+    fn query(&mut self, body: &mut Body) -> Result<(), ServerError> {
+        let statements = sql::parse(&body.string()?)?;
+        if statements.is_empty() {
+            self.send(Message::new(b'I').finish());
+        }
+        for statement in &statements {
+            // later statements may depend on this one, so they aren't run if it fails
+            match self.connection.run(statement)? {
+                QueryResult::Rows { columns, rows } => {
+                    // there's no plan to tell the types from, but the values have them
+                    let types: Vec<Option<DataType>> = (0..columns.len()).map(|i| rows.iter().find_map(|row| row[i].data_type())).collect();
+                    let formats = vec![Format::Text; columns.len()];
+                    self.send(row_description(&columns, &types, &formats));
+                    for row in &rows {
+                        self.data_row(row, &formats);
+                    }
+                    self.send(Message::new(b'C').string(&command_tag(statement, rows.len())).finish());
+                }
+                QueryResult::Affected(count) => self.send(Message::new(b'C').string(&command_tag(statement, count)).finish()),
+            }
+        }
+        Ok(())
+    }
+
+    /// a `Parse`: prepares a statement, under a name or as the unnamed one
+    fn parse(&mut self, body: &mut Body) -> Result<(), ServerError> {
+        let (name, sql) = (body.string()?, body.string()?);
+        let count = body.i16()?;
+        let oids = (0..count).map(|_| body.i32()).collect::<Result<Vec<i32>, _>>()?;
+        let prepared = if sql::tokenize(&sql)?.iter().all(|token| *token == Token::Semicolon) {
+            Prepared { statement: None, parameter_types: Vec::new() }
+        } else {
+            let statement = self.connection.prepare(&sql)?;
+            let parameter_types = statement
+                .parameter_types()
+                .iter()
+                .enumerate()
+                .map(|(i, data_type)| oids.get(i).and_then(|&oid| oid_type(oid)).or(*data_type))
+                .collect();
+            Prepared { statement: Some(statement), parameter_types }
+        };
+        self.statements.insert(name, prepared);
+        self.send(Message::new(b'1').finish());
+        Ok(())
+    }
+
+    /// a `Bind`: gives the parameters of a prepared statement values, as a portal
+    fn bind(&mut self, body: &mut Body) -> Result<(), ServerError> {
+        let (portal, name) = (body.string()?, body.string()?);
+        let prepared = self.statements.get(&name).ok_or(ServerError::UnknownStatement(name))?.clone();
+        let format_codes = body.i16s()?;
+        let values = (0..body.i16()?).map(|_| body.value()).collect::<Result<Vec<_>, _>>()?;
+        let formats = Format::for_each(&format_codes, values.len())?;
+        let parameters = values
+            .iter()
+            .zip(formats)
+            .enumerate()
+            .map(|(i, (value, format))| decode_value(*value, prepared.parameter_types.get(i).copied().flatten(), format))
+            .collect::<Result<Vec<Value>, _>>()?;
+        let columns = prepared.statement.as_ref().map_or(0, |statement| statement.column_names().len());
+        let formats = Format::for_each(&body.i16s()?, columns)?;
+        self.portals.insert(portal, Portal { statement: prepared.statement, parameters, formats, result: None });
+        self.send(Message::new(b'2').finish());
+        Ok(())
+    }
+
+    /// a `Describe`: the parameters and columns of a prepared statement, or the columns of a portal
+    fn describe(&mut self, body: &mut Body) -> Result<(), ServerError> {
+        let (kind, name) = (body.u8()?, body.string()?);
+        let (statement, formats) = match kind {
+            b'S' => {
+                let prepared = self.statements.get(&name).ok_or(ServerError::UnknownStatement(name))?.clone();
+                let mut message = Message::new(b't').i16(prepared.parameter_types.len() as i16);
+                for data_type in &prepared.parameter_types {
+                    message = message.i32(type_oid(*data_type));
+                }
+                self.send(message.finish());
+                // the formats aren't known until the statement is bound
+                let columns = prepared.statement.as_ref().map_or(0, |statement| statement.column_names().len());
+                (prepared.statement, vec![Format::Text; columns])
+            }
+            b'P' => {
+                let portal = self.portals.get(&name).ok_or(ServerError::UnknownPortal(name))?;
+                (portal.statement.clone(), portal.formats.clone())
+            }
+            kind => return Err(ServerError::InvalidMessage(format!("unknown Describe kind {:?}", kind as char))),
+        };
+        match statement.filter(|_| !formats.is_empty()) {
+            Some(statement) => self.send(row_description(&statement.column_names(), &statement.column_types(), &formats)),
+            None => self.send(Message::new(b'n').finish()),
+        }
+        Ok(())
+    }
+
+    /// an `Execute`: runs a portal's statement, and sends up to a number of its rows (all of them for 0)
+    fn execute(&mut self, body: &mut Body) -> Result<(), ServerError> {
+        let (name, max_rows) = (body.string()?, body.i32()?);
+        let mut portal = self.portals.remove(&name).ok_or_else(|| ServerError::UnknownPortal(name.clone()))?;
+        let result = self.execute_portal(&mut portal, max_rows);
+        self.portals.insert(name, portal);
+        result
+    }
+
+    fn execute_portal(&mut self, portal: &mut Portal, max_rows: i32) -> Result<(), ServerError> {
+        let Some(statement) = &mut portal.statement else {
+            self.send(Message::new(b'I').finish());
+            return Ok(());
+        };
+        if portal.result.is_none() {
+            portal.result = Some(match statement.run(&portal.parameters)? {
+                QueryResult::Rows { rows, .. } => PortalResult::Rows(rows.into_iter()),
+                QueryResult::Affected(count) => PortalResult::Done(command_tag(statement.parsed(), count)),
+            });
+        }
+        match portal.result.as_mut().unwrap() {
+            PortalResult::Rows(rows) => {
+                let count = if max_rows > 0 { (max_rows as usize).min(rows.len()) } else { rows.len() };
+                for row in rows.take(count).collect::<Vec<_>>() {
+                    self.data_row(&row, &portal.formats);
+                }
+                if rows.len() > 0 {
+                    self.send(Message::new(b's').finish());
+                } else {
+                    self.send(Message::new(b'C').string(&command_tag(statement.parsed(), count)).finish());
+                }
+            }
+            PortalResult::Done(tag) => {
+                let message = Message::new(b'C').string(tag).finish();
+                self.send(message);
+            }
+        }
+        Ok(())
+    }
+
+    /// a `Close`: drops a prepared statement or portal, if there is one
+    fn close(&mut self, body: &mut Body) -> Result<(), ServerError> {
+        let (kind, name) = (body.u8()?, body.string()?);
+        match kind {
+            b'S' => {
+                self.statements.remove(&name);
+            }
+            b'P' => {
+                self.portals.remove(&name);
+            }
+            kind => return Err(ServerError::InvalidMessage(format!("unknown Close kind {:?}", kind as char))),
+        }
+        self.send(Message::new(b'3').finish());
+        Ok(())
+    }
+
+    fn data_row(&mut self, row: &[Value], formats: &[Format]) {
+        let mut message = Message::new(b'D').i16(row.len() as i16);
+        for (value, format) in row.iter().zip(formats) {
+            message = message.value(encode_value(value, *format).as_deref());
+        }
+        self.send(message.finish());
+    }
+}
+
+/// the tag a CommandComplete for `statement` carries, having affected or returned `count` rows
+fn command_tag(statement: &sql::Statement, count: usize) -> String {
+    match statement {
+        sql::Statement::CreateTable(_) => "CREATE TABLE".to_string(),
+        sql::Statement::CreateIndex(_) => "CREATE INDEX".to_string(),
+        // the 0 is the OID of the row inserted, which rows don't have
+        sql::Statement::Insert(_) => format!("INSERT 0 {count}"),
+        sql::Statement::Select(_) => format!("SELECT {count}"),
+        sql::Statement::Update(_) => format!("UPDATE {count}"),
+        sql::Statement::Delete(_) => format!("DELETE {count}"),
+        sql::Statement::Analyze(_) => "ANALYZE".to_string(),
+        sql::Statement::Vacuum(_) => "VACUUM".to_string(),
+        sql::Statement::Explain(_) => "EXPLAIN".to_string(),
+        sql::Statement::Copy(_) => format!("COPY {count}"),
+        sql::Statement::Transaction(statement) => match statement {
+            sql::TransactionStatement::Begin(_) => "BEGIN",
+            sql::TransactionStatement::SetIsolationLevel(_) => "SET",
+            sql::TransactionStatement::Commit => "COMMIT",
+            sql::TransactionStatement::Rollback | sql::TransactionStatement::RollbackTo(_) => "ROLLBACK",
+            sql::TransactionStatement::Savepoint(_) => "SAVEPOINT",
+            sql::TransactionStatement::Release(_) => "RELEASE",
+        }
+        .to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    /// a client speaking the protocol to a server of its own, message by message
+    struct Client {
+        stream: TcpStream,
+        _dir: TempDir,
+    }
+
+    impl Client {
+        fn connect() -> Self {
+            let dir = TempDir::new().unwrap();
+            let server = Server::bind(&Database::open(dir.path().join("test.db")).unwrap(), "127.0.0.1:0").unwrap();
+            let stream = TcpStream::connect(server.local_addr().unwrap()).unwrap();
+            std::thread::spawn(move || server.run());
+            let mut client = Self { stream, _dir: dir };
+
+            // asking for TLS first, as psql does
+            client.send(&[&8i32.to_be_bytes()[..], &SSL_REQUEST.to_be_bytes()].concat());
+            let mut answer = [0];
+            client.stream.read_exact(&mut answer).unwrap();
+            assert_eq!(&answer, b"N");
+            let startup = Message::new(0).i32(PROTOCOL_VERSION).string("user").string("gandalf").u8(0).finish();
+            client.send(&startup[1..]);
+            assert_eq!(tags(&client.receive()), "RSSSSSSKZ");
+            client
+        }
+
+        fn send(&mut self, message: &[u8]) {
+            self.stream.write_all(message).unwrap();
+        }
+
+        /// the messages up to and including the next ReadyForQuery
+        fn receive(&mut self) -> Vec<(u8, Vec<u8>)> {
+            let mut messages = Vec::new();
+            loop {
+                let message = read_message(&mut self.stream).unwrap().unwrap();
+                let ready = message.0 == b'Z';
+                messages.push(message);
+                if ready {
+                    return messages;
+                }
+            }
+        }
+
+        fn query(&mut self, sql: &str) -> Vec<(u8, Vec<u8>)> {
+            self.send(&Message::new(b'Q').string(sql).finish());
+            self.receive()
+        }
+    }
+
+    fn tags(messages: &[(u8, Vec<u8>)]) -> String {
+        messages.iter().map(|(tag, _)| *tag as char).collect()
+    }
+
+    /// the values of a DataRow, as text
+    fn values(message: &[u8]) -> Vec<Option<String>> {
+        let mut body = Body::new(message);
+        (0..body.i16().unwrap()).map(|_| body.value().unwrap().map(|value| String::from_utf8_lossy(value).into_owned())).collect()
+    }
+
+    /// the message of a CommandComplete, or the SQLSTATE code of an ErrorResponse
+    fn string(tag: u8, message: &[u8]) -> String {
+        let mut body = Body::new(message);
+        if tag == b'E' {
+            while body.u8().unwrap() != b'C' {
+                body.string().unwrap();
+            }
+        }
+        body.string().unwrap()
+    }
+
+    #[test]
+    fn test_simple_query() {
+        let mut client = Client::connect();
+        let messages = client.query("CREATE TABLE t (id INT, name VARCHAR(8)); INSERT INTO t VALUES (1, 'one'), (2, NULL); SELECT * FROM t");
+        assert_eq!(tags(&messages), "CCTDDCZ");
+        assert_eq!(string(b'C', &messages[1].1), "INSERT 0 2");
+        assert_eq!(values(&messages[3].1), [Some("1".to_string()), Some("one".to_string())]);
+        assert_eq!(values(&messages[4].1), [Some("2".to_string()), None]);
+        assert_eq!(string(b'C', &messages[5].1), "SELECT 2");
+
+        // a failed statement stops the ones after it
+        let messages = client.query("UPDATE t SET id = id + 1; SELECT missing FROM t; DELETE FROM t");
+        assert_eq!(tags(&messages), "CEZ");
+        assert_eq!(string(b'E', &messages[1].1), "42703");
+        assert_eq!(tags(&client.query("SELECT id FROM t WHERE id > 2")), "TDCZ");
+        assert_eq!(tags(&client.query(" ; ")), "IZ");
+        assert_eq!(string(b'E', &client.query("SELEC 1")[0].1), "42601");
+    }
+
+    #[test]
+    fn test_transaction_blocks() {
+        let mut client = Client::connect();
+        let status = |messages: &[(u8, Vec<u8>)]| messages.last().unwrap().1.clone();
+        let messages = client.query("CREATE TABLE t (id INT PRIMARY KEY); BEGIN; INSERT INTO t VALUES (1); SAVEPOINT a");
+        assert_eq!(tags(&messages), "CCCCZ");
+        assert_eq!(string(b'C', &messages[1].1), "BEGIN");
+        assert_eq!(string(b'C', &messages[3].1), "SAVEPOINT");
+        assert_eq!(status(&messages), b"T");
+
+        let messages = client.query("INSERT INTO t VALUES (1)");
+        assert_eq!(status(&messages), b"E");
+        assert_eq!(string(b'E', &client.query("SELECT * FROM t")[0].1), "25P02");
+        let messages = client.query("ROLLBACK TO a; COMMIT");
+        assert_eq!(string(b'C', &messages[0].1), "ROLLBACK");
+        assert_eq!(status(&messages), b"I");
+        assert_eq!(string(b'E', &client.query("RELEASE a")[0].1), "25P01");
+    }
+
+    #[test]
+    fn test_extended_query() {
+        let mut client = Client::connect();
+        client.query("CREATE TABLE t (id INT, name VARCHAR(8)); INSERT INTO t VALUES (1, 'one'), (2, 'two'), (3, 'three')");
+
+        let parse = Message::new(b'P').string("by_id").string("SELECT name, id FROM t WHERE id >= $1").i16(0).finish();
+        let describe = Message::new(b'D').u8(b'S').string("by_id").finish();
+        client.send(&[parse, describe, Message::new(b'S').finish()].concat());
+        let messages = client.receive();
+        assert_eq!(tags(&messages), "1tTZ");
+        assert_eq!(messages[1].1, Message::new(0).i16(1).i32(23).finish()[5..]);
+
+        // a binary parameter, the rows a few at a time, the second column in binary
+        let bind = Message::new(b'B').string("").string("by_id").i16(1).i16(1).i16(1).value(Some(&2i32.to_be_bytes())).i16(2).i16(0).i16(1).finish();
+        let execute = Message::new(b'E').string("").i32(1).finish();
+        client.send(&[bind, execute.clone(), execute, Message::new(b'S').finish()].concat());
+        let messages = client.receive();
+        assert_eq!(tags(&messages), "2DsDCZ");
+        assert_eq!(values(&messages[1].1), [Some("two".to_string()), Some("\0\0\0\x02".to_string())]);
+        assert_eq!(string(b'C', &messages[4].1), "SELECT 1");
+
+        // after an error the messages up to the Sync are skipped
+        let bind = Message::new(b'B').string("").string("by_id").i16(0).i16(1).value(Some(b"two")).i16(0).finish();
+        let execute = Message::new(b'E').string("").i32(0).finish();
+        client.send(&[bind, execute, Message::new(b'S').finish()].concat());
+        let messages = client.receive();
+        assert_eq!(tags(&messages), "EZ");
+        assert_eq!(string(b'E', &messages[0].1), "22P02");
+        let execute = Message::new(b'E').string("nowhere").i32(0).finish();
+        client.send(&[execute, Message::new(b'S').finish()].concat());
+        assert_eq!(string(b'E', &client.receive()[0].1), "34000");
+
+        // the unnamed statement, without rows, and closed
+        let parse = Message::new(b'P').string("").string("UPDATE t SET name = ? WHERE id = ?").i16(0).finish();
+        let bind = Message::new(b'B').string("").string("").i16(0).i16(2).value(Some(b"uno")).value(Some(b"1")).i16(0).finish();
+        let describe = Message::new(b'D').u8(b'P').string("").finish();
+        let execute = Message::new(b'E').string("").i32(0).finish();
+        let close = Message::new(b'C').u8(b'S').string("").finish();
+        client.send(&[parse, bind, describe, execute, close, Message::new(b'S').finish()].concat());
+        let messages = client.receive();
+        assert_eq!(tags(&messages), "12nC3Z");
+        assert_eq!(string(b'C', &messages[3].1), "UPDATE 1");
+        let bind = Message::new(b'B').string("").string("").i16(0).i16(0).i16(0).finish();
+        client.send(&[bind, Message::new(b'S').finish()].concat());
+        assert_eq!(string(b'E', &client.receive()[0].1), "26000");
+        assert_eq!(values(&client.query("SELECT name FROM t WHERE id = 1")[1].1), [Some("uno".to_string())]);
+    }
+
+    #[test]
+    fn test_unsupported_protocol() {
+        let dir = TempDir::new().unwrap();
+        let server = Server::bind(&Database::open(dir.path().join("test.db")).unwrap(), "127.0.0.1:0").unwrap();
+        let mut stream = TcpStream::connect(server.local_addr().unwrap()).unwrap();
+        std::thread::spawn(move || server.run());
+
+        stream.write_all(&[&8i32.to_be_bytes()[..], &(2i32 << 16).to_be_bytes()].concat()).unwrap();
+        let (tag, body) = read_message(&mut stream).unwrap().unwrap();
+        assert_eq!(string(tag, &body), "08P01");
+        assert!(read_message(&mut stream).unwrap().is_none());
+    }
+}
@@ -0,0 +1,492 @@
+use super::ServerError;
+use crate::types::temporal::MICROS_PER_DAY;
+use crate::types::{DataType, Decimal, Interval, Value};
+use std::io::Read;
+
+/// the protocol version of a normal startup message, 3.0
+pub(super) const PROTOCOL_VERSION: i32 = 196608;
+/// the "version" of a startup message that asks to switch to TLS
+pub(super) const SSL_REQUEST: i32 = 80877103;
+/// the "version" of a startup message that asks to switch to GSSAPI encryption
+pub(super) const GSSENC_REQUEST: i32 = 80877104;
+/// the "version" of a startup message that asks to cancel another session's query
+pub(super) const CANCEL_REQUEST: i32 = 80877102;
+
+/// longest message a client may send, as in PostgreSQL
+const MAX_MESSAGE_LENGTH: usize = 1 << 30;
+
+/// type OIDs of the types values are sent as
+const BOOL_OID: i32 = 16;
+const INT8_OID: i32 = 20;
+const INT4_OID: i32 = 23;
+const TEXT_OID: i32 = 25;
+const VARCHAR_OID: i32 = 1043;
+const NUMERIC_OID: i32 = 1700;
+const DATE_OID: i32 = 1082;
+const TIME_OID: i32 = 1083;
+const TIMESTAMP_OID: i32 = 1114;
+const INTERVAL_OID: i32 = 1186;
+
+/// days from 1970-01-01, where dates count from here, to 2000-01-01, where
+/// PostgreSQL's binary dates and timestamps count from
+const POSTGRES_EPOCH_DAYS: i32 = 10957;
+
+/// How a value is written in a message, per column or parameter.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(super) enum Format {
+    Text,
+    Binary,
+}
+
+impl Format {
+    fn from_code(code: i16) -> Result<Self, ServerError> {
+        match code {
+            0 => Ok(Format::Text),
+            1 => Ok(Format::Binary),
+            code => Err(ServerError::InvalidMessage(format!("unknown format code {code}"))),
+        }
+    }
+
+    fn code(self) -> i16 {
+        match self {
+            Format::Text => 0,
+            Format::Binary => 1,
+        }
+    }
+
+    /// the format of each of `count` values, from format codes that are either
+    /// one per value, one for all of them or none for all text
+    pub(super) fn for_each(codes: &[i16], count: usize) -> Result<Vec<Self>, ServerError> {
+        match codes {
+            [] => Ok(vec![Format::Text; count]),
+            [code] => Ok(vec![Format::from_code(*code)?; count]),
+            codes if codes.len() == count => codes.iter().map(|code| Format::from_code(*code)).collect(),
+            codes => Err(ServerError::InvalidMessage(format!("{} format codes for {count} values", codes.len()))),
+        }
+    }
+}
+
+/// Reads the startup message a session begins with: its protocol version (or
+/// request code), then the rest of it. `None` if the client went away first.
+pub(super) fn read_startup(stream: &mut impl Read) -> Result<Option<(i32, Vec<u8>)>, ServerError> {
+    let mut length = [0; 4];
+    if !read_or_end(stream, &mut length)? {
+        return Ok(None);
+    }
+    let body = read_body(stream, i32::from_be_bytes(length))?;
+    let mut body = Body::new(&body);
+    let version = body.i32()?;
+    Ok(Some((version, body.rest().to_vec())))
+}
+
+/// Reads a message after startup: its type byte and its body. `None` if the
+/// client went away first.
+pub(super) fn read_message(stream: &mut impl Read) -> Result<Option<(u8, Vec<u8>)>, ServerError> {
+    let mut header = [0; 5];
+    if !read_or_end(stream, &mut header)? {
+        return Ok(None);
+    }
+    let body = read_body(stream, i32::from_be_bytes(header[1..].try_into().unwrap()))?;
+    Ok(Some((header[0], body)))
+}
+
+/// fills `buffer`, or returns false if the stream ended before the first byte
+fn read_or_end(stream: &mut impl Read, buffer: &mut [u8]) -> Result<bool, ServerError> {
+    match stream.read_exact(buffer) {
+        Ok(()) => Ok(true),
+        Err(error) if error.kind() == std::io::ErrorKind::UnexpectedEof => Ok(false),
+        Err(error) => Err(error.into()),
+    }
+}
+
+/// the rest of a message whose length, which counts itself, is `length`
+fn read_body(stream: &mut impl Read, length: i32) -> Result<Vec<u8>, ServerError> {
+    let body_length = usize::try_from(length).ok().and_then(|length| length.checked_sub(4)).filter(|&length| length <= MAX_MESSAGE_LENGTH);
+    let Some(body_length) = body_length else {
+        return Err(ServerError::InvalidMessage(format!("invalid message length {length}")));
+    };
+    let mut body = Vec::new();
+    stream.take(body_length as u64).read_to_end(&mut body)?;
+    if body.len() < body_length {
+        return Err(ServerError::IoError(std::io::ErrorKind::UnexpectedEof.into()));
+    }
+    Ok(body)
+}
+
+/// The fields of a message's body, read in order.
+pub(super) struct Body<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> Body<'a> {
+    pub(super) fn new(bytes: &'a [u8]) -> Self {
+        Self { bytes }
+    }
+
+    fn take(&mut self, length: usize) -> Result<&'a [u8], ServerError> {
+        if self.bytes.len() < length {
+            return Err(ServerError::InvalidMessage("message ends early".to_string()));
+        }
+        let (taken, rest) = self.bytes.split_at(length);
+        self.bytes = rest;
+        Ok(taken)
+    }
+
+    pub(super) fn u8(&mut self) -> Result<u8, ServerError> {
+        Ok(self.take(1)?[0])
+    }
+
+    pub(super) fn i16(&mut self) -> Result<i16, ServerError> {
+        Ok(i16::from_be_bytes(self.take(2)?.try_into().unwrap()))
+    }
+
+    pub(super) fn i32(&mut self) -> Result<i32, ServerError> {
+        Ok(i32::from_be_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    /// a NUL terminated string
+    pub(super) fn string(&mut self) -> Result<String, ServerError> {
+        let Some(end) = self.bytes.iter().position(|&byte| byte == 0) else {
+            return Err(ServerError::InvalidMessage("string without a terminator".to_string()));
+        };
+        let string = std::str::from_utf8(self.take(end)?).map_err(|_| ServerError::InvalidMessage("string isn't UTF-8".to_string()))?;
+        self.take(1)?;
+        Ok(string.to_string())
+    }
+
+    /// a count of 16-bit integers, then the integers
+    pub(super) fn i16s(&mut self) -> Result<Vec<i16>, ServerError> {
+        let count = self.i16()?;
+        (0..count).map(|_| self.i16()).collect()
+    }
+
+    /// a length, then that many bytes, or no bytes at all for a length of -1
+    pub(super) fn value(&mut self) -> Result<Option<&'a [u8]>, ServerError> {
+        match self.i32()? {
+            -1 => Ok(None),
+            length => Ok(Some(self.take(usize::try_from(length).map_err(|_| ServerError::InvalidMessage(format!("invalid value length {length}")))?)?)),
+        }
+    }
+
+    pub(super) fn rest(&mut self) -> &'a [u8] {
+        std::mem::take(&mut self.bytes)
+    }
+}
+
+/// A message to send, built up field by field.
+pub(super) struct Message {
+    bytes: Vec<u8>,
+}
+
+impl Message {
+    pub(super) fn new(tag: u8) -> Self {
+        // the length is filled in when the message is finished
+        Self { bytes: vec![tag, 0, 0, 0, 0] }
+    }
+
+    pub(super) fn u8(mut self, value: u8) -> Self {
+        self.bytes.push(value);
+        self
+    }
+
+    pub(super) fn i16(mut self, value: i16) -> Self {
+        self.bytes.extend_from_slice(&value.to_be_bytes());
+        self
+    }
+
+    pub(super) fn i32(mut self, value: i32) -> Self {
+        self.bytes.extend_from_slice(&value.to_be_bytes());
+        self
+    }
+
+    /// `value` NUL terminated
+    pub(super) fn string(mut self, value: &str) -> Self {
+        self.bytes.extend_from_slice(value.as_bytes());
+        self.bytes.push(0);
+        self
+    }
+
+    /// `value`'s length then its bytes, or a length of -1 for none
+    pub(super) fn value(mut self, value: Option<&[u8]>) -> Self {
+        match value {
+            Some(value) => {
+                self.bytes.extend_from_slice(&(value.len() as i32).to_be_bytes());
+                self.bytes.extend_from_slice(value);
+            }
+            None => self.bytes.extend_from_slice(&(-1i32).to_be_bytes()),
+        }
+        self
+    }
+
+    pub(super) fn finish(mut self) -> Vec<u8> {
+        let length = (self.bytes.len() - 1) as i32;
+        self.bytes[1..5].copy_from_slice(&length.to_be_bytes());
+        self.bytes
+    }
+}
+
+/// the OID of the type a value of `data_type` is sent as, text for a value of any type
+pub(super) fn type_oid(data_type: Option<DataType>) -> i32 {
+    match data_type {
+        Some(DataType::Bool) => BOOL_OID,
+        Some(DataType::Int) => INT4_OID,
+        Some(DataType::BigInt) => INT8_OID,
+        Some(DataType::Varchar(_)) => VARCHAR_OID,
+        Some(DataType::Numeric(_)) => NUMERIC_OID,
+        Some(DataType::Date) => DATE_OID,
+        Some(DataType::Time) => TIME_OID,
+        Some(DataType::Timestamp) => TIMESTAMP_OID,
+        Some(DataType::Interval) => INTERVAL_OID,
+        None => TEXT_OID,
+    }
+}
+
+/// the type of the values a client sends as type `oid`, if it's one of those values are sent as
+pub(super) fn oid_type(oid: i32) -> Option<DataType> {
+    match oid {
+        BOOL_OID => Some(DataType::Bool),
+        INT4_OID => Some(DataType::Int),
+        INT8_OID => Some(DataType::BigInt),
+        TEXT_OID | VARCHAR_OID => Some(DataType::Varchar(None)),
+        NUMERIC_OID => Some(DataType::Numeric(None)),
+        DATE_OID => Some(DataType::Date),
+        TIME_OID => Some(DataType::Time),
+        TIMESTAMP_OID => Some(DataType::Timestamp),
+        INTERVAL_OID => Some(DataType::Interval),
+        _ => None,
+    }
+}
+
+/// A RowDescription message for columns `names` of types `types`, each sent in its format of `formats`.
+pub(super) fn row_description(names: &[String], types: &[Option<DataType>], formats: &[Format]) -> Vec<u8> {
+    let mut message = Message::new(b'T').i16(names.len() as i16);
+    for ((name, data_type), format) in names.iter().zip(types).zip(formats) {
+        let (size, modifier) = match data_type {
+            Some(DataType::Bool) => (1, -1),
+            Some(DataType::Int) => (4, -1),
+            Some(DataType::BigInt) => (8, -1),
+            // the modifier of a VARCHAR(n) counts the length header
+            Some(DataType::Varchar(Some(length))) => (-1, *length as i32 + 4),
+            Some(DataType::Varchar(None)) | None => (-1, -1),
+            // and so does that of a NUMERIC(p, s), with the precision in the high half
+            Some(DataType::Numeric(Some((precision, scale)))) => (-1, ((*precision as i32) << 16 | *scale as i32) + 4),
+            Some(DataType::Numeric(None)) => (-1, -1),
+            Some(DataType::Date) => (4, -1),
+            Some(DataType::Time | DataType::Timestamp) => (8, -1),
+            Some(DataType::Interval) => (16, -1),
+        };
+        // no table or column it's from
+        message = message.string(name).i32(0).i16(0).i32(type_oid(*data_type)).i16(size).i32(modifier).i16(format.code());
+    }
+    message.finish()
+}
+
+/// `value` written in `format`, `None` for NULL
+pub(super) fn encode_value(value: &Value, format: Format) -> Option<Vec<u8>> {
+    let bytes = match (value, format) {
+        (Value::Null, _) => return None,
+        (Value::Bool(value), Format::Text) => (if *value { "t" } else { "f" }).as_bytes().to_vec(),
+        (Value::Bool(value), Format::Binary) => vec![*value as u8],
+        (Value::Int(value), Format::Binary) => value.to_be_bytes().to_vec(),
+        (Value::BigInt(value), Format::Binary) => value.to_be_bytes().to_vec(),
+        (Value::Numeric(value), Format::Binary) => encode_numeric(value),
+        (Value::Date(days), Format::Binary) => (*days - POSTGRES_EPOCH_DAYS).to_be_bytes().to_vec(),
+        (Value::Time(micros), Format::Binary) => micros.to_be_bytes().to_vec(),
+        (Value::Timestamp(micros), Format::Binary) => (*micros - POSTGRES_EPOCH_DAYS as i64 * MICROS_PER_DAY).to_be_bytes().to_vec(),
+        // the microseconds first, then the days and the months
+        (Value::Interval(interval), Format::Binary) => [&interval.micros.to_be_bytes()[..], &interval.days.to_be_bytes(), &interval.months.to_be_bytes()].concat(),
+        (Value::Varchar(value), _) => value.as_bytes().to_vec(),
+        (value, Format::Text) => value.to_string().into_bytes(),
+    };
+    Some(bytes)
+}
+
+/// The value `bytes` stands for, sent in `format` as a value of `data_type`
+/// (text, for a parameter of any type), `None` standing for NULL.
+pub(super) fn decode_value(bytes: Option<&[u8]>, data_type: Option<DataType>, format: Format) -> Result<Value, ServerError> {
+    let Some(bytes) = bytes else {
+        return Ok(Value::Null);
+    };
+    let invalid = || ServerError::InvalidValue(format!("{bytes:?} is not a valid {}", data_type.map_or("text".to_string(), |data_type| data_type.to_string())));
+    let value = match format {
+        Format::Text => {
+            let text = std::str::from_utf8(bytes).map_err(|_| invalid())?;
+            match data_type {
+                Some(data_type) => Value::parse(text, data_type).ok_or_else(invalid)?,
+                None => Value::Varchar(text.to_string()),
+            }
+        }
+        Format::Binary => match data_type {
+            Some(DataType::Bool) => match bytes {
+                [byte] => Value::Bool(*byte != 0),
+                _ => return Err(invalid()),
+            },
+            Some(DataType::Int) => Value::Int(i32::from_be_bytes(bytes.try_into().map_err(|_| invalid())?)),
+            Some(DataType::BigInt) => Value::BigInt(i64::from_be_bytes(bytes.try_into().map_err(|_| invalid())?)),
+            Some(DataType::Numeric(scale)) => {
+                let value = decode_numeric(bytes).ok_or_else(invalid)?;
+                Value::Numeric(match scale {
+                    Some((_, scale)) => value.rescale(scale).ok_or_else(invalid)?,
+                    None => value,
+                })
+            }
+            Some(DataType::Date) => {
+                let days = i32::from_be_bytes(bytes.try_into().map_err(|_| invalid())?);
+                Value::Date(days.checked_add(POSTGRES_EPOCH_DAYS).ok_or_else(invalid)?)
+            }
+            Some(DataType::Time) => Value::Time(i64::from_be_bytes(bytes.try_into().map_err(|_| invalid())?)),
+            Some(DataType::Timestamp) => {
+                let micros = i64::from_be_bytes(bytes.try_into().map_err(|_| invalid())?);
+                Value::Timestamp(micros.checked_add(POSTGRES_EPOCH_DAYS as i64 * MICROS_PER_DAY).ok_or_else(invalid)?)
+            }
+            Some(DataType::Interval) => {
+                let bytes: [u8; 16] = bytes.try_into().map_err(|_| invalid())?;
+                Value::Interval(Interval {
+                    micros: i64::from_be_bytes(bytes[..8].try_into().unwrap()),
+                    days: i32::from_be_bytes(bytes[8..12].try_into().unwrap()),
+                    months: i32::from_be_bytes(bytes[12..].try_into().unwrap()),
+                })
+            }
+            Some(DataType::Varchar(_)) | None => Value::Varchar(String::from_utf8(bytes.to_vec()).map_err(|_| invalid())?),
+        },
+    };
+    Ok(value)
+}
+
+/// `value` the way PostgreSQL sends a NUMERIC in binary: the number of its base
+/// 10000 digits, the weight of the first (how many come before the point, less
+/// one), its sign, its scale in decimal places and then the digits, without
+/// the zero ones at either end
+fn encode_numeric(value: &Decimal) -> Vec<u8> {
+    let scale = value.scale() as usize;
+    let digits = format!("{:0>width$}", value.mantissa().unsigned_abs(), width = scale + 1);
+    let (integer, fraction) = digits.split_at(digits.len() - scale);
+    // the point falls between base 10000 digits
+    let integer = format!("{integer:0>width$}", width = integer.len().div_ceil(4) * 4);
+    let fraction = format!("{fraction:0<width$}", width = fraction.len().div_ceil(4) * 4);
+    let mut groups: Vec<i16> = integer.as_bytes().chunks(4).chain(fraction.as_bytes().chunks(4)).map(|group| std::str::from_utf8(group).unwrap().parse().unwrap()).collect();
+    let mut weight = (integer.len() / 4) as i16 - 1;
+    let leading = groups.iter().take_while(|&&group| group == 0).count();
+    groups.drain(..leading);
+    weight -= leading as i16;
+    while groups.last() == Some(&0) {
+        groups.pop();
+    }
+    if groups.is_empty() {
+        weight = 0;
+    }
+
+    let sign: u16 = if value.mantissa() < 0 { 0x4000 } else { 0 };
+    let mut bytes = Vec::with_capacity(8 + 2 * groups.len());
+    for field in [groups.len() as i16, weight, sign as i16, scale as i16].into_iter().chain(groups) {
+        bytes.extend_from_slice(&field.to_be_bytes());
+    }
+    bytes
+}
+
+/// the number `encode_numeric` wrote as `bytes`, `None` for anything else,
+/// including NaN and numbers too large for a decimal
+fn decode_numeric(bytes: &[u8]) -> Option<Decimal> {
+    let fields: Vec<i16> = bytes.chunks(2).map(|field| field.try_into().map(i16::from_be_bytes)).collect::<Result<_, _>>().ok()?;
+    let [count, weight, sign, scale, groups @ ..] = fields.as_slice() else {
+        return None;
+    };
+    if groups.len() != *count as usize || !matches!(*sign as u16, 0 | 0x4000) || !(0..=Decimal::MAX_PRECISION as i16).contains(scale) {
+        return None;
+    }
+    let mut mantissa: i128 = 0;
+    for &group in groups {
+        if !(0..10000).contains(&group) {
+            return None;
+        }
+        mantissa = mantissa.checked_mul(10000)?.checked_add(group as i128)?;
+    }
+    // the last digit is 10000^(weight - count + 1)
+    let mut places = 4 * (*count as i32 - 1 - *weight as i32);
+    while places > Decimal::MAX_PRECISION as i32 && mantissa % 10 == 0 {
+        mantissa /= 10;
+        places -= 1;
+    }
+    let value = if places >= 0 {
+        Decimal::new(mantissa, u8::try_from(places).ok()?)?
+    } else {
+        Decimal::new(mantissa.checked_mul(10i128.checked_pow(places.unsigned_abs())?)?, 0)?
+    };
+    let value = value.rescale(*scale as u8)?;
+    Some(if *sign != 0 { -value } else { value })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_messages() {
+        let message = Message::new(b'C').string("SELECT 2").finish();
+        assert_eq!(message, b"C\0\0\0\x0dSELECT 2\0");
+        let (tag, body) = read_message(&mut &message[..]).unwrap().unwrap();
+        assert_eq!(tag, b'C');
+        assert_eq!(Body::new(&body).string().unwrap(), "SELECT 2");
+        assert!(read_message(&mut &b""[..]).unwrap().is_none());
+        assert!(matches!(read_message(&mut &b"C\0\0\0\x02"[..]), Err(ServerError::InvalidMessage(_))));
+        assert!(matches!(read_message(&mut &message[..6]), Err(ServerError::IoError(_))));
+
+        let message = Message::new(b'B').i16(2).i16(0).i16(-1).value(Some(b"ab")).value(None).u8(1).finish();
+        let mut body = Body::new(&message[5..]);
+        assert_eq!(body.i16s().unwrap(), [0, -1]);
+        assert_eq!(body.value().unwrap(), Some(&b"ab"[..]));
+        assert_eq!(body.value().unwrap(), None);
+        assert_eq!(body.u8().unwrap(), 1);
+        assert!(body.u8().is_err());
+
+        let startup = [&12i32.to_be_bytes()[..], &SSL_REQUEST.to_be_bytes(), b"abcd"].concat();
+        assert_eq!(read_startup(&mut &startup[..]).unwrap(), Some((SSL_REQUEST, b"abcd".to_vec())));
+    }
+
+    #[test]
+    fn test_values() {
+        let values = [
+            (Value::Int(-42), Some(DataType::Int)),
+            (Value::BigInt(1 << 40), Some(DataType::BigInt)),
+            (Value::Bool(true), Some(DataType::Bool)),
+            (Value::Varchar("mithril".into()), Some(DataType::Varchar(Some(10)))),
+            (Value::Varchar("anything".into()), None),
+            (Value::Null, Some(DataType::Int)),
+            (Value::Numeric(Decimal::parse("-12345678.9012").unwrap()), Some(DataType::Numeric(None))),
+            (Value::Numeric(Decimal::parse("0.00050").unwrap()), Some(DataType::Numeric(Some((10, 5))))),
+            (Value::Numeric(Decimal::parse("100000000").unwrap()), Some(DataType::Numeric(None))),
+            (Value::Numeric(Decimal::parse("0.000").unwrap()), Some(DataType::Numeric(None))),
+            (Value::Date(-1), Some(DataType::Date)),
+            (Value::Time(45_296_789_000), Some(DataType::Time)),
+            (Value::Timestamp(1_709_208_000_000_001), Some(DataType::Timestamp)),
+            (Value::Interval(Interval { months: 14, days: -3, micros: 3_723_000_000 }), Some(DataType::Interval)),
+        ];
+        for (value, data_type) in values {
+            for format in [Format::Text, Format::Binary] {
+                let bytes = encode_value(&value, format);
+                assert_eq!(decode_value(bytes.as_deref(), data_type, format).unwrap(), value);
+            }
+        }
+        assert_eq!(encode_value(&Value::Bool(false), Format::Text).unwrap(), b"f");
+        assert_eq!(decode_value(Some(b"on"), Some(DataType::Bool), Format::Text).unwrap(), Value::Bool(true));
+        assert!(matches!(decode_value(Some(b"12x"), Some(DataType::Int), Format::Text), Err(ServerError::InvalidValue(_))));
+        assert!(matches!(decode_value(Some(b"\0\0\0\0\0"), Some(DataType::Int), Format::Binary), Err(ServerError::InvalidValue(_))));
+
+        assert_eq!(Format::for_each(&[], 2).unwrap(), [Format::Text, Format::Text]);
+        assert_eq!(Format::for_each(&[1], 2).unwrap(), [Format::Binary, Format::Binary]);
+        assert_eq!(Format::for_each(&[0, 1], 2).unwrap(), [Format::Text, Format::Binary]);
+        assert!(Format::for_each(&[0, 1], 3).is_err());
+        assert!(Format::for_each(&[2], 1).is_err());
+        assert_eq!(oid_type(type_oid(Some(DataType::BigInt))), Some(DataType::BigInt));
+
+        // 1234.5 is 1234 and 5000 in base 10000, at weight 0 and scale 1
+        let numeric = encode_value(&Value::Numeric(Decimal::parse("1234.5").unwrap()), Format::Binary).unwrap();
+        assert_eq!(numeric, [0, 2, 0, 0, 0, 0, 0, 1, 0x04, 0xD2, 0x13, 0x88]);
+        assert_eq!(decode_value(Some(b"2.345"), Some(DataType::Numeric(Some((5, 2)))), Format::Text).unwrap().to_string(), "2.35");
+        assert!(matches!(decode_value(Some(&[0, 0, 0, 0, 0xC0, 0, 0, 0]), Some(DataType::Numeric(None)), Format::Binary), Err(ServerError::InvalidValue(_))));
+        // binary dates count from 2000-01-01
+        let millennium = Value::parse("2000-01-02", DataType::Date).unwrap();
+        assert_eq!(encode_value(&millennium, Format::Binary).unwrap(), [0, 0, 0, 1]);
+        assert_eq!(encode_value(&Value::Timestamp(0), Format::Text).unwrap(), b"1970-01-01 00:00:00");
+    }
+}
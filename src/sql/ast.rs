@@ -0,0 +1,456 @@
+use super::lexer::quote_identifier;
+use crate::transaction::IsolationLevel;
+use crate::types::{DataType, Decimal, Interval, Value};
+
+/// A single parsed SQL statement.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Statement {
+    CreateTable(CreateTable),
+    CreateIndex(CreateIndex),
+    Insert(Insert),
+    Select(Select),
+    Update(Update),
+    Delete(Delete),
+    Analyze(Analyze),
+    Vacuum(Vacuum),
+    Explain(Explain),
+    Copy(Copy),
+    Transaction(TransactionStatement),
+}
+
+/// `CREATE TABLE name (column type [constraint ...], ... [, table constraint, ...])`
+#[derive(Debug, Clone, PartialEq)]
+pub struct CreateTable {
+    pub name: String,
+    pub columns: Vec<ColumnDef>,
+    /// constraints listed after the columns, which can span several of them
+    pub constraints: Vec<TableConstraint>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ColumnDef {
+    pub name: String,
+    pub data_type: DataType,
+    pub constraints: Vec<ColumnConstraint>,
+}
+
+/// Constraint declared as part of a column definition, applying to that column.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ColumnConstraint {
+    /// `PRIMARY KEY`
+    PrimaryKey,
+    /// `UNIQUE`
+    Unique,
+    /// `NOT NULL`
+    NotNull,
+    /// `DEFAULT expr`, the value of the column in rows inserted without one
+    Default(Expr),
+    /// `[CONSTRAINT name] CHECK (expr)`
+    Check { name: Option<String>, expr: Expr },
+}
+
+/// Constraint declared on its own in a `CREATE TABLE`, naming its columns.
+#[derive(Debug, Clone, PartialEq)]
+pub enum TableConstraint {
+    /// `PRIMARY KEY (column, ...)`
+    PrimaryKey(Vec<String>),
+    /// `UNIQUE (column, ...)`
+    Unique(Vec<String>),
+    /// `[CONSTRAINT name] CHECK (expr)`, where `expr` can refer to any of the columns
+    Check { name: Option<String>, expr: Expr },
+}
+
+/// `CREATE [UNIQUE] INDEX name ON table (column [ASC | DESC] [NULLS FIRST | NULLS LAST], ...)`
+#[derive(Debug, Clone, PartialEq)]
+pub struct CreateIndex {
+    pub name: String,
+    pub table: String,
+    pub columns: Vec<IndexedColumn>,
+    pub unique: bool,
+}
+
+/// A column of a `CREATE INDEX` key, with the order it sorts in.
+#[derive(Debug, Clone, PartialEq)]
+pub struct IndexedColumn {
+    pub name: String,
+    /// `DESC`
+    pub descending: bool,
+    /// `NULLS FIRST` or `NULLS LAST`, `None` for the direction's default
+    pub nulls_first: Option<bool>,
+}
+
+/// `INSERT INTO table [(column, ...)] VALUES (expr, ...), ...`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Insert {
+    pub table: String,
+    /// target columns, empty when the statement doesn't list them (all columns in order)
+    pub columns: Vec<String>,
+    pub rows: Vec<Vec<Expr>>,
+}
+
+/// `SELECT items [FROM table, ...] [WHERE expr] [GROUP BY expr, ...] [HAVING expr]`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Select {
+    pub projection: Vec<SelectItem>,
+    /// tables in the `FROM` clause, empty without one
+    pub from: Vec<TableRef>,
+    pub where_clause: Option<Expr>,
+    /// empty without a `GROUP BY` clause
+    pub group_by: Vec<Expr>,
+    pub having: Option<Expr>,
+    /// empty without an `ORDER BY` clause
+    pub order_by: Vec<OrderByItem>,
+    /// `LIMIT n`, the most rows to return
+    pub limit: Option<u64>,
+    /// `OFFSET n`, the rows to skip before those, 0 without one
+    pub offset: u64,
+}
+
+/// An expression of an `ORDER BY` clause, with the order it sorts in. A number
+/// on its own refers to a column of the result by position, from 1.
+#[derive(Debug, Clone, PartialEq)]
+pub struct OrderByItem {
+    pub expr: Expr,
+    /// `DESC`
+    pub descending: bool,
+    /// `NULLS FIRST` or `NULLS LAST`, `None` for the direction's default
+    pub nulls_first: Option<bool>,
+}
+
+/// A table in a `FROM` clause: `table [[AS] alias]`, after the first one
+/// either listed with a comma, `CROSS JOIN`ed or `[INNER] JOIN`ed `ON` a condition.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TableRef {
+    pub name: String,
+    pub alias: Option<String>,
+    /// the `ON` condition of a `JOIN`
+    pub on: Option<Expr>,
+}
+
+impl TableRef {
+    /// Table without an alias or join condition.
+    pub fn new(name: &str) -> Self {
+        Self { name: name.to_string(), alias: None, on: None }
+    }
+
+    /// the name the query refers to the table by
+    pub fn reference(&self) -> &str {
+        self.alias.as_deref().unwrap_or(&self.name)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum SelectItem {
+    /// `*`
+    Wildcard,
+    Expr { expr: Expr, alias: Option<String> },
+}
+
+/// `UPDATE table SET column = expr, ... [WHERE expr]`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Update {
+    pub table: String,
+    pub assignments: Vec<(String, Expr)>,
+    pub where_clause: Option<Expr>,
+}
+
+/// `DELETE FROM table [WHERE expr]`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Delete {
+    pub table: String,
+    pub where_clause: Option<Expr>,
+}
+
+/// `ANALYZE [table]`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Analyze {
+    /// the table to gather statistics on, every table when `None`
+    pub table: Option<String>,
+}
+
+/// `VACUUM [table]`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Vacuum {
+    /// the table to reclaim the space of, every table when `None`
+    pub table: Option<String>,
+}
+
+/// `EXPLAIN [ANALYZE] query`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Explain {
+    /// run the query as well, and report what it actually did
+    pub analyze: bool,
+    pub query: Select,
+}
+
+/// `COPY table [(column, ...)] FROM | TO 'file' [[WITH] (option [value], ...)]`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Copy {
+    pub table: String,
+    /// columns the file holds, empty when the statement doesn't list them (all columns in order)
+    pub columns: Vec<String>,
+    pub direction: CopyDirection,
+    pub path: String,
+    pub options: CopyOptions,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CopyDirection {
+    /// `FROM`, reading rows from the file into the table
+    From,
+    /// `TO`, writing the table's rows to the file
+    To,
+}
+
+/// How the CSV of a `COPY` is laid out, PostgreSQL's defaults for CSV unless
+/// the statement says otherwise.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CopyOptions {
+    /// `DELIMITER 'c'`, what separates the fields of a line, `,` by default
+    pub delimiter: char,
+    /// `HEADER [TRUE | FALSE]`, whether the first line names the columns rather than holding a row
+    pub header: bool,
+    /// `NULL 'text'`, what an unquoted field stands for NULL with, nothing by default
+    pub null: String,
+}
+
+impl Default for CopyOptions {
+    fn default() -> Self {
+        Self { delimiter: ',', header: false, null: String::new() }
+    }
+}
+
+/// A statement that starts or ends a transaction block, or sets or undoes a
+/// savepoint in one.
+#[derive(Debug, Clone, PartialEq)]
+pub enum TransactionStatement {
+    /// `BEGIN [TRANSACTION | WORK]` or `START TRANSACTION`, either followed by
+    /// an optional `ISOLATION LEVEL level`
+    Begin(Option<IsolationLevel>),
+    /// `SET TRANSACTION ISOLATION LEVEL level`, before the first statement of a block
+    SetIsolationLevel(IsolationLevel),
+    /// `COMMIT [TRANSACTION | WORK]`
+    Commit,
+    /// `ROLLBACK [TRANSACTION | WORK]`
+    Rollback,
+    /// `SAVEPOINT name`
+    Savepoint(String),
+    /// `ROLLBACK [TRANSACTION | WORK] TO [SAVEPOINT] name`
+    RollbackTo(String),
+    /// `RELEASE [SAVEPOINT] name`
+    Release(String),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expr {
+    Literal(Literal),
+    /// column reference, optionally qualified with a table name (`table.column`)
+    Column { table: Option<String>, name: String },
+    /// the value given for parameter `$n` (counting from 1) when the statement runs,
+    /// what the `n`th `?` stands for too
+    Parameter(usize),
+    Unary { op: UnaryOp, expr: Box<Expr> },
+    Binary { left: Box<Expr>, op: BinaryOp, right: Box<Expr> },
+    /// `expr IS [NOT] NULL`
+    IsNull { expr: Box<Expr>, negated: bool },
+    /// an aggregate function of `arg` over a group of rows, `COUNT(*)` without one
+    Aggregate { function: AggregateFunction, arg: Option<Box<Expr>> },
+    /// a call of a function of each row's values, see `Function`
+    Function { function: Function, args: Vec<Expr> },
+}
+
+impl Expr {
+    /// Unqualified column reference.
+    pub fn column(name: &str) -> Self {
+        Expr::Column { table: None, name: name.to_string() }
+    }
+
+    pub fn binary(left: Expr, op: BinaryOp, right: Expr) -> Self {
+        Expr::Binary { left: Box::new(left), op, right: Box::new(right) }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AggregateFunction {
+    Count,
+    Sum,
+    Avg,
+    Min,
+    Max,
+}
+
+impl AggregateFunction {
+    /// the function called `name`, in any case
+    pub fn from_name(name: &str) -> Option<Self> {
+        let function = match name.to_ascii_lowercase().as_str() {
+            "count" => AggregateFunction::Count,
+            "sum" => AggregateFunction::Sum,
+            "avg" => AggregateFunction::Avg,
+            "min" => AggregateFunction::Min,
+            "max" => AggregateFunction::Max,
+            _ => return None,
+        };
+        Some(function)
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            AggregateFunction::Count => "count",
+            AggregateFunction::Sum => "sum",
+            AggregateFunction::Avg => "avg",
+            AggregateFunction::Min => "min",
+            AggregateFunction::Max => "max",
+        }
+    }
+}
+
+/// The functions that aren't aggregates.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Function {
+    /// `now()`, the current date and time
+    Now,
+    /// `date_trunc(unit, timestamp)`, the timestamp with everything smaller than
+    /// `unit` (like `'month'` or `'hour'`) zeroed
+    DateTrunc,
+    /// `date_part(field, value)`, or `EXTRACT(field FROM value)`: one field of a
+    /// date, time, timestamp or interval, like `'year'` or `'minute'`, as a number
+    DatePart,
+}
+
+impl Function {
+    /// the function called `name`, in any case
+    pub fn from_name(name: &str) -> Option<Self> {
+        let function = match name.to_ascii_lowercase().as_str() {
+            "now" => Function::Now,
+            "date_trunc" => Function::DateTrunc,
+            "date_part" => Function::DatePart,
+            _ => return None,
+        };
+        Some(function)
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Function::Now => "now",
+            Function::DateTrunc => "date_trunc",
+            Function::DatePart => "date_part",
+        }
+    }
+
+    /// how many arguments the function takes
+    pub fn arity(&self) -> usize {
+        match self {
+            Function::Now => 0,
+            Function::DateTrunc | Function::DatePart => 2,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Literal {
+    Integer(i64),
+    /// a number with a decimal point, or too large for an `i64`
+    Decimal(Decimal),
+    String(String),
+    Boolean(bool),
+    Null,
+    /// `DATE '2024-01-31'`, as days since 1970-01-01
+    Date(i32),
+    /// `TIME '12:30:00'`, as microseconds since midnight
+    Time(i64),
+    /// `TIMESTAMP '2024-01-31 12:30:00'`, as microseconds since 1970-01-01
+    Timestamp(i64),
+    /// `INTERVAL '1 day'`
+    Interval(Interval),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UnaryOp {
+    Not,
+    Minus,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BinaryOp {
+    Or,
+    And,
+    Eq,
+    NotEq,
+    Lt,
+    LtEq,
+    Gt,
+    GtEq,
+    Plus,
+    Minus,
+    Multiply,
+    Divide,
+    Modulo,
+}
+
+impl std::fmt::Display for BinaryOp {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let symbol = match self {
+            BinaryOp::Or => "OR",
+            BinaryOp::And => "AND",
+            BinaryOp::Eq => "=",
+            BinaryOp::NotEq => "<>",
+            BinaryOp::Lt => "<",
+            BinaryOp::LtEq => "<=",
+            BinaryOp::Gt => ">",
+            BinaryOp::GtEq => ">=",
+            BinaryOp::Plus => "+",
+            BinaryOp::Minus => "-",
+            BinaryOp::Multiply => "*",
+            BinaryOp::Divide => "/",
+            BinaryOp::Modulo => "%",
+        };
+        write!(f, "{symbol}")
+    }
+}
+
+impl std::fmt::Display for Literal {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Literal::Integer(value) => write!(f, "{value}"),
+            Literal::Decimal(value) => write!(f, "{value}"),
+            Literal::String(value) => write!(f, "'{}'", value.replace('\'', "''")),
+            Literal::Boolean(value) => write!(f, "{}", if *value { "TRUE" } else { "FALSE" }),
+            Literal::Null => write!(f, "NULL"),
+            Literal::Date(days) => write!(f, "DATE '{}'", Value::Date(*days)),
+            Literal::Time(micros) => write!(f, "TIME '{}'", Value::Time(*micros)),
+            Literal::Timestamp(micros) => write!(f, "TIMESTAMP '{}'", Value::Timestamp(*micros)),
+            Literal::Interval(interval) => write!(f, "INTERVAL '{interval}'"),
+        }
+    }
+}
+
+impl std::fmt::Display for Expr {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Expr::Literal(literal) => write!(f, "{literal}"),
+            Expr::Column { table: Some(table), name } => write!(f, "{}.{}", quote_identifier(table), quote_identifier(name)),
+            Expr::Column { table: None, name } => write!(f, "{}", quote_identifier(name)),
+            Expr::Parameter(number) => write!(f, "${number}"),
+            Expr::Unary { op: UnaryOp::Not, expr } => write!(f, "NOT {expr}"),
+            Expr::Unary { op: UnaryOp::Minus, expr } => {
+                let operand = expr.to_string();
+                // a minus before another one would start a comment
+                if operand.starts_with('-') || matches!(**expr, Expr::Unary { .. } | Expr::IsNull { .. }) { write!(f, "-({operand})") } else { write!(f, "-{operand}") }
+            }
+            // fully parenthesized, so the output never depends on precedence
+            Expr::Binary { left, op, right } => write!(f, "({left} {op} {right})"),
+            // IS binds less tightly than NOT, so `NOT a IS NULL` would be NOT (a IS NULL)
+            Expr::IsNull { expr, negated } if matches!(**expr, Expr::Unary { .. } | Expr::IsNull { .. }) => {
+                write!(f, "({expr}) IS {}NULL", if *negated { "NOT " } else { "" })
+            }
+            Expr::IsNull { expr, negated: false } => write!(f, "{expr} IS NULL"),
+            Expr::IsNull { expr, negated: true } => write!(f, "{expr} IS NOT NULL"),
+            Expr::Aggregate { function, arg: Some(arg) } => write!(f, "{}({arg})", function.name()),
+            Expr::Aggregate { function, arg: None } => write!(f, "{}(*)", function.name()),
+            Expr::Function { function, args } => {
+                let args: Vec<String> = args.iter().map(Expr::to_string).collect();
+                write!(f, "{}({})", function.name(), args.join(", "))
+            }
+        }
+    }
+}
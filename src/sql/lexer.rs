@@ -0,0 +1,386 @@
+use super::ParseError;
+
+/// Reserved words. Anything else that looks like a word is an identifier,
+/// including type names, which the parser matches by spelling.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Keyword {
+    Analyze,
+    And,
+    As,
+    Asc,
+    Begin,
+    By,
+    Check,
+    Commit,
+    Constraint,
+    Copy,
+    Create,
+    Cross,
+    Default,
+    Delete,
+    Desc,
+    Explain,
+    False,
+    First,
+    From,
+    Group,
+    Having,
+    Index,
+    Inner,
+    Insert,
+    Into,
+    Is,
+    Join,
+    Key,
+    Last,
+    Limit,
+    Not,
+    Null,
+    Nulls,
+    Offset,
+    On,
+    Or,
+    Order,
+    Primary,
+    Release,
+    Rollback,
+    Savepoint,
+    Select,
+    Set,
+    Start,
+    Table,
+    To,
+    Transaction,
+    True,
+    Unique,
+    Update,
+    Vacuum,
+    Values,
+    Where,
+    With,
+    Work,
+}
+
+impl Keyword {
+    fn from_word(word: &str) -> Option<Self> {
+        let keyword = match word.to_ascii_uppercase().as_str() {
+            "ANALYZE" => Keyword::Analyze,
+            "AND" => Keyword::And,
+            "AS" => Keyword::As,
+            "ASC" => Keyword::Asc,
+            "BEGIN" => Keyword::Begin,
+            "BY" => Keyword::By,
+            "CHECK" => Keyword::Check,
+            "COMMIT" => Keyword::Commit,
+            "CONSTRAINT" => Keyword::Constraint,
+            "COPY" => Keyword::Copy,
+            "CREATE" => Keyword::Create,
+            "CROSS" => Keyword::Cross,
+            "DEFAULT" => Keyword::Default,
+            "DELETE" => Keyword::Delete,
+            "DESC" => Keyword::Desc,
+            "EXPLAIN" => Keyword::Explain,
+            "FALSE" => Keyword::False,
+            "FIRST" => Keyword::First,
+            "FROM" => Keyword::From,
+            "GROUP" => Keyword::Group,
+            "HAVING" => Keyword::Having,
+            "INDEX" => Keyword::Index,
+            "INNER" => Keyword::Inner,
+            "INSERT" => Keyword::Insert,
+            "INTO" => Keyword::Into,
+            "IS" => Keyword::Is,
+            "JOIN" => Keyword::Join,
+            "KEY" => Keyword::Key,
+            "LAST" => Keyword::Last,
+            "LIMIT" => Keyword::Limit,
+            "NOT" => Keyword::Not,
+            "NULL" => Keyword::Null,
+            "NULLS" => Keyword::Nulls,
+            "OFFSET" => Keyword::Offset,
+            "ON" => Keyword::On,
+            "OR" => Keyword::Or,
+            "ORDER" => Keyword::Order,
+            "PRIMARY" => Keyword::Primary,
+            "RELEASE" => Keyword::Release,
+            "ROLLBACK" => Keyword::Rollback,
+            "SAVEPOINT" => Keyword::Savepoint,
+            "SELECT" => Keyword::Select,
+            "SET" => Keyword::Set,
+            "START" => Keyword::Start,
+            "TABLE" => Keyword::Table,
+            "TO" => Keyword::To,
+            "TRANSACTION" => Keyword::Transaction,
+            "TRUE" => Keyword::True,
+            "UNIQUE" => Keyword::Unique,
+            "UPDATE" => Keyword::Update,
+            "VACUUM" => Keyword::Vacuum,
+            "VALUES" => Keyword::Values,
+            "WHERE" => Keyword::Where,
+            "WITH" => Keyword::With,
+            "WORK" => Keyword::Work,
+            _ => return None,
+        };
+        Some(keyword)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Token {
+    Keyword(Keyword),
+    /// unquoted identifiers are folded to lowercase, quoted ones are kept as written
+    Identifier(String),
+    /// numeric literal, kept as written so the parser decides how to interpret it
+    Number(String),
+    /// single quoted string literal with `''` escapes resolved
+    String(String),
+    /// a placeholder for a value supplied when the statement runs, `$n` with its number or `?` without one
+    Parameter(Option<u32>),
+    LeftParen,
+    RightParen,
+    Comma,
+    Semicolon,
+    Dot,
+    Star,
+    Plus,
+    Minus,
+    Slash,
+    Percent,
+    Eq,
+    NotEq,
+    Lt,
+    LtEq,
+    Gt,
+    GtEq,
+}
+
+impl std::fmt::Display for Token {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Token::Keyword(keyword) => write!(f, "{}", format!("{keyword:?}").to_uppercase()),
+            Token::Identifier(name) => write!(f, "identifier \"{name}\""),
+            Token::Number(number) => write!(f, "number {number}"),
+            Token::String(string) => write!(f, "string '{string}'"),
+            Token::Parameter(Some(number)) => write!(f, "${number}"),
+            Token::Parameter(None) => write!(f, "?"),
+            Token::LeftParen => write!(f, "("),
+            Token::RightParen => write!(f, ")"),
+            Token::Comma => write!(f, ","),
+            Token::Semicolon => write!(f, ";"),
+            Token::Dot => write!(f, "."),
+            Token::Star => write!(f, "*"),
+            Token::Plus => write!(f, "+"),
+            Token::Minus => write!(f, "-"),
+            Token::Slash => write!(f, "/"),
+            Token::Percent => write!(f, "%"),
+            Token::Eq => write!(f, "="),
+            Token::NotEq => write!(f, "<>"),
+            Token::Lt => write!(f, "<"),
+            Token::LtEq => write!(f, "<="),
+            Token::Gt => write!(f, ">"),
+            Token::GtEq => write!(f, ">="),
+        }
+    }
+}
+
+/// Splits `sql` into tokens, skipping whitespace and `--` comments.
+pub fn tokenize(sql: &str) -> Result<Vec<Token>, ParseError> {
+    let mut tokens = Vec::new();
+    let mut chars = sql.char_indices().peekable();
+
+    while let Some(&(position, c)) = chars.peek() {
+        if c.is_whitespace() {
+            chars.next();
+            continue;
+        }
+
+        // line comment, runs to the end of the line
+        if c == '-' && sql[position..].starts_with("--") {
+            while chars.next_if(|&(_, c)| c != '\n').is_some() {}
+            continue;
+        }
+
+        if c.is_ascii_alphabetic() || c == '_' {
+            let mut word = String::new();
+            while let Some((_, c)) = chars.next_if(|&(_, c)| c.is_ascii_alphanumeric() || c == '_') {
+                word.push(c);
+            }
+            tokens.push(match Keyword::from_word(&word) {
+                Some(keyword) => Token::Keyword(keyword),
+                None => Token::Identifier(word.to_ascii_lowercase()),
+            });
+            continue;
+        }
+
+        if c.is_ascii_digit() {
+            let mut number = String::new();
+            while let Some((_, c)) = chars.next_if(|&(_, c)| c.is_ascii_digit() || c == '.') {
+                number.push(c);
+            }
+            tokens.push(Token::Number(number));
+            continue;
+        }
+
+        if c == '$' && sql[position + 1..].starts_with(|c: char| c.is_ascii_digit()) {
+            chars.next();
+            let mut number = String::new();
+            while let Some((_, c)) = chars.next_if(|&(_, c)| c.is_ascii_digit()) {
+                number.push(c);
+            }
+            tokens.push(Token::Parameter(Some(number.parse().map_err(|_| ParseError::InvalidParameter(format!("${number}")))?)));
+            continue;
+        }
+
+        if c == '\'' || c == '"' {
+            chars.next();
+            let text = read_quoted(&mut chars, c).ok_or(ParseError::UnterminatedString { position })?;
+            tokens.push(if c == '\'' { Token::String(text) } else { Token::Identifier(text) });
+            continue;
+        }
+
+        chars.next();
+        let token = match c {
+            '(' => Token::LeftParen,
+            ')' => Token::RightParen,
+            ',' => Token::Comma,
+            ';' => Token::Semicolon,
+            '.' => Token::Dot,
+            '*' => Token::Star,
+            '+' => Token::Plus,
+            '-' => Token::Minus,
+            '/' => Token::Slash,
+            '%' => Token::Percent,
+            '?' => Token::Parameter(None),
+            '=' => Token::Eq,
+            '!' if chars.next_if(|&(_, c)| c == '=').is_some() => Token::NotEq,
+            '<' if chars.next_if(|&(_, c)| c == '=').is_some() => Token::LtEq,
+            '<' if chars.next_if(|&(_, c)| c == '>').is_some() => Token::NotEq,
+            '<' => Token::Lt,
+            '>' if chars.next_if(|&(_, c)| c == '=').is_some() => Token::GtEq,
+            '>' => Token::Gt,
+            _ => return Err(ParseError::UnexpectedCharacter { character: c, position }),
+        };
+        tokens.push(token);
+    }
+
+    Ok(tokens)
+}
+
+/// `name` written so that it reads back as the same identifier: as it is if
+/// it's a lowercase word that isn't a keyword, and in double quotes otherwise.
+pub(super) fn quote_identifier(name: &str) -> String {
+    let plain = name.starts_with(|c: char| c.is_ascii_lowercase() || c == '_')
+        && name.chars().all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_')
+        && Keyword::from_word(name).is_none();
+    if plain { name.to_string() } else { format!("\"{}\"", name.replace('"', "\"\"")) }
+}
+
+/// `sql` as a single line of tokens separated by single spaces, without its
+/// comments, and with keywords and unquoted identifiers in one case, so
+/// statements written differently but made of the same tokens have the same
+/// text.
+///
+/// # Examples
+///
+/// ```
+/// use gondor_rdbms::sql::normalize;
+///
+/// let normalized = normalize("select Name\n  from users -- all of them").unwrap();
+/// assert_eq!(normalized, normalize("SELECT name FROM USERS").unwrap());
+/// assert_ne!(normalized, normalize("SELECT \"Name\" FROM users").unwrap());
+/// ```
+pub fn normalize(sql: &str) -> Result<String, ParseError> {
+    let tokens: Vec<String> = tokenize(sql)?
+        .into_iter()
+        .map(|token| match token {
+            // quoted, so an identifier can't be taken for a keyword
+            Token::Identifier(name) => format!("\"{}\"", name.replace('"', "\"\"")),
+            Token::Number(number) => number,
+            Token::String(text) => format!("'{}'", text.replace('\'', "''")),
+            token => token.to_string(),
+        })
+        .collect();
+    Ok(tokens.join(" "))
+}
+
+/// reads up to the closing `quote`, where a doubled quote stands for one quote character
+fn read_quoted(chars: &mut std::iter::Peekable<std::str::CharIndices>, quote: char) -> Option<String> {
+    let mut text = String::new();
+    loop {
+        let (_, c) = chars.next()?;
+        // a lone quote ends the string, a doubled one is a literal quote
+        if c == quote && chars.next_if(|&(_, c)| c == quote).is_none() {
+            return Some(text);
+        }
+        text.push(c);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tokenize_statement() {
+        let tokens = tokenize("SELECT id, Name FROM users WHERE age >= 21 -- adults only\n;").unwrap();
+        assert_eq!(
+            tokens,
+            vec![
+                Token::Keyword(Keyword::Select),
+                Token::Identifier("id".into()),
+                Token::Comma,
+                Token::Identifier("name".into()),
+                Token::Keyword(Keyword::From),
+                Token::Identifier("users".into()),
+                Token::Keyword(Keyword::Where),
+                Token::Identifier("age".into()),
+                Token::GtEq,
+                Token::Number("21".into()),
+                Token::Semicolon,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_quoting_and_operators() {
+        let tokens = tokenize(r#"'it''s' "Mixed Case" <> != <= < 1.5"#).unwrap();
+        assert_eq!(
+            tokens,
+            vec![
+                Token::String("it's".into()),
+                Token::Identifier("Mixed Case".into()),
+                Token::NotEq,
+                Token::NotEq,
+                Token::LtEq,
+                Token::Lt,
+                Token::Number("1.5".into()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_lexer_errors() {
+        assert_eq!(tokenize("SELECT 'oops"), Err(ParseError::UnterminatedString { position: 7 }));
+        assert_eq!(tokenize("SELECT #"), Err(ParseError::UnexpectedCharacter { character: '#', position: 7 }));
+        assert_eq!(tokenize("SELECT $a"), Err(ParseError::UnexpectedCharacter { character: '$', position: 7 }));
+        assert_eq!(tokenize("SELECT $99999999999"), Err(ParseError::InvalidParameter("$99999999999".into())));
+    }
+
+    #[test]
+    fn test_parameters() {
+        let tokens = tokenize("id = ? AND name=$12").unwrap();
+        assert_eq!(tokens[2], Token::Parameter(None));
+        assert_eq!(tokens[6], Token::Parameter(Some(12)));
+
+        assert_eq!(normalize("SELECT a,'it''s'  FROM \"T\" WHERE b = $1").unwrap(), r#"SELECT "a" , 'it''s' FROM "T" WHERE "b" = $1"#);
+    }
+
+    #[test]
+    fn test_quote_identifier() {
+        assert_eq!(quote_identifier("visits_2"), "visits_2");
+        for (name, quoted) in [("Name", r#""Name""#), ("check", r#""check""#), ("2nd", r#""2nd""#), ("a b", r#""a b""#), (r#"say "hi""#, r#""say ""hi""""#)] {
+            assert_eq!(quote_identifier(name), quoted);
+            assert_eq!(tokenize(quoted).unwrap(), [Token::Identifier(name.into())]);
+        }
+    }
+}
@@ -0,0 +1,63 @@
+mod lexer;
+pub use lexer::{Keyword, Token, normalize, tokenize};
+
+mod ast;
+pub use ast::{
+    AggregateFunction, Analyze, BinaryOp, ColumnConstraint, ColumnDef, Copy, CopyDirection, CopyOptions, CreateIndex, CreateTable, Delete, Explain, Expr, Function, IndexedColumn, Insert, Literal, OrderByItem,
+    Select, SelectItem, Statement, TableConstraint, TableRef, TransactionStatement, UnaryOp, Update, Vacuum,
+};
+
+mod parser;
+pub use parser::{parse, parse_expr, parse_statement};
+
+use crate::types::DataType;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ParseError {
+    UnexpectedCharacter { character: char, position: usize },
+    UnterminatedString { position: usize },
+    UnexpectedToken { expected: String, found: String },
+    UnexpectedEnd { expected: String },
+    InvalidNumber(String),
+    UnknownType(String),
+    /// a `NUMERIC(precision, scale)` with more digits than a decimal can have,
+    /// none, or more of them after the point than in all
+    InvalidPrecision { precision: u32, scale: u32 },
+    UnknownFunction(String),
+    /// a function called with more or fewer arguments than it takes
+    WrongArgumentCount { function: String, expected: usize, found: usize },
+    /// a typed literal, like `DATE '...'`, whose text isn't a value of the type
+    InvalidLiteral { data_type: DataType, text: String },
+    /// a `$n` parameter numbered 0 or beyond what a number can hold
+    InvalidParameter(String),
+    /// a statement with both `?` and `$n` parameters
+    MixedParameters,
+    /// an option of a `COPY` that doesn't exist, or a value it can't take
+    InvalidOption(String),
+}
+
+impl std::fmt::Display for ParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ParseError::UnexpectedCharacter { character, position } => {
+                write!(f, "Unexpected character '{character}' at position {position}")
+            }
+            ParseError::UnterminatedString { position } => write!(f, "Unterminated string starting at position {position}"),
+            ParseError::UnexpectedToken { expected, found } => write!(f, "Expected {expected}, found {found}"),
+            ParseError::UnexpectedEnd { expected } => write!(f, "Expected {expected}, found end of input"),
+            ParseError::InvalidNumber(number) => write!(f, "Invalid number: {number}"),
+            ParseError::UnknownType(name) => write!(f, "Unknown type: {name}"),
+            ParseError::InvalidPrecision { precision, scale } => {
+                write!(f, "Invalid NUMERIC({precision},{scale}): precision must be 1 to {} and scale 0 to the precision", crate::types::Decimal::MAX_PRECISION)
+            }
+            ParseError::UnknownFunction(name) => write!(f, "Unknown function: {name}"),
+            ParseError::WrongArgumentCount { function, expected, found } => write!(f, "Function {function} takes {expected} arguments, not {found}"),
+            ParseError::InvalidLiteral { data_type, text } => write!(f, "Invalid {data_type} literal: '{text}'"),
+            ParseError::InvalidParameter(parameter) => write!(f, "Invalid parameter: {parameter}"),
+            ParseError::MixedParameters => write!(f, "Cannot mix ? and $n parameters in one statement"),
+            ParseError::InvalidOption(option) => write!(f, "Invalid COPY option: {option}"),
+        }
+    }
+}
+
+impl std::error::Error for ParseError {}
@@ -0,0 +1,1263 @@
+use super::ParseError;
+use super::ast::{
+    AggregateFunction, Analyze, BinaryOp, ColumnConstraint, ColumnDef, Copy, CopyDirection, CopyOptions, CreateIndex, CreateTable, Delete, Explain, Expr, Function, IndexedColumn, Insert, Literal,
+    OrderByItem,
+    Select, SelectItem, Statement, TableConstraint, TableRef, TransactionStatement, UnaryOp, Update, Vacuum,
+};
+use super::lexer::{Keyword, Token, tokenize};
+use crate::transaction::IsolationLevel;
+use crate::types::{DataType, Decimal, Value};
+
+/// Parses a script of `;` separated statements.
+///
+/// # Examples
+///
+/// ```
+/// use gondor_rdbms::sql::{parse, Statement};
+///
+/// let statements = parse("CREATE TABLE users (id INT, name VARCHAR(32)); SELECT * FROM users;").unwrap();
+/// assert_eq!(statements.len(), 2);
+/// assert!(matches!(statements[1], Statement::Select(_)));
+/// ```
+pub fn parse(sql: &str) -> Result<Vec<Statement>, ParseError> {
+    let mut parser = Parser::new(tokenize(sql)?);
+    let mut statements = Vec::new();
+    loop {
+        while parser.consume(&Token::Semicolon) {}
+        if parser.peek().is_none() {
+            return Ok(statements);
+        }
+        statements.push(parser.statement()?);
+        if parser.peek().is_some() {
+            parser.expect(&Token::Semicolon)?;
+        }
+    }
+}
+
+/// Parses exactly one statement, optionally followed by a `;`.
+pub fn parse_statement(sql: &str) -> Result<Statement, ParseError> {
+    let mut parser = Parser::new(tokenize(sql)?);
+    let statement = parser.statement()?;
+    parser.consume(&Token::Semicolon);
+    match parser.next() {
+        Some(token) => Err(ParseError::UnexpectedToken {
+            expected: "end of statement".to_string(),
+            found: token.to_string(),
+        }),
+        None => Ok(statement),
+    }
+}
+
+/// Parses a single expression, like the `CHECK` constraints and column
+/// defaults the catalog keeps as text.
+pub fn parse_expr(sql: &str) -> Result<Expr, ParseError> {
+    let mut parser = Parser::new(tokenize(sql)?);
+    let expr = parser.expr()?;
+    match parser.next() {
+        Some(token) => Err(ParseError::UnexpectedToken { expected: "end of expression".to_string(), found: token.to_string() }),
+        None => Ok(expr),
+    }
+}
+
+/// one of the comma separated items between the parentheses of a `CREATE TABLE`
+enum TableElement {
+    Column(ColumnDef),
+    Constraint(TableConstraint),
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    position: usize,
+    /// how many `?` parameters the statement has had so far, the last one's number
+    positional: usize,
+    /// whether the statement has had a `$n` parameter
+    numbered: bool,
+}
+
+impl Parser {
+    fn new(tokens: Vec<Token>) -> Self {
+        Self { tokens, position: 0, positional: 0, numbered: false }
+    }
+
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.position)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.position).cloned();
+        self.position += 1;
+        token
+    }
+
+    /// advances past `token` if it's next, returns whether it did
+    fn consume(&mut self, token: &Token) -> bool {
+        if self.peek() == Some(token) {
+            self.position += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn consume_keyword(&mut self, keyword: Keyword) -> bool {
+        self.consume(&Token::Keyword(keyword))
+    }
+
+    fn expect(&mut self, token: &Token) -> Result<(), ParseError> {
+        if self.consume(token) { Ok(()) } else { Err(self.unexpected(&token.to_string())) }
+    }
+
+    fn expect_keyword(&mut self, keyword: Keyword) -> Result<(), ParseError> {
+        self.expect(&Token::Keyword(keyword))
+    }
+
+    fn expect_identifier(&mut self) -> Result<String, ParseError> {
+        match self.peek() {
+            Some(Token::Identifier(name)) => {
+                let name = name.clone();
+                self.position += 1;
+                Ok(name)
+            }
+            _ => Err(self.unexpected("identifier")),
+        }
+    }
+
+    fn expect_string(&mut self) -> Result<String, ParseError> {
+        match self.peek() {
+            Some(Token::String(string)) => {
+                let string = string.clone();
+                self.position += 1;
+                Ok(string)
+            }
+            _ => Err(self.unexpected("string")),
+        }
+    }
+
+    /// error for the token at the current position not being `expected`
+    fn unexpected(&self, expected: &str) -> ParseError {
+        match self.peek() {
+            Some(token) => ParseError::UnexpectedToken {
+                expected: expected.to_string(),
+                found: token.to_string(),
+            },
+            None => ParseError::UnexpectedEnd { expected: expected.to_string() },
+        }
+    }
+
+    /// parses a comma separated list of at least one item
+    fn list<T>(&mut self, mut item: impl FnMut(&mut Self) -> Result<T, ParseError>) -> Result<Vec<T>, ParseError> {
+        let mut items = vec![item(self)?];
+        while self.consume(&Token::Comma) {
+            items.push(item(self)?);
+        }
+        Ok(items)
+    }
+
+    fn statement(&mut self) -> Result<Statement, ParseError> {
+        // every statement numbers its parameters anew
+        (self.positional, self.numbered) = (0, false);
+        match self.next() {
+            Some(Token::Keyword(Keyword::Create)) => self.create(),
+            Some(Token::Keyword(Keyword::Insert)) => Ok(Statement::Insert(self.insert()?)),
+            Some(Token::Keyword(Keyword::Select)) => Ok(Statement::Select(self.select()?)),
+            Some(Token::Keyword(Keyword::Update)) => Ok(Statement::Update(self.update()?)),
+            Some(Token::Keyword(Keyword::Delete)) => Ok(Statement::Delete(self.delete()?)),
+            Some(Token::Keyword(Keyword::Analyze)) => Ok(Statement::Analyze(self.analyze()?)),
+            Some(Token::Keyword(Keyword::Vacuum)) => Ok(Statement::Vacuum(self.vacuum()?)),
+            Some(Token::Keyword(Keyword::Explain)) => Ok(Statement::Explain(self.explain()?)),
+            Some(Token::Keyword(Keyword::Copy)) => Ok(Statement::Copy(self.copy()?)),
+            Some(Token::Keyword(Keyword::Begin)) => {
+                self.transaction_noise();
+                Ok(Statement::Transaction(TransactionStatement::Begin(self.transaction_mode()?)))
+            }
+            Some(Token::Keyword(Keyword::Start)) => {
+                self.expect_keyword(Keyword::Transaction)?;
+                Ok(Statement::Transaction(TransactionStatement::Begin(self.transaction_mode()?)))
+            }
+            Some(Token::Keyword(Keyword::Set)) => {
+                self.expect_keyword(Keyword::Transaction)?;
+                match self.transaction_mode()? {
+                    Some(level) => Ok(Statement::Transaction(TransactionStatement::SetIsolationLevel(level))),
+                    None => Err(self.unexpected("ISOLATION")),
+                }
+            }
+            Some(Token::Keyword(Keyword::Commit)) => {
+                self.transaction_noise();
+                Ok(Statement::Transaction(TransactionStatement::Commit))
+            }
+            Some(Token::Keyword(Keyword::Rollback)) => Ok(Statement::Transaction(self.rollback()?)),
+            Some(Token::Keyword(Keyword::Savepoint)) => Ok(Statement::Transaction(TransactionStatement::Savepoint(self.expect_identifier()?))),
+            Some(Token::Keyword(Keyword::Release)) => {
+                self.consume_keyword(Keyword::Savepoint);
+                Ok(Statement::Transaction(TransactionStatement::Release(self.expect_identifier()?)))
+            }
+            _ => {
+                self.position -= 1;
+                Err(self.unexpected("statement"))
+            }
+        }
+    }
+
+    fn create(&mut self) -> Result<Statement, ParseError> {
+        if self.consume_keyword(Keyword::Table) {
+            return Ok(Statement::CreateTable(self.create_table()?));
+        }
+        let unique = self.consume_keyword(Keyword::Unique);
+        if self.consume_keyword(Keyword::Index) {
+            return Ok(Statement::CreateIndex(self.create_index(unique)?));
+        }
+        Err(self.unexpected(if unique { "INDEX" } else { "TABLE or INDEX" }))
+    }
+
+    fn create_table(&mut self) -> Result<CreateTable, ParseError> {
+        let name = self.expect_identifier()?;
+        self.expect(&Token::LeftParen)?;
+        let mut columns = Vec::new();
+        let mut constraints = Vec::new();
+        for element in self.list(Self::table_element)? {
+            match element {
+                TableElement::Column(column) => columns.push(column),
+                TableElement::Constraint(constraint) => constraints.push(constraint),
+            }
+        }
+        self.expect(&Token::RightParen)?;
+        Ok(CreateTable { name, columns, constraints })
+    }
+
+    fn create_index(&mut self, unique: bool) -> Result<CreateIndex, ParseError> {
+        let name = self.expect_identifier()?;
+        self.expect_keyword(Keyword::On)?;
+        let table = self.expect_identifier()?;
+        self.expect(&Token::LeftParen)?;
+        let columns = self.list(Self::indexed_column)?;
+        self.expect(&Token::RightParen)?;
+        Ok(CreateIndex { name, table, columns, unique })
+    }
+
+    fn indexed_column(&mut self) -> Result<IndexedColumn, ParseError> {
+        let name = self.expect_identifier()?;
+        let (descending, nulls_first) = self.sort_order()?;
+        Ok(IndexedColumn { name, descending, nulls_first })
+    }
+
+    /// `[ASC | DESC] [NULLS FIRST | NULLS LAST]`, as whether it's descending and where NULLs go if given
+    fn sort_order(&mut self) -> Result<(bool, Option<bool>), ParseError> {
+        let descending = if self.consume_keyword(Keyword::Desc) {
+            true
+        } else {
+            self.consume_keyword(Keyword::Asc);
+            false
+        };
+        if !self.consume_keyword(Keyword::Nulls) {
+            return Ok((descending, None));
+        }
+        if self.consume_keyword(Keyword::First) {
+            return Ok((descending, Some(true)));
+        }
+        self.expect_keyword(Keyword::Last)?;
+        Ok((descending, Some(false)))
+    }
+
+    fn table_element(&mut self) -> Result<TableElement, ParseError> {
+        if let Some((name, expr)) = self.check()? {
+            return Ok(TableElement::Constraint(TableConstraint::Check { name, expr }));
+        }
+        if self.consume_keyword(Keyword::Primary) {
+            self.expect_keyword(Keyword::Key)?;
+            return Ok(TableElement::Constraint(TableConstraint::PrimaryKey(self.column_list()?)));
+        }
+        if self.consume_keyword(Keyword::Unique) {
+            return Ok(TableElement::Constraint(TableConstraint::Unique(self.column_list()?)));
+        }
+        Ok(TableElement::Column(self.column_def()?))
+    }
+
+    /// `(column, ...)`
+    fn column_list(&mut self) -> Result<Vec<String>, ParseError> {
+        self.expect(&Token::LeftParen)?;
+        let columns = self.list(Self::expect_identifier)?;
+        self.expect(&Token::RightParen)?;
+        Ok(columns)
+    }
+
+    fn column_def(&mut self) -> Result<ColumnDef, ParseError> {
+        let name = self.expect_identifier()?;
+        let data_type = self.data_type()?;
+        let mut constraints = Vec::new();
+        loop {
+            if self.consume_keyword(Keyword::Primary) {
+                self.expect_keyword(Keyword::Key)?;
+                constraints.push(ColumnConstraint::PrimaryKey);
+            } else if self.consume_keyword(Keyword::Unique) {
+                constraints.push(ColumnConstraint::Unique);
+            } else if self.consume_keyword(Keyword::Not) {
+                self.expect_keyword(Keyword::Null)?;
+                constraints.push(ColumnConstraint::NotNull);
+            } else if self.consume_keyword(Keyword::Default) {
+                constraints.push(ColumnConstraint::Default(self.expr()?));
+            } else if let Some((name, expr)) = self.check()? {
+                constraints.push(ColumnConstraint::Check { name, expr });
+            } else {
+                return Ok(ColumnDef { name, data_type, constraints });
+            }
+        }
+    }
+
+    /// `[CONSTRAINT name] CHECK (expr)`, as its name if given and its
+    /// expression, `None` if there's no `CONSTRAINT` or `CHECK` next. Only a
+    /// check constraint can be named.
+    fn check(&mut self) -> Result<Option<(Option<String>, Expr)>, ParseError> {
+        let name = if self.consume_keyword(Keyword::Constraint) { Some(self.expect_identifier()?) } else { None };
+        if name.is_some() {
+            self.expect_keyword(Keyword::Check)?;
+        } else if !self.consume_keyword(Keyword::Check) {
+            return Ok(None);
+        }
+        self.expect(&Token::LeftParen)?;
+        let expr = self.expr()?;
+        self.expect(&Token::RightParen)?;
+        Ok(Some((name, expr)))
+    }
+
+    fn data_type(&mut self) -> Result<DataType, ParseError> {
+        let type_name = self.expect_identifier()?;
+        let data_type = DataType::from_name(&type_name).ok_or_else(|| ParseError::UnknownType(type_name.clone()))?;
+        if data_type == DataType::Varchar(None) && self.consume(&Token::LeftParen) {
+            let length = self.type_modifier("length")?;
+            self.expect(&Token::RightParen)?;
+            return Ok(DataType::Varchar(Some(length)));
+        }
+        if data_type == DataType::Numeric(None) && self.consume(&Token::LeftParen) {
+            let precision = self.type_modifier("precision")?;
+            let scale = if self.consume(&Token::Comma) { self.type_modifier("scale")? } else { 0 };
+            self.expect(&Token::RightParen)?;
+            if !(1..=Decimal::MAX_PRECISION as u32).contains(&precision) || scale > precision {
+                return Err(ParseError::InvalidPrecision { precision, scale });
+            }
+            return Ok(DataType::Numeric(Some((precision as u8, scale as u8))));
+        }
+        Ok(data_type)
+    }
+
+    /// a number in the parentheses after a type's name, `what` it is for error messages
+    fn type_modifier(&mut self, what: &str) -> Result<u32, ParseError> {
+        match self.next() {
+            Some(Token::Number(number)) => number.parse().map_err(|_| ParseError::InvalidNumber(number)),
+            _ => {
+                self.position -= 1;
+                Err(self.unexpected(what))
+            }
+        }
+    }
+
+    fn insert(&mut self) -> Result<Insert, ParseError> {
+        self.expect_keyword(Keyword::Into)?;
+        let table = self.expect_identifier()?;
+        let mut columns = Vec::new();
+        if self.peek() == Some(&Token::LeftParen) {
+            columns = self.column_list()?;
+        }
+        self.expect_keyword(Keyword::Values)?;
+        let rows = self.list(|parser| {
+            parser.expect(&Token::LeftParen)?;
+            let row = parser.list(Self::expr)?;
+            parser.expect(&Token::RightParen)?;
+            Ok(row)
+        })?;
+        Ok(Insert { table, columns, rows })
+    }
+
+    fn select(&mut self) -> Result<Select, ParseError> {
+        let projection = self.list(Self::select_item)?;
+        let from = if self.consume_keyword(Keyword::From) { self.table_refs()? } else { Vec::new() };
+        let where_clause = self.where_clause()?;
+        let group_by = if self.consume_keyword(Keyword::Group) {
+            self.expect_keyword(Keyword::By)?;
+            self.list(Self::expr)?
+        } else {
+            Vec::new()
+        };
+        let having = if self.consume_keyword(Keyword::Having) { Some(self.expr()?) } else { None };
+        let order_by = if self.consume_keyword(Keyword::Order) {
+            self.expect_keyword(Keyword::By)?;
+            self.list(Self::order_by_item)?
+        } else {
+            Vec::new()
+        };
+        let limit = if self.consume_keyword(Keyword::Limit) { Some(self.row_count()?) } else { None };
+        let offset = if self.consume_keyword(Keyword::Offset) { self.row_count()? } else { 0 };
+        Ok(Select { projection, from, where_clause, group_by, having, order_by, limit, offset })
+    }
+
+    fn order_by_item(&mut self) -> Result<OrderByItem, ParseError> {
+        let expr = self.expr()?;
+        let (descending, nulls_first) = self.sort_order()?;
+        Ok(OrderByItem { expr, descending, nulls_first })
+    }
+
+    /// the number of rows of a `LIMIT` or `OFFSET`
+    fn row_count(&mut self) -> Result<u64, ParseError> {
+        match self.next() {
+            Some(Token::Number(number)) => number.parse().map_err(|_| ParseError::InvalidNumber(number)),
+            _ => {
+                self.position -= 1;
+                Err(self.unexpected("row count"))
+            }
+        }
+    }
+
+    fn select_item(&mut self) -> Result<SelectItem, ParseError> {
+        if self.consume(&Token::Star) {
+            return Ok(SelectItem::Wildcard);
+        }
+        let expr = self.expr()?;
+        let alias = if self.consume_keyword(Keyword::As) {
+            Some(self.expect_identifier()?)
+        } else if let Some(Token::Identifier(_)) = self.peek() {
+            // `SELECT a b` aliases without the AS
+            Some(self.expect_identifier()?)
+        } else {
+            None
+        };
+        Ok(SelectItem::Expr { expr, alias })
+    }
+
+    /// `table [, table | CROSS JOIN table | [INNER] JOIN table ON expr] ...`
+    fn table_refs(&mut self) -> Result<Vec<TableRef>, ParseError> {
+        let mut tables = vec![self.table_ref()?];
+        loop {
+            if self.consume(&Token::Comma) {
+                tables.push(self.table_ref()?);
+            } else if self.consume_keyword(Keyword::Cross) {
+                self.expect_keyword(Keyword::Join)?;
+                tables.push(self.table_ref()?);
+            } else if self.consume_keyword(Keyword::Inner) || self.peek() == Some(&Token::Keyword(Keyword::Join)) {
+                self.expect_keyword(Keyword::Join)?;
+                let mut table = self.table_ref()?;
+                self.expect_keyword(Keyword::On)?;
+                table.on = Some(self.expr()?);
+                tables.push(table);
+            } else {
+                return Ok(tables);
+            }
+        }
+    }
+
+    fn table_ref(&mut self) -> Result<TableRef, ParseError> {
+        let name = self.expect_identifier()?;
+        let alias = if self.consume_keyword(Keyword::As) || matches!(self.peek(), Some(Token::Identifier(_))) {
+            Some(self.expect_identifier()?)
+        } else {
+            None
+        };
+        Ok(TableRef { name, alias, on: None })
+    }
+
+    fn update(&mut self) -> Result<Update, ParseError> {
+        let table = self.expect_identifier()?;
+        self.expect_keyword(Keyword::Set)?;
+        let assignments = self.list(|parser| {
+            let column = parser.expect_identifier()?;
+            parser.expect(&Token::Eq)?;
+            Ok((column, parser.expr()?))
+        })?;
+        let where_clause = self.where_clause()?;
+        Ok(Update { table, assignments, where_clause })
+    }
+
+    fn delete(&mut self) -> Result<Delete, ParseError> {
+        self.expect_keyword(Keyword::From)?;
+        let table = self.expect_identifier()?;
+        let where_clause = self.where_clause()?;
+        Ok(Delete { table, where_clause })
+    }
+
+    fn analyze(&mut self) -> Result<Analyze, ParseError> {
+        let table = if matches!(self.peek(), Some(Token::Identifier(_))) { Some(self.expect_identifier()?) } else { None };
+        Ok(Analyze { table })
+    }
+
+    fn vacuum(&mut self) -> Result<Vacuum, ParseError> {
+        let table = if matches!(self.peek(), Some(Token::Identifier(_))) { Some(self.expect_identifier()?) } else { None };
+        Ok(Vacuum { table })
+    }
+
+    fn explain(&mut self) -> Result<Explain, ParseError> {
+        let analyze = self.consume_keyword(Keyword::Analyze);
+        self.expect_keyword(Keyword::Select)?;
+        Ok(Explain { analyze, query: self.select()? })
+    }
+
+    /// the optional `TRANSACTION` or `WORK` after `BEGIN`, `COMMIT` and `ROLLBACK`
+    fn transaction_noise(&mut self) {
+        if !self.consume_keyword(Keyword::Transaction) {
+            self.consume_keyword(Keyword::Work);
+        }
+    }
+
+    /// `ISOLATION LEVEL level`, if that's what comes next; none of its words are reserved
+    fn transaction_mode(&mut self) -> Result<Option<IsolationLevel>, ParseError> {
+        if !self.consume_word("isolation") {
+            return Ok(None);
+        }
+        self.expect_word("level")?;
+        let level = if self.consume_word("read") {
+            // READ UNCOMMITTED is as READ COMMITTED as it gets
+            if !self.consume_word("uncommitted") {
+                self.expect_word("committed")?;
+            }
+            IsolationLevel::ReadCommitted
+        } else if self.consume_word("repeatable") {
+            self.expect_word("read")?;
+            IsolationLevel::RepeatableRead
+        } else {
+            self.expect_word("serializable")?;
+            IsolationLevel::Serializable
+        };
+        Ok(Some(level))
+    }
+
+    /// consumes the unquoted identifier `word`, if it's next
+    fn consume_word(&mut self, word: &str) -> bool {
+        let found = matches!(self.peek(), Some(Token::Identifier(name)) if name == word);
+        if found {
+            self.position += 1;
+        }
+        found
+    }
+
+    fn expect_word(&mut self, word: &str) -> Result<(), ParseError> {
+        if self.consume_word(word) { Ok(()) } else { Err(self.unexpected(&word.to_ascii_uppercase())) }
+    }
+
+    fn rollback(&mut self) -> Result<TransactionStatement, ParseError> {
+        self.transaction_noise();
+        if !self.consume_keyword(Keyword::To) {
+            return Ok(TransactionStatement::Rollback);
+        }
+        self.consume_keyword(Keyword::Savepoint);
+        Ok(TransactionStatement::RollbackTo(self.expect_identifier()?))
+    }
+
+    fn copy(&mut self) -> Result<Copy, ParseError> {
+        let table = self.expect_identifier()?;
+        let mut columns = Vec::new();
+        if self.peek() == Some(&Token::LeftParen) {
+            columns = self.column_list()?;
+        }
+        let direction = if self.consume_keyword(Keyword::From) {
+            CopyDirection::From
+        } else {
+            self.expect_keyword(Keyword::To)?;
+            CopyDirection::To
+        };
+        let path = self.expect_string()?;
+
+        let mut options = CopyOptions::default();
+        let with = self.consume_keyword(Keyword::With);
+        if with || self.peek() == Some(&Token::LeftParen) {
+            self.expect(&Token::LeftParen)?;
+            self.list(|parser| parser.copy_option(&mut options))?;
+            self.expect(&Token::RightParen)?;
+        }
+        Ok(Copy { table, columns, direction, path, options })
+    }
+
+    /// `FORMAT csv`, `DELIMITER 'c'`, `HEADER [TRUE | FALSE]` or `NULL 'text'`, set in `options`
+    fn copy_option(&mut self, options: &mut CopyOptions) -> Result<(), ParseError> {
+        if self.consume_keyword(Keyword::Null) {
+            options.null = self.expect_string()?;
+            return Ok(());
+        }
+        let name = self.expect_identifier()?;
+        match name.as_str() {
+            // the only format there is
+            "format" => match self.expect_identifier()?.as_str() {
+                "csv" => {}
+                format => return Err(ParseError::InvalidOption(format!("format {format}"))),
+            },
+            "delimiter" => {
+                let delimiter = self.expect_string()?;
+                let mut chars = delimiter.chars();
+                options.delimiter = match (chars.next(), chars.next()) {
+                    (Some(c), None) if !matches!(c, '"' | '\r' | '\n') => c,
+                    _ => return Err(ParseError::InvalidOption(format!("delimiter '{delimiter}'"))),
+                };
+            }
+            "header" => {
+                options.header = !self.consume_keyword(Keyword::False);
+                if options.header {
+                    self.consume_keyword(Keyword::True);
+                }
+            }
+            _ => return Err(ParseError::InvalidOption(name)),
+        }
+        Ok(())
+    }
+
+    fn where_clause(&mut self) -> Result<Option<Expr>, ParseError> {
+        if self.consume_keyword(Keyword::Where) { Ok(Some(self.expr()?)) } else { Ok(None) }
+    }
+
+    // expressions, from loosest to tightest binding:
+    // OR, AND, NOT, comparisons and IS [NOT] NULL, + -, * / %, unary minus
+
+    fn expr(&mut self) -> Result<Expr, ParseError> {
+        let mut left = self.and_expr()?;
+        while self.consume_keyword(Keyword::Or) {
+            left = Expr::binary(left, BinaryOp::Or, self.and_expr()?);
+        }
+        Ok(left)
+    }
+
+    fn and_expr(&mut self) -> Result<Expr, ParseError> {
+        let mut left = self.not_expr()?;
+        while self.consume_keyword(Keyword::And) {
+            left = Expr::binary(left, BinaryOp::And, self.not_expr()?);
+        }
+        Ok(left)
+    }
+
+    fn not_expr(&mut self) -> Result<Expr, ParseError> {
+        if self.consume_keyword(Keyword::Not) {
+            let expr = self.not_expr()?;
+            return Ok(Expr::Unary { op: UnaryOp::Not, expr: Box::new(expr) });
+        }
+        self.comparison()
+    }
+
+    fn comparison(&mut self) -> Result<Expr, ParseError> {
+        let left = self.additive()?;
+        if self.consume_keyword(Keyword::Is) {
+            let negated = self.consume_keyword(Keyword::Not);
+            self.expect_keyword(Keyword::Null)?;
+            return Ok(Expr::IsNull { expr: Box::new(left), negated });
+        }
+        let op = match self.peek() {
+            Some(Token::Eq) => BinaryOp::Eq,
+            Some(Token::NotEq) => BinaryOp::NotEq,
+            Some(Token::Lt) => BinaryOp::Lt,
+            Some(Token::LtEq) => BinaryOp::LtEq,
+            Some(Token::Gt) => BinaryOp::Gt,
+            Some(Token::GtEq) => BinaryOp::GtEq,
+            _ => return Ok(left),
+        };
+        self.position += 1;
+        Ok(Expr::binary(left, op, self.additive()?))
+    }
+
+    fn additive(&mut self) -> Result<Expr, ParseError> {
+        let mut left = self.multiplicative()?;
+        loop {
+            let op = match self.peek() {
+                Some(Token::Plus) => BinaryOp::Plus,
+                Some(Token::Minus) => BinaryOp::Minus,
+                _ => return Ok(left),
+            };
+            self.position += 1;
+            left = Expr::binary(left, op, self.multiplicative()?);
+        }
+    }
+
+    fn multiplicative(&mut self) -> Result<Expr, ParseError> {
+        let mut left = self.unary()?;
+        loop {
+            let op = match self.peek() {
+                Some(Token::Star) => BinaryOp::Multiply,
+                Some(Token::Slash) => BinaryOp::Divide,
+                Some(Token::Percent) => BinaryOp::Modulo,
+                _ => return Ok(left),
+            };
+            self.position += 1;
+            left = Expr::binary(left, op, self.unary()?);
+        }
+    }
+
+    fn unary(&mut self) -> Result<Expr, ParseError> {
+        if self.consume(&Token::Minus) {
+            // fold the sign into number literals so i64::MIN can be written
+            if let Some(Token::Number(number)) = self.peek() {
+                let number = format!("-{number}");
+                self.position += 1;
+                return Ok(Expr::Literal(number_literal(number)?));
+            }
+            let expr = self.unary()?;
+            return Ok(Expr::Unary { op: UnaryOp::Minus, expr: Box::new(expr) });
+        }
+        self.primary()
+    }
+
+    fn primary(&mut self) -> Result<Expr, ParseError> {
+        let expr = match self.next() {
+            Some(Token::Number(number)) => Expr::Literal(number_literal(number)?),
+            Some(Token::String(string)) => Expr::Literal(Literal::String(string)),
+            Some(Token::Keyword(Keyword::True)) => Expr::Literal(Literal::Boolean(true)),
+            Some(Token::Keyword(Keyword::False)) => Expr::Literal(Literal::Boolean(false)),
+            Some(Token::Keyword(Keyword::Null)) => Expr::Literal(Literal::Null),
+            Some(Token::Parameter(number)) => self.parameter(number)?,
+            Some(Token::Identifier(name)) if self.peek() == Some(&Token::LeftParen) => self.function(name)?,
+            Some(Token::Identifier(name)) if matches!(self.peek(), Some(Token::String(_))) && literal_type(&name).is_some() => self.typed_literal(&name)?,
+            Some(Token::Identifier(name)) => {
+                if self.consume(&Token::Dot) {
+                    let column = self.expect_identifier()?;
+                    Expr::Column { table: Some(name), name: column }
+                } else {
+                    Expr::Column { table: None, name }
+                }
+            }
+            Some(Token::LeftParen) => {
+                let expr = self.expr()?;
+                self.expect(&Token::RightParen)?;
+                expr
+            }
+            _ => {
+                self.position -= 1;
+                return Err(self.unexpected("expression"));
+            }
+        };
+        Ok(expr)
+    }
+
+    /// a `$n` parameter, or the `?` after as many others
+    fn parameter(&mut self, number: Option<u32>) -> Result<Expr, ParseError> {
+        match number {
+            None if self.numbered => Err(ParseError::MixedParameters),
+            None => {
+                self.positional += 1;
+                Ok(Expr::Parameter(self.positional))
+            }
+            Some(_) if self.positional > 0 => Err(ParseError::MixedParameters),
+            Some(0) => Err(ParseError::InvalidParameter("$0".to_string())),
+            Some(number) => {
+                self.numbered = true;
+                Ok(Expr::Parameter(number as usize))
+            }
+        }
+    }
+
+    /// `function(expr, ...)`, `COUNT(*)` or `EXTRACT(field FROM expr)`, after the function's name
+    fn function(&mut self, name: String) -> Result<Expr, ParseError> {
+        self.expect(&Token::LeftParen)?;
+        if let Some(function) = AggregateFunction::from_name(&name) {
+            let arg = if function == AggregateFunction::Count && self.consume(&Token::Star) { None } else { Some(Box::new(self.expr()?)) };
+            self.expect(&Token::RightParen)?;
+            return Ok(Expr::Aggregate { function, arg });
+        }
+        if name.eq_ignore_ascii_case("extract") {
+            // the field is a word, which date_part takes as a string
+            let field = self.expect_identifier()?;
+            self.expect_keyword(Keyword::From)?;
+            let expr = self.expr()?;
+            self.expect(&Token::RightParen)?;
+            return Ok(Expr::Function { function: Function::DatePart, args: vec![Expr::Literal(Literal::String(field)), expr] });
+        }
+        let function = Function::from_name(&name).ok_or(ParseError::UnknownFunction(name))?;
+        let args = if self.peek() == Some(&Token::RightParen) { Vec::new() } else { self.list(Self::expr)? };
+        self.expect(&Token::RightParen)?;
+        if args.len() != function.arity() {
+            return Err(ParseError::WrongArgumentCount { function: function.name().to_string(), expected: function.arity(), found: args.len() });
+        }
+        Ok(Expr::Function { function, args })
+    }
+
+    /// the value whose text is next, after the name of its type (one `literal_type` knows)
+    fn typed_literal(&mut self, type_name: &str) -> Result<Expr, ParseError> {
+        let data_type = literal_type(type_name).unwrap();
+        let text = self.expect_string()?;
+        let literal = match Value::parse(&text, data_type) {
+            Some(Value::Date(days)) => Literal::Date(days),
+            Some(Value::Time(micros)) => Literal::Time(micros),
+            Some(Value::Timestamp(micros)) => Literal::Timestamp(micros),
+            Some(Value::Interval(interval)) => Literal::Interval(interval),
+            _ => return Err(ParseError::InvalidLiteral { data_type, text }),
+        };
+        Ok(Expr::Literal(literal))
+    }
+}
+
+/// the type a literal written as `name 'text'` has, for the types that can be written that way
+fn literal_type(name: &str) -> Option<DataType> {
+    DataType::from_name(name).filter(|data_type| matches!(data_type, DataType::Date | DataType::Time | DataType::Timestamp | DataType::Interval))
+}
+
+/// numbers are integers if they can be, like PostgreSQL's, and decimals otherwise
+fn number_literal(number: String) -> Result<Literal, ParseError> {
+    if let Ok(value) = number.parse() {
+        return Ok(Literal::Integer(value));
+    }
+    Decimal::parse(&number).map(Literal::Decimal).ok_or(ParseError::InvalidNumber(number))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn int(value: i64) -> Expr {
+        Expr::Literal(Literal::Integer(value))
+    }
+
+    #[test]
+    fn test_create_table() {
+        let statement = parse_statement("CREATE TABLE users (id INT, name VARCHAR(32), active boolean)").unwrap();
+        assert_eq!(
+            statement,
+            Statement::CreateTable(CreateTable {
+                name: "users".into(),
+                columns: vec![
+                    ColumnDef { name: "id".into(), data_type: DataType::Int, constraints: vec![] },
+                    ColumnDef { name: "name".into(), data_type: DataType::Varchar(Some(32)), constraints: vec![] },
+                    ColumnDef { name: "active".into(), data_type: DataType::Bool, constraints: vec![] },
+                ],
+                constraints: vec![],
+            })
+        );
+        assert_eq!(
+            parse_statement("CREATE TABLE t (x FLOAT)"),
+            Err(ParseError::UnknownType("float".into()))
+        );
+    }
+
+    #[test]
+    fn test_numeric_types_and_literals() {
+        let Statement::CreateTable(create) = parse_statement("CREATE TABLE t (a NUMERIC(10, 2), b DECIMAL(5), c numeric)").unwrap() else {
+            panic!("expected a create table");
+        };
+        let types: Vec<DataType> = create.columns.iter().map(|column| column.data_type).collect();
+        assert_eq!(types, [DataType::Numeric(Some((10, 2))), DataType::Numeric(Some((5, 0))), DataType::Numeric(None)]);
+        assert_eq!(parse_statement("CREATE TABLE t (a NUMERIC(39, 2))"), Err(ParseError::InvalidPrecision { precision: 39, scale: 2 }));
+        assert_eq!(parse_statement("CREATE TABLE t (a NUMERIC(2, 3))"), Err(ParseError::InvalidPrecision { precision: 2, scale: 3 }));
+        assert!(matches!(parse_statement("CREATE TABLE t (a NUMERIC(10,))"), Err(ParseError::UnexpectedToken { .. })));
+
+        let Statement::Select(select) = parse_statement("SELECT 1.50, -0.25, 99999999999999999999, 7").unwrap() else {
+            panic!("expected a select");
+        };
+        let literals: Vec<String> = select
+            .projection
+            .iter()
+            .map(|item| match item {
+                SelectItem::Expr { expr: Expr::Literal(literal @ (Literal::Decimal(_) | Literal::Integer(_))), .. } => literal.to_string(),
+                item => panic!("expected a number, got {item:?}"),
+            })
+            .collect();
+        assert_eq!(literals, ["1.50", "-0.25", "99999999999999999999", "7"]);
+    }
+
+    #[test]
+    fn test_temporal_literals_and_functions() {
+        let Statement::CreateTable(create) = parse_statement("CREATE TABLE t (a DATE, b time, c TIMESTAMP, d INTERVAL)").unwrap() else {
+            panic!("expected a create table");
+        };
+        let types: Vec<DataType> = create.columns.iter().map(|column| column.data_type).collect();
+        assert_eq!(types, [DataType::Date, DataType::Time, DataType::Timestamp, DataType::Interval]);
+
+        let select = |sql: &str| -> Vec<String> {
+            let Statement::Select(select) = parse_statement(&format!("SELECT {sql}")).unwrap() else {
+                panic!("expected a select");
+            };
+            select.projection.iter().map(|item| match item {
+                SelectItem::Expr { expr, .. } => expr.to_string(),
+                SelectItem::Wildcard => panic!("expected an expression"),
+            }).collect()
+        };
+        assert_eq!(
+            select("date '2024-02-29', TIME '23:59:59.5', timestamp '2024-02-29T12:00:00+01:00', INTERVAL '1 day ago', date - 1"),
+            ["DATE '2024-02-29'", "TIME '23:59:59.5'", "TIMESTAMP '2024-02-29 11:00:00'", "INTERVAL '-1 days'", "(date - 1)"]
+        );
+        assert_eq!(
+            select("now(), DATE_TRUNC('month', c), extract(year FROM c), date_part('dow', a)"),
+            ["now()", "date_trunc('month', c)", "date_part('year', c)", "date_part('dow', a)"]
+        );
+        assert_eq!(parse_statement("SELECT DATE '2023-02-29'"), Err(ParseError::InvalidLiteral { data_type: DataType::Date, text: "2023-02-29".into() }));
+        assert_eq!(parse_statement("SELECT now(1)"), Err(ParseError::WrongArgumentCount { function: "now".into(), expected: 0, found: 1 }));
+        assert_eq!(parse_statement("SELECT age(c)"), Err(ParseError::UnknownFunction("age".into())));
+        assert!(matches!(parse_statement("SELECT name 'x'"), Err(ParseError::UnexpectedToken { .. })));
+    }
+
+    #[test]
+    fn test_create_table_constraints() {
+        let statement = parse_statement("CREATE TABLE t (id INT PRIMARY KEY, a INT UNIQUE, b TEXT, UNIQUE (a, b), PRIMARY KEY (b))").unwrap();
+        let Statement::CreateTable(create) = statement else {
+            panic!("expected a create table");
+        };
+        let constraints: Vec<_> = create.columns.iter().map(|column| column.constraints.clone()).collect();
+        assert_eq!(constraints, vec![vec![ColumnConstraint::PrimaryKey], vec![ColumnConstraint::Unique], vec![]]);
+        assert_eq!(
+            create.constraints,
+            vec![
+                TableConstraint::Unique(vec!["a".into(), "b".into()]),
+                TableConstraint::PrimaryKey(vec!["b".into()]),
+            ]
+        );
+
+        assert!(parse_statement("CREATE TABLE t (id INT PRIMARY)").is_err());
+        assert!(parse_statement("CREATE TABLE t (id INT, UNIQUE ())").is_err());
+    }
+
+    #[test]
+    fn test_check_not_null_and_default() {
+        let sql = "CREATE TABLE t (id INT NOT NULL DEFAULT -1 CHECK (id >= -1), b TEXT CONSTRAINT named CHECK (b <> '') DEFAULT now(), CONSTRAINT positive CHECK (id > 0 OR b IS NULL), CHECK (b <> ''))";
+        let Statement::CreateTable(create) = parse_statement(sql).unwrap() else {
+            panic!("expected a create table");
+        };
+        let expr = |sql: &str| parse_expr(sql).unwrap();
+        assert_eq!(
+            create.columns[0].constraints,
+            [ColumnConstraint::NotNull, ColumnConstraint::Default(expr("-1")), ColumnConstraint::Check { name: None, expr: expr("id >= -1") }]
+        );
+        assert_eq!(
+            create.columns[1].constraints,
+            [ColumnConstraint::Check { name: Some("named".into()), expr: expr("b <> ''") }, ColumnConstraint::Default(expr("now()"))]
+        );
+        assert_eq!(
+            create.constraints,
+            [TableConstraint::Check { name: Some("positive".into()), expr: expr("id > 0 OR b IS NULL") }, TableConstraint::Check { name: None, expr: expr("b <> ''") }]
+        );
+
+        // only check constraints can be named, and need parentheses
+        assert!(parse_statement("CREATE TABLE t (id INT CONSTRAINT pk PRIMARY KEY)").is_err());
+        assert!(parse_statement("CREATE TABLE t (id INT CHECK id > 0)").is_err());
+        assert!(parse_statement("CREATE TABLE t (id INT NOT)").is_err());
+        assert!(matches!(parse_expr("a b"), Err(ParseError::UnexpectedToken { .. })));
+    }
+
+    #[test]
+    fn test_expressions_read_back_the_same() {
+        for sql in [
+            "a + b * -c",
+            "NOT (a IS NULL) IS NOT NULL",
+            "- -1",
+            "-(-a)",
+            "-(a IS NULL)",
+            "\"Mixed Case\" > \"select\".\"from\"",
+            "date_part('year', at) = 2024 AND at < TIMESTAMP '2024-06-01 12:00:00'",
+            "s <> 'it''s' OR n = -1.50",
+        ] {
+            let expr = parse_expr(sql).unwrap();
+            assert_eq!(parse_expr(&expr.to_string()), Ok(expr), "{sql}");
+        }
+    }
+
+    #[test]
+    fn test_create_index() {
+        let statement = parse_statement("CREATE UNIQUE INDEX scores_idx ON scores (game, score DESC NULLS LAST, player ASC NULLS FIRST)").unwrap();
+        let column = |name: &str, descending, nulls_first| IndexedColumn { name: name.into(), descending, nulls_first };
+        assert_eq!(
+            statement,
+            Statement::CreateIndex(CreateIndex {
+                name: "scores_idx".into(),
+                table: "scores".into(),
+                columns: vec![column("game", false, None), column("score", true, Some(false)), column("player", false, Some(true))],
+                unique: true,
+            })
+        );
+        let Statement::CreateIndex(create) = parse_statement("create index i on t (a)").unwrap() else {
+            panic!("expected a create index");
+        };
+        assert!(!create.unique);
+
+        assert!(parse_statement("CREATE INDEX i ON t ()").is_err());
+        assert!(parse_statement("CREATE INDEX i ON t (a NULLS)").is_err());
+        assert!(parse_statement("CREATE UNIQUE TABLE t (a INT)").is_err());
+    }
+
+    #[test]
+    fn test_insert() {
+        let statement = parse_statement("INSERT INTO users (id, name) VALUES (1, 'alice'), (-2, NULL);").unwrap();
+        assert_eq!(
+            statement,
+            Statement::Insert(Insert {
+                table: "users".into(),
+                columns: vec!["id".into(), "name".into()],
+                rows: vec![
+                    vec![int(1), Expr::Literal(Literal::String("alice".into()))],
+                    vec![int(-2), Expr::Literal(Literal::Null)],
+                ],
+            })
+        );
+
+        let Statement::Insert(insert) = parse_statement("INSERT INTO users VALUES (1, TRUE)").unwrap() else {
+            panic!("expected an insert");
+        };
+        assert!(insert.columns.is_empty());
+    }
+
+    #[test]
+    fn test_select_where() {
+        let statement = parse_statement("SELECT *, id + 1 AS next, u.name FROM users WHERE id > 3 AND name IS NOT NULL").unwrap();
+        assert_eq!(
+            statement,
+            Statement::Select(Select {
+                projection: vec![
+                    SelectItem::Wildcard,
+                    SelectItem::Expr {
+                        expr: Expr::binary(Expr::column("id"), BinaryOp::Plus, int(1)),
+                        alias: Some("next".into()),
+                    },
+                    SelectItem::Expr {
+                        expr: Expr::Column { table: Some("u".into()), name: "name".into() },
+                        alias: None,
+                    },
+                ],
+                from: vec![TableRef::new("users")],
+                where_clause: Some(Expr::binary(
+                    Expr::binary(Expr::column("id"), BinaryOp::Gt, int(3)),
+                    BinaryOp::And,
+                    Expr::IsNull { expr: Box::new(Expr::column("name")), negated: true },
+                )),
+                group_by: Vec::new(),
+                having: None,
+                order_by: Vec::new(),
+                limit: None,
+                offset: 0,
+            })
+        );
+    }
+
+    #[test]
+    fn test_group_by() {
+        let Statement::Select(select) = parse_statement("SELECT kind, COUNT(*), sum(price * 2) FROM items GROUP BY kind, size HAVING Max(price) > 10").unwrap() else {
+            panic!("expected a select");
+        };
+        let aggregate = |function, arg: Option<Expr>| Expr::Aggregate { function, arg: arg.map(Box::new) };
+        assert_eq!(
+            select.projection[1..],
+            [
+                SelectItem::Expr { expr: aggregate(AggregateFunction::Count, None), alias: None },
+                SelectItem::Expr { expr: aggregate(AggregateFunction::Sum, Some(Expr::binary(Expr::column("price"), BinaryOp::Multiply, int(2)))), alias: None },
+            ]
+        );
+        assert_eq!(select.group_by, [Expr::column("kind"), Expr::column("size")]);
+        assert_eq!(select.having, Some(Expr::binary(aggregate(AggregateFunction::Max, Some(Expr::column("price"))), BinaryOp::Gt, int(10))));
+        assert_eq!(select.having.unwrap().to_string(), "(max(price) > 10)");
+
+        assert_eq!(parse_statement("SELECT median(x) FROM t"), Err(ParseError::UnknownFunction("median".into())));
+        assert!(matches!(parse_statement("SELECT sum(*) FROM t"), Err(ParseError::UnexpectedToken { .. })));
+        assert!(matches!(parse_statement("SELECT x FROM t GROUP x"), Err(ParseError::UnexpectedToken { .. })));
+    }
+
+    #[test]
+    fn test_order_by_and_limit() {
+        let Statement::Select(select) = parse_statement("SELECT name, score FROM players ORDER BY score DESC NULLS LAST, 1, name ASC LIMIT 10 OFFSET 20").unwrap() else {
+            panic!("expected a select");
+        };
+        assert_eq!(
+            select.order_by,
+            [
+                OrderByItem { expr: Expr::column("score"), descending: true, nulls_first: Some(false) },
+                OrderByItem { expr: int(1), descending: false, nulls_first: None },
+                OrderByItem { expr: Expr::column("name"), descending: false, nulls_first: None },
+            ]
+        );
+        assert_eq!((select.limit, select.offset), (Some(10), 20));
+
+        let Statement::Select(select) = parse_statement("SELECT * FROM players OFFSET 5").unwrap() else {
+            panic!("expected a select");
+        };
+        assert_eq!((select.order_by.len(), select.limit, select.offset), (0, None, 5));
+
+        assert!(matches!(parse_statement("SELECT * FROM players LIMIT -1"), Err(ParseError::UnexpectedToken { .. })));
+        assert!(matches!(parse_statement("SELECT * FROM players LIMIT 2.5"), Err(ParseError::InvalidNumber(_))));
+        assert!(matches!(parse_statement("SELECT * FROM players ORDER score"), Err(ParseError::UnexpectedToken { .. })));
+    }
+
+    #[test]
+    fn test_select_joins() {
+        let statement = parse_statement("SELECT * FROM users u, orders AS o JOIN items ON o.id = items.order_id CROSS JOIN tags INNER JOIN x ON TRUE").unwrap();
+        let Statement::Select(select) = statement else {
+            panic!("expected a select");
+        };
+        let on = Expr::binary(
+            Expr::Column { table: Some("o".into()), name: "id".into() },
+            BinaryOp::Eq,
+            Expr::Column { table: Some("items".into()), name: "order_id".into() },
+        );
+        assert_eq!(
+            select.from,
+            vec![
+                TableRef { name: "users".into(), alias: Some("u".into()), on: None },
+                TableRef { name: "orders".into(), alias: Some("o".into()), on: None },
+                TableRef { name: "items".into(), alias: None, on: Some(on) },
+                TableRef::new("tags"),
+                TableRef { name: "x".into(), alias: None, on: Some(Expr::Literal(Literal::Boolean(true))) },
+            ]
+        );
+        assert_eq!(select.from[1].reference(), "o");
+        assert_eq!(select.from[3].reference(), "tags");
+
+        assert!(parse_statement("SELECT * FROM a JOIN b").is_err());
+        assert!(parse_statement("SELECT * FROM a CROSS b").is_err());
+        assert!(parse_statement("SELECT * FROM a INNER b ON TRUE").is_err());
+        assert!(parse_statement("SELECT * FROM a,").is_err());
+    }
+
+    #[test]
+    fn test_update_and_delete() {
+        let statement = parse_statement("UPDATE users SET name = 'bob', id = id * 2 WHERE id = 1").unwrap();
+        assert_eq!(
+            statement,
+            Statement::Update(Update {
+                table: "users".into(),
+                assignments: vec![
+                    ("name".into(), Expr::Literal(Literal::String("bob".into()))),
+                    ("id".into(), Expr::binary(Expr::column("id"), BinaryOp::Multiply, int(2))),
+                ],
+                where_clause: Some(Expr::binary(Expr::column("id"), BinaryOp::Eq, int(1))),
+            })
+        );
+
+        let statement = parse_statement("DELETE FROM users").unwrap();
+        assert_eq!(statement, Statement::Delete(Delete { table: "users".into(), where_clause: None }));
+    }
+
+    #[test]
+    fn test_analyze() {
+        assert_eq!(parse_statement("ANALYZE users").unwrap(), Statement::Analyze(Analyze { table: Some("users".into()) }));
+        assert_eq!(parse_statement("analyze;").unwrap(), Statement::Analyze(Analyze { table: None }));
+        assert!(parse_statement("ANALYZE users, orders").is_err());
+    }
+
+    #[test]
+    fn test_vacuum() {
+        assert_eq!(parse_statement("VACUUM users").unwrap(), Statement::Vacuum(Vacuum { table: Some("users".into()) }));
+        assert_eq!(parse_statement("vacuum").unwrap(), Statement::Vacuum(Vacuum { table: None }));
+        assert!(parse_statement("VACUUM users, orders").is_err());
+    }
+
+    #[test]
+    fn test_explain() {
+        let Statement::Select(query) = parse_statement("SELECT * FROM users WHERE id = 1").unwrap() else { unreachable!() };
+        assert_eq!(parse_statement("EXPLAIN SELECT * FROM users WHERE id = 1").unwrap(), Statement::Explain(Explain { analyze: false, query: query.clone() }));
+        assert_eq!(parse_statement("explain analyze SELECT * FROM users WHERE id = 1").unwrap(), Statement::Explain(Explain { analyze: true, query }));
+        assert_eq!(
+            parse_statement("EXPLAIN DELETE FROM users"),
+            Err(ParseError::UnexpectedToken { expected: "SELECT".into(), found: "DELETE".into() })
+        );
+    }
+
+    #[test]
+    fn test_copy() {
+        assert_eq!(
+            parse_statement("COPY users FROM '/tmp/users.csv'").unwrap(),
+            Statement::Copy(Copy {
+                table: "users".into(),
+                columns: vec![],
+                direction: CopyDirection::From,
+                path: "/tmp/users.csv".into(),
+                options: CopyOptions::default(),
+            })
+        );
+        let statement = parse_statement("COPY users (id, name) TO 'users.csv' WITH (FORMAT csv, DELIMITER ';', HEADER, NULL 'none')").unwrap();
+        assert_eq!(
+            statement,
+            Statement::Copy(Copy {
+                table: "users".into(),
+                columns: vec!["id".into(), "name".into()],
+                direction: CopyDirection::To,
+                path: "users.csv".into(),
+                options: CopyOptions { delimiter: ';', header: true, null: "none".into() },
+            })
+        );
+        let Statement::Copy(copy) = parse_statement("COPY users TO 'users.csv' (HEADER FALSE)").unwrap() else { unreachable!() };
+        assert!(!copy.options.header);
+
+        assert_eq!(parse_statement("COPY users TO 'users.csv' (DELIMITER ';;')"), Err(ParseError::InvalidOption("delimiter ';;'".into())));
+        assert_eq!(parse_statement("COPY users TO 'users.csv' (FORMAT binary)"), Err(ParseError::InvalidOption("format binary".into())));
+        assert_eq!(parse_statement("COPY users TO 'users.csv' (QUOTE '|')"), Err(ParseError::InvalidOption("quote".into())));
+        assert!(parse_statement("COPY users TO users").is_err());
+    }
+
+    #[test]
+    fn test_transaction_statements() {
+        let transaction = |sql| match parse_statement(sql).unwrap() {
+            Statement::Transaction(statement) => statement,
+            statement => panic!("expected a transaction statement, got {statement:?}"),
+        };
+        assert_eq!(transaction("BEGIN"), TransactionStatement::Begin(None));
+        assert_eq!(transaction("begin work;"), TransactionStatement::Begin(None));
+        assert_eq!(transaction("START TRANSACTION"), TransactionStatement::Begin(None));
+        assert_eq!(transaction("BEGIN ISOLATION LEVEL REPEATABLE READ"), TransactionStatement::Begin(Some(IsolationLevel::RepeatableRead)));
+        assert_eq!(transaction("start transaction isolation level serializable"), TransactionStatement::Begin(Some(IsolationLevel::Serializable)));
+        assert_eq!(transaction("SET TRANSACTION ISOLATION LEVEL READ COMMITTED"), TransactionStatement::SetIsolationLevel(IsolationLevel::ReadCommitted));
+        assert_eq!(transaction("SET TRANSACTION ISOLATION LEVEL READ UNCOMMITTED"), TransactionStatement::SetIsolationLevel(IsolationLevel::ReadCommitted));
+        assert_eq!(transaction("COMMIT TRANSACTION"), TransactionStatement::Commit);
+        assert_eq!(transaction("ROLLBACK"), TransactionStatement::Rollback);
+        assert_eq!(transaction("SAVEPOINT before_update"), TransactionStatement::Savepoint("before_update".into()));
+        assert_eq!(transaction("ROLLBACK TO before_update"), TransactionStatement::RollbackTo("before_update".into()));
+        assert_eq!(transaction("ROLLBACK WORK TO SAVEPOINT before_update"), TransactionStatement::RollbackTo("before_update".into()));
+        assert_eq!(transaction("RELEASE SAVEPOINT before_update"), TransactionStatement::Release("before_update".into()));
+        assert_eq!(transaction("RELEASE before_update"), TransactionStatement::Release("before_update".into()));
+        assert!(parse_statement("START").is_err());
+        assert!(parse_statement("SAVEPOINT").is_err());
+        assert!(parse_statement("ROLLBACK TO SAVEPOINT").is_err());
+        assert!(parse_statement("SET TRANSACTION").is_err());
+        assert!(parse_statement("BEGIN ISOLATION LEVEL REPEATABLE").is_err());
+        // the words stay usable as names
+        assert!(parse_statement("SELECT level, read FROM isolation").is_ok());
+    }
+
+    #[test]
+    fn test_operator_precedence() {
+        let Statement::Select(select) = parse_statement("SELECT 1 + 2 * 3 = 7 OR NOT a AND b").unwrap() else {
+            panic!("expected a select");
+        };
+        let SelectItem::Expr { expr, .. } = &select.projection[0] else {
+            panic!("expected an expression");
+        };
+        assert_eq!(expr.to_string(), "(((1 + (2 * 3)) = 7) OR (NOT a AND b))");
+
+        let Statement::Select(select) = parse_statement("SELECT (1 + 2) * 3 - 4 - 5").unwrap() else {
+            panic!("expected a select");
+        };
+        let SelectItem::Expr { expr, .. } = &select.projection[0] else {
+            panic!("expected an expression");
+        };
+        assert_eq!(expr.to_string(), "((((1 + 2) * 3) - 4) - 5)");
+    }
+
+    #[test]
+    fn test_parameters() {
+        let Statement::Update(update) = parse_statement("UPDATE t SET a = ? WHERE b = ? AND c <> ?").unwrap() else {
+            panic!("expected an update");
+        };
+        assert_eq!(update.assignments[0].1, Expr::Parameter(1));
+        assert_eq!(update.where_clause.unwrap().to_string(), "((b = $2) AND (c <> $3))");
+
+        // numbered ones can come in any order, and be used more than once
+        let Statement::Select(select) = parse_statement("SELECT $2 FROM t WHERE a = $1 OR b = $2").unwrap() else {
+            panic!("expected a select");
+        };
+        assert_eq!(select.where_clause.unwrap().to_string(), "((a = $1) OR (b = $2))");
+        // the count starts over with every statement
+        let statements = parse("DELETE FROM t WHERE a = ?; DELETE FROM t WHERE a = ?").unwrap();
+        assert_eq!(statements[0], statements[1]);
+
+        assert_eq!(parse_statement("SELECT $1 + ?"), Err(ParseError::MixedParameters));
+        assert_eq!(parse_statement("SELECT ? + $1"), Err(ParseError::MixedParameters));
+        assert_eq!(parse_statement("SELECT $0"), Err(ParseError::InvalidParameter("$0".into())));
+    }
+
+    #[test]
+    fn test_multiple_statements() {
+        let statements = parse("SELECT 1;; DELETE FROM t; ").unwrap();
+        assert_eq!(statements.len(), 2);
+        assert!(parse("").unwrap().is_empty());
+        assert!(parse("SELECT 1 SELECT 2").is_err());
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert_eq!(
+            parse_statement("SELECT FROM users"),
+            Err(ParseError::UnexpectedToken { expected: "expression".into(), found: "FROM".into() })
+        );
+        assert_eq!(
+            parse_statement("INSERT INTO users VALUES (1"),
+            Err(ParseError::UnexpectedEnd { expected: ")".into() })
+        );
+        assert_eq!(parse_statement("SELECT 1.2.3"), Err(ParseError::InvalidNumber("1.2.3".into())));
+        let too_many_digits = "9".repeat(39);
+        assert_eq!(parse_statement(&format!("SELECT {too_many_digits}")), Err(ParseError::InvalidNumber(too_many_digits)));
+        assert_eq!(parse_statement("SELECT -9223372036854775808").unwrap(), parse_statement("SELECT -9223372036854775808;").unwrap());
+        assert!(matches!(parse_statement("DROP TABLE users"), Err(ParseError::UnexpectedToken { .. })));
+    }
+}
@@ -1,114 +1,1126 @@
-use super::Page;
+use super::{DiskManager, DiskManagerError, LruPolicy, Page, PageError, PageType, ReplacementPolicy};
+use crate::metrics::{Counter, Gauge, MetricsRegistry};
+use crate::wal::{INVALID_LSN, LogManager, Lsn, WalError};
 use std::collections::HashMap;
-use std::fs::File;
-use std::io::Read;
-use std::io::Write;
+use std::sync::{Arc, Mutex};
+
+/// bookkeeping bytes charged per resident frame on top of the page buffer itself
+/// (hash map entry, page id in the admission queue, allocator slack)
+const FRAME_OVERHEAD: usize = 64;
 
 #[derive(Debug)]
 pub enum BufferPoolError {
     PageNotFound,
+    PageNotPinned,
+    OutOfMemory,
+    OutOfDiskSpace,
+    PageError(PageError),
+    WalError(WalError),
     IoError(std::io::Error),
 }
 
+impl std::fmt::Display for BufferPoolError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BufferPoolError::PageNotFound => write!(f, "Page not found"),
+            BufferPoolError::PageNotPinned => write!(f, "Page not pinned"),
+            BufferPoolError::OutOfMemory => write!(f, "Out of memory"),
+            BufferPoolError::OutOfDiskSpace => write!(f, "Out of disk space"),
+            BufferPoolError::PageError(error) => write!(f, "Page error: {error}"),
+            BufferPoolError::WalError(error) => write!(f, "WAL error: {error}"),
+            BufferPoolError::IoError(error) => write!(f, "I/O error: {error}"),
+        }
+    }
+}
+
+impl std::error::Error for BufferPoolError {}
+
+impl From<PageError> for BufferPoolError {
+    fn from(error: PageError) -> Self {
+        BufferPoolError::PageError(error)
+    }
+}
+
+impl From<WalError> for BufferPoolError {
+    fn from(error: WalError) -> Self {
+        BufferPoolError::WalError(error)
+    }
+}
+
 impl From<std::io::Error> for BufferPoolError {
     fn from(error: std::io::Error) -> Self {
-        BufferPoolError::IoError(error)
+        // ENOSPC gets its own variant so callers can tell a full disk apart from other I/O failures
+        if error.kind() == std::io::ErrorKind::StorageFull {
+            BufferPoolError::OutOfDiskSpace
+        } else {
+            BufferPoolError::IoError(error)
+        }
+    }
+}
+
+impl From<DiskManagerError> for BufferPoolError {
+    fn from(error: DiskManagerError) -> Self {
+        match error {
+            DiskManagerError::PageOutOfRange(_) => BufferPoolError::PageNotFound,
+            DiskManagerError::IoError(error) => error.into(),
+            other => BufferPoolError::IoError(std::io::Error::other(other)),
+        }
+    }
+}
+
+/// Snapshot of the buffer pool's memory accounting, all values in bytes.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MemoryUsage {
+    /// configured budget, or `None` when the pool is unbounded
+    pub budget: Option<usize>,
+    /// bytes held by resident frames (page buffers plus per-frame overhead)
+    pub resident: usize,
+    /// bytes set aside by outstanding reservations and not yet drawn on
+    pub reserved: usize,
+}
+
+/// Snapshot of the buffer pool's activity since it was created, returned by
+/// `BufferPool::stats`.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct BufferPoolStats {
+    /// page requests served by a resident page
+    pub hits: u64,
+    /// page requests that had to read the page from disk
+    pub misses: u64,
+    pub evictions: u64,
+    /// modified pages written back to disk, whether evicted or flushed
+    pub dirty_flushes: u64,
+    /// resident pages with at least one pin right now
+    pub pinned_pages: usize,
+    /// resident pages modified since they were last written, right now
+    pub dirty_pages: usize,
+}
+
+impl BufferPoolStats {
+    /// Fraction of page requests served without going to disk, 0 before the first request.
+    pub fn hit_ratio(&self) -> f64 {
+        let requests = self.hits + self.misses;
+        if requests == 0 {
+            return 0.0;
+        }
+        self.hits as f64 / requests as f64
+    }
+}
+
+/// A claim on part of the buffer pool's memory budget.
+///
+/// Operations that need several frames at once (e.g. a B-tree split) take a
+/// reservation up front so they fail immediately with `OutOfMemory` instead of
+/// running out of frames halfway through. Until the holder draws on them with
+/// `BufferPool::with_reservation`, the reserved bytes count as used for every
+/// other admission, so nobody else can take those frames. Whatever is left is
+/// released when the reservation is dropped.
+#[derive(Debug)]
+pub struct Reservation {
+    id: u64,
+    bytes: usize,
+    /// the pool's outstanding reservations, by id, with the bytes each has left
+    ledger: Arc<Mutex<HashMap<u64, usize>>>,
+}
+
+impl Reservation {
+    pub fn bytes(&self) -> usize {
+        self.bytes
+    }
+
+    /// Bytes not drawn on yet.
+    pub fn remaining(&self) -> usize {
+        self.ledger.lock().unwrap().get(&self.id).copied().unwrap_or(0)
+    }
+}
+
+impl Drop for Reservation {
+    fn drop(&mut self) {
+        self.ledger.lock().unwrap().remove(&self.id);
     }
 }
 
+/// Caches pages in memory, reading them from the database file on demand.
+///
+/// The pool can be bounded by a number of frames (`with_capacity`) or by bytes
+/// (`with_memory_budget`). Once a bound is reached, admitting another page
+/// evicts a page that isn't pinned, writing it back to disk first if it was
+/// modified. Pinned pages are never evicted; if nothing can be evicted the
+/// admission fails with `OutOfMemory`. Which page goes is up to the pool's
+/// `ReplacementPolicy`: least recently used by default, see
+/// `with_replacement_policy` for the others.
+///
+/// # Examples
+///
+/// ```
+/// use gondor_rdbms::storage::{BufferPool, DiskManager};
+///
+/// let dir = tempfile::tempdir().unwrap();
+/// let disk_manager = DiskManager::open(dir.path().join("example.db")).unwrap();
+/// let mut buffer_pool = BufferPool::with_capacity(disk_manager, 16);
+///
+/// let page_id = buffer_pool.new_page().unwrap();
+/// let slot_id = buffer_pool.get_page_mut(page_id).unwrap().insert_tuple(b"Hello, world!").unwrap();
+/// buffer_pool.flush_all().unwrap();
+/// assert_eq!(buffer_pool.get_page(page_id).unwrap().get_data(slot_id).unwrap(), b"Hello, world!");
+/// ```
 pub struct BufferPool {
-    page_paths: HashMap<u32, String>,
+    disk_manager: DiskManager,
+    /// log forced to disk before any page is written, if the pool has one
+    log_manager: Option<Arc<Mutex<LogManager>>>,
     pages: HashMap<u32, Page>,
+    /// maximum number of resident frames, `None` for unbounded
+    capacity: Option<usize>,
+    /// maximum number of bytes resident frames may occupy, `None` for unbounded
+    memory_budget: Option<usize>,
+    /// picks the eviction victim, least recently used unless another policy was chosen
+    replacer: Box<dyn ReplacementPolicy>,
+    /// number of outstanding pins per page, pages without an entry are unpinned
+    pin_counts: HashMap<u32, u32>,
+    /// resident pages modified since they were last written to disk, with the
+    /// LSN the log was at when each became dirty (its recovery LSN)
+    dirty_pages: HashMap<u32, Lsn>,
+    /// outstanding reservations, by id, with the bytes each has left
+    reservations: Arc<Mutex<HashMap<u64, usize>>>,
+    next_reservation_id: u64,
+    /// the reservation admissions draw on inside `with_reservation`
+    drawing_on: Option<u64>,
+    hits: Counter,
+    misses: Counter,
+    pages_read: Counter,
+    pages_written: Counter,
+    evictions: Counter,
+    dirty_flushes: Counter,
+    resident_bytes: Gauge,
 }
 
 impl BufferPool {
-    pub fn new() -> Self {
-        Self { 
-            page_paths: HashMap::new(),
-            pages: HashMap::new() 
+    /// Creates an unbounded buffer pool over the pages stored by `disk_manager`.
+    pub fn new(disk_manager: DiskManager) -> Self {
+        Self {
+            disk_manager,
+            log_manager: None,
+            pages: HashMap::new(),
+            capacity: None,
+            memory_budget: None,
+            replacer: Box::new(LruPolicy::default()),
+            pin_counts: HashMap::new(),
+            dirty_pages: HashMap::new(),
+            reservations: Arc::new(Mutex::new(HashMap::new())),
+            next_reservation_id: 0,
+            drawing_on: None,
+            hits: Counter::new(),
+            misses: Counter::new(),
+            pages_read: Counter::new(),
+            pages_written: Counter::new(),
+            evictions: Counter::new(),
+            dirty_flushes: Counter::new(),
+            resident_bytes: Gauge::new(),
         }
     }
 
+    /// Creates a buffer pool that holds at most `frames` pages in memory at once.
+    pub fn with_capacity(disk_manager: DiskManager, frames: usize) -> Self {
+        Self {
+            capacity: Some(frames),
+            ..Self::new(disk_manager)
+        }
+    }
+
+    /// Creates a buffer pool whose resident frames never occupy more than `bytes`.
+    ///
+    /// Each resident page is charged its buffer size plus a fixed per-frame
+    /// overhead. Admitting a page evicts pages until the new frame fits
+    /// under the budget.
+    pub fn with_memory_budget(disk_manager: DiskManager, bytes: usize) -> Self {
+        Self {
+            memory_budget: Some(bytes),
+            ..Self::new(disk_manager)
+        }
+    }
+
+    /// Replaces the default LRU policy that picks which page to evict.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use gondor_rdbms::storage::{BufferPool, DiskManager, LruKPolicy};
+    ///
+    /// let dir = tempfile::tempdir().unwrap();
+    /// let disk_manager = DiskManager::open(dir.path().join("example.db")).unwrap();
+    /// let buffer_pool = BufferPool::with_capacity(disk_manager, 64).with_replacement_policy(LruKPolicy::new(2));
+    /// ```
+    pub fn with_replacement_policy(mut self, policy: impl ReplacementPolicy + 'static) -> Self {
+        self.replacer = Box::new(policy);
+        // normally nothing is resident yet, but don't leave pages the new policy can't evict
+        for &page_id in self.pages.keys() {
+            self.replacer.record_admission(page_id);
+        }
+        self
+    }
+
+    /// Makes the pool follow the write-ahead rule: before a page is written to
+    /// the database file, the log is flushed up to the page LSN so every record
+    /// describing the page's changes is durable first.
+    pub fn set_log_manager(&mut self, log_manager: Arc<Mutex<LogManager>>) {
+        self.log_manager = Some(log_manager);
+    }
+
+    /// Number of bytes charged against the budget for a single resident frame
+    /// holding a page of `page_size` bytes.
+    pub fn frame_size(page_size: usize) -> usize {
+        page_size + std::mem::size_of::<Page>() + FRAME_OVERHEAD
+    }
+
+    /// Size in bytes of the pages in the pool, as recorded in the database file.
+    pub fn page_size(&self) -> usize {
+        self.disk_manager.page_size()
+    }
+
+    /// Registers the pool's counters and gauges with `registry` so they show up in
+    /// its snapshots and Prometheus output.
+    pub fn register_metrics(&self, registry: &MetricsRegistry) {
+        registry.register_counter("gondor_buffer_pool_hits_total", "Page requests served by a resident page", &self.hits);
+        registry.register_counter("gondor_buffer_pool_misses_total", "Page requests that read the page from disk", &self.misses);
+        registry.register_counter("gondor_buffer_pool_pages_read_total", "Pages read from disk into the buffer pool", &self.pages_read);
+        registry.register_counter("gondor_buffer_pool_pages_written_total", "Pages written from the buffer pool to disk", &self.pages_written);
+        registry.register_counter("gondor_buffer_pool_evictions_total", "Pages evicted from the buffer pool", &self.evictions);
+        registry.register_counter("gondor_buffer_pool_dirty_flushes_total", "Modified pages written back to disk", &self.dirty_flushes);
+        registry.register_gauge("gondor_buffer_pool_resident_bytes", "Bytes held by resident buffer pool frames", &self.resident_bytes);
+    }
+
+    /// Hits, misses, evictions and write-backs so far, plus how many pages are
+    /// pinned and dirty right now.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use gondor_rdbms::storage::{BufferPool, DiskManager};
+    ///
+    /// let dir = tempfile::tempdir().unwrap();
+    /// let disk_manager = DiskManager::open(dir.path().join("example.db")).unwrap();
+    /// let mut buffer_pool = BufferPool::new(disk_manager);
+    ///
+    /// let page_id = buffer_pool.new_page().unwrap();
+    /// buffer_pool.get_page(page_id).unwrap();
+    /// buffer_pool.flush_all().unwrap();
+    ///
+    /// let stats = buffer_pool.stats();
+    /// assert_eq!((stats.hits, stats.misses, stats.dirty_flushes, stats.dirty_pages), (1, 0, 1, 0));
+    /// ```
+    pub fn stats(&self) -> BufferPoolStats {
+        BufferPoolStats {
+            hits: self.hits.get(),
+            misses: self.misses.get(),
+            evictions: self.evictions.get(),
+            dirty_flushes: self.dirty_flushes.get(),
+            pinned_pages: self.pin_counts.len(),
+            dirty_pages: self.dirty_pages.len(),
+        }
+    }
+
+    pub fn memory_usage(&self) -> MemoryUsage {
+        MemoryUsage {
+            budget: self.memory_budget,
+            resident: self.pages.len() * Self::frame_size(self.page_size()),
+            reserved: self.reserved(),
+        }
+    }
+
+    /// bytes outstanding reservations haven't drawn on yet
+    fn reserved(&self) -> usize {
+        self.reservations.lock().unwrap().values().sum()
+    }
+
+    /// Sets aside `bytes` of the memory budget for an operation that must not
+    /// run out of frames partway through.
+    ///
+    /// Pages are evicted until the resident frames, the outstanding
+    /// reservations and this one all fit in the budget, so the holder's
+    /// admissions never have to evict. Fails with `OutOfMemory` if they can't
+    /// be made to fit. Unbounded pools always grant reservations.
+    pub fn reserve(&mut self, bytes: usize) -> Result<Reservation, BufferPoolError> {
+        if let Some(budget) = self.memory_budget {
+            if self.reserved() + bytes > budget {
+                return Err(BufferPoolError::OutOfMemory);
+            }
+            while self.pages.len() * Self::frame_size(self.page_size()) + self.reserved() + bytes > budget {
+                self.evict_one()?;
+            }
+        }
+
+        let id = self.next_reservation_id;
+        self.next_reservation_id += 1;
+        self.reservations.lock().unwrap().insert(id, bytes);
+        Ok(Reservation {
+            id,
+            bytes,
+            ledger: Arc::clone(&self.reservations),
+        })
+    }
+
+    /// Runs `f` with the pool admitting pages against `reservation`: each page
+    /// made resident takes its frame out of the reservation rather than out of
+    /// what's left of the budget, for as long as the reservation has a frame left.
+    pub fn with_reservation<T>(&mut self, reservation: &Reservation, f: impl FnOnce(&mut Self) -> T) -> T {
+        let previous = self.drawing_on.replace(reservation.id);
+        let result = f(self);
+        self.drawing_on = previous;
+        result
+    }
+
     pub fn read_page_from_disk(&mut self, page_id: u32) -> Result<&Page, BufferPoolError> {
-        let page_path = self.page_paths.get(&page_id).ok_or(BufferPoolError::PageNotFound)?;
-        let mut file = File::open(page_path)?;
-        let mut contents = Vec::new();
-        file.read_to_end(&mut contents)?;
-        let mut page = Page::new(page_id);
-        page.set_contents(&contents).map_err(|_| BufferPoolError::IoError(
-            std::io::Error::new(std::io::ErrorKind::InvalidData, "Invalid page contents")
-        ))?;
-        self.pages.insert(page_id, page);
+        let mut contents = vec![0u8; self.page_size()];
+        self.disk_manager.read_page(page_id, &mut contents)?;
+        self.pages_read.inc();
+        // pages are written out empty and synced when allocated, so zeroes mean the page was
+        // wiped or a write was lost, not that there's nothing on it
+        if contents.iter().all(|&byte| byte == 0) {
+            return Err(PageError::ChecksumMismatch.into());
+        }
+        let page = Page::from_bytes(&contents)?;
+        // don't hand out garbage tuples from a page that was corrupted on disk
+        page.verify_checksum()?;
+        self.admit_page(page_id, page)?;
+        // whatever was resident before has been replaced by the on-disk version
+        self.dirty_pages.remove(&page_id);
         Ok(self.pages.get(&page_id).unwrap())
     }
 
     pub fn write_page_to_disk(&mut self, page_id: u32) -> Result<(), BufferPoolError> {
-        let page = self.pages.get(&page_id).ok_or(BufferPoolError::PageNotFound)?;
-        let page_path = self.page_paths.get(&page_id).ok_or(BufferPoolError::PageNotFound)?;
-        let mut file = File::create(page_path)?;
-        file.write_all(page.get_raw_contents())?;
+        self.write_pages_to_disk(&[page_id])
+    }
+
+    /// Writes every dirty page back to disk and syncs the database file.
+    ///
+    /// The pages are written in one go, so if writing fails they all stay dirty.
+    pub fn flush_all(&mut self) -> Result<(), BufferPoolError> {
+        let mut dirty_page_ids: Vec<u32> = self.dirty_pages.keys().copied().collect();
+        dirty_page_ids.sort();
+        self.write_pages_to_disk(&dirty_page_ids)?;
+        self.disk_manager.sync()?;
         Ok(())
     }
 
-    pub fn add_page_path(&mut self, page_id: u32, path: String) {
-        self.page_paths.insert(page_id, path);
+    /// writes resident pages back in one batch, after forcing the log far enough for all of them
+    fn write_pages_to_disk(&mut self, page_ids: &[u32]) -> Result<(), BufferPoolError> {
+        let mut max_lsn = INVALID_LSN;
+        for page_id in page_ids {
+            let page = self.pages.get_mut(page_id).ok_or(BufferPoolError::PageNotFound)?;
+            page.update_checksum();
+            max_lsn = max_lsn.max(page.lsn());
+        }
+        if let Some(log_manager) = &self.log_manager {
+            log_manager.lock().unwrap().flush_to_lsn(max_lsn)?;
+        }
+
+        let pages: Vec<(u32, &[u8])> = page_ids.iter().map(|&page_id| (page_id, self.pages[&page_id].get_raw_contents())).collect();
+        self.disk_manager.write_pages(&pages)?;
+        for page_id in page_ids {
+            self.pages_written.inc();
+            if self.dirty_pages.remove(page_id).is_some() {
+                self.dirty_flushes.inc();
+            }
+        }
+        Ok(())
+    }
+
+    /// Syncs the database file, making every page written so far durable.
+    pub fn sync(&mut self) -> Result<(), BufferPoolError> {
+        self.disk_manager.sync()?;
+        Ok(())
+    }
+
+    pub fn is_dirty(&self, page_id: u32) -> bool {
+        self.dirty_pages.contains_key(&page_id)
+    }
+
+    /// The dirty page table: every dirty page with its recovery LSN, the first
+    /// log record whose change may not have reached disk. Sorted by page id.
+    pub fn dirty_page_table(&self) -> Vec<(u32, Lsn)> {
+        let mut dirty_pages: Vec<(u32, Lsn)> = self.dirty_pages.iter().map(|(&page_id, &rec_lsn)| (page_id, rec_lsn)).collect();
+        dirty_pages.sort();
+        dirty_pages
+    }
+
+    pub fn dirty_page_count(&self) -> usize {
+        self.dirty_pages.len()
+    }
+
+    /// Number of pages in the database file, resident or not.
+    pub fn num_pages(&self) -> u32 {
+        self.disk_manager.num_pages()
+    }
+
+    /// Grows the database file until `page_id` exists, e.g. when recovery replays
+    /// an allocation that never made it to disk. New pages read back as empty.
+    pub fn ensure_allocated(&mut self, page_id: u32) -> Result<(), BufferPoolError> {
+        while self.disk_manager.num_pages() <= page_id {
+            self.disk_manager.allocate_page()?;
+        }
+        Ok(())
+    }
+
+    /// Allocates a fresh, empty heap page at the end of the database file and makes it resident.
+    pub fn new_page(&mut self) -> Result<u32, BufferPoolError> {
+        self.new_page_of_type(PageType::Heap)
+    }
+
+    /// Like `new_page`, for a page of type `page_type`.
+    pub fn new_page_of_type(&mut self, page_type: PageType) -> Result<u32, BufferPoolError> {
+        // room for the page comes first, so a pool that can't make any doesn't
+        // leave a page on disk that nothing will ever link to
+        self.make_room()?;
+        let page_id = self.disk_manager.allocate_page()?;
+        self.replacer.record_admission(page_id);
+        self.store(page_id, Page::with_size(page_id, page_type, self.page_size()));
+        // the header only exists in memory so far
+        self.mark_dirty(page_id);
+        Ok(page_id)
+    }
+
+    /// Returns the page with the given id, reading it from disk if it isn't resident.
+    pub fn get_page(&mut self, page_id: u32) -> Result<&Page, BufferPoolError> {
+        self.make_resident(page_id)?;
+        Ok(self.pages.get(&page_id).unwrap())
+    }
+
+    /// Like `get_page`, but for modifying the page in place. The page is marked dirty.
+    pub fn get_page_mut(&mut self, page_id: u32) -> Result<&mut Page, BufferPoolError> {
+        self.make_resident(page_id)?;
+        self.mark_dirty(page_id);
+        Ok(self.pages.get_mut(&page_id).unwrap())
+    }
+
+    /// Pins a page, reading it in if necessary. A pinned page is never evicted
+    /// until every pin on it has been released with `unpin_page`.
+    pub fn pin_page(&mut self, page_id: u32) -> Result<(), BufferPoolError> {
+        self.make_resident(page_id)?;
+        *self.pin_counts.entry(page_id).or_insert(0) += 1;
+        Ok(())
+    }
+
+    pub fn unpin_page(&mut self, page_id: u32) -> Result<(), BufferPoolError> {
+        let pin_count = self.pin_counts.get_mut(&page_id).ok_or(BufferPoolError::PageNotPinned)?;
+        *pin_count -= 1;
+        if *pin_count == 0 {
+            self.pin_counts.remove(&page_id);
+        }
+        Ok(())
+    }
+
+    pub fn pin_count(&self, page_id: u32) -> u32 {
+        self.pin_counts.get(&page_id).copied().unwrap_or(0)
+    }
+
+    fn mark_dirty(&mut self, page_id: u32) {
+        if !self.dirty_pages.contains_key(&page_id) {
+            // whatever is about to change the page gets logged at the next LSN or later
+            let rec_lsn = self.log_manager.as_ref().map_or(INVALID_LSN, |log_manager| log_manager.lock().unwrap().last_lsn() + 1);
+            self.dirty_pages.insert(page_id, rec_lsn);
+        }
+    }
+
+    fn make_resident(&mut self, page_id: u32) -> Result<(), BufferPoolError> {
+        if self.pages.contains_key(&page_id) {
+            self.hits.inc();
+            self.replacer.record_access(page_id);
+        } else {
+            self.misses.inc();
+            self.read_page_from_disk(page_id)?;
+        }
+        Ok(())
+    }
+
+    /// Makes `page` resident, evicting pages first if the pool is full.
+    fn admit_page(&mut self, page_id: u32, page: Page) -> Result<(), BufferPoolError> {
+        if self.pages.contains_key(&page_id) {
+            self.replacer.record_access(page_id);
+        } else {
+            self.make_room()?;
+            self.replacer.record_admission(page_id);
+        }
+        self.store(page_id, page);
+        Ok(())
+    }
+
+    /// evicts pages until there's a frame free for one more, failing if all
+    /// that could go are pinned
+    fn make_room(&mut self) -> Result<(), BufferPoolError> {
+        self.draw_frame();
+        while self.exceeds_limits(self.pages.len() + 1) {
+            self.evict_one()?;
+        }
+        Ok(())
+    }
+
+    /// puts `page` in its frame, which there must be room for
+    fn store(&mut self, page_id: u32, page: Page) {
+        self.pages.insert(page_id, page);
+        self.resident_bytes.set((self.pages.len() * Self::frame_size(self.page_size())) as i64);
+    }
+
+    /// moves a frame's worth of bytes out of the reservation being drawn on, if
+    /// it has that much left, so the frame about to be admitted is charged to it
+    fn draw_frame(&mut self) {
+        let frame_size = Self::frame_size(self.page_size());
+        let Some(id) = self.drawing_on else { return };
+        if let Some(remaining) = self.reservations.lock().unwrap().get_mut(&id)
+            && *remaining >= frame_size
+        {
+            *remaining -= frame_size;
+        }
+    }
+
+    /// whether `resident_frames` would break the capacity or, together with the
+    /// bytes reservations haven't drawn on yet, the memory budget
+    fn exceeds_limits(&self, resident_frames: usize) -> bool {
+        let over_capacity = self.capacity.is_some_and(|capacity| resident_frames > capacity);
+        let over_budget = self.memory_budget.is_some_and(|budget| resident_frames * Self::frame_size(self.page_size()) + self.reserved() > budget);
+        over_capacity || over_budget
+    }
+
+    fn evict_one(&mut self) -> Result<(), BufferPoolError> {
+        let victim = self.replacer
+            .victim(&|page_id| !self.pin_counts.contains_key(&page_id))
+            .ok_or(BufferPoolError::OutOfMemory)?;
+
+        if self.dirty_pages.contains_key(&victim) {
+            self.write_page_to_disk(victim)?;
+        }
+        self.pages.remove(&victim);
+        self.replacer.remove(victim);
+        self.evictions.inc();
+        self.resident_bytes.set((self.pages.len() * Self::frame_size(self.page_size())) as i64);
+        Ok(())
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::metrics::MetricValue;
+    use crate::storage::{ClockPolicy, DEFAULT_PAGE_SIZE, LruKPolicy};
     use tempfile::NamedTempFile;
 
+    /// creates a database file holding `num_pages` fresh pages, returned with its temp file so it lives long enough
+    fn disk_with_pages(num_pages: u32) -> (DiskManager, NamedTempFile) {
+        let temp_file = NamedTempFile::new().expect("Failed to create temp file");
+        let mut disk_manager = DiskManager::open(temp_file.path()).expect("Failed to open database file");
+        for page_id in 0..num_pages {
+            disk_manager.allocate_page().unwrap();
+            disk_manager.write_page(page_id, Page::new(page_id).get_raw_contents()).expect("Failed to write page");
+        }
+        (disk_manager, temp_file)
+    }
+
     #[test]
     fn test_read_page_from_disk() {
-        // Create a valid page in memory with page_id = 42
+        // Create a database file whose page 42 holds a valid empty page
         let page_id = 42u32;
-        let page = Page::new(page_id);
-        
-        // Get the raw page contents (should be 4096 bytes with valid header)
-        let page_contents = page.get_raw_contents();
-        
-        // Create a temporary file and write the page contents to it
-        let mut temp_file = NamedTempFile::new().expect("Failed to create temp file");
-        temp_file.write_all(page_contents).expect("Failed to write to temp file");
-        let temp_path = temp_file.path().to_string_lossy().to_string();
-        
-        // Create a buffer pool and add the page path
-        let mut buffer_pool = BufferPool::new();
-        buffer_pool.add_page_path(page_id, temp_path);
-        
+        let (disk_manager, _file) = disk_with_pages(page_id + 1);
+        let mut buffer_pool = BufferPool::new(disk_manager);
+
         // Read the page from disk
         let read_page = buffer_pool.read_page_from_disk(page_id).expect("Failed to read page from disk");
-        
+
         // Verify the page was read correctly
         let header = read_page.get_header();
         assert_eq!(header.page_id, page_id);
-        assert_eq!(header.free_space_total, 4080); // PAGE_SIZE - HEADER_SIZE = 4096 - 16
-        assert_eq!(header.offset_begin_free_space, 16); // HEADER_SIZE
-        assert_eq!(header.offset_end_free_space, 4096); // PAGE_SIZE
+        assert_eq!(header.free_space_total, 4072); // DEFAULT_PAGE_SIZE - HEADER_SIZE = 4096 - 24
+        assert_eq!(header.offset_begin_free_space, 24); // HEADER_SIZE
+        assert_eq!(header.offset_end_free_space, 4096); // DEFAULT_PAGE_SIZE
     }
 
     #[test]
     fn test_write_page_to_disk() {
-        // Create a valid page in memory with page_id = 42
-        let page_id = 42u32;
-        let page = Page::new(page_id);
-
-        let mut buffer_pool = BufferPool::new();
-        buffer_pool.add_page_path(page_id, "test_page.bin".to_string());
-        buffer_pool.pages.insert(page_id, page);
+        let (disk_manager, file) = disk_with_pages(0);
+        let mut buffer_pool = BufferPool::new(disk_manager);
+        let page_id = buffer_pool.new_page().unwrap();
+        buffer_pool.get_page_mut(page_id).unwrap().insert_tuple(b"Hello, world!").unwrap();
 
         buffer_pool.write_page_to_disk(page_id).expect("Failed to write page to disk");
 
         let read_page = buffer_pool.read_page_from_disk(page_id).expect("Failed to read page from disk");
-        let header = read_page.unwrap().get_header();
+        let header = read_page.get_header();
         assert_eq!(header.page_id, page_id);
-        assert_eq!(header.free_space_total, 4080); // PAGE_SIZE - HEADER_SIZE = 4096 - 16
-        assert_eq!(header.offset_begin_free_space, 16); // HEADER_SIZE
-        assert_eq!(header.offset_end_free_space, 4096); // PAGE_SIZE
+        assert_eq!(read_page.get_data(0).unwrap(), b"Hello, world!");
+
+        // the page landed at its offset in the single database file, right after the superblock
+        let contents = std::fs::read(file.path()).unwrap();
+        assert_eq!(contents.len(), 2 * DEFAULT_PAGE_SIZE);
+        assert_eq!(&contents[DEFAULT_PAGE_SIZE..], read_page.get_raw_contents());
+    }
+
+    #[test]
+    fn test_pages_follow_the_database_page_size() {
+        let file = NamedTempFile::new().unwrap();
+        std::fs::remove_file(file.path()).unwrap();
+        let disk_manager = DiskManager::open_with_page_size(file.path(), 32768).unwrap();
+        let mut buffer_pool = BufferPool::with_memory_budget(disk_manager, BufferPool::frame_size(32768));
+        assert_eq!(buffer_pool.page_size(), 32768);
+
+        let page_id = buffer_pool.new_page().unwrap();
+        let tuple = vec![7u8; 20000];
+        let slot_id = buffer_pool.get_page_mut(page_id).unwrap().insert_tuple(&tuple).unwrap();
+        buffer_pool.flush_all().unwrap();
+        assert_eq!(buffer_pool.memory_usage().resident, BufferPool::frame_size(32768));
+
+        let mut reopened = BufferPool::new(DiskManager::open(file.path()).unwrap());
+        let page = reopened.get_page(page_id).unwrap();
+        assert_eq!(page.page_size(), 32768);
+        assert_eq!(page.get_data(slot_id).unwrap(), &tuple[..]);
+    }
+
+    #[test]
+    fn test_allocated_but_unwritten_page_reads_as_empty() {
+        let (mut disk_manager, _file) = disk_with_pages(0);
+        let page_id = disk_manager.allocate_page().unwrap();
+        let mut buffer_pool = BufferPool::new(disk_manager);
+
+        let page = buffer_pool.get_page(page_id).expect("a freshly allocated page isn't corrupt");
+        assert_eq!(page.get_header().page_id, page_id);
+        assert_eq!(page.get_header().free_space_total, 4072);
+    }
+
+    #[test]
+    fn test_zeroed_page_is_corrupt() {
+        let (disk_manager, file) = disk_with_pages(0);
+        let mut buffer_pool = BufferPool::new(disk_manager);
+        let first = buffer_pool.new_page_of_type(PageType::BTreeLeaf).unwrap();
+        let second = buffer_pool.new_page().unwrap();
+        buffer_pool.get_page_mut(second).unwrap().insert_tuple(b"Hello, world!").unwrap();
+        buffer_pool.flush_all().unwrap();
+
+        // wipe the second page, the last one in the file, the way a lost write can
+        let mut contents = std::fs::read(file.path()).unwrap();
+        let start = contents.len() - DEFAULT_PAGE_SIZE;
+        contents[start..].fill(0);
+        std::fs::write(file.path(), &contents).unwrap();
+
+        let result = buffer_pool.read_page_from_disk(second);
+        assert!(matches!(result, Err(BufferPoolError::PageError(PageError::ChecksumMismatch))));
+        assert_eq!(buffer_pool.read_page_from_disk(first).unwrap().page_type(), PageType::BTreeLeaf);
+    }
+
+    #[test]
+    fn test_read_corrupted_page_reports_checksum_mismatch() {
+        let (disk_manager, file) = disk_with_pages(0);
+        let mut buffer_pool = BufferPool::new(disk_manager);
+        let first = buffer_pool.new_page().unwrap();
+        let second = buffer_pool.new_page().unwrap();
+        buffer_pool.get_page_mut(second).unwrap().insert_tuple(b"Hello, world!").unwrap();
+        buffer_pool.flush_all().expect("Failed to flush pages");
+
+        // a clean round trip verifies fine
+        let read_page = buffer_pool.read_page_from_disk(second).expect("Failed to read page from disk");
+        assert_eq!(read_page.get_data(0).unwrap(), b"Hello, world!");
+
+        // flip a bit in the tuple data, which lives at the end of the second page (the end of the file)
+        let mut contents = std::fs::read(file.path()).unwrap();
+        let last = contents.len() - 1;
+        contents[last] ^= 0x01;
+        std::fs::write(file.path(), &contents).unwrap();
+
+        let result = buffer_pool.read_page_from_disk(second);
+        assert!(matches!(result, Err(BufferPoolError::PageError(PageError::ChecksumMismatch))));
+        // the other page in the file is untouched
+        assert!(buffer_pool.read_page_from_disk(first).is_ok());
+    }
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn test_write_to_full_disk_reports_out_of_disk_space() {
+        // every write to /dev/full fails with ENOSPC, including the one that allocates the page
+        let disk_manager = DiskManager::open("/dev/full").expect("Failed to open /dev/full");
+        let mut buffer_pool = BufferPool::new(disk_manager);
+
+        assert!(matches!(buffer_pool.new_page(), Err(BufferPoolError::OutOfDiskSpace)));
+        assert_eq!(buffer_pool.num_pages(), 0);
+    }
+
+    #[test]
+    fn test_memory_budget_never_exceeded() {
+        let frame_size = BufferPool::frame_size(DEFAULT_PAGE_SIZE);
+        let budget = frame_size * 4 + frame_size / 2; // room for 4 frames, not 5
+        let (disk_manager, _file) = disk_with_pages(16);
+        let mut buffer_pool = BufferPool::with_memory_budget(disk_manager, budget);
+
+        // simple LCG so the access pattern is pseudo-random but reproducible
+        let mut state: u64 = 0x2545F4914F6CDD1D;
+        for _ in 0..500 {
+            state = state.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+            let page_id = ((state >> 33) % 16) as u32;
+
+            let page = buffer_pool.read_page_from_disk(page_id).expect("Failed to read page");
+            assert_eq!(page.get_header().page_id, page_id);
+
+            let usage = buffer_pool.memory_usage();
+            assert!(usage.resident <= budget, "resident {} exceeds budget {}", usage.resident, budget);
+        }
 
-        // remove test_page.bin
-        std::fs::remove_file("test_page.bin").expect("Failed to remove test_page.bin");
+        assert_eq!(buffer_pool.memory_usage().resident, frame_size * 4);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_budget_too_small_for_single_frame() {
+        let (disk_manager, _file) = disk_with_pages(8);
+        let mut buffer_pool = BufferPool::with_memory_budget(disk_manager, BufferPool::frame_size(DEFAULT_PAGE_SIZE) - 1);
+
+        let result = buffer_pool.read_page_from_disk(7);
+        assert!(matches!(result, Err(BufferPoolError::OutOfMemory)));
+        assert_eq!(buffer_pool.memory_usage().resident, 0);
+    }
+
+    #[test]
+    fn test_new_page_without_room_leaves_file_alone() {
+        let (disk_manager, _file) = disk_with_pages(0);
+        let mut buffer_pool = BufferPool::with_capacity(disk_manager, 1);
+        let page_id = buffer_pool.new_page().unwrap();
+        buffer_pool.pin_page(page_id).unwrap();
+
+        // every frame is pinned, and each try mustn't grow the file by a page nothing links to
+        for _ in 0..3 {
+            assert!(matches!(buffer_pool.new_page(), Err(BufferPoolError::OutOfMemory)));
+            assert_eq!(buffer_pool.num_pages(), 1);
+        }
+        buffer_pool.unpin_page(page_id).unwrap();
+        assert_eq!(buffer_pool.new_page().unwrap(), 1);
+    }
+
+    #[test]
+    fn test_reservation_fails_when_budget_is_tiny() {
+        let frame_size = BufferPool::frame_size(DEFAULT_PAGE_SIZE);
+        let (disk_manager, _file) = disk_with_pages(0);
+        let mut buffer_pool = BufferPool::with_memory_budget(disk_manager, frame_size * 2);
+
+        // a B-tree split needs three frames pinned at once, which can never fit
+        let result = buffer_pool.reserve(frame_size * 3);
+        assert!(matches!(result, Err(BufferPoolError::OutOfMemory)));
+
+        let first = buffer_pool.reserve(frame_size).expect("first frame should fit");
+        let second = buffer_pool.reserve(frame_size).expect("second frame should fit");
+        assert_eq!(buffer_pool.memory_usage().reserved, frame_size * 2);
+        assert!(matches!(buffer_pool.reserve(1), Err(BufferPoolError::OutOfMemory)));
+
+        // dropping a reservation hands its bytes back to the budget
+        drop(first);
+        assert_eq!(buffer_pool.memory_usage().reserved, frame_size);
+        assert!(buffer_pool.reserve(frame_size).is_ok());
+        drop(second);
+        assert_eq!(buffer_pool.memory_usage().reserved, 0);
+    }
+
+    #[test]
+    fn test_reserved_frames_are_kept_for_the_holder() {
+        let frame_size = BufferPool::frame_size(DEFAULT_PAGE_SIZE);
+        let (disk_manager, _file) = disk_with_pages(8);
+        let mut buffer_pool = BufferPool::with_memory_budget(disk_manager, frame_size * 3);
+        for page_id in 0..3 {
+            buffer_pool.get_page(page_id).unwrap();
+        }
+
+        // reserving evicts to make room, and the room can't be taken by anyone else
+        let reservation = buffer_pool.reserve(frame_size * 2).unwrap();
+        assert_eq!(buffer_pool.memory_usage().resident, frame_size);
+        buffer_pool.pin_page(2).unwrap();
+        assert!(matches!(buffer_pool.get_page(3), Err(BufferPoolError::OutOfMemory)));
+
+        // the holder gets both frames without evicting its way in
+        buffer_pool.with_reservation(&reservation, |pool| {
+            pool.get_page(4).unwrap();
+            pool.get_page(5).unwrap();
+        });
+        assert_eq!(reservation.remaining(), 0);
+        assert_eq!(buffer_pool.memory_usage().resident, frame_size * 3);
+        assert_eq!(buffer_pool.stats().evictions, 2);
+
+        // once it's dropped, unpinned frames can be evicted for others again
+        drop(reservation);
+        buffer_pool.get_page(3).unwrap();
+    }
+
+    #[test]
+    fn test_metrics_track_reads_writes_and_evictions() {
+        let registry = MetricsRegistry::new();
+        let (disk_manager, _file) = disk_with_pages(3);
+        let mut buffer_pool = BufferPool::with_memory_budget(disk_manager, BufferPool::frame_size(DEFAULT_PAGE_SIZE) * 2);
+        buffer_pool.register_metrics(&registry);
+
+        // three admissions into a two-frame pool force exactly one eviction
+        for page_id in 0..3 {
+            buffer_pool.read_page_from_disk(page_id).expect("Failed to read page");
+        }
+        buffer_pool.write_page_to_disk(2).expect("Failed to write page");
+
+        let snapshot = registry.snapshot();
+        assert_eq!(snapshot["gondor_buffer_pool_pages_read_total"], MetricValue::Counter(3));
+        // the evicted page was never modified, so only the explicit write hits the disk
+        assert_eq!(snapshot["gondor_buffer_pool_pages_written_total"], MetricValue::Counter(1));
+        assert_eq!(snapshot["gondor_buffer_pool_evictions_total"], MetricValue::Counter(1));
+        assert_eq!(
+            snapshot["gondor_buffer_pool_resident_bytes"],
+            MetricValue::Gauge((BufferPool::frame_size(DEFAULT_PAGE_SIZE) * 2) as i64)
+        );
+    }
+
+    #[test]
+    fn test_stats_track_hits_misses_and_write_backs() {
+        let (disk_manager, _file) = disk_with_pages(3);
+        let mut buffer_pool = BufferPool::with_capacity(disk_manager, 2);
+
+        buffer_pool.get_page_mut(0).unwrap();
+        buffer_pool.get_page(0).unwrap();
+        buffer_pool.pin_page(1).unwrap();
+        assert_eq!(buffer_pool.stats(), BufferPoolStats {
+            hits: 1,
+            misses: 2,
+            evictions: 0,
+            dirty_flushes: 0,
+            pinned_pages: 1,
+            dirty_pages: 1,
+        });
+
+        // page 0 is the only one that can go, and has to be written back first
+        buffer_pool.get_page(2).unwrap();
+        buffer_pool.unpin_page(1).unwrap();
+        let stats = buffer_pool.stats();
+        assert_eq!((stats.misses, stats.evictions, stats.dirty_flushes), (3, 1, 1));
+        assert_eq!((stats.pinned_pages, stats.dirty_pages), (0, 0));
+        assert_eq!(stats.hit_ratio(), 0.25);
+
+        // clean pages aren't written back
+        buffer_pool.flush_all().unwrap();
+        assert_eq!(buffer_pool.stats().dirty_flushes, 1);
+        assert_eq!(BufferPoolStats::default().hit_ratio(), 0.0);
+    }
+
+    #[test]
+    fn test_new_page_and_get_page_mut() {
+        let (disk_manager, _file) = disk_with_pages(0);
+        let mut buffer_pool = BufferPool::new(disk_manager);
+        let first = buffer_pool.new_page().expect("Failed to allocate page");
+        let second = buffer_pool.new_page().expect("Failed to allocate page");
+        assert_ne!(first, second);
+        assert_eq!(buffer_pool.num_pages(), 2);
+
+        let slot_id = buffer_pool.get_page_mut(second).unwrap().insert_tuple(b"Hello, world!").unwrap();
+        let page = buffer_pool.get_page(second).unwrap();
+        assert_eq!(page.get_header().page_id, second);
+        assert_eq!(page.get_data(slot_id).unwrap(), b"Hello, world!");
+
+        assert!(matches!(buffer_pool.get_page(99), Err(BufferPoolError::PageNotFound)));
+    }
+
+    #[test]
+    fn test_eviction_writes_back_modified_pages() {
+        let (disk_manager, _file) = disk_with_pages(2);
+        let mut buffer_pool = BufferPool::with_memory_budget(disk_manager, BufferPool::frame_size(DEFAULT_PAGE_SIZE));
+
+        let slot_id = buffer_pool.get_page_mut(0).unwrap().insert_tuple(b"survives eviction").unwrap();
+        // only one frame fits, so this evicts page 0
+        buffer_pool.get_page(1).unwrap();
+        assert_eq!(buffer_pool.memory_usage().resident, BufferPool::frame_size(DEFAULT_PAGE_SIZE));
+
+        let page = buffer_pool.get_page(0).unwrap();
+        assert_eq!(page.get_data(slot_id).unwrap(), b"survives eviction");
+    }
+
+    #[test]
+    fn test_new_pages_are_evicted_to_the_database_file() {
+        let (disk_manager, _file) = disk_with_pages(0);
+        let mut buffer_pool = BufferPool::with_memory_budget(disk_manager, BufferPool::frame_size(DEFAULT_PAGE_SIZE));
+        let first = buffer_pool.new_page().expect("first page fits");
+        buffer_pool.get_page_mut(first).unwrap().insert_tuple(b"first page").unwrap();
+
+        // the freshly allocated page already has a home in the file, so it can make room
+        let second = buffer_pool.new_page().expect("first page should be evicted");
+        assert!(!buffer_pool.pages.contains_key(&first));
+        assert_eq!(buffer_pool.get_page(first).unwrap().get_data(0).unwrap(), b"first page");
+        assert!(!buffer_pool.pages.contains_key(&second));
+    }
+
+    #[test]
+    fn test_lru_evicts_least_recently_used_page() {
+        let (disk_manager, _file) = disk_with_pages(3);
+        let mut buffer_pool = BufferPool::with_capacity(disk_manager, 2);
+
+        buffer_pool.get_page(0).unwrap();
+        buffer_pool.get_page(1).unwrap();
+        // touching page 0 again makes page 1 the least recently used
+        buffer_pool.get_page(0).unwrap();
+        buffer_pool.get_page(2).unwrap();
+
+        assert!(buffer_pool.pages.contains_key(&0));
+        assert!(!buffer_pool.pages.contains_key(&1));
+        assert!(buffer_pool.pages.contains_key(&2));
+        assert_eq!(buffer_pool.pages.len(), 2);
+    }
+
+    #[test]
+    fn test_lru_k_keeps_hot_pages_through_a_scan() {
+        let (disk_manager, _file) = disk_with_pages(8);
+        let mut buffer_pool = BufferPool::with_capacity(disk_manager, 3).with_replacement_policy(LruKPolicy::new(2));
+
+        buffer_pool.get_page(0).unwrap();
+        buffer_pool.get_page(0).unwrap();
+        // a scan over the rest of the file touches each page once
+        for page_id in 1..8 {
+            buffer_pool.get_page(page_id).unwrap();
+        }
+        assert!(buffer_pool.pages.contains_key(&0));
+        assert_eq!(buffer_pool.pages.len(), 3);
+    }
+
+    #[test]
+    fn test_clock_skips_pinned_pages() {
+        let (disk_manager, _file) = disk_with_pages(3);
+        let mut buffer_pool = BufferPool::with_capacity(disk_manager, 2).with_replacement_policy(ClockPolicy::default());
+
+        buffer_pool.pin_page(0).unwrap();
+        buffer_pool.get_page(1).unwrap();
+        buffer_pool.get_page(2).unwrap();
+        assert!(buffer_pool.pages.contains_key(&0));
+        assert!(!buffer_pool.pages.contains_key(&1));
+    }
+
+    #[test]
+    fn test_pinned_pages_are_not_evicted() {
+        let (disk_manager, _file) = disk_with_pages(3);
+        let mut buffer_pool = BufferPool::with_capacity(disk_manager, 2);
+
+        buffer_pool.pin_page(0).unwrap();
+        buffer_pool.get_page(1).unwrap();
+        buffer_pool.get_page(2).unwrap();
+
+        // page 0 is the least recently used, but pinned, so page 1 went instead
+        assert!(buffer_pool.pages.contains_key(&0));
+        assert!(!buffer_pool.pages.contains_key(&1));
+        assert_eq!(buffer_pool.pin_count(0), 1);
+
+        buffer_pool.pin_page(2).unwrap();
+        assert!(matches!(buffer_pool.get_page(1), Err(BufferPoolError::OutOfMemory)));
+
+        // once unpinned, page 0 is evictable again
+        buffer_pool.unpin_page(0).unwrap();
+        assert_eq!(buffer_pool.pin_count(0), 0);
+        buffer_pool.get_page(1).unwrap();
+        assert!(!buffer_pool.pages.contains_key(&0));
+    }
+
+    #[test]
+    fn test_unpin_without_pin_fails() {
+        let (disk_manager, _file) = disk_with_pages(0);
+        let mut buffer_pool = BufferPool::new(disk_manager);
+        let page_id = buffer_pool.new_page().unwrap();
+        assert!(matches!(buffer_pool.unpin_page(page_id), Err(BufferPoolError::PageNotPinned)));
+
+        buffer_pool.pin_page(page_id).unwrap();
+        buffer_pool.pin_page(page_id).unwrap();
+        buffer_pool.unpin_page(page_id).unwrap();
+        buffer_pool.unpin_page(page_id).unwrap();
+        assert!(matches!(buffer_pool.unpin_page(page_id), Err(BufferPoolError::PageNotPinned)));
+    }
+
+    #[test]
+    fn test_capacity_bounds_resident_pages_for_heap_sized_workload() {
+        let (disk_manager, _file) = disk_with_pages(32);
+        let mut buffer_pool = BufferPool::with_capacity(disk_manager, 4);
+
+        // write a marker into every page, then read them all back through a pool a fraction of the size
+        for page_id in 0..32u32 {
+            buffer_pool.get_page_mut(page_id).unwrap().insert_tuple(&page_id.to_le_bytes()).unwrap();
+            assert!(buffer_pool.pages.len() <= 4);
+        }
+        for page_id in 0..32u32 {
+            let page = buffer_pool.get_page(page_id).unwrap();
+            assert_eq!(page.get_data(0).unwrap(), page_id.to_le_bytes());
+        }
+    }
+
+    #[test]
+    fn test_dirty_tracking() {
+        let (disk_manager, _file) = disk_with_pages(1);
+        let mut buffer_pool = BufferPool::new(disk_manager);
+
+        buffer_pool.get_page(0).unwrap();
+        assert!(!buffer_pool.is_dirty(0));
+
+        buffer_pool.get_page_mut(0).unwrap().insert_tuple(b"Hello, world!").unwrap();
+        assert!(buffer_pool.is_dirty(0));
+        assert_eq!(buffer_pool.dirty_page_count(), 1);
+
+        buffer_pool.write_page_to_disk(0).unwrap();
+        assert!(!buffer_pool.is_dirty(0));
+        assert_eq!(buffer_pool.dirty_page_count(), 0);
+    }
+
+    #[test]
+    fn test_flush_all_persists_modifications() {
+        let registry = MetricsRegistry::new();
+        let (disk_manager, file) = disk_with_pages(4);
+        let mut buffer_pool = BufferPool::new(disk_manager);
+        buffer_pool.register_metrics(&registry);
+
+        // modify two of the four pages
+        buffer_pool.get_page(0).unwrap();
+        buffer_pool.get_page_mut(1).unwrap().insert_tuple(b"page one").unwrap();
+        buffer_pool.get_page(2).unwrap();
+        buffer_pool.get_page_mut(3).unwrap().insert_tuple(b"page three").unwrap();
+
+        buffer_pool.flush_all().unwrap();
+        assert_eq!(buffer_pool.dirty_page_count(), 0);
+        // clean pages aren't rewritten
+        assert_eq!(registry.snapshot()["gondor_buffer_pool_pages_written_total"], MetricValue::Counter(2));
+
+        // a fresh pool over the same file sees the modifications
+        let mut reopened = BufferPool::new(DiskManager::open(file.path()).unwrap());
+        assert_eq!(reopened.num_pages(), 4);
+        assert_eq!(reopened.get_page(1).unwrap().get_data(0).unwrap(), b"page one");
+        assert_eq!(reopened.get_page(3).unwrap().get_data(0).unwrap(), b"page three");
+    }
+
+    #[test]
+    fn test_clean_pages_evicted_without_write_back() {
+        let registry = MetricsRegistry::new();
+        let (disk_manager, _file) = disk_with_pages(2);
+        let mut buffer_pool = BufferPool::with_capacity(disk_manager, 1);
+        buffer_pool.register_metrics(&registry);
+        buffer_pool.get_page(0).unwrap();
+
+        buffer_pool.get_page(1).expect("evicting a clean page shouldn't touch the disk");
+        let snapshot = registry.snapshot();
+        assert_eq!(snapshot["gondor_buffer_pool_evictions_total"], MetricValue::Counter(1));
+        assert_eq!(snapshot["gondor_buffer_pool_pages_written_total"], MetricValue::Counter(0));
+    }
+
+    #[test]
+    fn test_log_forced_before_page_write() {
+        use crate::wal::{INVALID_LSN, LogRecordBody};
+
+        let (disk_manager, _file) = disk_with_pages(1);
+        let log_file = NamedTempFile::new().unwrap();
+        let log_manager = Arc::new(Mutex::new(LogManager::open(log_file.path()).unwrap()));
+        let mut buffer_pool = BufferPool::new(disk_manager);
+        buffer_pool.set_log_manager(Arc::clone(&log_manager));
+
+        let tuple = b"Hello, world!".to_vec();
+        let page = buffer_pool.get_page_mut(0).unwrap();
+        let slot_id = page.insert_tuple(&tuple).unwrap();
+        let lsn = log_manager.lock().unwrap().append(1, INVALID_LSN, LogRecordBody::Insert { rid: (0, slot_id).into(), tuple });
+        page.set_lsn(lsn);
+        assert_eq!(log_manager.lock().unwrap().flushed_lsn(), INVALID_LSN);
+
+        buffer_pool.flush_all().unwrap();
+        assert!(log_manager.lock().unwrap().flushed_lsn() >= lsn);
+    }
+
+    #[test]
+    fn test_unbounded_pool_grants_reservations() {
+        let (disk_manager, _file) = disk_with_pages(0);
+        let mut buffer_pool = BufferPool::new(disk_manager);
+        let reservation = buffer_pool.reserve(usize::MAX / 2).expect("unbounded pool should grant reservations");
+        assert_eq!(reservation.bytes(), usize::MAX / 2);
+        assert_eq!(buffer_pool.memory_usage().budget, None);
+    }
+}
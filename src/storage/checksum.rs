@@ -0,0 +1,82 @@
+/// reflected form of the IEEE 802.3 CRC-32 polynomial (the one used by zlib, PNG, ...)
+const CRC32_POLYNOMIAL: u32 = 0xEDB8_8320;
+
+/// lookup table with the CRC of every possible byte value, built at compile time
+const CRC32_TABLE: [u32; 256] = build_crc32_table();
+
+const fn build_crc32_table() -> [u32; 256] {
+    let mut table = [0u32; 256];
+    let mut byte = 0;
+    while byte < 256 {
+        let mut crc = byte as u32;
+        let mut bit = 0;
+        while bit < 8 {
+            if crc & 1 == 1 {
+                crc = (crc >> 1) ^ CRC32_POLYNOMIAL;
+            } else {
+                crc >>= 1;
+            }
+            bit += 1;
+        }
+        table[byte] = crc;
+        byte += 1;
+    }
+    table
+}
+
+/// Incremental CRC-32 so a checksum can be computed over non-contiguous byte ranges.
+#[derive(Debug, Clone, Copy)]
+pub struct Crc32 {
+    state: u32,
+}
+
+impl Default for Crc32 {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Crc32 {
+    pub fn new() -> Self {
+        Self { state: 0xFFFF_FFFF }
+    }
+
+    pub fn update(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            let index = ((self.state ^ byte as u32) & 0xFF) as usize;
+            self.state = (self.state >> 8) ^ CRC32_TABLE[index];
+        }
+    }
+
+    pub fn finish(&self) -> u32 {
+        self.state ^ 0xFFFF_FFFF
+    }
+}
+
+/// Computes the CRC-32 (IEEE) checksum of `bytes`.
+pub fn crc32(bytes: &[u8]) -> u32 {
+    let mut crc = Crc32::new();
+    crc.update(bytes);
+    crc.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_crc32_known_values() {
+        // standard check value for CRC-32/ISO-HDLC
+        assert_eq!(crc32(b"123456789"), 0xCBF4_3926);
+        assert_eq!(crc32(b""), 0);
+        assert_eq!(crc32(b"The quick brown fox jumps over the lazy dog"), 0x414F_A339);
+    }
+
+    #[test]
+    fn test_crc32_incremental_matches_one_shot() {
+        let mut crc = Crc32::new();
+        crc.update(b"The quick brown fox ");
+        crc.update(b"jumps over the lazy dog");
+        assert_eq!(crc.finish(), crc32(b"The quick brown fox jumps over the lazy dog"));
+    }
+}
@@ -0,0 +1,621 @@
+use super::checksum::crc32;
+use super::double_write::DoubleWriteBuffer;
+use super::page::{DEFAULT_PAGE_SIZE, Page, PageType, SUPPORTED_PAGE_SIZES};
+use super::SyncMode;
+use std::ffi::OsString;
+use std::fs::{File, OpenOptions};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+/// identifies a file as a gondor database, stored at the very start of the superblock
+const MAGIC: &[u8; 8] = b"GONDORDB";
+
+/// version of the superblock layout written by this code
+const SUPERBLOCK_VERSION: u32 = 1;
+
+/// magic (8 bytes), version (4 bytes), page size (4 bytes) and a CRC-32 of those (4 bytes)
+const SUPERBLOCK_LENGTH: usize = 20;
+
+/// pages the double-write buffer holds before the database file is synced so it can start over
+const DOUBLE_WRITE_CAPACITY: usize = 128;
+
+#[derive(Debug)]
+pub enum DiskManagerError {
+    /// the page id is past the end of the database file
+    PageOutOfRange(u32),
+    /// the buffer handed in isn't exactly one page long
+    InvalidBufferSize(usize),
+    /// the file doesn't start with a superblock this version can read
+    InvalidSuperblock,
+    /// the page size isn't one of `SUPPORTED_PAGE_SIZES`
+    UnsupportedPageSize(usize),
+    /// the database was created with a different page size than the one asked for
+    PageSizeMismatch { expected: usize, found: usize },
+    IoError(std::io::Error),
+}
+
+impl std::fmt::Display for DiskManagerError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DiskManagerError::PageOutOfRange(page_id) => write!(f, "Page {page_id} is out of range"),
+            DiskManagerError::InvalidBufferSize(size) => write!(f, "Buffer of {size} bytes is not one page"),
+            DiskManagerError::InvalidSuperblock => write!(f, "File is not a database or its superblock is corrupt"),
+            DiskManagerError::UnsupportedPageSize(page_size) => write!(f, "Unsupported page size {page_size}"),
+            DiskManagerError::PageSizeMismatch { expected, found } => write!(f, "Expected a page size of {expected}, but the database uses {found}"),
+            DiskManagerError::IoError(error) => write!(f, "I/O error: {error}"),
+        }
+    }
+}
+
+impl std::error::Error for DiskManagerError {}
+
+impl From<std::io::Error> for DiskManagerError {
+    fn from(error: std::io::Error) -> Self {
+        DiskManagerError::IoError(error)
+    }
+}
+
+/// Stores every page of a database in a single file.
+///
+/// The file starts with a superblock recording the page size the database was
+/// created with, padded to a whole page so the pages after it stay aligned.
+/// Page `n` lives at byte offset `(n + 1) * page_size`, so the rest of the file
+/// is simply the pages laid end to end. New pages are allocated at the end of
+/// the file.
+///
+/// # Examples
+///
+/// ```
+/// use gondor_rdbms::storage::{DiskManager, Page, DEFAULT_PAGE_SIZE};
+///
+/// let dir = tempfile::tempdir().unwrap();
+/// let mut disk_manager = DiskManager::open(dir.path().join("example.db")).unwrap();
+/// assert_eq!(disk_manager.page_size(), DEFAULT_PAGE_SIZE);
+///
+/// let page_id = disk_manager.allocate_page().unwrap();
+/// disk_manager.write_page(page_id, Page::new(page_id).get_raw_contents()).unwrap();
+///
+/// let mut contents = [0u8; DEFAULT_PAGE_SIZE];
+/// disk_manager.read_page(page_id, &mut contents).unwrap();
+/// ```
+#[derive(Debug)]
+pub struct DiskManager {
+    path: PathBuf,
+    file: File,
+    /// how `sync` forces the file to stable storage
+    sync_mode: SyncMode,
+    /// copies of the pages written since the last sync, `None` unless enabled
+    double_write: Option<DoubleWriteBuffer>,
+    /// size of every page in the file, superblock included
+    page_size: usize,
+    /// number of pages allocated in the file
+    num_pages: u32,
+    /// makes the next allocation fail with a full disk after writing this many bytes
+    #[cfg(test)]
+    fail_allocation_after: Option<usize>,
+}
+
+impl DiskManager {
+    /// Opens the database file at `path`, creating an empty one with pages of
+    /// `DEFAULT_PAGE_SIZE` if it doesn't exist. An existing file keeps the page
+    /// size recorded in its superblock.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, DiskManagerError> {
+        Self::open_inner(path.as_ref(), None)
+    }
+
+    /// Like `open`, but creates the file with pages of `page_size` bytes, which
+    /// must be one of `SUPPORTED_PAGE_SIZES`. Fails with `PageSizeMismatch` if
+    /// the file already exists with another page size.
+    pub fn open_with_page_size(path: impl AsRef<Path>, page_size: usize) -> Result<Self, DiskManagerError> {
+        if !SUPPORTED_PAGE_SIZES.contains(&page_size) {
+            return Err(DiskManagerError::UnsupportedPageSize(page_size));
+        }
+        Self::open_inner(path.as_ref(), Some(page_size))
+    }
+
+    fn open_inner(path: &Path, requested_page_size: Option<usize>) -> Result<Self, DiskManagerError> {
+        let mut file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(path)?;
+
+        let length = file.metadata()?.len();
+        if length == 0 {
+            // a new database, the superblock is written along with the first page
+            return Ok(Self {
+                path: path.to_path_buf(),
+                file,
+                sync_mode: SyncMode::default(),
+                double_write: None,
+                page_size: requested_page_size.unwrap_or(DEFAULT_PAGE_SIZE),
+                num_pages: 0,
+                #[cfg(test)]
+                fail_allocation_after: None,
+            });
+        }
+
+        let page_size = Self::read_superblock(&mut file, length)?;
+        if let Some(expected) = requested_page_size.filter(|&expected| expected != page_size) {
+            return Err(DiskManagerError::PageSizeMismatch { expected, found: page_size });
+        }
+        // a crash part way through extending the file leaves part of a page on
+        // the end, which was never handed out, or which redo allocates again.
+        // the superblock's page is only ever short of its zero padding
+        let whole_pages = (length - length % page_size as u64).max(page_size as u64);
+        if whole_pages != length {
+            file.set_len(whole_pages)?;
+        }
+
+        Ok(Self {
+            path: path.to_path_buf(),
+            file,
+            sync_mode: SyncMode::default(),
+            double_write: None,
+            page_size,
+            // the superblock takes up the first page's worth of the file
+            num_pages: (whole_pages / page_size as u64 - 1) as u32,
+            #[cfg(test)]
+            fail_allocation_after: None,
+        })
+    }
+
+    pub fn num_pages(&self) -> u32 {
+        self.num_pages
+    }
+
+    pub fn sync_mode(&self) -> SyncMode {
+        self.sync_mode
+    }
+
+    /// Changes how the file is forced to stable storage, `SyncMode::Fdatasync`
+    /// unless set otherwise. Everything written so far is synced under the old
+    /// mode first, and the file is reopened if the new one needs other flags.
+    pub fn set_sync_mode(&mut self, sync_mode: SyncMode) -> Result<(), DiskManagerError> {
+        self.sync()?;
+        self.file = sync_mode.apply(OpenOptions::new().read(true).write(true)).open(&self.path)?;
+        self.sync_mode = sync_mode;
+        Ok(())
+    }
+
+    /// Size in bytes of every page in the file.
+    pub fn page_size(&self) -> usize {
+        self.page_size
+    }
+
+    /// Protects page writes against being torn by a crash, and repairs the pages
+    /// whose writes were torn by the last one. Returns the ids of the repaired pages.
+    ///
+    /// A page is written in several pieces, so a crash part way through can leave
+    /// it half old and half new, which neither its checksum nor the log can fix.
+    /// With the double-write buffer, every page is first copied to a file next to
+    /// the database (`-dwb` appended to its name) and only then written in place;
+    /// the copies stay until the database file has been synced. Opening the buffer
+    /// writes back every copy whose page doesn't match it.
+    ///
+    /// Pages aren't copied under `SyncMode::Off`, which can't promise anything
+    /// after a crash anyway.
+    pub fn enable_double_write(&mut self) -> Result<Vec<u32>, DiskManagerError> {
+        let mut path = OsString::from(&self.path);
+        path.push("-dwb");
+        let mut double_write = DoubleWriteBuffer::open(Path::new(&path), self.page_size)?;
+
+        let mut copies: Vec<(u32, Vec<u8>)> = double_write.read_entries(self.page_size)?.into_iter().collect();
+        copies.sort_by_key(|&(page_id, _)| page_id);
+        let mut repaired = Vec::new();
+        let mut contents = vec![0u8; self.page_size];
+        for (page_id, copy) in copies {
+            // the file may have been extended after the copy was made, but not durably
+            while self.num_pages <= page_id {
+                self.allocate_page()?;
+            }
+            self.read_page(page_id, &mut contents)?;
+            if contents != copy {
+                self.file.seek(SeekFrom::Start(self.page_offset(page_id)))?;
+                self.file.write_all(&copy)?;
+                repaired.push(page_id);
+            }
+        }
+        // the copies can only go once the repairs are durable
+        self.file.sync_data()?;
+        double_write.clear()?;
+
+        self.double_write = Some(double_write);
+        Ok(repaired)
+    }
+
+    /// Reserves a new page at the end of the file and returns its id.
+    ///
+    /// The file is extended with an empty heap page, checksum and all; until
+    /// the page is first written, that's what reading it back returns, so a
+    /// page of zeroes is never a page that simply hasn't been written yet. The
+    /// page is actually written (rather than leaving a hole) so a full disk is
+    /// reported here instead of on some later write-back. It's synced (as far
+    /// as the sync mode asks) before the id is handed out, so a crash can't
+    /// leave the file long enough to hold the page but with zeroes in it. If
+    /// writing it fails, the file is cut back to its old length, which `open`
+    /// still accepts.
+    pub fn allocate_page(&mut self) -> Result<u32, DiskManagerError> {
+        let length = self.file.metadata()?.len();
+        let page_id = self.num_pages;
+        if let Err(error) = self.extend(page_id).and_then(|()| Ok(self.sync_mode.sync(&self.file)?)) {
+            // the error worth reporting is the one from the write, not this
+            let _ = self.file.set_len(length);
+            return Err(error);
+        }
+        self.num_pages += 1;
+        Ok(page_id)
+    }
+
+    /// writes page `page_id` as an empty page, and the superblock first if it's the first page
+    fn extend(&mut self, page_id: u32) -> Result<(), DiskManagerError> {
+        if page_id == 0 {
+            self.write_superblock()?;
+        }
+        let page = Page::with_size(page_id, PageType::Heap, self.page_size);
+        let contents = page.get_raw_contents();
+        self.file.seek(SeekFrom::Start(self.page_offset(page_id)))?;
+        #[cfg(test)]
+        if let Some(written) = self.fail_allocation_after.take() {
+            self.file.write_all(&contents[..written])?;
+            return Err(std::io::Error::from(std::io::ErrorKind::StorageFull).into());
+        }
+        self.file.write_all(contents)?;
+        Ok(())
+    }
+
+    /// Reads page `page_id` into `contents`, which must be exactly one page long.
+    pub fn read_page(&mut self, page_id: u32, contents: &mut [u8]) -> Result<(), DiskManagerError> {
+        self.check_access(page_id, contents.len())?;
+        self.file.seek(SeekFrom::Start(self.page_offset(page_id)))?;
+        self.file.read_exact(contents)?;
+        Ok(())
+    }
+
+    /// Writes `contents`, which must be exactly one page long, to page `page_id`.
+    pub fn write_page(&mut self, page_id: u32, contents: &[u8]) -> Result<(), DiskManagerError> {
+        self.write_pages(&[(page_id, contents)])
+    }
+
+    /// Writes several pages, each `(page_id, contents)` like `write_page`.
+    ///
+    /// With the double-write buffer enabled, the pages are copied there in
+    /// batches, so the copies cost one sync per batch rather than one per page.
+    pub fn write_pages(&mut self, pages: &[(u32, &[u8])]) -> Result<(), DiskManagerError> {
+        for &(page_id, contents) in pages {
+            self.check_access(page_id, contents.len())?;
+        }
+
+        for batch in pages.chunks(DOUBLE_WRITE_CAPACITY) {
+            if let Some(double_write) = &mut self.double_write
+                && self.sync_mode != SyncMode::Off
+            {
+                if double_write.len() + batch.len() > DOUBLE_WRITE_CAPACITY {
+                    // make the writes the buffer protects durable so it can start over
+                    self.file.sync_data()?;
+                    double_write.clear()?;
+                }
+                double_write.append(batch)?;
+            }
+
+            for &(page_id, contents) in batch {
+                self.file.seek(SeekFrom::Start(self.page_offset(page_id)))?;
+                self.file.write_all(contents)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Forces everything written so far to stable storage, as far as the sync mode asks for.
+    pub fn sync(&mut self) -> Result<(), DiskManagerError> {
+        self.sync_mode.sync(&self.file)?;
+        if let Some(double_write) = &mut self.double_write
+            && self.sync_mode != SyncMode::Off
+        {
+            // the pages can't be torn any more
+            double_write.clear()?;
+        }
+        Ok(())
+    }
+
+    /// writes the superblock, padded with zeroes to a whole page
+    fn write_superblock(&mut self) -> Result<(), DiskManagerError> {
+        let mut superblock = vec![0u8; self.page_size];
+        superblock[0..8].copy_from_slice(MAGIC);
+        superblock[8..12].copy_from_slice(&SUPERBLOCK_VERSION.to_le_bytes());
+        superblock[12..16].copy_from_slice(&(self.page_size as u32).to_le_bytes());
+        let checksum = crc32(&superblock[..16]);
+        superblock[16..SUPERBLOCK_LENGTH].copy_from_slice(&checksum.to_le_bytes());
+
+        self.file.seek(SeekFrom::Start(0))?;
+        self.file.write_all(&superblock)?;
+        Ok(())
+    }
+
+    /// checks the superblock of an existing file and returns the page size it records
+    fn read_superblock(file: &mut File, length: u64) -> Result<usize, DiskManagerError> {
+        if length < SUPERBLOCK_LENGTH as u64 {
+            return Err(DiskManagerError::InvalidSuperblock);
+        }
+        let mut superblock = [0u8; SUPERBLOCK_LENGTH];
+        file.seek(SeekFrom::Start(0))?;
+        file.read_exact(&mut superblock)?;
+
+        let checksum = u32::from_le_bytes(superblock[16..20].try_into().unwrap());
+        if &superblock[0..8] != MAGIC || checksum != crc32(&superblock[..16]) {
+            return Err(DiskManagerError::InvalidSuperblock);
+        }
+        if u32::from_le_bytes(superblock[8..12].try_into().unwrap()) != SUPERBLOCK_VERSION {
+            return Err(DiskManagerError::InvalidSuperblock);
+        }
+
+        let page_size = u32::from_le_bytes(superblock[12..16].try_into().unwrap()) as usize;
+        if !SUPPORTED_PAGE_SIZES.contains(&page_size) {
+            return Err(DiskManagerError::UnsupportedPageSize(page_size));
+        }
+        Ok(page_size)
+    }
+
+    fn check_access(&self, page_id: u32, buffer_size: usize) -> Result<(), DiskManagerError> {
+        if buffer_size != self.page_size {
+            return Err(DiskManagerError::InvalidBufferSize(buffer_size));
+        }
+        if page_id >= self.num_pages {
+            return Err(DiskManagerError::PageOutOfRange(page_id));
+        }
+        Ok(())
+    }
+
+    fn page_offset(&self, page_id: u32) -> u64 {
+        (page_id as u64 + 1) * self.page_size as u64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::{Page, PageType};
+
+    #[test]
+    fn test_allocate_write_and_read() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut disk_manager = DiskManager::open(dir.path().join("test.db")).unwrap();
+        assert_eq!(disk_manager.num_pages(), 0);
+
+        let first = disk_manager.allocate_page().unwrap();
+        let second = disk_manager.allocate_page().unwrap();
+        assert_eq!((first, second), (0, 1));
+
+        let mut page = Page::new(second);
+        page.insert_tuple(b"Hello, world!").unwrap();
+        disk_manager.write_page(second, page.get_raw_contents()).unwrap();
+
+        let mut contents = [0u8; DEFAULT_PAGE_SIZE];
+        disk_manager.read_page(second, &mut contents).unwrap();
+        assert_eq!(&contents[..], page.get_raw_contents());
+
+        // the first page was allocated but never written, so it's still empty
+        disk_manager.read_page(first, &mut contents).unwrap();
+        let page = Page::from_bytes(&contents).unwrap();
+        page.verify_checksum().unwrap();
+        assert_eq!((page.page_type(), page.slot_count()), (PageType::Heap, 0));
+    }
+
+    #[test]
+    fn test_reopen_keeps_pages() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("test.db");
+        {
+            let mut disk_manager = DiskManager::open(&path).unwrap();
+            for page_id in 0..3 {
+                disk_manager.allocate_page().unwrap();
+                disk_manager.write_page(page_id, Page::new(page_id).get_raw_contents()).unwrap();
+            }
+            disk_manager.sync().unwrap();
+        }
+
+        let mut disk_manager = DiskManager::open(&path).unwrap();
+        assert_eq!(disk_manager.num_pages(), 3);
+        // the three pages plus the superblock in front of them
+        assert_eq!(std::fs::metadata(&path).unwrap().len(), 4 * DEFAULT_PAGE_SIZE as u64);
+
+        let mut contents = [0u8; DEFAULT_PAGE_SIZE];
+        disk_manager.read_page(2, &mut contents).unwrap();
+        assert_eq!(&contents[..], Page::new(2).get_raw_contents());
+    }
+
+    #[test]
+    fn test_out_of_range_and_bad_buffers() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut disk_manager = DiskManager::open(dir.path().join("test.db")).unwrap();
+        disk_manager.allocate_page().unwrap();
+
+        let mut contents = [0u8; DEFAULT_PAGE_SIZE];
+        assert!(matches!(disk_manager.read_page(1, &mut contents), Err(DiskManagerError::PageOutOfRange(1))));
+        assert!(matches!(disk_manager.write_page(5, &contents), Err(DiskManagerError::PageOutOfRange(5))));
+        assert!(matches!(disk_manager.write_page(0, &contents[..100]), Err(DiskManagerError::InvalidBufferSize(100))));
+    }
+
+    #[test]
+    fn test_every_sync_mode_keeps_pages() {
+        for sync_mode in [SyncMode::Fsync, SyncMode::Fdatasync, SyncMode::ODsync, SyncMode::Off] {
+            let dir = tempfile::tempdir().unwrap();
+            let path = dir.path().join("test.db");
+            let mut page = Page::new(0);
+            page.insert_tuple(b"Hello, world!").unwrap();
+            {
+                let mut disk_manager = DiskManager::open(&path).unwrap();
+                disk_manager.set_sync_mode(sync_mode).unwrap();
+                assert_eq!(disk_manager.sync_mode(), sync_mode);
+                disk_manager.allocate_page().unwrap();
+                disk_manager.write_page(0, page.get_raw_contents()).unwrap();
+                disk_manager.sync().unwrap();
+            }
+
+            let mut disk_manager = DiskManager::open(&path).unwrap();
+            let mut contents = [0u8; DEFAULT_PAGE_SIZE];
+            disk_manager.read_page(0, &mut contents).unwrap();
+            assert_eq!(&contents[..], page.get_raw_contents(), "{sync_mode:?}");
+        }
+    }
+
+    /// overwrites the second half of page `page_id` in the file, as if a crash interrupted its write
+    fn tear_page(path: &Path, page_id: u32) {
+        let mut file = OpenOptions::new().write(true).open(path).unwrap();
+        let offset = (page_id as u64 + 1) * DEFAULT_PAGE_SIZE as u64 + DEFAULT_PAGE_SIZE as u64 / 2;
+        file.seek(SeekFrom::Start(offset)).unwrap();
+        file.write_all(&[0xEE; DEFAULT_PAGE_SIZE / 2]).unwrap();
+    }
+
+    #[test]
+    fn test_double_write_repairs_torn_pages() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("test.db");
+        let mut page = Page::new(1);
+        {
+            let mut disk_manager = DiskManager::open(&path).unwrap();
+            assert_eq!(disk_manager.enable_double_write().unwrap(), Vec::<u32>::new());
+            for page_id in 0..2 {
+                disk_manager.allocate_page().unwrap();
+                disk_manager.write_page(page_id, Page::new(page_id).get_raw_contents()).unwrap();
+            }
+            disk_manager.sync().unwrap();
+
+            // the crash hits while the new version of page 1 is being written
+            page.insert_tuple(b"Hello, world!").unwrap();
+            page.update_checksum();
+            disk_manager.write_page(1, page.get_raw_contents()).unwrap();
+        }
+        tear_page(&path, 1);
+
+        let mut disk_manager = DiskManager::open(&path).unwrap();
+        assert_eq!(disk_manager.enable_double_write().unwrap(), vec![1]);
+        let mut contents = [0u8; DEFAULT_PAGE_SIZE];
+        disk_manager.read_page(1, &mut contents).unwrap();
+        assert_eq!(&contents[..], page.get_raw_contents());
+        disk_manager.read_page(0, &mut contents).unwrap();
+        assert_eq!(&contents[..], Page::new(0).get_raw_contents());
+
+        // repaired for good, nothing left to do the next time
+        drop(disk_manager);
+        let mut disk_manager = DiskManager::open(&path).unwrap();
+        assert_eq!(disk_manager.enable_double_write().unwrap(), Vec::<u32>::new());
+    }
+
+    #[test]
+    fn test_sync_drops_double_write_copies() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("test.db");
+        let dwb_length = || std::fs::metadata(dir.path().join("test.db-dwb")).unwrap().len();
+        {
+            let mut disk_manager = DiskManager::open(&path).unwrap();
+            disk_manager.enable_double_write().unwrap();
+            disk_manager.allocate_page().unwrap();
+            disk_manager.write_page(0, Page::new(0).get_raw_contents()).unwrap();
+            assert_eq!(dwb_length(), 8 + DEFAULT_PAGE_SIZE as u64);
+            disk_manager.sync().unwrap();
+            assert_eq!(dwb_length(), 0);
+        }
+
+        // a page torn after the sync is beyond the buffer's reach, and the checksum's job
+        tear_page(&path, 0);
+        let mut disk_manager = DiskManager::open(&path).unwrap();
+        assert_eq!(disk_manager.enable_double_write().unwrap(), Vec::<u32>::new());
+    }
+
+    #[test]
+    fn test_partial_page_cut_off_on_open() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("test.db");
+        let page_size = 16 * 1024;
+        {
+            let mut disk_manager = DiskManager::open_with_page_size(&path, page_size).unwrap();
+            disk_manager.allocate_page().unwrap();
+            disk_manager.write_page(0, Page::with_size(0, PageType::Heap, page_size).get_raw_contents()).unwrap();
+        }
+        // a crash a few blocks into extending the file by another page
+        let mut file = OpenOptions::new().append(true).open(&path).unwrap();
+        file.write_all(&[0u8; 4096]).unwrap();
+
+        let mut disk_manager = DiskManager::open(&path).unwrap();
+        assert_eq!(disk_manager.num_pages(), 1);
+        assert_eq!(std::fs::metadata(&path).unwrap().len(), 2 * page_size as u64);
+        let mut contents = vec![0u8; page_size];
+        disk_manager.read_page(0, &mut contents).unwrap();
+        assert_eq!(disk_manager.allocate_page().unwrap(), 1);
+    }
+
+    #[test]
+    fn test_failed_allocation_leaves_file_usable() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("test.db");
+        let mut disk_manager = DiskManager::open(&path).unwrap();
+        disk_manager.allocate_page().unwrap();
+        disk_manager.write_page(0, Page::new(0).get_raw_contents()).unwrap();
+
+        // a short write, the way a disk filling up partway through one goes
+        disk_manager.fail_allocation_after = Some(100);
+        assert!(matches!(disk_manager.allocate_page(), Err(DiskManagerError::IoError(error)) if error.kind() == std::io::ErrorKind::StorageFull));
+        assert_eq!(disk_manager.num_pages(), 1);
+        assert_eq!(std::fs::metadata(&path).unwrap().len(), 2 * DEFAULT_PAGE_SIZE as u64);
+
+        // the next allocation gets the page the failed one didn't
+        assert_eq!(disk_manager.allocate_page().unwrap(), 1);
+        drop(disk_manager);
+        let mut disk_manager = DiskManager::open(&path).unwrap();
+        assert_eq!(disk_manager.num_pages(), 2);
+        let mut contents = [0u8; DEFAULT_PAGE_SIZE];
+        disk_manager.read_page(0, &mut contents).unwrap();
+        assert_eq!(contents, Page::new(0).get_raw_contents());
+
+        // failing on the very first page takes the superblock back off too
+        let path = dir.path().join("new.db");
+        let mut disk_manager = DiskManager::open(&path).unwrap();
+        disk_manager.fail_allocation_after = Some(0);
+        assert!(disk_manager.allocate_page().is_err());
+        drop(disk_manager);
+        assert_eq!(DiskManager::open(&path).unwrap().num_pages(), 0);
+    }
+
+    #[test]
+    fn test_rejects_files_without_superblock() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("test.db");
+        std::fs::write(&path, [0u8; 100]).unwrap();
+        assert!(matches!(DiskManager::open(&path), Err(DiskManagerError::InvalidSuperblock)));
+
+        std::fs::write(&path, b"GONDOR").unwrap();
+        assert!(matches!(DiskManager::open(&path), Err(DiskManagerError::InvalidSuperblock)));
+    }
+
+    #[test]
+    fn test_page_size_is_recorded_in_superblock() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("test.db");
+        {
+            let mut disk_manager = DiskManager::open_with_page_size(&path, 16384).unwrap();
+            disk_manager.allocate_page().unwrap();
+            let page = Page::with_size(0, PageType::Heap, 16384);
+            disk_manager.write_page(0, page.get_raw_contents()).unwrap();
+            assert!(matches!(disk_manager.write_page(0, &[0u8; DEFAULT_PAGE_SIZE]), Err(DiskManagerError::InvalidBufferSize(DEFAULT_PAGE_SIZE))));
+        }
+        assert_eq!(std::fs::metadata(&path).unwrap().len(), 2 * 16384);
+
+        // reopening without a page size picks up the recorded one
+        let mut disk_manager = DiskManager::open(&path).unwrap();
+        assert_eq!((disk_manager.page_size(), disk_manager.num_pages()), (16384, 1));
+        let mut contents = vec![0u8; 16384];
+        disk_manager.read_page(0, &mut contents).unwrap();
+        assert_eq!(Page::from_bytes(&contents).unwrap().page_size(), 16384);
+
+        assert!(matches!(
+            DiskManager::open_with_page_size(&path, 8192),
+            Err(DiskManagerError::PageSizeMismatch { expected: 8192, found: 16384 })
+        ));
+        assert!(matches!(
+            DiskManager::open_with_page_size(dir.path().join("other.db"), 1000),
+            Err(DiskManagerError::UnsupportedPageSize(1000))
+        ));
+    }
+}
@@ -0,0 +1,92 @@
+use super::checksum::Crc32;
+use std::collections::HashMap;
+use std::fs::{File, OpenOptions};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::Path;
+
+/// Copies of the pages the disk manager is about to write to the database file.
+///
+/// Each batch of pages is appended here and synced before any of them is
+/// written to its place in the database file. If a crash tears one of those
+/// writes, leaving the page half old and half new, the intact copy is still
+/// here to put back. The copies are dropped once the database file has been
+/// synced, since from then on the pages can't be torn any more.
+///
+/// An entry is the page id (4 bytes), a CRC-32 of the id and image (4 bytes)
+/// and the page image. A crash while appending leaves a torn entry at the end,
+/// which fails its checksum and is ignored; none of its pages were written yet.
+#[derive(Debug)]
+pub(super) struct DoubleWriteBuffer {
+    file: File,
+    /// number of entries in the file
+    len: usize,
+}
+
+impl DoubleWriteBuffer {
+    pub(super) fn open(path: &Path, page_size: usize) -> std::io::Result<Self> {
+        let file = OpenOptions::new().read(true).write(true).create(true).truncate(false).open(path)?;
+        // a torn entry at the end counts too, so clearing gets rid of it
+        let len = (file.metadata()?.len() as usize).div_ceil(8 + page_size);
+        Ok(Self { file, len })
+    }
+
+    pub(super) fn len(&self) -> usize {
+        self.len
+    }
+
+    /// The most recent intact copy of every page in the buffer, by page id.
+    pub(super) fn read_entries(&mut self, page_size: usize) -> std::io::Result<HashMap<u32, Vec<u8>>> {
+        let mut contents = Vec::new();
+        self.file.seek(SeekFrom::Start(0))?;
+        self.file.read_to_end(&mut contents)?;
+
+        let mut pages = HashMap::new();
+        for entry in contents.chunks_exact(8 + page_size) {
+            let page_id = u32::from_le_bytes(entry[0..4].try_into().unwrap());
+            let checksum = u32::from_le_bytes(entry[4..8].try_into().unwrap());
+            if checksum != entry_checksum(&entry[0..4], &entry[8..]) {
+                // torn while being appended, and so is anything after it
+                break;
+            }
+            // later copies of a page are newer
+            pages.insert(page_id, entry[8..].to_vec());
+        }
+        Ok(pages)
+    }
+
+    /// Appends copies of `pages` and syncs them, so they're safe before the
+    /// pages themselves are written.
+    pub(super) fn append(&mut self, pages: &[(u32, &[u8])]) -> std::io::Result<()> {
+        let mut entries = Vec::new();
+        for &(page_id, contents) in pages {
+            let page_id = page_id.to_le_bytes();
+            entries.extend_from_slice(&page_id);
+            entries.extend_from_slice(&entry_checksum(&page_id, contents).to_le_bytes());
+            entries.extend_from_slice(contents);
+        }
+
+        self.file.seek(SeekFrom::End(0))?;
+        self.file.write_all(&entries)?;
+        self.file.sync_data()?;
+        self.len += pages.len();
+        Ok(())
+    }
+
+    /// Drops every copy, once the pages they protect are durable in the database file.
+    pub(super) fn clear(&mut self) -> std::io::Result<()> {
+        if self.len == 0 {
+            return Ok(());
+        }
+        self.file.set_len(0)?;
+        self.file.sync_data()?;
+        self.len = 0;
+        Ok(())
+    }
+}
+
+fn entry_checksum(page_id: &[u8], contents: &[u8]) -> u32 {
+    let mut crc = Crc32::new();
+    crc.update(page_id);
+    crc.update(contents);
+    crc.finish()
+}
@@ -0,0 +1,58 @@
+use std::collections::{BTreeMap, HashMap};
+
+/// The pages of heap files with room to spare, by the root page of their heap file.
+///
+/// A heap file only ever inserts into the page it allocated last, so the space
+/// deletes free up in its other pages goes unused until vacuum finds it and
+/// records it here. Inserts then fill those pages before allocating new ones.
+/// The amounts are hints: an insert that finds less room than recorded moves
+/// on to the next page.
+///
+/// The map only lives in memory. After the database is reopened, freed space is
+/// reused once the next vacuum has found it again.
+///
+/// # Examples
+///
+/// ```
+/// use gondor_rdbms::storage::FreeSpaceMap;
+///
+/// let mut free_space_map = FreeSpaceMap::new();
+/// free_space_map.record(1, 7, 100);
+/// free_space_map.record(1, 9, 3000);
+/// assert_eq!(free_space_map.find(1, 500), Some(9));
+/// assert_eq!(free_space_map.find(2, 50), None);
+/// ```
+#[derive(Debug, Default)]
+pub struct FreeSpaceMap {
+    /// free bytes of each page with any, by page id, by heap file
+    heaps: HashMap<u32, BTreeMap<u32, u16>>,
+}
+
+impl FreeSpaceMap {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Notes that page `page_id` of the heap file rooted at `heap` has `free`
+    /// bytes of room, forgetting it if that's none.
+    pub fn record(&mut self, heap: u32, page_id: u32, free: u16) {
+        let pages = self.heaps.entry(heap).or_default();
+        if free == 0 {
+            pages.remove(&page_id);
+        } else {
+            pages.insert(page_id, free);
+        }
+    }
+
+    /// The first page of the heap file rooted at `heap` with at least `size`
+    /// bytes of room, as far as the map knows.
+    pub fn find(&self, heap: u32, size: usize) -> Option<u32> {
+        let pages = self.heaps.get(&heap)?;
+        pages.iter().find(|&(_, &free)| free as usize >= size).map(|(&page_id, _)| page_id)
+    }
+
+    /// Free bytes recorded for the pages of the heap file rooted at `heap`, in total.
+    pub fn free_bytes(&self, heap: u32) -> usize {
+        self.heaps.get(&heap).map_or(0, |pages| pages.values().map(|&free| free as usize).sum())
+    }
+}
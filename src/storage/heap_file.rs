@@ -0,0 +1,573 @@
+use super::{BufferPool, BufferPoolError, FreeSpaceMap, Page, PageError, PageType, RecordId};
+use crate::transaction::Transaction;
+use crate::wal::{LogManager, LogRecordBody};
+use std::sync::{Arc, Mutex};
+
+/// data pages listed on a directory page, the slot after them links to the next directory page
+const DIRECTORY_CAPACITY: u16 = 500;
+
+#[derive(Debug)]
+pub enum HeapError {
+    /// the record id doesn't belong to this heap file
+    PageNotInHeap,
+    /// the tuple doesn't fit even in an empty page
+    TupleTooLarge,
+    /// the directory page holds something other than page ids
+    CorruptDirectory(u32),
+    PageError(PageError),
+    BufferPoolError(BufferPoolError),
+}
+
+impl std::fmt::Display for HeapError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            HeapError::PageNotInHeap => write!(f, "Page does not belong to this heap file"),
+            HeapError::TupleTooLarge => write!(f, "Tuple is too large to fit in a page"),
+            HeapError::CorruptDirectory(page_id) => write!(f, "Page {page_id} is not a valid heap directory page"),
+            HeapError::PageError(error) => write!(f, "Page error: {error}"),
+            HeapError::BufferPoolError(error) => write!(f, "Buffer pool error: {error}"),
+        }
+    }
+}
+
+impl std::error::Error for HeapError {}
+
+impl From<PageError> for HeapError {
+    fn from(error: PageError) -> Self {
+        HeapError::PageError(error)
+    }
+}
+
+impl From<BufferPoolError> for HeapError {
+    fn from(error: BufferPoolError) -> Self {
+        HeapError::BufferPoolError(error)
+    }
+}
+
+/// An unordered collection of tuples spread over as many pages as it needs.
+///
+/// The heap file picks a page for each new tuple, allocating a fresh page from
+/// the buffer pool once the existing ones are full. Tuples are addressed by
+/// `RecordId`.
+///
+/// The pages that belong to the heap file are listed in a chain of directory
+/// pages starting at its root page, so the root page id is all it takes to
+/// open the heap file again later. Adding a page to the directory is logged as
+/// a redo-only change: rolling back the transaction that triggered it removes
+/// its tuples but leaves the page in the heap file for later inserts.
+///
+/// Changes are made on behalf of a `Transaction`. If the heap file has a log
+/// manager, every change is logged for that transaction and stamped with its
+/// LSN, so it can be rolled back or replayed after a crash.
+///
+/// New tuples go to the page allocated last, or else to the pages a
+/// `FreeSpaceMap` has room recorded on, if the heap file has one, before a new
+/// page is allocated.
+///
+/// # Examples
+///
+/// ```
+/// use gondor_rdbms::storage::{BufferPool, DiskManager, HeapFile};
+/// use gondor_rdbms::transaction::Transaction;
+/// use std::sync::{Arc, Mutex};
+///
+/// let dir = tempfile::tempdir().unwrap();
+/// let disk_manager = DiskManager::open(dir.path().join("example.db")).unwrap();
+/// let buffer_pool = Arc::new(Mutex::new(BufferPool::new(disk_manager)));
+/// let mut heap = HeapFile::create(buffer_pool.clone()).unwrap();
+///
+/// let mut txn = Transaction::new(1);
+/// let rid = heap.insert(&mut txn, b"Hello, world!").unwrap();
+///
+/// let reopened = HeapFile::open(buffer_pool, heap.root_page_id()).unwrap();
+/// assert_eq!(reopened.get(rid).unwrap(), b"Hello, world!");
+/// ```
+pub struct HeapFile {
+    buffer_pool: Arc<Mutex<BufferPool>>,
+    /// changes are logged here, if set
+    log_manager: Option<Arc<Mutex<LogManager>>>,
+    /// pages with room for new tuples besides the last one, if set
+    free_space_map: Option<Arc<Mutex<FreeSpaceMap>>>,
+    /// directory pages in chain order, the first one is the root page
+    directory_page_ids: Vec<u32>,
+    /// data pages owned by this heap file, in allocation order
+    page_ids: Vec<u32>,
+    /// type of every page the heap file allocates, directory pages included
+    page_type: PageType,
+}
+
+impl HeapFile {
+    /// Creates an empty heap file with a fresh root page. Data pages are
+    /// allocated lazily on the first insert.
+    pub fn create(buffer_pool: Arc<Mutex<BufferPool>>) -> Result<Self, HeapError> {
+        Self::create_of_type(buffer_pool, PageType::Heap)
+    }
+
+    /// Like `create`, but tags the heap file's pages with `page_type` instead of
+    /// `PageType::Heap`, e.g. for the catalog.
+    pub fn create_of_type(buffer_pool: Arc<Mutex<BufferPool>>, page_type: PageType) -> Result<Self, HeapError> {
+        let root_page_id = {
+            let mut buffer_pool = buffer_pool.lock().unwrap();
+            let root_page_id = buffer_pool.new_page_of_type(page_type)?;
+            // creating the root isn't logged, write it out so a crash can't lose its type
+            buffer_pool.write_page_to_disk(root_page_id)?;
+            root_page_id
+        };
+        Ok(Self {
+            buffer_pool,
+            log_manager: None,
+            free_space_map: None,
+            directory_page_ids: vec![root_page_id],
+            page_ids: Vec::new(),
+            page_type,
+        })
+    }
+
+    /// Opens the heap file rooted at `root_page_id`, reading its pages from the directory.
+    pub fn open(buffer_pool: Arc<Mutex<BufferPool>>, root_page_id: u32) -> Result<Self, HeapError> {
+        let mut directory_page_ids = vec![root_page_id];
+        let mut page_ids = Vec::new();
+        let page_type;
+        {
+            let mut buffer_pool = buffer_pool.lock().unwrap();
+            page_type = buffer_pool.get_page(root_page_id)?.page_type();
+            if !matches!(page_type, PageType::Heap | PageType::Catalog) {
+                // some other subsystem's page, reading it as a directory would make no sense
+                return Err(HeapError::CorruptDirectory(root_page_id));
+            }
+            loop {
+                let directory_page_id = *directory_page_ids.last().unwrap();
+                let page = buffer_pool.get_page(directory_page_id)?;
+                let entries = page.slot_count();
+                for slot_id in 0..entries.min(DIRECTORY_CAPACITY) {
+                    page_ids.push(read_directory_entry(page, directory_page_id, slot_id)?);
+                }
+                if entries <= DIRECTORY_CAPACITY {
+                    break;
+                }
+                directory_page_ids.push(read_directory_entry(page, directory_page_id, DIRECTORY_CAPACITY)?);
+            }
+        }
+
+        Ok(Self {
+            buffer_pool,
+            log_manager: None,
+            free_space_map: None,
+            directory_page_ids,
+            page_ids,
+            page_type,
+        })
+    }
+
+    /// Logs every subsequent change to `log_manager`.
+    pub fn set_log_manager(&mut self, log_manager: Arc<Mutex<LogManager>>) {
+        self.log_manager = Some(log_manager);
+    }
+
+    /// Fills the pages `free_space_map` has room recorded on before allocating new ones.
+    pub fn set_free_space_map(&mut self, free_space_map: Arc<Mutex<FreeSpaceMap>>) {
+        self.free_space_map = Some(free_space_map);
+    }
+
+    /// Page the heap file's directory starts at, pass it to `open` to reopen the heap file.
+    pub fn root_page_id(&self) -> u32 {
+        self.directory_page_ids[0]
+    }
+
+    /// Data pages owned by this heap file, not counting its directory pages.
+    pub fn page_ids(&self) -> &[u32] {
+        &self.page_ids
+    }
+
+    pub fn insert(&mut self, txn: &mut Transaction, tuple: &[u8]) -> Result<RecordId, HeapError> {
+        let mut buffer_pool = self.buffer_pool.lock().unwrap();
+
+        // new tuples go to the most recently allocated page -- earlier pages were full when we moved on
+        if let Some(&page_id) = self.page_ids.last() {
+            let page = buffer_pool.get_page_mut(page_id)?;
+            match page.insert_tuple(tuple) {
+                Ok(slot_id) => return Ok(self.log_insert(txn, page, RecordId::new(page_id, slot_id), tuple)),
+                Err(PageError::NotEnoughSpace) => {}
+                Err(error) => return Err(error.into()),
+            }
+        }
+
+        // then the ones vacuum found room on
+        if let Some(free_space_map) = &self.free_space_map {
+            let mut free_space_map = free_space_map.lock().unwrap();
+            while let Some(page_id) = free_space_map.find(self.root_page_id(), tuple.len()) {
+                let page = buffer_pool.get_page_mut(page_id)?;
+                match page.insert_tuple(tuple) {
+                    Ok(slot_id) => {
+                        free_space_map.record(self.root_page_id(), page_id, page.get_header().free_space_total);
+                        return Ok(self.log_insert(txn, page, RecordId::new(page_id, slot_id), tuple));
+                    }
+                    // less room than recorded, or none for the slot
+                    Err(PageError::NotEnoughSpace) => free_space_map.record(self.root_page_id(), page_id, 0),
+                    Err(error) => return Err(error.into()),
+                }
+            }
+        }
+
+        let page_id = buffer_pool.new_page_of_type(self.page_type)?;
+        self.log(txn, buffer_pool.get_page_mut(page_id)?, LogRecordBody::AllocatePage { page_id, page_type: self.page_type });
+        if let Some(directory_page_id) = self.add_to_directory(&mut buffer_pool, txn, page_id)? {
+            self.directory_page_ids.push(directory_page_id);
+        }
+        self.page_ids.push(page_id);
+
+        let page = buffer_pool.get_page_mut(page_id)?;
+        match page.insert_tuple(tuple) {
+            Ok(slot_id) => Ok(self.log_insert(txn, page, RecordId::new(page_id, slot_id), tuple)),
+            // not even an empty page can hold it
+            Err(PageError::NotEnoughSpace) => Err(HeapError::TupleTooLarge),
+            Err(error) => Err(error.into()),
+        }
+    }
+
+    pub fn get(&self, rid: RecordId) -> Result<Vec<u8>, HeapError> {
+        self.check_page(rid.page_id)?;
+        let mut buffer_pool = self.buffer_pool.lock().unwrap();
+        let page = buffer_pool.get_page(rid.page_id)?;
+        Ok(page.get_data(rid.slot_id)?.to_vec())
+    }
+    /// Replaces the tuple at `rid`, returning where it now lives.
+    ///
+    /// The tuple stays put whenever its page has room for the new version. If it
+    /// doesn't, the tuple is moved to another page and the returned `RecordId`
+    /// differs from `rid`, so callers holding on to record ids must use the result.
+    pub fn update(&mut self, txn: &mut Transaction, rid: RecordId, tuple: &[u8]) -> Result<RecordId, HeapError> {
+        self.check_page(rid.page_id)?;
+        {
+            let mut buffer_pool = self.buffer_pool.lock().unwrap();
+            let page = buffer_pool.get_page_mut(rid.page_id)?;
+            let before = page.get_data(rid.slot_id)?.to_vec();
+            match page.update_tuple(rid.slot_id, tuple) {
+                Ok(_) => {
+                    self.log(txn, page, LogRecordBody::Update { rid, before, after: tuple.to_vec() });
+                    return Ok(rid);
+                }
+                Err(PageError::NotEnoughSpace) => {}
+                Err(error) => return Err(error.into()),
+            }
+        }
+
+        // insert first so a tuple that doesn't fit anywhere leaves the old version intact
+        let new_rid = self.insert(txn, tuple)?;
+        self.delete(txn, rid)?;
+        Ok(new_rid)
+    }
+
+    pub fn delete(&mut self, txn: &mut Transaction, rid: RecordId) -> Result<(), HeapError> {
+        self.check_page(rid.page_id)?;
+        let mut buffer_pool = self.buffer_pool.lock().unwrap();
+        let page = buffer_pool.get_page_mut(rid.page_id)?;
+        let tuple = page.get_data(rid.slot_id)?.to_vec();
+        page.delete_tuple(rid.slot_id)?;
+        self.log(txn, page, LogRecordBody::Delete { rid, tuple });
+        Ok(())
+    }
+
+    /// Iterates over every live tuple, page by page in allocation order.
+    pub fn scan(&self) -> HeapScan {
+        HeapScan {
+            buffer_pool: self.buffer_pool.clone(),
+            page_ids: self.page_ids.clone().into_iter(),
+            tuples: Vec::new().into_iter(),
+        }
+    }
+
+    /// lists `page_id` in the directory, chaining on a new directory page when the last
+    /// one is full, and returns the new directory page if there is one
+    fn add_to_directory(&self, buffer_pool: &mut BufferPool, txn: &mut Transaction, page_id: u32) -> Result<Option<u32>, HeapError> {
+        let mut directory_page_id = *self.directory_page_ids.last().unwrap();
+        let position = self.page_ids.len() - (self.directory_page_ids.len() - 1) * DIRECTORY_CAPACITY as usize;
+
+        let mut new_directory_page_id = None;
+        if position == DIRECTORY_CAPACITY as usize {
+            let next_page_id = buffer_pool.new_page_of_type(self.page_type)?;
+            let allocation = LogRecordBody::AllocatePage { page_id: next_page_id, page_type: self.page_type };
+            self.log(txn, buffer_pool.get_page_mut(next_page_id)?, allocation);
+            let link = RecordId::new(directory_page_id, DIRECTORY_CAPACITY);
+            self.insert_redo_only(txn, buffer_pool.get_page_mut(directory_page_id)?, link, &next_page_id.to_le_bytes())?;
+            directory_page_id = next_page_id;
+            new_directory_page_id = Some(next_page_id);
+        }
+
+        let slot_id = (position % DIRECTORY_CAPACITY as usize) as u16;
+        let entry = RecordId::new(directory_page_id, slot_id);
+        self.insert_redo_only(txn, buffer_pool.get_page_mut(directory_page_id)?, entry, &page_id.to_le_bytes())?;
+        Ok(new_directory_page_id)
+    }
+
+    /// inserts `tuple` at `rid` and logs it as a compensation record, which redo replays
+    /// but rolling back `txn` skips over
+    fn insert_redo_only(&self, txn: &mut Transaction, page: &mut Page, rid: RecordId, tuple: &[u8]) -> Result<(), HeapError> {
+        page.insert_tuple_at(rid.slot_id, tuple)?;
+        let body = LogRecordBody::Compensation {
+            undo_next_lsn: txn.prev_lsn(),
+            action: Box::new(LogRecordBody::Insert { rid, tuple: tuple.to_vec() }),
+        };
+        self.log(txn, page, body);
+        Ok(())
+    }
+
+    fn log_insert(&self, txn: &mut Transaction, page: &mut Page, rid: RecordId, tuple: &[u8]) -> RecordId {
+        self.log(txn, page, LogRecordBody::Insert { rid, tuple: tuple.to_vec() });
+        rid
+    }
+
+    /// logs a change already made to `page` and stamps the page with its LSN
+    fn log(&self, txn: &mut Transaction, page: &mut Page, body: LogRecordBody) {
+        if let Some(log_manager) = &self.log_manager {
+            let lsn = txn.log(&mut log_manager.lock().unwrap(), body);
+            page.set_lsn(lsn);
+        }
+    }
+
+    fn check_page(&self, page_id: u32) -> Result<(), HeapError> {
+        if !self.page_ids.contains(&page_id) {
+            return Err(HeapError::PageNotInHeap);
+        }
+        Ok(())
+    }
+}
+
+
+/// page id stored in `slot_id` of directory page `page_id`
+fn read_directory_entry(page: &Page, page_id: u32, slot_id: u16) -> Result<u32, HeapError> {
+    let entry = page.get_data(slot_id).map_err(|_| HeapError::CorruptDirectory(page_id))?;
+    let bytes = entry.try_into().map_err(|_| HeapError::CorruptDirectory(page_id))?;
+    Ok(u32::from_le_bytes(bytes))
+}
+
+/// Iterator over the tuples of a `HeapFile`, returned by `HeapFile::scan`.
+///
+/// Pages are read one at a time as the scan reaches them, so a scan doesn't
+/// hold the buffer pool locked between items.
+pub struct HeapScan {
+    buffer_pool: Arc<Mutex<BufferPool>>,
+    /// pages not read yet
+    page_ids: std::vec::IntoIter<u32>,
+    /// remaining tuples of the current page
+    tuples: std::vec::IntoIter<(RecordId, Vec<u8>)>,
+}
+
+impl Iterator for HeapScan {
+    type Item = Result<(RecordId, Vec<u8>), HeapError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(tuple) = self.tuples.next() {
+                return Some(Ok(tuple));
+            }
+            let page_id = self.page_ids.next()?;
+
+            let mut buffer_pool = self.buffer_pool.lock().unwrap();
+            let page = match buffer_pool.get_page(page_id) {
+                Ok(page) => page,
+                Err(error) => return Some(Err(error.into())),
+            };
+            let tuples: Vec<(RecordId, Vec<u8>)> = page.iter().map(|(slot_id, data)| (RecordId::new(page_id, slot_id), data.to_vec())).collect();
+            self.tuples = tuples.into_iter();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::DiskManager;
+    use tempfile::NamedTempFile;
+
+    /// buffer pool over a fresh database file, returned with its temp file so it lives long enough
+    fn new_buffer_pool() -> (Arc<Mutex<BufferPool>>, NamedTempFile) {
+        let temp_file = NamedTempFile::new().expect("Failed to create temp file");
+        let disk_manager = DiskManager::open(temp_file.path()).expect("Failed to open database file");
+        (Arc::new(Mutex::new(BufferPool::new(disk_manager))), temp_file)
+    }
+
+    fn new_heap() -> (HeapFile, NamedTempFile) {
+        let (buffer_pool, temp_file) = new_buffer_pool();
+        (HeapFile::create(buffer_pool).unwrap(), temp_file)
+    }
+
+    #[test]
+    fn test_insert_and_get() {
+        let (mut heap, _file) = new_heap();
+        let mut txn = Transaction::new(1);
+        let first = heap.insert(&mut txn, b"first tuple").unwrap();
+        let second = heap.insert(&mut txn, b"second tuple").unwrap();
+
+        assert_eq!(heap.get(first).unwrap(), b"first tuple");
+        assert_eq!(heap.get(second).unwrap(), b"second tuple");
+        assert_eq!(heap.page_ids().len(), 1);
+    }
+
+    #[test]
+    fn test_insert_spans_multiple_pages() {
+        let (mut heap, _file) = new_heap();
+        let mut txn = Transaction::new(1);
+        let rids: Vec<RecordId> = (0..100u32)
+            .map(|i| heap.insert(&mut txn, &[i as u8; 200]).unwrap())
+            .collect();
+
+        // 200 byte tuples plus their slots only fit ~20 to a page
+        assert!(heap.page_ids().len() >= 5);
+        for (i, rid) in rids.iter().enumerate() {
+            assert_eq!(heap.get(*rid).unwrap(), vec![i as u8; 200]);
+        }
+    }
+
+    #[test]
+    fn test_update_in_place_and_relocated() {
+        let (mut heap, _file) = new_heap();
+        let mut txn = Transaction::new(1);
+        let rid = heap.insert(&mut txn, b"short").unwrap();
+
+        let same_rid = heap.update(&mut txn, rid, b"longer but still fits").unwrap();
+        assert_eq!(same_rid, rid);
+        assert_eq!(heap.get(rid).unwrap(), b"longer but still fits");
+
+        // fill up the rest of the page so the next growth can't stay put
+        while heap.page_ids().len() == 1 {
+            heap.insert(&mut txn, &[0u8; 100]).unwrap();
+        }
+        let big = vec![0xAB; 1000];
+        let moved_rid = heap.update(&mut txn, rid, &big).unwrap();
+
+        assert_ne!(moved_rid, rid);
+        assert_eq!(heap.get(moved_rid).unwrap(), big);
+        assert!(matches!(heap.get(rid), Err(HeapError::PageError(PageError::TupleNotFound))));
+    }
+
+    #[test]
+    fn test_delete() {
+        let (mut heap, _file) = new_heap();
+        let mut txn = Transaction::new(1);
+        let rid = heap.insert(&mut txn, b"Hello, world!").unwrap();
+        heap.delete(&mut txn, rid).unwrap();
+
+        assert!(matches!(heap.get(rid), Err(HeapError::PageError(PageError::TupleNotFound))));
+        assert!(matches!(heap.delete(&mut txn, rid), Err(HeapError::PageError(PageError::TupleNotFound))));
+    }
+
+    #[test]
+    fn test_tuple_too_large() {
+        let (mut heap, _file) = new_heap();
+        let mut txn = Transaction::new(1);
+        let result = heap.insert(&mut txn, &[0u8; 5000]);
+        assert!(matches!(result, Err(HeapError::TupleTooLarge)));
+    }
+
+    #[test]
+    fn test_record_from_other_heap_rejected() {
+        let (buffer_pool, _file) = new_buffer_pool();
+        let mut first_heap = HeapFile::create(buffer_pool.clone()).unwrap();
+        let second_heap = HeapFile::create(buffer_pool).unwrap();
+        let mut txn = Transaction::new(1);
+
+        let rid = first_heap.insert(&mut txn, b"Hello, world!").unwrap();
+        assert!(matches!(second_heap.get(rid), Err(HeapError::PageNotInHeap)));
+    }
+
+    #[test]
+    fn test_open_existing_pages() {
+        let (buffer_pool, _file) = new_buffer_pool();
+        let mut heap = HeapFile::create(buffer_pool.clone()).unwrap();
+        let mut txn = Transaction::new(1);
+        let rid = heap.insert(&mut txn, b"Hello, world!").unwrap();
+
+        let reopened = HeapFile::open(buffer_pool, heap.root_page_id()).unwrap();
+        assert_eq!(reopened.page_ids(), heap.page_ids());
+        assert_eq!(reopened.get(rid).unwrap(), b"Hello, world!");
+    }
+
+    #[test]
+    fn test_open_rejects_pages_of_other_types() {
+        let (buffer_pool, _file) = new_buffer_pool();
+        let page_id = buffer_pool.lock().unwrap().new_page_of_type(PageType::BTreeLeaf).unwrap();
+        assert!(matches!(HeapFile::open(buffer_pool.clone(), page_id), Err(HeapError::CorruptDirectory(id)) if id == page_id));
+
+        // the type of the root carries over to the pages a heap file allocates
+        let mut heap = HeapFile::create_of_type(buffer_pool.clone(), PageType::Catalog).unwrap();
+        let rid = heap.insert(&mut Transaction::new(1), b"Hello, world!").unwrap();
+        let heap = HeapFile::open(buffer_pool.clone(), heap.root_page_id()).unwrap();
+        assert_eq!(heap.page_ids(), &[rid.page_id]);
+        assert_eq!(buffer_pool.lock().unwrap().get_page(rid.page_id).unwrap().page_type(), PageType::Catalog);
+    }
+
+    #[test]
+    fn test_changes_are_logged_and_stamp_page_lsn() {
+        let (buffer_pool, _file) = new_buffer_pool();
+        let log_file = NamedTempFile::new().unwrap();
+        let log_manager = Arc::new(Mutex::new(LogManager::open(log_file.path()).unwrap()));
+        let mut heap = HeapFile::create(buffer_pool.clone()).unwrap();
+        heap.set_log_manager(log_manager.clone());
+        let mut txn = Transaction::new(7);
+
+        let rid = heap.insert(&mut txn, b"first").unwrap();
+        heap.update(&mut txn, rid, b"second").unwrap();
+        heap.delete(&mut txn, rid).unwrap();
+
+        log_manager.lock().unwrap().flush().unwrap();
+        let records = log_manager.lock().unwrap().read_records().unwrap();
+        let bodies: Vec<LogRecordBody> = records.iter().map(|record| record.body.clone()).collect();
+        let directory_entry = LogRecordBody::Insert {
+            rid: RecordId::new(heap.root_page_id(), 0),
+            tuple: rid.page_id.to_le_bytes().to_vec(),
+        };
+        assert_eq!(
+            bodies,
+            vec![
+                LogRecordBody::AllocatePage { page_id: rid.page_id, page_type: PageType::Heap },
+                LogRecordBody::Compensation { undo_next_lsn: records[0].lsn, action: Box::new(directory_entry) },
+                LogRecordBody::Insert { rid, tuple: b"first".to_vec() },
+                LogRecordBody::Update { rid, before: b"first".to_vec(), after: b"second".to_vec() },
+                LogRecordBody::Delete { rid, tuple: b"second".to_vec() },
+            ]
+        );
+        // chained newest to oldest, all for the same transaction
+        assert!(records.iter().all(|record| record.txn_id == 7));
+        assert!(records.windows(2).all(|pair| pair[1].prev_lsn == pair[0].lsn));
+        assert_eq!(txn.prev_lsn(), records.last().unwrap().lsn);
+        assert_eq!(buffer_pool.lock().unwrap().get_page(rid.page_id).unwrap().lsn(), txn.prev_lsn());
+    }
+
+    #[test]
+    fn test_directory_chains_across_pages() {
+        let (buffer_pool, _file) = new_buffer_pool();
+        let mut heap = HeapFile::create(buffer_pool.clone()).unwrap();
+        let mut txn = Transaction::new(1);
+        // more than half a page each, so every tuple gets a page of its own
+        let rids: Vec<RecordId> = (0..DIRECTORY_CAPACITY as u32 + 3)
+            .map(|i| heap.insert(&mut txn, &[i as u8; 2100]).unwrap())
+            .collect();
+        assert_eq!(heap.page_ids().len(), rids.len());
+        assert_eq!(heap.directory_page_ids.len(), 2);
+
+        let reopened = HeapFile::open(buffer_pool, heap.root_page_id()).unwrap();
+        assert_eq!(reopened.page_ids(), heap.page_ids());
+        assert_eq!(reopened.directory_page_ids, heap.directory_page_ids);
+        assert_eq!(reopened.get(*rids.last().unwrap()).unwrap(), vec![(rids.len() - 1) as u8; 2100]);
+    }
+
+    #[test]
+    fn test_scan_skips_deleted_tuples() {
+        let (mut heap, _file) = new_heap();
+        let mut txn = Transaction::new(1);
+        let rids: Vec<RecordId> = (0..50u8).map(|i| heap.insert(&mut txn, &[i; 300]).unwrap()).collect();
+        heap.delete(&mut txn, rids[3]).unwrap();
+        heap.delete(&mut txn, rids[40]).unwrap();
+
+        let scanned: Vec<(RecordId, Vec<u8>)> = heap.scan().collect::<Result<_, _>>().unwrap();
+        let expected: Vec<(RecordId, Vec<u8>)> = rids
+            .iter()
+            .enumerate()
+            .filter(|(i, _)| *i != 3 && *i != 40)
+            .map(|(i, rid)| (*rid, vec![i as u8; 300]))
+            .collect();
+        assert_eq!(scanned, expected);
+        assert_eq!(HeapFile::create(heap.buffer_pool.clone()).unwrap().scan().count(), 0);
+    }
+}
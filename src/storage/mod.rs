@@ -1,5 +1,28 @@
+pub mod checksum;
+
 mod page;
-pub use page::Page;
+pub use page::{DEFAULT_PAGE_SIZE, PAGE_FORMAT_VERSION, Page, PageError, PageIter, PageType, SUPPORTED_PAGE_SIZES, max_tuple_size};
+
+mod disk_manager;
+pub use disk_manager::{DiskManager, DiskManagerError};
+
+mod double_write;
+
+mod sync_mode;
+pub use sync_mode::SyncMode;
 
 mod buffer_pool;
-pub use buffer_pool::BufferPool;
\ No newline at end of file
+pub use buffer_pool::{BufferPool, BufferPoolError, BufferPoolStats, MemoryUsage, Reservation};
+
+mod replacement;
+pub use replacement::{ClockPolicy, LruKPolicy, LruPolicy, ReplacementPolicy};
+
+
+mod heap_file;
+pub use heap_file::{HeapError, HeapFile, HeapScan};
+
+mod free_space_map;
+pub use free_space_map::FreeSpaceMap;
+
+mod record_id;
+pub use record_id::RecordId;
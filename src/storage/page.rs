@@ -1,9 +1,19 @@
+use super::checksum::Crc32;
+use crate::wal::{INVALID_LSN, Lsn};
+
 #[derive(Debug, PartialEq)]
 pub enum PageError {
     TupleNotFound,
     InvalidSlot,
     NotEnoughSpace,
     InvalidPageContents,
+    /// the header or slot array contradicts itself, so the page can't be trusted
+    Corrupt,
+    ChecksumMismatch,
+    /// the header holds a page type tag this version doesn't know
+    UnknownPageType(u8),
+    /// the page was written in a format version this version can't read
+    UnsupportedVersion(u8),
 }
 
 impl std::fmt::Display for PageError {
@@ -13,6 +23,10 @@ impl std::fmt::Display for PageError {
             PageError::InvalidSlot => write!(f, "Invalid slot"),
             PageError::NotEnoughSpace => write!(f, "Not enough space"),
             PageError::InvalidPageContents => write!(f, "Invalid page contents"),
+            PageError::Corrupt => write!(f, "Page is corrupt"),
+            PageError::ChecksumMismatch => write!(f, "Checksum mismatch"),
+            PageError::UnknownPageType(tag) => write!(f, "Unknown page type {tag}"),
+            PageError::UnsupportedVersion(version) => write!(f, "Unsupported page format version {version}"),
         }
     }
 }
@@ -20,19 +34,100 @@ impl std::fmt::Display for PageError {
 impl std::error::Error for PageError {}
 
 /// size of the page header in bytes
-const HEADER_SIZE: usize = 16;
+const HEADER_SIZE: usize = 24;
+
+/// size of a page in bytes, unless the database was created with another one
+pub const DEFAULT_PAGE_SIZE: usize = 4096;
+
+/// page sizes a database can be created with
+pub const SUPPORTED_PAGE_SIZES: [usize; 4] = [4096, 8192, 16384, 32768];
+
+/// offset of the 4 byte checksum within the header
+const CHECKSUM_OFFSET: usize = 10;
+
+/// offset of the 1 byte page type tag within the header
+const PAGE_TYPE_OFFSET: usize = 14;
+
+/// offset of the 1 byte format version within the header
+const VERSION_OFFSET: usize = 15;
+
+/// offset of the 8 byte page LSN within the header
+const LSN_OFFSET: usize = 16;
+
+/// version of the page layout written by this code, bumped whenever it or the
+/// layout of the tuples on it changes (2 for the tuple null bitmap)
+pub const PAGE_FORMAT_VERSION: u8 = 2;
+
+/// size of a slot array entry in bytes (2 bytes tuple offset, 2 bytes tuple length)
+const SLOT_SIZE: usize = 4;
 
-/// size of a page in bytes
-const PAGE_SIZE: usize = 4096;
+/// Largest tuple that fits on an empty page of `page_size` bytes, next to its
+/// slot array entry.
+pub const fn max_tuple_size(page_size: usize) -> usize {
+    page_size - HEADER_SIZE - SLOT_SIZE
+}
+
+/// What a page is used for, stored in its header so that subsystems sharing
+/// the buffer pool can tell their pages apart.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PageType {
+    /// data or directory page of a heap file
+    Heap,
+    BTreeInternal,
+    BTreeLeaf,
+    /// continuation of a value too large for a single page
+    Overflow,
+    /// page of the heap file holding the system catalog
+    Catalog,
+    /// page listing free pages of the database file
+    FreeList,
+    /// directory of a hash index, mapping hash prefixes to buckets
+    HashDirectory,
+    /// bucket (or overflow page of one) of a hash index
+    HashBucket,
+}
+
+impl PageType {
+    /// Tag stored in the page header.
+    pub fn tag(self) -> u8 {
+        match self {
+            PageType::Heap => 1,
+            PageType::BTreeInternal => 2,
+            PageType::BTreeLeaf => 3,
+            PageType::Overflow => 4,
+            PageType::Catalog => 5,
+            PageType::FreeList => 6,
+            PageType::HashDirectory => 7,
+            PageType::HashBucket => 8,
+        }
+    }
+
+    pub fn from_tag(tag: u8) -> Option<Self> {
+        match tag {
+            1 => Some(PageType::Heap),
+            2 => Some(PageType::BTreeInternal),
+            3 => Some(PageType::BTreeLeaf),
+            4 => Some(PageType::Overflow),
+            5 => Some(PageType::Catalog),
+            6 => Some(PageType::FreeList),
+            7 => Some(PageType::HashDirectory),
+            8 => Some(PageType::HashBucket),
+            _ => None,
+        }
+    }
+}
 
 /// Extracts and parses the page header from the raw page contents.
 ///
-/// The header is stored in the first 16 bytes of the page and contains:
+/// The header is stored in the first 24 bytes of the page and contains:
 /// - Page ID (4 bytes)
 /// - Free space (2 bytes)
 /// - Free begin offset (2 bytes)
 /// - Free end offset (2 bytes)
-/// - Reserved space (6 bytes)
+/// - Checksum (4 bytes)
+/// - Page type (1 byte)
+/// - Format version (1 byte)
+/// - Page LSN (8 bytes)
 ///
 /// # Returns
 ///
@@ -53,22 +148,32 @@ pub struct PageHeader {
     pub free_space_total: u16,
     pub offset_begin_free_space: u16,
     pub offset_end_free_space: u16,
+    pub checksum: u32,
+    /// LSN of the last logged change applied to the page
+    pub lsn: Lsn,
 }
 
 /// Represents the header of a page in the database storage system.
 ///
-/// The header is stored in the first 16 bytes of the page and contains:
+/// The header is stored in the first 24 bytes of the page and contains:
 /// - Page ID (4 bytes)
 /// - Free space (2 bytes)
 /// - Free space begin offset (2 bytes)
 /// - Free space end offset (2 bytes)
+/// - Checksum (4 bytes)
+/// - Page type (1 byte)
+/// - Format version (1 byte)
+/// - Page LSN (8 bytes)
 impl PageHeader {
-    pub fn new(page_id: u32) -> Self {
+    /// Header of an empty page of `page_size` bytes.
+    pub fn new(page_id: u32, page_size: usize) -> Self {
         Self {
             page_id,
-            free_space_total: (PAGE_SIZE - HEADER_SIZE) as u16,
+            free_space_total: (page_size - HEADER_SIZE) as u16,
             offset_begin_free_space: HEADER_SIZE as u16,
-            offset_end_free_space: PAGE_SIZE as u16,
+            offset_end_free_space: page_size as u16,
+            checksum: 0,
+            lsn: INVALID_LSN,
         }
     }
 }
@@ -76,16 +181,37 @@ impl PageHeader {
 /// Represents a page in the database storage system.
 ///
 /// A page is the fundamental unit of storage in the database, containing both
-/// a header section and the actual data. Each page has a fixed size of 4096 bytes.
+/// a header section and the actual data. Pages are `DEFAULT_PAGE_SIZE` (4096)
+/// bytes unless the database was created with one of the larger
+/// `SUPPORTED_PAGE_SIZES`; every page of a database has the same size.
 ///
 /// The page layout is as follows:
-/// - Header (16 bytes)
+/// - Header (24 bytes)
 ///   - Page ID (4 bytes)
 ///   - Free space (2 bytes)
 ///   - Free space begin offset (2 bytes)
 ///   - Free space end offset (2 bytes)
-///   - Reserved space (6 bytes)
-/// - Data section (4080 bytes)
+///   - Checksum (4 bytes)
+///   - Page type (1 byte)
+///   - Format version (1 byte)
+///   - Page LSN (8 bytes)
+/// - Data section (the rest of the page, 4072 bytes by default)
+///
+/// Free space counts both the gap between the slot array and the tuple data and
+/// the dead bytes left behind by deletes and relocating updates. Dead bytes are
+/// reclaimed by `compact`, which inserts and updates run automatically when the
+/// contiguous gap alone is too small.
+///
+/// The checksum is a CRC-32 over the whole page except the checksum field itself.
+/// It is only refreshed by `update_checksum` (the buffer pool does this when it
+/// writes a page out), so it is stale while a page is being modified in memory.
+///
+/// The page type says which subsystem owns the page, and the format version
+/// which layout it was written in. Both are checked when a page is loaded.
+///
+/// The page LSN is the LSN of the last logged change applied to the page. The
+/// buffer pool flushes the log up to it before writing the page, and recovery
+/// compares it against log records so replaying the log twice is harmless.
 ///
 /// # Examples
 ///
@@ -96,21 +222,46 @@ impl PageHeader {
 /// assert_eq!(page.get_header().page_id, 42);
 /// ```
 pub struct Page {
-    /// Raw contents of the page as a fixed-size byte array
-    contents: [u8; PAGE_SIZE],
+    /// Raw contents of the page, exactly one page size long
+    contents: Box<[u8]>,
 }
 
 impl Page {
+    /// Creates an empty heap page.
     pub fn new(page_id: u32) -> Self {
-        let mut contents = [0u8; PAGE_SIZE];
-        let header = PageHeader::new(page_id);
+        Self::with_type(page_id, PageType::Heap)
+    }
+
+    /// Creates an empty page of the given type.
+    pub fn with_type(page_id: u32, page_type: PageType) -> Self {
+        Self::with_size(page_id, page_type, DEFAULT_PAGE_SIZE)
+    }
+
+    /// Creates an empty page of the given type that is `page_size` bytes long.
+    ///
+    /// # Panics
+    ///
+    /// If `page_size` isn't one of `SUPPORTED_PAGE_SIZES`.
+    pub fn with_size(page_id: u32, page_type: PageType, page_size: usize) -> Self {
+        assert!(SUPPORTED_PAGE_SIZES.contains(&page_size), "unsupported page size {page_size}");
+        let mut contents = vec![0u8; page_size].into_boxed_slice();
+        let header = PageHeader::new(page_id, page_size);
         
         contents[0..4].copy_from_slice(&header.page_id.to_le_bytes());
         contents[4..6].copy_from_slice(&header.free_space_total.to_le_bytes());
         contents[6..8].copy_from_slice(&header.offset_begin_free_space.to_le_bytes());
         contents[8..10].copy_from_slice(&header.offset_end_free_space.to_le_bytes());
-        
-        Self { contents }
+        contents[PAGE_TYPE_OFFSET] = page_type.tag();
+        contents[VERSION_OFFSET] = PAGE_FORMAT_VERSION;
+
+        let mut page = Self { contents };
+        page.update_checksum();
+        page
+    }
+
+    /// Size of the page in bytes.
+    pub fn page_size(&self) -> usize {
+        self.contents.len()
     }
 
     pub fn get_header(&self) -> PageHeader {
@@ -119,194 +270,407 @@ impl Page {
         let free_space_total = u16::from_le_bytes([header_bytes[4], header_bytes[5]]);
         let offset_begin_free_space = u16::from_le_bytes([header_bytes[6], header_bytes[7]]);
         let offset_end_free_space = u16::from_le_bytes([header_bytes[8], header_bytes[9]]);
+        let checksum = u32::from_le_bytes([header_bytes[10], header_bytes[11], header_bytes[12], header_bytes[13]]);
+        let lsn = Lsn::from_le_bytes(header_bytes[LSN_OFFSET..LSN_OFFSET + 8].try_into().unwrap());
 
         PageHeader {
             page_id,
             free_space_total,
             offset_begin_free_space,
             offset_end_free_space,
+            checksum,
+            lsn,
         }
 
     }
 
-    pub fn get_data(&self, slot_id: u16) -> Result<&[u8], PageError> {
-        let header = self.get_header();
-        
-        let (tuple_offset, tuple_length) = self.get_tuple_offset_and_length(slot_id)?;
+    /// Iterates over the live tuples on the page as `(slot_id, data)`, in slot
+    /// order, skipping the slots of deleted tuples.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use gondor_rdbms::storage::Page;
+    ///
+    /// let mut page = Page::new(1);
+    /// let first = page.insert_tuple(b"first").unwrap();
+    /// page.insert_tuple(b"second").unwrap();
+    /// page.delete_tuple(first).unwrap();
+    ///
+    /// let tuples: Vec<(u16, &[u8])> = page.iter().collect();
+    /// assert_eq!(tuples, vec![(1, &b"second"[..])]);
+    /// ```
+    pub fn iter(&self) -> PageIter<'_> {
+        PageIter { page: self, next_slot_id: 0 }
+    }
 
-        if tuple_offset + tuple_length > PAGE_SIZE as u16 {
-            return Err(PageError::TupleNotFound);
-        }
-        else if tuple_offset < header.offset_begin_free_space {
-            // this means the tuple is in the slot array or header space
-            // this ultimately means the tuple isn't there -- it could have been deleted (slot array points to header)
-            // or it may have never existed at all
-            return Err(PageError::TupleNotFound);
-        }
+    pub fn get_data(&self, slot_id: u16) -> Result<&[u8], PageError> {
+        let (tuple_offset, tuple_length) = self.get_live_tuple_offset_and_length(slot_id)?;
 
         Ok(&self.contents[tuple_offset as usize..(tuple_offset + tuple_length) as usize])
     }
 
+    /// Inserts `tuple` and returns its slot id.
+    ///
+    /// Slots left behind by deleted tuples are reused, lowest first, before the
+    /// slot array is extended with a new one.
     pub fn insert_tuple(&mut self, tuple: &[u8]) -> Result<u16, PageError> {
-        let header = self.get_header();
+        if let Some(slot_id) = self.free_slot() {
+            // a reused slot needs no new slot array entry, only space for the data
+            self.insert_tuple_at(slot_id, tuple)?;
+            return Ok(slot_id);
+        }
+
+        let mut header = self.get_header();
 
         // Check if we have enough space for both the tuple data AND the slot array entry (4 bytes)
-        let total_space_needed = tuple.len() as u16 + 4; // 4 bytes for slot array entry
+        if tuple.len() > self.page_size() - HEADER_SIZE {
+            return Err(PageError::NotEnoughSpace);
+        }
+        let total_space_needed = tuple.len() as u16 + SLOT_SIZE as u16;
         if header.free_space_total < total_space_needed {
             return Err(PageError::NotEnoughSpace);
         }
 
+        // the space is there, but it may be scattered between live tuples -- slide them together first
+        if header.offset_end_free_space - header.offset_begin_free_space < total_space_needed {
+            self.compact();
+            header = self.get_header();
+        }
+
         // data and slot array grow towards each other, so slot array ends where free space begins
         let slot_offset = header.offset_begin_free_space;
-        let slot_id = (slot_offset - HEADER_SIZE as u16) / 2;
+        let slot_id = (slot_offset - HEADER_SIZE as u16) / SLOT_SIZE as u16;
 
         // data and slot array grow towards each other, so data array begins where free space ends
         let tuple_offset_end = header.offset_end_free_space;
         let tuple_offset_begin = tuple_offset_end - tuple.len() as u16; // end of tuple should be where free space ends
-        
-        // modify slot array to point to the new tuple (don't update header here)
-        self.update_slot_data_only(slot_id, tuple_offset_begin, tuple.len() as u16)?;
 
         // copy tuple into the appropraite slot on page
-        let tuple_data = &mut self.contents[tuple_offset_begin as usize..tuple_offset_end as usize];
-        tuple_data.copy_from_slice(tuple);
+        self.modify_tuple_data(tuple_offset_begin, tuple)?;
 
         // calculate new free space - subtract both tuple size AND slot space
         let new_free_space_total = header.free_space_total - total_space_needed;
-        let new_offset_begin_free_space = slot_offset + 4;
+        let new_offset_begin_free_space = slot_offset + SLOT_SIZE as u16;
         let new_offset_end_free_space = tuple_offset_begin;
+        self.update_header(new_free_space_total, new_offset_begin_free_space, new_offset_end_free_space)?;
 
-        self.update_header(new_free_space_total, new_offset_begin_free_space, new_offset_end_free_space);
+        // modify slot array to point to the new tuple, now that the slot array covers it
+        self.update_slot(slot_id, tuple_offset_begin, tuple.len() as u16)?;
 
         // return slot id of the new tuple
         Ok(slot_id)
     }
 
-    pub fn update_tuple(&mut self, slot_id: u16, tuple: &[u8]) -> Result<u16, PageError> {
-        let header = self.get_header();
+    /// Inserts `tuple` into a specific slot, growing the slot array if needed.
+    ///
+    /// Replaying and rolling back logged operations has to put a tuple back exactly
+    /// where it was, so this takes the slot id instead of picking one. Fails with
+    /// `InvalidSlot` if the slot already holds a live tuple.
+    pub fn insert_tuple_at(&mut self, slot_id: u16, tuple: &[u8]) -> Result<(), PageError> {
+        if slot_id < self.slot_count() && self.get_live_tuple_offset_and_length(slot_id).is_ok() {
+            return Err(PageError::InvalidSlot);
+        }
+        if tuple.len() > self.page_size() - HEADER_SIZE {
+            return Err(PageError::NotEnoughSpace);
+        }
 
-        let (old_tuple_offset, old_tuple_length) = self.get_tuple_offset_and_length(slot_id)?;
+        // the tuple plus any slots the slot array has to grow by to reach slot_id
+        let space_needed = |page: &Self| {
+            let new_slots = (slot_id as usize + 1).saturating_sub(page.slot_count() as usize);
+            tuple.len() + new_slots * SLOT_SIZE
+        };
 
-        if old_tuple_offset < header.offset_begin_free_space {
-            // this means the tuple is in the slot array or header space
-            // this ultimately means the tuple isn't there -- it could have been deleted (slot array points to header)
-            // or it may have never existed at all
-            return Err(PageError::TupleNotFound);
-        } else if tuple.len() as u16 > old_tuple_length {
-            // this is OK so long as we still have enough space
-            if tuple.len() as u16 > header.free_space_total {
-                return Err(PageError::NotEnoughSpace);
-            }
+        let mut header = self.get_header();
+        if space_needed(self) > header.free_space_total as usize {
+            return Err(PageError::NotEnoughSpace);
+        }
+        if ((header.offset_end_free_space - header.offset_begin_free_space) as usize) < space_needed(self) {
+            // compaction may drop trailing tombstones, which frees exactly as many bytes as it adds back to space_needed
+            self.compact();
+            header = self.get_header();
+        }
+        let total_space_needed = space_needed(self) as u16;
+
+        let tuple_offset_begin = header.offset_end_free_space - tuple.len() as u16;
+        self.modify_tuple_data(tuple_offset_begin, tuple)?;
+
+        // slots between the old end of the slot array and slot_id start out deleted
+        let new_offset_begin_free_space = header.offset_begin_free_space.max((HEADER_SIZE + (slot_id as usize + 1) * SLOT_SIZE) as u16);
+        self.contents[header.offset_begin_free_space as usize..new_offset_begin_free_space as usize].fill(0);
+
+        let new_free_space_total = header.free_space_total - total_space_needed;
+        self.update_header(new_free_space_total, new_offset_begin_free_space, tuple_offset_begin)?;
+        self.update_slot(slot_id, tuple_offset_begin, tuple.len() as u16)?;
 
-            let new_tuple_offset = header.offset_end_free_space - tuple.len() as u16;   
-            self.modify_tuple_data(new_tuple_offset, tuple)?;
+        Ok(())
+    }
 
-            // we now need to update the slot array to point to the new tuple
-            self.update_slot(slot_id, new_tuple_offset, tuple.len() as u16)?;
-            // at this point nothing points to the old data, so we can get rid of it next time we compact
-            
-            // we could delete the old tuple, but we don't need to since it's not marked in the slot array
-            // it will be gone when we do compaction on the page
-        } else if tuple.len() as u16 <= old_tuple_length {
+    pub fn update_tuple(&mut self, slot_id: u16, tuple: &[u8]) -> Result<u16, PageError> {
+        let (old_tuple_offset, old_tuple_length) = self.get_live_tuple_offset_and_length(slot_id)?;
+        let mut header = self.get_header();
+
+        if tuple.len() <= old_tuple_length as usize {
             // we just modify the tuple data in its old spot
+            // the tail of the old tuple becomes dead space until the next compaction
             self.modify_tuple_data(old_tuple_offset, tuple)?;
+            self.update_slot(slot_id, old_tuple_offset, tuple.len() as u16)?;
+
+            let new_free_space_total = header.free_space_total + (old_tuple_length - tuple.len() as u16);
+            self.update_header(new_free_space_total, header.offset_begin_free_space, header.offset_end_free_space)?;
+            return Ok(slot_id);
+        }
+
+        // the longer tuple must fit in the free space on its own -- the old bytes only become free once it's moved
+        if tuple.len() > header.free_space_total as usize {
+            return Err(PageError::NotEnoughSpace);
+        }
+
+        if ((header.offset_end_free_space - header.offset_begin_free_space) as usize) < tuple.len() {
+            self.compact();
+            header = self.get_header();
         }
 
-        // we get the old tuple length back -- it's no longer used -- so we add only the delta
-        // note that this works for both a longer and a shorter tuple than the original tuple
-        let new_free_space_total = header.free_space_total - (tuple.len() as u16 - old_tuple_length) as u16;
-        let new_offset_begin_free_space = header.offset_begin_free_space;
-        let new_offset_end_free_space = header.offset_end_free_space;
-        self.update_header(new_free_space_total, new_offset_begin_free_space, new_offset_end_free_space);
+        let new_tuple_offset = header.offset_end_free_space - tuple.len() as u16;
+        self.modify_tuple_data(new_tuple_offset, tuple)?;
+
+        // we now need to update the slot array to point to the new tuple
+        // at this point nothing points to the old data, so it is dead space until we compact
+        self.update_slot(slot_id, new_tuple_offset, tuple.len() as u16)?;
+
+        // the new tuple takes its full length out of free space, the old tuple gives its length back
+        let new_free_space_total = header.free_space_total - tuple.len() as u16 + old_tuple_length;
+        self.update_header(new_free_space_total, header.offset_begin_free_space, new_tuple_offset)?;
 
         Ok(slot_id)
     }
 
     pub fn delete_tuple(&mut self, slot_id: u16) -> Result<(), PageError> {
-        let header = self.get_header();
-        
+        let (_, old_tuple_length) = self.get_live_tuple_offset_and_length(slot_id)?;
+
         // simply modify slot array to indicate the tuple is deleted
-        // we deal with this later upon page compaction
+        // the tuple bytes count as free space now, but are only reclaimed upon page compaction
         self.update_slot(slot_id, 0, 0)?; // zero means deleted, since that points to header
 
+        let header = self.get_header();
+        let new_free_space_total = header.free_space_total + old_tuple_length;
+        self.update_header(new_free_space_total, header.offset_begin_free_space, header.offset_end_free_space)?;
+
         Ok(())
     }
 
-    pub fn set_contents(&mut self, contents: &[u8]) -> Result<(), PageError> {
-        if contents.len() != PAGE_SIZE {
+    /// Slides all live tuples towards the end of the page so that the dead bytes left
+    /// behind by deletes and relocating updates become one contiguous free region.
+    ///
+    /// Slot ids of live tuples are unchanged. Deleted slots at the end of the slot
+    /// array are dropped, since nothing can still refer to them.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use gondor_rdbms::storage::Page;
+    ///
+    /// let mut page = Page::new(1);
+    /// let first = page.insert_tuple(b"first").unwrap();
+    /// let second = page.insert_tuple(b"second").unwrap();
+    /// page.delete_tuple(first).unwrap();
+    ///
+    /// page.compact();
+    /// assert_eq!(page.get_data(second).unwrap(), b"second");
+    /// ```
+    pub fn compact(&mut self) {
+        let slot_count = self.slot_count();
+
+        // gather live tuples, highest offset first, so moving each one towards the end of
+        // the page never overwrites a tuple that hasn't been moved yet
+        let mut live_tuples: Vec<(u16, u16, u16)> = (0..slot_count)
+            .filter_map(|slot_id| {
+                self.get_live_tuple_offset_and_length(slot_id)
+                    .ok()
+                    .map(|(offset, length)| (slot_id, offset, length))
+            })
+            .collect();
+        live_tuples.sort_by_key(|&(_, offset, _)| std::cmp::Reverse(offset));
+
+        let mut write_offset = self.page_size();
+        for (slot_id, offset, length) in live_tuples {
+            write_offset -= length as usize;
+            self.contents.copy_within(offset as usize..(offset + length) as usize, write_offset);
+            // the slot is known to be inside the slot array, so this can't fail
+            let _ = self.update_slot(slot_id, write_offset as u16, length);
+        }
+
+        // trailing tombstones can be dropped from the slot array entirely
+        let mut new_slot_count = slot_count;
+        while new_slot_count > 0 && self.get_live_tuple_offset_and_length(new_slot_count - 1).is_err() {
+            new_slot_count -= 1;
+        }
+        let new_offset_begin_free_space = (HEADER_SIZE + new_slot_count as usize * SLOT_SIZE) as u16;
+
+        // the free region is contiguous again, so free space is exactly the gap between slot array and data
+        let new_free_space_total = write_offset as u16 - new_offset_begin_free_space;
+        let _ = self.update_header(new_free_space_total, new_offset_begin_free_space, write_offset as u16);
+    }
+
+    /// Loads a page from its on-disk form, e.g. as read by the disk manager.
+    ///
+    /// Fails with `Corrupt` unless the header and slot array are consistent:
+    /// offsets within the page, every live tuple inside the data section, and
+    /// the free space count matching what the tuples leave over. Pages of an
+    /// unknown type or format version are rejected too. The page size is taken
+    /// from the length of `bytes`, which fails with `InvalidPageContents` if it
+    /// isn't one of `SUPPORTED_PAGE_SIZES`. The checksum isn't checked here, see
+    /// `verify_checksum`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use gondor_rdbms::storage::{Page, PageError};
+    ///
+    /// let mut page = Page::new(7);
+    /// let slot_id = page.insert_tuple(b"Hello, world!").unwrap();
+    ///
+    /// let loaded = Page::from_bytes(page.to_bytes()).unwrap();
+    /// assert_eq!(loaded.get_data(slot_id).unwrap(), b"Hello, world!");
+    ///
+    /// let mut garbage = page.to_bytes().to_vec();
+    /// garbage[6..8].copy_from_slice(&u16::MAX.to_le_bytes());
+    /// assert_eq!(Page::from_bytes(&garbage).err(), Some(PageError::Corrupt));
+    /// ```
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, PageError> {
+        if !SUPPORTED_PAGE_SIZES.contains(&bytes.len()) {
             return Err(PageError::InvalidPageContents);
         }
+        let page = Self { contents: bytes.into() };
+        page.validate()?;
+        Ok(page)
+    }
 
-        self.contents.copy_from_slice(contents);
+    /// The page in its on-disk form.
+    pub fn to_bytes(&self) -> &[u8] {
+        &self.contents
+    }
 
-        // try to parse the header
-        let header = self.parse_header_from_contents()?;
-        
+    /// Replaces the contents of the page with `contents`, validated like
+    /// `from_bytes`, which must be as long as the page already is. The page is
+    /// left as it was if they don't pass.
+    pub fn set_contents(&mut self, contents: &[u8]) -> Result<(), PageError> {
+        if contents.len() != self.page_size() {
+            return Err(PageError::InvalidPageContents);
+        }
+        *self = Self::from_bytes(contents)?;
         Ok(())
     }
 
+    /// Same as `to_bytes`.
     pub fn get_raw_contents(&self) -> &[u8] {
         &self.contents
     }
 
-    fn parse_header_from_contents(&self) -> Result<PageHeader, PageError> {
-        let header_bytes = &self.contents[0..HEADER_SIZE];
-        let page_id = u32::from_le_bytes([header_bytes[0], header_bytes[1], header_bytes[2], header_bytes[3]]);
-        let free_space_total = u16::from_le_bytes([header_bytes[4], header_bytes[5]]);
-        let offset_begin_free_space = u16::from_le_bytes([header_bytes[6], header_bytes[7]]);
-        let offset_end_free_space = u16::from_le_bytes([header_bytes[8], header_bytes[9]]);
+    /// Computes the CRC-32 of the page contents, skipping the stored checksum itself.
+    pub fn compute_checksum(&self) -> u32 {
+        let mut crc = Crc32::new();
+        crc.update(&self.contents[..CHECKSUM_OFFSET]);
+        crc.update(&self.contents[CHECKSUM_OFFSET + 4..]);
+        crc.finish()
+    }
 
-        if free_space_total > PAGE_SIZE as u16 - HEADER_SIZE as u16 {
-            return Err(PageError::InvalidPageContents);
+    pub fn page_type(&self) -> PageType {
+        // the tag is checked when the page is loaded, and only ever set from a PageType after that
+        PageType::from_tag(self.contents[PAGE_TYPE_OFFSET]).expect("page type is validated on load")
+    }
+
+    pub fn set_page_type(&mut self, page_type: PageType) {
+        self.contents[PAGE_TYPE_OFFSET] = page_type.tag();
+    }
+
+    pub fn format_version(&self) -> u8 {
+        self.contents[VERSION_OFFSET]
+    }
+
+    /// LSN of the last logged change applied to the page.
+    pub fn lsn(&self) -> Lsn {
+        self.get_header().lsn
+    }
+
+    pub fn set_lsn(&mut self, lsn: Lsn) {
+        self.contents[LSN_OFFSET..LSN_OFFSET + 8].copy_from_slice(&lsn.to_le_bytes());
+    }
+
+    /// Stores the checksum of the current contents in the header.
+    pub fn update_checksum(&mut self) {
+        let checksum = self.compute_checksum();
+        self.contents[CHECKSUM_OFFSET..CHECKSUM_OFFSET + 4].copy_from_slice(&checksum.to_le_bytes());
+    }
+
+    /// Checks the stored checksum against the contents, e.g. after reading the page from disk.
+    pub fn verify_checksum(&self) -> Result<(), PageError> {
+        if self.get_header().checksum != self.compute_checksum() {
+            return Err(PageError::ChecksumMismatch);
         }
 
-        if offset_begin_free_space > PAGE_SIZE as u16 {
-            return Err(PageError::InvalidPageContents);
+        Ok(())
+    }
+
+    /// checks the invariants every operation on the page maintains, see `from_bytes`
+    fn validate(&self) -> Result<(), PageError> {
+        let version = self.contents[VERSION_OFFSET];
+        if version != PAGE_FORMAT_VERSION {
+            return Err(PageError::UnsupportedVersion(version));
         }
 
-        if offset_end_free_space > PAGE_SIZE as u16 {
-            return Err(PageError::InvalidPageContents);
+        let page_type_tag = self.contents[PAGE_TYPE_OFFSET];
+        if PageType::from_tag(page_type_tag).is_none() {
+            return Err(PageError::UnknownPageType(page_type_tag));
         }
 
-        if offset_begin_free_space > offset_end_free_space {
-            return Err(PageError::InvalidPageContents);
+        let header = self.get_header();
+        let page_size = self.page_size();
+        let begin = header.offset_begin_free_space as usize;
+        let end = header.offset_end_free_space as usize;
+        let free_space_total = header.free_space_total as usize;
+
+        // the slot array starts right after the header and is made of whole slots
+        if begin < HEADER_SIZE || !(begin - HEADER_SIZE).is_multiple_of(SLOT_SIZE) {
+            return Err(PageError::Corrupt);
+        }
+        if begin > end || end > page_size || free_space_total < end - begin {
+            return Err(PageError::Corrupt);
         }
 
-        Ok(PageHeader {
-            page_id,
-            free_space_total,
-            offset_begin_free_space,
-            offset_end_free_space,
-        })
-    }
+        let mut live_bytes = 0;
+        for slot_id in 0..self.slot_count() {
+            let (offset, length) = self.get_tuple_offset_and_length(slot_id)?;
+            if (offset as usize) < begin {
+                // deleted
+                continue;
+            }
+            if (offset as usize) < end || offset as usize + length as usize > page_size {
+                return Err(PageError::Corrupt);
+            }
+            live_bytes += length as usize;
+        }
 
-    fn update_slot(&mut self, slot_id: u16, tuple_offset_begin: u16, tuple_length: u16) -> Result<(), PageError> {
-        // This version updates both slot data and header (for use in update_tuple)
-        self.update_slot_data_only(slot_id, tuple_offset_begin, tuple_length)?;
-        
-        // update header with new free space beginning, since slot array is now extended
-        let header = self.get_header();
-        let slot_offset = HEADER_SIZE as u16 + slot_id * 2;
-        let new_free_space_total = header.free_space_total - 4;
-        let new_offset_begin_free_space = slot_offset + 4;
-        self.update_header(new_free_space_total, new_offset_begin_free_space, header.offset_end_free_space);
+        // everything past the slot array is either a live tuple or free space, dead bytes included
+        if begin + live_bytes + free_space_total != page_size {
+            return Err(PageError::Corrupt);
+        }
 
         Ok(())
     }
 
-    fn update_slot_data_only(&mut self, slot_id: u16, tuple_offset_begin: u16, tuple_length: u16) -> Result<(), PageError> {
-        let slot_offset = HEADER_SIZE as u16 + slot_id * 2;
+    fn update_slot(&mut self, slot_id: u16, tuple_offset_begin: u16, tuple_length: u16) -> Result<(), PageError> {
+        let slot_offset = HEADER_SIZE + slot_id as usize * SLOT_SIZE;
 
-        if slot_offset + 4 > PAGE_SIZE as u16 {
+        if slot_offset + SLOT_SIZE > self.page_size() {
+            return Err(PageError::InvalidSlot);
+        } else if slot_offset + SLOT_SIZE > self.get_header().offset_begin_free_space as usize {
+            // this case would mean we are trying to modify slot array data past the end of the slot array
             return Err(PageError::InvalidSlot);
-        } else if slot_offset + 4 > self.get_header().offset_end_free_space {
-            // this case would mean we are trying to modify slot array data that is in the data space
-            // typically this means we do not have enough space
-            return Err(PageError::NotEnoughSpace);
         }
 
         // update slot array data only
-        let slot_data = &mut self.contents[slot_offset as usize..(slot_offset + 4) as usize];
+        let slot_data = &mut self.contents[slot_offset..slot_offset + SLOT_SIZE];
         slot_data[0] = (tuple_offset_begin & 0xFF) as u8; // get lower 8 bits -- mask upper 8 bits of offset
         slot_data[1] = ((tuple_offset_begin >> 8) & 0xFF) as u8; // get upper 8 bits -- shift and mask upper 8 bits of offset (should be 0, but just in case)
         slot_data[2] = (tuple_length & 0xFF) as u8; // get lower 8 bits -- mask upper 8 bits of length
@@ -328,27 +692,86 @@ impl Page {
     }
 
     fn get_tuple_offset_and_length(&self, slot_id: u16) -> Result<(u16, u16), PageError> {
-        let slot_offset = HEADER_SIZE as u16 + slot_id * 2;
+        let slot_offset = HEADER_SIZE + slot_id as usize * SLOT_SIZE;
 
-        if slot_offset + 4 > PAGE_SIZE as u16 {
+        if slot_offset + SLOT_SIZE > self.page_size() {
             return Err(PageError::InvalidSlot);
         }
 
-        let slot_data = &self.contents[slot_offset as usize..(slot_offset + 4) as usize];
+        let slot_data = &self.contents[slot_offset..slot_offset + SLOT_SIZE];
         let tuple_offset = u16::from_le_bytes([slot_data[0], slot_data[1]]);
         let tuple_length = u16::from_le_bytes([slot_data[2], slot_data[3]]);
 
         Ok((tuple_offset, tuple_length))
     }
 
+    /// Like `get_tuple_offset_and_length`, but fails with `TupleNotFound` unless the slot
+    /// is part of the slot array and points at a live tuple in the data section.
+    fn get_live_tuple_offset_and_length(&self, slot_id: u16) -> Result<(u16, u16), PageError> {
+        let header = self.get_header();
+        let (tuple_offset, tuple_length) = self.get_tuple_offset_and_length(slot_id)?;
+
+        if slot_id >= self.slot_count() {
+            // the slot was never handed out
+            return Err(PageError::TupleNotFound);
+        } else if tuple_offset as usize + tuple_length as usize > self.page_size() {
+            return Err(PageError::TupleNotFound);
+        } else if tuple_offset < header.offset_begin_free_space {
+            // this means the tuple is in the slot array or header space
+            // this ultimately means the tuple isn't there -- it has been deleted (slot array points to header)
+            return Err(PageError::TupleNotFound);
+        }
+
+        Ok((tuple_offset, tuple_length))
+    }
+
+    /// first slot in the slot array whose tuple has been deleted, if any
+    fn free_slot(&self) -> Option<u16> {
+        (0..self.slot_count()).find(|&slot_id| self.get_live_tuple_offset_and_length(slot_id).is_err())
+    }
+
+    /// Number of entries in the slot array, including deleted ones.
+    pub fn slot_count(&self) -> u16 {
+        let header = self.get_header();
+        ((header.offset_begin_free_space as usize).saturating_sub(HEADER_SIZE) / SLOT_SIZE) as u16
+    }
+
     fn modify_tuple_data(&mut self, tuple_offset: u16, tuple: &[u8]) -> Result<(), PageError> {
         let tuple_data = &mut self.contents[tuple_offset as usize..(tuple_offset as usize + tuple.len())];
         tuple_data.copy_from_slice(tuple);
 
         Ok(())
     }
+}
+
+impl<'a> IntoIterator for &'a Page {
+    type Item = (u16, &'a [u8]);
+    type IntoIter = PageIter<'a>;
 
-    
+    fn into_iter(self) -> PageIter<'a> {
+        self.iter()
+    }
+}
+
+/// Iterator over the live tuples of a `Page`, returned by `Page::iter`.
+pub struct PageIter<'a> {
+    page: &'a Page,
+    next_slot_id: u16,
+}
+
+impl<'a> Iterator for PageIter<'a> {
+    type Item = (u16, &'a [u8]);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while self.next_slot_id < self.page.slot_count() {
+            let slot_id = self.next_slot_id;
+            self.next_slot_id += 1;
+            if let Ok(data) = self.page.get_data(slot_id) {
+                return Some((slot_id, data));
+            }
+        }
+        None
+    }
 }
 
 #[cfg(test)]
@@ -359,9 +782,106 @@ mod tests {
     fn test_page_creation() {
         let page = Page::new(1);
         assert_eq!(page.get_header().page_id, 1);
-        assert_eq!(page.get_header().free_space_total, (PAGE_SIZE - HEADER_SIZE) as u16);
+        assert_eq!(page.get_header().free_space_total, (DEFAULT_PAGE_SIZE - HEADER_SIZE) as u16);
         assert_eq!(page.get_header().offset_begin_free_space, HEADER_SIZE as u16);
-        assert_eq!(page.get_header().offset_end_free_space, PAGE_SIZE as u16);
+        assert_eq!(page.get_header().offset_end_free_space, DEFAULT_PAGE_SIZE as u16);
+    }
+
+    #[test]
+    fn test_page_type_and_version() {
+        let page = Page::new(1);
+        assert_eq!(page.page_type(), PageType::Heap);
+        assert_eq!(page.format_version(), PAGE_FORMAT_VERSION);
+
+        let mut page = Page::with_type(2, PageType::BTreeLeaf);
+        assert_eq!(page.page_type(), PageType::BTreeLeaf);
+        page.set_page_type(PageType::BTreeInternal);
+        assert_eq!(page.page_type(), PageType::BTreeInternal);
+
+        // survives a round trip through raw contents
+        let mut loaded = Page::new(0);
+        loaded.set_contents(page.get_raw_contents()).unwrap();
+        assert_eq!(loaded.page_type(), PageType::BTreeInternal);
+    }
+
+    #[test]
+    fn test_load_rejects_unknown_type_or_version() {
+        let mut contents = Page::new(1).get_raw_contents().to_vec();
+        contents[PAGE_TYPE_OFFSET] = 0xEE;
+        assert_eq!(Page::new(1).set_contents(&contents), Err(PageError::UnknownPageType(0xEE)));
+
+        let mut contents = Page::new(1).get_raw_contents().to_vec();
+        contents[VERSION_OFFSET] = PAGE_FORMAT_VERSION + 1;
+        assert_eq!(Page::new(1).set_contents(&contents), Err(PageError::UnsupportedVersion(PAGE_FORMAT_VERSION + 1)));
+    }
+
+    #[test]
+    fn test_from_bytes_round_trips() {
+        let mut page = Page::with_type(3, PageType::Catalog);
+        let slot_ids: Vec<u16> = (0..6u8).map(|i| page.insert_tuple(&[i; 30]).unwrap()).collect();
+        page.delete_tuple(slot_ids[1]).unwrap();
+        page.update_tuple(slot_ids[2], &[0xAA; 5]).unwrap();
+        page.update_tuple(slot_ids[3], &[0xBB; 80]).unwrap();
+
+        let loaded = Page::from_bytes(page.to_bytes()).unwrap();
+        assert_eq!(loaded.to_bytes(), page.to_bytes());
+        assert_eq!(loaded.page_type(), PageType::Catalog);
+        assert_eq!(loaded.get_data(slot_ids[3]).unwrap(), &[0xBB; 80]);
+        assert_eq!(loaded.iter().count(), 5);
+    }
+
+    #[test]
+    fn test_from_bytes_rejects_inconsistent_pages() {
+        let mut page = Page::new(1);
+        let slot_id = page.insert_tuple(b"Hello, world!").unwrap();
+        let header = page.get_header();
+        let corrupted = |offset: usize, value: u16| {
+            let mut bytes = page.to_bytes().to_vec();
+            bytes[offset..offset + 2].copy_from_slice(&value.to_le_bytes());
+            Page::from_bytes(&bytes).err()
+        };
+
+        // free space begins inside the header, or in the middle of a slot
+        assert_eq!(corrupted(6, HEADER_SIZE as u16 - SLOT_SIZE as u16), Some(PageError::Corrupt));
+        assert_eq!(corrupted(6, header.offset_begin_free_space + 1), Some(PageError::Corrupt));
+        // free space ends past the page, or before it begins
+        assert_eq!(corrupted(8, DEFAULT_PAGE_SIZE as u16 + 1), Some(PageError::Corrupt));
+        assert_eq!(corrupted(8, HEADER_SIZE as u16), Some(PageError::Corrupt));
+        // free space that doesn't add up with the tuples on the page
+        assert_eq!(corrupted(4, header.free_space_total - 1), Some(PageError::Corrupt));
+        assert_eq!(corrupted(4, header.free_space_total + 1), Some(PageError::Corrupt));
+
+        // a slot pointing into the free gap, or running off the end of the page
+        let slot_offset = HEADER_SIZE + slot_id as usize * SLOT_SIZE;
+        assert_eq!(corrupted(slot_offset, header.offset_begin_free_space + 10), Some(PageError::Corrupt));
+        assert_eq!(corrupted(slot_offset + 2, 100), Some(PageError::Corrupt));
+
+        // the wrong length never makes it to validation
+        assert_eq!(Page::new(1).set_contents(&[0u8; 100]), Err(PageError::InvalidPageContents));
+    }
+
+    #[test]
+    fn test_larger_page_sizes() {
+        for page_size in SUPPORTED_PAGE_SIZES {
+            let mut page = Page::with_size(4, PageType::Heap, page_size);
+            assert_eq!(page.page_size(), page_size);
+            assert_eq!(page.get_header().offset_end_free_space as usize, page_size);
+
+            // a tuple filling the whole page fits, one byte more doesn't
+            let too_large = vec![0x5A; max_tuple_size(page_size) + 1];
+            assert_eq!(page.insert_tuple(&too_large), Err(PageError::NotEnoughSpace));
+            let slot_id = page.insert_tuple(&too_large[1..]).unwrap();
+            assert_eq!(page.get_header().free_space_total, 0);
+
+            let loaded = Page::from_bytes(page.to_bytes()).unwrap();
+            assert_eq!(loaded.page_size(), page_size);
+            assert_eq!(loaded.get_data(slot_id).unwrap(), &too_large[1..]);
+        }
+
+        // contents of another page size can't replace a page's
+        let bigger = Page::with_size(1, PageType::Heap, 8192);
+        assert_eq!(Page::new(1).set_contents(bigger.to_bytes()), Err(PageError::InvalidPageContents));
+        assert_eq!(Page::from_bytes(&[0u8; 5000]).err(), Some(PageError::InvalidPageContents));
     }
 
     #[test]
@@ -378,7 +898,6 @@ mod tests {
     fn test_insert_tuple_not_enough_space() {
         let mut page = Page::new(1);
         // make page header show that there is no space left
-        let header = page.get_header();
         let header_bytes = &mut page.contents[0..HEADER_SIZE];
 
         header_bytes[4] = 0; // no free space total
@@ -474,11 +993,11 @@ mod tests {
         let slot_size = 4; // 4 bytes per slot array entry
         
         // Calculate how many tuples we can fit mathematically
-        // Available space = PAGE_SIZE - HEADER_SIZE = 4096 - 16 = 4080 bytes
+        // Available space = DEFAULT_PAGE_SIZE - HEADER_SIZE = 4096 - 24 = 4072 bytes
         // Each tuple uses: tuple_size + slot_size = 10 + 4 = 14 bytes
-        let available_space = PAGE_SIZE - HEADER_SIZE; // 4080 bytes
+        let available_space = DEFAULT_PAGE_SIZE - HEADER_SIZE; // 4072 bytes
         let space_per_tuple = tuple_size + slot_size; // 14 bytes
-        let max_tuples = available_space / space_per_tuple; // 4080 / 14 = 291 tuples
+        let max_tuples = available_space / space_per_tuple; // 4072 / 14 = 290 tuples
         
         // Insert exactly max_tuples - 1 to leave some space for testing update failure
         let tuples_to_insert = max_tuples - 1; // 290 tuples
@@ -486,7 +1005,7 @@ mod tests {
         
         for i in 0..tuples_to_insert {
             let slot_id = page.insert_tuple(&tuple_data)
-                .expect(&format!("Should be able to insert tuple {}", i));
+                .unwrap_or_else(|_| panic!("Should be able to insert tuple {}", i));
             slot_ids.push(slot_id);
         }
         
@@ -524,4 +1043,228 @@ mod tests {
         assert_eq!(result.unwrap_err(), PageError::TupleNotFound);
     }
 
-}
\ No newline at end of file
+    #[test]
+    fn test_checksum_detects_corruption() {
+        let mut page = Page::new(1);
+        page.verify_checksum().expect("a new page should have a valid checksum");
+
+        let slot_id = page.insert_tuple(b"Hello, world!").unwrap();
+        // modifications leave the checksum stale until it is refreshed
+        assert_eq!(page.verify_checksum().unwrap_err(), PageError::ChecksumMismatch);
+        page.update_checksum();
+        page.verify_checksum().unwrap();
+
+        // flip a single bit in the tuple data
+        let (offset, _) = page.get_tuple_offset_and_length(slot_id).unwrap();
+        page.contents[offset as usize] ^= 0x01;
+        assert_eq!(page.verify_checksum().unwrap_err(), PageError::ChecksumMismatch);
+    }
+
+    #[test]
+    fn test_update_tuple_shorter() {
+        let mut page = Page::new(1);
+        let slot_id = page.insert_tuple(b"Hello, world!").unwrap();
+        let free_space_before = page.get_header().free_space_total;
+
+        page.update_tuple(slot_id, b"Hi").unwrap();
+
+        assert_eq!(page.get_data(slot_id).unwrap(), b"Hi");
+        // the 11 bytes no longer used by the tuple count as free space
+        assert_eq!(page.get_header().free_space_total, free_space_before + 11);
+    }
+
+    #[test]
+    fn test_update_tuple_longer_does_not_clobber_later_inserts() {
+        let mut page = Page::new(1);
+        let first = page.insert_tuple(b"short").unwrap();
+        let second = page.insert_tuple(b"second tuple").unwrap();
+
+        page.update_tuple(first, b"a much longer first tuple").unwrap();
+        let third = page.insert_tuple(b"third tuple").unwrap();
+
+        assert_eq!(page.get_data(first).unwrap(), b"a much longer first tuple");
+        assert_eq!(page.get_data(second).unwrap(), b"second tuple");
+        assert_eq!(page.get_data(third).unwrap(), b"third tuple");
+    }
+
+    #[test]
+    fn test_delete_tuple_twice() {
+        let mut page = Page::new(1);
+        let slot_id = page.insert_tuple(b"Hello, world!").unwrap();
+        page.delete_tuple(slot_id).unwrap();
+        assert_eq!(page.delete_tuple(slot_id).unwrap_err(), PageError::TupleNotFound);
+        assert_eq!(page.delete_tuple(slot_id + 1).unwrap_err(), PageError::TupleNotFound);
+    }
+
+    #[test]
+    fn test_compact_reclaims_dead_space() {
+        let mut page = Page::new(1);
+        let slot_ids: Vec<u16> = (0..10u8)
+            .map(|i| page.insert_tuple(&[i; 100]).unwrap())
+            .collect();
+
+        // delete every other tuple and shrink one of the survivors
+        for slot_id in slot_ids.iter().step_by(2) {
+            page.delete_tuple(*slot_id).unwrap();
+        }
+        page.update_tuple(slot_ids[1], &[0xAA; 40]).unwrap();
+        let free_space_before = page.get_header().free_space_total;
+
+        page.compact();
+
+        let header = page.get_header();
+        assert_eq!(header.free_space_total, free_space_before);
+        assert_eq!(header.free_space_total, header.offset_end_free_space - header.offset_begin_free_space);
+        assert_eq!(page.get_data(slot_ids[1]).unwrap(), &[0xAA; 40]);
+        for (i, slot_id) in slot_ids.iter().enumerate().skip(3).step_by(2) {
+            assert_eq!(page.get_data(*slot_id).unwrap(), &[i as u8; 100]);
+        }
+        for slot_id in slot_ids.iter().step_by(2) {
+            assert_eq!(page.get_data(*slot_id).unwrap_err(), PageError::TupleNotFound);
+        }
+    }
+
+    #[test]
+    fn test_compact_drops_trailing_deleted_slots() {
+        let mut page = Page::new(1);
+        let first = page.insert_tuple(b"first").unwrap();
+        let second = page.insert_tuple(b"second").unwrap();
+        let third = page.insert_tuple(b"third").unwrap();
+        page.delete_tuple(second).unwrap();
+        page.delete_tuple(third).unwrap();
+
+        page.compact();
+
+        let header = page.get_header();
+        assert_eq!(header.offset_begin_free_space, (HEADER_SIZE + SLOT_SIZE) as u16);
+        assert_eq!(header.free_space_total, (DEFAULT_PAGE_SIZE - HEADER_SIZE - SLOT_SIZE - 5) as u16);
+        assert_eq!(page.get_data(first).unwrap(), b"first");
+    }
+
+    #[test]
+    fn test_insert_compacts_fragmented_space() {
+        let mut page = Page::new(1);
+        let mut slot_ids = Vec::new();
+        while let Ok(slot_id) = page.insert_tuple(&[slot_ids.len() as u8; 200]) {
+            slot_ids.push(slot_id);
+        }
+
+        // free every other tuple -- plenty of space in total, but no 300 byte gap anywhere
+        for slot_id in slot_ids.iter().step_by(2) {
+            page.delete_tuple(*slot_id).unwrap();
+        }
+        let header = page.get_header();
+        assert!(header.offset_end_free_space - header.offset_begin_free_space < 304);
+        assert!(header.free_space_total >= 304);
+
+        let new_slot_id = page.insert_tuple(&[0xEE; 300]).expect("insert should compact the page");
+
+        assert_eq!(page.get_data(new_slot_id).unwrap(), &[0xEE; 300]);
+        for (i, slot_id) in slot_ids.iter().enumerate().skip(1).step_by(2) {
+            assert_eq!(page.get_data(*slot_id).unwrap(), &[i as u8; 200]);
+        }
+    }
+
+    #[test]
+    fn test_update_compacts_fragmented_space() {
+        let mut page = Page::new(1);
+        let mut slot_ids = Vec::new();
+        while let Ok(slot_id) = page.insert_tuple(&[slot_ids.len() as u8; 200]) {
+            slot_ids.push(slot_id);
+        }
+        page.delete_tuple(slot_ids[0]).unwrap();
+        page.delete_tuple(slot_ids[2]).unwrap();
+
+        // growing a tuple by 150 bytes only fits once the two holes are merged
+        page.update_tuple(slot_ids[1], &[0xBB; 350]).expect("update should compact the page");
+
+        assert_eq!(page.get_data(slot_ids[1]).unwrap(), &[0xBB; 350]);
+        assert_eq!(page.get_data(slot_ids[3]).unwrap(), &[3u8; 200]);
+    }
+
+    #[test]
+    fn test_lsn_round_trips_and_is_checksummed() {
+        let mut page = Page::new(1);
+        assert_eq!(page.lsn(), INVALID_LSN);
+
+        page.set_lsn(0x0102_0304_0506_0708);
+        assert_eq!(page.lsn(), 0x0102_0304_0506_0708);
+        assert_eq!(page.get_header().lsn, 0x0102_0304_0506_0708);
+        // the LSN lives in the header, which the checksum covers
+        assert_eq!(page.verify_checksum(), Err(PageError::ChecksumMismatch));
+        page.update_checksum();
+        assert!(page.verify_checksum().is_ok());
+    }
+
+    #[test]
+    fn test_insert_tuple_at_specific_slots() {
+        let mut page = Page::new(1);
+        let first = page.insert_tuple(b"first").unwrap();
+        page.delete_tuple(first).unwrap();
+
+        // refill a deleted slot, then skip ahead past the end of the slot array
+        page.insert_tuple_at(first, b"first again").unwrap();
+        page.insert_tuple_at(3, b"fourth").unwrap();
+
+        assert_eq!(page.get_data(first).unwrap(), b"first again");
+        assert_eq!(page.get_data(3).unwrap(), b"fourth");
+        // the slots skipped over are tombstones
+        assert_eq!(page.get_data(1), Err(PageError::TupleNotFound));
+        assert_eq!(page.get_data(2), Err(PageError::TupleNotFound));
+        assert_eq!(page.insert_tuple_at(3, b"taken"), Err(PageError::InvalidSlot));
+
+        // 2 tuples plus 4 slots taken out of the free space
+        let expected_free_space = DEFAULT_PAGE_SIZE - HEADER_SIZE - b"first again".len() - b"fourth".len() - 4 * SLOT_SIZE;
+        assert_eq!(page.get_header().free_space_total as usize, expected_free_space);
+        // the skipped slots are free for the taking
+        assert_eq!(page.insert_tuple(b"next").unwrap(), 1);
+    }
+
+    #[test]
+    fn test_insert_reuses_deleted_slots() {
+        let mut page = Page::new(1);
+        let slot_ids: Vec<u16> = (0..4u8).map(|i| page.insert_tuple(&[i; 10]).unwrap()).collect();
+        page.delete_tuple(slot_ids[2]).unwrap();
+        page.delete_tuple(slot_ids[1]).unwrap();
+        let free_space_before = page.get_header().free_space_total;
+
+        // lowest free slot first, and no new slot array entry is charged
+        assert_eq!(page.insert_tuple(b"reused").unwrap(), 1);
+        assert_eq!(page.get_header().free_space_total as usize, free_space_before as usize - b"reused".len());
+        assert_eq!(page.insert_tuple(b"reused too").unwrap(), 2);
+        assert_eq!(page.slot_count(), 4);
+
+        // only once every slot is taken does the slot array grow
+        assert_eq!(page.insert_tuple(b"new").unwrap(), 4);
+        assert_eq!(page.get_data(1).unwrap(), b"reused");
+        assert_eq!(page.get_data(2).unwrap(), b"reused too");
+        assert_eq!(page.get_data(3).unwrap(), &[3u8; 10]);
+    }
+
+    #[test]
+    fn test_iter_skips_deleted_tuples() {
+        let mut page = Page::new(1);
+        assert_eq!(page.iter().count(), 0);
+
+        let slot_ids: Vec<u16> = (0..5u8).map(|i| page.insert_tuple(&[i; 3]).unwrap()).collect();
+        page.delete_tuple(slot_ids[0]).unwrap();
+        page.delete_tuple(slot_ids[2]).unwrap();
+        page.delete_tuple(slot_ids[4]).unwrap();
+
+        let tuples: Vec<(u16, &[u8])> = page.iter().collect();
+        assert_eq!(tuples, vec![(slot_ids[1], &[1u8; 3][..]), (slot_ids[3], &[3u8; 3][..])]);
+        assert_eq!((&page).into_iter().count(), 2);
+    }
+
+    #[test]
+    fn test_delete_insert_churn_keeps_slot_array_bounded() {
+        let mut page = Page::new(1);
+        let mut slot_ids: Vec<u16> = (0..8u8).map(|i| page.insert_tuple(&[i; 50]).unwrap()).collect();
+        for round in 0..1000usize {
+            let victim = slot_ids.remove(round % slot_ids.len());
+            page.delete_tuple(victim).unwrap();
+            slot_ids.push(page.insert_tuple(&[round as u8; 50]).unwrap());
+        }
+        assert_eq!(page.slot_count(), 8);
+    }
+}
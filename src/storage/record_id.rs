@@ -0,0 +1,101 @@
+/// Stable address of a tuple: the page it lives on and its slot within that page.
+///
+/// Record ids are what indexes store to point back at heap tuples, so they have a
+/// fixed-size serialized form (`RecordId::SIZE` bytes, little endian page id
+/// followed by slot id). They order by page id first and slot id second, which is
+/// also the order a sequential scan visits them in.
+///
+/// # Examples
+///
+/// ```
+/// use gondor_rdbms::storage::RecordId;
+///
+/// let rid = RecordId::new(7, 3);
+/// let bytes = rid.to_bytes();
+/// assert_eq!(RecordId::from_bytes(&bytes), Some(rid));
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct RecordId {
+    pub page_id: u32,
+    pub slot_id: u16,
+}
+
+impl RecordId {
+    /// size of a serialized record id in bytes
+    pub const SIZE: usize = 6;
+
+    pub fn new(page_id: u32, slot_id: u16) -> Self {
+        Self { page_id, slot_id }
+    }
+
+    pub fn to_bytes(&self) -> [u8; Self::SIZE] {
+        let mut bytes = [0u8; Self::SIZE];
+        bytes[0..4].copy_from_slice(&self.page_id.to_le_bytes());
+        bytes[4..6].copy_from_slice(&self.slot_id.to_le_bytes());
+        bytes
+    }
+
+    /// Reads a record id from the first `RecordId::SIZE` bytes, or `None` if there aren't enough.
+    pub fn from_bytes(bytes: &[u8]) -> Option<Self> {
+        if bytes.len() < Self::SIZE {
+            return None;
+        }
+
+        let page_id = u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+        let slot_id = u16::from_le_bytes([bytes[4], bytes[5]]);
+        Some(Self { page_id, slot_id })
+    }
+}
+
+impl std::fmt::Display for RecordId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "({}, {})", self.page_id, self.slot_id)
+    }
+}
+
+impl From<(u32, u16)> for RecordId {
+    fn from((page_id, slot_id): (u32, u16)) -> Self {
+        Self { page_id, slot_id }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trip_bytes() {
+        let rid = RecordId::new(0xDEAD_BEEF, 0x1234);
+        let bytes = rid.to_bytes();
+        assert_eq!(bytes, [0xEF, 0xBE, 0xAD, 0xDE, 0x34, 0x12]);
+        assert_eq!(RecordId::from_bytes(&bytes), Some(rid));
+    }
+
+    #[test]
+    fn test_from_bytes_too_short() {
+        assert_eq!(RecordId::from_bytes(&[1, 2, 3, 4, 5]), None);
+    }
+
+    #[test]
+    fn test_ordering_is_page_then_slot() {
+        let mut rids = vec![
+            RecordId::new(2, 0),
+            RecordId::new(1, 5),
+            RecordId::new(1, 2),
+            RecordId::new(0, 9),
+        ];
+        rids.sort();
+        assert_eq!(rids, vec![
+            RecordId::new(0, 9),
+            RecordId::new(1, 2),
+            RecordId::new(1, 5),
+            RecordId::new(2, 0),
+        ]);
+    }
+
+    #[test]
+    fn test_display() {
+        assert_eq!(RecordId::new(3, 14).to_string(), "(3, 14)");
+        assert_eq!(RecordId::from((3, 14)), RecordId::new(3, 14));
+    }
+}
@@ -0,0 +1,267 @@
+use std::collections::{BTreeMap, HashMap, VecDeque};
+
+/// Decides which resident page the buffer pool evicts when it needs room for
+/// another one.
+///
+/// The pool tells the policy about every page that becomes resident
+/// (`record_admission`), every later access to it (`record_access`) and every
+/// page that leaves the pool (`remove`). Keeping admissions apart from accesses
+/// is what scan-resistant policies hook into: a page read once by a sequential
+/// scan can be treated differently from one that keeps being used.
+///
+/// # Examples
+///
+/// ```
+/// use gondor_rdbms::storage::{BufferPool, ClockPolicy, DiskManager};
+///
+/// let dir = tempfile::tempdir().unwrap();
+/// let disk_manager = DiskManager::open(dir.path().join("example.db")).unwrap();
+/// let mut buffer_pool = BufferPool::with_capacity(disk_manager, 2).with_replacement_policy(ClockPolicy::default());
+///
+/// let first = buffer_pool.new_page().unwrap();
+/// buffer_pool.new_page().unwrap();
+/// // the pool is full, so the clock picks a page to write back and evict
+/// buffer_pool.new_page().unwrap();
+/// assert!(buffer_pool.get_page(first).is_ok());
+/// ```
+pub trait ReplacementPolicy: Send {
+    /// `page_id` was accessed while resident.
+    fn record_access(&mut self, page_id: u32);
+
+    /// `page_id` just became resident, read from disk or freshly allocated.
+    /// Counts as an access unless the policy says otherwise.
+    fn record_admission(&mut self, page_id: u32) {
+        self.record_access(page_id);
+    }
+
+    /// `page_id` is no longer resident, so the policy can forget it.
+    fn remove(&mut self, page_id: u32);
+
+    /// Picks the page to evict among the resident pages for which `can_evict`
+    /// holds (the pool passes one that rules out pinned pages), or `None` if
+    /// there is no such page. The victim stays tracked until it's `remove`d.
+    fn victim(&mut self, can_evict: &dyn Fn(u32) -> bool) -> Option<u32>;
+}
+
+/// Evicts the least recently used page. This is the pool's default policy.
+#[derive(Debug, Default)]
+pub struct LruPolicy {
+    /// logical clock, bumped on every access
+    tick: u64,
+    /// resident page ids keyed by the tick of their last access, least recent first
+    by_recency: BTreeMap<u64, u32>,
+    last_access: HashMap<u32, u64>,
+}
+
+impl ReplacementPolicy for LruPolicy {
+    fn record_access(&mut self, page_id: u32) {
+        self.remove(page_id);
+        self.tick += 1;
+        self.by_recency.insert(self.tick, page_id);
+        self.last_access.insert(page_id, self.tick);
+    }
+
+    fn remove(&mut self, page_id: u32) {
+        if let Some(tick) = self.last_access.remove(&page_id) {
+            self.by_recency.remove(&tick);
+        }
+    }
+
+    fn victim(&mut self, can_evict: &dyn Fn(u32) -> bool) -> Option<u32> {
+        self.by_recency.values().copied().find(|&page_id| can_evict(page_id))
+    }
+}
+
+/// Approximates LRU with a reference bit per page and a hand sweeping over the
+/// pages in a circle, the classic second-chance algorithm.
+///
+/// An access only sets the page's reference bit, so it's cheaper than keeping
+/// exact recency. The hand clears set bits as it passes and evicts the first
+/// page it finds with the bit already clear.
+#[derive(Debug, Default)]
+pub struct ClockPolicy {
+    /// the circle of pages with their reference bits, `None` where a page was removed
+    frames: Vec<Option<(u32, bool)>>,
+    /// position of each tracked page in `frames`
+    positions: HashMap<u32, usize>,
+    /// removed positions, reused before the circle grows
+    free: Vec<usize>,
+    hand: usize,
+}
+
+impl ReplacementPolicy for ClockPolicy {
+    fn record_access(&mut self, page_id: u32) {
+        if let Some(&position) = self.positions.get(&page_id) {
+            self.frames[position] = Some((page_id, true));
+            return;
+        }
+
+        let position = match self.free.pop() {
+            Some(position) => position,
+            None => {
+                self.frames.push(None);
+                self.frames.len() - 1
+            }
+        };
+        self.frames[position] = Some((page_id, true));
+        self.positions.insert(page_id, position);
+    }
+
+    fn remove(&mut self, page_id: u32) {
+        if let Some(position) = self.positions.remove(&page_id) {
+            self.frames[position] = None;
+            self.free.push(position);
+        }
+    }
+
+    fn victim(&mut self, can_evict: &dyn Fn(u32) -> bool) -> Option<u32> {
+        // two full turns are enough: the first clears every reference bit it passes
+        for _ in 0..2 * self.frames.len() {
+            let position = self.hand;
+            self.hand = (self.hand + 1) % self.frames.len();
+
+            let Some((page_id, referenced)) = &mut self.frames[position] else {
+                continue;
+            };
+            if !can_evict(*page_id) {
+                continue;
+            }
+            if *referenced {
+                *referenced = false;
+            } else {
+                // leave the hand just past the victim, where the next sweep picks up
+                return Some(*page_id);
+            }
+        }
+        None
+    }
+}
+
+/// Evicts the page whose k-th most recent access is furthest in the past
+/// (O'Neil et al., "The LRU-K Page Replacement Algorithm").
+///
+/// Pages accessed fewer than k times count as infinitely far back and go
+/// first, least recently used among them. That makes the policy resistant to
+/// sequential scans: pages a scan touches once are evicted before pages that
+/// are used over and over. History is dropped when a page leaves the pool.
+#[derive(Debug)]
+pub struct LruKPolicy {
+    k: usize,
+    /// logical clock, bumped on every access
+    tick: u64,
+    /// ticks of the last (up to) k accesses of each resident page, most recent last
+    history: HashMap<u32, VecDeque<u64>>,
+}
+
+impl LruKPolicy {
+    /// # Panics
+    ///
+    /// If `k` is zero.
+    pub fn new(k: usize) -> Self {
+        assert!(k > 0, "LRU-K needs k of at least 1");
+        Self {
+            k,
+            tick: 0,
+            history: HashMap::new(),
+        }
+    }
+}
+
+impl ReplacementPolicy for LruKPolicy {
+    fn record_access(&mut self, page_id: u32) {
+        self.tick += 1;
+        let history = self.history.entry(page_id).or_default();
+        if history.len() == self.k {
+            history.pop_front();
+        }
+        history.push_back(self.tick);
+    }
+
+    fn remove(&mut self, page_id: u32) {
+        self.history.remove(&page_id);
+    }
+
+    fn victim(&mut self, can_evict: &dyn Fn(u32) -> bool) -> Option<u32> {
+        self.history
+            .iter()
+            .filter(|&(&page_id, _)| can_evict(page_id))
+            .min_by_key(|&(_, history)| {
+                // pages without k accesses sort first, oldest last access among them
+                let has_k_accesses = history.len() == self.k;
+                let key_tick = if has_k_accesses { history[0] } else { *history.back().unwrap() };
+                (has_k_accesses, key_tick)
+            })
+            .map(|(&page_id, _)| page_id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn anything(_: u32) -> bool {
+        true
+    }
+
+    #[test]
+    fn test_lru_evicts_least_recently_used() {
+        let mut policy = LruPolicy::default();
+        for page_id in 0..3 {
+            policy.record_admission(page_id);
+        }
+        policy.record_access(0);
+        assert_eq!(policy.victim(&anything), Some(1));
+
+        policy.remove(1);
+        assert_eq!(policy.victim(&anything), Some(2));
+        assert_eq!(policy.victim(&|page_id| page_id != 2), Some(0));
+    }
+
+    #[test]
+    fn test_clock_gives_referenced_pages_a_second_chance() {
+        let mut policy = ClockPolicy::default();
+        for page_id in 0..3 {
+            policy.record_admission(page_id);
+        }
+
+        // every bit is set, so the hand clears them all and comes back round to page 0
+        assert_eq!(policy.victim(&anything), Some(0));
+        policy.remove(0);
+
+        // page 1 is referenced again, so page 2 goes before it
+        policy.record_access(1);
+        assert_eq!(policy.victim(&anything), Some(2));
+        policy.remove(2);
+
+        // the removed positions are reused, page 4 taking page 0's right under the hand
+        policy.record_admission(3);
+        policy.record_admission(4);
+        assert_eq!(policy.frames.len(), 3);
+        assert_eq!(policy.victim(&|page_id| page_id != 1), Some(4));
+        assert_eq!(policy.victim(&|_| false), None);
+    }
+
+    #[test]
+    fn test_lru_k_evicts_pages_without_k_accesses_first() {
+        let mut policy = LruKPolicy::new(2);
+        policy.record_admission(0);
+        policy.record_access(0);
+        policy.record_admission(1);
+        policy.record_access(1);
+        // a scan touches pages 2 and 3 once each
+        policy.record_admission(2);
+        policy.record_admission(3);
+
+        assert_eq!(policy.victim(&anything), Some(2));
+        policy.remove(2);
+        assert_eq!(policy.victim(&anything), Some(3));
+        policy.remove(3);
+
+        // with k accesses each, the older second-to-last access loses
+        assert_eq!(policy.victim(&anything), Some(0));
+        policy.record_access(0);
+        policy.record_access(0);
+        assert_eq!(policy.victim(&anything), Some(1));
+        assert_eq!(policy.victim(&|page_id| page_id == 0), Some(0));
+    }
+}
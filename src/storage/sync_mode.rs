@@ -0,0 +1,50 @@
+use std::fs::{File, OpenOptions};
+
+/// How the database file and the write-ahead log are forced to stable storage.
+///
+/// Everything but `Off` makes a flushed log record or synced page survive a
+/// power failure; they differ in how much they cost. `Off` skips forcing
+/// altogether, so a crash of the machine (not just the process) can lose
+/// committed transactions or leave the files inconsistent. It's meant for tests
+/// and bulk loads that can simply be redone.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SyncMode {
+    /// `fsync`: the data and all of the file's metadata
+    Fsync,
+    /// `fdatasync`: the data and only the metadata needed to read it back,
+    /// e.g. the file length but not the modification time
+    #[default]
+    Fdatasync,
+    /// open the files with `O_DSYNC`, so every write returns only once it is on
+    /// stable storage and there's nothing left to force afterwards. Same as
+    /// `Fdatasync` on platforms without `O_DSYNC`
+    ODsync,
+    /// never force anything
+    Off,
+}
+
+impl SyncMode {
+    /// Sets the flags a file written under this mode has to be opened with.
+    pub(crate) fn apply(self, options: &mut OpenOptions) -> &mut OpenOptions {
+        #[cfg(unix)]
+        if self == SyncMode::ODsync {
+            use std::os::unix::fs::OpenOptionsExt;
+            options.custom_flags(libc::O_DSYNC);
+        }
+        options
+    }
+
+    /// Forces what was written to `file` so far to stable storage, as far as the mode asks for.
+    pub(crate) fn sync(self, file: &File) -> std::io::Result<()> {
+        match self {
+            SyncMode::Fsync => file.sync_all(),
+            SyncMode::Fdatasync => file.sync_data(),
+            // every write was synchronous already
+            #[cfg(unix)]
+            SyncMode::ODsync => Ok(()),
+            #[cfg(not(unix))]
+            SyncMode::ODsync => file.sync_data(),
+            SyncMode::Off => Ok(()),
+        }
+    }
+}
@@ -0,0 +1,271 @@
+use crate::recovery::{self, RecoveryError};
+use crate::storage::BufferPool;
+use crate::wal::{GroupCommit, INVALID_LSN, LogManager, LogRecordBody, Lsn, TxnId, WalError};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// How much a transaction sees of the transactions that run alongside it.
+///
+/// `READ UNCOMMITTED` isn't one of them: like PostgreSQL, it's taken to mean
+/// `ReadCommitted`, which never shows uncommitted changes either.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum IsolationLevel {
+    /// each statement sees what was committed when it started
+    #[default]
+    ReadCommitted,
+    /// every statement sees what was committed when the first one started
+    RepeatableRead,
+    /// like `RepeatableRead`, and the outcome is that of running the
+    /// transactions one after another
+    Serializable,
+}
+
+impl std::fmt::Display for IsolationLevel {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            IsolationLevel::ReadCommitted => write!(f, "READ COMMITTED"),
+            IsolationLevel::RepeatableRead => write!(f, "REPEATABLE READ"),
+            IsolationLevel::Serializable => write!(f, "SERIALIZABLE"),
+        }
+    }
+}
+
+/// Handle for a running transaction.
+///
+/// Carries the transaction's id and the LSN of the last log record it wrote,
+/// so each new record can be chained to the previous one. Storage structures
+/// that log their changes (e.g. `HeapFile`) take a `&mut Transaction` and
+/// advance it as they append records.
+///
+/// It also carries its isolation level, which is up to whatever runs
+/// transactions side by side to keep to: a `Database` does so for the
+/// transaction blocks of its connections.
+#[derive(Debug)]
+pub struct Transaction {
+    id: TxnId,
+    prev_lsn: Lsn,
+    isolation_level: IsolationLevel,
+}
+
+impl Transaction {
+    /// Creates a handle for transaction `id` that hasn't logged anything yet,
+    /// at the default isolation level.
+    pub fn new(id: TxnId) -> Self {
+        Self { id, prev_lsn: INVALID_LSN, isolation_level: IsolationLevel::default() }
+    }
+
+    pub fn id(&self) -> TxnId {
+        self.id
+    }
+
+    /// LSN of the last record this transaction logged, `INVALID_LSN` if none.
+    pub fn prev_lsn(&self) -> Lsn {
+        self.prev_lsn
+    }
+
+    pub fn isolation_level(&self) -> IsolationLevel {
+        self.isolation_level
+    }
+
+    pub fn set_isolation_level(&mut self, isolation_level: IsolationLevel) {
+        self.isolation_level = isolation_level;
+    }
+
+    /// Appends a record for this transaction and chains it after the previous one.
+    pub fn log(&mut self, log_manager: &mut LogManager, body: LogRecordBody) -> Lsn {
+        let lsn = log_manager.append(self.id, self.prev_lsn, body);
+        self.prev_lsn = lsn;
+        lsn
+    }
+}
+
+/// Starts, commits and rolls back transactions against a buffer pool and its log.
+///
+/// Committing forces the commit record to disk, so a committed transaction
+/// survives a crash even if none of its pages were written. The flush goes
+/// through a `GroupCommit`, so transactions committing at the same time share
+/// one. Aborting undoes the transaction's changes by walking its log records
+/// backwards.
+///
+/// # Examples
+///
+/// ```
+/// use gondor_rdbms::storage::{BufferPool, DiskManager, HeapFile};
+/// use gondor_rdbms::transaction::TransactionManager;
+/// use gondor_rdbms::wal::LogManager;
+/// use std::sync::{Arc, Mutex};
+///
+/// let dir = tempfile::tempdir().unwrap();
+/// let disk_manager = DiskManager::open(dir.path().join("example.db")).unwrap();
+/// let log_manager = Arc::new(Mutex::new(LogManager::open(dir.path().join("example.wal")).unwrap()));
+/// let buffer_pool = Arc::new(Mutex::new(BufferPool::new(disk_manager)));
+///
+/// let mut heap = HeapFile::create(buffer_pool.clone()).unwrap();
+/// heap.set_log_manager(log_manager.clone());
+/// let mut transaction_manager = TransactionManager::new(buffer_pool, log_manager);
+///
+/// let mut txn = transaction_manager.begin();
+/// let rid = heap.insert(&mut txn, b"Hello, world!").unwrap();
+/// transaction_manager.abort(txn).unwrap();
+/// assert!(heap.get(rid).is_err());
+/// ```
+pub struct TransactionManager {
+    buffer_pool: Arc<Mutex<BufferPool>>,
+    log_manager: Arc<Mutex<LogManager>>,
+    /// batches the log flushes of concurrent commits
+    group_commit: Arc<GroupCommit>,
+    /// id handed to the next transaction
+    next_txn_id: TxnId,
+}
+
+impl TransactionManager {
+    /// Creates a transaction manager whose transaction ids continue after the
+    /// highest id the log has seen, even if the log has since been truncated.
+    /// Commits don't wait for others to share their flush until a window is set
+    /// with `set_group_commit`.
+    pub fn new(buffer_pool: Arc<Mutex<BufferPool>>, log_manager: Arc<Mutex<LogManager>>) -> Self {
+        let max_txn_id = log_manager.lock().unwrap().max_txn_id();
+        Self {
+            buffer_pool,
+            group_commit: Arc::new(GroupCommit::new(log_manager.clone(), Duration::ZERO)),
+            log_manager,
+            next_txn_id: max_txn_id + 1,
+        }
+    }
+
+    /// Flushes commit records through `group_commit` instead, which has to be
+    /// over the same log.
+    pub fn set_group_commit(&mut self, group_commit: Arc<GroupCommit>) {
+        self.group_commit = group_commit;
+    }
+
+    pub fn group_commit(&self) -> &Arc<GroupCommit> {
+        &self.group_commit
+    }
+
+    pub fn begin(&mut self) -> Transaction {
+        self.begin_with(IsolationLevel::default())
+    }
+
+    /// Starts a transaction at `isolation_level`.
+    pub fn begin_with(&mut self, isolation_level: IsolationLevel) -> Transaction {
+        let mut txn = Transaction::new(self.next_txn_id);
+        txn.set_isolation_level(isolation_level);
+        self.next_txn_id += 1;
+        txn.log(&mut self.log_manager.lock().unwrap(), LogRecordBody::Begin);
+        txn
+    }
+
+    /// Commits `txn`, returning once its commit record is on stable storage.
+    pub fn commit(&mut self, txn: Transaction) -> Result<(), WalError> {
+        let lsn = self.append_commit(txn);
+        self.group_commit.wait_for(lsn)
+    }
+
+    /// Logs `txn`'s commit record without waiting for it to reach the disk, and
+    /// returns its LSN. The transaction only counts as committed once
+    /// `group_commit().wait_for` that LSN has returned, which can happen after
+    /// whatever serializes transactions has moved on to the next one.
+    pub fn append_commit(&mut self, mut txn: Transaction) -> Lsn {
+        txn.log(&mut self.log_manager.lock().unwrap(), LogRecordBody::Commit)
+    }
+
+    /// Rolls back every change `txn` made and logs that it aborted.
+    pub fn abort(&mut self, txn: Transaction) -> Result<(), RecoveryError> {
+        recovery::rollback(&self.buffer_pool, &self.log_manager, txn.id(), txn.prev_lsn())
+    }
+
+    /// Rolls back the changes `txn` made since `savepoint`, its `prev_lsn` at the
+    /// time, and leaves it running. Savepoints set since then can't be rolled
+    /// back to anymore, but earlier ones (and the whole transaction) still can.
+    pub fn rollback_to(&mut self, txn: &mut Transaction, savepoint: Lsn) -> Result<(), RecoveryError> {
+        txn.prev_lsn = recovery::rollback_to(&self.buffer_pool, &self.log_manager, txn.id, txn.prev_lsn, savepoint)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::{DiskManager, HeapFile};
+    use tempfile::TempDir;
+
+    fn open(dir: &TempDir) -> TransactionManager {
+        let disk_manager = DiskManager::open(dir.path().join("test.db")).unwrap();
+        let log_manager = Arc::new(Mutex::new(LogManager::open(dir.path().join("test.wal")).unwrap()));
+        TransactionManager::new(Arc::new(Mutex::new(BufferPool::new(disk_manager))), log_manager)
+    }
+
+    #[test]
+    fn test_commit_forces_log() {
+        let dir = TempDir::new().unwrap();
+        let mut transaction_manager = open(&dir);
+
+        let txn = transaction_manager.begin();
+        assert_eq!(transaction_manager.log_manager.lock().unwrap().flushed_lsn(), INVALID_LSN);
+        let begin_lsn = txn.prev_lsn();
+        transaction_manager.commit(txn).unwrap();
+
+        let log_manager = transaction_manager.log_manager.lock().unwrap();
+        assert_eq!(log_manager.flushed_lsn(), begin_lsn + 1);
+        let records = log_manager.read_records().unwrap();
+        assert_eq!(records[1].body, LogRecordBody::Commit);
+        assert_eq!(records[1].prev_lsn, begin_lsn);
+    }
+
+    #[test]
+    fn test_rollback_to_savepoint() {
+        let dir = TempDir::new().unwrap();
+        let mut transaction_manager = open(&dir);
+        let mut heap = HeapFile::create(transaction_manager.buffer_pool.clone()).unwrap();
+        heap.set_log_manager(transaction_manager.log_manager.clone());
+
+        let mut txn = transaction_manager.begin();
+        let kept = heap.insert(&mut txn, b"kept").unwrap();
+        let outer = txn.prev_lsn();
+        let changed = heap.insert(&mut txn, b"changed back").unwrap();
+        let inner = txn.prev_lsn();
+        heap.update(&mut txn, changed, b"changed").unwrap();
+        let lost = heap.insert(&mut txn, b"lost").unwrap();
+
+        transaction_manager.rollback_to(&mut txn, inner).unwrap();
+        assert_eq!(heap.get(changed).unwrap(), b"changed back");
+        assert!(heap.get(lost).is_err());
+        // nothing to undo the second time
+        let last_lsn = txn.prev_lsn();
+        transaction_manager.rollback_to(&mut txn, inner).unwrap();
+        assert_eq!(txn.prev_lsn(), last_lsn);
+
+        // the transaction goes on, and an earlier savepoint skips what was already undone
+        let again = heap.insert(&mut txn, b"again").unwrap();
+        transaction_manager.rollback_to(&mut txn, outer).unwrap();
+        assert!(heap.get(changed).is_err() && heap.get(again).is_err());
+        assert_eq!(heap.get(kept).unwrap(), b"kept");
+        let compensations = |transaction_manager: &TransactionManager| {
+            let mut log_manager = transaction_manager.log_manager.lock().unwrap();
+            log_manager.flush().unwrap();
+            log_manager.read_records().unwrap().iter().filter(|record| matches!(record.body, LogRecordBody::Compensation { .. })).count()
+        };
+        assert_eq!(compensations(&transaction_manager), 5);
+
+        // and rolling back the rest only undoes the first insert
+        transaction_manager.abort(txn).unwrap();
+        assert!(heap.get(kept).is_err());
+        assert_eq!(compensations(&transaction_manager), 6);
+    }
+
+    #[test]
+    fn test_txn_ids_continue_after_restart() {
+        let dir = TempDir::new().unwrap();
+        {
+            let mut transaction_manager = open(&dir);
+            let first = transaction_manager.begin();
+            let second = transaction_manager.begin();
+            assert_eq!((first.id(), second.id()), (1, 2));
+            transaction_manager.commit(second).unwrap();
+        }
+
+        let mut transaction_manager = open(&dir);
+        assert_eq!(transaction_manager.begin().id(), 3);
+    }
+}
@@ -0,0 +1,97 @@
+/// Column types supported by the engine.
+///
+/// # Examples
+///
+/// ```
+/// use gondor_rdbms::types::DataType;
+///
+/// assert_eq!(DataType::from_name("integer"), Some(DataType::Int));
+/// assert_eq!(DataType::Varchar(Some(20)).to_string(), "VARCHAR(20)");
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum DataType {
+    /// 32-bit signed integer
+    Int,
+    /// 64-bit signed integer
+    BigInt,
+    /// variable length UTF-8 string, with an optional maximum length in characters
+    Varchar(Option<u32>),
+    Bool,
+    /// exact decimal number, with an optional precision and scale: at most
+    /// `precision` digits, `scale` of them after the point
+    Numeric(Option<(u8, u8)>),
+    /// calendar date
+    Date,
+    /// time of day, to the microsecond
+    Time,
+    /// date and time of day, to the microsecond, without a time zone
+    Timestamp,
+    /// span of time in months, days and microseconds
+    Interval,
+}
+
+impl DataType {
+    /// Looks up a type by its SQL name, ignoring case. Types that take parameters
+    /// (like `VARCHAR(n)` and `NUMERIC(p, s)`) are returned without them.
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_ascii_uppercase().as_str() {
+            "INT" | "INTEGER" | "INT4" => Some(DataType::Int),
+            "BIGINT" | "INT8" => Some(DataType::BigInt),
+            "VARCHAR" | "TEXT" => Some(DataType::Varchar(None)),
+            "BOOL" | "BOOLEAN" => Some(DataType::Bool),
+            "NUMERIC" | "DECIMAL" => Some(DataType::Numeric(None)),
+            "DATE" => Some(DataType::Date),
+            "TIME" => Some(DataType::Time),
+            "TIMESTAMP" => Some(DataType::Timestamp),
+            "INTERVAL" => Some(DataType::Interval),
+            _ => None,
+        }
+    }
+}
+
+impl std::fmt::Display for DataType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DataType::Int => write!(f, "INT"),
+            DataType::BigInt => write!(f, "BIGINT"),
+            DataType::Varchar(Some(length)) => write!(f, "VARCHAR({length})"),
+            DataType::Varchar(None) => write!(f, "VARCHAR"),
+            DataType::Bool => write!(f, "BOOL"),
+            DataType::Numeric(Some((precision, scale))) => write!(f, "NUMERIC({precision},{scale})"),
+            DataType::Numeric(None) => write!(f, "NUMERIC"),
+            DataType::Date => write!(f, "DATE"),
+            DataType::Time => write!(f, "TIME"),
+            DataType::Timestamp => write!(f, "TIMESTAMP"),
+            DataType::Interval => write!(f, "INTERVAL"),
+        }
+    }
+}
+
+/// A named, typed column of a table.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Column {
+    pub name: String,
+    pub data_type: DataType,
+}
+
+impl Column {
+    pub fn new(name: &str, data_type: DataType) -> Self {
+        Self { name: name.to_string(), data_type }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_names_round_trip_through_display() {
+        for data_type in [DataType::Int, DataType::BigInt, DataType::Varchar(None), DataType::Bool, DataType::Numeric(None), DataType::Date, DataType::Time, DataType::Timestamp, DataType::Interval] {
+            assert_eq!(DataType::from_name(&data_type.to_string()), Some(data_type));
+        }
+        assert_eq!(DataType::from_name("Boolean"), Some(DataType::Bool));
+        assert_eq!(DataType::from_name("decimal"), Some(DataType::Numeric(None)));
+        assert_eq!(DataType::Numeric(Some((10, 2))).to_string(), "NUMERIC(10,2)");
+        assert_eq!(DataType::from_name("float"), None);
+    }
+}
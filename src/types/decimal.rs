@@ -0,0 +1,350 @@
+use std::cmp::Ordering;
+use std::hash::{Hash, Hasher};
+
+/// An exact decimal number, the value of a `NUMERIC` column.
+///
+/// It's an integer `mantissa` of up to 38 digits, shifted `scale` places to the
+/// right of the decimal point: 12.50 is 1250 with a scale of 2. The scale is
+/// kept through arithmetic, like PostgreSQL does, so `1.50 + 1` is `2.50`, but
+/// numbers of different scales with the same value are equal.
+///
+/// Adding, subtracting and multiplying are exact, failing rather than rounding
+/// when the result doesn't fit in 38 digits. Division rounds half away from
+/// zero to `DIVISION_SCALE` places, or the scale of either operand if that's
+/// more.
+///
+/// # Examples
+///
+/// ```
+/// use gondor_rdbms::types::Decimal;
+///
+/// let price = Decimal::parse("19.99").unwrap();
+/// let total = price.checked_mul(Decimal::from(3)).unwrap();
+/// assert_eq!(total.to_string(), "59.97");
+/// assert_eq!(total, Decimal::parse("59.9700").unwrap());
+/// assert_eq!(Decimal::parse("0.125").unwrap().rescale(2).unwrap().to_string(), "0.13");
+/// ```
+#[derive(Debug, Clone, Copy)]
+pub struct Decimal {
+    mantissa: i128,
+    scale: u8,
+}
+
+impl Decimal {
+    /// Most digits a decimal can have, and so the most places it can have after the point.
+    pub const MAX_PRECISION: u8 = 38;
+    /// Places a quotient has at least.
+    pub const DIVISION_SCALE: u8 = 16;
+
+    /// `mantissa` shifted `scale` places to the right of the decimal point, `None`
+    /// if either is out of range.
+    pub fn new(mantissa: i128, scale: u8) -> Option<Self> {
+        (scale <= Self::MAX_PRECISION && mantissa.unsigned_abs() < pow10(Self::MAX_PRECISION) as u128).then_some(Self { mantissa, scale })
+    }
+
+    /// The decimal `text` spells, digits with an optional sign and decimal point,
+    /// `None` if it isn't one or has too many digits. Keeps the places written
+    /// after the point, trailing zeros included.
+    pub fn parse(text: &str) -> Option<Self> {
+        let (negative, unsigned) = match text.strip_prefix('-') {
+            Some(unsigned) => (true, unsigned),
+            None => (false, text.strip_prefix('+').unwrap_or(text)),
+        };
+        let (integer, fraction) = unsigned.split_once('.').unwrap_or((unsigned, ""));
+        let digits = || integer.bytes().chain(fraction.bytes());
+        if integer.len() + fraction.len() == 0 || !digits().all(|digit| digit.is_ascii_digit()) || fraction.len() > Self::MAX_PRECISION as usize {
+            return None;
+        }
+
+        let mut mantissa: i128 = 0;
+        for digit in digits() {
+            mantissa = mantissa.checked_mul(10)?.checked_add((digit - b'0') as i128)?;
+        }
+        Self::new(if negative { -mantissa } else { mantissa }, fraction.len() as u8)
+    }
+
+    pub fn mantissa(&self) -> i128 {
+        self.mantissa
+    }
+
+    /// Places after the decimal point.
+    pub fn scale(&self) -> u8 {
+        self.scale
+    }
+
+    /// Digits in the number, not counting the zeros in front of the first nonzero
+    /// one but counting those after the point: 0.05 has one, 10.50 four.
+    pub fn precision(&self) -> u8 {
+        let mut digits = 1;
+        while (digits as u8) < Self::MAX_PRECISION && self.mantissa.unsigned_abs() >= pow10(digits as u8) as u128 {
+            digits += 1;
+        }
+        digits as u8
+    }
+
+    /// Whether the number is stored as is in a `NUMERIC(precision, scale)` column:
+    /// it has that scale and no more digits than that.
+    pub fn fits(&self, precision: u8, scale: u8) -> bool {
+        self.scale == scale && self.precision() <= precision
+    }
+
+    /// The same number with `scale` places, rounded half away from zero if that's
+    /// fewer, `None` if it doesn't fit in 38 digits.
+    pub fn rescale(&self, scale: u8) -> Option<Self> {
+        match scale.cmp(&self.scale) {
+            Ordering::Equal => Some(*self),
+            Ordering::Greater => Self::new(self.mantissa.checked_mul(checked_pow10(scale - self.scale)?)?, scale),
+            Ordering::Less => {
+                let divisor = pow10(self.scale - scale);
+                let (quotient, remainder) = (self.mantissa / divisor, self.mantissa % divisor);
+                let rounded = if remainder.unsigned_abs() * 2 >= divisor as u128 { quotient + self.mantissa.signum() } else { quotient };
+                Self::new(rounded, scale)
+            }
+        }
+    }
+
+    /// The same number without the zeros at the end of its places.
+    pub fn normalize(&self) -> Self {
+        let (mut mantissa, mut scale) = (self.mantissa, self.scale);
+        while scale > 0 && mantissa % 10 == 0 {
+            mantissa /= 10;
+            scale -= 1;
+        }
+        Self { mantissa, scale }
+    }
+
+    /// The number as an integer, `None` if it has a nonzero fraction or doesn't fit.
+    pub fn to_i64(&self) -> Option<i64> {
+        let divisor = pow10(self.scale);
+        if self.mantissa % divisor != 0 {
+            return None;
+        }
+        i64::try_from(self.mantissa / divisor).ok()
+    }
+
+    /// The nearest float, for estimates that don't need to be exact.
+    pub fn to_f64(&self) -> f64 {
+        self.mantissa as f64 / 10f64.powi(self.scale as i32)
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.mantissa == 0
+    }
+
+    pub fn checked_add(self, other: Self) -> Option<Self> {
+        let (left, right, scale) = self.aligned(other);
+        Self::new(left?.checked_add(right?)?, scale)
+    }
+
+    pub fn checked_sub(self, other: Self) -> Option<Self> {
+        let (left, right, scale) = self.aligned(other);
+        Self::new(left?.checked_sub(right?)?, scale)
+    }
+
+    /// The product, with the places of both factors.
+    pub fn checked_mul(self, other: Self) -> Option<Self> {
+        Self::new(self.mantissa.checked_mul(other.mantissa)?, self.scale.checked_add(other.scale)?)
+    }
+
+    /// The quotient rounded as the type's docs say, `None` for division by zero
+    /// as well as overflow.
+    pub fn checked_div(self, other: Self) -> Option<Self> {
+        if other.is_zero() {
+            return None;
+        }
+        let scale = Self::DIVISION_SCALE.max(self.scale).max(other.scale);
+        // |self| * 10^shift / |other| is the quotient at `scale`, worked out a digit
+        // at a time so the dividend doesn't have to fit in an i128 shifted
+        let shift = scale + other.scale - self.scale;
+        let divisor = other.mantissa.unsigned_abs();
+        let (mut quotient, mut remainder) = (self.mantissa.unsigned_abs() / divisor, self.mantissa.unsigned_abs() % divisor);
+        for _ in 0..shift {
+            let dividend = remainder.checked_mul(10)?;
+            quotient = quotient.checked_mul(10)?.checked_add(dividend / divisor)?;
+            remainder = dividend % divisor;
+        }
+        if remainder.checked_mul(2)? >= divisor {
+            quotient = quotient.checked_add(1)?;
+        }
+        let quotient = i128::try_from(quotient).ok()?;
+        Self::new(if (self.mantissa < 0) != (other.mantissa < 0) { -quotient } else { quotient }, scale)
+    }
+
+    /// The remainder of dividing by `other` a whole number of times, with the sign
+    /// of `self`. `None` for division by zero as well as overflow.
+    pub fn checked_rem(self, other: Self) -> Option<Self> {
+        let (left, right, scale) = self.aligned(other);
+        Self::new(left?.checked_rem(right?)?, scale)
+    }
+
+    /// The 17 bytes a decimal is stored as: its mantissa, little endian, then its scale.
+    pub fn to_bytes(&self) -> [u8; 17] {
+        let mut bytes = [0; 17];
+        bytes[..16].copy_from_slice(&self.mantissa.to_le_bytes());
+        bytes[16] = self.scale;
+        bytes
+    }
+
+    /// The decimal `to_bytes` made these bytes from, `None` if they aren't one.
+    pub fn from_bytes(bytes: [u8; 17]) -> Option<Self> {
+        Self::new(i128::from_le_bytes(bytes[..16].try_into().unwrap()), bytes[16])
+    }
+
+    /// the mantissas of both numbers at the larger of their scales, and that scale.
+    /// a mantissa is `None` if it doesn't fit in an i128 at that scale
+    fn aligned(self, other: Self) -> (Option<i128>, Option<i128>, u8) {
+        let scale = self.scale.max(other.scale);
+        let shifted = |decimal: Self| checked_pow10(scale - decimal.scale).and_then(|factor| decimal.mantissa.checked_mul(factor));
+        (shifted(self), shifted(other), scale)
+    }
+}
+
+/// 10^exponent, for exponents up to `Decimal::MAX_PRECISION`
+fn pow10(exponent: u8) -> i128 {
+    10i128.pow(exponent as u32)
+}
+
+fn checked_pow10(exponent: u8) -> Option<i128> {
+    10i128.checked_pow(exponent as u32)
+}
+
+impl From<i64> for Decimal {
+    fn from(value: i64) -> Self {
+        Self { mantissa: value as i128, scale: 0 }
+    }
+}
+
+impl From<i32> for Decimal {
+    fn from(value: i32) -> Self {
+        Self { mantissa: value as i128, scale: 0 }
+    }
+}
+
+impl std::ops::Neg for Decimal {
+    type Output = Self;
+
+    fn neg(self) -> Self {
+        // the mantissa is under 10^38 either way
+        Self { mantissa: -self.mantissa, scale: self.scale }
+    }
+}
+
+impl Ord for Decimal {
+    fn cmp(&self, other: &Self) -> Ordering {
+        match self.aligned(*other) {
+            (Some(left), Some(right), _) => left.cmp(&right),
+            // only the one with fewer places is shifted, so the one that overflows is
+            // the larger of the two in size
+            (None, _, _) => self.mantissa.cmp(&0),
+            (_, None, _) => 0.cmp(&other.mantissa),
+        }
+    }
+}
+
+impl PartialOrd for Decimal {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for Decimal {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Decimal {}
+
+impl Hash for Decimal {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        // equal numbers have the same normal form, whatever their scales
+        let normal = self.normalize();
+        normal.mantissa.hash(state);
+        normal.scale.hash(state);
+    }
+}
+
+impl std::fmt::Display for Decimal {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let digits = self.mantissa.unsigned_abs().to_string();
+        // at least one digit in front of the point
+        let digits = format!("{digits:0>width$}", width = self.scale as usize + 1);
+        let (integer, fraction) = digits.split_at(digits.len() - self.scale as usize);
+        let sign = if self.mantissa < 0 { "-" } else { "" };
+        if fraction.is_empty() { write!(f, "{sign}{integer}") } else { write!(f, "{sign}{integer}.{fraction}") }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn decimal(text: &str) -> Decimal {
+        Decimal::parse(text).unwrap()
+    }
+
+    #[test]
+    fn test_parse_and_display() {
+        for text in ["0", "1.50", "-0.05", "123456789.000", "-99999999999999999999999999999999999999", "0.00000000000000000000000000000000000001"] {
+            assert_eq!(decimal(text).to_string(), text);
+        }
+        assert_eq!(decimal("+.5").to_string(), "0.5");
+        assert_eq!(decimal("7.").to_string(), "7");
+        for text in ["", ".", "1.2.3", "1e5", "--1", " 1", "100000000000000000000000000000000000000"] {
+            assert_eq!(Decimal::parse(text), None, "{text}");
+        }
+        assert_eq!(decimal("10.50").precision(), 4);
+        assert_eq!(decimal("-0.05").precision(), 1);
+        assert!(decimal("999.99").fits(5, 2));
+        assert!(!decimal("1000.00").fits(5, 2));
+        assert!(!decimal("1.5").fits(5, 2));
+    }
+
+    #[test]
+    fn test_compare_across_scales() {
+        assert_eq!(decimal("1.5"), decimal("1.500"));
+        assert!(decimal("-2.01") < decimal("-2"));
+        assert!(decimal("0.1") > decimal("0.09999"));
+        // shifting the one with fewer places would overflow
+        let big = decimal("99999999999999999999999999999999999999");
+        let tiny = decimal("0.00000000000000000000000000000000000001");
+        assert!(big > tiny && -big < tiny && -big < -tiny);
+
+        let hash = |value: Decimal| {
+            let mut hasher = std::collections::hash_map::DefaultHasher::new();
+            value.hash(&mut hasher);
+            hasher.finish()
+        };
+        assert_eq!(hash(decimal("2.50")), hash(decimal("2.5")));
+    }
+
+    #[test]
+    fn test_arithmetic_is_exact() {
+        assert_eq!(decimal("0.1").checked_add(decimal("0.2")).unwrap().to_string(), "0.3");
+        assert_eq!(decimal("1.50").checked_sub(decimal("3")).unwrap().to_string(), "-1.50");
+        assert_eq!(decimal("1.05").checked_mul(decimal("-0.5")).unwrap().to_string(), "-0.525");
+        assert_eq!(decimal("7.5").checked_rem(decimal("2")).unwrap().to_string(), "1.5");
+        assert_eq!(decimal("-7.5").checked_rem(decimal("2")).unwrap().to_string(), "-1.5");
+        assert_eq!(decimal("10").checked_div(decimal("4")).unwrap().to_string(), "2.5000000000000000");
+        assert_eq!(decimal("2").checked_div(decimal("-3")).unwrap().to_string(), "-0.6666666666666667");
+        assert_eq!(decimal("1").checked_div(decimal("0.000")), None);
+
+        let big = decimal("99999999999999999999999999999999999999");
+        assert_eq!(big.checked_add(decimal("1")), None);
+        assert_eq!(big.checked_mul(decimal("10")), None);
+        assert_eq!(big.checked_div(big).unwrap(), decimal("1"));
+    }
+
+    #[test]
+    fn test_rescale_rounds_half_away_from_zero() {
+        assert_eq!(decimal("2.345").rescale(2).unwrap().to_string(), "2.35");
+        assert_eq!(decimal("-2.345").rescale(2).unwrap().to_string(), "-2.35");
+        assert_eq!(decimal("2.344").rescale(0).unwrap().to_string(), "2");
+        assert_eq!(decimal("2.5").rescale(4).unwrap().to_string(), "2.5000");
+        assert_eq!(decimal("99999999999999999999999999999999999999").rescale(1), None);
+        assert_eq!(decimal("12.3400").normalize().to_string(), "12.34");
+        assert_eq!(decimal("12.00").to_i64(), Some(12));
+        assert_eq!(decimal("12.01").to_i64(), None);
+        assert_eq!(Decimal::from_bytes(decimal("-3.14").to_bytes()).unwrap().to_string(), "-3.14");
+    }
+}
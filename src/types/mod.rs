@@ -0,0 +1,14 @@
+mod data_type;
+pub use data_type::{Column, DataType};
+
+mod decimal;
+pub use decimal::Decimal;
+
+pub mod temporal;
+pub use temporal::Interval;
+
+mod value;
+pub use value::Value;
+
+mod tuple;
+pub use tuple::{Tuple, TupleError};
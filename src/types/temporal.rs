@@ -0,0 +1,447 @@
+use std::cmp::Ordering;
+use std::hash::{Hash, Hasher};
+
+// `DATE`s are days since 1970-01-01, `TIME`s microseconds since midnight and
+// `TIMESTAMP`s microseconds since 1970-01-01 00:00:00, without a time zone
+
+pub const MICROS_PER_SECOND: i64 = 1_000_000;
+pub const MICROS_PER_MINUTE: i64 = 60 * MICROS_PER_SECOND;
+pub const MICROS_PER_HOUR: i64 = 60 * MICROS_PER_MINUTE;
+pub const MICROS_PER_DAY: i64 = 24 * MICROS_PER_HOUR;
+
+/// Days since 1970-01-01 of the day `day` of month `month` of `year`, `None` if
+/// there's no such day. Years before 1 AD count back through year 0, like ISO 8601's.
+pub fn days_from_civil(year: i32, month: u32, day: u32) -> Option<i32> {
+    if !(1..=12).contains(&month) || day < 1 || day > days_in_month(year, month) {
+        return None;
+    }
+    // Howard Hinnant's days_from_civil, with years starting in March so the leap day comes last
+    let year = year as i64 - (month <= 2) as i64;
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * ((month as i64 + 9) % 12) + 2) / 5 + day as i64 - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    i32::try_from(era * 146097 + day_of_era - 719468).ok()
+}
+
+/// The year, month and day of the date `days` after 1970-01-01.
+pub fn civil_from_days(days: i32) -> (i32, u32, u32) {
+    let days = days as i64 + 719468;
+    let era = days.div_euclid(146097);
+    let day_of_era = days - era * 146097;
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_from_march = (5 * day_of_year + 2) / 153;
+    let day = (day_of_year - (153 * month_from_march + 2) / 5 + 1) as u32;
+    let month = if month_from_march < 10 { month_from_march + 3 } else { month_from_march - 9 } as u32;
+    ((year_of_era + era * 400 + (month <= 2) as i64) as i32, month, day)
+}
+
+pub fn days_in_month(year: i32, month: u32) -> u32 {
+    match month {
+        2 if year % 4 == 0 && (year % 100 != 0 || year % 400 == 0) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+/// `days` moved `months` months on, to the last day of the month it lands in if
+/// that's shorter: a month after January 31st is the end of February.
+pub fn add_months(days: i32, months: i32) -> Option<i32> {
+    let (year, month, day) = civil_from_days(days);
+    let month_index = (year as i64 * 12 + month as i64 - 1).checked_add(months as i64)?;
+    let (year, month) = (i32::try_from(month_index.div_euclid(12)).ok()?, month_index.rem_euclid(12) as u32 + 1);
+    days_from_civil(year, month, day.min(days_in_month(year, month)))
+}
+
+/// The date an ISO 8601 `YYYY-MM-DD` spells, as days since 1970-01-01.
+pub fn parse_date(text: &str) -> Option<i32> {
+    let mut parts = text.trim().splitn(3, '-');
+    let (year, month, day) = (parts.next()?, parts.next()?, parts.next()?);
+    if year.len() < 4 || !(1..=2).contains(&month.len()) || !(1..=2).contains(&day.len()) {
+        return None;
+    }
+    days_from_civil(digits(year)? as i32, digits(month)? as u32, digits(day)? as u32)
+}
+
+/// The time of day an ISO 8601 `HH:MM[:SS[.ffffff]]` spells, as microseconds since midnight.
+pub fn parse_time(text: &str) -> Option<i64> {
+    let mut parts = text.trim().splitn(3, ':');
+    let (hour, minute) = (parts.next()?, parts.next()?);
+    let (second, fraction) = match parts.next() {
+        Some(seconds) => seconds.split_once('.').unwrap_or((seconds, "")),
+        None => ("00", ""),
+    };
+    if [hour, minute, second].iter().any(|part| part.len() != 2) || fraction.len() > 6 || (fraction.is_empty() && text.trim().ends_with('.')) {
+        return None;
+    }
+    let (hour, minute, second) = (digits(hour)?, digits(minute)?, digits(second)?);
+    if hour > 23 || minute > 59 || second > 59 {
+        return None;
+    }
+    let micros = if fraction.is_empty() { 0 } else { digits(fraction)? * 10i64.pow(6 - fraction.len() as u32) };
+    Some(hour * MICROS_PER_HOUR + minute * MICROS_PER_MINUTE + second * MICROS_PER_SECOND + micros)
+}
+
+/// The moment an ISO 8601 date, optionally followed by a time of day after a `T`
+/// or a space, spells, as microseconds since 1970-01-01 00:00:00. A time zone at
+/// the end (`Z`, or an offset like `+02:00`) is taken into account, so the
+/// timestamp is in UTC.
+pub fn parse_timestamp(text: &str) -> Option<i64> {
+    let text = text.trim();
+    let (date, time) = match text.find(['T', ' ']) {
+        Some(split) => (&text[..split], text[split + 1..].trim_start()),
+        None => (text, ""),
+    };
+    let days = parse_date(date)? as i64;
+    let (time, offset) = if let Some(time) = time.strip_suffix('Z') {
+        (time, 0)
+    } else if let Some(sign) = time.rfind(['+', '-']) {
+        // +HH, +HH:MM or +HHMM
+        let offset = &time[sign + 1..];
+        let (hours, minutes) = offset.split_once(':').unwrap_or(if offset.len() == 4 { offset.split_at(2) } else { (offset, "00") });
+        if hours.len() != 2 || minutes.len() != 2 {
+            return None;
+        }
+        let offset = digits(hours)? * MICROS_PER_HOUR + digits(minutes)? * MICROS_PER_MINUTE;
+        (&time[..sign], if &time[sign..=sign] == "-" { -offset } else { offset })
+    } else {
+        (time, 0)
+    };
+    let time = if time.is_empty() { 0 } else { parse_time(time)? };
+    days.checked_mul(MICROS_PER_DAY)?.checked_add(time)?.checked_sub(offset)
+}
+
+pub fn format_date(days: i32) -> String {
+    let (year, month, day) = civil_from_days(days);
+    format!("{year:04}-{month:02}-{day:02}")
+}
+
+/// `HH:MM:SS`, with as many places of the seconds' fraction as it takes
+pub fn format_time(micros: i64) -> String {
+    let seconds = micros / MICROS_PER_SECOND;
+    let mut text = format!("{:02}:{:02}:{:02}", seconds / 3600, seconds / 60 % 60, seconds % 60);
+    if micros % MICROS_PER_SECOND != 0 {
+        text.push_str(format!(".{:06}", micros % MICROS_PER_SECOND).trim_end_matches('0'));
+    }
+    text
+}
+
+pub fn format_timestamp(micros: i64) -> String {
+    let days = micros.div_euclid(MICROS_PER_DAY);
+    format!("{} {}", format_date(days as i32), format_time(micros.rem_euclid(MICROS_PER_DAY)))
+}
+
+/// the value of a string of ASCII digits
+fn digits(text: &str) -> Option<i64> {
+    if text.is_empty() || !text.bytes().all(|byte| byte.is_ascii_digit()) {
+        return None;
+    }
+    text.parse().ok()
+}
+
+/// A span of time, the value of an `INTERVAL` column.
+///
+/// Like PostgreSQL's, it's kept as months, days and microseconds, as a month
+/// isn't always as many days long, nor (with daylight saving time) a day as
+/// many hours. To compare them, a month counts as 30 days, so `1 mon` and
+/// `30 days` are equal.
+///
+/// Intervals are written the way PostgreSQL does, and read either that way or
+/// as ISO 8601 durations.
+///
+/// # Examples
+///
+/// ```
+/// use gondor_rdbms::types::Interval;
+///
+/// let interval = Interval::parse("1 year 2 months 3 days 04:05:06.5").unwrap();
+/// assert_eq!(interval, Interval { months: 14, days: 3, micros: 14_706_500_000 });
+/// assert_eq!(interval.to_string(), "1 year 2 mons 3 days 04:05:06.5");
+/// assert_eq!(Interval::parse("P1DT12H").unwrap().to_string(), "1 day 12:00:00");
+/// assert_eq!(Interval::parse("1 mon"), Interval::parse("30 days"));
+/// ```
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Interval {
+    pub months: i32,
+    pub days: i32,
+    pub micros: i64,
+}
+
+impl Interval {
+    /// The interval `text` spells, `None` if it doesn't spell one.
+    ///
+    /// That's either quantities and their units (`3 days`, `-1 hour`,
+    /// `2 years 6 mons`, `90 seconds` or `1.5 s`), optionally with a time of day
+    /// (`1 day 02:30:00`) and all negated by a trailing `ago`, or an ISO 8601
+    /// duration like `P1Y2M3DT4H5M6S`.
+    pub fn parse(text: &str) -> Option<Self> {
+        let text = text.trim();
+        match text.strip_prefix('P') {
+            Some(duration) => Self::parse_iso(duration),
+            None => Self::parse_units(text),
+        }
+    }
+
+    fn parse_units(text: &str) -> Option<Self> {
+        let mut interval = Interval::default();
+        let mut words = text.split_whitespace().peekable();
+        let mut seen_any = false;
+        while let Some(word) = words.next() {
+            if word.eq_ignore_ascii_case("ago") && words.peek().is_none() && seen_any {
+                return interval.checked_neg();
+            }
+            seen_any = true;
+            if word.contains(':') {
+                let (negative, time) = match word.strip_prefix('-') {
+                    Some(time) => (true, time),
+                    None => (false, word.strip_prefix('+').unwrap_or(word)),
+                };
+                // any number of hours, past a day too
+                let (hours, rest) = time.split_once(':')?;
+                let micros = digits(hours)?.checked_mul(MICROS_PER_HOUR)?.checked_add(parse_time(&format!("00:{rest}"))?)?;
+                interval.micros = interval.micros.checked_add(if negative { -micros } else { micros })?;
+                continue;
+            }
+            let unit = words.next()?.to_ascii_lowercase();
+            interval = interval.checked_add(Self::of_unit(word, &unit)?)?;
+        }
+        seen_any.then_some(interval)
+    }
+
+    /// `quantity` of `unit`, a fraction only being allowed for seconds and smaller units
+    fn of_unit(quantity: &str, unit: &str) -> Option<Self> {
+        let micros_per = match unit {
+            "us" | "usec" | "usecs" | "microsecond" | "microseconds" => 1,
+            "ms" | "msec" | "msecs" | "millisecond" | "milliseconds" => 1000,
+            "s" | "sec" | "secs" | "second" | "seconds" => MICROS_PER_SECOND,
+            "m" | "min" | "mins" | "minute" | "minutes" => MICROS_PER_MINUTE,
+            "h" | "hr" | "hrs" | "hour" | "hours" => MICROS_PER_HOUR,
+            _ => {
+                let quantity: i32 = quantity.parse().ok()?;
+                return match unit {
+                    "d" | "day" | "days" => Some(Self { days: quantity, ..Self::default() }),
+                    "w" | "week" | "weeks" => Some(Self { days: quantity.checked_mul(7)?, ..Self::default() }),
+                    "mon" | "mons" | "month" | "months" => Some(Self { months: quantity, ..Self::default() }),
+                    "y" | "yr" | "yrs" | "year" | "years" => Some(Self { months: quantity.checked_mul(12)?, ..Self::default() }),
+                    _ => None,
+                };
+            }
+        };
+        Some(Self { micros: scaled(quantity, micros_per)?, ..Self::default() })
+    }
+
+    fn parse_iso(duration: &str) -> Option<Self> {
+        let (date, time) = duration.split_once('T').unwrap_or((duration, ""));
+        if date.is_empty() && time.is_empty() {
+            return None;
+        }
+        let mut interval = Interval::default();
+        for (part, is_time) in [(date, false), (time, true)] {
+            let mut quantity = String::new();
+            for c in part.chars() {
+                if c.is_ascii_digit() || c == '.' || c == '-' {
+                    quantity.push(c);
+                    continue;
+                }
+                let unit = match (c, is_time) {
+                    ('Y', false) => "year",
+                    ('M', false) => "month",
+                    ('W', false) => "week",
+                    ('D', false) => "day",
+                    ('H', true) => "hour",
+                    ('M', true) => "minute",
+                    ('S', true) => "second",
+                    _ => return None,
+                };
+                interval = interval.checked_add(Self::of_unit(&std::mem::take(&mut quantity), unit)?)?;
+            }
+            if !quantity.is_empty() {
+                return None;
+            }
+        }
+        Some(interval)
+    }
+
+    /// Microseconds in the interval, counting a month as 30 days.
+    pub fn span(&self) -> i128 {
+        (self.months as i128 * 30 + self.days as i128) * MICROS_PER_DAY as i128 + self.micros as i128
+    }
+
+    pub fn checked_add(self, other: Self) -> Option<Self> {
+        Some(Self { months: self.months.checked_add(other.months)?, days: self.days.checked_add(other.days)?, micros: self.micros.checked_add(other.micros)? })
+    }
+
+    pub fn checked_sub(self, other: Self) -> Option<Self> {
+        self.checked_add(other.checked_neg()?)
+    }
+
+    pub fn checked_neg(self) -> Option<Self> {
+        Some(Self { months: self.months.checked_neg()?, days: self.days.checked_neg()?, micros: self.micros.checked_neg()? })
+    }
+
+    /// The interval `factor` times over.
+    pub fn checked_mul(self, factor: i64) -> Option<Self> {
+        let factor32 = i32::try_from(factor).ok()?;
+        Some(Self { months: self.months.checked_mul(factor32)?, days: self.days.checked_mul(factor32)?, micros: self.micros.checked_mul(factor)? })
+    }
+
+    /// The timestamp `timestamp` moved on by the interval: by its months first,
+    /// then its days, then the rest.
+    pub fn add_to(&self, timestamp: i64) -> Option<i64> {
+        let (days, time) = (timestamp.div_euclid(MICROS_PER_DAY), timestamp.rem_euclid(MICROS_PER_DAY));
+        let days = add_months(i32::try_from(days).ok()?, self.months)? as i64 + self.days as i64;
+        days.checked_mul(MICROS_PER_DAY)?.checked_add(time)?.checked_add(self.micros)
+    }
+
+    /// The 16 bytes an interval is stored as: its months, days and microseconds, little endian.
+    pub fn to_bytes(&self) -> [u8; 16] {
+        let mut bytes = [0; 16];
+        bytes[..4].copy_from_slice(&self.months.to_le_bytes());
+        bytes[4..8].copy_from_slice(&self.days.to_le_bytes());
+        bytes[8..].copy_from_slice(&self.micros.to_le_bytes());
+        bytes
+    }
+
+    pub fn from_bytes(bytes: [u8; 16]) -> Self {
+        Self {
+            months: i32::from_le_bytes(bytes[..4].try_into().unwrap()),
+            days: i32::from_le_bytes(bytes[4..8].try_into().unwrap()),
+            micros: i64::from_le_bytes(bytes[8..].try_into().unwrap()),
+        }
+    }
+}
+
+/// `quantity`, a decimal number, times `unit`, rounded to a whole number
+fn scaled(quantity: &str, unit: i64) -> Option<i64> {
+    let (negative, quantity) = match quantity.strip_prefix('-') {
+        Some(quantity) => (true, quantity),
+        None => (false, quantity.strip_prefix('+').unwrap_or(quantity)),
+    };
+    let (whole, fraction) = quantity.split_once('.').unwrap_or((quantity, ""));
+    let whole = if whole.is_empty() && !fraction.is_empty() { 0 } else { digits(whole)? };
+    let mut value = whole.checked_mul(unit)?;
+    if !fraction.is_empty() {
+        let places = fraction.len().min(12) as u32;
+        let fraction = digits(&fraction[..places as usize])? as i128;
+        value = value.checked_add(((fraction * unit as i128 + 10i128.pow(places) / 2) / 10i128.pow(places)) as i64)?;
+    }
+    Some(if negative { -value } else { value })
+}
+
+impl Ord for Interval {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.span().cmp(&other.span())
+    }
+}
+
+impl PartialOrd for Interval {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for Interval {
+    fn eq(&self, other: &Self) -> bool {
+        self.span() == other.span()
+    }
+}
+
+impl Eq for Interval {}
+
+impl Hash for Interval {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        // equal intervals have the same span, however it's split up
+        self.span().hash(state);
+    }
+}
+
+impl std::fmt::Display for Interval {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let plural = |count: i32| if count == 1 { "" } else { "s" };
+        let mut parts = Vec::new();
+        let (years, months) = (self.months / 12, self.months % 12);
+        if years != 0 {
+            parts.push(format!("{years} year{}", plural(years)));
+        }
+        if months != 0 {
+            parts.push(format!("{months} mon{}", plural(months)));
+        }
+        if self.days != 0 {
+            parts.push(format!("{} day{}", self.days, plural(self.days)));
+        }
+        if self.micros != 0 || parts.is_empty() {
+            let sign = if self.micros < 0 { "-" } else { "" };
+            parts.push(format!("{sign}{}", format_time(self.micros.unsigned_abs() as i64)));
+        }
+        write!(f, "{}", parts.join(" "))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_civil_days() {
+        assert_eq!(days_from_civil(1970, 1, 1), Some(0));
+        assert_eq!(days_from_civil(2000, 3, 1), Some(11017));
+        assert_eq!(days_from_civil(1969, 12, 31), Some(-1));
+        assert_eq!(days_from_civil(2023, 2, 29), None);
+        for days in [-800_000, -1, 0, 59, 11016, 19_000, 2_000_000] {
+            let (year, month, day) = civil_from_days(days);
+            assert_eq!(days_from_civil(year, month, day), Some(days));
+        }
+        let date = |text| parse_date(text).unwrap();
+        assert_eq!(add_months(date("2024-01-31"), 1), Some(date("2024-02-29")));
+        assert_eq!(add_months(date("2024-03-15"), -15), Some(date("2022-12-15")));
+    }
+
+    #[test]
+    fn test_parse_and_format() {
+        assert_eq!(format_date(parse_date("2024-02-29").unwrap()), "2024-02-29");
+        assert_eq!(format_date(parse_date("0099-1-5").unwrap()), "0099-01-05");
+        for text in ["2024-02-30", "2024-13-01", "24-01-01", "2024-01", "2024/01/01", "2024-01-01x"] {
+            assert_eq!(parse_date(text), None, "{text}");
+        }
+
+        assert_eq!(format_time(parse_time("23:59:59.25").unwrap()), "23:59:59.25");
+        assert_eq!(format_time(parse_time("07:30").unwrap()), "07:30:00");
+        for text in ["24:00", "12:60", "1:00", "12:00:00.1234567", "12:00:00."] {
+            assert_eq!(parse_time(text), None, "{text}");
+        }
+
+        let timestamp = |text| format_timestamp(parse_timestamp(text).unwrap());
+        assert_eq!(timestamp("2024-01-31T12:00:00Z"), "2024-01-31 12:00:00");
+        assert_eq!(timestamp("2024-01-31 01:30:00+02:00"), "2024-01-30 23:30:00");
+        assert_eq!(timestamp("2024-01-31 23:00-0130"), "2024-02-01 00:30:00");
+        assert_eq!(timestamp("1969-12-31"), "1969-12-31 00:00:00");
+        assert_eq!(parse_timestamp("2024-01-31 12:00:00 junk"), None);
+    }
+
+    #[test]
+    fn test_intervals() {
+        let interval = |text| Interval::parse(text).unwrap();
+        assert_eq!(interval("2 hours 30 minutes").to_string(), "02:30:00");
+        assert_eq!(interval("-1 year -2 mons").to_string(), "-1 years -2 mons");
+        assert_eq!(interval("3 days ago").to_string(), "-3 days");
+        assert_eq!(interval("1 week 1.5 s").to_string(), "7 days 00:00:01.5");
+        assert_eq!(interval("10 ms").micros, 10_000);
+        assert_eq!(interval("0 days").to_string(), "00:00:00");
+        assert_eq!(interval("1 day -01:00:00").to_string(), "1 day -01:00:00");
+        assert_eq!(interval("36:00").to_string(), "36:00:00");
+        assert_eq!(interval("PT1M30S"), interval("90 seconds"));
+        assert_eq!(interval("P1Y2M10D"), Interval { months: 14, days: 10, micros: 0 });
+        for text in ["", "1", "1 fortnight", "1.5 days", "ago", "P", "P1H", "PT1D"] {
+            assert_eq!(Interval::parse(text), None, "{text}");
+        }
+
+        assert!(interval("1 mon") < interval("31 days"));
+        let odd = Interval { months: -1, days: 40, micros: -5 };
+        assert_eq!(Interval::from_bytes(odd.to_bytes()), odd);
+        assert_eq!(interval("1 day").checked_mul(3).unwrap().checked_sub(interval("12 hours")).unwrap().to_string(), "3 days -12:00:00");
+        let leap_day = parse_timestamp("2024-01-31 10:00").unwrap();
+        assert_eq!(format_timestamp(interval("1 mon 1 day 1 hour").add_to(leap_day).unwrap()), "2024-03-01 11:00:00");
+    }
+}
@@ -0,0 +1,286 @@
+use super::{Column, DataType, Decimal, Interval, Value};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum TupleError {
+    /// the tuple has a different number of values than the schema has columns
+    ColumnCountMismatch { expected: usize, found: usize },
+    /// the value can't be stored in the column
+    TypeMismatch { column: String, data_type: DataType, value: Value },
+    /// the bytes aren't a tuple encoded with this schema
+    Corrupt,
+}
+
+impl std::fmt::Display for TupleError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TupleError::ColumnCountMismatch { expected, found } => write!(f, "Expected {expected} values, found {found}"),
+            TupleError::TypeMismatch { column, data_type, value } => {
+                write!(f, "Value {value} does not fit column {column} of type {data_type}")
+            }
+            TupleError::Corrupt => write!(f, "Tuple does not match its schema"),
+        }
+    }
+}
+
+impl std::error::Error for TupleError {}
+
+/// A row of values, one per column of a schema.
+///
+/// Tuples are stored on pages as bytes laid out by the schema: a null bitmap
+/// with a bit per column, the lowest bit of the first byte for the first
+/// column, set when the column is `NULL`, and then the value of every column
+/// that isn't, in order. The bitmap is rounded up to whole bytes, with the
+/// bits past the last column left clear.
+///
+/// `INT`, `BIGINT` and `BOOL` take 4, 8 and 1 bytes, `VARCHAR` a 4 byte length
+/// and then its UTF-8 bytes, and `NUMERIC` a 16 byte mantissa and a byte for
+/// its scale. `DATE` takes 4 bytes, `TIME` and `TIMESTAMP` 8, and `INTERVAL`
+/// 16. All integers are little endian.
+///
+/// # Examples
+///
+/// ```
+/// use gondor_rdbms::types::{Column, DataType, Tuple, Value};
+///
+/// let schema = vec![Column::new("id", DataType::Int), Column::new("name", DataType::Varchar(None))];
+/// let tuple = Tuple::new(vec![Value::Int(1), Value::Varchar("Samwise".to_string())]);
+///
+/// let bytes = tuple.encode(&schema).unwrap();
+/// assert_eq!(Tuple::decode(&bytes, &schema).unwrap(), tuple);
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct Tuple {
+    values: Vec<Value>,
+}
+
+impl Tuple {
+    pub fn new(values: Vec<Value>) -> Self {
+        Self { values }
+    }
+
+    pub fn values(&self) -> &[Value] {
+        &self.values
+    }
+
+    pub fn into_values(self) -> Vec<Value> {
+        self.values
+    }
+
+    /// Value of the column at `index`, `None` if there's no such column.
+    pub fn get(&self, index: usize) -> Option<&Value> {
+        self.values.get(index)
+    }
+
+    /// Encodes the tuple for storage, checking every value against its column.
+    pub fn encode(&self, schema: &[Column]) -> Result<Vec<u8>, TupleError> {
+        if self.values.len() != schema.len() {
+            return Err(TupleError::ColumnCountMismatch { expected: schema.len(), found: self.values.len() });
+        }
+
+        let mut bytes = vec![0; bitmap_len(schema.len())];
+        for (position, (value, column)) in self.values.iter().zip(schema).enumerate() {
+            if !value.fits(column.data_type) {
+                return Err(TupleError::TypeMismatch {
+                    column: column.name.clone(),
+                    data_type: column.data_type,
+                    value: value.clone(),
+                });
+            }
+            match value {
+                Value::Null => bytes[position / 8] |= 1 << (position % 8),
+                Value::Int(value) => bytes.extend_from_slice(&value.to_le_bytes()),
+                Value::BigInt(value) => bytes.extend_from_slice(&value.to_le_bytes()),
+                Value::Bool(value) => bytes.push(*value as u8),
+                Value::Varchar(value) => {
+                    bytes.extend_from_slice(&(value.len() as u32).to_le_bytes());
+                    bytes.extend_from_slice(value.as_bytes());
+                }
+                Value::Numeric(value) => bytes.extend_from_slice(&value.to_bytes()),
+                Value::Date(days) => bytes.extend_from_slice(&days.to_le_bytes()),
+                Value::Time(micros) | Value::Timestamp(micros) => bytes.extend_from_slice(&micros.to_le_bytes()),
+                Value::Interval(interval) => bytes.extend_from_slice(&interval.to_bytes()),
+            }
+        }
+        Ok(bytes)
+    }
+
+    /// Decodes a tuple produced by `encode` with the same schema.
+    pub fn decode(bytes: &[u8], schema: &[Column]) -> Result<Self, TupleError> {
+        Self::decode_columns(bytes, schema, &(0..schema.len()).collect::<Vec<_>>())
+    }
+
+    /// Decodes only the values of the columns at `positions`, which must be in
+    /// increasing order, of a tuple produced by `encode` with `schema`. The
+    /// bytes of the other columns are skipped over without being looked at.
+    pub fn decode_columns(mut bytes: &[u8], schema: &[Column], positions: &[usize]) -> Result<Self, TupleError> {
+        let bytes = &mut bytes;
+        let bitmap = take(bytes, bitmap_len(schema.len()))?;
+        // bits past the last column are never set
+        if !schema.len().is_multiple_of(8) && bitmap[schema.len() / 8] >> (schema.len() % 8) != 0 {
+            return Err(TupleError::Corrupt);
+        }
+        let mut values = Vec::with_capacity(positions.len());
+        let mut wanted = positions.iter().peekable();
+        for (position, column) in schema.iter().enumerate() {
+            let keep = wanted.next_if_eq(&&position).is_some();
+            let value = if bitmap[position / 8] & (1 << (position % 8)) != 0 {
+                Value::Null
+            } else {
+                match column.data_type {
+                    DataType::Int => Value::Int(i32::from_le_bytes(take(bytes, 4)?.try_into().unwrap())),
+                    DataType::BigInt => Value::BigInt(i64::from_le_bytes(take(bytes, 8)?.try_into().unwrap())),
+                    DataType::Bool => match take(bytes, 1)?[0] {
+                        0 => Value::Bool(false),
+                        1 => Value::Bool(true),
+                        _ => return Err(TupleError::Corrupt),
+                    },
+                    DataType::Numeric(_) => Value::Numeric(Decimal::from_bytes(take(bytes, 17)?.try_into().unwrap()).ok_or(TupleError::Corrupt)?),
+                    DataType::Date => Value::Date(i32::from_le_bytes(take(bytes, 4)?.try_into().unwrap())),
+                    DataType::Time => Value::Time(i64::from_le_bytes(take(bytes, 8)?.try_into().unwrap())),
+                    DataType::Timestamp => Value::Timestamp(i64::from_le_bytes(take(bytes, 8)?.try_into().unwrap())),
+                    DataType::Interval => Value::Interval(Interval::from_bytes(take(bytes, 16)?.try_into().unwrap())),
+                    DataType::Varchar(_) => {
+                        let length = u32::from_le_bytes(take(bytes, 4)?.try_into().unwrap());
+                        let text = take(bytes, length as usize)?;
+                        if !keep {
+                            continue;
+                        }
+                        Value::Varchar(String::from_utf8(text.to_vec()).map_err(|_| TupleError::Corrupt)?)
+                    }
+                }
+            };
+            if keep {
+                values.push(value);
+            }
+        }
+
+        if !bytes.is_empty() {
+            return Err(TupleError::Corrupt);
+        }
+        Ok(Self { values })
+    }
+}
+
+/// bytes in the null bitmap of a tuple with `columns` columns
+fn bitmap_len(columns: usize) -> usize {
+    columns.div_ceil(8)
+}
+
+/// splits the first `length` bytes off the front of `bytes`
+fn take<'a>(bytes: &mut &'a [u8], length: usize) -> Result<&'a [u8], TupleError> {
+    let (front, rest) = bytes.split_at_checked(length).ok_or(TupleError::Corrupt)?;
+    *bytes = rest;
+    Ok(front)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn schema() -> Vec<Column> {
+        vec![
+            Column::new("id", DataType::Int),
+            Column::new("visits", DataType::BigInt),
+            Column::new("name", DataType::Varchar(Some(16))),
+            Column::new("active", DataType::Bool),
+        ]
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let tuples = [
+            Tuple::new(vec![Value::Int(-7), Value::BigInt(i64::MAX), Value::Varchar("Éowyn".into()), Value::Bool(true)]),
+            Tuple::new(vec![Value::Null, Value::Null, Value::Null, Value::Null]),
+            Tuple::new(vec![Value::Int(0), Value::Null, Value::Varchar(String::new()), Value::Bool(false)]),
+        ];
+        for tuple in tuples {
+            let bytes = tuple.encode(&schema()).unwrap();
+            assert_eq!(Tuple::decode(&bytes, &schema()).unwrap(), tuple);
+        }
+    }
+
+    #[test]
+    fn test_numeric_round_trip() {
+        let schema = [Column::new("balance", DataType::Numeric(Some((10, 2)))), Column::new("rate", DataType::Numeric(None))];
+        let tuple = Tuple::new(vec![Value::Numeric(Decimal::parse("-12345678.90").unwrap()), Value::Numeric(Decimal::parse("0.000125").unwrap())]);
+        let bytes = tuple.encode(&schema).unwrap();
+        assert_eq!(bytes.len(), 1 + 2 * 17);
+        assert_eq!(Tuple::decode(&bytes, &schema).unwrap().values[1].to_string(), "0.000125");
+
+        // a column with a scale only takes numbers with exactly that many places
+        let unscaled = Tuple::new(vec![Value::Numeric(Decimal::parse("1.5").unwrap()), Value::Null]);
+        assert!(matches!(unscaled.encode(&schema), Err(TupleError::TypeMismatch { column, .. }) if column == "balance"));
+    }
+
+    #[test]
+    fn test_temporal_round_trip() {
+        let schema = [
+            Column::new("born", DataType::Date),
+            Column::new("alarm", DataType::Time),
+            Column::new("seen", DataType::Timestamp),
+            Column::new("every", DataType::Interval),
+        ];
+        let values = [("1954-07-29", DataType::Date), ("06:30:00", DataType::Time), ("2024-02-29 12:00:00.5", DataType::Timestamp), ("-1 years 3 days", DataType::Interval)]
+            .map(|(text, data_type)| Value::parse(text, data_type).unwrap());
+        let tuple = Tuple::new(values.to_vec());
+        let bytes = tuple.encode(&schema).unwrap();
+        assert_eq!(bytes.len(), 1 + 4 + 8 + 8 + 16);
+        let decoded = Tuple::decode(&bytes, &schema).unwrap();
+        assert_eq!(decoded.values.iter().map(Value::to_string).collect::<Vec<_>>(), ["1954-07-29", "06:30:00", "2024-02-29 12:00:00.5", "-1 years 3 days"]);
+    }
+
+    #[test]
+    fn test_encode_checks_schema() {
+        let short = Tuple::new(vec![Value::Int(1)]);
+        assert_eq!(short.encode(&schema()), Err(TupleError::ColumnCountMismatch { expected: 4, found: 1 }));
+
+        let wrong_type = Tuple::new(vec![Value::BigInt(1), Value::Null, Value::Null, Value::Null]);
+        assert!(matches!(wrong_type.encode(&schema()), Err(TupleError::TypeMismatch { column, .. }) if column == "id"));
+
+        let too_long = Tuple::new(vec![Value::Null, Value::Null, Value::Varchar("x".repeat(17)), Value::Null]);
+        assert!(matches!(too_long.encode(&schema()), Err(TupleError::TypeMismatch { column, .. }) if column == "name"));
+    }
+
+    #[test]
+    fn test_decode_rejects_garbage() {
+        let tuple = Tuple::new(vec![Value::Int(1), Value::BigInt(2), Value::Varchar("abc".into()), Value::Bool(true)]);
+        let bytes = tuple.encode(&schema()).unwrap();
+
+        assert_eq!(Tuple::decode(&bytes[..bytes.len() - 1], &schema()), Err(TupleError::Corrupt));
+        assert_eq!(Tuple::decode(&[bytes.as_slice(), &[0]].concat(), &schema()), Err(TupleError::Corrupt));
+        // a bit set for a fifth column the schema doesn't have
+        let mut bad_bitmap = bytes.clone();
+        bad_bitmap[0] = 0b10000;
+        assert_eq!(Tuple::decode(&bad_bitmap, &schema()), Err(TupleError::Corrupt));
+    }
+
+    #[test]
+    fn test_null_bitmap() {
+        // NULLs take a bit in the bitmap and nothing more
+        let nulls = Tuple::new(vec![Value::Null; 4]);
+        assert_eq!(nulls.encode(&schema()).unwrap(), vec![0b1111]);
+        let tuple = Tuple::new(vec![Value::Int(1), Value::Null, Value::Null, Value::Bool(true)]);
+        assert_eq!(tuple.encode(&schema()).unwrap(), vec![0b0110, 1, 0, 0, 0, 1]);
+
+        // past 8 columns the bitmap takes another byte
+        let wide: Vec<_> = (0..10).map(|i| Column::new(&format!("c{i}"), DataType::Int)).collect();
+        let values: Vec<_> = (0..10).map(|i| if i % 3 == 0 { Value::Null } else { Value::Int(i) }).collect();
+        let bytes = Tuple::new(values.clone()).encode(&wide).unwrap();
+        assert_eq!(&bytes[..2], &[0b01001001, 0b10]);
+        assert_eq!(bytes.len(), 2 + 6 * 4);
+        assert_eq!(Tuple::decode(&bytes, &wide).unwrap().values, values);
+        assert_eq!(Tuple::decode_columns(&bytes, &wide, &[8, 9]).unwrap().values, vec![Value::Int(8), Value::Null]);
+        assert_eq!(Tuple::new(vec![]).encode(&[]).unwrap(), Vec::<u8>::new());
+    }
+
+    #[test]
+    fn test_decode_columns() {
+        let tuple = Tuple::new(vec![Value::Int(1), Value::Null, Value::Varchar("abc".into()), Value::Bool(true)]);
+        let bytes = tuple.encode(&schema()).unwrap();
+
+        assert_eq!(Tuple::decode_columns(&bytes, &schema(), &[0, 3]).unwrap().values, vec![Value::Int(1), Value::Bool(true)]);
+        assert_eq!(Tuple::decode_columns(&bytes, &schema(), &[1, 2]).unwrap().values, vec![Value::Null, Value::Varchar("abc".into())]);
+        assert_eq!(Tuple::decode_columns(&bytes, &schema(), &[]).unwrap().values, vec![]);
+        assert_eq!(Tuple::decode_columns(&bytes[..bytes.len() - 1], &schema(), &[0]), Err(TupleError::Corrupt));
+    }
+}
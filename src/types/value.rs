@@ -0,0 +1,169 @@
+use super::temporal::{self, Interval};
+use super::{DataType, Decimal};
+
+/// A single SQL value.
+///
+/// `Null` has no type of its own and fits a column of any type.
+///
+/// # Examples
+///
+/// ```
+/// use gondor_rdbms::types::{DataType, Value};
+///
+/// let value = Value::Varchar("Frodo".to_string());
+/// assert!(value.fits(DataType::Varchar(Some(10))));
+/// assert!(!value.fits(DataType::Varchar(Some(3))));
+/// assert!(Value::Null.fits(DataType::Int));
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Value {
+    Null,
+    Int(i32),
+    BigInt(i64),
+    Varchar(String),
+    Bool(bool),
+    Numeric(Decimal),
+    /// days since 1970-01-01
+    Date(i32),
+    /// microseconds since midnight
+    Time(i64),
+    /// microseconds since 1970-01-01 00:00:00
+    Timestamp(i64),
+    Interval(Interval),
+}
+
+impl Value {
+    pub fn is_null(&self) -> bool {
+        matches!(self, Value::Null)
+    }
+
+    /// Type of the value, `None` for `Null`.
+    pub fn data_type(&self) -> Option<DataType> {
+        match self {
+            Value::Null => None,
+            Value::Int(_) => Some(DataType::Int),
+            Value::BigInt(_) => Some(DataType::BigInt),
+            Value::Varchar(_) => Some(DataType::Varchar(None)),
+            Value::Bool(_) => Some(DataType::Bool),
+            Value::Numeric(_) => Some(DataType::Numeric(None)),
+            Value::Date(_) => Some(DataType::Date),
+            Value::Time(_) => Some(DataType::Time),
+            Value::Timestamp(_) => Some(DataType::Timestamp),
+            Value::Interval(_) => Some(DataType::Interval),
+        }
+    }
+
+    /// The number as a decimal, `None` if it isn't a number.
+    pub fn to_decimal(&self) -> Option<Decimal> {
+        match self {
+            Value::Int(value) => Some(Decimal::from(*value)),
+            Value::BigInt(value) => Some(Decimal::from(*value)),
+            Value::Numeric(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    /// Whether the value can be stored in a column of type `data_type` as is,
+    /// including the length limit of a `VARCHAR(n)` and the precision and scale
+    /// of a `NUMERIC(p, s)`.
+    pub fn fits(&self, data_type: DataType) -> bool {
+        match (self, data_type) {
+            (Value::Null, _) => true,
+            (Value::Int(_), DataType::Int) => true,
+            (Value::BigInt(_), DataType::BigInt) => true,
+            (Value::Bool(_), DataType::Bool) => true,
+            (Value::Varchar(_), DataType::Varchar(None)) => true,
+            (Value::Varchar(text), DataType::Varchar(Some(length))) => text.chars().count() <= length as usize,
+            (Value::Numeric(_), DataType::Numeric(None)) => true,
+            (Value::Numeric(value), DataType::Numeric(Some((precision, scale)))) => value.fits(precision, scale),
+            (Value::Date(_), DataType::Date) => true,
+            (Value::Time(_), DataType::Time) => true,
+            (Value::Timestamp(_), DataType::Timestamp) => true,
+            (Value::Interval(_), DataType::Interval) => true,
+            _ => false,
+        }
+    }
+
+    /// The value of type `data_type` that `text` spells, the way PostgreSQL
+    /// reads values written as text, or `None` if it isn't one. Rounds a number
+    /// to the scale of a `NUMERIC(p, s)`, but leaves its precision, and the
+    /// length limit of a `VARCHAR(n)`, for `fits` to check. Dates and times are
+    /// read in ISO 8601, see `types::temporal`.
+    pub fn parse(text: &str, data_type: DataType) -> Option<Value> {
+        let value = match data_type {
+            DataType::Bool => match text.trim().to_ascii_lowercase().as_str() {
+                "t" | "true" | "y" | "yes" | "on" | "1" => Value::Bool(true),
+                "f" | "false" | "n" | "no" | "off" | "0" => Value::Bool(false),
+                _ => return None,
+            },
+            DataType::Int => Value::Int(text.trim().parse().ok()?),
+            DataType::BigInt => Value::BigInt(text.trim().parse().ok()?),
+            DataType::Varchar(_) => Value::Varchar(text.to_string()),
+            DataType::Numeric(None) => Value::Numeric(Decimal::parse(text.trim())?),
+            DataType::Numeric(Some((_, scale))) => Value::Numeric(Decimal::parse(text.trim())?.rescale(scale)?),
+            DataType::Date => Value::Date(temporal::parse_date(text)?),
+            DataType::Time => Value::Time(temporal::parse_time(text)?),
+            DataType::Timestamp => Value::Timestamp(temporal::parse_timestamp(text)?),
+            DataType::Interval => Value::Interval(Interval::parse(text)?),
+        };
+        Some(value)
+    }
+}
+
+impl std::fmt::Display for Value {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Value::Null => write!(f, "NULL"),
+            Value::Int(value) => write!(f, "{value}"),
+            Value::BigInt(value) => write!(f, "{value}"),
+            Value::Varchar(value) => write!(f, "{value}"),
+            Value::Bool(value) => write!(f, "{value}"),
+            Value::Numeric(value) => write!(f, "{value}"),
+            Value::Date(days) => write!(f, "{}", temporal::format_date(*days)),
+            Value::Time(micros) => write!(f, "{}", temporal::format_time(*micros)),
+            Value::Timestamp(micros) => write!(f, "{}", temporal::format_timestamp(*micros)),
+            Value::Interval(interval) => write!(f, "{interval}"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fits() {
+        assert!(Value::Int(1).fits(DataType::Int));
+        assert!(!Value::Int(1).fits(DataType::BigInt));
+        assert!(!Value::Bool(true).fits(DataType::Varchar(None)));
+        // the limit counts characters, not bytes
+        assert!(Value::Varchar("ëëë".into()).fits(DataType::Varchar(Some(3))));
+        assert!(!Value::Varchar("abcd".into()).fits(DataType::Varchar(Some(3))));
+        assert_eq!(Value::Null.data_type(), None);
+        assert_eq!(Value::BigInt(5).data_type(), Some(DataType::BigInt));
+
+        let price = Value::Numeric(Decimal::parse("12.50").unwrap());
+        assert!(price.fits(DataType::Numeric(None)));
+        assert!(price.fits(DataType::Numeric(Some((4, 2)))));
+        assert!(!price.fits(DataType::Numeric(Some((3, 2)))));
+        assert!(!price.fits(DataType::Numeric(Some((5, 1)))));
+    }
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(Value::parse(" 42 ", DataType::Int), Some(Value::Int(42)));
+        assert_eq!(Value::parse("4000000000", DataType::Int), None);
+        assert_eq!(Value::parse("4000000000", DataType::BigInt), Some(Value::BigInt(4_000_000_000)));
+        assert_eq!(Value::parse("Yes", DataType::Bool), Some(Value::Bool(true)));
+        assert_eq!(Value::parse("f", DataType::Bool), Some(Value::Bool(false)));
+        assert_eq!(Value::parse("maybe", DataType::Bool), None);
+        assert_eq!(Value::parse(" 2.675 ", DataType::Numeric(Some((5, 2)))), Some(Value::Numeric(Decimal::parse("2.68").unwrap())));
+        assert_eq!(Value::parse("-1e3", DataType::Numeric(None)), None);
+        for (text, data_type) in [("2024-02-29", DataType::Date), ("12:30:00.5", DataType::Time), ("2024-02-29 12:30:00", DataType::Timestamp), ("1 day 02:00:00", DataType::Interval)] {
+            assert_eq!(Value::parse(text, data_type).unwrap().to_string(), text);
+        }
+        assert_eq!(Value::parse("2024-02-30", DataType::Date), None);
+        // text is kept as is, spaces and all
+        assert_eq!(Value::parse(" frodo ", DataType::Varchar(Some(3))), Some(Value::Varchar(" frodo ".into())));
+    }
+}
@@ -0,0 +1,180 @@
+use crate::storage::{BufferPool, FreeSpaceMap, HeapError, HeapFile};
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+/// What vacuuming a heap file found and did, see `vacuum_heap`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct VacuumStats {
+    /// data pages looked at
+    pub pages: usize,
+    /// pages that had dead space and were compacted
+    pub pages_compacted: usize,
+    /// bytes of room left on the pages afterwards, all of it reusable
+    pub free_bytes: usize,
+}
+
+impl std::ops::AddAssign for VacuumStats {
+    fn add_assign(&mut self, other: Self) {
+        self.pages += other.pages;
+        self.pages_compacted += other.pages_compacted;
+        self.free_bytes += other.free_bytes;
+    }
+}
+
+/// Reclaims the space deletes and relocating updates left behind in `heap`.
+///
+/// Deleted tuples are gone from their pages the moment they're deleted (there
+/// are no older versions of a row to keep around for anyone), and so are their
+/// index entries, but their bytes stay where they were until the page is
+/// compacted, and the heap file never inserts into any page but its last one
+/// again. So this walks the data pages, compacts those with dead space, and
+/// records the room on every page in `free_space_map`, where inserts find it.
+///
+/// Compacting moves tuples within their page without changing their slots, so
+/// it isn't logged, like the compaction inserts do on their own. It mustn't run
+/// while a transaction that changed the heap file hasn't ended, as a rollback
+/// could then need the room it hands out.
+///
+/// # Examples
+///
+/// ```
+/// use gondor_rdbms::storage::{BufferPool, DiskManager, FreeSpaceMap, HeapFile};
+/// use gondor_rdbms::transaction::Transaction;
+/// use gondor_rdbms::vacuum;
+/// use std::sync::{Arc, Mutex};
+///
+/// let dir = tempfile::tempdir().unwrap();
+/// let disk_manager = DiskManager::open(dir.path().join("example.db")).unwrap();
+/// let buffer_pool = Arc::new(Mutex::new(BufferPool::new(disk_manager)));
+/// let mut heap = HeapFile::create(buffer_pool.clone()).unwrap();
+///
+/// let mut txn = Transaction::new(1);
+/// let rids: Vec<_> = (0..100).map(|_| heap.insert(&mut txn, &[0; 100]).unwrap()).collect();
+/// for &rid in &rids[..50] {
+///     heap.delete(&mut txn, rid).unwrap();
+/// }
+///
+/// let free_space_map = Mutex::new(FreeSpaceMap::new());
+/// let stats = vacuum::vacuum_heap(&buffer_pool, &heap, &free_space_map).unwrap();
+/// assert!(stats.pages_compacted > 0);
+/// assert!(free_space_map.lock().unwrap().find(heap.root_page_id(), 100).is_some());
+/// ```
+pub fn vacuum_heap(buffer_pool: &Mutex<BufferPool>, heap: &HeapFile, free_space_map: &Mutex<FreeSpaceMap>) -> Result<VacuumStats, HeapError> {
+    let mut stats = VacuumStats::default();
+    for &page_id in heap.page_ids() {
+        // a page at a time, so statements waiting on the buffer pool get a look in
+        let mut buffer_pool = buffer_pool.lock().unwrap();
+        let header = buffer_pool.get_page(page_id)?.get_header();
+        if header.free_space_total > header.offset_end_free_space - header.offset_begin_free_space {
+            buffer_pool.get_page_mut(page_id)?.compact();
+            stats.pages_compacted += 1;
+        }
+        let free = buffer_pool.get_page(page_id)?.get_header().free_space_total;
+        free_space_map.lock().unwrap().record(heap.root_page_id(), page_id, free);
+        stats.pages += 1;
+        stats.free_bytes += free as usize;
+    }
+    Ok(stats)
+}
+
+/// Calls `vacuum` every `interval` on a background thread, until the returned
+/// worker is dropped.
+pub fn start(interval: Duration, mut vacuum: impl FnMut() + Send + 'static) -> VacuumWorker {
+    let stop = Arc::new((Mutex::new(false), Condvar::new()));
+    let thread_stop = stop.clone();
+    let thread = std::thread::spawn(move || {
+        let (stopped, condvar) = &*thread_stop;
+        loop {
+            let stopped = condvar.wait_timeout_while(stopped.lock().unwrap(), interval, |stopped| !*stopped).unwrap().0;
+            if *stopped {
+                break;
+            }
+            // not holding the flag, dropping the worker from `vacuum` mustn't wait on it
+            drop(stopped);
+            vacuum();
+        }
+    });
+
+    VacuumWorker { stop, thread: Some(thread) }
+}
+
+/// Background thread vacuuming periodically, see `start`. Dropping it stops
+/// the thread, waiting for a vacuum in progress to finish.
+pub struct VacuumWorker {
+    /// set to stop the thread, with the condvar it sleeps on between runs
+    stop: Arc<(Mutex<bool>, Condvar)>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl Drop for VacuumWorker {
+    fn drop(&mut self) {
+        let (stopped, condvar) = &*self.stop;
+        *stopped.lock().unwrap() = true;
+        condvar.notify_all();
+        if let Some(thread) = self.thread.take() {
+            // dropped by its own `vacuum`, which then returns into the loop and sees the flag
+            if thread.thread().id() != std::thread::current().id() {
+                let _ = thread.join();
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::DiskManager;
+    use crate::transaction::Transaction;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_vacuum_heap() {
+        let dir = TempDir::new().unwrap();
+        let buffer_pool = Arc::new(Mutex::new(BufferPool::new(DiskManager::open(dir.path().join("test.db")).unwrap())));
+        let mut heap = HeapFile::create(buffer_pool.clone()).unwrap();
+        let mut txn = Transaction::new(1);
+        let rids: Vec<_> = (0..200u8).map(|i| heap.insert(&mut txn, &[i; 100]).unwrap()).collect();
+        let pages = heap.page_ids().len();
+        // every other row of the first half
+        for &rid in rids[..100].iter().step_by(2) {
+            heap.delete(&mut txn, rid).unwrap();
+        }
+
+        let free_space_map = Arc::new(Mutex::new(FreeSpaceMap::new()));
+        let stats = vacuum_heap(&buffer_pool, &heap, &free_space_map).unwrap();
+        assert_eq!(stats.pages, pages);
+        assert!(stats.pages_compacted >= 2 && stats.pages_compacted < pages);
+        assert!(stats.free_bytes >= 50 * 100);
+        // nothing left to compact the second time round
+        assert_eq!(vacuum_heap(&buffer_pool, &heap, &free_space_map).unwrap().pages_compacted, 0);
+
+        // the rows that are left are where they were, and new ones fill the gaps
+        heap.set_free_space_map(free_space_map);
+        for (i, &rid) in rids.iter().enumerate().skip(1).step_by(2) {
+            assert_eq!(heap.get(rid).unwrap(), [i as u8; 100]);
+        }
+        for _ in 0..50 {
+            heap.insert(&mut txn, &[0xFF; 100]).unwrap();
+        }
+        assert_eq!(heap.page_ids().len(), pages);
+        assert_eq!(heap.scan().count(), 200);
+    }
+
+    #[test]
+    fn test_worker_runs_until_dropped() {
+        let runs = Arc::new(AtomicUsize::new(0));
+        let counter = runs.clone();
+        let worker = start(Duration::from_millis(5), move || {
+            counter.fetch_add(1, Ordering::Relaxed);
+        });
+        while runs.load(Ordering::Relaxed) < 3 {
+            std::thread::sleep(Duration::from_millis(5));
+        }
+        drop(worker);
+        let after = runs.load(Ordering::Relaxed);
+        std::thread::sleep(Duration::from_millis(20));
+        assert_eq!(runs.load(Ordering::Relaxed), after);
+    }
+}
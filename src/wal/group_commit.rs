@@ -0,0 +1,155 @@
+use super::{LogManager, Lsn, WalError};
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::Duration;
+
+/// Makes commit records durable in batches, so transactions committing at the
+/// same time share a single fsync instead of queueing up for one each.
+///
+/// A committer appends its commit record, lets go of the log, and waits with
+/// `wait_for`. The first waiter to find no flush in progress becomes the
+/// leader: it waits out the commit window so other committers can append
+/// their records too, then flushes the log once for all of them. Everyone
+/// whose record made it into that flush returns; anyone who arrived too late
+/// waits for the next one.
+///
+/// A zero window adds no latency; commits that arrive while a flush is running
+/// are still batched into the next one.
+///
+/// # Examples
+///
+/// ```
+/// use gondor_rdbms::wal::{GroupCommit, LogManager, LogRecordBody, INVALID_LSN};
+/// use std::sync::{Arc, Mutex};
+/// use std::time::Duration;
+///
+/// let dir = tempfile::tempdir().unwrap();
+/// let log_manager = Arc::new(Mutex::new(LogManager::open(dir.path().join("example.wal")).unwrap()));
+/// let group_commit = GroupCommit::new(log_manager.clone(), Duration::from_millis(1));
+///
+/// let commit = log_manager.lock().unwrap().append(1, INVALID_LSN, LogRecordBody::Commit);
+/// group_commit.wait_for(commit).unwrap();
+/// assert_eq!(log_manager.lock().unwrap().flushed_lsn(), commit);
+/// ```
+#[derive(Debug)]
+pub struct GroupCommit {
+    log_manager: Arc<Mutex<LogManager>>,
+    /// how long a leader waits for more commits before flushing
+    window: Duration,
+    state: Mutex<GroupState>,
+    /// signalled whenever a flush finishes
+    flushed: Condvar,
+}
+
+#[derive(Debug, Default)]
+struct GroupState {
+    /// a leader is waiting out the window or flushing
+    flushing: bool,
+    /// number of flushes leaders have done
+    flush_count: u64,
+}
+
+impl GroupCommit {
+    pub fn new(log_manager: Arc<Mutex<LogManager>>, window: Duration) -> Self {
+        Self {
+            log_manager,
+            window,
+            state: Mutex::new(GroupState::default()),
+            flushed: Condvar::new(),
+        }
+    }
+
+    pub fn window(&self) -> Duration {
+        self.window
+    }
+
+    /// Number of flushes done on behalf of waiters so far, each covering every
+    /// commit record appended before it started.
+    pub fn flush_count(&self) -> u64 {
+        self.state.lock().unwrap().flush_count
+    }
+
+    /// Returns once every log record up to and including `lsn` is on stable
+    /// storage, flushing the log (for everyone waiting) if needed.
+    ///
+    /// The caller must not hold the log lock, or nobody could flush.
+    pub fn wait_for(&self, lsn: Lsn) -> Result<(), WalError> {
+        let mut state = self.state.lock().unwrap();
+        loop {
+            if self.log_manager.lock().unwrap().flushed_lsn() >= lsn {
+                return Ok(());
+            }
+            if state.flushing {
+                state = self.flushed.wait(state).unwrap();
+                continue;
+            }
+
+            // nobody is flushing, so this waiter leads the next group
+            state.flushing = true;
+            drop(state);
+            if !self.window.is_zero() {
+                std::thread::sleep(self.window);
+            }
+            let result = self.log_manager.lock().unwrap().flush();
+
+            state = self.state.lock().unwrap();
+            state.flushing = false;
+            state.flush_count += 1;
+            self.flushed.notify_all();
+            // on failure the followers wake up and one of them tries again
+            result?;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::wal::{INVALID_LSN, LogRecordBody};
+    use tempfile::TempDir;
+
+    fn open(dir: &TempDir) -> Arc<Mutex<LogManager>> {
+        Arc::new(Mutex::new(LogManager::open(dir.path().join("test.wal")).unwrap()))
+    }
+
+    #[test]
+    fn test_concurrent_commits_share_flushes() {
+        let dir = TempDir::new().unwrap();
+        let log_manager = open(&dir);
+        let group_commit = Arc::new(GroupCommit::new(log_manager.clone(), Duration::from_millis(50)));
+
+        let committers: Vec<_> = (1..=8)
+            .map(|txn_id| {
+                let log_manager = log_manager.clone();
+                let group_commit = group_commit.clone();
+                std::thread::spawn(move || {
+                    let lsn = log_manager.lock().unwrap().append(txn_id, INVALID_LSN, LogRecordBody::Commit);
+                    group_commit.wait_for(lsn).unwrap();
+                    // durable by the time the commit returns
+                    assert!(log_manager.lock().unwrap().flushed_lsn() >= lsn);
+                })
+            })
+            .collect();
+        for committer in committers {
+            committer.join().unwrap();
+        }
+
+        assert_eq!(log_manager.lock().unwrap().flushed_lsn(), 8);
+        assert!(group_commit.flush_count() < 8, "{} flushes for 8 commits", group_commit.flush_count());
+    }
+
+    #[test]
+    fn test_already_flushed_records_need_no_flush() {
+        let dir = TempDir::new().unwrap();
+        let log_manager = open(&dir);
+        let group_commit = GroupCommit::new(log_manager.clone(), Duration::ZERO);
+
+        let lsn = log_manager.lock().unwrap().append(1, INVALID_LSN, LogRecordBody::Commit);
+        log_manager.lock().unwrap().flush().unwrap();
+        group_commit.wait_for(lsn).unwrap();
+        assert_eq!(group_commit.flush_count(), 0);
+
+        let lsn = log_manager.lock().unwrap().append(2, INVALID_LSN, LogRecordBody::Commit);
+        group_commit.wait_for(lsn).unwrap();
+        assert_eq!(group_commit.flush_count(), 1);
+    }
+}
@@ -0,0 +1,401 @@
+use super::{ActiveTxn, INVALID_LSN, LogRecord, LogRecordBody, Lsn, TxnId};
+use crate::storage::SyncMode;
+use std::collections::HashMap;
+use std::ffi::OsString;
+use std::fs::{File, OpenOptions};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+#[derive(Debug)]
+pub enum WalError {
+    IoError(std::io::Error),
+}
+
+impl std::fmt::Display for WalError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            WalError::IoError(error) => write!(f, "I/O error: {error}"),
+        }
+    }
+}
+
+impl std::error::Error for WalError {}
+
+impl From<std::io::Error> for WalError {
+    fn from(error: std::io::Error) -> Self {
+        WalError::IoError(error)
+    }
+}
+
+/// Appends log records to the write-ahead log file.
+///
+/// Appended records are buffered in memory and only reach the disk when the
+/// log is forced with `flush_to_lsn`. The write-ahead rule is that a page may
+/// only be written to the database file once every record describing a change
+/// to it has been flushed, and a transaction only counts as committed once its
+/// commit record has been flushed.
+///
+/// # Examples
+///
+/// ```
+/// use gondor_rdbms::wal::{LogManager, LogRecordBody, INVALID_LSN};
+///
+/// let dir = tempfile::tempdir().unwrap();
+/// let mut log_manager = LogManager::open(dir.path().join("example.wal")).unwrap();
+///
+/// let begin = log_manager.append(1, INVALID_LSN, LogRecordBody::Begin);
+/// let commit = log_manager.append(1, begin, LogRecordBody::Commit);
+/// log_manager.flush_to_lsn(commit).unwrap();
+/// assert_eq!(log_manager.flushed_lsn(), commit);
+/// ```
+#[derive(Debug)]
+pub struct LogManager {
+    path: PathBuf,
+    file: File,
+    /// how flushes force the log to stable storage
+    sync_mode: SyncMode,
+    /// encoded records appended since the last flush
+    buffer: Vec<u8>,
+    /// LSN handed to the next appended record
+    next_lsn: Lsn,
+    /// every record up to and including this LSN is on stable storage
+    flushed_lsn: Lsn,
+    /// transactions with records in the log but no commit or abort yet
+    active_txns: HashMap<TxnId, ActiveTxn>,
+    /// highest transaction id the log has seen, including ids truncated away
+    max_txn_id: TxnId,
+}
+
+impl LogManager {
+    /// Opens the log file at `path`, creating an empty one if it doesn't exist.
+    ///
+    /// A record left half written by a crash is cut off the end of the log;
+    /// it was never flushed, so nobody can have relied on it.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, WalError> {
+        let path = path.as_ref().to_path_buf();
+        let mut file = OpenOptions::new().read(true).append(true).create(true).open(&path)?;
+
+        let mut contents = Vec::new();
+        file.read_to_end(&mut contents)?;
+        let (records, valid_length) = parse_records(&contents);
+        if valid_length < contents.len() {
+            file.set_len(valid_length as u64)?;
+        }
+
+        let last_lsn = records.last().map_or(INVALID_LSN, |record| record.lsn);
+        let mut log_manager = Self {
+            path,
+            file,
+            sync_mode: SyncMode::default(),
+            buffer: Vec::new(),
+            next_lsn: last_lsn + 1,
+            flushed_lsn: last_lsn,
+            active_txns: HashMap::new(),
+            max_txn_id: 0,
+        };
+        for record in &records {
+            log_manager.track(record);
+        }
+        Ok(log_manager)
+    }
+
+    pub fn sync_mode(&self) -> SyncMode {
+        self.sync_mode
+    }
+
+    /// Changes how flushes force the log to stable storage, `SyncMode::Fdatasync`
+    /// unless set otherwise. Buffered records are flushed under the old mode
+    /// first, and the file is reopened if the new one needs other flags.
+    pub fn set_sync_mode(&mut self, sync_mode: SyncMode) -> Result<(), WalError> {
+        self.flush()?;
+        self.file = sync_mode.apply(OpenOptions::new().read(true).append(true)).open(&self.path)?;
+        self.sync_mode = sync_mode;
+        Ok(())
+    }
+
+    /// Appends a record to the log buffer and returns its LSN.
+    ///
+    /// `prev_lsn` is the LSN of the transaction's previous record, or
+    /// `INVALID_LSN` if this is its first one. The record isn't durable until
+    /// it has been flushed.
+    pub fn append(&mut self, txn_id: TxnId, prev_lsn: Lsn, body: LogRecordBody) -> Lsn {
+        let lsn = self.next_lsn;
+        let record = LogRecord { lsn, prev_lsn, txn_id, body };
+        self.buffer.extend_from_slice(&record.to_bytes());
+        self.track(&record);
+        self.next_lsn += 1;
+        lsn
+    }
+
+    /// keeps the active transaction table and highest transaction id up to date with `record`
+    fn track(&mut self, record: &LogRecord) {
+        match &record.body {
+            LogRecordBody::Checkpoint { max_txn_id, active_txns, .. } => {
+                self.max_txn_id = self.max_txn_id.max(*max_txn_id);
+                for txn in active_txns {
+                    self.active_txns.entry(txn.txn_id).or_insert(*txn);
+                }
+            }
+            LogRecordBody::Commit | LogRecordBody::Abort => {
+                self.active_txns.remove(&record.txn_id);
+            }
+            _ => {
+                let txn = self.active_txns.entry(record.txn_id).or_insert(ActiveTxn {
+                    txn_id: record.txn_id,
+                    first_lsn: record.lsn,
+                    last_lsn: record.lsn,
+                });
+                txn.last_lsn = record.lsn;
+            }
+        }
+        self.max_txn_id = self.max_txn_id.max(record.txn_id);
+    }
+
+    /// Forces every record up to and including `lsn` to stable storage.
+    ///
+    /// Records are flushed in order, so this also flushes anything appended
+    /// after `lsn`. It's a no-op if `lsn` was already flushed.
+    pub fn flush_to_lsn(&mut self, lsn: Lsn) -> Result<(), WalError> {
+        if lsn <= self.flushed_lsn || self.buffer.is_empty() {
+            return Ok(());
+        }
+
+        self.file.write_all(&self.buffer)?;
+        self.sync_mode.sync(&self.file)?;
+        self.buffer.clear();
+        self.flushed_lsn = self.last_lsn();
+        Ok(())
+    }
+
+    /// Flushes every record appended so far.
+    pub fn flush(&mut self) -> Result<(), WalError> {
+        self.flush_to_lsn(self.last_lsn())
+    }
+
+    /// LSN of the most recently appended record, `INVALID_LSN` if the log is empty.
+    pub fn last_lsn(&self) -> Lsn {
+        self.next_lsn - 1
+    }
+
+    pub fn flushed_lsn(&self) -> Lsn {
+        self.flushed_lsn
+    }
+
+    /// Transactions that have logged records but haven't committed or aborted, by id.
+    pub fn active_txns(&self) -> Vec<ActiveTxn> {
+        let mut active_txns: Vec<ActiveTxn> = self.active_txns.values().copied().collect();
+        active_txns.sort_by_key(|txn| txn.txn_id);
+        active_txns
+    }
+
+    /// Highest transaction id ever logged, 0 if there is none. Survives truncation
+    /// through the checkpoint records.
+    pub fn max_txn_id(&self) -> TxnId {
+        self.max_txn_id
+    }
+
+    /// Drops every record older than `lsn` from the log file, once nothing can
+    /// need them any more: their pages are on disk and their transactions have
+    /// finished.
+    ///
+    /// The most recent record is always kept so LSNs carry on where they left
+    /// off after a restart. The retained records are written to a new file that
+    /// replaces the old one, so a crash part way through leaves either log intact.
+    pub fn truncate(&mut self, lsn: Lsn) -> Result<(), WalError> {
+        self.flush()?;
+        let lsn = lsn.min(self.last_lsn());
+
+        let mut retained = Vec::new();
+        for record in self.read_records()?.iter().filter(|record| record.lsn >= lsn) {
+            retained.extend_from_slice(&record.to_bytes());
+        }
+
+        let mut temp_path = OsString::from(&self.path);
+        temp_path.push(".tmp");
+        let mut temp_file = File::create(&temp_path)?;
+        temp_file.write_all(&retained)?;
+        // the rename must not be able to replace the old log with a half written new one
+        if self.sync_mode != SyncMode::Off {
+            temp_file.sync_all()?;
+        }
+        std::fs::rename(&temp_path, &self.path)?;
+        self.file = self.sync_mode.apply(OpenOptions::new().read(true).append(true)).open(&self.path)?;
+        Ok(())
+    }
+
+    /// Reads back every record that has been flushed, oldest first.
+    pub fn read_records(&self) -> Result<Vec<LogRecord>, WalError> {
+        let mut file = &self.file;
+        let mut contents = Vec::new();
+        file.seek(SeekFrom::Start(0))?;
+        file.read_to_end(&mut contents)?;
+        Ok(parse_records(&contents).0)
+    }
+}
+
+/// decodes records until the bytes run out or stop making sense, returning them
+/// along with the length of the valid prefix
+fn parse_records(contents: &[u8]) -> (Vec<LogRecord>, usize) {
+    let mut records = Vec::new();
+    let mut position = 0;
+    while let Some(record) = LogRecord::from_bytes(&contents[position..]) {
+        // from_bytes only succeeds if the whole prefixed length was there
+        position += LogRecord::encoded_length(&contents[position..]).unwrap();
+        records.push(record);
+    }
+    (records, position)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::{PageType, RecordId};
+    use tempfile::NamedTempFile;
+
+    #[test]
+    fn test_append_assigns_increasing_lsns() {
+        let file = NamedTempFile::new().unwrap();
+        let mut log_manager = LogManager::open(file.path()).unwrap();
+        assert_eq!(log_manager.last_lsn(), INVALID_LSN);
+
+        let first = log_manager.append(1, INVALID_LSN, LogRecordBody::Begin);
+        let second = log_manager.append(1, first, LogRecordBody::AllocatePage { page_id: 0, page_type: PageType::Heap });
+        assert_eq!((first, second), (1, 2));
+        assert_eq!(log_manager.last_lsn(), 2);
+    }
+
+    #[test]
+    fn test_records_not_durable_until_flushed() {
+        let file = NamedTempFile::new().unwrap();
+        let mut log_manager = LogManager::open(file.path()).unwrap();
+
+        let begin = log_manager.append(1, INVALID_LSN, LogRecordBody::Begin);
+        let insert = log_manager.append(1, begin, LogRecordBody::Insert { rid: RecordId::new(0, 0), tuple: b"Hello".to_vec() });
+        assert_eq!(log_manager.flushed_lsn(), INVALID_LSN);
+        assert!(log_manager.read_records().unwrap().is_empty());
+
+        // flushing the first record takes the second along with it
+        log_manager.flush_to_lsn(begin).unwrap();
+        assert_eq!(log_manager.flushed_lsn(), insert);
+        let records = log_manager.read_records().unwrap();
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[1].prev_lsn, begin);
+    }
+
+    #[test]
+    fn test_reopen_continues_after_last_record() {
+        let file = NamedTempFile::new().unwrap();
+        {
+            let mut log_manager = LogManager::open(file.path()).unwrap();
+            log_manager.append(1, INVALID_LSN, LogRecordBody::Begin);
+            log_manager.append(1, 1, LogRecordBody::Commit);
+            log_manager.flush().unwrap();
+            // appended but never flushed, lost in the "crash"
+            log_manager.append(2, INVALID_LSN, LogRecordBody::Begin);
+        }
+
+        let mut log_manager = LogManager::open(file.path()).unwrap();
+        assert_eq!(log_manager.flushed_lsn(), 2);
+        assert_eq!(log_manager.append(2, INVALID_LSN, LogRecordBody::Begin), 3);
+    }
+
+    #[test]
+    fn test_every_sync_mode_keeps_flushed_records() {
+        for sync_mode in [SyncMode::Fsync, SyncMode::Fdatasync, SyncMode::ODsync, SyncMode::Off] {
+            let file = NamedTempFile::new().unwrap();
+            {
+                let mut log_manager = LogManager::open(file.path()).unwrap();
+                log_manager.append(1, INVALID_LSN, LogRecordBody::Begin);
+                log_manager.set_sync_mode(sync_mode).unwrap();
+                // switching modes flushed the buffered record
+                assert_eq!(log_manager.flushed_lsn(), 1);
+                log_manager.append(1, 1, LogRecordBody::Commit);
+                log_manager.flush().unwrap();
+                log_manager.truncate(2).unwrap();
+                log_manager.append(2, INVALID_LSN, LogRecordBody::Begin);
+                log_manager.flush().unwrap();
+            }
+
+            let log_manager = LogManager::open(file.path()).unwrap();
+            let lsns: Vec<Lsn> = log_manager.read_records().unwrap().iter().map(|record| record.lsn).collect();
+            assert_eq!(lsns, vec![2, 3], "{sync_mode:?}");
+        }
+    }
+
+    #[test]
+    fn test_tracks_active_txns() {
+        let file = NamedTempFile::new().unwrap();
+        let mut log_manager = LogManager::open(file.path()).unwrap();
+        let begin = log_manager.append(1, INVALID_LSN, LogRecordBody::Begin);
+        log_manager.append(2, INVALID_LSN, LogRecordBody::Begin);
+        let insert = log_manager.append(1, begin, LogRecordBody::Insert { rid: RecordId::new(0, 0), tuple: b"Hello".to_vec() });
+        log_manager.append(2, 2, LogRecordBody::Commit);
+        assert_eq!(log_manager.active_txns(), vec![ActiveTxn { txn_id: 1, first_lsn: begin, last_lsn: insert }]);
+        assert_eq!(log_manager.max_txn_id(), 2);
+
+        // rebuilt from the log on reopen
+        log_manager.flush().unwrap();
+        let log_manager = LogManager::open(file.path()).unwrap();
+        assert_eq!(log_manager.active_txns(), vec![ActiveTxn { txn_id: 1, first_lsn: begin, last_lsn: insert }]);
+    }
+
+    #[test]
+    fn test_truncate_keeps_later_records() {
+        let file = NamedTempFile::new().unwrap();
+        let mut log_manager = LogManager::open(file.path()).unwrap();
+        for txn_id in 1..=3 {
+            let begin = log_manager.append(txn_id, INVALID_LSN, LogRecordBody::Begin);
+            log_manager.append(txn_id, begin, LogRecordBody::Commit);
+        }
+        let checkpoint = log_manager.append(0, INVALID_LSN, LogRecordBody::Checkpoint { max_txn_id: 3, active_txns: Vec::new(), dirty_pages: Vec::new() });
+        log_manager.flush().unwrap();
+        let full_length = std::fs::metadata(file.path()).unwrap().len();
+
+        log_manager.truncate(checkpoint).unwrap();
+        let records = log_manager.read_records().unwrap();
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0].lsn, checkpoint);
+        assert!(std::fs::metadata(file.path()).unwrap().len() < full_length);
+
+        // new records land in the truncated file, and ids and LSNs carry on after a reopen
+        log_manager.append(4, INVALID_LSN, LogRecordBody::Begin);
+        log_manager.flush().unwrap();
+        drop(log_manager);
+        let mut log_manager = LogManager::open(file.path()).unwrap();
+        assert_eq!(log_manager.read_records().unwrap().len(), 2);
+        assert_eq!(log_manager.max_txn_id(), 4);
+        assert_eq!(log_manager.append(4, checkpoint + 1, LogRecordBody::Commit), checkpoint + 2);
+
+        // the newest record survives even when everything is truncated
+        log_manager.truncate(Lsn::MAX).unwrap();
+        assert_eq!(log_manager.read_records().unwrap().len(), 1);
+        assert_eq!(log_manager.last_lsn(), checkpoint + 2);
+    }
+
+    #[test]
+    fn test_torn_tail_is_truncated() {
+        let file = NamedTempFile::new().unwrap();
+        {
+            let mut log_manager = LogManager::open(file.path()).unwrap();
+            log_manager.append(1, INVALID_LSN, LogRecordBody::Begin);
+            log_manager.flush().unwrap();
+        }
+        // half of a second record made it to disk before the crash
+        let torn = LogRecord { lsn: 2, prev_lsn: 1, txn_id: 1, body: LogRecordBody::Commit }.to_bytes();
+        let mut contents = std::fs::read(file.path()).unwrap();
+        let valid_length = contents.len();
+        contents.extend_from_slice(&torn[..torn.len() / 2]);
+        std::fs::write(file.path(), &contents).unwrap();
+
+        let mut log_manager = LogManager::open(file.path()).unwrap();
+        assert_eq!(std::fs::metadata(file.path()).unwrap().len(), valid_length as u64);
+        assert_eq!(log_manager.last_lsn(), 1);
+
+        // new records go after the last intact one
+        let commit = log_manager.append(1, 1, LogRecordBody::Commit);
+        log_manager.flush().unwrap();
+        let records = log_manager.read_records().unwrap();
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[1].lsn, commit);
+    }
+}
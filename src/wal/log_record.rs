@@ -0,0 +1,358 @@
+use crate::storage::{PageType, RecordId};
+use crate::storage::checksum::crc32;
+
+/// Log sequence number. LSNs are handed out in increasing order starting at 1.
+pub type Lsn = u64;
+
+/// Identifies the transaction a log record belongs to.
+pub type TxnId = u64;
+
+/// LSN that no record ever gets, used for "nothing logged yet" and the end of a `prev_lsn` chain.
+pub const INVALID_LSN: Lsn = 0;
+
+/// length prefix + lsn + prev_lsn + txn_id + kind
+const RECORD_HEADER_SIZE: usize = 4 + 8 + 8 + 8 + 1;
+
+/// trailing CRC-32 over everything before it
+const RECORD_CHECKSUM_SIZE: usize = 4;
+
+/// What a log record describes.
+#[derive(Debug, Clone, PartialEq)]
+pub enum LogRecordBody {
+    Begin,
+    Commit,
+    Abort,
+    /// `tuple` was inserted at `rid`
+    Insert { rid: RecordId, tuple: Vec<u8> },
+    /// the tuple at `rid` changed from `before` to `after`
+    Update { rid: RecordId, before: Vec<u8>, after: Vec<u8> },
+    /// `tuple` was deleted from `rid`; kept so the delete can be undone
+    Delete { rid: RecordId, tuple: Vec<u8> },
+    /// page `page_id` was added to the database file as a `page_type` page
+    AllocatePage { page_id: u32, page_type: PageType },
+    /// compensation log record (CLR) written while rolling back: `action` undid an
+    /// earlier change, and `undo_next_lsn` is the next record of the transaction
+    /// still left to undo
+    Compensation { undo_next_lsn: Lsn, action: Box<LogRecordBody> },
+    /// fuzzy checkpoint, written outside of any transaction: the transactions
+    /// running and the pages dirty in the buffer pool (with the first LSN that
+    /// may not have reached disk) when it was taken
+    Checkpoint { max_txn_id: TxnId, active_txns: Vec<ActiveTxn>, dirty_pages: Vec<(u32, Lsn)> },
+}
+
+/// A transaction that was running when a checkpoint was taken.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ActiveTxn {
+    pub txn_id: TxnId,
+    /// its first record, the log can't be truncated past this while it runs
+    pub first_lsn: Lsn,
+    /// its most recent record, where rolling it back starts
+    pub last_lsn: Lsn,
+}
+
+impl LogRecordBody {
+    fn kind(&self) -> u8 {
+        match self {
+            LogRecordBody::Begin => 1,
+            LogRecordBody::Commit => 2,
+            LogRecordBody::Abort => 3,
+            LogRecordBody::Insert { .. } => 4,
+            LogRecordBody::Update { .. } => 5,
+            LogRecordBody::Delete { .. } => 6,
+            LogRecordBody::AllocatePage { .. } => 7,
+            LogRecordBody::Compensation { .. } => 8,
+            LogRecordBody::Checkpoint { .. } => 9,
+        }
+    }
+
+    /// Record that this change touched, if it is a change to a tuple.
+    pub fn rid(&self) -> Option<RecordId> {
+        match self {
+            LogRecordBody::Insert { rid, .. } | LogRecordBody::Update { rid, .. } | LogRecordBody::Delete { rid, .. } => Some(*rid),
+            LogRecordBody::Compensation { action, .. } => action.rid(),
+            _ => None,
+        }
+    }
+
+    /// Page that this change touched, if any.
+    pub fn page_id(&self) -> Option<u32> {
+        match self {
+            LogRecordBody::AllocatePage { page_id, .. } => Some(*page_id),
+            _ => self.rid().map(|rid| rid.page_id),
+        }
+    }
+
+    fn encode(&self, bytes: &mut Vec<u8>) {
+        bytes.push(self.kind());
+        match self {
+            LogRecordBody::Begin | LogRecordBody::Commit | LogRecordBody::Abort => {}
+            LogRecordBody::Insert { rid, tuple } | LogRecordBody::Delete { rid, tuple } => {
+                bytes.extend_from_slice(&rid.to_bytes());
+                write_bytes(bytes, tuple);
+            }
+            LogRecordBody::Update { rid, before, after } => {
+                bytes.extend_from_slice(&rid.to_bytes());
+                write_bytes(bytes, before);
+                write_bytes(bytes, after);
+            }
+            LogRecordBody::AllocatePage { page_id, page_type } => {
+                bytes.extend_from_slice(&page_id.to_le_bytes());
+                bytes.push(page_type.tag());
+            }
+            LogRecordBody::Compensation { undo_next_lsn, action } => {
+                bytes.extend_from_slice(&undo_next_lsn.to_le_bytes());
+                action.encode(bytes);
+            }
+            LogRecordBody::Checkpoint { max_txn_id, active_txns, dirty_pages } => {
+                bytes.extend_from_slice(&max_txn_id.to_le_bytes());
+                bytes.extend_from_slice(&(active_txns.len() as u32).to_le_bytes());
+                for txn in active_txns {
+                    bytes.extend_from_slice(&txn.txn_id.to_le_bytes());
+                    bytes.extend_from_slice(&txn.first_lsn.to_le_bytes());
+                    bytes.extend_from_slice(&txn.last_lsn.to_le_bytes());
+                }
+                bytes.extend_from_slice(&(dirty_pages.len() as u32).to_le_bytes());
+                for (page_id, rec_lsn) in dirty_pages {
+                    bytes.extend_from_slice(&page_id.to_le_bytes());
+                    bytes.extend_from_slice(&rec_lsn.to_le_bytes());
+                }
+            }
+        }
+    }
+
+    fn decode(reader: &mut Reader) -> Option<Self> {
+        let body = match reader.read_u8()? {
+            1 => LogRecordBody::Begin,
+            2 => LogRecordBody::Commit,
+            3 => LogRecordBody::Abort,
+            4 => LogRecordBody::Insert { rid: reader.read_rid()?, tuple: reader.read_bytes()? },
+            5 => LogRecordBody::Update {
+                rid: reader.read_rid()?,
+                before: reader.read_bytes()?,
+                after: reader.read_bytes()?,
+            },
+            6 => LogRecordBody::Delete { rid: reader.read_rid()?, tuple: reader.read_bytes()? },
+            7 => LogRecordBody::AllocatePage {
+                page_id: reader.read_u32()?,
+                page_type: PageType::from_tag(reader.read_u8()?)?,
+            },
+            8 => {
+                let undo_next_lsn = reader.read_u64()?;
+                let action = Self::decode(reader)?;
+                // only tuple changes get compensated, and compensations are never undone themselves
+                action.rid()?;
+                if matches!(action, LogRecordBody::Compensation { .. }) {
+                    return None;
+                }
+                LogRecordBody::Compensation { undo_next_lsn, action: Box::new(action) }
+            }
+            9 => {
+                let max_txn_id = reader.read_u64()?;
+                // counts come from the (checksummed) record, but don't trust them with an allocation
+                let mut active_txns = Vec::new();
+                for _ in 0..reader.read_u32()? {
+                    active_txns.push(ActiveTxn { txn_id: reader.read_u64()?, first_lsn: reader.read_u64()?, last_lsn: reader.read_u64()? });
+                }
+                let mut dirty_pages = Vec::new();
+                for _ in 0..reader.read_u32()? {
+                    dirty_pages.push((reader.read_u32()?, reader.read_u64()?));
+                }
+                LogRecordBody::Checkpoint { max_txn_id, active_txns, dirty_pages }
+            }
+            _ => return None,
+        };
+        Some(body)
+    }
+}
+
+/// A single entry in the write-ahead log.
+///
+/// Records of the same transaction are chained through `prev_lsn`, newest to
+/// oldest, so a transaction's changes can be walked backwards when it has to be
+/// rolled back.
+///
+/// On disk a record is a little endian length prefix, the fixed header, the
+/// body and a CRC-32 of all of the above, so a record torn by a crash in the
+/// middle of a write is detected rather than replayed.
+///
+/// # Examples
+///
+/// ```
+/// use gondor_rdbms::storage::RecordId;
+/// use gondor_rdbms::wal::{LogRecord, LogRecordBody, INVALID_LSN};
+///
+/// let record = LogRecord {
+///     lsn: 1,
+///     prev_lsn: INVALID_LSN,
+///     txn_id: 7,
+///     body: LogRecordBody::Insert { rid: RecordId::new(0, 0), tuple: b"Hello, world!".to_vec() },
+/// };
+/// let bytes = record.to_bytes();
+/// assert_eq!(LogRecord::from_bytes(&bytes), Some(record));
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct LogRecord {
+    pub lsn: Lsn,
+    /// previous record of the same transaction, `INVALID_LSN` for its first record
+    pub prev_lsn: Lsn,
+    pub txn_id: TxnId,
+    pub body: LogRecordBody,
+}
+
+impl LogRecord {
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = vec![0u8; 4];
+        bytes.extend_from_slice(&self.lsn.to_le_bytes());
+        bytes.extend_from_slice(&self.prev_lsn.to_le_bytes());
+        bytes.extend_from_slice(&self.txn_id.to_le_bytes());
+        self.body.encode(&mut bytes);
+
+        let length = (bytes.len() + RECORD_CHECKSUM_SIZE) as u32;
+        bytes[0..4].copy_from_slice(&length.to_le_bytes());
+        let checksum = crc32(&bytes);
+        bytes.extend_from_slice(&checksum.to_le_bytes());
+        bytes
+    }
+
+    /// Decodes a record from the start of `bytes`.
+    ///
+    /// Returns `None` if `bytes` is too short to hold the whole record, the
+    /// checksum doesn't match, or the contents don't make sense.
+    pub fn from_bytes(bytes: &[u8]) -> Option<Self> {
+        let length = Self::encoded_length(bytes)?;
+        if length < RECORD_HEADER_SIZE + RECORD_CHECKSUM_SIZE || bytes.len() < length {
+            return None;
+        }
+
+        let (contents, checksum) = bytes[..length].split_at(length - RECORD_CHECKSUM_SIZE);
+        if crc32(contents) != u32::from_le_bytes(checksum.try_into().ok()?) {
+            return None;
+        }
+
+        let mut reader = Reader { bytes: contents, position: 4 };
+        let lsn = reader.read_u64()?;
+        let prev_lsn = reader.read_u64()?;
+        let txn_id = reader.read_u64()?;
+        let body = LogRecordBody::decode(&mut reader)?;
+
+        // trailing garbage inside the record means the length prefix lied
+        if reader.position != contents.len() {
+            return None;
+        }
+
+        Some(Self { lsn, prev_lsn, txn_id, body })
+    }
+
+    /// Length of the record starting at `bytes` according to its length prefix.
+    pub fn encoded_length(bytes: &[u8]) -> Option<usize> {
+        let prefix = bytes.get(0..4)?;
+        Some(u32::from_le_bytes(prefix.try_into().ok()?) as usize)
+    }
+}
+
+/// length prefixed byte string
+fn write_bytes(bytes: &mut Vec<u8>, data: &[u8]) {
+    bytes.extend_from_slice(&(data.len() as u32).to_le_bytes());
+    bytes.extend_from_slice(data);
+}
+
+/// cursor over an encoded record, every read returns `None` once the bytes run out
+struct Reader<'a> {
+    bytes: &'a [u8],
+    position: usize,
+}
+
+impl Reader<'_> {
+    fn take(&mut self, count: usize) -> Option<&[u8]> {
+        let end = self.position.checked_add(count)?;
+        let slice = self.bytes.get(self.position..end)?;
+        self.position = end;
+        Some(slice)
+    }
+
+    fn read_u8(&mut self) -> Option<u8> {
+        Some(self.take(1)?[0])
+    }
+
+    fn read_u32(&mut self) -> Option<u32> {
+        Some(u32::from_le_bytes(self.take(4)?.try_into().ok()?))
+    }
+
+    fn read_u64(&mut self) -> Option<u64> {
+        Some(u64::from_le_bytes(self.take(8)?.try_into().ok()?))
+    }
+
+    fn read_rid(&mut self) -> Option<RecordId> {
+        RecordId::from_bytes(self.take(RecordId::SIZE)?)
+    }
+
+    fn read_bytes(&mut self) -> Option<Vec<u8>> {
+        let length = self.read_u32()? as usize;
+        Some(self.take(length)?.to_vec())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(body: LogRecordBody) -> LogRecord {
+        LogRecord { lsn: 42, prev_lsn: 41, txn_id: 3, body }
+    }
+
+    #[test]
+    fn test_round_trip_every_kind() {
+        let rid = RecordId::new(5, 9);
+        let bodies = vec![
+            LogRecordBody::Begin,
+            LogRecordBody::Commit,
+            LogRecordBody::Abort,
+            LogRecordBody::Insert { rid, tuple: b"inserted".to_vec() },
+            LogRecordBody::Update { rid, before: b"before".to_vec(), after: b"after, and longer".to_vec() },
+            LogRecordBody::Delete { rid, tuple: Vec::new() },
+            LogRecordBody::AllocatePage { page_id: 12, page_type: PageType::BTreeLeaf },
+            LogRecordBody::Compensation {
+                undo_next_lsn: 40,
+                action: Box::new(LogRecordBody::Delete { rid, tuple: b"undone insert".to_vec() }),
+            },
+            LogRecordBody::Checkpoint {
+                max_txn_id: 7,
+                active_txns: vec![ActiveTxn { txn_id: 6, first_lsn: 30, last_lsn: 41 }, ActiveTxn { txn_id: 7, first_lsn: 38, last_lsn: 38 }],
+                dirty_pages: vec![(5, 33), (12, 40)],
+            },
+            LogRecordBody::Checkpoint { max_txn_id: 0, active_txns: Vec::new(), dirty_pages: Vec::new() },
+        ];
+
+        for body in bodies {
+            let original = record(body);
+            let bytes = original.to_bytes();
+            assert_eq!(LogRecord::encoded_length(&bytes), Some(bytes.len()));
+            assert_eq!(LogRecord::from_bytes(&bytes), Some(original));
+        }
+    }
+
+    #[test]
+    fn test_torn_or_corrupted_record_rejected() {
+        let bytes = record(LogRecordBody::Insert { rid: RecordId::new(1, 2), tuple: b"Hello, world!".to_vec() }).to_bytes();
+
+        // cut short, as if the crash happened mid-write
+        assert_eq!(LogRecord::from_bytes(&bytes[..bytes.len() - 1]), None);
+
+        // any flipped bit fails the checksum
+        let mut corrupted = bytes.clone();
+        corrupted[20] ^= 0x01;
+        assert_eq!(LogRecord::from_bytes(&corrupted), None);
+
+        assert_eq!(LogRecord::from_bytes(&[]), None);
+    }
+
+    #[test]
+    fn test_touched_page_and_rid() {
+        let rid = RecordId::new(5, 9);
+        let clr = LogRecordBody::Compensation { undo_next_lsn: 1, action: Box::new(LogRecordBody::Delete { rid, tuple: Vec::new() }) };
+        assert_eq!(clr.rid(), Some(rid));
+        assert_eq!(clr.page_id(), Some(5));
+        assert_eq!(LogRecordBody::AllocatePage { page_id: 3, page_type: PageType::Heap }.page_id(), Some(3));
+        assert_eq!(LogRecordBody::Commit.page_id(), None);
+    }
+}
@@ -0,0 +1,8 @@
+mod log_record;
+pub use log_record::{ActiveTxn, INVALID_LSN, LogRecord, LogRecordBody, Lsn, TxnId};
+
+mod log_manager;
+pub use log_manager::{LogManager, WalError};
+
+mod group_commit;
+pub use group_commit::GroupCommit;